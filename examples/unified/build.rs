@@ -44,7 +44,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let status = Command::new("protoc")
         .args([
             "--plugin=protoc-gen-synapse=../../target/release/protoc-gen-synapse",
-            &format!("--synapse_out=backend=seaorm:{}", src_generated.display()),
+            &format!("--synapse_out=backend=seaorm,health=true:{}", src_generated.display()),
             "-I../../proto",
             "-Iproto",
             "proto/iam/services.proto",
@@ -67,7 +67,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let status = Command::new("protoc")
         .args([
             "--plugin=protoc-gen-synapse=../../target/release/protoc-gen-synapse",
-            &format!("--synapse_out=backend=seaorm:{}", src_generated.display()),
+            &format!("--synapse_out=backend=seaorm,health=true:{}", src_generated.display()),
             "-I../../proto",
             "-Iproto",
             "proto/blog/services.proto",
@@ -121,16 +121,28 @@ mod string_filter;
 mod bool_filter;
 mod float_filter;
 mod timestamp_filter;
+mod string_array_filter;
+mod int_array_filter;
+mod geo_point;
+mod geo_point_filter;
 mod order_direction;
 mod page_info;
+mod batch_error;
+mod context_resolver;
 
 pub use int_filter::IntFilter;
 pub use string_filter::StringFilter;
 pub use bool_filter::BoolFilter;
 pub use float_filter::FloatFilter;
 pub use timestamp_filter::TimestampFilter;
+pub use string_array_filter::StringArrayFilter;
+pub use int_array_filter::IntArrayFilter;
+pub use geo_point::{GeoPoint, GeoPointInput};
+pub use geo_point_filter::{GeoPointFilter, GeoRadiusInput};
 pub use order_direction::OrderDirection;
 pub use page_info::PageInfo;
+pub use batch_error::BatchError;
+pub use context_resolver::{context_value, missing_context, ContextResolver, ContextSource, JsonContext};
 "#,
     )?;
 
@@ -5,17 +5,46 @@
 use std::net::SocketAddr;
 
 use sea_orm::Database;
-use tonic::transport::Server;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use synapse_unified_example::blog::{
-    author_service_server::AuthorServiceServer,
-    post_service_server::PostServiceServer,
+    grpc::server,
     SeaOrmAuthorServiceStorage,
     SeaOrmPostServiceStorage,
     AuthorServiceGrpcService,
     PostServiceGrpcService,
 };
+use synapse_unified_example::blog::outbox::{self, EventPublisher, OutboxEvent, PublishError};
+use synapse_unified_example::blog::storage::CachedPostServiceStorage;
+
+/// Publishes outbox events to the log when no broker is configured
+struct LogPublisher;
+
+#[async_trait::async_trait]
+impl EventPublisher for LogPublisher {
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), PublishError> {
+        tracing::info!(
+            event_type = %event.event_type,
+            aggregate_id = %event.aggregate_id,
+            "domain event"
+        );
+        Ok(())
+    }
+}
+
+/// Kafka (`KAFKA_BROKERS`) or NATS (`NATS_URL`) when compiled in and configured
+async fn event_publisher() -> anyhow::Result<std::sync::Arc<dyn EventPublisher>> {
+    #[cfg(feature = "kafka")]
+    if let Ok(brokers) = std::env::var("KAFKA_BROKERS") {
+        return Ok(std::sync::Arc::new(outbox::KafkaPublisher::new(&brokers)?));
+    }
+    #[cfg(feature = "nats")]
+    if let Ok(url) = std::env::var("NATS_URL") {
+        let client = async_nats::connect(url).await?;
+        return Ok(std::sync::Arc::new(outbox::NatsPublisher::new(client)));
+    }
+    Ok(std::sync::Arc::new(LogPublisher))
+}
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -35,11 +64,30 @@ async fn main() -> anyhow::Result<()> {
     let db = Database::connect(&database_url).await?;
     tracing::info!("Database connected!");
 
+    outbox::spawn_relay(
+        db.clone(),
+        event_publisher().await?,
+        outbox::RelayConfig::default(),
+        |err| tracing::warn!("outbox relay: {}", err),
+    );
+
     let author_storage = SeaOrmAuthorServiceStorage::new(db.clone());
-    let post_storage = SeaOrmPostServiceStorage::new(db);
+    let post_storage = match std::env::var("DATABASE_REPLICA_URL") {
+        Ok(replica_url) => {
+            let replica = Database::connect(&replica_url).await?;
+            SeaOrmPostServiceStorage::with_replica(db, replica)
+        }
+        Err(_) => SeaOrmPostServiceStorage::new(db),
+    };
 
     let author_grpc = AuthorServiceGrpcService::new(author_storage);
-    let post_grpc = PostServiceGrpcService::new(post_storage);
+    let post_grpc = match std::env::var("REDIS_URL") {
+        Ok(redis_url) => {
+            let redis = redis::Client::open(redis_url)?.get_connection_manager().await?;
+            PostServiceGrpcService::new_dyn(CachedPostServiceStorage::new(post_storage, redis))
+        }
+        Err(_) => PostServiceGrpcService::new_dyn(post_storage),
+    };
 
     let addr: SocketAddr = std::env::var("BLOG_ADDR")
         .unwrap_or_else(|_| "0.0.0.0:50053".into())
@@ -47,11 +95,7 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("Blog gRPC service listening on {}", addr);
 
-    Server::builder()
-        .add_service(AuthorServiceServer::new(author_grpc))
-        .add_service(PostServiceServer::new(post_grpc))
-        .serve(addr)
-        .await?;
+    server::serve(addr, author_grpc, post_grpc).await?;
 
     Ok(())
 }
@@ -8,9 +8,14 @@ use std::net::SocketAddr;
 
 use async_graphql::{EmptySubscription, MergedObject, Schema};
 use async_graphql::dataloader::DataLoader;
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
-use axum::{extract::State, routing::get, Router};
-use tonic::transport::Channel;
+use async_graphql_axum::GraphQLBatchRequest;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, Method},
+    response::Response,
+    routing::get,
+    Router,
+};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use synapse_unified_example::{
@@ -24,15 +29,19 @@ use synapse_unified_example::{
             TeamServiceQuery, TeamServiceMutation,
             UserLoader, OrganizationLoader, TeamLoader,
             TeamsByOrganizationLoader, UsersByOrganizationLoader,
+            UserServiceBackend, OrganizationServiceBackend, TeamServiceBackend,
+            GatewayConfig, UpstreamConfig, execute_graphql,
         },
     },
     blog::{
+        prelude::{with_request_context, RequestContext},
         author_service_client::AuthorServiceClient,
         post_service_client::PostServiceClient,
         graphql::{
             AuthorServiceQuery, AuthorServiceMutation,
             PostServiceQuery, PostServiceMutation,
             AuthorLoader, PostLoader, PostsByAuthorLoader,
+            AuthorServiceBackend, PostServiceBackend,
         },
     },
 };
@@ -59,9 +68,15 @@ pub type AppSchema = Schema<Query, Mutation, EmptySubscription>;
 
 async fn graphql_handler(
     State(schema): State<AppSchema>,
-    req: GraphQLRequest,
-) -> GraphQLResponse {
-    schema.execute(req.into_inner()).await.into()
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+    req: GraphQLBatchRequest,
+) -> Response {
+    // Blog writes record who made them and from where; resolvers forward this to the service
+    let context = RequestContext::from_headers(&headers, Some(remote_addr));
+    // Successful GET queries carry the schema's cache hints as Cache-Control
+    with_request_context(context, execute_graphql(&schema, &method, &headers, req.into_inner())).await
 }
 
 async fn apollo_sandbox() -> impl axum::response::IntoResponse {
@@ -96,18 +111,17 @@ async fn main() -> anyhow::Result<()> {
 
     dotenvy::dotenv().ok();
 
-    // gRPC service endpoints
-    let iam_endpoint = std::env::var("IAM_GRPC_ENDPOINT")
-        .unwrap_or_else(|_| "http://127.0.0.1:50052".into());
-    let blog_endpoint = std::env::var("BLOG_GRPC_ENDPOINT")
-        .unwrap_or_else(|_| "http://127.0.0.1:50053".into());
+    // gRPC service endpoints (IAM_GRPC_ENDPOINT, BLOG_GRPC_ENDPOINT)
+    let iam_upstream = UpstreamConfig::from_env("IAM", "http://127.0.0.1:50052");
+    let blog_upstream = UpstreamConfig::from_env("BLOG", "http://127.0.0.1:50053");
 
-    tracing::info!("Connecting to IAM gRPC at {}", iam_endpoint);
-    tracing::info!("Connecting to Blog gRPC at {}", blog_endpoint);
+    tracing::info!("Connecting to IAM gRPC at {}", iam_upstream.endpoint);
+    tracing::info!("Connecting to Blog gRPC at {}", blog_upstream.endpoint);
 
-    // Connect lazily (won't fail if services aren't up yet)
-    let iam_channel = Channel::from_shared(iam_endpoint)?.connect_lazy();
-    let blog_channel = Channel::from_shared(blog_endpoint)?.connect_lazy();
+    // Lazy by default (won't fail if services aren't up yet); set *_GRPC_EAGER
+    // to fail fast and *_GRPC_RESOLVE_SECS to follow DNS changes
+    let iam_channel = iam_upstream.connect().await?;
+    let blog_channel = blog_upstream.connect().await?;
 
     let user_client = UserServiceClient::new(iam_channel.clone());
     let org_client = OrganizationServiceClient::new(iam_channel.clone());
@@ -117,16 +131,16 @@ async fn main() -> anyhow::Result<()> {
 
     // Build GraphQL schema
     let schema = Schema::build(Query::default(), Mutation::default(), EmptySubscription)
-        .data(user_client.clone())
-        .data(org_client.clone())
-        .data(team_client.clone())
+        .data(UserServiceBackend::from(user_client.clone()))
+        .data(OrganizationServiceBackend::from(org_client.clone()))
+        .data(TeamServiceBackend::from(team_client.clone()))
         .data(DataLoader::new(UserLoader::new(user_client.clone()), tokio::spawn))
         .data(DataLoader::new(OrganizationLoader::new(org_client.clone()), tokio::spawn))
         .data(DataLoader::new(TeamLoader::new(team_client.clone()), tokio::spawn))
         .data(DataLoader::new(TeamsByOrganizationLoader::new(team_client), tokio::spawn))
         .data(DataLoader::new(UsersByOrganizationLoader::new(user_client), tokio::spawn))
-        .data(author_client.clone())
-        .data(post_client.clone())
+        .data(AuthorServiceBackend::from(author_client.clone()))
+        .data(PostServiceBackend::from(post_client.clone()))
         .data(DataLoader::new(AuthorLoader::new(author_client), tokio::spawn))
         .data(DataLoader::new(PostLoader::new(post_client.clone()), tokio::spawn))
         .data(DataLoader::new(PostsByAuthorLoader::new(post_client), tokio::spawn))
@@ -140,12 +154,14 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Apollo Sandbox at http://{}/", addr);
 
     let app = Router::new()
-        .route("/graphql", get(apollo_sandbox).post(graphql_handler))
+        .route("/graphql", get(graphql_handler).post(graphql_handler))
         .route("/", get(apollo_sandbox))
         .with_state(schema);
+    let gateway = GatewayConfig::from_env();
+    let app = gateway.apply(app);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    gateway.serve(listener, app).await?;
 
     Ok(())
 }
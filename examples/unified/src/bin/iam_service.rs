@@ -5,13 +5,10 @@
 use std::net::SocketAddr;
 
 use sea_orm::Database;
-use tonic::transport::Server;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use synapse_unified_example::iam::{
-    user_service_server::UserServiceServer,
-    organization_service_server::OrganizationServiceServer,
-    team_service_server::TeamServiceServer,
+    grpc::server,
     SeaOrmUserServiceStorage,
     SeaOrmOrganizationServiceStorage,
     SeaOrmTeamServiceStorage,
@@ -52,12 +49,7 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::info!("IAM gRPC service listening on {}", addr);
 
-    Server::builder()
-        .add_service(UserServiceServer::new(user_grpc))
-        .add_service(OrganizationServiceServer::new(org_grpc))
-        .add_service(TeamServiceServer::new(team_grpc))
-        .serve(addr)
-        .await?;
+    server::serve(addr, user_grpc, org_grpc, team_grpc).await?;
 
     Ok(())
 }
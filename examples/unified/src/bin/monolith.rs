@@ -1,38 +1,27 @@
 //! Monolith binary - runs everything in one process
 //!
-//! - IAM gRPC service
-//! - Blog gRPC service
+//! - IAM services
+//! - Blog services
 //! - GraphQL gateway
 //!
-//! Uses Unix domain sockets for internal gRPC communication.
+//! GraphQL resolvers and loaders call the gRPC service implementations
+//! in-process through the generated `{Service}Backend`s, so there is no
+//! internal network hop or protobuf encoding.
 //!
 //! Run with: cargo run --bin monolith
 
 use std::net::SocketAddr;
-use std::path::PathBuf;
 
 use async_graphql::{EmptySubscription, MergedObject, Schema};
 use async_graphql::dataloader::DataLoader;
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::{extract::State, routing::get, Router};
-use http::Uri;
-use hyper_util::rt::TokioIo;
 use sea_orm::Database;
-use tokio::net::{UnixListener, UnixStream};
-use tokio_stream::wrappers::UnixListenerStream;
-use tonic::transport::{Channel, Endpoint, Server as TonicServer};
-use tower::service_fn;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 use synapse_unified_example::{
     CurrentUser,
     iam::{
-        user_service_server::UserServiceServer,
-        organization_service_server::OrganizationServiceServer,
-        team_service_server::TeamServiceServer,
-        user_service_client::UserServiceClient,
-        organization_service_client::OrganizationServiceClient,
-        team_service_client::TeamServiceClient,
         SeaOrmUserServiceStorage,
         SeaOrmOrganizationServiceStorage,
         SeaOrmTeamServiceStorage,
@@ -45,13 +34,11 @@ use synapse_unified_example::{
             TeamServiceQuery, TeamServiceMutation,
             UserLoader, OrganizationLoader, TeamLoader,
             TeamsByOrganizationLoader, UsersByOrganizationLoader,
+            UserServiceBackend, OrganizationServiceBackend, TeamServiceBackend,
+            GatewayConfig,
         },
     },
     blog::{
-        author_service_server::AuthorServiceServer,
-        post_service_server::PostServiceServer,
-        author_service_client::AuthorServiceClient,
-        post_service_client::PostServiceClient,
         SeaOrmAuthorServiceStorage,
         SeaOrmPostServiceStorage,
         AuthorServiceGrpcService,
@@ -60,6 +47,7 @@ use synapse_unified_example::{
             AuthorServiceQuery, AuthorServiceMutation,
             PostServiceQuery, PostServiceMutation,
             AuthorLoader, PostLoader, PostsByAuthorLoader,
+            AuthorServiceBackend, PostServiceBackend,
         },
     },
 };
@@ -125,21 +113,6 @@ async fn apollo_sandbox() -> impl axum::response::IntoResponse {
 </html>"#)
 }
 
-/// Create a gRPC channel that connects via Unix domain socket
-async fn unix_channel(socket_path: PathBuf) -> anyhow::Result<Channel> {
-    // Endpoint URI doesn't matter for UDS, but tonic requires a valid URI
-    let channel = Endpoint::try_from("http://[::]:50051")?
-        .connect_with_connector(service_fn(move |_: Uri| {
-            let path = socket_path.clone();
-            async move {
-                let stream = UnixStream::connect(path).await?;
-                Ok::<_, std::io::Error>(TokioIo::new(stream))
-            }
-        }))
-        .await?;
-    Ok(channel)
-}
-
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::registry()
@@ -173,77 +146,35 @@ async fn main() -> anyhow::Result<()> {
     let author_storage = SeaOrmAuthorServiceStorage::new(db.clone());
     let post_storage = SeaOrmPostServiceStorage::new(db.clone());
 
-    // Wrap in gRPC services
-    let user_grpc = UserServiceGrpcService::new(user_storage);
-    let org_grpc = OrganizationServiceGrpcService::new(org_storage);
-    let team_grpc = TeamServiceGrpcService::new(team_storage);
-    let author_grpc = AuthorServiceGrpcService::new(author_storage);
-    let post_grpc = PostServiceGrpcService::new(post_storage);
-
-    // Unix socket path for internal gRPC
-    let socket_path = std::env::var("GRPC_SOCKET")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| std::env::temp_dir().join("synapse-grpc.sock"));
-
-    // Remove old socket file if it exists
-    let _ = std::fs::remove_file(&socket_path);
+    // Wrap in gRPC services and call them in-process
+    let user = UserServiceBackend::in_process(UserServiceGrpcService::new(user_storage));
+    let org = OrganizationServiceBackend::in_process(OrganizationServiceGrpcService::new(org_storage));
+    let team = TeamServiceBackend::in_process(TeamServiceGrpcService::new(team_storage));
+    let author = AuthorServiceBackend::in_process(AuthorServiceGrpcService::new(author_storage));
+    let post = PostServiceBackend::in_process(PostServiceGrpcService::new(post_storage));
 
     // GraphQL server address
     let graphql_addr: SocketAddr = std::env::var("GRAPHQL_ADDR")
         .unwrap_or_else(|_| "0.0.0.0:4000".into())
         .parse()?;
 
-    tracing::info!("Starting gRPC server on unix://{}", socket_path.display());
-
-    // Create Unix socket listener
-    let uds = UnixListener::bind(&socket_path)?;
-    let uds_stream = UnixListenerStream::new(uds);
-
-    // Start gRPC server on Unix socket
-    let grpc_server = TonicServer::builder()
-        .add_service(UserServiceServer::new(user_grpc))
-        .add_service(OrganizationServiceServer::new(org_grpc))
-        .add_service(TeamServiceServer::new(team_grpc))
-        .add_service(AuthorServiceServer::new(author_grpc))
-        .add_service(PostServiceServer::new(post_grpc))
-        .serve_with_incoming(uds_stream);
-
-    tokio::spawn(async move {
-        if let Err(e) = grpc_server.await {
-            tracing::error!("gRPC server error: {}", e);
-        }
-    });
-
-    // Small delay to ensure server is ready
-    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
-
-    // Create gRPC channel via Unix socket
-    tracing::info!("Connecting GraphQL to gRPC via unix://{}", socket_path.display());
-    let channel = unix_channel(socket_path).await?;
-
-    let user_client = UserServiceClient::new(channel.clone());
-    let org_client = OrganizationServiceClient::new(channel.clone());
-    let team_client = TeamServiceClient::new(channel.clone());
-    let author_client = AuthorServiceClient::new(channel.clone());
-    let post_client = PostServiceClient::new(channel);
-
     // Build GraphQL schema
     let schema = Schema::build(Query::default(), Mutation::default(), EmptySubscription)
-        // IAM clients and loaders
-        .data(user_client.clone())
-        .data(org_client.clone())
-        .data(team_client.clone())
-        .data(DataLoader::new(UserLoader::new(user_client.clone()), tokio::spawn))
-        .data(DataLoader::new(OrganizationLoader::new(org_client.clone()), tokio::spawn))
-        .data(DataLoader::new(TeamLoader::new(team_client.clone()), tokio::spawn))
-        .data(DataLoader::new(TeamsByOrganizationLoader::new(team_client), tokio::spawn))
-        .data(DataLoader::new(UsersByOrganizationLoader::new(user_client), tokio::spawn))
-        // Blog clients and loaders
-        .data(author_client.clone())
-        .data(post_client.clone())
-        .data(DataLoader::new(AuthorLoader::new(author_client), tokio::spawn))
-        .data(DataLoader::new(PostLoader::new(post_client.clone()), tokio::spawn))
-        .data(DataLoader::new(PostsByAuthorLoader::new(post_client), tokio::spawn))
+        // IAM backends and loaders
+        .data(user.clone())
+        .data(org.clone())
+        .data(team.clone())
+        .data(DataLoader::new(UserLoader::new(user.clone()), tokio::spawn))
+        .data(DataLoader::new(OrganizationLoader::new(org), tokio::spawn))
+        .data(DataLoader::new(TeamLoader::new(team.clone()), tokio::spawn))
+        .data(DataLoader::new(TeamsByOrganizationLoader::new(team), tokio::spawn))
+        .data(DataLoader::new(UsersByOrganizationLoader::new(user), tokio::spawn))
+        // Blog backends and loaders
+        .data(author.clone())
+        .data(post.clone())
+        .data(DataLoader::new(AuthorLoader::new(author), tokio::spawn))
+        .data(DataLoader::new(PostLoader::new(post.clone()), tokio::spawn))
+        .data(DataLoader::new(PostsByAuthorLoader::new(post), tokio::spawn))
         .finish();
 
     tracing::info!("GraphQL server listening on http://{}", graphql_addr);
@@ -253,9 +184,11 @@ async fn main() -> anyhow::Result<()> {
         .route("/graphql", get(apollo_sandbox).post(graphql_handler))
         .route("/", get(apollo_sandbox))
         .with_state(schema);
+    let gateway = GatewayConfig::from_env();
+    let app = gateway.apply(app);
 
     let listener = tokio::net::TcpListener::bind(graphql_addr).await?;
-    axum::serve(listener, app).await?;
+    gateway.serve(listener, app).await?;
 
     Ok(())
 }
@@ -16,6 +16,7 @@
 #![allow(missing_docs)]
 
 mod generated;
+mod trending;
 
 pub use generated::*;
 
@@ -24,8 +25,9 @@ pub use generated::*;
 /// This struct is populated from authentication middleware and made
 /// available to GraphQL resolvers via `ctx.data::<CurrentUser>()`.
 ///
-/// Fields marked with `from_context` in proto definitions will be
-/// automatically populated from this context.
+/// Fields marked with `from_context = { path: "current_user.id" }` in proto
+/// definitions are populated from its `id` when the request has no
+/// `ContextSource`.
 #[derive(Debug, Clone)]
 pub struct CurrentUser {
     /// User's internal ID
@@ -0,0 +1,44 @@
+//! Hand-written storage queries
+//!
+//! `ListTrendingPosts` is a `custom` storage method: the generated
+//! `SeaOrmPostServiceStorage` forwards it to `PostServiceStorageCustom`,
+//! which is implemented here.
+
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+
+use crate::blog::entities::post;
+use crate::blog::storage::post_service_storage::{PostServiceStorage, StorageError};
+use crate::blog::storage::sea_orm_post_service_storage::PostServiceStorageCustom;
+use crate::blog::{ListPostsRequest, PostConnection, PostEdge, SeaOrmPostServiceStorage};
+use crate::synapse::relay::PageInfo;
+
+#[async_trait::async_trait]
+impl PostServiceStorageCustom for SeaOrmPostServiceStorage {
+    /// The most recently updated published posts, `first` of them (20 by default)
+    async fn list_trending_posts(&self, request: ListPostsRequest) -> Result<PostConnection, StorageError> {
+        let models = post::Entity::find()
+            .filter(post::Column::Published.eq(true))
+            .order_by_desc(post::Column::UpdatedAt)
+            .order_by_asc(post::Column::Id)
+            .limit(request.first.unwrap_or(20).clamp(0, 100) as u64)
+            .all(self.read_db())
+            .await?;
+
+        let edges: Vec<PostEdge> = models
+            .into_iter()
+            .map(|m| PostEdge {
+                cursor: m.id.to_string(),
+                node: Some(m.into()),
+            })
+            .collect();
+        Ok(PostConnection {
+            page_info: Some(PageInfo {
+                has_next_page: false,
+                has_previous_page: false,
+                start_cursor: edges.first().map(|e| e.cursor.clone()),
+                end_cursor: edges.last().map(|e| e.cursor.clone()),
+            }),
+            edges,
+        })
+    }
+}
@@ -0,0 +1,416 @@
+//! Admin UI generation
+//!
+//! Generates the graphql/admin.rs module: an optional axum router serving a
+//! generic CRUD back-office at `/admin`. The page is driven by the package
+//! registry (entities and column metadata) plus the GraphQL operation names
+//! of each service, and performs every read and write through the GraphQL
+//! endpoint, so resolver guards and gateway CSRF checks apply unchanged.
+
+use crate::error::GeneratorError;
+use crate::storage::defaults::infer_operation;
+use crate::storage::seaorm::options::{
+    get_cached_graphql_mutation_options, get_cached_graphql_query_options,
+    get_cached_graphql_service_options, get_cached_rpc_method_options,
+};
+use crate::storage::seaorm::package::collect_package_info_all_files;
+use heck::{ToLowerCamelCase, ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::{FileDescriptorProto, ServiceDescriptorProto};
+use quote::quote;
+
+/// GraphQL operations the admin UI can call for one entity
+#[derive(Debug, Default, PartialEq)]
+struct EntityOperations {
+    entity: String,
+    list: Option<String>,
+    get: Option<String>,
+    create: Option<(String, String)>,
+    update: Option<(String, String)>,
+    delete: Option<String>,
+}
+
+/// Whether the admin module is generated for this file
+///
+/// Requires GraphQL services and storage entities (for the registry).
+pub fn is_enabled(file: &FileDescriptorProto, all_files: &[FileDescriptorProto]) -> bool {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let has_graphql_service = file.service.iter().any(|svc| {
+        get_cached_graphql_service_options(file_name, svc.name.as_deref().unwrap_or("")).is_some()
+    });
+    has_graphql_service && !collect_package_info_all_files(all_files, file).entities.is_empty()
+}
+
+/// Generate the graphql/admin.rs file for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || !is_enabled(file, all_files) {
+        return Ok(None);
+    }
+
+    let file_name = file.name.as_deref().unwrap_or("");
+    let operations: Vec<TokenStream> = file
+        .service
+        .iter()
+        .filter(|svc| {
+            get_cached_graphql_service_options(file_name, svc.name.as_deref().unwrap_or("")).is_some()
+        })
+        .map(|svc| collect_operations(file_name, svc))
+        .map(|ops| operations_tokens(&ops))
+        .collect();
+
+    let page = ADMIN_PAGE;
+
+    let code = quote! {
+        //! Admin back-office
+        //!
+        //! Mount with `router.merge(admin::router("/graphql"))`. The UI reads
+        //! entity metadata from the registry and calls the GraphQL endpoint for
+        //! all data, so the same authorization applies as for any client.
+        //! @generated
+
+        #![allow(missing_docs)]
+
+        use axum::extract::State;
+        use axum::response::{Html, IntoResponse};
+        use axum::routing::get;
+        use axum::{Json, Router};
+
+        use super::super::registry;
+
+        /// GraphQL operation names for one entity (create/update carry their input type)
+        pub struct AdminOperations {
+            pub entity: &'static str,
+            pub list: Option<&'static str>,
+            pub get: Option<&'static str>,
+            pub create: Option<(&'static str, &'static str)>,
+            pub update: Option<(&'static str, &'static str)>,
+            pub delete: Option<&'static str>,
+        }
+
+        /// Operations exposed by this package's GraphQL services
+        pub static OPERATIONS: &[AdminOperations] = &[#(#operations),*];
+
+        /// Router serving the admin UI at `/admin`
+        ///
+        /// `graphql_path` is the endpoint the UI posts queries to.
+        pub fn router<S>(graphql_path: &'static str) -> Router<S>
+        where
+            S: Clone + Send + Sync + 'static,
+        {
+            Router::new()
+                .route("/admin", get(page))
+                .route("/admin/schema.json", get(schema))
+                .with_state(graphql_path)
+        }
+
+        async fn page() -> impl IntoResponse {
+            Html(#page)
+        }
+
+        async fn schema(State(graphql_path): State<&'static str>) -> impl IntoResponse {
+            Json(schema_json(graphql_path))
+        }
+
+        /// Entity metadata and operations as consumed by the admin page
+        pub fn schema_json(graphql_path: &str) -> serde_json::Value {
+            let entities: Vec<serde_json::Value> = registry::entities()
+                .iter()
+                .filter(|e| e.graphql_type.is_some())
+                .filter_map(|e| {
+                    let ops = OPERATIONS.iter().find(|o| o.entity == e.name)?;
+                    let fields: Vec<serde_json::Value> = e
+                        .fields
+                        .iter()
+                        .filter(|f| f.graphql)
+                        .map(|f| serde_json::json!({
+                            "name": f.name,
+                            "type": f.proto_type,
+                            "primaryKey": f.primary_key,
+                            "nullable": f.nullable,
+                            "pii": f.pii,
+                        }))
+                        .collect();
+                    Some(serde_json::json!({
+                        "name": e.name,
+                        "graphqlType": e.graphql_type,
+                        "node": e.node,
+                        "fields": fields,
+                        "list": ops.list,
+                        "get": ops.get,
+                        "create": ops.create.map(|(f, i)| serde_json::json!({ "field": f, "input": i })),
+                        "update": ops.update.map(|(f, i)| serde_json::json!({ "field": f, "input": i })),
+                        "delete": ops.delete,
+                    }))
+                })
+                .collect();
+            serde_json::json!({ "graphql": graphql_path, "entities": entities })
+        }
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/graphql/admin.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Map a service's GraphQL queries and mutations to CRUD operations
+fn collect_operations(file_name: &str, service: &ServiceDescriptorProto) -> EntityOperations {
+    let svc_name = service.name.as_deref().unwrap_or("");
+    let mut ops = EntityOperations {
+        entity: svc_name.trim_end_matches("Service").to_upper_camel_case(),
+        ..Default::default()
+    };
+
+    for method in &service.method {
+        let method_name = method.name.as_deref().unwrap_or("");
+        let lower = method_name.to_lowercase();
+
+        // Custom storage methods run hand-written queries, not CRUD
+        let method_options = get_cached_rpc_method_options(file_name, svc_name, method_name);
+        if method_options.as_ref().is_some_and(|o| o.custom) {
+            continue;
+        }
+        // Get-many queries take a list of ids; the admin gets one record
+        let operation = method_options
+            .filter(|o| !o.operation.is_empty())
+            .map(|o| o.operation.to_snake_case())
+            .unwrap_or_else(|| infer_operation(method_name).to_string());
+        let input = method
+            .input_type
+            .as_deref()
+            .map(|t| t.rsplit('.').next().unwrap_or(t).replace("Request", "Input"))
+            .unwrap_or_default();
+
+        if let Some(opts) = get_cached_graphql_query_options(file_name, svc_name, method_name) {
+            let field = if opts.name.is_empty() { method_name } else { &opts.name }.to_lower_camel_case();
+            if lower.starts_with("list") {
+                ops.list = Some(field);
+            } else if lower.starts_with("get") && operation != "get_many" {
+                ops.get = Some(field);
+            }
+        } else if let Some(opts) = get_cached_graphql_mutation_options(file_name, svc_name, method_name) {
+            let field = if opts.name.is_empty() { method_name } else { &opts.name }.to_lower_camel_case();
+            // The admin writes one record at a time
+            if lower.starts_with("create") && !lower.starts_with("createmany") {
+                ops.create = Some((field, input));
+            } else if lower.starts_with("update") && operation != "update_many" {
+                ops.update = Some((field, input));
+            } else if lower.starts_with("delete") && operation != "delete_many" {
+                ops.delete = Some(field);
+            }
+        }
+    }
+
+    ops
+}
+
+/// Generate the AdminOperations literal for an entity
+fn operations_tokens(ops: &EntityOperations) -> TokenStream {
+    fn opt(value: &Option<String>) -> TokenStream {
+        match value {
+            Some(v) => quote! { Some(#v) },
+            None => quote! { None },
+        }
+    }
+    fn opt_pair(value: &Option<(String, String)>) -> TokenStream {
+        match value {
+            Some((field, input)) => quote! { Some((#field, #input)) },
+            None => quote! { None },
+        }
+    }
+
+    let entity = &ops.entity;
+    let list = opt(&ops.list);
+    let get = opt(&ops.get);
+    let create = opt_pair(&ops.create);
+    let update = opt_pair(&ops.update);
+    let delete = opt(&ops.delete);
+
+    quote! {
+        AdminOperations {
+            entity: #entity,
+            list: #list,
+            get: #get,
+            create: #create,
+            update: #update,
+            delete: #delete,
+        }
+    }
+}
+
+/// Single-page admin UI
+///
+/// Lists rows through the entity's list query (with an `eq` filter on any
+/// column), edits them with forms built from column metadata, and sends the
+/// gateway's CSRF cookie back as a header on every request.
+const ADMIN_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Admin</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 0; display: flex; }
+nav { width: 200px; padding: 1rem; background: #f4f4f5; min-height: 100vh; }
+nav a { display: block; padding: .25rem 0; cursor: pointer; }
+main { flex: 1; padding: 1rem; }
+table { border-collapse: collapse; width: 100%; }
+td, th { border: 1px solid #ddd; padding: .25rem .5rem; text-align: left; }
+form label { display: block; margin: .25rem 0; }
+.error { color: #b91c1c; }
+</style>
+</head>
+<body>
+<nav id="nav"></nav>
+<main id="main">Select an entity</main>
+<script>
+const INT_TYPES = ["int32", "int64", "uint32", "uint64", "sint32", "sint64", "fixed32", "fixed64", "sfixed32", "sfixed64"];
+const FLOAT_TYPES = ["double", "float"];
+let schema;
+
+const camel = (s) => s.replace(/_([a-z])/g, (_, c) => c.toUpperCase());
+const esc = (v) => String(v ?? "").replace(/[&<>"]/g, (c) => ({ "&": "&amp;", "<": "&lt;", ">": "&gt;", '"': "&quot;" })[c]);
+const scalar = (f) => !f.type.includes(".") || f.type === "google.protobuf.Timestamp";
+const idField = (e) => (e.node ? "internalId" : "id");
+const idType = (e) => (INT_TYPES.includes((e.fields.find((f) => f.primaryKey) || {}).type) ? "Int!" : "String!");
+
+async function gql(query, variables) {
+  const csrf = document.cookie.split("; ").find((c) => c.startsWith("csrf_token="));
+  const res = await fetch(schema.graphql, {
+    method: "POST",
+    credentials: "same-origin",
+    headers: Object.assign({ "content-type": "application/json" }, csrf ? { "x-csrf-token": csrf.split("=")[1] } : {}),
+    body: JSON.stringify({ query, variables }),
+  });
+  const body = await res.json();
+  if (body.errors) throw new Error(body.errors.map((e) => e.message).join("; "));
+  return body.data;
+}
+
+function coerce(field, raw) {
+  if (raw === "" && field.nullable) return null;
+  if (INT_TYPES.includes(field.type)) return parseInt(raw, 10);
+  if (FLOAT_TYPES.includes(field.type)) return parseFloat(raw);
+  if (field.type === "bool") return raw === true || raw === "true";
+  return raw;
+}
+
+async function list(entity, filter) {
+  const main = document.getElementById("main");
+  const fields = entity.fields.filter(scalar);
+  const selection = [idField(entity)].concat(fields.map((f) => camel(f.name)).filter((n) => n !== idField(entity)));
+  try {
+    const data = await gql(
+      `query($filter: ${entity.name}Filter) { ${entity.list}(first: 50, filter: $filter) { edges { node { ${selection.join(" ")} } } } }`,
+      { filter }
+    );
+    const rows = data[entity.list].edges.map((e) => e.node);
+    main.innerHTML = `<h2>${esc(entity.graphqlType)}</h2>
+      <form id="filter">
+        <select name="field">${fields.map((f) => `<option>${esc(f.name)}</option>`).join("")}</select>
+        <input name="value" placeholder="equals"> <button>Filter</button>
+        ${entity.create ? '<button type="button" id="new">New</button>' : ""}
+      </form>
+      <table><tr>${selection.map((n) => `<th>${esc(n)}</th>`).join("")}<th></th></tr>
+      ${rows.map((r, i) => `<tr>${selection.map((n) => `<td>${esc(r[n])}</td>`).join("")}
+        <td>${entity.update ? `<a data-edit="${i}">edit</a>` : ""} ${entity.delete ? `<a data-delete="${i}">delete</a>` : ""}</td></tr>`).join("")}
+      </table>`;
+    document.getElementById("filter").onsubmit = (ev) => {
+      ev.preventDefault();
+      const form = new FormData(ev.target);
+      const field = entity.fields.find((f) => f.name === form.get("field"));
+      const value = form.get("value");
+      list(entity, value === "" ? null : { [camel(field.name)]: { eq: coerce(field, value) } });
+    };
+    const create = document.getElementById("new");
+    if (create) create.onclick = () => edit(entity, null);
+    main.querySelectorAll("[data-edit]").forEach((a) => (a.onclick = () => edit(entity, rows[a.dataset.edit])));
+    main.querySelectorAll("[data-delete]").forEach((a) => (a.onclick = async () => {
+      if (!confirm("Delete?")) return;
+      await gql(`mutation($id: ${idType(entity)}) { ${entity.delete}(id: $id) }`, { id: rows[a.dataset.delete][idField(entity)] });
+      list(entity, filter);
+    }));
+  } catch (err) {
+    main.innerHTML = `<p class="error">${esc(err.message)}</p>`;
+  }
+}
+
+function edit(entity, row) {
+  const main = document.getElementById("main");
+  const op = row ? entity.update : entity.create;
+  const fields = entity.fields.filter((f) => scalar(f) && !f.primaryKey && f.type !== "google.protobuf.Timestamp");
+  main.innerHTML = `<h2>${row ? "Edit" : "New"} ${esc(entity.graphqlType)}</h2>
+    <form id="edit">${fields.map((f) => f.type === "bool"
+      ? `<label><input type="checkbox" name="${esc(f.name)}" ${row && row[camel(f.name)] ? "checked" : ""}> ${esc(f.name)}</label>`
+      : `<label>${esc(f.name)} <input name="${esc(f.name)}" value="${esc(row ? row[camel(f.name)] : "")}"></label>`).join("")}
+    <button>Save</button> <span class="error" id="err"></span></form>`;
+  document.getElementById("edit").onsubmit = async (ev) => {
+    ev.preventDefault();
+    const form = ev.target;
+    const input = {};
+    fields.forEach((f) => {
+      const el = form.elements[f.name];
+      input[camel(f.name)] = coerce(f, f.type === "bool" ? el.checked : el.value);
+    });
+    try {
+      if (row) {
+        await gql(`mutation($id: ${idType(entity)}, $input: ${op.input}!) { ${op.field}(id: $id, input: $input) { __typename } }`,
+          { id: row[idField(entity)], input });
+      } else {
+        await gql(`mutation($input: ${op.input}!) { ${op.field}(input: $input) { __typename } }`, { input });
+      }
+      list(entity, null);
+    } catch (err) {
+      document.getElementById("err").textContent = err.message;
+    }
+  };
+}
+
+fetch("/admin/schema.json").then((r) => r.json()).then((s) => {
+  schema = s;
+  const nav = document.getElementById("nav");
+  s.entities.filter((e) => e.list).forEach((e) => {
+    const a = document.createElement("a");
+    a.textContent = e.graphqlType;
+    a.onclick = () => list(e, null);
+    nav.appendChild(a);
+  });
+});
+</script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operations_tokens() {
+        let ops = EntityOperations {
+            entity: "User".to_string(),
+            list: Some("listUsers".to_string()),
+            create: Some(("createUser".to_string(), "CreateUserInput".to_string())),
+            ..Default::default()
+        };
+        let code = operations_tokens(&ops).to_string();
+        assert!(code.contains("\"listUsers\""));
+        assert!(code.contains("(\"createUser\" , \"CreateUserInput\")"));
+        assert!(code.contains("delete : None"));
+    }
+
+    #[test]
+    fn test_admin_page_uses_csrf_header() {
+        assert!(ADMIN_PAGE.contains("x-csrf-token"));
+        assert!(ADMIN_PAGE.contains("admin/schema.json"));
+    }
+}
@@ -0,0 +1,269 @@
+//! Per-item results of partial batch mutations
+//!
+//! Create-many mutations whose response reports `synapse.relay.BatchError`s
+//! return one `{Method}Result` per input item instead of the created nodes.
+//! Each result pairs the item's index with the created node, or with the
+//! errors that kept it from being written.
+//!
+//! Items failing custom validators are reported the same way without being
+//! sent. When the storage method is `atomic`, any such item sends nothing
+//! and the other items are reported as `ABORTED`, like the storage does.
+
+use crate::error::GeneratorError;
+use heck::ToSnakeCase;
+use proc_macro2::{Ident, TokenStream};
+use prost_types::compiler::code_generator_response::File;
+use quote::{format_ident, quote};
+
+/// Generate the BatchError type (in shared synapse/relay/graphql location)
+pub(super) fn generate_batch_error() -> Result<File, GeneratorError> {
+    let code = quote! {
+        //! Auto-generated BatchError type
+        //! @generated
+
+        #![allow(missing_docs)]
+
+        use async_graphql::SimpleObject;
+
+        /// Why an item of a batch mutation was not written
+        #[derive(SimpleObject, Clone, Debug, Default)]
+        pub struct BatchError {
+            /// Position of the item in the input
+            pub index: i32,
+            /// Status code (e.g. `ALREADY_EXISTS`) or validation code (e.g. `required`)
+            pub code: String,
+            /// Field at fault, when known
+            pub field: Option<String>,
+            /// Human-readable error message
+            pub message: String,
+        }
+
+        impl From<super::super::BatchError> for BatchError {
+            fn from(e: super::super::BatchError) -> Self {
+                Self {
+                    index: e.index,
+                    code: e.code,
+                    field: (!e.field.is_empty()).then_some(e.field),
+                    message: e.message,
+                }
+            }
+        }
+    };
+
+    Ok(File {
+        name: Some("synapse/relay/graphql/batch_error.rs".to_string()),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    })
+}
+
+/// A partial create-many mutation's result type and response fields
+pub(super) struct BatchResult {
+    /// Result type, `{Method}Result`
+    pub ident: Ident,
+    /// GraphQL field name of the mutation
+    pub mutation: String,
+    /// Output type of the created nodes
+    pub output_type: Ident,
+    /// Response field holding the created entities
+    pub output_field: Ident,
+    /// Response field holding the per-item errors
+    pub errors_field: Ident,
+}
+
+impl BatchResult {
+    /// Result field holding the created node, named after the output type
+    fn node_field(&self) -> Ident {
+        format_ident!("{}", self.output_type.to_string().to_snake_case())
+    }
+
+    /// Generate the `{Method}Result` object
+    pub(super) fn generate_type(&self) -> TokenStream {
+        let ident = &self.ident;
+        let output_type = &self.output_type;
+        let node_field = self.node_field();
+        let doc = format!("Outcome of one item of `{}`", self.mutation);
+        quote! {
+            #[doc = #doc]
+            #[derive(async_graphql::SimpleObject)]
+            pub struct #ident {
+                /// Position of the item in the input
+                pub index: i32,
+                /// The created node, when the item was written
+                pub #node_field: Option<super::#output_type>,
+                /// Why the item was not written
+                pub errors: Vec<super::super::super::synapse::relay::graphql::BatchError>,
+            }
+        }
+    }
+
+    /// Check each of `items` with `check`, keeping the failing ones out of the request
+    ///
+    /// `check` evaluates to the `Result` of validating `request`. Failing items
+    /// leave their errors in `rejected`; `sent` holds the input index of each
+    /// item left in `items`. An `atomic` batch with a failing item returns its
+    /// results without sending anything.
+    pub(super) fn generate_item_checks(&self, check: TokenStream, atomic: bool) -> TokenStream {
+        let ident = &self.ident;
+        let node_field = self.node_field();
+        let abort = if atomic {
+            quote! {
+                if !rejected.is_empty() {
+                    let mut results: Vec<#ident> = (0..count as i32)
+                        .map(|index| #ident { index, #node_field: None, errors: Vec::new() })
+                        .collect();
+                    for error in rejected {
+                        if let Some(result) = results.get_mut(error.index as usize) {
+                            result.errors.push(error);
+                        }
+                    }
+                    for result in results.iter_mut().filter(|r| r.errors.is_empty()) {
+                        result.errors.push(super::super::super::synapse::relay::graphql::BatchError {
+                            index: result.index,
+                            code: "ABORTED".to_string(),
+                            field: None,
+                            message: "not written: another item of the batch failed".to_string(),
+                        });
+                    }
+                    return Ok(results);
+                }
+            }
+        } else {
+            quote! {}
+        };
+        quote! {
+            let mut rejected = Vec::new();
+            let mut sent = Vec::with_capacity(count);
+            let mut checked = Vec::with_capacity(count);
+            for (index, request) in items.into_iter().enumerate() {
+                match #check {
+                    Ok(()) => {
+                        sent.push(index as i32);
+                        checked.push(request);
+                    }
+                    Err(e) => rejected.extend(e.errors().iter().map(|error| {
+                        super::super::super::synapse::relay::graphql::BatchError {
+                            index: index as i32,
+                            code: error.code.clone(),
+                            field: (!error.field.is_empty()).then(|| error.field.clone()),
+                            message: error.message.clone(),
+                        }
+                    })),
+                }
+            }
+            #abort
+            let items = checked;
+        }
+    }
+
+    /// Pair the created nodes and errors of `response` with the `count` input items
+    ///
+    /// Created entities and errors both come in input order, so an item takes
+    /// the errors with its index or, having none, the next created entity.
+    /// With `checked` items, the response's indexes are positions in `sent`,
+    /// and the items in `rejected` were never sent.
+    pub(super) fn generate_results(&self, checked: bool) -> TokenStream {
+        let ident = &self.ident;
+        let output_type = &self.output_type;
+        let output_field = &self.output_field;
+        let errors_field = &self.errors_field;
+        let node_field = self.node_field();
+        let errors = if checked {
+            quote! {
+                let mut errors: Vec<_> = response
+                    .#errors_field
+                    .into_iter()
+                    .map(|mut error| {
+                        error.index = sent.get(error.index as usize).copied().unwrap_or(error.index);
+                        super::super::super::synapse::relay::graphql::BatchError::from(error)
+                    })
+                    .chain(rejected)
+                    .collect();
+                errors.sort_by_key(|e| e.index);
+                let mut errors = errors.into_iter().peekable();
+            }
+        } else {
+            quote! {
+                let mut errors = response
+                    .#errors_field
+                    .into_iter()
+                    .map(super::super::super::synapse::relay::graphql::BatchError::from)
+                    .peekable();
+            }
+        };
+        quote! {
+            let response = response.into_inner();
+            let mut nodes = response.#output_field.into_iter();
+            #errors
+            let mut results = Vec::with_capacity(count);
+            for index in 0..count as i32 {
+                let mut item_errors = Vec::new();
+                while let Some(error) = errors.next_if(|e| e.index == index) {
+                    item_errors.push(error);
+                }
+                let node = if item_errors.is_empty() {
+                    nodes.next().map(super::#output_type::from)
+                } else {
+                    None
+                };
+                results.push(#ident {
+                    index,
+                    #node_field: node,
+                    errors: item_errors,
+                });
+            }
+            Ok(results)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result() -> BatchResult {
+        BatchResult {
+            ident: format_ident!("CreateManyPostsResult"),
+            mutation: "createManyPosts".to_string(),
+            output_type: format_ident!("Post"),
+            output_field: format_ident!("posts"),
+            errors_field: format_ident!("errors"),
+        }
+    }
+
+    #[test]
+    fn test_generate_type() {
+        let code = result().generate_type().to_string();
+        assert!(code.contains("pub struct CreateManyPostsResult"));
+        assert!(code.contains("pub post : Option < super :: Post >"));
+        assert!(code.contains("pub errors : Vec < super :: super :: super :: synapse :: relay :: graphql :: BatchError >"));
+    }
+
+    #[test]
+    fn test_generate_results() {
+        let code = result().generate_results(false).to_string();
+        assert!(code.contains("response . errors . into_iter () . map (super :: super :: super :: synapse :: relay :: graphql :: BatchError :: from) . peekable ()"));
+        assert!(code.contains("errors . next_if (| e | e . index == index)"));
+        assert!(code.contains("nodes . next () . map (super :: Post :: from)"));
+
+        // Checked items are re-indexed from their position among the sent ones
+        let code = result().generate_results(true).to_string();
+        assert!(code.contains("error . index = sent . get (error . index as usize) . copied () . unwrap_or (error . index) ;"));
+        assert!(code.contains(". chain (rejected)"));
+    }
+
+    #[test]
+    fn test_generate_item_checks() {
+        let check = quote! { check(&request).await };
+        let partial = result().generate_item_checks(check.clone(), false).to_string();
+        assert!(partial.contains("match check (& request) . await { Ok (()) => { sent . push (index as i32) ; checked . push (request) ; }"));
+        assert!(partial.contains("let items = checked ;"));
+        assert!(!partial.contains("ABORTED"));
+
+        // Atomic batches send nothing once an item fails
+        let atomic = result().generate_item_checks(check, true).to_string();
+        assert!(atomic.contains("if ! rejected . is_empty ()"));
+        assert!(atomic.contains("code : \"ABORTED\" . to_string ()"));
+        assert!(atomic.contains("return Ok (results) ;"));
+    }
+}
@@ -0,0 +1,284 @@
+//! Fault injection generation
+//!
+//! With `chaos=true` (test and staging builds), generates graphql/chaos.rs:
+//! a tower layer wrapping the channel under the resolvers' gRPC clients that
+//! delays calls and fails a share of them with a chosen status. Faults can be
+//! limited to some RPCs, so a query sees partial data while the other fields
+//! resolve. Resolver backends gain a `Faulty` variant built from the layer.
+//! Nothing is injected unless a `FaultConfig` is applied.
+
+use crate::error::GeneratorError;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::quote;
+
+/// Status injected by default (UNAVAILABLE, as for a downstream outage)
+const DEFAULT_ERROR_CODE: i32 = 14;
+
+/// Generate the graphql/chaos.rs file for a package
+pub fn generate(file: &FileDescriptorProto) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || file.service.is_empty() || !crate::params::chaos() {
+        return Ok(None);
+    }
+
+    let config = generate_config();
+    let layer = generate_layer();
+
+    let code = quote! {
+        //! Fault injection for the resolvers' gRPC clients
+        //!
+        //! For test and staging only: wraps a channel so calls are delayed or
+        //! fail, to check timeouts, partial data, and error extensions.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(dead_code)]
+
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::Arc;
+        use std::task::{Context, Poll};
+        use std::time::Duration;
+
+        #config
+
+        #layer
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/graphql/chaos.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate FaultConfig with its environment overrides
+fn generate_config() -> TokenStream {
+    let error_code = DEFAULT_ERROR_CODE;
+
+    quote! {
+        /// Faults injected into gRPC calls
+        #[derive(Debug, Clone)]
+        pub struct FaultConfig {
+            /// Delay added before every targeted call
+            pub latency: Duration,
+            /// Extra random delay, up to this much
+            pub jitter: Duration,
+            /// Share of targeted calls that fail (0.0 to 1.0)
+            pub error_rate: f64,
+            /// Status returned by failed calls
+            pub error_code: tonic::Code,
+            /// RPCs to target, as `Method` or `package.Service/Method`; empty targets all
+            pub methods: Vec<String>,
+            /// Seed for the fault schedule, so runs are reproducible
+            pub seed: u64,
+        }
+
+        impl Default for FaultConfig {
+            fn default() -> Self {
+                Self {
+                    latency: Duration::ZERO,
+                    jitter: Duration::ZERO,
+                    error_rate: 0.0,
+                    error_code: tonic::Code::from(#error_code),
+                    methods: Vec::new(),
+                    seed: 0,
+                }
+            }
+        }
+
+        impl FaultConfig {
+            /// Build a config from environment variables, or `None` when no fault is set
+            ///
+            /// - `SYNAPSE_CHAOS_LATENCY_MS`
+            /// - `SYNAPSE_CHAOS_JITTER_MS`
+            /// - `SYNAPSE_CHAOS_ERROR_RATE`
+            /// - `SYNAPSE_CHAOS_ERROR_CODE` (numeric gRPC code)
+            /// - `SYNAPSE_CHAOS_METHODS` (comma-separated)
+            /// - `SYNAPSE_CHAOS_SEED`
+            pub fn from_env() -> Option<Self> {
+                fn var<T: std::str::FromStr>(key: &str) -> Option<T> {
+                    std::env::var(key).ok().and_then(|v| v.parse().ok())
+                }
+
+                let defaults = Self::default();
+                let config = Self {
+                    latency: var("SYNAPSE_CHAOS_LATENCY_MS").map(Duration::from_millis).unwrap_or(defaults.latency),
+                    jitter: var("SYNAPSE_CHAOS_JITTER_MS").map(Duration::from_millis).unwrap_or(defaults.jitter),
+                    error_rate: var("SYNAPSE_CHAOS_ERROR_RATE").unwrap_or(defaults.error_rate),
+                    error_code: var::<i32>("SYNAPSE_CHAOS_ERROR_CODE")
+                        .map(tonic::Code::from)
+                        .unwrap_or(defaults.error_code),
+                    methods: std::env::var("SYNAPSE_CHAOS_METHODS")
+                        .map(|v| {
+                            v.split(',')
+                                .map(|s| s.trim().to_string())
+                                .filter(|s| !s.is_empty())
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    seed: var("SYNAPSE_CHAOS_SEED").unwrap_or(defaults.seed),
+                };
+                config.is_active().then_some(config)
+            }
+
+            /// Whether any fault is configured
+            pub fn is_active(&self) -> bool {
+                !self.latency.is_zero() || !self.jitter.is_zero() || self.error_rate > 0.0
+            }
+
+            /// Whether a request path (`/package.Service/Method`) is targeted
+            pub fn targets(&self, path: &str) -> bool {
+                self.methods.is_empty()
+                    || self
+                        .methods
+                        .iter()
+                        .any(|m| path.ends_with(&format!("/{}", m)) || path.trim_start_matches('/') == m)
+            }
+        }
+    }
+}
+
+/// Generate FaultLayer and the FaultService it wraps channels in
+fn generate_layer() -> TokenStream {
+    quote! {
+        /// Tower layer injecting the configured faults
+        ///
+        /// ```ignore
+        /// let channel = tower::ServiceBuilder::new().layer(FaultLayer::new(config)).service(channel);
+        /// let client = UserServiceClient::new(channel);
+        /// ```
+        #[derive(Debug, Clone)]
+        pub struct FaultLayer {
+            config: Arc<FaultConfig>,
+            state: Arc<AtomicU64>,
+        }
+
+        impl FaultLayer {
+            pub fn new(config: FaultConfig) -> Self {
+                let state = Arc::new(AtomicU64::new(config.seed));
+                Self {
+                    config: Arc::new(config),
+                    state,
+                }
+            }
+        }
+
+        impl<S> tower::Layer<S> for FaultLayer {
+            type Service = FaultService<S>;
+
+            fn layer(&self, inner: S) -> Self::Service {
+                FaultService {
+                    inner,
+                    config: self.config.clone(),
+                    state: self.state.clone(),
+                }
+            }
+        }
+
+        /// A gRPC transport whose calls are delayed or failed per `FaultConfig`
+        #[derive(Debug, Clone)]
+        pub struct FaultService<S> {
+            inner: S,
+            config: Arc<FaultConfig>,
+            state: Arc<AtomicU64>,
+        }
+
+        impl<S> FaultService<S> {
+            /// Next value of the shared splitmix64 sequence, in [0, 1)
+            fn roll(&self) -> f64 {
+                let mut z = self
+                    .state
+                    .fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed)
+                    .wrapping_add(0x9E37_79B9_7F4A_7C15);
+                z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+                z ^= z >> 31;
+                (z >> 11) as f64 / (1u64 << 53) as f64
+            }
+        }
+
+        /// Trailers-only gRPC response carrying an injected status
+        fn fault_response<B: Default>(code: tonic::Code, path: &str) -> http::Response<B> {
+            let status = tonic::Status::new(code, format!("injected fault: {}", path));
+            let mut response = http::Response::new(B::default());
+            response.headers_mut().insert(
+                http::header::CONTENT_TYPE,
+                http::HeaderValue::from_static("application/grpc"),
+            );
+            let _ = status.add_header(response.headers_mut());
+            response
+        }
+
+        impl<S, ReqBody, ResBody> tower::Service<http::Request<ReqBody>> for FaultService<S>
+        where
+            S: tower::Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+            S::Future: Send + 'static,
+            ReqBody: Send + 'static,
+            ResBody: Default + Send + 'static,
+        {
+            type Response = http::Response<ResBody>;
+            type Error = S::Error;
+            type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+            fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+                self.inner.poll_ready(cx)
+            }
+
+            fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+                let path = request.uri().path().to_string();
+                let targeted = self.config.targets(&path);
+                let delay = if targeted {
+                    self.config.latency + self.config.jitter.mul_f64(self.roll())
+                } else {
+                    Duration::ZERO
+                };
+                let fail = targeted && self.roll() < self.config.error_rate;
+                let code = self.config.error_code;
+
+                // The ready service handles this call; keep a fresh clone for the next
+                let clone = self.inner.clone();
+                let mut inner = std::mem::replace(&mut self.inner, clone);
+
+                Box::pin(async move {
+                    if !delay.is_zero() {
+                        tokio::time::sleep(delay).await;
+                    }
+                    if fail {
+                        return Ok(fault_response(code, &path));
+                    }
+                    inner.call(request).await
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_config() {
+        let code = generate_config().to_string();
+        assert!(code.contains("\"SYNAPSE_CHAOS_ERROR_RATE\""));
+        assert!(code.contains("tonic :: Code :: from (14i32)"));
+        assert!(code.contains("then_some (config)"));
+    }
+
+    #[test]
+    fn test_generate_layer() {
+        let code = generate_layer().to_string();
+        assert!(code.contains("impl < S > tower :: Layer < S > for FaultLayer"));
+        assert!(code.contains("status . add_header"));
+        assert!(code.contains("tokio :: time :: sleep (delay)"));
+    }
+}
@@ -0,0 +1,345 @@
+//! GraphQL schema compatibility generation
+//!
+//! Generates graphql/compat.rs: `check_compat(old_sdl, new_sdl)`, which diffs
+//! two SDL documents and classifies each change as breaking (removed types,
+//! fields, arguments, enum values, or union members; incompatible type
+//! changes; new required arguments and input fields) or additive. With
+//! `graphql_snapshot=<dir>`, the module also gets a test comparing the
+//! package's `schema_sdl()` to the committed `<dir>/<package>.graphql`: the
+//! SDL only exists once the generated code compiles, so the check runs under
+//! `cargo test` rather than inside protoc.
+
+use crate::error::GeneratorError;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::quote;
+
+/// Generate the graphql/compat.rs file for a package
+pub fn generate(file: &FileDescriptorProto) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || file.service.is_empty() {
+        return Ok(None);
+    }
+
+    let snapshot = crate::params::graphql_snapshot()
+        .map(|dir| format!("{}/{}.graphql", dir.trim_end_matches('/'), package));
+    let content = generate_compat(snapshot.as_deref()).to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/graphql/compat.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the SDL diff and, with a snapshot path, its test
+fn generate_compat(snapshot: Option<&str>) -> TokenStream {
+    let snapshot_test = snapshot.map(|path| {
+        quote! {
+            #[cfg(test)]
+            mod snapshot {
+                /// Fails on breaking changes against the committed schema;
+                /// `SYNAPSE_UPDATE_SNAPSHOT=1` accepts the current schema
+                #[test]
+                fn graphql_schema_is_compatible() {
+                    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/", #path);
+                    if let Err(error) = super::check_snapshot(path) {
+                        panic!("{}", error);
+                    }
+                }
+            }
+        }
+    });
+
+    quote! {
+        //! GraphQL schema compatibility checks
+        //!
+        //! Breaking changes fail client queries that worked before; additive
+        //! changes are safe for existing clients but worth a review.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(dead_code)]
+
+        use std::collections::HashMap;
+        use std::fmt;
+
+        use async_graphql::parser::types::{
+            BaseType, EnumType, FieldDefinition, InputValueDefinition, ServiceDocument, Type,
+            TypeDefinition, TypeKind, TypeSystemDefinition, UnionType,
+        };
+        use async_graphql::parser::Positioned;
+        use async_graphql::Name;
+
+        /// One difference between two schemas
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct SchemaChange {
+            /// Schema coordinate, e.g. `Post.title` or `Query.posts(first:)`
+            pub path: String,
+            pub message: String,
+        }
+
+        impl fmt::Display for SchemaChange {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}: {}", self.path, self.message)
+            }
+        }
+
+        /// Classified changes from an old schema to a new one
+        #[derive(Debug, Clone, Default)]
+        pub struct CompatReport {
+            /// Changes that can fail existing client operations
+            pub breaking: Vec<SchemaChange>,
+            /// Changes existing clients do not observe
+            pub additive: Vec<SchemaChange>,
+        }
+
+        impl CompatReport {
+            pub fn is_compatible(&self) -> bool {
+                self.breaking.is_empty()
+            }
+
+            fn breaking(&mut self, path: impl Into<String>, message: impl Into<String>) {
+                self.breaking.push(SchemaChange { path: path.into(), message: message.into() });
+            }
+
+            fn additive(&mut self, path: impl Into<String>, message: impl Into<String>) {
+                self.additive.push(SchemaChange { path: path.into(), message: message.into() });
+            }
+        }
+
+        impl fmt::Display for CompatReport {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                for change in &self.breaking {
+                    writeln!(f, "BREAKING {}", change)?;
+                }
+                for change in &self.additive {
+                    writeln!(f, "additive {}", change)?;
+                }
+                Ok(())
+            }
+        }
+
+        /// Compare two SDL documents
+        pub fn check_compat(old_sdl: &str, new_sdl: &str) -> Result<CompatReport, String> {
+            let old = types(old_sdl).map_err(|e| format!("old schema: {}", e))?;
+            let new = types(new_sdl).map_err(|e| format!("new schema: {}", e))?;
+            let mut report = CompatReport::default();
+
+            for (name, old_type) in &old {
+                match new.get(name) {
+                    Some(new_type) => compare_type(&mut report, name, &old_type.kind, &new_type.kind),
+                    None => report.breaking(name.as_str(), "type removed"),
+                }
+            }
+            for name in new.keys().filter(|name| !old.contains_key(*name)) {
+                report.additive(name.as_str(), "type added");
+            }
+
+            report.breaking.sort_by(|a, b| a.path.cmp(&b.path));
+            report.additive.sort_by(|a, b| a.path.cmp(&b.path));
+            Ok(report)
+        }
+
+        /// Compare `super::schema_sdl()` to the snapshot at `path`
+        ///
+        /// Errors on breaking changes and prints additive ones. A missing
+        /// snapshot is written; `SYNAPSE_UPDATE_SNAPSHOT=1` rewrites it.
+        pub fn check_snapshot(path: &str) -> Result<CompatReport, String> {
+            let current = super::schema_sdl();
+            let update = std::env::var("SYNAPSE_UPDATE_SNAPSHOT").is_ok_and(|v| v == "1");
+            let snapshot = match std::fs::read_to_string(path) {
+                Ok(snapshot) if !update => snapshot,
+                _ => {
+                    if let Some(dir) = std::path::Path::new(path).parent() {
+                        std::fs::create_dir_all(dir).map_err(|e| format!("cannot create {}: {}", dir.display(), e))?;
+                    }
+                    std::fs::write(path, &current).map_err(|e| format!("cannot write {}: {}", path, e))?;
+                    return Ok(CompatReport::default());
+                }
+            };
+
+            let report = check_compat(&snapshot, &current)?;
+            if !report.is_compatible() {
+                return Err(format!(
+                    "GraphQL schema has breaking changes against {} (SYNAPSE_UPDATE_SNAPSHOT=1 accepts them):\n{}",
+                    path, report
+                ));
+            }
+            if !report.additive.is_empty() {
+                eprintln!("GraphQL schema differs from {} (SYNAPSE_UPDATE_SNAPSHOT=1 updates it):\n{}", path, report);
+            }
+            Ok(report)
+        }
+
+        /// Named type definitions of an SDL document
+        fn types(sdl: &str) -> Result<HashMap<String, TypeDefinition>, String> {
+            let document: ServiceDocument = async_graphql::parser::parse_schema(sdl).map_err(|e| e.to_string())?;
+            Ok(document
+                .definitions
+                .into_iter()
+                .filter_map(|definition| match definition {
+                    TypeSystemDefinition::Type(ty) => Some((ty.node.name.node.to_string(), ty.node)),
+                    _ => None,
+                })
+                .collect())
+        }
+
+        fn compare_type(report: &mut CompatReport, name: &str, old: &TypeKind, new: &TypeKind) {
+            match (old, new) {
+                (TypeKind::Object(old), TypeKind::Object(new)) => {
+                    compare_implements(report, name, &old.implements, &new.implements);
+                    compare_fields(report, name, &old.fields, &new.fields);
+                }
+                (TypeKind::Interface(old), TypeKind::Interface(new)) => {
+                    compare_implements(report, name, &old.implements, &new.implements);
+                    compare_fields(report, name, &old.fields, &new.fields);
+                }
+                (TypeKind::InputObject(old), TypeKind::InputObject(new)) => {
+                    compare_inputs(report, name, "input field", &old.fields, &new.fields);
+                }
+                (TypeKind::Enum(old), TypeKind::Enum(new)) => {
+                    let values = |kind: &EnumType| -> Vec<String> {
+                        kind.values.iter().map(|v| v.node.value.node.to_string()).collect()
+                    };
+                    let (old, new) = (values(old), values(new));
+                    for value in old.iter().filter(|v| !new.contains(v)) {
+                        report.breaking(format!("{}.{}", name, value), "enum value removed");
+                    }
+                    for value in new.iter().filter(|v| !old.contains(v)) {
+                        report.additive(format!("{}.{}", name, value), "enum value added");
+                    }
+                }
+                (TypeKind::Union(old), TypeKind::Union(new)) => {
+                    let members = |kind: &UnionType| -> Vec<String> {
+                        kind.members.iter().map(|m| m.node.to_string()).collect()
+                    };
+                    let (old, new) = (members(old), members(new));
+                    for member in old.iter().filter(|m| !new.contains(m)) {
+                        report.breaking(name, format!("union member {} removed", member));
+                    }
+                    for member in new.iter().filter(|m| !old.contains(m)) {
+                        report.additive(name, format!("union member {} added", member));
+                    }
+                }
+                (TypeKind::Scalar, TypeKind::Scalar) => {}
+                _ => report.breaking(name, "kind changed"),
+            }
+        }
+
+        fn compare_implements(
+            report: &mut CompatReport,
+            name: &str,
+            old: &[Positioned<Name>],
+            new: &[Positioned<Name>],
+        ) {
+            for interface in old.iter().filter(|i| !new.iter().any(|n| n.node == i.node)) {
+                report.breaking(name, format!("no longer implements {}", interface.node));
+            }
+        }
+
+        fn compare_fields(
+            report: &mut CompatReport,
+            name: &str,
+            old: &[Positioned<FieldDefinition>],
+            new: &[Positioned<FieldDefinition>],
+        ) {
+            for old_field in old {
+                let field = old_field.node.name.node.as_str();
+                let path = format!("{}.{}", name, field);
+                let Some(new_field) = new.iter().find(|f| f.node.name.node.as_str() == field) else {
+                    report.breaking(path, "field removed");
+                    continue;
+                };
+                let (old_ty, new_ty) = (&old_field.node.ty.node, &new_field.node.ty.node);
+                if !output_compatible(old_ty, new_ty) {
+                    report.breaking(path.as_str(), format!("type changed from {} to {}", old_ty, new_ty));
+                } else if old_ty != new_ty {
+                    report.additive(path.as_str(), format!("type narrowed from {} to {}", old_ty, new_ty));
+                }
+                compare_inputs(report, &path, "argument", &old_field.node.arguments, &new_field.node.arguments);
+            }
+            for new_field in new.iter().filter(|f| !old.iter().any(|o| o.node.name.node == f.node.name.node)) {
+                report.additive(format!("{}.{}", name, new_field.node.name.node), "field added");
+            }
+        }
+
+        /// Compare arguments or input fields
+        fn compare_inputs(
+            report: &mut CompatReport,
+            path: &str,
+            what: &str,
+            old: &[Positioned<InputValueDefinition>],
+            new: &[Positioned<InputValueDefinition>],
+        ) {
+            for old_input in old {
+                let input = old_input.node.name.node.as_str();
+                let path = format!("{}({}:)", path, input);
+                let Some(new_input) = new.iter().find(|i| i.node.name.node.as_str() == input) else {
+                    report.breaking(path, format!("{} removed", what));
+                    continue;
+                };
+                let (old_ty, new_ty) = (&old_input.node.ty.node, &new_input.node.ty.node);
+                if !input_compatible(old_ty, new_ty) {
+                    report.breaking(path.as_str(), format!("type changed from {} to {}", old_ty, new_ty));
+                } else if old_ty != new_ty {
+                    report.additive(path.as_str(), format!("type widened from {} to {}", old_ty, new_ty));
+                }
+            }
+            for new_input in new.iter().filter(|i| !old.iter().any(|o| o.node.name.node == i.node.name.node)) {
+                let path = format!("{}({}:)", path, new_input.node.name.node);
+                if !new_input.node.ty.node.nullable && new_input.node.default_value.is_none() {
+                    report.breaking(path, format!("required {} added", what));
+                } else {
+                    report.additive(path, format!("optional {} added", what));
+                }
+            }
+        }
+
+        /// Clients reading `old` still get a value they accept from `new`
+        fn output_compatible(old: &Type, new: &Type) -> bool {
+            (old.nullable || !new.nullable)
+                && match (&old.base, &new.base) {
+                    (BaseType::Named(old), BaseType::Named(new)) => old == new,
+                    (BaseType::List(old), BaseType::List(new)) => output_compatible(old, new),
+                    _ => false,
+                }
+        }
+
+        /// Values clients sent for `old` are still accepted by `new`
+        fn input_compatible(old: &Type, new: &Type) -> bool {
+            (new.nullable || !old.nullable)
+                && match (&old.base, &new.base) {
+                    (BaseType::Named(old), BaseType::Named(new)) => old == new,
+                    (BaseType::List(old), BaseType::List(new)) => input_compatible(old, new),
+                    _ => false,
+                }
+        }
+
+        #snapshot_test
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_compat() {
+        let code = generate_compat(None).to_string();
+        assert!(code.contains("pub fn check_compat (old_sdl : & str , new_sdl : & str) -> Result < CompatReport , String >"));
+        assert!(code.contains("pub fn check_snapshot (path : & str)"));
+        assert!(!code.contains("graphql_schema_is_compatible"));
+    }
+
+    #[test]
+    fn test_generate_snapshot_test() {
+        let code = generate_compat(Some("schema/blog.graphql")).to_string();
+        assert!(code.contains("fn graphql_schema_is_compatible"));
+        assert!(code.contains("\"schema/blog.graphql\""));
+    }
+}
@@ -0,0 +1,273 @@
+//! Concurrency budget generation
+//!
+//! Generates graphql/concurrency.rs: a `ConcurrencyLimit` extension giving
+//! each GraphQL document a semaphore in its request data. Root resolvers and
+//! the paginated relation resolvers take a permit around their gRPC call, so
+//! one document cannot fan out hundreds of parallel calls downstream. The
+//! budget comes from the `max_concurrency` of the root fields the document
+//! selects (the smallest wins). DataLoader-backed relations already batch
+//! their calls and do not take permits.
+
+use crate::error::GeneratorError;
+use crate::storage::seaorm::options::{
+    get_cached_graphql_mutation_options, get_cached_graphql_query_options, get_cached_graphql_service_options,
+};
+use heck::{ToLowerCamelCase, ToSnakeCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::quote;
+
+/// Root fields with a `max_concurrency`, as (GraphQL field name, limit)
+#[derive(Debug, Default)]
+pub struct RootLimits {
+    pub queries: Vec<(String, usize)>,
+    pub mutations: Vec<(String, usize)>,
+}
+
+impl RootLimits {
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty() && self.mutations.is_empty()
+    }
+}
+
+/// Collect the `max_concurrency` of the file's query and mutation methods
+pub fn collect_limits(file: &FileDescriptorProto) -> RootLimits {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let mut limits = RootLimits::default();
+
+    for service in &file.service {
+        let svc_name = service.name.as_deref().unwrap_or("");
+        if get_cached_graphql_service_options(file_name, svc_name).is_some_and(|o| o.skip) {
+            continue;
+        }
+        for method in &service.method {
+            let method_name = method.name.as_deref().unwrap_or("");
+            // Same classification as the resolvers: mutation options win
+            let (skip, name, max_concurrency, target) = match (
+                get_cached_graphql_mutation_options(file_name, svc_name, method_name),
+                get_cached_graphql_query_options(file_name, svc_name, method_name),
+            ) {
+                (Some(opts), _) => (opts.skip, opts.name, opts.max_concurrency, &mut limits.mutations),
+                (None, Some(opts)) => (opts.skip, opts.name, opts.max_concurrency, &mut limits.queries),
+                (None, None) => continue,
+            };
+            if !skip && max_concurrency > 0 {
+                target.push((root_field_name(&name, method_name), max_concurrency as usize));
+            }
+        }
+    }
+
+    limits
+}
+
+/// GraphQL name of a root field, as async-graphql renames the resolver
+fn root_field_name(name: &str, method_name: &str) -> String {
+    let field = if name.is_empty() { method_name } else { name };
+    field.to_snake_case().to_lower_camel_case()
+}
+
+/// Generate the graphql/concurrency.rs file for a package
+pub fn generate(file: &FileDescriptorProto) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || file.service.is_empty() {
+        return Ok(None);
+    }
+
+    let content = generate_concurrency(&collect_limits(file)).to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/graphql/concurrency.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the budget, its extension, and the root field limits
+fn generate_concurrency(limits: &RootLimits) -> TokenStream {
+    let table = |entries: &[(String, usize)]| {
+        let entries = entries.iter().map(|(name, limit)| quote! { (#name, #limit) });
+        quote! { &[#(#entries),*] }
+    };
+    let query_limits = table(&limits.queries);
+    let mutation_limits = table(&limits.mutations);
+
+    quote! {
+        //! Per-document concurrency budget for resolver gRPC calls
+        //!
+        //! A permit is held only for the duration of one gRPC call, never
+        //! while nested fields resolve, so a budget cannot deadlock.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(dead_code)]
+
+        use std::sync::Arc;
+
+        use async_graphql::extensions::{
+            Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextPrepareRequest,
+        };
+        use async_graphql::parser::types::{ExecutableDocument, OperationType, Selection, SelectionSet};
+        use async_graphql::{Context, Request, ServerResult, Variables};
+        use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+        /// `max_concurrency` of root query fields
+        const QUERY_LIMITS: &[(&str, usize)] = #query_limits;
+
+        /// `max_concurrency` of root mutation fields
+        const MUTATION_LIMITS: &[(&str, usize)] = #mutation_limits;
+
+        /// Fragment nesting followed when reading root fields; deeper (or
+        /// cyclic) spreads are left to validation
+        const MAX_FRAGMENT_DEPTH: usize = 8;
+
+        /// Semaphore shared by the resolvers of one document
+        #[derive(Debug, Clone)]
+        pub struct ConcurrencyBudget(Arc<Semaphore>);
+
+        /// Wait for a permit of the document's budget
+        ///
+        /// `None` when the schema has no `ConcurrencyLimit` extension; the call
+        /// then proceeds unlimited.
+        pub async fn acquire(ctx: &Context<'_>) -> Option<OwnedSemaphorePermit> {
+            let budget = ctx.data_opt::<ConcurrencyBudget>()?;
+            budget.0.clone().acquire_owned().await.ok()
+        }
+
+        /// Extension giving each document a concurrency budget
+        ///
+        /// Documents selecting a root field with `max_concurrency` get the
+        /// smallest such limit; others get `default_limit`, or no limit.
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct ConcurrencyLimit {
+            default_limit: Option<usize>,
+        }
+
+        impl ConcurrencyLimit {
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            /// Budget for documents whose root fields set no limit
+            pub fn default_limit(mut self, limit: usize) -> Self {
+                self.default_limit = Some(limit);
+                self
+            }
+        }
+
+        impl ExtensionFactory for ConcurrencyLimit {
+            fn create(&self) -> Arc<dyn Extension> {
+                Arc::new(ConcurrencyLimitExtension {
+                    default_limit: self.default_limit,
+                    // Permits are added once the document is parsed
+                    budget: ConcurrencyBudget(Arc::new(Semaphore::new(0))),
+                })
+            }
+        }
+
+        struct ConcurrencyLimitExtension {
+            default_limit: Option<usize>,
+            budget: ConcurrencyBudget,
+        }
+
+        #[async_trait::async_trait]
+        impl Extension for ConcurrencyLimitExtension {
+            async fn prepare_request(
+                &self,
+                ctx: &ExtensionContext<'_>,
+                request: Request,
+                next: NextPrepareRequest<'_>,
+            ) -> ServerResult<Request> {
+                next.run(ctx, request.data(self.budget.clone())).await
+            }
+
+            async fn parse_query(
+                &self,
+                ctx: &ExtensionContext<'_>,
+                query: &str,
+                variables: &Variables,
+                next: NextParseQuery<'_>,
+            ) -> ServerResult<ExecutableDocument> {
+                let document = next.run(ctx, query, variables).await?;
+                let limit = document_limit(&document)
+                    .or(self.default_limit)
+                    .unwrap_or(Semaphore::MAX_PERMITS);
+                self.budget.0.add_permits(limit.clamp(1, Semaphore::MAX_PERMITS));
+                Ok(document)
+            }
+        }
+
+        /// Smallest limit among the root fields of the document's operations
+        fn document_limit(document: &ExecutableDocument) -> Option<usize> {
+            document
+                .operations
+                .iter()
+                .filter_map(|(_, operation)| {
+                    let limits = match operation.node.ty {
+                        OperationType::Query => QUERY_LIMITS,
+                        OperationType::Mutation => MUTATION_LIMITS,
+                        OperationType::Subscription => return None,
+                    };
+                    selection_limit(document, &operation.node.selection_set.node, limits, 0)
+                })
+                .min()
+        }
+
+        fn selection_limit(
+            document: &ExecutableDocument,
+            selection_set: &SelectionSet,
+            limits: &[(&str, usize)],
+            depth: usize,
+        ) -> Option<usize> {
+            if depth > MAX_FRAGMENT_DEPTH {
+                return None;
+            }
+            selection_set
+                .items
+                .iter()
+                .filter_map(|item| match &item.node {
+                    Selection::Field(field) => limits
+                        .iter()
+                        .find(|(name, _)| *name == field.node.name.node.as_str())
+                        .map(|(_, limit)| *limit),
+                    Selection::InlineFragment(fragment) => {
+                        selection_limit(document, &fragment.node.selection_set.node, limits, depth + 1)
+                    }
+                    Selection::FragmentSpread(spread) => document
+                        .fragments
+                        .get(&spread.node.fragment_name.node)
+                        .and_then(|fragment| {
+                            selection_limit(document, &fragment.node.selection_set.node, limits, depth + 1)
+                        }),
+                })
+                .min()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_root_field_name() {
+        assert_eq!(root_field_name("", "ListUsers"), "listUsers");
+        assert_eq!(root_field_name("users", "ListUsers"), "users");
+        assert_eq!(root_field_name("user_posts", "ListPosts"), "userPosts");
+    }
+
+    #[test]
+    fn test_generate_concurrency() {
+        let limits = RootLimits {
+            queries: vec![("users".to_string(), 8)],
+            mutations: Vec::new(),
+        };
+        let code = generate_concurrency(&limits).to_string();
+        assert!(code.contains("const QUERY_LIMITS : & [(& str , usize)] = & [(\"users\" , 8usize)]"));
+        assert!(code.contains("const MUTATION_LIMITS : & [(& str , usize)] = & []"));
+        assert!(code.contains("request . data (self . budget . clone ())"));
+    }
+}
@@ -2,6 +2,8 @@
 //!
 //! Generates:
 //! - PageInfo type (once per package)
+//! - BatchError type (shared), and ContextResolver types when a request has
+//!   `from_context` fields
 //! - Entity Edge types (UserEdge, PostEdge, etc.)
 //! - Entity Connection types (UserConnection, PostConnection, etc.)
 
@@ -19,12 +21,21 @@ use quote::{format_ident, quote};
 pub fn generate_connections_for_package(
     file: &FileDescriptorProto,
     entities: &[&DescriptorProto],
+    all_files: &[FileDescriptorProto],
 ) -> Result<Vec<File>, GeneratorError> {
     let mut files = Vec::new();
 
     // Always generate PageInfo (proto PageInfo needs GraphQL wrapper)
     files.push(generate_page_info(file)?);
 
+    // Always generate BatchError (shared per-item errors of batch mutations)
+    files.push(super::batch::generate_batch_error()?);
+
+    // The ContextResolver behind `from_context` fields, when some request has one
+    if super::context::uses_context(all_files) {
+        files.push(super::context::generate_context_resolver()?);
+    }
+
     // Always generate Edge and Connection types for each entity
     // (proto Connection types have synapse.storage.connection_type, not graphql.message)
     for entity in entities {
@@ -73,10 +84,7 @@ fn generate_page_info(_file: &FileDescriptorProto) -> Result<File, GeneratorErro
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output to shared location: synapse/relay/graphql/
     let output_path = "synapse/relay/graphql/page_info.rs".to_string();
@@ -117,10 +125,7 @@ fn generate_entity_edge(
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     let package = file.package.as_deref().unwrap_or("");
     let output_path = format!(
@@ -186,10 +191,7 @@ fn generate_entity_connection(
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     let package = file.package.as_deref().unwrap_or("");
     let output_path = format!(
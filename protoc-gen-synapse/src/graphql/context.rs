@@ -0,0 +1,347 @@
+//! Context-injected request fields
+//!
+//! A request field with `from_context` is left out of the GraphQL input and
+//! filled by the resolver from the request's context. The shared
+//! `ContextResolver` trait (in synapse/relay/graphql) maps a dotted path such
+//! as `current_user.id` or `tenant.id` to a JSON value, which is deserialized
+//! into the field's type. `required` fields fail the mutation with an
+//! `UNAUTHENTICATED` error (or `error_message`) when the path has no value;
+//! others fall back to the type's default.
+
+use crate::error::GeneratorError;
+use crate::options::synapse::graphql::ContextSource;
+use crate::storage::seaorm::options::get_cached_graphql_field_options;
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{FieldDescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+
+/// A request field filled from the GraphQL context
+pub(super) struct ContextField {
+    /// Field name (snake_case)
+    pub name: String,
+    /// Rust type of the prost field
+    pub rust_type: TokenStream,
+    /// Type read from the context (the field type without `Option`)
+    value_type: TokenStream,
+    /// Whether the field has proto3 presence
+    optional: bool,
+    /// The field's `from_context` option
+    pub source: ContextSource,
+}
+
+impl ContextField {
+    /// Context path (e.g., "current_user.id")
+    pub fn path(&self) -> &str {
+        &self.source.path
+    }
+
+    /// Generate `let <field> = ...;` reading the field from the resolver's `ctx`
+    pub fn extraction(&self) -> TokenStream {
+        let ident = format_ident!("{}", self.name);
+        let path = &self.source.path;
+        let value_type = &self.value_type;
+        let read = quote! {
+            super::super::super::synapse::relay::graphql::context_value::<#value_type>(ctx, #path)?
+        };
+        // `required` defaults to true when unset
+        let value = match (self.source.required.unwrap_or(true), self.optional) {
+            (true, optional) => {
+                let message = self.missing_message();
+                let value = quote! {
+                    #read.ok_or_else(|| super::super::super::synapse::relay::graphql::missing_context(#message))?
+                };
+                if optional {
+                    quote! { Some(#value) }
+                } else {
+                    value
+                }
+            }
+            (false, true) => read,
+            (false, false) => quote! { #read.unwrap_or_default() },
+        };
+        quote! {
+            let #ident = #value;
+        }
+    }
+
+    /// Error message of a required field whose path has no value
+    fn missing_message(&self) -> String {
+        if !self.source.error_message.is_empty() {
+            self.source.error_message.clone()
+        } else if self.source.path.starts_with("current_user.") {
+            "Authentication required".to_string()
+        } else {
+            format!("Missing context: {}", self.source.path)
+        }
+    }
+}
+
+/// Context-injected fields of a request message
+pub(super) fn context_fields(
+    file: &FileDescriptorProto,
+    message_name: &str,
+) -> Result<Vec<ContextField>, GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let Some(message) = file
+        .message_type
+        .iter()
+        .find(|m| m.name.as_deref() == Some(message_name))
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut fields = Vec::new();
+    for field in &message.field {
+        let Some(source) = get_cached_graphql_field_options(file_name, message_name, field.number.unwrap_or(0))
+            .and_then(|o| o.from_context)
+        else {
+            continue;
+        };
+        let field_name = field.name.as_deref().unwrap_or("");
+        let Some(value_type) = value_type(field) else {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{}.{}: from_context fields must be scalars, strings, bytes or enums",
+                message_name, field_name
+            )));
+        };
+        let optional = field.proto3_optional.unwrap_or(false);
+        let rust_type = if optional {
+            quote! { Option<#value_type> }
+        } else {
+            value_type.clone()
+        };
+        fields.push(ContextField {
+            name: field_name.to_snake_case(),
+            rust_type,
+            value_type,
+            optional,
+            source,
+        });
+    }
+    Ok(fields)
+}
+
+/// Rust type of a context-injected field's values, or `None` for messages
+fn value_type(field: &FieldDescriptorProto) -> Option<TokenStream> {
+    let scalar = match field.r#type() {
+        Type::Double => quote! { f64 },
+        Type::Float => quote! { f32 },
+        Type::Int64 | Type::Sfixed64 | Type::Sint64 => quote! { i64 },
+        Type::Uint64 | Type::Fixed64 => quote! { u64 },
+        Type::Int32 | Type::Sfixed32 | Type::Sint32 | Type::Enum => quote! { i32 },
+        Type::Uint32 | Type::Fixed32 => quote! { u32 },
+        Type::Bool => quote! { bool },
+        Type::String => quote! { String },
+        Type::Bytes => quote! { Vec<u8> },
+        Type::Message | Type::Group => return None,
+    };
+    Some(if field.label() == Label::Repeated {
+        quote! { Vec<#scalar> }
+    } else {
+        scalar
+    })
+}
+
+/// Whether any message of `files` has a `from_context` field
+pub(super) fn uses_context(files: &[FileDescriptorProto]) -> bool {
+    files.iter().any(|file| {
+        let file_name = file.name.as_deref().unwrap_or("");
+        file.message_type.iter().any(|message| {
+            let message_name = message.name.as_deref().unwrap_or("");
+            message.field.iter().any(|field| {
+                get_cached_graphql_field_options(file_name, message_name, field.number.unwrap_or(0))
+                    .is_some_and(|o| o.from_context.is_some())
+            })
+        })
+    })
+}
+
+/// Generate the ContextResolver types (in shared synapse/relay/graphql location)
+pub(super) fn generate_context_resolver() -> Result<File, GeneratorError> {
+    let code = quote! {
+        //! Auto-generated resolution of `from_context` request fields
+        //! @generated
+
+        #![allow(missing_docs)]
+
+        use async_graphql::ErrorExtensions;
+
+        /// Source of the values `from_context` request fields are filled from
+        ///
+        /// Implement it for the application's request context (or wrap a
+        /// serializable one in [`JsonContext`]) and add it to each request
+        /// with `request.data(ContextSource::new(context))`.
+        pub trait ContextResolver: Send + Sync + 'static {
+            /// Value at a dotted path (e.g. `current_user.id`), `None` when absent
+            fn resolve(&self, path: &str) -> Option<serde_json::Value>;
+        }
+
+        /// Resolver walking dotted paths over the JSON form of a context
+        pub struct JsonContext(pub serde_json::Value);
+
+        impl JsonContext {
+            /// Resolve paths over the serialized `context`
+            pub fn new(context: &impl serde::Serialize) -> serde_json::Result<Self> {
+                Ok(Self(serde_json::to_value(context)?))
+            }
+        }
+
+        impl ContextResolver for JsonContext {
+            fn resolve(&self, path: &str) -> Option<serde_json::Value> {
+                path.split('.')
+                    .try_fold(&self.0, |value, key| value.get(key))
+                    .filter(|value| !value.is_null())
+                    .cloned()
+            }
+        }
+
+        /// Request data holding the request's context resolver
+        #[derive(Clone)]
+        pub struct ContextSource(std::sync::Arc<dyn ContextResolver>);
+
+        impl ContextSource {
+            /// Fill `from_context` fields of the request from `resolver`
+            pub fn new(resolver: impl ContextResolver) -> Self {
+                Self(std::sync::Arc::new(resolver))
+            }
+        }
+
+        /// Value of a `from_context` field at `path`, `None` when absent
+        ///
+        /// Reads the request's [`ContextSource`]. Without one, `current_user.id`
+        /// is the `id` of the request's `crate::CurrentUser`.
+        pub fn context_value<T: serde::de::DeserializeOwned>(
+            ctx: &async_graphql::Context<'_>,
+            path: &str,
+        ) -> async_graphql::Result<Option<T>> {
+            let value = match ctx.data_opt::<ContextSource>() {
+                Some(source) => source.0.resolve(path),
+                None if path == "current_user.id" => ctx
+                    .data_opt::<crate::CurrentUser>()
+                    .map(|user| serde_json::Value::from(user.id.clone())),
+                None => None,
+            };
+            value
+                .map(|value| {
+                    serde_json::from_value(value).map_err(|e| {
+                        async_graphql::Error::new(format!("Invalid context value at '{}': {}", path, e))
+                    })
+                })
+                .transpose()
+        }
+
+        /// Error of a required `from_context` field whose path has no value
+        pub fn missing_context(message: &str) -> async_graphql::Error {
+            async_graphql::Error::new(message).extend_with(|_, extensions| {
+                extensions.set("code", "UNAUTHENTICATED");
+            })
+        }
+    };
+
+    Ok(File {
+        name: Some("synapse/relay/graphql/context_resolver.rs".to_string()),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context_field(required: Option<bool>, optional: bool, path: &str, error_message: &str) -> ContextField {
+        ContextField {
+            name: "tenant_id".to_string(),
+            rust_type: quote! { String },
+            value_type: quote! { String },
+            optional,
+            source: ContextSource {
+                path: path.to_string(),
+                required,
+                error_message: error_message.to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_extraction_required() {
+        let code = context_field(Some(true), false, "tenant.id", "").extraction().to_string();
+        assert!(code.contains("context_value :: < String > (ctx , \"tenant.id\") ?"));
+        assert!(code.contains("missing_context (\"Missing context: tenant.id\")"));
+
+        let code = context_field(Some(true), true, "current_user.id", "").extraction().to_string();
+        assert!(code.starts_with("let tenant_id = Some ("));
+        assert!(code.contains("missing_context (\"Authentication required\")"));
+
+        let code = context_field(Some(true), false, "claims.sub", "Sign in first").extraction().to_string();
+        assert!(code.contains("missing_context (\"Sign in first\")"));
+    }
+
+    #[test]
+    fn test_extraction_required_when_unset() {
+        let code = context_field(None, false, "current_user.id", "").extraction().to_string();
+        assert!(code.contains("missing_context (\"Authentication required\")"));
+        assert!(!code.contains("unwrap_or_default"));
+    }
+
+    #[test]
+    fn test_extraction_not_required() {
+        let code = context_field(Some(false), false, "tenant.id", "").extraction().to_string();
+        assert!(code.ends_with(". unwrap_or_default () ;"));
+
+        let code = context_field(Some(false), true, "tenant.id", "").extraction().to_string();
+        assert!(!code.contains("unwrap_or_default"));
+        assert!(!code.contains("missing_context"));
+    }
+
+    #[test]
+    fn test_uses_context() {
+        use crate::options::synapse::graphql::FieldOptions;
+        use crate::storage::seaorm::options::cache_graphql_field_options;
+        use prost_types::DescriptorProto;
+
+        let file = |name: &str| FileDescriptorProto {
+            name: Some(name.to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("CreateNoteRequest".to_string()),
+                field: vec![FieldDescriptorProto {
+                    name: Some("author_id".to_string()),
+                    number: Some(1),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        cache_graphql_field_options(
+            "uses_context/with.proto",
+            "CreateNoteRequest",
+            1,
+            FieldOptions {
+                from_context: Some(ContextSource {
+                    path: "current_user.id".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+
+        let plain = file("uses_context/without.proto");
+        assert!(!uses_context(std::slice::from_ref(&plain)));
+        assert!(uses_context(&[plain, file("uses_context/with.proto")]));
+    }
+
+    #[test]
+    fn test_value_type() {
+        use crate::test_util::{field, repeated};
+
+        let ty = |f| value_type(&f).map(|t| t.to_string());
+        assert_eq!(ty(field("name", 1, Type::String)), Some("String".to_string()));
+        assert_eq!(ty(field("role", 1, Type::Enum)), Some("i32".to_string()));
+        assert_eq!(ty(repeated(field("scopes", 1, Type::String))), Some("Vec < String >".to_string()));
+        assert_eq!(ty(field("profile", 1, Type::Message)), None);
+    }
+}
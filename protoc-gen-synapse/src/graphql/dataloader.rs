@@ -6,13 +6,20 @@
 //! Two types of loaders are generated:
 //! 1. ID Loaders (for BelongsTo): Load entities by their primary key
 //! 2. Relation Loaders (for HasMany): Load related entities by foreign key
+//!
+//! Loaders fetch through a per-entity `{Type}Fetcher` trait rather than a
+//! tonic client directly. The gRPC client, the service backend, and
+//! `StorageFetcher` (wrapping the storage trait) implement it, so monolith
+//! deployments can back the same loaders in-process without a network hop.
 
 use crate::error::GeneratorError;
+use crate::storage::defaults::infer_operation;
 use crate::storage::seaorm::options::{
-    get_cached_entity_options, get_cached_graphql_type_options,
+    get_cached_entity_options, get_cached_graphql_type_options, get_cached_rpc_method_options,
 };
 use heck::{ToSnakeCase, ToUpperCamelCase};
 use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::Label;
 use prost_types::{DescriptorProto, FileDescriptorProto};
 use quote::{format_ident, quote};
 
@@ -99,6 +106,14 @@ fn generate_relation_loader(
     // Check if the FK field on the related entity is optional
     let fk_is_optional = find_field_optionality(all_files, related_type, foreign_key);
 
+    // FK key type follows the field (i64, or String for UUID keys)
+    let related_msg = all_files
+        .iter()
+        .flat_map(|f| f.message_type.iter())
+        .find(|m| m.name.as_deref().map(|n| n.to_upper_camel_case()) == Some(related_type.to_upper_camel_case()));
+    let key_ty = super::key_type(related_msg, &foreign_key.to_snake_case());
+    let key_filter = super::key_filter_type(related_msg, &foreign_key.to_snake_case());
+
     // Generate loader name (e.g., PostsByUserLoader)
     let loader_name = format!(
         "{}sBy{}Loader",
@@ -113,22 +128,15 @@ fn generate_relation_loader(
     // Foreign key ident for grouping results
     let fk_ident = format_ident!("{}", foreign_key.to_snake_case());
 
-    // Service client type
-    let service_name = format!("{}Service", related_type.to_upper_camel_case());
-    let client_module = format!("{}_client", service_name.to_snake_case());
-    let client_module_ident = format_ident!("{}", client_module);
-    let client_ident = format_ident!("{}Client", service_name);
-
-    // List request and filter type names
-    let list_request = format_ident!("List{}sRequest", related_type.to_upper_camel_case());
-    let list_method = format_ident!("list_{}s", related_type.to_snake_case());
+    // Fetcher for the related entity and its filter type
+    let fetcher_ident = format_ident!("{}Fetcher", related_type.to_upper_camel_case());
     let filter_type = format_ident!("{}Filter", related_type.to_upper_camel_case());
 
     // Generate populate code based on FK optionality
     let populate_code = if fk_is_optional {
         // Optional FK: unwrap before using as map key
         quote! {
-            if let Some(key) = entity.#fk_ident {
+            if let Some(key) = entity.#fk_ident.clone() {
                 if let Some(vec) = map.get_mut(&key) {
                     vec.push(entity);
                 }
@@ -137,7 +145,7 @@ fn generate_relation_loader(
     } else {
         // Required FK: use directly
         quote! {
-            let key = entity.#fk_ident;
+            let key = entity.#fk_ident.clone();
             if let Some(vec) = map.get_mut(&key) {
                 vec.push(entity);
             }
@@ -153,76 +161,63 @@ fn generate_relation_loader(
 
         use async_graphql::dataloader::Loader;
         use std::collections::HashMap;
-        use tonic::transport::Channel;
-        // Import gRPC client and types from parent module
-        use super::super::#client_module_ident::#client_ident;
-        use super::super::#list_request;
+        use std::sync::Arc;
+        // Import fetcher and types from parent module
+        use super::#fetcher_ident;
         use super::super::#filter_type;
-        use super::super::super::synapse::relay::IntFilter;
+        use super::super::super::synapse::relay::#key_filter;
 
         /// DataLoader for fetching #related_type by #parent_type ID (HasMany)
         ///
-        /// Uses List RPC with IN filter on foreign key for true batch loading.
+        /// Fetches with an IN filter on the foreign key for true batch loading.
         pub struct #loader_ident {
-            client: #client_ident<Channel>,
+            fetcher: Arc<dyn #fetcher_ident>,
         }
 
         impl #loader_ident {
-            /// Create a new loader with the given gRPC client
-            pub fn new(client: #client_ident<Channel>) -> Self {
-                Self { client }
+            /// Create a new loader backed by a fetcher (gRPC client or storage)
+            pub fn new(fetcher: impl #fetcher_ident) -> Self {
+                Self { fetcher: Arc::new(fetcher) }
             }
         }
 
-        impl Loader<i64> for #loader_ident {
+        impl Loader<#key_ty> for #loader_ident {
             type Value = Vec<super::#related_ident>;
             type Error = async_graphql::Error;
 
             async fn load(
                 &self,
-                keys: &[i64],
-            ) -> Result<HashMap<i64, Self::Value>, Self::Error> {
+                keys: &[#key_ty],
+            ) -> Result<HashMap<#key_ty, Self::Value>, Self::Error> {
                 if keys.is_empty() {
                     return Ok(HashMap::new());
                 }
 
                 // Build filter with IN clause on foreign key
                 let filter = #filter_type {
-                    #fk_ident: Some(IntFilter {
+                    #fk_ident: Some(#key_filter {
                         r#in: keys.to_vec(),
                         ..Default::default()
                     }),
                     ..Default::default()
                 };
 
-                // Single List RPC call with IN filter
+                // Single fetch with IN filter
                 // Use a high limit to get all related items
-                let request = #list_request {
-                    filter: Some(filter),
-                    first: Some(1000), // High limit for batch loading
-                    ..Default::default()
-                };
-
-                let response = self.client
-                    .clone()
-                    .#list_method(request)
-                    .await
-                    .map_err(|e| async_graphql::Error::new(e.message()))?;
+                let nodes = self.fetcher.fetch(filter, 1000).await?;
 
                 // Group results by foreign key
-                let mut map: HashMap<i64, Vec<super::#related_ident>> = HashMap::new();
+                let mut map: HashMap<#key_ty, Vec<super::#related_ident>> = HashMap::new();
 
                 // Initialize empty vecs for all requested keys
-                for &key in keys {
-                    map.insert(key, Vec::new());
+                for key in keys {
+                    map.insert(key.clone(), Vec::new());
                 }
 
-                // Populate from response
-                for edge in response.into_inner().edges {
-                    if let Some(node) = edge.node {
-                        let entity = super::#related_ident::from(node);
-                        #populate_code
-                    }
+                // Populate from fetched nodes
+                for node in nodes {
+                    let entity = super::#related_ident::from(node);
+                    #populate_code
                 }
 
                 Ok(map)
@@ -232,10 +227,7 @@ fn generate_relation_loader(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Determine output file path
     let package = file.package.as_deref().unwrap_or("");
@@ -254,11 +246,13 @@ fn generate_relation_loader(
 
 /// Generate an ID-based loader file for fetching entities by their primary key
 ///
-/// Uses the List RPC with an IN filter for true batch loading (single query).
+/// Uses the service's get-many RPC (`Get{Entities}ByIds`) when it has one, the
+/// List RPC with an IN filter otherwise; either way a batch is a single query.
 /// It's used for BelongsTo relations (e.g., Post.author uses UserLoader).
 pub fn generate_entity_loader(
     file: &FileDescriptorProto,
     message: &DescriptorProto,
+    all_files: &[FileDescriptorProto],
 ) -> Result<Option<File>, GeneratorError> {
     let file_name = file.name.as_deref().unwrap_or("");
     let msg_name = message.name.as_deref().unwrap_or("");
@@ -299,6 +293,63 @@ pub fn generate_entity_loader(
     let list_method = format_ident!("list_{}", format!("{}s", type_name.to_snake_case()));
     let filter_type = format_ident!("{}Filter", type_name);
 
+    // Primary key type (i64, or String for UUID keys)
+    let key_ty = super::key_type(Some(message), "id");
+    let key_filter = super::key_filter_type(Some(message), "id");
+
+    // Fetcher trait, storage trait, and proto message names
+    let fetcher_ident = format_ident!("{}Fetcher", type_name);
+    let backend_ident = format_ident!("{}Backend", service_name);
+    let storage_trait_ident = format_ident!("{}Storage", service_name);
+    let proto_ident = format_ident!("{}", msg_name.to_upper_camel_case());
+
+    // Batch gets go through the get-many RPC when the service has one
+    let get_many = get_many_method(all_files, file.package.as_deref().unwrap_or(""), &service_name);
+    let (get_many_import, client_fetch_by_ids, backend_fetch_by_ids, storage_fetch_by_ids) = match &get_many {
+        Some((method, request, field)) => {
+            let method_ident = format_ident!("{}", method.to_snake_case());
+            let request_ident = format_ident!("{}", request);
+            let field_ident = format_ident!("{}", field);
+            let signature = quote! {
+                async fn fetch_by_ids(&self, ids: &[#key_ty]) -> Result<Vec<#proto_ident>, async_graphql::Error>
+            };
+            (
+                Some(quote! { use super::super::#request_ident; }),
+                Some(quote! {
+                    #signature {
+                        let request = #request_ident { ids: ids.to_vec() };
+                        let response = self
+                            .clone()
+                            .#method_ident(request)
+                            .await
+                            .map_err(super::status_error)?;
+                        Ok(response.into_inner().#field_ident)
+                    }
+                }),
+                Some(quote! {
+                    #signature {
+                        let request = #request_ident { ids: ids.to_vec() };
+                        let response = self
+                            .#method_ident(request)
+                            .await
+                            .map_err(super::status_error)?;
+                        Ok(response.into_inner().#field_ident)
+                    }
+                }),
+                Some(quote! {
+                    #signature {
+                        let request = #request_ident { ids: ids.to_vec() };
+                        let response = self.0.#method_ident(request).await?;
+                        Ok(response.#field_ident)
+                    }
+                }),
+            )
+        }
+        None => (None, None, None, None),
+    };
+
+    let fetcher_doc = format!("Source of {} entities for DataLoaders", type_name);
+
     let code = quote! {
         //! DataLoader for #type_name entities
         //! @generated
@@ -308,69 +359,153 @@ pub fn generate_entity_loader(
 
         use async_graphql::dataloader::Loader;
         use std::collections::HashMap;
+        use std::sync::Arc;
         use tonic::transport::Channel;
-        // Import gRPC client and types from parent module
+        // Import gRPC client, storage, and types from parent module
         use super::super::#client_module_ident::#client_ident;
-        use super::super::#list_request;
-        use super::super::#filter_type;
-        use super::super::super::synapse::relay::IntFilter;
+        use super::super::#storage_trait_ident;
+        use super::super::{#list_request, #filter_type, #proto_ident};
+        #get_many_import
+        use super::super::super::synapse::relay::#key_filter;
+        use super::{StorageFetcher, #backend_ident};
+
+        #[doc = #fetcher_doc]
+        ///
+        /// Implemented for the gRPC client and for `StorageFetcher`, so the same
+        /// loaders work in gateway and in-process (monolith) deployments.
+        #[async_trait::async_trait]
+        pub trait #fetcher_ident: Send + Sync + 'static {
+            /// Fetch up to `limit` entities matching the filter
+            ///
+            /// Loaders use an IN filter on `id` (batch get) or on a foreign
+            /// key (list by FK).
+            async fn fetch(
+                &self,
+                filter: #filter_type,
+                limit: i32,
+            ) -> Result<Vec<#proto_ident>, async_graphql::Error>;
+
+            /// Fetch entities by primary key
+            ///
+            /// Defaults to an IN filter on `id`; the service's get-many RPC
+            /// is used instead when it has one.
+            async fn fetch_by_ids(&self, ids: &[#key_ty]) -> Result<Vec<#proto_ident>, async_graphql::Error> {
+                let filter = #filter_type {
+                    id: Some(#key_filter {
+                        r#in: ids.to_vec(),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                };
+                self.fetch(filter, ids.len() as i32).await
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl #fetcher_ident for #client_ident<Channel> {
+            async fn fetch(
+                &self,
+                filter: #filter_type,
+                limit: i32,
+            ) -> Result<Vec<#proto_ident>, async_graphql::Error> {
+                let request = #list_request {
+                    filter: Some(filter),
+                    first: Some(limit),
+                    ..Default::default()
+                };
+
+                let response = self
+                    .clone()
+                    .#list_method(request)
+                    .await
+                    .map_err(super::status_error)?;
+
+                Ok(response.into_inner().edges.into_iter().filter_map(|e| e.node).collect())
+            }
+
+            #client_fetch_by_ids
+        }
+
+        #[async_trait::async_trait]
+        impl #fetcher_ident for #backend_ident {
+            async fn fetch(
+                &self,
+                filter: #filter_type,
+                limit: i32,
+            ) -> Result<Vec<#proto_ident>, async_graphql::Error> {
+                let request = #list_request {
+                    filter: Some(filter),
+                    first: Some(limit),
+                    ..Default::default()
+                };
+
+                let response = self
+                    .#list_method(request)
+                    .await
+                    .map_err(super::status_error)?;
+
+                Ok(response.into_inner().edges.into_iter().filter_map(|e| e.node).collect())
+            }
+
+            #backend_fetch_by_ids
+        }
+
+        #[async_trait::async_trait]
+        impl<S: #storage_trait_ident + 'static> #fetcher_ident for StorageFetcher<S> {
+            async fn fetch(
+                &self,
+                filter: #filter_type,
+                limit: i32,
+            ) -> Result<Vec<#proto_ident>, async_graphql::Error> {
+                let request = #list_request {
+                    filter: Some(filter),
+                    first: Some(limit),
+                    ..Default::default()
+                };
+
+                let response = self.0.#list_method(request).await?;
+
+                Ok(response.edges.into_iter().filter_map(|e| e.node).collect())
+            }
+
+            #storage_fetch_by_ids
+        }
 
         /// DataLoader for fetching #type_name entities by ID
         ///
-        /// Uses List RPC with IN filter for true batch loading (single query).
+        /// Fetches each batch with a single query through `fetch_by_ids`.
         pub struct #loader_ident {
-            client: #client_ident<Channel>,
+            fetcher: Arc<dyn #fetcher_ident>,
         }
 
         impl #loader_ident {
-            /// Create a new loader with the given gRPC client
-            pub fn new(client: #client_ident<Channel>) -> Self {
-                Self { client }
+            /// Create a new loader backed by a fetcher (gRPC client or storage)
+            pub fn new(fetcher: impl #fetcher_ident) -> Self {
+                Self { fetcher: Arc::new(fetcher) }
             }
         }
 
-        impl Loader<i64> for #loader_ident {
+        impl Loader<#key_ty> for #loader_ident {
             type Value = super::#type_ident;
             type Error = async_graphql::Error;
 
             async fn load(
                 &self,
-                keys: &[i64],
-            ) -> Result<HashMap<i64, Self::Value>, Self::Error> {
+                keys: &[#key_ty],
+            ) -> Result<HashMap<#key_ty, Self::Value>, Self::Error> {
                 if keys.is_empty() {
                     return Ok(HashMap::new());
                 }
 
-                // Build filter with IN clause for batch loading
-                let filter = #filter_type {
-                    id: Some(IntFilter {
-                        r#in: keys.to_vec(),
-                        ..Default::default()
-                    }),
-                    ..Default::default()
-                };
-
-                // Single List RPC call with IN filter
-                let request = #list_request {
-                    filter: Some(filter),
-                    first: Some(keys.len() as i32),
-                    ..Default::default()
-                };
+                // Single batched fetch by id
+                let nodes = self.fetcher.fetch_by_ids(keys).await?;
 
-                let response = self.client
-                    .clone()
-                    .#list_method(request)
-                    .await
-                    .map_err(|e| async_graphql::Error::new(e.message()))?;
-
-                // Map results by ID from connection edges
-                let mut map: HashMap<i64, Self::Value> = HashMap::new();
-                for edge in response.into_inner().edges {
-                    if let Some(node) = edge.node {
-                        let entity = super::#type_ident::from(node);
-                        // Use internal id field for mapping
-                        map.insert(entity.id, entity);
-                    }
+                // Map results by ID
+                let mut map: HashMap<#key_ty, Self::Value> = HashMap::new();
+                for node in nodes {
+                    let entity = super::#type_ident::from(node);
+                    // Use internal id field for mapping
+                    map.insert(entity.id.clone(), entity);
                 }
 
                 Ok(map)
@@ -380,10 +515,7 @@ pub fn generate_entity_loader(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Determine output file path
     let package = file.package.as_deref().unwrap_or("");
@@ -400,6 +532,48 @@ pub fn generate_entity_loader(
     }))
 }
 
+/// Get-many method of a service: RPC name, request type, and response field
+///
+/// A method is get-many by its `operation` storage option or, without one, by
+/// a `Get{Entities}ByIds` name. The rows are the response's first repeated field.
+fn get_many_method(
+    all_files: &[FileDescriptorProto],
+    package: &str,
+    service_name: &str,
+) -> Option<(String, String, String)> {
+    let file = all_files.iter().find(|f| {
+        f.package.as_deref().unwrap_or("") == package
+            && f.service.iter().any(|s| s.name.as_deref() == Some(service_name))
+    })?;
+    let file_name = file.name.as_deref().unwrap_or("");
+    let service = file.service.iter().find(|s| s.name.as_deref() == Some(service_name))?;
+
+    let method = service.method.iter().find(|m| {
+        let method_name = m.name.as_deref().unwrap_or("");
+        let operation = get_cached_rpc_method_options(file_name, service_name, method_name)
+            .filter(|o| !o.operation.is_empty())
+            .map(|o| o.operation.to_snake_case())
+            .unwrap_or_else(|| infer_operation(method_name).to_string());
+        operation == "get_many" && !m.client_streaming() && !m.server_streaming()
+    })?;
+
+    let short = |t: &str| t.rsplit('.').next().unwrap_or(t).to_string();
+    let request = short(method.input_type());
+    let response = short(method.output_type());
+    let field = all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .flat_map(|f| f.message_type.iter())
+        .find(|m| m.name.as_deref() == Some(response.as_str()))?
+        .field
+        .iter()
+        .find(|f| f.label() == Label::Repeated)?
+        .name
+        .clone()?;
+
+    Some((method.name().to_string(), request, field))
+}
+
 /// Find if a field on a message is optional (proto3_optional)
 fn find_field_optionality(
     all_files: &[FileDescriptorProto],
@@ -425,3 +599,56 @@ fn find_field_optionality(
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::synapse::storage::{RelationDef, RelationType};
+    use prost_types::field_descriptor_proto::Type;
+    use prost_types::FieldDescriptorProto;
+
+    fn files(author_id: Type) -> Vec<FileDescriptorProto> {
+        let post = DescriptorProto {
+            name: Some("Post".to_string()),
+            field: vec![FieldDescriptorProto {
+                name: Some("author_id".to_string()),
+                r#type: Some(author_id as i32),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        vec![FileDescriptorProto {
+            name: Some("blog/post.proto".to_string()),
+            package: Some("blog".to_string()),
+            message_type: vec![post],
+            ..Default::default()
+        }]
+    }
+
+    fn has_many_loader(author_id: Type) -> String {
+        let files = files(author_id);
+        let relation = RelationDef {
+            name: "posts".to_string(),
+            r#type: RelationType::HasMany as i32,
+            related: "Post".to_string(),
+            foreign_key: "author_id".to_string(),
+            ..Default::default()
+        };
+        generate_relation_loader(&files[0], "Author", &relation, &files)
+            .unwrap()
+            .unwrap()
+            .content
+            .unwrap()
+    }
+
+    #[test]
+    fn test_relation_loader_keys_follow_foreign_key_type() {
+        let code = has_many_loader(Type::String);
+        assert!(code.contains("impl Loader<String> for PostsByAuthorLoader"));
+        assert!(code.contains("relay::StringFilter"));
+
+        let code = has_many_loader(Type::Int64);
+        assert!(code.contains("impl Loader<i64> for PostsByAuthorLoader"));
+        assert!(code.contains("relay::IntFilter"));
+    }
+}
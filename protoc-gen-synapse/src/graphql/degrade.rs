@@ -0,0 +1,190 @@
+//! Partial degradation generation
+//!
+//! Relations marked `non_critical` (on the relation, or via the GraphQL field
+//! option on their foreign key) resolve to null when the related service
+//! fails, instead of failing the query. Generates graphql/degrade.rs: the
+//! `degrade` call those resolvers make, and a `DegradedWarnings` extension
+//! that lists the degraded fields under `warnings` in the response
+//! extensions, so clients can tell a missing value from a failed one.
+
+use crate::error::GeneratorError;
+use crate::options::synapse::storage::RelationDef;
+use crate::storage::seaorm::options::{get_cached_entity_options, get_cached_graphql_field_options};
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::{DescriptorProto, FileDescriptorProto};
+use quote::quote;
+
+/// Whether a relation degrades to null when its service fails
+pub fn is_non_critical(file_name: &str, message: &DescriptorProto, relation: &RelationDef) -> bool {
+    if relation.non_critical {
+        return true;
+    }
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let foreign_key = relation.foreign_key.to_snake_case();
+    message
+        .field
+        .iter()
+        .filter(|f| f.name.as_deref().map(ToSnakeCase::to_snake_case) == Some(foreign_key.clone()))
+        .any(|f| {
+            get_cached_graphql_field_options(file_name, msg_name, f.number.unwrap_or(0)).is_some_and(|o| o.non_critical)
+        })
+}
+
+/// Whether any entity of the file's package has a non-critical relation
+pub fn has_non_critical(file: &FileDescriptorProto, all_files: &[FileDescriptorProto]) -> bool {
+    let package = file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.message_type.iter().any(|message| {
+                get_cached_entity_options(file_name, message.name.as_deref().unwrap_or("")).is_some_and(|entity| {
+                    entity
+                        .relations
+                        .iter()
+                        .any(|relation| is_non_critical(file_name, message, relation))
+                })
+            })
+        })
+}
+
+/// Generate the graphql/degrade.rs file for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || file.service.is_empty() || !has_non_critical(file, all_files) {
+        return Ok(None);
+    }
+
+    let content = generate_degrade().to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/graphql/degrade.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the degrade call and the warnings extension
+fn generate_degrade() -> TokenStream {
+    quote! {
+        //! Partial degradation of non-critical relations
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(dead_code)]
+
+        use std::sync::{Arc, Mutex};
+
+        use async_graphql::extensions::{
+            Extension, ExtensionContext, ExtensionFactory, NextExecute, NextPrepareRequest,
+        };
+        use async_graphql::{Context, Request, Response, ServerResult, Value};
+
+        /// Degraded fields of one request
+        #[derive(Debug, Clone, Default)]
+        pub struct Degraded(Arc<Mutex<Vec<Value>>>);
+
+        impl Degraded {
+            fn take(&self) -> Vec<Value> {
+                std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+            }
+        }
+
+        /// Resolve a non-critical field to null after its service failed
+        ///
+        /// The failure is recorded for the `warnings` response extension when
+        /// the schema has `DegradedWarnings`.
+        pub fn degrade<T>(ctx: &Context<'_>, error: async_graphql::Error) -> Option<T> {
+            let path = ctx.path_node.map(|node| node.to_string()).unwrap_or_default();
+            if let Some(degraded) = ctx.data_opt::<Degraded>() {
+                degraded
+                    .0
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(async_graphql::value!({ "path": path, "message": error.message }));
+            }
+            None
+        }
+
+        /// Extension adding `warnings: [{ path, message }]` to responses that
+        /// had non-critical fields degraded
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct DegradedWarnings;
+
+        impl ExtensionFactory for DegradedWarnings {
+            fn create(&self) -> Arc<dyn Extension> {
+                Arc::new(DegradedWarningsExtension {
+                    degraded: Degraded::default(),
+                })
+            }
+        }
+
+        struct DegradedWarningsExtension {
+            degraded: Degraded,
+        }
+
+        #[async_trait::async_trait]
+        impl Extension for DegradedWarningsExtension {
+            async fn prepare_request(
+                &self,
+                ctx: &ExtensionContext<'_>,
+                request: Request,
+                next: NextPrepareRequest<'_>,
+            ) -> ServerResult<Request> {
+                next.run(ctx, request.data(self.degraded.clone())).await
+            }
+
+            async fn execute(
+                &self,
+                ctx: &ExtensionContext<'_>,
+                operation_name: Option<&str>,
+                next: NextExecute<'_>,
+            ) -> Response {
+                let response = next.run(ctx, operation_name).await;
+                let warnings = self.degraded.take();
+                if warnings.is_empty() {
+                    response
+                } else {
+                    response.extension("warnings", Value::List(warnings))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_degrade() {
+        let code = generate_degrade().to_string();
+        assert!(code.contains("pub fn degrade < T > (ctx : & Context < '_ > , error : async_graphql :: Error) -> Option < T >"));
+        assert!(code.contains("response . extension (\"warnings\" , Value :: List (warnings))"));
+    }
+
+    #[test]
+    fn test_relation_option_is_non_critical() {
+        let message = DescriptorProto {
+            name: Some("Post".to_string()),
+            ..Default::default()
+        };
+        let mut relation = RelationDef {
+            name: "author".to_string(),
+            foreign_key: "author_id".to_string(),
+            ..Default::default()
+        };
+        assert!(!is_non_critical("blog/entities.proto", &message, &relation));
+        relation.non_critical = true;
+        assert!(is_non_critical("blog/entities.proto", &message, &relation));
+    }
+}
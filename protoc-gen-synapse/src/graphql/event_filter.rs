@@ -0,0 +1,488 @@
+//! Subscription filters evaluated against event payloads
+//!
+//! A subscriber's `{Entity}Filter` is checked against the entity an event
+//! carries when every field of the filter compares a payload field: integer,
+//! string, bool, float and timestamp fields, and array fields. The generated
+//! `{entity}_matches` follows the storage filter semantics (null passes only
+//! `isNull`, `like` patterns, empty `in` lists match everything) and
+//! evaluates `and`, `or` and `not`. Filters that need the database, such as
+//! geography or case-insensitive columns, are checked through storage
+//! instead.
+
+use crate::storage::seaorm::implementation::case_insensitive_columns;
+use heck::ToSnakeCase;
+use proc_macro2::{Ident, TokenStream};
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+use std::collections::BTreeSet;
+
+/// Relay filter type a payload field is compared with
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Check {
+    Int,
+    String,
+    Bool,
+    Float,
+    Timestamp,
+    IntArray,
+    StringArray,
+}
+
+impl Check {
+    /// Relay filter type of the check
+    fn filter_type(self) -> Ident {
+        format_ident!(
+            "{}",
+            match self {
+                Check::Int => "IntFilter",
+                Check::String => "StringFilter",
+                Check::Bool => "BoolFilter",
+                Check::Float => "FloatFilter",
+                Check::Timestamp => "TimestampFilter",
+                Check::IntArray => "IntArrayFilter",
+                Check::StringArray => "StringArrayFilter",
+            }
+        )
+    }
+
+    /// Helper function checking a value against the filter type
+    fn helper(self) -> Ident {
+        format_ident!("{}_matches", format!("{:?}", self).to_snake_case())
+    }
+}
+
+/// A filter field compared with the payload field of the same name
+pub struct PayloadField {
+    /// Field of the GraphQL filter
+    pub field: Ident,
+    /// Filter type of the field
+    pub check: Check,
+    /// Value of the payload field `node`, as the helper of `check` takes it
+    pub value: TokenStream,
+}
+
+/// An entity filter that can be evaluated against event payloads
+pub struct PayloadFilter {
+    /// Compared fields, in filter order
+    pub fields: Vec<PayloadField>,
+    /// Whether the filter has `or`
+    pub or: bool,
+    /// Whether the filter has `not`
+    pub not: bool,
+}
+
+/// The payload checks of `{type_name}Filter`, if every field has one
+///
+/// The filter must have `and`, which subscriptions rely on.
+pub fn payload_filter(
+    all_files: &[FileDescriptorProto],
+    entity: &DescriptorProto,
+    type_name: &str,
+) -> Option<PayloadFilter> {
+    let filter_name = format!("{}Filter", type_name);
+    let filter = all_files
+        .iter()
+        .flat_map(|f| f.message_type.iter())
+        .find(|m| m.name.as_deref() == Some(filter_name.as_str()))?;
+    let entity_name = entity.name.as_deref().unwrap_or("");
+    let case_insensitive = case_insensitive_columns(all_files, entity_name);
+
+    let mut result = PayloadFilter { fields: Vec::new(), or: false, not: false };
+    for field in &filter.field {
+        let name = field.name.as_deref().unwrap_or("");
+        match name {
+            "and" => continue,
+            "or" => {
+                result.or = true;
+                continue;
+            }
+            "not" => {
+                result.not = true;
+                continue;
+            }
+            _ => {}
+        }
+        let filter_type = field.type_name.as_deref()?.rsplit('.').next()?;
+        let column = entity.field.iter().find(|f| f.name.as_deref() == Some(name))?;
+        let check = match filter_type {
+            "IntFilter" | "Int64Filter" | "Int32Filter" => Check::Int,
+            "StringFilter" if case_insensitive.iter().any(|(c, _)| c == name) => return None,
+            "StringFilter" => Check::String,
+            "BoolFilter" => Check::Bool,
+            "FloatFilter" => Check::Float,
+            "TimestampFilter" => Check::Timestamp,
+            "IntArrayFilter" => Check::IntArray,
+            "StringArrayFilter" => Check::StringArray,
+            _ => return None,
+        };
+        let value = payload_value(column, check)?;
+        result.fields.push(PayloadField {
+            field: format_ident!("{}", name.to_snake_case()),
+            check,
+            value,
+        });
+    }
+    Some(result)
+}
+
+/// Value of the payload field for a check, or None if the types disagree
+fn payload_value(column: &FieldDescriptorProto, check: Check) -> Option<TokenStream> {
+    let ident = field_ident(column);
+    let repeated = column.label() == Label::Repeated;
+    let optional = column.proto3_optional.unwrap_or(false);
+    let ty = column.r#type();
+    let value = match (check, ty) {
+        (Check::IntArray | Check::StringArray, _) if !repeated => return None,
+        (_, _) if repeated && !matches!(check, Check::IntArray | Check::StringArray) => return None,
+        (Check::Int, Type::Int64 | Type::Sint64 | Type::Sfixed64) => {
+            if optional {
+                quote! { node.#ident }
+            } else {
+                quote! { Some(node.#ident) }
+            }
+        }
+        (Check::Int, Type::Int32 | Type::Sint32 | Type::Sfixed32 | Type::Uint32 | Type::Fixed32 | Type::Enum) => {
+            if optional {
+                quote! { node.#ident.map(i64::from) }
+            } else {
+                quote! { Some(i64::from(node.#ident)) }
+            }
+        }
+        (Check::Int, Type::Uint64 | Type::Fixed64) => {
+            if optional {
+                quote! { node.#ident.map(|v| v as i64) }
+            } else {
+                quote! { Some(node.#ident as i64) }
+            }
+        }
+        (Check::String, Type::String) => {
+            if optional {
+                quote! { node.#ident.as_deref() }
+            } else {
+                quote! { Some(node.#ident.as_str()) }
+            }
+        }
+        (Check::Bool, Type::Bool) => {
+            if optional {
+                quote! { node.#ident }
+            } else {
+                quote! { Some(node.#ident) }
+            }
+        }
+        (Check::Float, Type::Double) => {
+            if optional {
+                quote! { node.#ident }
+            } else {
+                quote! { Some(node.#ident) }
+            }
+        }
+        (Check::Float, Type::Float) => {
+            if optional {
+                quote! { node.#ident.map(f64::from) }
+            } else {
+                quote! { Some(f64::from(node.#ident)) }
+            }
+        }
+        (Check::Timestamp, Type::Message) if column.type_name() == ".google.protobuf.Timestamp" => {
+            quote! { node.#ident.as_ref().map(|t| (t.seconds, t.nanos)) }
+        }
+        (Check::IntArray, Type::Int64 | Type::Sint64 | Type::Sfixed64) => quote! { &node.#ident },
+        (Check::IntArray, Type::Int32 | Type::Sint32 | Type::Sfixed32) => {
+            quote! { &node.#ident.iter().copied().map(i64::from).collect::<Vec<_>>() }
+        }
+        (Check::StringArray, Type::String) => quote! { &node.#ident },
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Rust identifier prost uses for a field (raw for keywords like `type`)
+fn field_ident(field: &FieldDescriptorProto) -> Ident {
+    let name = field.name.as_deref().unwrap_or("").to_snake_case();
+    if syn::parse_str::<Ident>(&name).is_ok() {
+        format_ident!("{}", name)
+    } else {
+        format_ident!("r#{}", name)
+    }
+}
+
+/// Name of an entity's payload matcher, `{entity}_matches`
+pub fn matcher_ident(type_name: &str) -> Ident {
+    format_ident!("{}_matches", type_name.to_snake_case())
+}
+
+/// Generate `{entity}_matches(filter, node)` for an entity's payload filter
+pub fn generate_matcher(type_name: &str, proto_ident: &Ident, filter: &PayloadFilter) -> TokenStream {
+    let matcher = matcher_ident(type_name);
+    let filter_ident = format_ident!("{}Filter", type_name);
+    let doc = format!("Whether a {} event's payload matches `filter`", type_name);
+    let mut conditions: Vec<TokenStream> = filter
+        .fields
+        .iter()
+        .map(|PayloadField { field, check, value }| {
+            let helper = check.helper();
+            quote! { filter.#field.as_ref().is_none_or(|f| #helper(f, #value)) }
+        })
+        .collect();
+    conditions.push(quote! {
+        filter.and.as_ref().is_none_or(|filters| filters.iter().all(|f| #matcher(f, node)))
+    });
+    if filter.or {
+        conditions.push(quote! {
+            filter.or.as_ref().is_none_or(|filters| filters.is_empty() || filters.iter().any(|f| #matcher(f, node)))
+        });
+    }
+    if filter.not {
+        conditions.push(quote! {
+            filter.not.as_ref().is_none_or(|f| !#matcher(f, node))
+        });
+    }
+    quote! {
+        #[doc = #doc]
+        fn #matcher(filter: &super::#filter_ident, node: &super::super::#proto_ident) -> bool {
+            #(#conditions)&&*
+        }
+    }
+}
+
+/// Generate the value checks used by `filters`, with their filter type imports
+pub fn generate_helpers<'a>(filters: impl IntoIterator<Item = &'a PayloadFilter>) -> TokenStream {
+    let checks: BTreeSet<Check> = filters
+        .into_iter()
+        .flat_map(|f| f.fields.iter().map(|field| field.check))
+        .collect();
+    if checks.is_empty() {
+        return quote! {};
+    }
+    let filter_types = checks.iter().map(|c| c.filter_type());
+    let helpers = checks.iter().map(|check| match check {
+        Check::Int => ordered_helper(*check, quote! { i64 }, quote! { v }, true),
+        Check::Timestamp => ordered_helper(*check, quote! { (i64, i32) }, quote! { (v, 0) }, false),
+        Check::Float => quote! {
+            /// Whether a value passes `FloatFilter`; null passes no comparison
+            fn float_matches(f: &FloatFilter, value: Option<f64>) -> bool {
+                let Some(value) = value else {
+                    return f.eq.is_none() && f.ne.is_none() && f.gt.is_none() && f.gte.is_none() && f.lt.is_none() && f.lte.is_none();
+                };
+                f.eq.is_none_or(|v| value == v)
+                    && f.ne.is_none_or(|v| value != v)
+                    && f.gt.is_none_or(|v| value > v)
+                    && f.gte.is_none_or(|v| value >= v)
+                    && f.lt.is_none_or(|v| value < v)
+                    && f.lte.is_none_or(|v| value <= v)
+            }
+        },
+        Check::Bool => quote! {
+            /// Whether a value passes `BoolFilter`; null only passes `isNull`
+            fn bool_matches(f: &BoolFilter, value: Option<bool>) -> bool {
+                match value {
+                    Some(value) => f.is_null != Some(true) && f.eq.is_none_or(|v| value == v),
+                    None => f.is_null != Some(false) && f.eq.is_none(),
+                }
+            }
+        },
+        Check::String => quote! {
+            /// Whether a value passes `StringFilter`; null only passes `isNull`
+            fn string_matches(f: &StringFilter, value: Option<&str>) -> bool {
+                let Some(value) = value else {
+                    return f.is_null != Some(false)
+                        && f.eq.is_none() && f.neq.is_none()
+                        && f.gt.is_none() && f.gte.is_none() && f.lt.is_none() && f.lte.is_none()
+                        && f.r#in.as_ref().is_none_or(|values| values.is_empty())
+                        && f.like.is_none() && f.ilike.is_none()
+                        && f.starts_with.is_none() && f.ends_with.is_none() && f.contains.is_none();
+                };
+                f.is_null != Some(true)
+                    && f.eq.as_deref().is_none_or(|v| value == v)
+                    && f.neq.as_deref().is_none_or(|v| value != v)
+                    && f.gt.as_deref().is_none_or(|v| value > v)
+                    && f.gte.as_deref().is_none_or(|v| value >= v)
+                    && f.lt.as_deref().is_none_or(|v| value < v)
+                    && f.lte.as_deref().is_none_or(|v| value <= v)
+                    && f.r#in.as_ref().is_none_or(|values| values.is_empty() || values.iter().any(|v| v == value))
+                    && f.like.as_deref().is_none_or(|pattern| like(pattern, value))
+                    && f.ilike.as_deref().is_none_or(|pattern| like(&pattern.to_lowercase(), &value.to_lowercase()))
+                    && f.starts_with.as_deref().is_none_or(|v| value.starts_with(v))
+                    && f.ends_with.as_deref().is_none_or(|v| value.ends_with(v))
+                    && f.contains.as_deref().is_none_or(|v| value.contains(v))
+            }
+
+            /// SQL `LIKE`: `%` matches any run of characters, `_` one character, `\` escapes
+            fn like(pattern: &str, value: &str) -> bool {
+                let pattern: Vec<char> = pattern.chars().collect();
+                let value: Vec<char> = value.chars().collect();
+                let (mut p, mut v) = (0, 0);
+                // Pattern position after the last `%`, and the value position it resumes from
+                let mut resume: Option<(usize, usize)> = None;
+                while v < value.len() {
+                    match pattern.get(p) {
+                        Some('%') => {
+                            p += 1;
+                            resume = Some((p, v));
+                        }
+                        Some('\\') if pattern.get(p + 1) == Some(&value[v]) => {
+                            p += 2;
+                            v += 1;
+                        }
+                        Some('_') => {
+                            p += 1;
+                            v += 1;
+                        }
+                        Some(c) if *c != '\\' && *c == value[v] => {
+                            p += 1;
+                            v += 1;
+                        }
+                        _ => match resume {
+                            Some((rp, rv)) => {
+                                p = rp;
+                                v = rv + 1;
+                                resume = Some((rp, rv + 1));
+                            }
+                            None => return false,
+                        },
+                    }
+                }
+                pattern[p..].iter().all(|c| *c == '%')
+            }
+        },
+        Check::IntArray => array_helper(*check, quote! { i64 }),
+        Check::StringArray => array_helper(*check, quote! { String }),
+    });
+
+    quote! {
+        use super::super::super::synapse::relay::graphql::{#(#filter_types),*};
+
+        #(#helpers)*
+    }
+}
+
+/// Generate the check of an ordered value: eq/neq/gt/gte/lt/lte, `in`, `isNull`
+///
+/// `bound` turns a filter value `v` into the value's type.
+fn ordered_helper(check: Check, value_type: TokenStream, bound: TokenStream, has_in: bool) -> TokenStream {
+    let helper = check.helper();
+    let filter_type = check.filter_type();
+    let (in_null, in_check) = if has_in {
+        (
+            quote! { && f.r#in.as_ref().is_none_or(|values| values.is_empty()) },
+            quote! { && f.r#in.as_ref().is_none_or(|values| values.is_empty() || values.iter().any(|&v| value == #bound)) },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+    let doc = format!("Whether a value passes `{}`; null only passes `isNull`", filter_type);
+    quote! {
+        #[doc = #doc]
+        fn #helper(f: &#filter_type, value: Option<#value_type>) -> bool {
+            let Some(value) = value else {
+                return f.is_null != Some(false)
+                    && f.eq.is_none() && f.neq.is_none()
+                    && f.gt.is_none() && f.gte.is_none() && f.lt.is_none() && f.lte.is_none()
+                    #in_null;
+            };
+            f.is_null != Some(true)
+                && f.eq.is_none_or(|v| value == #bound)
+                && f.neq.is_none_or(|v| value != #bound)
+                && f.gt.is_none_or(|v| value > #bound)
+                && f.gte.is_none_or(|v| value >= #bound)
+                && f.lt.is_none_or(|v| value < #bound)
+                && f.lte.is_none_or(|v| value <= #bound)
+                #in_check
+        }
+    }
+}
+
+/// Generate the check of an array: `has`, `hasAny`, `hasAll`
+fn array_helper(check: Check, element: TokenStream) -> TokenStream {
+    let helper = check.helper();
+    let filter_type = check.filter_type();
+    let doc = format!("Whether an array passes `{}`", filter_type);
+    quote! {
+        #[doc = #doc]
+        fn #helper(f: &#filter_type, values: &[#element]) -> bool {
+            f.has.as_ref().is_none_or(|v| values.contains(v))
+                && f.has_any.as_ref().is_none_or(|any| any.is_empty() || any.iter().any(|v| values.contains(v)))
+                && f.has_all.as_ref().is_none_or(|all| all.iter().all(|v| values.contains(v)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{field, message_field};
+
+
+    fn files(filter_fields: Vec<FieldDescriptorProto>) -> (Vec<FileDescriptorProto>, DescriptorProto) {
+        let post = DescriptorProto {
+            name: Some("Post".to_string()),
+            field: vec![
+                field("id", 1, Type::Int64),
+                field("title", 2, Type::String),
+                field("published", 3, Type::Bool),
+                message_field("location", 4, ".synapse.storage.GeoPoint"),
+                message_field("created_at", 5, ".google.protobuf.Timestamp"),
+            ],
+            ..Default::default()
+        };
+        let filter = DescriptorProto {
+            name: Some("PostFilter".to_string()),
+            field: filter_fields,
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("blog/post.proto".to_string()),
+            package: Some("blog".to_string()),
+            message_type: vec![post.clone(), filter],
+            ..Default::default()
+        };
+        (vec![file], post)
+    }
+
+    #[test]
+    fn test_payload_filter() {
+        let (all_files, post) = files(vec![
+            message_field("id", 1, ".synapse.relay.IntFilter"),
+            message_field("title", 2, ".synapse.relay.StringFilter"),
+            message_field("created_at", 3, ".synapse.relay.TimestampFilter"),
+            message_field("and", 4, ".blog.PostFilter"),
+            message_field("not", 5, ".blog.PostFilter"),
+        ]);
+        let filter = payload_filter(&all_files, &post, "Post").unwrap();
+        assert!(!filter.or);
+        assert!(filter.not);
+        let checks: Vec<_> = filter.fields.iter().map(|f| f.check).collect();
+        assert_eq!(checks, vec![Check::Int, Check::String, Check::Timestamp]);
+        assert_eq!(filter.fields[1].value.to_string(), "Some (node . title . as_str ())");
+        assert_eq!(
+            filter.fields[2].value.to_string(),
+            "node . created_at . as_ref () . map (| t | (t . seconds , t . nanos))"
+        );
+
+        let code = generate_matcher("Post", &format_ident!("Post"), &filter).to_string();
+        assert!(code.contains("fn post_matches (filter : & super :: PostFilter , node : & super :: super :: Post) -> bool"));
+        assert!(code.contains("filter . id . as_ref () . is_none_or (| f | int_matches (f , Some (node . id)))"));
+        assert!(code.contains("filter . not . as_ref () . is_none_or (| f | ! post_matches (f , node))"));
+        assert!(!code.contains("filter . or"));
+
+        let helpers = generate_helpers([&filter]).to_string();
+        assert!(helpers.contains("graphql :: { IntFilter , StringFilter , TimestampFilter }"));
+        assert!(helpers.contains("fn like (pattern : & str , value : & str) -> bool"));
+        assert!(helpers.contains("value > (v , 0)"));
+        assert!(!helpers.contains("fn bool_matches"));
+    }
+
+    #[test]
+    fn test_payload_filter_needs_storage() {
+        let (all_files, post) = files(vec![
+            message_field("id", 1, ".synapse.relay.IntFilter"),
+            message_field("location", 2, ".synapse.relay.GeoPointFilter"),
+        ]);
+        assert!(payload_filter(&all_files, &post, "Post").is_none());
+
+        // The filter field type must match the payload field
+        let (all_files, post) = files(vec![message_field("published", 1, ".synapse.relay.IntFilter")]);
+        assert!(payload_filter(&all_files, &post, "Post").is_none());
+    }
+}
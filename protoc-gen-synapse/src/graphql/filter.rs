@@ -2,14 +2,17 @@
 //!
 //! Generates:
 //! - Primitive filter types (IntFilter, StringFilter, BoolFilter)
+//! - Array filter types for Postgres array columns (StringArrayFilter, IntArrayFilter)
+//! - Geography point types for PostGIS columns (GeoPoint, GeoPointInput, GeoPointFilter)
 //! - Entity-specific filter types (UserFilter, PostFilter, etc.)
 //! - Entity-specific order by types (UserOrderBy, PostOrderBy, etc.)
 //! - OrderDirection enum
 
 use crate::error::GeneratorError;
+use crate::storage::seaorm::implementation::GEO_POINT_TYPE;
 use heck::{ToSnakeCase, ToUpperCamelCase};
 use prost_types::compiler::code_generator_response::File;
-use prost_types::field_descriptor_proto::Type;
+use prost_types::field_descriptor_proto::{Label, Type};
 use prost_types::{DescriptorProto, FileDescriptorProto};
 use quote::{format_ident, quote};
 
@@ -33,6 +36,10 @@ pub fn generate_filters_for_package(
     files.push(generate_bool_filter(file)?);
     files.push(generate_float_filter(file)?);
     files.push(generate_timestamp_filter(file)?);
+    files.push(generate_array_filter("StringArrayFilter", quote! { String })?);
+    files.push(generate_array_filter("IntArrayFilter", quote! { i64 })?);
+    files.push(generate_geo_point()?);
+    files.push(generate_geo_point_filter()?);
 
     // Always generate OrderDirection enum
     files.push(generate_order_direction(file)?);
@@ -49,6 +56,7 @@ pub fn generate_filters_for_package(
 
 /// Generate IntFilter type (in shared synapse/relay/graphql location)
 fn generate_int_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorError> {
+    let serde_derives = super::input_serde_derives("camelCase");
     let code = quote! {
         //! Auto-generated IntFilter type
         //! @generated
@@ -58,6 +66,7 @@ fn generate_int_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorErr
         use async_graphql::InputObject;
 
         /// Filter for integer fields
+        #serde_derives
         #[derive(InputObject, Default, Clone)]
         pub struct IntFilter {
             /// Equals
@@ -97,10 +106,7 @@ fn generate_int_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorErr
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output to shared location: synapse/relay/graphql/
     let output_path = "synapse/relay/graphql/int_filter.rs".to_string();
@@ -114,6 +120,7 @@ fn generate_int_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorErr
 
 /// Generate StringFilter type (in shared synapse/relay/graphql location)
 fn generate_string_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorError> {
+    let serde_derives = super::input_serde_derives("camelCase");
     let code = quote! {
         //! Auto-generated StringFilter type
         //! @generated
@@ -123,6 +130,7 @@ fn generate_string_filter(_file: &FileDescriptorProto) -> Result<File, Generator
         use async_graphql::InputObject;
 
         /// Filter for string fields
+        #serde_derives
         #[derive(InputObject, Default, Clone)]
         pub struct StringFilter {
             /// Equals
@@ -177,10 +185,7 @@ fn generate_string_filter(_file: &FileDescriptorProto) -> Result<File, Generator
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output to shared location: synapse/relay/graphql/
     let output_path = "synapse/relay/graphql/string_filter.rs".to_string();
@@ -194,6 +199,7 @@ fn generate_string_filter(_file: &FileDescriptorProto) -> Result<File, Generator
 
 /// Generate BoolFilter type (in shared synapse/relay/graphql location)
 fn generate_bool_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorError> {
+    let serde_derives = super::input_serde_derives("camelCase");
     let code = quote! {
         //! Auto-generated BoolFilter type
         //! @generated
@@ -203,6 +209,7 @@ fn generate_bool_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorEr
         use async_graphql::InputObject;
 
         /// Filter for boolean fields
+        #serde_derives
         #[derive(InputObject, Default, Clone)]
         pub struct BoolFilter {
             /// Equals
@@ -223,10 +230,7 @@ fn generate_bool_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorEr
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output to shared location: synapse/relay/graphql/
     let output_path = "synapse/relay/graphql/bool_filter.rs".to_string();
@@ -240,6 +244,7 @@ fn generate_bool_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorEr
 
 /// Generate FloatFilter type (in shared synapse/relay/graphql location)
 fn generate_float_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorError> {
+    let serde_derives = super::input_serde_derives("camelCase");
     let code = quote! {
         //! Auto-generated FloatFilter type
         //! @generated
@@ -249,6 +254,7 @@ fn generate_float_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorE
         use async_graphql::InputObject;
 
         /// Filter for float/double fields
+        #serde_derives
         #[derive(InputObject, Default, Clone)]
         pub struct FloatFilter {
             /// Equals
@@ -267,10 +273,7 @@ fn generate_float_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorE
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output to shared location: synapse/relay/graphql/
     let output_path = "synapse/relay/graphql/float_filter.rs".to_string();
@@ -282,8 +285,161 @@ fn generate_float_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorE
     })
 }
 
+/// Generate an array filter type (in shared synapse/relay/graphql location)
+fn generate_array_filter(name: &str, element: proc_macro2::TokenStream) -> Result<File, GeneratorError> {
+    let filter_ident = format_ident!("{}", name);
+    let module_doc = format!("Auto-generated {} type", name);
+
+    let serde_derives = super::input_serde_derives("camelCase");
+    let code = quote! {
+        #![doc = #module_doc]
+        //! @generated
+
+        #![allow(missing_docs)]
+
+        use async_graphql::InputObject;
+
+        /// Filter for array columns
+        #serde_derives
+        #[derive(InputObject, Default, Clone)]
+        pub struct #filter_ident {
+            /// Array contains the value
+            pub has: Option<#element>,
+            /// Array contains any of the values
+            pub has_any: Option<Vec<#element>>,
+            /// Array contains all of the values
+            pub has_all: Option<Vec<#element>>,
+        }
+
+        // Convert to proto type
+        impl From<#filter_ident> for super::super::#filter_ident {
+            fn from(f: #filter_ident) -> Self {
+                Self {
+                    has: f.has,
+                    has_any: f.has_any.unwrap_or_default(),
+                    has_all: f.has_all.unwrap_or_default(),
+                }
+            }
+        }
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    // Output to shared location: synapse/relay/graphql/
+    let output_path = format!("synapse/relay/graphql/{}.rs", name.to_snake_case());
+
+    Ok(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    })
+}
+
+/// Generate GeoPoint output and input types (in shared synapse/relay/graphql location)
+fn generate_geo_point() -> Result<File, GeneratorError> {
+    let serde_derives = super::input_serde_derives("camelCase");
+    let code = quote! {
+        //! Auto-generated GeoPoint types
+        //! @generated
+
+        #![allow(missing_docs)]
+
+        use async_graphql::{InputObject, SimpleObject};
+
+        /// WGS 84 coordinate
+        #[derive(SimpleObject, Clone, Copy, Debug, Default, PartialEq)]
+        pub struct GeoPoint {
+            pub latitude: f64,
+            pub longitude: f64,
+        }
+
+        /// WGS 84 coordinate
+        #serde_derives
+        #[derive(InputObject, Clone, Copy, Debug, Default, PartialEq)]
+        pub struct GeoPointInput {
+            #[graphql(validator(minimum = -90.0, maximum = 90.0))]
+            pub latitude: f64,
+            #[graphql(validator(minimum = -180.0, maximum = 180.0))]
+            pub longitude: f64,
+        }
+
+        impl From<super::super::GeoPoint> for GeoPoint {
+            fn from(p: super::super::GeoPoint) -> Self {
+                Self { latitude: p.latitude, longitude: p.longitude }
+            }
+        }
+
+        impl From<GeoPointInput> for super::super::GeoPoint {
+            fn from(p: GeoPointInput) -> Self {
+                Self { latitude: p.latitude, longitude: p.longitude }
+            }
+        }
+    };
+
+    Ok(File {
+        name: Some("synapse/relay/graphql/geo_point.rs".to_string()),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    })
+}
+
+/// Generate GeoPointFilter type (in shared synapse/relay/graphql location)
+fn generate_geo_point_filter() -> Result<File, GeneratorError> {
+    let serde_derives = super::input_serde_derives("camelCase");
+    let code = quote! {
+        //! Auto-generated GeoPointFilter type
+        //! @generated
+
+        #![allow(missing_docs)]
+
+        use async_graphql::InputObject;
+        use super::geo_point::GeoPointInput;
+
+        /// Circle on the earth's surface
+        #serde_derives
+        #[derive(InputObject, Clone, Default)]
+        pub struct GeoRadiusInput {
+            pub center: GeoPointInput,
+            /// Radius in meters
+            #[graphql(validator(minimum = 0.0))]
+            pub meters: f64,
+        }
+
+        /// Filter for geography point fields
+        #serde_derives
+        #[derive(InputObject, Default, Clone)]
+        pub struct GeoPointFilter {
+            /// Within the radius of the center point
+            pub within_radius: Option<GeoRadiusInput>,
+            /// Is null check
+            pub is_null: Option<bool>,
+        }
+
+        // Convert to proto type
+        impl From<GeoPointFilter> for super::super::GeoPointFilter {
+            fn from(f: GeoPointFilter) -> Self {
+                Self {
+                    within_radius: f.within_radius.map(|r| super::super::GeoRadius {
+                        center: Some(r.center.into()),
+                        meters: r.meters,
+                    }),
+                    is_null: f.is_null,
+                }
+            }
+        }
+    };
+
+    Ok(File {
+        name: Some("synapse/relay/graphql/geo_point_filter.rs".to_string()),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    })
+}
+
 /// Generate TimestampFilter type (in shared synapse/relay/graphql location)
 fn generate_timestamp_filter(_file: &FileDescriptorProto) -> Result<File, GeneratorError> {
+    let serde_derives = super::input_serde_derives("camelCase");
     let code = quote! {
         //! Auto-generated TimestampFilter type
         //! @generated
@@ -293,6 +449,7 @@ fn generate_timestamp_filter(_file: &FileDescriptorProto) -> Result<File, Genera
         use async_graphql::InputObject;
 
         /// Filter for timestamp fields (using seconds since Unix epoch)
+        #serde_derives
         #[derive(InputObject, Default, Clone)]
         pub struct TimestampFilter {
             /// Equals (seconds since Unix epoch)
@@ -328,10 +485,7 @@ fn generate_timestamp_filter(_file: &FileDescriptorProto) -> Result<File, Genera
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output to shared location: synapse/relay/graphql/
     let output_path = "synapse/relay/graphql/timestamp_filter.rs".to_string();
@@ -345,6 +499,7 @@ fn generate_timestamp_filter(_file: &FileDescriptorProto) -> Result<File, Genera
 
 /// Generate OrderDirection enum (in shared synapse/relay/graphql location)
 fn generate_order_direction(_file: &FileDescriptorProto) -> Result<File, GeneratorError> {
+    let serde_derives = super::input_serde_derives("UPPERCASE");
     let code = quote! {
         //! Auto-generated OrderDirection enum
         //! @generated
@@ -354,6 +509,7 @@ fn generate_order_direction(_file: &FileDescriptorProto) -> Result<File, Generat
         use async_graphql::Enum;
 
         /// Order direction for sorting
+        #serde_derives
         #[derive(Enum, Clone, Copy, PartialEq, Eq, Default)]
         pub enum OrderDirection {
             #[default]
@@ -384,10 +540,7 @@ fn generate_order_direction(_file: &FileDescriptorProto) -> Result<File, Generat
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output to shared location: synapse/relay/graphql/
     let output_path = "synapse/relay/graphql/order_direction.rs".to_string();
@@ -427,6 +580,20 @@ fn generate_entity_filter(
         entity.field.iter().collect()
     };
 
+    // Message (and its file) whose field options name the filter's fields
+    let names_msg = if proto_filter.is_some() { filter_name.as_str() } else { entity_name };
+    let names_file = all_files
+        .iter()
+        .find(|f| f.message_type.iter().any(|m| m.name.as_deref() == Some(names_msg)))
+        .or(Some(file))
+        .and_then(|f| f.name.as_deref())
+        .unwrap_or("");
+
+    // Array and geo filters are only imported when used, so packages without
+    // those columns keep compiling against older synapse::relay::graphql modules
+    let mut uses_array_filters = false;
+    let mut uses_geo_filter = false;
+
     // Track if we need logical operators (and, or, not)
     let mut has_and = false;
     let mut has_or = false;
@@ -466,12 +633,30 @@ fn generate_entity_filter(
             // Proto Filter field - get the referenced type name
             field.type_name.as_ref().and_then(|type_name| {
                 let simple_name = type_name.rsplit('.').next().unwrap_or(type_name);
+                if simple_name.ends_with("ArrayFilter") {
+                    uses_array_filters = true;
+                }
+                if simple_name == "GeoPointFilter" {
+                    uses_geo_filter = true;
+                }
                 let type_ident = format_ident!("{}", simple_name);
                 Some(quote! { #type_ident })
             })
         } else {
             // Entity field - derive filter type from primitive type
+            // (repeated fields only as array columns)
+            let repeated = field.label() == Label::Repeated;
             match field.r#type() {
+                Type::String if repeated => {
+                    uses_array_filters = true;
+                    Some(quote! { StringArrayFilter })
+                }
+                Type::Int64 | Type::Int32 | Type::Sint32 | Type::Sint64
+                | Type::Sfixed32 | Type::Sfixed64 if repeated => {
+                    uses_array_filters = true;
+                    Some(quote! { IntArrayFilter })
+                }
+                _ if repeated => None,
                 Type::Int64 | Type::Int32 | Type::Uint64 | Type::Uint32
                 | Type::Sint32 | Type::Sint64 | Type::Fixed32 | Type::Fixed64
                 | Type::Sfixed32 | Type::Sfixed64 => {
@@ -486,12 +671,18 @@ fn generate_entity_filter(
                 Type::Float | Type::Double => {
                     Some(quote! { FloatFilter })
                 }
+                Type::Message if field.type_name.as_deref() == Some(GEO_POINT_TYPE) => {
+                    uses_geo_filter = true;
+                    Some(quote! { GeoPointFilter })
+                }
                 _ => None,
             }
         };
 
         if let Some(filter_ty) = filter_type {
+            let explicit_name = super::explicit_input_name(names_file, names_msg, field);
             field_tokens.push(quote! {
+                #explicit_name
                 pub #field_ident: Option<#filter_ty>,
             });
             conversion_tokens.push(quote! {
@@ -529,6 +720,18 @@ fn generate_entity_filter(
         });
     }
 
+    let array_imports = if uses_array_filters {
+        quote! { use super::super::super::synapse::relay::graphql::{StringArrayFilter, IntArrayFilter}; }
+    } else {
+        quote! {}
+    };
+    let geo_imports = if uses_geo_filter {
+        quote! { use super::super::super::synapse::relay::graphql::GeoPointFilter; }
+    } else {
+        quote! {}
+    };
+
+    let serde_derives = super::input_serde_derives("camelCase");
     let code = quote! {
         //! Auto-generated filter type for entity
         //! @generated
@@ -538,8 +741,11 @@ fn generate_entity_filter(
         use async_graphql::InputObject;
         // Import shared filter types from synapse::relay::graphql
         use super::super::super::synapse::relay::graphql::{IntFilter, StringFilter, BoolFilter, TimestampFilter};
+        #array_imports
+        #geo_imports
 
         /// Filter for entity queries
+        #serde_derives
         #[derive(InputObject, Default, Clone)]
         pub struct #filter_ident {
             #(#field_tokens)*
@@ -555,10 +761,7 @@ fn generate_entity_filter(
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     let package = file.package.as_deref().unwrap_or("");
     let output_path = format!(
@@ -636,6 +839,7 @@ fn generate_entity_order_by(
         }
     }
 
+    let serde_derives = super::input_serde_derives("camelCase");
     let code = quote! {
         //! Auto-generated order by type for entity
         //! @generated
@@ -647,6 +851,7 @@ fn generate_entity_order_by(
         use super::super::super::synapse::relay::graphql::OrderDirection;
 
         /// Order by options for entity queries
+        #serde_derives
         #[derive(InputObject, Default, Clone)]
         pub struct #order_by_ident {
             #(#field_tokens)*
@@ -662,10 +867,7 @@ fn generate_entity_order_by(
     };
 
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     let package = file.package.as_deref().unwrap_or("");
     let output_path = format!(
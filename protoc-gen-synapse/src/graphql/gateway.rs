@@ -0,0 +1,1400 @@
+//! Gateway HTTP wiring generation
+//!
+//! Generates the graphql/gateway.rs module that hardens the axum router
+//! serving the GraphQL schema. It creates:
+//! - GatewayConfig with HTTP limits (body size, request timeout, concurrency)
+//! - CorsConfig for allowed origins, headers, and credentials
+//! - CsrfConfig for double-submit token checks on cookie-authenticated requests
+//! - GatewayConfig::from_env for deployment-time overrides
+//! - GatewayConfig::apply to wrap a router in the matching tower layers
+//! - Http2Config and GatewayConfig::serve for HTTP/2 keepalive and stream limits
+//! - Problem, an RFC 7807 error body mapped from tonic::Status for REST routes
+//!   and used for the gateway's own rejections
+//! - UpstreamConfig for the channels to upstream gRPC services, with optional
+//!   fail-fast warm-up and DNS re-resolution balanced over every address
+//! - execute_graphql, turning the schema's cache hints into `Cache-Control`
+//! - graphql_subscription, the websocket handler of GraphQL subscriptions
+
+use crate::error::GeneratorError;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::quote;
+
+/// Default maximum request body size (1 MiB)
+const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Default request timeout in seconds
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 30;
+
+/// Default maximum number of in-flight requests
+const DEFAULT_CONCURRENCY_LIMIT: usize = 512;
+
+/// Default cookie holding the CSRF token
+const DEFAULT_CSRF_COOKIE: &str = "csrf_token";
+
+/// Default header echoing the CSRF token
+const DEFAULT_CSRF_HEADER: &str = "x-csrf-token";
+
+/// Default HTTP/2 keepalive ping interval in seconds
+const DEFAULT_HTTP2_KEEPALIVE_SECS: u64 = 20;
+
+/// Default time to wait for a keepalive ping acknowledgement in seconds
+const DEFAULT_HTTP2_KEEPALIVE_TIMEOUT_SECS: u64 = 20;
+
+/// Default maximum concurrent HTTP/2 streams per connection
+const DEFAULT_HTTP2_MAX_STREAMS: u32 = 256;
+
+/// Default upstream connect timeout in seconds
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 5;
+
+/// Generate the graphql/gateway.rs file for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || file.service.is_empty() {
+        return Ok(None);
+    }
+
+    let config = generate_config();
+    let http2 = generate_http2();
+    let cors = generate_cors();
+    let csrf = generate_csrf();
+    let apply = generate_apply();
+    let problem = generate_problem();
+    let upstream = generate_upstream(crate::params::rustls());
+    let federation = !super::federation::collect(file, all_files).is_empty();
+    let cache_control = generate_cache_control(federation);
+    let subscription = (!super::subscription::collect(file, all_files).is_empty())
+        .then(|| generate_subscription(crate::grpc::package_has_request_context(all_files, file)));
+
+    let code = quote! {
+        //! Gateway HTTP wiring
+        //!
+        //! Applies request limits, compression, CORS, and CSRF protection to the
+        //! axum router serving the GraphQL schema, serves it with tuned HTTP/2
+        //! settings, and provides RFC 7807 error bodies for its own rejections
+        //! and for REST routes mounted on the same router.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(dead_code)]
+
+        use std::collections::HashSet;
+        use std::net::SocketAddr;
+        use std::time::Duration;
+
+        use axum::extract::{DefaultBodyLimit, Request, State};
+        use axum::http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+        use axum::middleware::{self, Next};
+        use axum::response::{IntoResponse, Response};
+        use axum::Router;
+        use hyper_util::rt::{TokioExecutor, TokioIo};
+        use hyper_util::server::conn::auto::Builder;
+        use hyper_util::server::graceful::GracefulShutdown;
+        use hyper_util::service::TowerToHyperService;
+        use tower::limit::GlobalConcurrencyLimitLayer;
+        use tower::Service as _;
+        use tower_http::compression::CompressionLayer;
+        use tower_http::cors::{AllowOrigin, CorsLayer};
+        use tower_http::limit::RequestBodyLimitLayer;
+        use tower_http::timeout::TimeoutLayer;
+        use tonic::transport::channel::Change;
+        use tonic::transport::{Channel, Endpoint};
+        use async_graphql::parser::types::OperationType;
+        use async_graphql::{BatchRequest, ObjectType, Schema, SubscriptionType};
+        use async_graphql_axum::GraphQLResponse;
+
+        /// Read an environment variable and parse it, falling back to a default
+        fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+            std::env::var(key)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        }
+
+        /// Read a comma-separated environment variable into a list
+        fn env_list(key: &str) -> Option<Vec<String>> {
+            std::env::var(key).ok().map(|v| {
+                v.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            })
+        }
+
+        #config
+
+        #http2
+
+        #cors
+
+        #csrf
+
+        #apply
+
+        #problem
+
+        #upstream
+
+        #cache_control
+
+        #subscription
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/graphql/gateway.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the GatewayConfig struct with defaults and environment overrides
+fn generate_config() -> TokenStream {
+    let max_body_bytes = DEFAULT_MAX_BODY_BYTES;
+    let request_timeout_secs = DEFAULT_REQUEST_TIMEOUT_SECS;
+    let concurrency_limit = DEFAULT_CONCURRENCY_LIMIT;
+
+    quote! {
+        /// HTTP hardening applied to the gateway router
+        #[derive(Debug, Clone)]
+        pub struct GatewayConfig {
+            /// Maximum accepted request body size in bytes
+            pub max_body_bytes: usize,
+            /// Maximum time a request may take before a 408 is returned
+            pub request_timeout: Duration,
+            /// Maximum number of requests processed concurrently
+            pub concurrency_limit: usize,
+            /// Compress responses (br/gzip) for clients that accept it
+            pub compression: bool,
+            /// HTTP/2 connection settings used by [`GatewayConfig::serve`]
+            pub http2: Http2Config,
+            /// Cross-origin resource sharing policy
+            pub cors: CorsConfig,
+            /// CSRF protection for cookie-authenticated requests
+            pub csrf: CsrfConfig,
+        }
+
+        impl Default for GatewayConfig {
+            fn default() -> Self {
+                Self {
+                    max_body_bytes: #max_body_bytes,
+                    request_timeout: Duration::from_secs(#request_timeout_secs),
+                    concurrency_limit: #concurrency_limit,
+                    compression: true,
+                    http2: Http2Config::default(),
+                    cors: CorsConfig::default(),
+                    csrf: CsrfConfig::default(),
+                }
+            }
+        }
+
+        impl GatewayConfig {
+            /// Build a config from environment variables, falling back to defaults
+            ///
+            /// - `GATEWAY_MAX_BODY_BYTES`
+            /// - `GATEWAY_REQUEST_TIMEOUT_SECS`
+            /// - `GATEWAY_CONCURRENCY_LIMIT`
+            /// - `GATEWAY_COMPRESSION` (`true`/`false`)
+            /// - `GATEWAY_HTTP2_*` (see [`Http2Config::from_env`])
+            /// - `GATEWAY_CORS_*` (see [`CorsConfig::from_env`])
+            /// - `GATEWAY_CSRF_*` (see [`CsrfConfig::from_env`])
+            pub fn from_env() -> Self {
+                let defaults = Self::default();
+                Self {
+                    max_body_bytes: env_or("GATEWAY_MAX_BODY_BYTES", defaults.max_body_bytes),
+                    request_timeout: Duration::from_secs(env_or(
+                        "GATEWAY_REQUEST_TIMEOUT_SECS",
+                        defaults.request_timeout.as_secs(),
+                    )),
+                    concurrency_limit: env_or(
+                        "GATEWAY_CONCURRENCY_LIMIT",
+                        defaults.concurrency_limit,
+                    ),
+                    compression: env_or("GATEWAY_COMPRESSION", defaults.compression),
+                    http2: Http2Config::from_env(),
+                    cors: CorsConfig::from_env(),
+                    csrf: CsrfConfig::from_env(),
+                }
+            }
+        }
+    }
+}
+
+/// Generate the Http2Config struct and GatewayConfig::serve
+fn generate_http2() -> TokenStream {
+    let keep_alive_secs = DEFAULT_HTTP2_KEEPALIVE_SECS;
+    let keep_alive_timeout_secs = DEFAULT_HTTP2_KEEPALIVE_TIMEOUT_SECS;
+    let max_streams = DEFAULT_HTTP2_MAX_STREAMS;
+
+    quote! {
+        /// HTTP/2 connection settings
+        ///
+        /// Keepalive pings detect dead connections behind load balancers that
+        /// drop idle streams silently; the stream limit caps how many requests
+        /// one client connection multiplexes.
+        #[derive(Debug, Clone)]
+        pub struct Http2Config {
+            /// Interval between keepalive pings, or `None` to disable them
+            pub keep_alive_interval: Option<Duration>,
+            /// Time to wait for a ping acknowledgement before closing
+            pub keep_alive_timeout: Duration,
+            /// Maximum concurrent streams per connection
+            pub max_concurrent_streams: u32,
+        }
+
+        impl Default for Http2Config {
+            fn default() -> Self {
+                Self {
+                    keep_alive_interval: Some(Duration::from_secs(#keep_alive_secs)),
+                    keep_alive_timeout: Duration::from_secs(#keep_alive_timeout_secs),
+                    max_concurrent_streams: #max_streams,
+                }
+            }
+        }
+
+        impl Http2Config {
+            /// Build HTTP/2 settings from environment variables
+            ///
+            /// - `GATEWAY_HTTP2_KEEPALIVE_SECS` (`0` disables pings)
+            /// - `GATEWAY_HTTP2_KEEPALIVE_TIMEOUT_SECS`
+            /// - `GATEWAY_HTTP2_MAX_STREAMS`
+            pub fn from_env() -> Self {
+                let defaults = Self::default();
+                let keep_alive_secs = env_or(
+                    "GATEWAY_HTTP2_KEEPALIVE_SECS",
+                    defaults.keep_alive_interval.map_or(0, |d| d.as_secs()),
+                );
+                Self {
+                    keep_alive_interval: (keep_alive_secs > 0).then(|| Duration::from_secs(keep_alive_secs)),
+                    keep_alive_timeout: Duration::from_secs(env_or(
+                        "GATEWAY_HTTP2_KEEPALIVE_TIMEOUT_SECS",
+                        defaults.keep_alive_timeout.as_secs(),
+                    )),
+                    max_concurrent_streams: env_or(
+                        "GATEWAY_HTTP2_MAX_STREAMS",
+                        defaults.max_concurrent_streams,
+                    ),
+                }
+            }
+        }
+
+        impl GatewayConfig {
+            /// Serve a router over HTTP/1.1 and HTTP/2 with the HTTP/2 settings
+            ///
+            /// Use instead of `axum::serve`, which does not expose them. Plaintext
+            /// connections negotiate HTTP/2 by prior knowledge. Handlers can
+            /// extract the peer address with `ConnectInfo<SocketAddr>`. Stops on
+            /// Ctrl+C or SIGTERM, see [`GatewayConfig::serve_with_shutdown`].
+            pub async fn serve(&self, listener: tokio::net::TcpListener, router: Router) -> std::io::Result<()> {
+                self.serve_with_shutdown(listener, router, shutdown_signal()).await
+            }
+
+            /// Serve until `signal` resolves, then stop accepting connections
+            ///
+            /// Requests already in flight get up to `request_timeout` to finish.
+            pub async fn serve_with_shutdown(
+                &self,
+                listener: tokio::net::TcpListener,
+                router: Router,
+                signal: impl std::future::Future<Output = ()>,
+            ) -> std::io::Result<()> {
+                let mut builder = Builder::new(TokioExecutor::new());
+                builder
+                    .http2()
+                    .timer(hyper_util::rt::TokioTimer::new())
+                    .keep_alive_interval(self.http2.keep_alive_interval)
+                    .keep_alive_timeout(self.http2.keep_alive_timeout)
+                    .max_concurrent_streams(self.http2.max_concurrent_streams);
+
+                let mut make_service = router.into_make_service_with_connect_info::<SocketAddr>();
+                let graceful = GracefulShutdown::new();
+                let mut signal = std::pin::pin!(signal);
+                loop {
+                    let (stream, remote) = tokio::select! {
+                        accepted = listener.accept() => match accepted {
+                            Ok(accepted) => accepted,
+                            // Out of file descriptors and similar: back off, keep serving
+                            Err(_) => {
+                                tokio::time::sleep(Duration::from_millis(100)).await;
+                                continue;
+                            }
+                        },
+                        _ = &mut signal => break,
+                    };
+                    let service = match make_service.call(remote).await {
+                        Ok(service) => service,
+                        Err(infallible) => match infallible {},
+                    };
+                    let connection = builder
+                        .serve_connection_with_upgrades(TokioIo::new(stream), TowerToHyperService::new(service))
+                        .into_owned();
+                    let connection = graceful.watch(connection);
+                    tokio::spawn(async move {
+                        // Connection errors (resets, protocol errors) only end that connection
+                        let _ = connection.await;
+                    });
+                }
+
+                let _ = tokio::time::timeout(self.request_timeout, graceful.shutdown()).await;
+                Ok(())
+            }
+        }
+
+        /// Resolve on Ctrl+C or, on Unix, SIGTERM
+        pub async fn shutdown_signal() {
+            let ctrl_c = async {
+                let _ = tokio::signal::ctrl_c().await;
+            };
+            #[cfg(unix)]
+            let terminate = async {
+                match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+                    Ok(mut terminate) => {
+                        terminate.recv().await;
+                    }
+                    Err(_) => std::future::pending::<()>().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let terminate = std::future::pending::<()>();
+            tokio::select! {
+                _ = ctrl_c => {}
+                _ = terminate => {}
+            }
+        }
+    }
+}
+
+/// Generate the CorsConfig struct and its tower-http layer
+fn generate_cors() -> TokenStream {
+    quote! {
+        /// Cross-origin resource sharing policy
+        ///
+        /// CORS is disabled (same-origin only) when `allowed_origins` is empty.
+        /// A single `*` origin allows any origin; credentials are then never sent.
+        #[derive(Debug, Clone, Default)]
+        pub struct CorsConfig {
+            /// Origins allowed to call the gateway (e.g. `https://app.example.com`)
+            pub allowed_origins: Vec<String>,
+            /// Extra request headers allowed beyond `content-type`
+            pub allowed_headers: Vec<String>,
+            /// Whether browsers may send cookies and authorization headers
+            pub allow_credentials: bool,
+        }
+
+        impl CorsConfig {
+            /// Build a CORS policy from environment variables
+            ///
+            /// - `GATEWAY_CORS_ORIGINS` (comma-separated, `*` for any)
+            /// - `GATEWAY_CORS_HEADERS` (comma-separated)
+            /// - `GATEWAY_CORS_CREDENTIALS` (`true`/`false`)
+            pub fn from_env() -> Self {
+                Self {
+                    allowed_origins: env_list("GATEWAY_CORS_ORIGINS").unwrap_or_default(),
+                    allowed_headers: env_list("GATEWAY_CORS_HEADERS").unwrap_or_default(),
+                    allow_credentials: env_or("GATEWAY_CORS_CREDENTIALS", false),
+                }
+            }
+
+            /// Build the tower-http layer, or `None` when CORS is disabled
+            pub fn layer(&self) -> Option<CorsLayer> {
+                if self.allowed_origins.is_empty() {
+                    return None;
+                }
+
+                let wildcard = self.allowed_origins.iter().any(|o| o == "*");
+                let origin = if wildcard {
+                    AllowOrigin::any()
+                } else {
+                    AllowOrigin::list(
+                        self.allowed_origins
+                            .iter()
+                            .filter_map(|o| HeaderValue::from_str(o).ok()),
+                    )
+                };
+
+                let mut headers = vec![header::CONTENT_TYPE, header::AUTHORIZATION];
+                headers.extend(
+                    self.allowed_headers
+                        .iter()
+                        .filter_map(|h| HeaderName::from_bytes(h.as_bytes()).ok()),
+                );
+
+                Some(
+                    CorsLayer::new()
+                        .allow_origin(origin)
+                        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+                        .allow_headers(headers)
+                        .allow_credentials(self.allow_credentials && !wildcard),
+                )
+            }
+        }
+    }
+}
+
+/// Generate the CsrfConfig struct and the double-submit middleware
+fn generate_csrf() -> TokenStream {
+    let cookie_name = DEFAULT_CSRF_COOKIE;
+    let header_name = DEFAULT_CSRF_HEADER;
+
+    quote! {
+        /// CSRF protection for cookie-authenticated requests
+        ///
+        /// Uses the double-submit pattern: a state-changing request that carries
+        /// cookies must echo the CSRF cookie value in the CSRF header. GraphQL
+        /// mutations are only accepted over POST, so safe methods pass through.
+        /// Requests without cookies (e.g. bearer-token clients) are not checked.
+        #[derive(Debug, Clone)]
+        pub struct CsrfConfig {
+            /// Whether the CSRF check is enforced
+            pub enabled: bool,
+            /// Cookie holding the CSRF token
+            pub cookie_name: String,
+            /// Header that must echo the cookie value
+            pub header_name: String,
+        }
+
+        impl Default for CsrfConfig {
+            fn default() -> Self {
+                Self {
+                    enabled: false,
+                    cookie_name: #cookie_name.to_string(),
+                    header_name: #header_name.to_string(),
+                }
+            }
+        }
+
+        impl CsrfConfig {
+            /// Build a CSRF policy from environment variables
+            ///
+            /// - `GATEWAY_CSRF` (`true`/`false`)
+            /// - `GATEWAY_CSRF_COOKIE`
+            /// - `GATEWAY_CSRF_HEADER`
+            pub fn from_env() -> Self {
+                let defaults = Self::default();
+                Self {
+                    enabled: env_or("GATEWAY_CSRF", defaults.enabled),
+                    cookie_name: env_or("GATEWAY_CSRF_COOKIE", defaults.cookie_name),
+                    header_name: env_or("GATEWAY_CSRF_HEADER", defaults.header_name),
+                }
+            }
+
+            /// Check whether a request passes the double-submit check
+            pub fn verify(&self, request: &Request) -> bool {
+                if matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+                    return true;
+                }
+
+                let cookies: Vec<&str> = request
+                    .headers()
+                    .get_all(header::COOKIE)
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .flat_map(|v| v.split(';'))
+                    .map(str::trim)
+                    .collect();
+
+                // Not cookie-authenticated: nothing for a forged request to ride on
+                if cookies.is_empty() {
+                    return true;
+                }
+
+                let cookie_token = cookies.iter().find_map(|c| {
+                    c.split_once('=')
+                        .filter(|(name, _)| *name == self.cookie_name)
+                        .map(|(_, value)| value)
+                });
+                let header_token = request
+                    .headers()
+                    .get(self.header_name.as_str())
+                    .and_then(|v| v.to_str().ok());
+
+                match (cookie_token, header_token) {
+                    (Some(cookie), Some(header)) => !cookie.is_empty() && cookie == header,
+                    _ => false,
+                }
+            }
+        }
+
+        /// Middleware rejecting cookie-authenticated requests without a valid CSRF token
+        async fn csrf_guard(
+            State(config): State<CsrfConfig>,
+            request: Request,
+            next: Next,
+        ) -> Response {
+            if config.verify(&request) {
+                next.run(request).await
+            } else {
+                Problem::rejection(
+                    StatusCode::FORBIDDEN,
+                    tonic::Code::PermissionDenied,
+                    "CSRF token missing or invalid",
+                )
+                .negotiate(request.headers())
+            }
+        }
+    }
+}
+
+/// Generate GatewayConfig::apply which layers the limits onto a router
+fn generate_apply() -> TokenStream {
+    quote! {
+        impl GatewayConfig {
+            /// Wrap a router with limits, compression, CORS, and CSRF protection
+            ///
+            /// Oversized bodies are rejected with 413, slow requests with 408,
+            /// and failed CSRF checks with 403, all as [`Problem`]s. The
+            /// concurrency limit is shared
+            /// across all routes. Responses are compressed per `Accept-Encoding`
+            /// (brotli or gzip; tiny and already-compressed bodies are left
+            /// alone). CORS is outermost so preflight requests are answered
+            /// before any other check runs.
+            pub fn apply<S>(&self, router: Router<S>) -> Router<S>
+            where
+                S: Clone + Send + Sync + 'static,
+            {
+                let mut router = router;
+
+                if self.csrf.enabled {
+                    router = router.layer(middleware::from_fn_with_state(
+                        self.csrf.clone(),
+                        csrf_guard,
+                    ));
+                }
+
+                if self.compression {
+                    router = router.layer(CompressionLayer::new().br(true).gzip(true));
+                }
+
+                router = router
+                    .layer(DefaultBodyLimit::max(self.max_body_bytes))
+                    .layer(RequestBodyLimitLayer::new(self.max_body_bytes))
+                    .layer(TimeoutLayer::with_status_code(
+                        StatusCode::REQUEST_TIMEOUT,
+                        self.request_timeout,
+                    ))
+                    .layer(GlobalConcurrencyLimitLayer::new(self.concurrency_limit))
+                    .layer(middleware::from_fn(problem_rejections));
+
+                if let Some(cors) = self.cors.layer() {
+                    router = router.layer(cors);
+                }
+
+                router
+            }
+        }
+    }
+}
+
+/// Generate the RFC 7807 Problem type and its tonic::Status mapping
+///
+/// Codes, retryability, and correlation IDs follow the gRPC status so REST
+/// clients see the same error semantics as gRPC and GraphQL clients.
+fn generate_problem() -> TokenStream {
+    quote! {
+        /// Media type for RFC 7807 problem details
+        pub const PROBLEM_JSON: &str = "application/problem+json";
+
+        /// Header carrying the request correlation ID
+        pub const CORRELATION_HEADER: &str = "x-request-id";
+
+        /// RFC 7807 problem details for REST error responses
+        #[derive(Debug, Clone, serde::Serialize)]
+        pub struct Problem {
+            /// URI identifying the problem type
+            #[serde(rename = "type")]
+            pub type_uri: String,
+            /// Short, human-readable summary of the problem type
+            pub title: String,
+            /// HTTP status code
+            pub status: u16,
+            /// Human-readable explanation of this occurrence
+            pub detail: String,
+            /// Machine-readable error code (gRPC code name, e.g. NOT_FOUND)
+            pub code: String,
+            /// Whether the client may retry the request unchanged
+            pub retryable: bool,
+            /// Seconds to wait before retrying, from the upstream `retry-after` metadata
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub retry_after: Option<u64>,
+            /// Correlation ID for tracing the request across services
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub correlation_id: Option<String>,
+        }
+
+        impl Problem {
+            /// Build a problem from a gRPC status
+            ///
+            /// The correlation ID and `retry-after` (e.g. of a rate limited
+            /// call) are taken from the status metadata when the upstream
+            /// service set them.
+            pub fn from_status(status: &tonic::Status) -> Self {
+                use tonic::Code;
+
+                let (http, code) = match status.code() {
+                    Code::Ok => (StatusCode::OK, "OK"),
+                    Code::Cancelled => (StatusCode::from_u16(499).unwrap_or(StatusCode::BAD_REQUEST), "CANCELLED"),
+                    Code::Unknown => (StatusCode::INTERNAL_SERVER_ERROR, "UNKNOWN"),
+                    Code::InvalidArgument => (StatusCode::BAD_REQUEST, "INVALID_ARGUMENT"),
+                    Code::DeadlineExceeded => (StatusCode::GATEWAY_TIMEOUT, "DEADLINE_EXCEEDED"),
+                    Code::NotFound => (StatusCode::NOT_FOUND, "NOT_FOUND"),
+                    Code::AlreadyExists => (StatusCode::CONFLICT, "ALREADY_EXISTS"),
+                    Code::PermissionDenied => (StatusCode::FORBIDDEN, "PERMISSION_DENIED"),
+                    Code::ResourceExhausted => (StatusCode::TOO_MANY_REQUESTS, "RESOURCE_EXHAUSTED"),
+                    Code::FailedPrecondition => (StatusCode::BAD_REQUEST, "FAILED_PRECONDITION"),
+                    Code::Aborted => (StatusCode::CONFLICT, "ABORTED"),
+                    Code::OutOfRange => (StatusCode::BAD_REQUEST, "OUT_OF_RANGE"),
+                    Code::Unimplemented => (StatusCode::NOT_IMPLEMENTED, "UNIMPLEMENTED"),
+                    Code::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
+                    Code::Unavailable => (StatusCode::SERVICE_UNAVAILABLE, "UNAVAILABLE"),
+                    Code::DataLoss => (StatusCode::INTERNAL_SERVER_ERROR, "DATA_LOSS"),
+                    Code::Unauthenticated => (StatusCode::UNAUTHORIZED, "UNAUTHENTICATED"),
+                };
+
+                let retryable = matches!(
+                    status.code(),
+                    Code::Unavailable | Code::ResourceExhausted | Code::Aborted | Code::DeadlineExceeded
+                );
+
+                let correlation_id = status
+                    .metadata()
+                    .get(CORRELATION_HEADER)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+
+                let retry_after = status
+                    .metadata()
+                    .get("retry-after")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(|v| v.parse().ok());
+
+                Self {
+                    type_uri: format!("urn:synapse:error:{}", code.to_ascii_lowercase()),
+                    title: http.canonical_reason().unwrap_or("Error").to_string(),
+                    status: http.as_u16(),
+                    detail: status.message().to_string(),
+                    code: code.to_string(),
+                    retryable,
+                    retry_after,
+                    correlation_id,
+                }
+            }
+
+            /// Build a problem for a request the gateway rejects itself
+            ///
+            /// `code` carries the error code and retryability; `status` is the
+            /// HTTP status the gateway answers with.
+            pub fn rejection(status: StatusCode, code: tonic::Code, detail: impl Into<String>) -> Self {
+                Self {
+                    title: status.canonical_reason().unwrap_or("Error").to_string(),
+                    status: status.as_u16(),
+                    ..Self::from_status(&tonic::Status::new(code, detail))
+                }
+            }
+
+            /// Attach a correlation ID, keeping one already set by the upstream service
+            pub fn with_correlation_id(mut self, id: impl Into<String>) -> Self {
+                self.correlation_id.get_or_insert_with(|| id.into());
+                self
+            }
+
+            /// Render the problem in the format the client asked for
+            ///
+            /// Clients accepting JSON (or anything) get `application/problem+json`;
+            /// others get a plain-text summary with the same status code. The
+            /// request's correlation header is echoed when the problem has none.
+            pub fn negotiate(self, headers: &HeaderMap) -> Response {
+                let problem = match headers
+                    .get(CORRELATION_HEADER)
+                    .and_then(|v| v.to_str().ok())
+                {
+                    Some(id) => self.with_correlation_id(id),
+                    None => self,
+                };
+
+                let wants_json = headers
+                    .get(header::ACCEPT)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|accept| {
+                        accept.contains(PROBLEM_JSON)
+                            || accept.contains("application/json")
+                            || accept.contains("*/*")
+                    })
+                    .unwrap_or(true);
+
+                if wants_json {
+                    problem.into_response()
+                } else {
+                    let status = StatusCode::from_u16(problem.status)
+                        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                    (status, format!("{}: {}", problem.code, problem.detail)).into_response()
+                }
+            }
+        }
+
+        impl From<tonic::Status> for Problem {
+            fn from(status: tonic::Status) -> Self {
+                Self::from_status(&status)
+            }
+        }
+
+        impl IntoResponse for Problem {
+            fn into_response(self) -> Response {
+                let status = StatusCode::from_u16(self.status)
+                    .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                let body = serde_json::to_vec(&self).unwrap_or_default();
+                let mut response = (status, body).into_response();
+                response
+                    .headers_mut()
+                    .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+                if let Some(seconds) = self.retry_after {
+                    response
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+                }
+                response
+            }
+        }
+
+        /// Middleware rendering the limit layers' rejections as problems
+        ///
+        /// Timeouts (408) and oversized bodies (413) come from tower layers
+        /// with empty or plain-text bodies; they are negotiated against the
+        /// request's `Accept` header like any other problem. A 408 or 413
+        /// carrying any other body was produced deliberately by a handler or
+        /// upstream and passes through untouched.
+        async fn problem_rejections(request: Request, next: Next) -> Response {
+            let mut headers = HeaderMap::new();
+            for name in [header::ACCEPT, HeaderName::from_static(CORRELATION_HEADER)] {
+                if let Some(value) = request.headers().get(&name) {
+                    headers.insert(name, value.clone());
+                }
+            }
+
+            let response = next.run(request).await;
+            let (code, detail) = match response.status() {
+                StatusCode::REQUEST_TIMEOUT => (tonic::Code::DeadlineExceeded, "request timed out"),
+                StatusCode::PAYLOAD_TOO_LARGE => (tonic::Code::InvalidArgument, "request body too large"),
+                _ => return response,
+            };
+            let from_layer = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .is_none_or(|content_type| content_type.starts_with("text/plain"));
+            if !from_layer {
+                return response;
+            }
+            Problem::rejection(response.status(), code, detail).negotiate(&headers)
+        }
+
+        #[cfg(test)]
+        mod problem_tests {
+            use super::*;
+            use axum::body::Body;
+
+            async fn json_body(response: Response) -> serde_json::Value {
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice(&body).unwrap()
+            }
+
+            #[tokio::test]
+            async fn problem_forwards_upstream_retry_after() {
+                let mut status = tonic::Status::resource_exhausted("post create quota exceeded");
+                status.metadata_mut().insert("retry-after", "30".parse().unwrap());
+                let response = Problem::from(status).into_response();
+
+                assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+                assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+                assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+                let body = json_body(response).await;
+                assert_eq!(body["code"], "RESOURCE_EXHAUSTED");
+                assert_eq!(body["detail"], "post create quota exceeded");
+                assert_eq!(body["retryable"], true);
+                assert_eq!(body["retry_after"], 30);
+            }
+
+            #[tokio::test]
+            async fn gateway_rejections_are_problems() {
+                let config = GatewayConfig {
+                    max_body_bytes: 4,
+                    csrf: CsrfConfig { enabled: true, ..CsrfConfig::default() },
+                    ..GatewayConfig::default()
+                };
+                let mut router = config.apply(Router::new().route(
+                    "/graphql",
+                    axum::routing::post(|body: String| async move { body }),
+                ));
+
+                let request = axum::http::Request::builder()
+                    .method(Method::POST)
+                    .uri("/graphql")
+                    .header(header::COOKIE, "csrf_token=abc")
+                    .header(CORRELATION_HEADER, "req-1")
+                    .body(Body::empty())
+                    .unwrap();
+                let response = router.call(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::FORBIDDEN);
+                assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+                assert!(response.headers().get(header::RETRY_AFTER).is_none());
+                let body = json_body(response).await;
+                assert_eq!(body["code"], "PERMISSION_DENIED");
+                assert_eq!(body["retryable"], false);
+                assert_eq!(body["correlation_id"], "req-1");
+
+                let request = axum::http::Request::builder()
+                    .method(Method::POST)
+                    .uri("/graphql")
+                    .header(header::CONTENT_LENGTH, "9")
+                    .body(Body::from("too large"))
+                    .unwrap();
+                let response = router.call(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+                assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+                let body = json_body(response).await;
+                assert_eq!(body["status"], 413);
+                assert_eq!(body["code"], "INVALID_ARGUMENT");
+            }
+
+            #[tokio::test]
+            async fn handler_rejections_pass_through() {
+                let mut router = GatewayConfig::default().apply(Router::new().route(
+                    "/upload",
+                    axum::routing::post(|| async {
+                        (
+                            StatusCode::PAYLOAD_TOO_LARGE,
+                            axum::Json(serde_json::json!({ "limit": 1024 })),
+                        )
+                    }),
+                ));
+
+                let request = axum::http::Request::builder()
+                    .method(Method::POST)
+                    .uri("/upload")
+                    .body(Body::empty())
+                    .unwrap();
+                let response = router.call(request).await.unwrap();
+                assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+                assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+                let body = json_body(response).await;
+                assert_eq!(body["limit"], 1024);
+            }
+        }
+    }
+}
+
+/// Generate UpstreamConfig, which builds the channels to upstream services
+///
+/// With `rustls`, https upstreams get a rustls client config (webpki roots
+/// unless overridden), applied to every balanced address as well.
+fn generate_upstream(rustls: bool) -> TokenStream {
+    let connect_timeout_secs = DEFAULT_CONNECT_TIMEOUT_SECS;
+
+    let (tls_field, tls_default, tls_doc, base_tls, address_tls) = if rustls {
+        (
+            quote! {
+                /// TLS for https endpoints; `None` verifies against the webpki roots
+                pub tls: Option<tonic::transport::ClientTlsConfig>,
+            },
+            quote! { tls: None, },
+            quote! {
+                /// https endpoints use rustls, with the same TLS config for every
+                /// balanced address and the host as the verified server name.
+            },
+            quote! {
+                let endpoint = if https {
+                    endpoint.tls_config(self.client_tls())?
+                } else {
+                    endpoint
+                };
+            },
+            quote! {
+                let tls = https.then(|| self.client_tls());
+                let tls_host = host.clone();
+            },
+        )
+    } else {
+        (
+            quote! {},
+            quote! {},
+            quote! {
+                /// https endpoints need the generator's `tls=rustls` parameter.
+            },
+            quote! {},
+            quote! {},
+        )
+    };
+    let address_tls_config = if rustls {
+        quote! {
+            match &tls {
+                Some(tls) => endpoint.tls_config(tls.clone().domain_name(tls_host.clone()))?,
+                None => endpoint,
+            }
+        }
+    } else {
+        quote! { endpoint }
+    };
+    let client_tls = if rustls {
+        quote! {
+            /// The configured TLS, or rustls with the webpki roots
+            fn client_tls(&self) -> tonic::transport::ClientTlsConfig {
+                self.tls
+                    .clone()
+                    .unwrap_or_else(|| tonic::transport::ClientTlsConfig::new().with_webpki_roots())
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        /// How the gateway reaches an upstream gRPC service
+        ///
+        /// By default the channel connects lazily to the address the host first
+        /// resolves to. `eager` connects at startup and fails if the service is
+        /// unreachable. `resolve_interval` re-resolves the host periodically and
+        /// balances over every address it returns, so scaled or replaced backends
+        /// are picked up without a restart. Balanced addresses keep the endpoint's
+        /// scheme and its host as the `:authority`.
+        ///
+        #tls_doc
+        #[derive(Debug, Clone)]
+        pub struct UpstreamConfig {
+            /// Service URI, e.g. `http://iam:50052`
+            pub endpoint: String,
+            /// Connect at startup and fail when no address accepts a connection
+            pub eager: bool,
+            /// How often to re-resolve the host; `None` pins the first address
+            pub resolve_interval: Option<Duration>,
+            /// Time allowed to establish each connection
+            pub connect_timeout: Duration,
+            #tls_field
+        }
+
+        /// Error building an upstream channel
+        #[derive(Debug)]
+        pub enum UpstreamError {
+            Transport(tonic::transport::Error),
+            Resolve(std::io::Error),
+            NoAddresses(String),
+        }
+
+        impl std::fmt::Display for UpstreamError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    Self::Transport(e) => write!(f, "upstream connection failed: {}", e),
+                    Self::Resolve(e) => write!(f, "upstream resolution failed: {}", e),
+                    Self::NoAddresses(endpoint) => write!(f, "{} resolved to no addresses", endpoint),
+                }
+            }
+        }
+
+        impl std::error::Error for UpstreamError {}
+
+        impl From<tonic::transport::Error> for UpstreamError {
+            fn from(e: tonic::transport::Error) -> Self {
+                Self::Transport(e)
+            }
+        }
+
+        impl UpstreamConfig {
+            pub fn new(endpoint: impl Into<String>) -> Self {
+                Self {
+                    endpoint: endpoint.into(),
+                    eager: false,
+                    resolve_interval: None,
+                    connect_timeout: Duration::from_secs(#connect_timeout_secs),
+                    #tls_default
+                }
+            }
+
+            #client_tls
+
+            /// Build a config from `{prefix}_`-prefixed environment variables
+            ///
+            /// - `{prefix}_GRPC_ENDPOINT` (`default_endpoint` when unset)
+            /// - `{prefix}_GRPC_EAGER` (`true`/`false`)
+            /// - `{prefix}_GRPC_RESOLVE_SECS` (0 or unset pins the first address)
+            /// - `{prefix}_GRPC_CONNECT_TIMEOUT_SECS`
+            pub fn from_env(prefix: &str, default_endpoint: &str) -> Self {
+                let defaults = Self::new(
+                    std::env::var(format!("{}_GRPC_ENDPOINT", prefix))
+                        .unwrap_or_else(|_| default_endpoint.to_string()),
+                );
+                let resolve_secs: u64 = env_or(&format!("{}_GRPC_RESOLVE_SECS", prefix), 0);
+                Self {
+                    eager: env_or(&format!("{}_GRPC_EAGER", prefix), defaults.eager),
+                    resolve_interval: (resolve_secs > 0).then(|| Duration::from_secs(resolve_secs)),
+                    connect_timeout: Duration::from_secs(env_or(
+                        &format!("{}_GRPC_CONNECT_TIMEOUT_SECS", prefix),
+                        defaults.connect_timeout.as_secs(),
+                    )),
+                    ..defaults
+                }
+            }
+
+            /// Build the channel, connecting first when `eager` is set
+            ///
+            /// With `resolve_interval`, spawns a task on the current tokio runtime
+            /// that keeps the balanced address set in sync with DNS. It stops once
+            /// every clone of the channel is dropped.
+            pub async fn connect(&self) -> Result<Channel, UpstreamError> {
+                let endpoint = Endpoint::from_shared(self.endpoint.clone())?.connect_timeout(self.connect_timeout);
+                let https = endpoint.uri().scheme_str() == Some("https");
+                #base_tls
+                let Some(interval) = self.resolve_interval else {
+                    return Ok(if self.eager {
+                        endpoint.connect().await?
+                    } else {
+                        endpoint.connect_lazy()
+                    });
+                };
+
+                let uri = endpoint.uri().clone();
+                let host = uri
+                    .host()
+                    .ok_or_else(|| UpstreamError::NoAddresses(self.endpoint.clone()))?
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .to_string();
+                let scheme = uri.scheme_str().unwrap_or("http").to_string();
+                let port = uri.port_u16().unwrap_or(if https { 443 } else { 80 });
+                let connect_timeout = self.connect_timeout;
+                #address_tls
+                let address_endpoint = move |addr: SocketAddr| -> Result<Endpoint, UpstreamError> {
+                    let endpoint = Endpoint::from_shared(format!("{}://{}", scheme, addr))?
+                        .origin(uri.clone())
+                        .connect_timeout(connect_timeout);
+                    Ok(#address_tls_config)
+                };
+
+                let addresses = resolve(&host, port).await?;
+                if addresses.is_empty() {
+                    return Err(UpstreamError::NoAddresses(self.endpoint.clone()));
+                }
+                if self.eager {
+                    let mut last_error = None;
+                    for addr in &addresses {
+                        match address_endpoint(*addr)?.connect().await {
+                            Ok(_) => {
+                                last_error = None;
+                                break;
+                            }
+                            Err(e) => last_error = Some(e),
+                        }
+                    }
+                    if let Some(e) = last_error {
+                        return Err(e.into());
+                    }
+                }
+
+                // Room for the initial addresses, which are queued before any request polls the balancer
+                let (channel, changes) = Channel::balance_channel::<SocketAddr>(addresses.len().max(64));
+                for addr in &addresses {
+                    let _ = changes.send(Change::Insert(*addr, address_endpoint(*addr)?)).await;
+                }
+
+                tokio::spawn(async move {
+                    let mut current = addresses;
+                    loop {
+                        tokio::time::sleep(interval).await;
+                        // Keep the known addresses when resolution fails or comes back empty
+                        let next = match resolve(&host, port).await {
+                            Ok(next) if !next.is_empty() => next,
+                            _ => continue,
+                        };
+                        for addr in next.difference(&current) {
+                            let Ok(endpoint) = address_endpoint(*addr) else { continue };
+                            if changes.send(Change::Insert(*addr, endpoint)).await.is_err() {
+                                return;
+                            }
+                        }
+                        for addr in current.difference(&next) {
+                            if changes.send(Change::Remove(*addr)).await.is_err() {
+                                return;
+                            }
+                        }
+                        current = next;
+                    }
+                });
+
+                Ok(channel)
+            }
+        }
+
+        /// Resolve a host to its current set of addresses
+        async fn resolve(host: &str, port: u16) -> Result<HashSet<SocketAddr>, UpstreamError> {
+            tokio::net::lookup_host((host, port))
+                .await
+                .map(|addrs| addrs.collect())
+                .map_err(UpstreamError::Resolve)
+        }
+    }
+}
+
+/// Generate execute_graphql, which sets `Cache-Control` from cache hints
+///
+/// async-graphql merges the `cache_control` hints of everything a response
+/// resolved; only successful GET queries may expose that to caches. A
+/// federation subgraph also gives each request an entity cache; POST
+/// `_entities` fetches stay `no-store`.
+fn generate_cache_control(federation: bool) -> TokenStream {
+    let federation_hints = federation.then(|| {
+        quote! {
+            let request = super::federation::with_entity_cache(request);
+        }
+    });
+
+    quote! {
+        /// Execute a GraphQL request, with the schema's cache hints as `Cache-Control`
+        ///
+        /// Call from the GraphQL handler, mounted for GET and POST. A successful
+        /// GET query gets the combined hint of the types and fields it resolved
+        /// (smallest `max_age`, `private` if any hint is), so CDNs can cache
+        /// public queries. Everything else is `no-store`: POST requests,
+        /// responses with errors, and queries without hints. Mutations over GET
+        /// are rejected with a 405 `Problem`, negotiated from the request's
+        /// `headers`, so a cache can never replay them.
+        pub async fn execute_graphql<Q, M, S>(
+            schema: &Schema<Q, M, S>,
+            method: &Method,
+            headers: &HeaderMap,
+            request: BatchRequest,
+        ) -> Response
+        where
+            Q: ObjectType + 'static,
+            M: ObjectType + 'static,
+            S: SubscriptionType + 'static,
+        {
+            let cacheable = *method == Method::GET || *method == Method::HEAD;
+            if cacheable && has_mutation(&request) {
+                return Problem::rejection(
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    tonic::Code::InvalidArgument,
+                    "mutations must use POST",
+                )
+                .negotiate(headers);
+            }
+            #federation_hints
+
+            let response = schema.execute_batch(request).await;
+            let hint = (cacheable && response.is_ok())
+                .then(|| response.cache_control().value())
+                .flatten()
+                .and_then(|value| HeaderValue::from_str(&value).ok());
+
+            let mut http = GraphQLResponse::from(response).into_response();
+            http.headers_mut().insert(
+                header::CACHE_CONTROL,
+                hint.unwrap_or_else(|| HeaderValue::from_static("no-store")),
+            );
+            http
+        }
+
+        /// Whether any request of a batch would run a mutation or subscription
+        fn has_mutation(request: &BatchRequest) -> bool {
+            let requests = match request {
+                BatchRequest::Single(request) => std::slice::from_ref(request),
+                BatchRequest::Batch(requests) => requests.as_slice(),
+            };
+            requests.iter().any(|request| {
+                // Unparsable documents fail validation without running anything
+                let Ok(document) = async_graphql::parser::parse_query(&request.query) else {
+                    return false;
+                };
+                document.operations.iter().any(|(name, operation)| {
+                    let selected = match (&request.operation_name, name) {
+                        (Some(wanted), Some(name)) => wanted.as_str() == name.as_str(),
+                        _ => true,
+                    };
+                    selected && operation.node.ty != OperationType::Query
+                })
+            })
+        }
+    }
+}
+
+/// Generate `graphql_subscription`, serving the schema's subscriptions over websockets
+///
+/// With `request_context`, the upgrade request's context is kept for the
+/// connection, so subscriptions fetch changed entities on the subscriber's
+/// behalf.
+fn generate_subscription(request_context: bool) -> TokenStream {
+    let (params, data) = if request_context {
+        (
+            quote! {
+                axum::extract::ConnectInfo(remote_addr): axum::extract::ConnectInfo<SocketAddr>,
+                headers: HeaderMap,
+            },
+            quote! {
+                let mut data = async_graphql::Data::default();
+                data.insert(super::super::prelude::RequestContext::from_headers(&headers, Some(remote_addr)));
+            },
+        )
+    } else {
+        (quote! {}, quote! { let data = async_graphql::Data::default(); })
+    };
+
+    quote! {
+        /// Path the subscription websocket is conventionally mounted at
+        pub const SUBSCRIPTION_PATH: &str = "/ws";
+
+        /// Serve the schema's subscriptions over a websocket
+        ///
+        /// Speaks both `graphql-transport-ws` and the legacy `graphql-ws`.
+        /// Mount it for GET, e.g. `.route(SUBSCRIPTION_PATH, get(graphql_subscription))`
+        /// on a router whose state is the schema.
+        pub async fn graphql_subscription<Q, M, S>(
+            State(schema): State<Schema<Q, M, S>>,
+            #params
+            protocol: async_graphql_axum::GraphQLProtocol,
+            upgrade: axum::extract::ws::WebSocketUpgrade,
+        ) -> Response
+        where
+            Q: ObjectType + 'static,
+            M: ObjectType + 'static,
+            S: SubscriptionType + 'static,
+        {
+            #data
+            upgrade
+                .protocols(async_graphql::http::ALL_WEBSOCKET_PROTOCOLS)
+                .on_upgrade(move |stream| {
+                    async_graphql_axum::GraphQLWebSocket::new(stream, schema, protocol)
+                        .with_data(data)
+                        .serve()
+                })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_subscription() {
+        let code = generate_subscription(false).to_string();
+        assert!(code.contains("pub async fn graphql_subscription < Q , M , S >"));
+        assert!(code.contains("ALL_WEBSOCKET_PROTOCOLS"));
+        assert!(code.contains("GraphQLWebSocket :: new (stream , schema , protocol)"));
+        assert!(!code.contains("RequestContext"));
+
+        let code = generate_subscription(true).to_string();
+        assert!(code.contains("RequestContext :: from_headers (& headers , Some (remote_addr))"));
+        assert!(code.contains(". with_data (data)"));
+    }
+
+    #[test]
+    fn test_generate_config_defaults() {
+        let code = generate_config().to_string();
+        assert!(code.contains("GatewayConfig"));
+        assert!(code.contains("GATEWAY_MAX_BODY_BYTES"));
+        assert!(code.contains("GATEWAY_REQUEST_TIMEOUT_SECS"));
+        assert!(code.contains("GATEWAY_CONCURRENCY_LIMIT"));
+        assert!(code.contains("GATEWAY_COMPRESSION"));
+    }
+
+    #[test]
+    fn test_generate_http2() {
+        let code = generate_http2().to_string();
+        assert!(code.contains("GATEWAY_HTTP2_KEEPALIVE_SECS"));
+        assert!(code.contains("GATEWAY_HTTP2_MAX_STREAMS"));
+        assert!(code.contains("max_concurrent_streams : 256u32"));
+        assert!(code.contains("serve_connection_with_upgrades"));
+        assert!(code.contains("into_make_service_with_connect_info :: < SocketAddr > ()"));
+        assert!(code.contains("graceful . watch (connection)"));
+        assert!(code.contains("self . serve_with_shutdown (listener , router , shutdown_signal ())"));
+    }
+
+    #[test]
+    fn test_generate_cors() {
+        let code = generate_cors().to_string();
+        assert!(code.contains("CorsLayer"));
+        assert!(code.contains("GATEWAY_CORS_ORIGINS"));
+        assert!(code.contains("allow_credentials"));
+    }
+
+    #[test]
+    fn test_generate_csrf() {
+        let code = generate_csrf().to_string();
+        assert!(code.contains("csrf_guard"));
+        assert!(code.contains("\"csrf_token\""));
+        assert!(code.contains("\"x-csrf-token\""));
+        assert!(code.contains("FORBIDDEN"));
+        assert!(code.contains("Problem :: rejection"));
+    }
+
+    #[test]
+    fn test_generate_apply_layers() {
+        let code = generate_apply().to_string();
+        assert!(code.contains("DefaultBodyLimit"));
+        assert!(code.contains("RequestBodyLimitLayer"));
+        assert!(code.contains("TimeoutLayer"));
+        assert!(code.contains("GlobalConcurrencyLimitLayer"));
+        assert!(code.contains("CompressionLayer"));
+        assert!(code.contains("from_fn_with_state"));
+        assert!(code.contains("middleware :: from_fn (problem_rejections)"));
+    }
+
+    #[test]
+    fn test_generate_problem() {
+        let code = generate_problem().to_string();
+        assert!(code.contains("application/problem+json"));
+        assert!(code.contains("from_status"));
+        assert!(code.contains("retryable"));
+        assert!(code.contains("correlation_id"));
+        assert!(code.contains("negotiate"));
+        // Retry-After follows the upstream metadata instead of a fixed value
+        assert!(code.contains(". get (\"retry-after\")"));
+        assert!(!code.contains("from_static (\"1\")"));
+        // The behaviour itself is checked by the generated tests
+        assert!(code.contains("async fn problem_forwards_upstream_retry_after ()"));
+        assert!(code.contains("async fn gateway_rejections_are_problems ()"));
+        assert!(code.contains("async fn handler_rejections_pass_through ()"));
+        // Only the headers negotiation reads are kept across the request
+        assert!(!code.contains("request . headers () . clone ()"));
+    }
+
+    #[test]
+    fn test_generate_upstream() {
+        let code = generate_upstream(false).to_string();
+        assert!(code.contains("Channel :: balance_channel :: < SocketAddr > (addresses . len () . max (64))"));
+        assert!(code.contains("Change :: Remove"));
+        assert!(code.contains("endpoint . connect_lazy ()"));
+        assert!(code.contains("Duration :: from_secs (5u64)"));
+        assert!(code.contains("format ! (\"{}://{}\" , scheme , addr)"));
+        assert!(code.contains("unwrap_or (if https { 443 } else { 80 })"));
+        assert!(!code.contains("ClientTlsConfig"));
+    }
+
+    #[test]
+    fn test_generate_upstream_tls() {
+        let code = generate_upstream(true).to_string();
+        assert!(code.contains("pub tls : Option < tonic :: transport :: ClientTlsConfig >"));
+        assert!(code.contains("endpoint . tls_config (self . client_tls ()) ?"));
+        assert!(code.contains("tls . clone () . domain_name (tls_host . clone ())"));
+        assert!(code.contains("with_webpki_roots"));
+    }
+
+    #[test]
+    fn test_generate_cache_control() {
+        let code = generate_cache_control(false).to_string();
+        assert!(code.contains("pub async fn execute_graphql < Q , M , S >"));
+        assert!(code.contains("response . cache_control () . value ()"));
+        assert!(code.contains("HeaderValue :: from_static (\"no-store\")"));
+        assert!(code.contains("METHOD_NOT_ALLOWED"));
+        assert!(code.contains(". negotiate (headers) ;"));
+        assert!(!code.contains("with_entity_cache"));
+
+        let code = generate_cache_control(true).to_string();
+        assert!(!code.contains("let cacheable = cacheable ||"));
+        assert!(code.contains("super :: federation :: with_entity_cache (request)"));
+    }
+}
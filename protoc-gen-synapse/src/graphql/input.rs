@@ -3,28 +3,55 @@
 //! Generates InputObject types from mutation request messages:
 //! - CreateUserRequest → CreateUserInput (all fields except context-injected)
 //! - UpdateUserRequest → UpdateUserInput (all fields except id and context-injected)
+//! - CreateManyUsersRequest → a list of CreateUserInput, or of
+//!   CreateManyUsersItemInput when the response reports per-item errors
+//! - UpdateManyUsersRequest → its patch's input, e.g. UserPatch → UserPatchInput
+//! - other mutation requests with context-injected fields, except deletes,
+//!   e.g. ArchiveUserRequest → ArchiveUserInput (all fields except context-injected)
 //!
 //! Fields marked with `from_context` are excluded from the GraphQL input
-//! and populated server-side from the authentication context.
+//! and populated server-side from the request's context. Timestamps
+//! managed by storage (`timestamps: true`) and database-generated columns
+//! are excluded as well.
+//!
+//! `synapse.validate.field` length, range, and pattern rules become
+//! async-graphql validators, so the gateway rejects obviously invalid input
+//! before the gRPC call. The service's domain types still run the full,
+//! authoritative validation. Item inputs of batches reporting per-item errors
+//! have no validators: the service reports an invalid item in its result
+//! instead of the whole list failing.
 
 use crate::error::GeneratorError;
+use crate::storage::seaorm::batch;
+use crate::storage::seaorm::implementation::{
+    create_many_item_type, generated_columns, has_managed_timestamps, update_many_patch_type, GEO_POINT_TYPE,
+    MANAGED_TIMESTAMPS,
+};
+use crate::options::synapse::validate;
 use crate::storage::seaorm::options::{
-    get_cached_graphql_field_options, get_cached_graphql_mutation_options,
+    get_cached_entity_options, get_cached_graphql_field_options,
+    get_cached_graphql_mutation_options, get_cached_validate_field_options,
+};
+use crate::validate::{
+    field_sanitize, generate_sanitize, generate_sanitize_optional, range_bounds, Bound,
 };
 use heck::{ToSnakeCase, ToUpperCamelCase};
 use prost_types::compiler::code_generator_response::File;
 use prost_types::field_descriptor_proto::Type;
-use prost_types::{FieldDescriptorProto, FileDescriptorProto, ServiceDescriptorProto};
+use proc_macro2::{Literal, TokenStream};
+use prost_types::{FieldDescriptorProto, FileDescriptorProto, MethodDescriptorProto, ServiceDescriptorProto};
 use quote::{format_ident, quote};
 
 /// Generate input types for mutation methods in a service
 pub fn generate_inputs_for_service(
     file: &FileDescriptorProto,
     service: &ServiceDescriptorProto,
+    all_files: &[FileDescriptorProto],
 ) -> Result<Vec<File>, GeneratorError> {
     let file_name = file.name.as_deref().unwrap_or("");
     let svc_name = service.name.as_deref().unwrap_or("");
     let mut files = Vec::new();
+    let mut generated: Vec<String> = Vec::new();
 
     for method in &service.method {
         let method_name = method.name.as_deref().unwrap_or("");
@@ -40,61 +67,154 @@ pub fn generate_inputs_for_service(
             continue;
         }
 
+        // Get the request message type
+        let mut request_type_name = method
+            .input_type
+            .as_ref()
+            .map(|t| t.rsplit('.').next().unwrap_or(t).to_string())
+            .unwrap_or_default();
+
         // Check if this is a create or update operation
-        let is_create = method_name.to_lowercase().starts_with("create");
-        let is_update = method_name.to_lowercase().starts_with("update");
+        // Upserts take the same input as creates
+        let is_create = method_name.to_lowercase().starts_with("create")
+            || method_name.to_lowercase().starts_with("upsert");
+        let is_update_many = method_name.to_lowercase().starts_with("updatemany");
+        let is_update = method_name.to_lowercase().starts_with("update") && !is_update_many;
 
-        if !is_create && !is_update {
+        if !is_create && !is_update && !is_update_many && !is_custom_with_context(file, method_name, &request_type_name) {
             continue;
         }
 
-        // Get the request message type
-        let request_type_name = method
-            .input_type
-            .as_ref()
-            .map(|t| t.rsplit('.').next().unwrap_or(t))
-            .unwrap_or("");
+        // Update-many mutations take the input of their patch
+        let mut input_name = None;
+        if is_update_many {
+            let patch_type = file
+                .message_type
+                .iter()
+                .find(|m| m.name.as_deref() == Some(request_type_name.as_str()))
+                .and_then(update_many_patch_type);
+            match patch_type {
+                Some(patch_type) => {
+                    input_name = Some(patch_input_name(&patch_type));
+                    request_type_name = patch_type;
+                }
+                None => continue,
+            }
+        }
+
+        // Create-many mutations take a list of their item input
+        let mut validated = true;
+        if method_name.to_lowercase().starts_with("createmany") {
+            match create_many_input(file, method) {
+                Some(item) => {
+                    request_type_name = item.request;
+                    input_name = Some(item.input);
+                    validated = !item.per_item_errors;
+                }
+                None => continue,
+            }
+        }
 
         // Find the request message in the file
         let request_msg = file
             .message_type
             .iter()
-            .find(|m| m.name.as_deref() == Some(request_type_name));
+            .find(|m| m.name.as_deref() == Some(request_type_name.as_str()));
 
         if let Some(msg) = request_msg {
             // Generate input type name: CreateUserRequest → CreateUserInput
-            let input_name = request_type_name.replace("Request", "Input");
+            let input_name = input_name.unwrap_or_else(|| request_type_name.replace("Request", "Input"));
 
+            // A create and a create-many mutation share the input
+            if generated.contains(&input_name) {
+                continue;
+            }
             if let Some(input_file) =
-                generate_input_type(file, msg, &input_name, is_update)?
+                generate_input_type(file, msg, &input_name, is_update, validated, all_files)?
             {
                 files.push(input_file);
             }
+            generated.push(input_name);
         }
     }
 
     Ok(files)
 }
 
-/// Information about a context-injected field
-struct ContextField {
-    /// Field name (snake_case)
-    name: String,
-    /// Rust type for the field
-    rust_type: proc_macro2::TokenStream,
-    /// Context path (e.g., "current_user.id")
-    path: String,
-    /// Whether the field is required (for future error handling)
-    #[allow(dead_code)]
-    required: bool,
+/// The item input of a create-many mutation
+pub(crate) struct CreateManyInput {
+    /// Request message of one item, e.g. CreateUserRequest
+    pub request: String,
+    /// GraphQL input type of one item
+    pub input: String,
+    /// Whether the response reports per-item errors
+    pub per_item_errors: bool,
+}
+
+/// The item input of a create-many mutation, if its request lists items
+///
+/// Items share the create's input (CreateUserRequest → CreateUserInput),
+/// except when the response reports per-item errors: then they get an input
+/// without validators, e.g. CreateManyUsersRequest → CreateManyUsersItemInput.
+pub(crate) fn create_many_input(file: &FileDescriptorProto, method: &MethodDescriptorProto) -> Option<CreateManyInput> {
+    let type_name = |t: Option<&str>| t.map(|t| t.rsplit('.').next().unwrap_or(t).to_string()).unwrap_or_default();
+    let request_name = type_name(method.input_type.as_deref());
+    let response_name = type_name(method.output_type.as_deref());
+    let find = |name: &str| file.message_type.iter().find(|m| m.name.as_deref() == Some(name));
+    let request = find(&request_name).and_then(create_many_item_type)?;
+    let per_item_errors = find(&response_name).and_then(batch::errors_field).is_some();
+    let input = if per_item_errors {
+        format!("{}ItemInput", request_name.trim_end_matches("Request"))
+    } else {
+        request.replace("Request", "Input")
+    };
+    Some(CreateManyInput { request, input, per_item_errors })
+}
+
+/// Input type name of an update-many patch: UserPatch → UserPatchInput
+pub(crate) fn patch_input_name(patch_type: &str) -> String {
+    if patch_type.contains("Request") {
+        patch_type.replace("Request", "Input")
+    } else {
+        format!("{}Input", patch_type)
+    }
+}
+
+/// Whether a request message has fields injected from the GraphQL context
+pub(crate) fn has_context_fields(file: &FileDescriptorProto, message_name: &str) -> bool {
+    let file_name = file.name.as_deref().unwrap_or("");
+    file.message_type
+        .iter()
+        .find(|m| m.name.as_deref() == Some(message_name))
+        .is_some_and(|message| {
+            message.field.iter().any(|field| {
+                get_cached_graphql_field_options(file_name, message_name, field.number.unwrap_or(0))
+                    .is_some_and(|o| o.from_context.is_some())
+            })
+        })
+}
+
+/// Whether a mutation other than a create, update or delete gets a derived input
+///
+/// Its request is otherwise the GraphQL argument as is; context-injected
+/// fields need an input without them.
+pub(crate) fn is_custom_with_context(file: &FileDescriptorProto, method_name: &str, request_name: &str) -> bool {
+    let lower = method_name.to_lowercase();
+    let crud = ["create", "upsert", "update", "delete"];
+    !crud.iter().any(|prefix| lower.starts_with(prefix)) && has_context_fields(file, request_name)
 }
 
 /// Generate a GraphQL InputObject from a request message
+///
+/// The entity, whose storage-managed columns are left out, may be declared in
+/// any of `all_files`.
 fn generate_input_type(
     file: &FileDescriptorProto,
     message: &prost_types::DescriptorProto,
     input_name: &str,
     is_update: bool,
+    validated: bool,
+    all_files: &[FileDescriptorProto],
 ) -> Result<Option<File>, GeneratorError> {
     let file_name = file.name.as_deref().unwrap_or("");
     let package = file.package.as_deref().unwrap_or("");
@@ -102,11 +222,37 @@ fn generate_input_type(
 
     let input_ident = format_ident!("{}", input_name);
     let request_ident = format_ident!("{}", msg_name);
+    // Update ids follow the request's id field (i64, or String for UUID keys)
+    let id_ty = super::key_type(Some(message), "id");
 
     let mut field_tokens = Vec::new();
     let mut from_conversion_tokens = Vec::new();
     let mut self_conversion_tokens = Vec::new();
-    let mut context_fields: Vec<ContextField> = Vec::new();
+    let mut sanitizers = Vec::new();
+    let context_fields = super::context::context_fields(file, msg_name)?;
+
+    // Storage-managed timestamps are never client input
+    let entity_name = msg_name
+        .trim_start_matches("Create")
+        .trim_start_matches("Update")
+        .trim_start_matches("Upsert")
+        .trim_end_matches("Request");
+    let entity = all_files.iter().find_map(|f| {
+        f.message_type
+            .iter()
+            .find(|m| m.name.as_deref() == Some(entity_name))
+            .map(|m| (f.name.as_deref().unwrap_or(""), m))
+    });
+    let entity_options = entity.and_then(|(entity_file, _)| get_cached_entity_options(entity_file, entity_name));
+    let mut managed: Vec<String> = if has_managed_timestamps(entity_options.as_ref()) {
+        MANAGED_TIMESTAMPS.iter().map(|s| s.to_string()).collect()
+    } else {
+        Vec::new()
+    };
+    // Database-generated columns are never client input either
+    if let Some((entity_file, entity)) = entity {
+        managed.extend(generated_columns(entity_file, entity));
+    }
 
     for field in &message.field {
         let field_name = field.name.as_deref().unwrap_or("");
@@ -119,23 +265,27 @@ fn generate_input_type(
             continue;
         }
 
+        // Managed timestamps and generated columns are left at their default
+        if managed.contains(&snake_name) {
+            from_conversion_tokens.push(quote! {
+                #field_ident: Default::default(),
+            });
+            self_conversion_tokens.push(quote! {
+                #field_ident: Default::default(),
+            });
+            continue;
+        }
+
         // Check for from_context option - these fields are excluded from input
         // and populated server-side
         let field_opts = get_cached_graphql_field_options(file_name, msg_name, field_number);
-        if let Some(ref opts) = field_opts {
-            if let Some(ref ctx_source) = opts.from_context {
-                // Track this as a context-injected field
-                context_fields.push(ContextField {
-                    name: snake_name.clone(),
-                    rust_type: proto_type_to_rust_type(field),
-                    path: ctx_source.path.clone(),
-                    required: ctx_source.required,
-                });
-                continue; // Skip from input type
-            }
+        if field_opts.as_ref().is_some_and(|o| o.from_context.is_some()) {
+            continue; // Skip from input type
         }
 
-        let is_optional = field.proto3_optional.unwrap_or(false);
+        // GeoPoint fields keep message presence
+        let is_geo_point = field.type_name.as_deref() == Some(GEO_POINT_TYPE);
+        let is_optional = field.proto3_optional.unwrap_or(false) || is_geo_point;
         let rust_type = proto_type_to_rust_type(field);
 
         let field_type = if is_optional {
@@ -144,16 +294,65 @@ fn generate_input_type(
             quote! { #rust_type }
         };
 
+        let validator = get_cached_validate_field_options(file_name, msg_name, field_number)
+            .filter(|opts| validated && !opts.skip)
+            .and_then(|opts| opts.rules)
+            .map(|rules| generate_validator(field, &rules))
+            .unwrap_or_default();
+
+        // Sanitizers run while parsing, before the validators
+        let sanitize = field_sanitize(file_name, msg_name, field);
+        let process_with = match &sanitize {
+            Some(sanitize) => {
+                let fn_name = format!("sanitize_{}", snake_name);
+                let fn_ident = format_ident!("{}", fn_name);
+                let value = quote! { std::mem::take(value) };
+                let sanitized = if is_optional {
+                    generate_sanitize_optional(value, sanitize)
+                } else {
+                    generate_sanitize(value, sanitize)
+                };
+                sanitizers.push(quote! {
+                    fn #fn_ident(value: &mut #field_type) {
+                        *value = #sanitized;
+                    }
+                });
+                quote! { #[graphql(process_with = #fn_name)] }
+            }
+            None => quote! {},
+        };
+
+        let explicit_name = super::explicit_input_name(file_name, msg_name, field);
         field_tokens.push(quote! {
+            #explicit_name
+            #process_with
+            #validator
             pub #field_ident: #field_type,
         });
 
+        // Inputs built in code skip parsing, so conversions sanitize too
+        let (from_value, self_value) = match sanitize {
+            _ if is_geo_point => (
+                quote! { input.#field_ident.map(Into::into) },
+                quote! { self.#field_ident.map(Into::into) },
+            ),
+            Some(sanitize) if is_optional => (
+                generate_sanitize_optional(quote! { input.#field_ident }, &sanitize),
+                generate_sanitize_optional(quote! { self.#field_ident }, &sanitize),
+            ),
+            Some(sanitize) => (
+                generate_sanitize(quote! { input.#field_ident }, &sanitize),
+                generate_sanitize(quote! { self.#field_ident }, &sanitize),
+            ),
+            None => (quote! { input.#field_ident }, quote! { self.#field_ident }),
+        };
+
         from_conversion_tokens.push(quote! {
-            #field_ident: input.#field_ident,
+            #field_ident: #from_value,
         });
 
         self_conversion_tokens.push(quote! {
-            #field_ident: self.#field_ident,
+            #field_ident: #self_value,
         });
     }
 
@@ -180,7 +379,7 @@ fn generate_input_type(
         // Generate doc comment showing context paths
         let ctx_doc: Vec<_> = context_fields
             .iter()
-            .map(|cf| format!("- `{}`: from context path `{}`", cf.name, cf.path))
+            .map(|cf| format!("- `{}`: from context path `{}`", cf.name, cf.path()))
             .collect();
         let ctx_doc_str = ctx_doc.join("\n    /// ");
 
@@ -191,7 +390,7 @@ fn generate_input_type(
                     ///
                     /// Context-injected fields:
                     #[doc = #ctx_doc_str]
-                    pub fn to_request(self, id: i64, #(#ctx_params),*) -> super::super::#request_ident {
+                    pub fn to_request(self, id: #id_ty, #(#ctx_params),*) -> super::super::#request_ident {
                         super::super::#request_ident {
                             id,
                             #(#ctx_fields_assign,)*
@@ -221,7 +420,7 @@ fn generate_input_type(
         quote! {
             impl #input_ident {
                 /// Convert to proto request with the given id
-                pub fn to_request(self, id: i64) -> super::super::#request_ident {
+                pub fn to_request(self, id: #id_ty) -> super::super::#request_ident {
                     super::super::#request_ident {
                         id,
                         #(#self_conversion_tokens)*
@@ -242,6 +441,7 @@ fn generate_input_type(
         }
     };
 
+    let serde_derives = super::input_serde_derives("camelCase");
     let code = quote! {
         //! Auto-generated GraphQL input type from request message
         //! @generated
@@ -252,20 +452,20 @@ fn generate_input_type(
         use async_graphql::InputObject;
 
         /// GraphQL input type (auto-generated from request message)
+        #serde_derives
         #[derive(InputObject, Default, Clone)]
         pub struct #input_ident {
             #(#field_tokens)*
         }
 
+        #(#sanitizers)*
+
         #from_impl
     };
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Determine output file path
     let output_path = format!(
@@ -281,6 +481,105 @@ fn generate_input_type(
     }))
 }
 
+/// Generate a `#[graphql(validator(...))]` attribute mirroring the validate rules
+///
+/// Only rules async-graphql can check on the value alone are carried over.
+/// Exclusive float bounds have no async-graphql equivalent and are left to
+/// the service. With `graphql_serde=true` the same bounds are added to the
+/// JSON Schema as `#[schemars(...)]` constraints.
+fn generate_validator(field: &FieldDescriptorProto, rules: &validate::Rules) -> TokenStream {
+    let mut args = Vec::new();
+    let mut schema_length = Vec::new();
+    let mut schema_range = Vec::new();
+    let mut schema = Vec::new();
+
+    if field.r#type() == Type::String {
+        if let Some(ref length) = rules.length {
+            let min = length.equal.or(length.min).filter(|n| *n > 0);
+            let max = length.equal.or(length.max).filter(|n| *n > 0);
+            if let Some(min) = min {
+                let min = Literal::usize_unsuffixed(min as usize);
+                args.push(quote! { min_length = #min });
+                schema_length.push(quote! { min = #min });
+            }
+            if let Some(max) = max {
+                let max = Literal::usize_unsuffixed(max as usize);
+                args.push(quote! { max_length = #max });
+                schema_length.push(quote! { max = #max });
+            }
+        }
+        if !rules.pattern.is_empty() {
+            let pattern = &rules.pattern;
+            args.push(quote! { regex = #pattern });
+            schema.push(quote! { regex(pattern = #pattern) });
+        }
+    }
+
+    if let Some(ref range) = rules.range {
+        let is_int = matches!(
+            field.r#type(),
+            Type::Int64
+                | Type::Sfixed64
+                | Type::Sint64
+                | Type::Uint64
+                | Type::Fixed64
+                | Type::Int32
+                | Type::Sfixed32
+                | Type::Sint32
+                | Type::Uint32
+                | Type::Fixed32
+        );
+        let is_float = matches!(field.r#type(), Type::Double | Type::Float);
+        let (lower, upper) = range_bounds(range);
+
+        // Integer bounds are tightened to the nearest value actually allowed
+        let bound = |bound: Bound, lower: bool| -> Option<TokenStream> {
+            if is_int {
+                let n = match (lower, bound.exclusive) {
+                    (true, false) => bound.value.ceil(),
+                    (true, true) => bound.value.floor() + 1.0,
+                    (false, false) => bound.value.floor(),
+                    (false, true) => bound.value.ceil() - 1.0,
+                };
+                let lit = Literal::i64_unsuffixed(n as i64);
+                Some(quote! { #lit })
+            } else if is_float && !bound.exclusive {
+                let lit = Literal::f64_unsuffixed(bound.value);
+                Some(quote! { #lit })
+            } else {
+                None
+            }
+        };
+        if let Some(min) = lower.and_then(|b| bound(b, true)) {
+            args.push(quote! { minimum = #min });
+            schema_range.push(quote! { min = #min });
+        }
+        if let Some(max) = upper.and_then(|b| bound(b, false)) {
+            args.push(quote! { maximum = #max });
+            schema_range.push(quote! { max = #max });
+        }
+    }
+
+    if args.is_empty() {
+        return quote! {};
+    }
+    if !schema_length.is_empty() {
+        schema.push(quote! { length(#(#schema_length),*) });
+    }
+    if !schema_range.is_empty() {
+        schema.push(quote! { range(#(#schema_range),*) });
+    }
+    let schema = if crate::params::graphql_serde() {
+        quote! { #[schemars(#(#schema),*)] }
+    } else {
+        quote! {}
+    };
+    quote! {
+        #[graphql(validator(#(#args),*))]
+        #schema
+    }
+}
+
 /// Convert proto field type to Rust type
 fn proto_type_to_rust_type(field: &FieldDescriptorProto) -> proc_macro2::TokenStream {
     let proto_type = field.r#type();
@@ -300,6 +599,9 @@ fn proto_type_to_rust_type(field: &FieldDescriptorProto) -> proc_macro2::TokenSt
                 if type_name.contains("Timestamp") {
                     return quote! { String };
                 }
+                if type_name == GEO_POINT_TYPE {
+                    return quote! { super::super::super::synapse::relay::graphql::GeoPointInput };
+                }
                 let name = type_name
                     .rsplit('.')
                     .next()
@@ -313,3 +615,191 @@ fn proto_type_to_rust_type(field: &FieldDescriptorProto) -> proc_macro2::TokenSt
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{field, message_field, repeated};
+
+
+    #[test]
+    fn test_generate_validator_string() {
+        let rules = validate::Rules {
+            length: Some(validate::LengthConstraint {
+                min: Some(3),
+                max: Some(50),
+                equal: None,
+            }),
+            pattern: "^[a-z]+$".to_string(),
+            ..Default::default()
+        };
+        let code = generate_validator(&field("value", 1, Type::String), &rules).to_string();
+        assert!(code.contains("min_length = 3"));
+        assert!(code.contains("max_length = 50"));
+        assert!(code.contains("regex = \"^[a-z]+$\""));
+    }
+
+    #[test]
+    fn test_generate_validator_int_range() {
+        let rules = validate::Rules {
+            range: Some(validate::RangeConstraint {
+                greater_than: Some(0.0),
+                max: Some(10.5),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let code = generate_validator(&field("value", 1, Type::Int32), &rules).to_string();
+        assert!(code.contains("minimum = 1"));
+        assert!(code.contains("maximum = 10"));
+    }
+
+    #[test]
+    fn test_generate_validator_skips_exclusive_float() {
+        let rules = validate::Rules {
+            range: Some(validate::RangeConstraint {
+                greater_than: Some(0.0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(generate_validator(&field("value", 1, Type::Double), &rules).is_empty());
+    }
+
+    #[test]
+    fn test_generated_columns_of_entity_in_another_file() {
+        use crate::storage::seaorm::options::{cache_column_options, storage};
+
+        let entities = FileDescriptorProto {
+            name: Some("input_test/entities.proto".to_string()),
+            message_type: vec![prost_types::DescriptorProto {
+                name: Some("Tag".to_string()),
+                field: vec![field("name", 1, Type::String), field("slug", 2, Type::String)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let request = prost_types::DescriptorProto {
+            name: Some("CreateTagRequest".to_string()),
+            field: vec![field("name", 1, Type::String), field("slug", 2, Type::String)],
+            ..Default::default()
+        };
+        let services = FileDescriptorProto {
+            name: Some("input_test/services.proto".to_string()),
+            package: Some("input_test".to_string()),
+            message_type: vec![request.clone()],
+            ..Default::default()
+        };
+        cache_column_options(
+            "input_test/entities.proto",
+            "Tag",
+            2,
+            storage::ColumnOptions {
+                generated_expr: "lower(name)".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let all_files = [entities, services.clone()];
+        let file = generate_input_type(&services, &request, "CreateTagInput", false, true, &all_files)
+            .unwrap()
+            .unwrap();
+        let code = file.content.unwrap();
+        assert!(code.contains("pub name: String"));
+        assert!(!code.contains("pub slug"));
+        assert!(code.contains("slug: Default::default()"));
+    }
+
+    #[test]
+    fn test_sanitize_runs_while_parsing() {
+        use crate::storage::seaorm::options::cache_validate_field_options;
+
+        let request = prost_types::DescriptorProto {
+            name: Some("CreateMemberRequest".to_string()),
+            field: vec![field("email", 1, Type::String)],
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("input_sanitize_test.proto".to_string()),
+            package: Some("input_sanitize_test".to_string()),
+            message_type: vec![request.clone()],
+            ..Default::default()
+        };
+        cache_validate_field_options(
+            "input_sanitize_test.proto",
+            "CreateMemberRequest",
+            1,
+            validate::FieldOptions {
+                rules: Some(validate::Rules {
+                    length: Some(validate::LengthConstraint {
+                        min: Some(3),
+                        max: None,
+                        equal: None,
+                    }),
+                    ..Default::default()
+                }),
+                sanitize: Some(validate::Sanitize {
+                    trim: true,
+                    lowercase: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        );
+
+        let code = generate_input_type(&file, &request, "CreateMemberInput", false, true, std::slice::from_ref(&file))
+            .unwrap()
+            .unwrap()
+            .content
+            .unwrap();
+        let code: String = code.split_whitespace().collect();
+        assert!(code.contains("#[graphql(process_with=\"sanitize_email\")]#[graphql(validator(min_length=3))]pubemail:String"));
+        assert!(code.contains("fnsanitize_email(value:&mutString){*value={letvalue:String=std::mem::take(value);"));
+
+        // Unvalidated inputs still sanitize
+        let code = generate_input_type(&file, &request, "CreateMemberInput", false, false, std::slice::from_ref(&file))
+            .unwrap()
+            .unwrap()
+            .content
+            .unwrap();
+        let code: String = code.split_whitespace().collect();
+        assert!(code.contains("#[graphql(process_with=\"sanitize_email\")]pubemail:String"));
+        assert!(!code.contains("validator"));
+    }
+
+    #[test]
+    fn test_create_many_input() {
+        use crate::storage::seaorm::batch::BATCH_ERROR_TYPE;
+
+        let message = |name: &str, fields: Vec<FieldDescriptorProto>| prost_types::DescriptorProto {
+            name: Some(name.to_string()),
+            field: fields,
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("create_many_input.proto".to_string()),
+            package: Some("blog".to_string()),
+            message_type: vec![
+                message("CreateManyPostsRequest", vec![repeated(message_field("items", 1, ".blog.CreatePostRequest"))]),
+                message("CreateManyPostsResponse", vec![repeated(message_field("posts", 1, ".blog.Post"))]),
+                message("ImportManyPostsResponse", vec![repeated(message_field("errors", 1, BATCH_ERROR_TYPE))]),
+            ],
+            ..Default::default()
+        };
+        let method = |output: &str| MethodDescriptorProto {
+            name: Some("CreateManyPosts".to_string()),
+            input_type: Some(".blog.CreateManyPostsRequest".to_string()),
+            output_type: Some(format!(".blog.{}", output)),
+            ..Default::default()
+        };
+
+        let shared = create_many_input(&file, &method("CreateManyPostsResponse")).unwrap();
+        assert_eq!((shared.request.as_str(), shared.input.as_str()), ("CreatePostRequest", "CreatePostInput"));
+        assert!(!shared.per_item_errors);
+
+        // Items of batches reporting per-item errors get their own input
+        let own = create_many_input(&file, &method("ImportManyPostsResponse")).unwrap();
+        assert_eq!((own.request.as_str(), own.input.as_str()), ("CreatePostRequest", "CreateManyPostsItemInput"));
+        assert!(own.per_item_errors);
+    }
+}
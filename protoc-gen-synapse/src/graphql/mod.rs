@@ -9,19 +9,52 @@
 //! - Combined schema with Query/Mutation/Subscription
 //! - Auto-generated filter types (IntFilter, StringFilter, etc.)
 //! - Auto-generated connection types (PageInfo, Edge, Connection)
+//! - Per-item results of partial batch mutations (BatchError)
+//! - Context-injected request fields resolved through a ContextResolver
+//! - Gateway HTTP wiring (request limits, CORS, CSRF for the axum router)
+//! - Admin CRUD UI served from the package registry
+//! - Full-text search resolvers backed by an external index
+//! - Fault injection for the resolvers' gRPC clients (test/staging)
+//! - Record/replay of the resolvers' gRPC calls for gateway tests
+//! - Per-document concurrency budgets for the resolvers' gRPC calls
+//! - Partial degradation of non-critical relations when a service fails
+//! - SDL compatibility checks against a committed schema snapshot
+//! - Apollo Federation entity resolvers batched through the DataLoaders
+//! - Subscriptions streaming entity change events from the outbox
 
+mod admin;
+mod batch;
+mod cassette;
+mod chaos;
+mod compat;
+mod concurrency;
 mod connection;
+mod context;
 mod dataloader;
+mod degrade;
+mod event_filter;
+mod federation;
 mod filter;
+mod gateway;
 mod input;
 mod node;
 mod object;
 mod resolver;
 mod schema;
+mod search;
+mod subscription;
+
+pub(crate) use node::package_id_prefixes;
+pub(crate) use object::object_field_graphql_names;
 
 use crate::error::GeneratorError;
+use crate::storage::seaorm::options::{get_cached_graphql_field_options, get_cached_graphql_mutation_options};
+use heck::{ToLowerCamelCase, ToSnakeCase};
+use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
-use prost_types::{DescriptorProto, FileDescriptorProto, ServiceDescriptorProto};
+use prost_types::field_descriptor_proto::Type;
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto, ServiceDescriptorProto};
+use quote::quote;
 
 /// Generate GraphQL Object types from a message
 #[allow(dead_code)]
@@ -37,15 +70,19 @@ pub fn generate_message(
 pub fn generate_service(
     file: &FileDescriptorProto,
     service: &ServiceDescriptorProto,
+    all_files: &[FileDescriptorProto],
 ) -> Result<Vec<File>, GeneratorError> {
-    resolver::generate(file, service)
+    resolver::generate(file, service, all_files)
 }
 
 /// Generate the Relay Node interface for a file
 #[allow(dead_code)]
-pub fn generate_node_interface(file: &FileDescriptorProto) -> Result<Option<File>, GeneratorError> {
+pub fn generate_node_interface(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
     let node_types = node::collect_node_types(file);
-    node::generate_node_interface(file, &node_types)
+    node::generate_node_interface(file, all_files, &node_types)
 }
 
 /// Generate DataLoaders for a message (based on its relations)
@@ -65,8 +102,9 @@ pub fn generate_dataloaders(
 pub fn generate_entity_loader(
     file: &FileDescriptorProto,
     message: &DescriptorProto,
+    all_files: &[FileDescriptorProto],
 ) -> Result<Option<File>, GeneratorError> {
-    dataloader::generate_entity_loader(file, message)
+    dataloader::generate_entity_loader(file, message, all_files)
 }
 
 /// Generate the unified GraphQL schema mod.rs for a file
@@ -84,6 +122,107 @@ pub fn generate_schema(
     schema::generate(file, all_files)
 }
 
+/// Generate the gateway HTTP wiring for a file
+///
+/// Creates graphql/gateway.rs with GatewayConfig, which applies body size,
+/// timeout, and concurrency limits plus CORS and CSRF protection to the
+/// router serving the schema.
+pub fn generate_gateway(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    gateway::generate(file, all_files)
+}
+
+/// Generate the fault-injection layer for a file
+///
+/// Creates graphql/chaos.rs with FaultConfig and FaultLayer, which delay or
+/// fail calls made by the resolvers' gRPC clients. Only with `chaos=true`.
+pub fn generate_chaos(file: &FileDescriptorProto) -> Result<Option<File>, GeneratorError> {
+    chaos::generate(file)
+}
+
+/// Generate record/replay of resolver gRPC calls for a file
+///
+/// Creates graphql/cassette.rs with Cassette, which stores request/response
+/// pairs as files. Only with `record_replay=true`.
+pub fn generate_cassette(file: &FileDescriptorProto) -> Result<Option<File>, GeneratorError> {
+    cassette::generate(file)
+}
+
+/// Generate the per-document concurrency budget for a file
+///
+/// Creates graphql/concurrency.rs with the ConcurrencyLimit extension, which
+/// caps the resolvers' in-flight gRPC calls per document using the root
+/// fields' `max_concurrency`.
+pub fn generate_concurrency(file: &FileDescriptorProto) -> Result<Option<File>, GeneratorError> {
+    concurrency::generate(file)
+}
+
+/// Generate the schema compatibility checks for a file
+///
+/// Creates graphql/compat.rs with `check_compat` and `check_snapshot`, plus a
+/// snapshot test when `graphql_snapshot` is set.
+pub fn generate_compat(file: &FileDescriptorProto) -> Result<Option<File>, GeneratorError> {
+    compat::generate(file)
+}
+
+/// Generate partial degradation for a file's package
+///
+/// Creates graphql/degrade.rs with `degrade` and the DegradedWarnings
+/// extension when the package has `non_critical` relations.
+pub fn generate_degrade(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    degrade::generate(file, all_files)
+}
+
+/// Generate the admin back-office router for a file
+///
+/// Creates graphql/admin.rs, serving a generic CRUD UI at `/admin` built from
+/// the package registry and the services' GraphQL operations.
+pub fn generate_admin(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    admin::generate(file, all_files)
+}
+
+/// Generate the full-text search resolvers for a file
+///
+/// Creates graphql/search.rs with a `SearchQuery` object that queries the
+/// package's search index and hydrates hits through the entity DataLoaders.
+pub fn generate_search(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    search::generate(file, all_files)
+}
+
+/// Generate the federation entity resolvers for a file
+///
+/// Creates graphql/federation.rs with a `FederationQuery` resolving node
+/// entities for `_entities` through their DataLoaders. Only with
+/// `federation=true`.
+pub fn generate_federation(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    federation::generate(file, all_files)
+}
+
+/// Generate the entity change subscriptions for a file
+///
+/// Creates graphql/subscription.rs with the `Subscription` root streaming
+/// `emit_events` entity changes. Only with `subscriptions=true`.
+pub fn generate_subscription(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    subscription::generate(file, all_files)
+}
+
 /// Generate auto-generated filter types for entities in a package
 ///
 /// Creates:
@@ -103,13 +242,16 @@ pub fn generate_filters(
 ///
 /// Creates:
 /// - PageInfo type
+/// - BatchError type of partial batch mutations
+/// - ContextResolver types, when a message of `all_files` has `from_context` fields
 /// - Entity Edge types (UserEdge, PostEdge)
 /// - Entity Connection types (UserConnection, PostConnection)
 pub fn generate_connections(
     file: &FileDescriptorProto,
     entities: &[&DescriptorProto],
+    all_files: &[FileDescriptorProto],
 ) -> Result<Vec<File>, GeneratorError> {
-    connection::generate_connections_for_package(file, entities)
+    connection::generate_connections_for_package(file, entities, all_files)
 }
 
 /// Generate auto-generated input types from mutation request messages
@@ -120,6 +262,113 @@ pub fn generate_connections(
 pub fn generate_inputs(
     file: &FileDescriptorProto,
     service: &ServiceDescriptorProto,
+    all_files: &[FileDescriptorProto],
 ) -> Result<Vec<File>, GeneratorError> {
-    input::generate_inputs_for_service(file, service)
+    input::generate_inputs_for_service(file, service, all_files)
+}
+
+/// Rust type of an integer-or-UUID key field (`id` or a foreign key)
+///
+/// UUID keys travel as strings; everything else is an `i64`.
+pub(crate) fn key_type(message: Option<&DescriptorProto>, field: &str) -> TokenStream {
+    if is_string_key(message, field) {
+        quote! { String }
+    } else {
+        quote! { i64 }
+    }
+}
+
+/// Relay filter type matching a key field (`IntFilter` or `StringFilter`)
+pub(crate) fn key_filter_type(message: Option<&DescriptorProto>, field: &str) -> TokenStream {
+    if is_string_key(message, field) {
+        quote! { StringFilter }
+    } else {
+        quote! { IntFilter }
+    }
+}
+
+/// Whether a field of an object type is named like a generated Relay field
+///
+/// `edges` and `page_info` belong to connections, `cursor` and `node` to edges,
+/// and `id` to Node types; elsewhere they make a type look like one of those to
+/// Relay clients and to the generated smoke tests.
+pub(crate) fn shadows_relay_field(msg_name: &str, is_node: bool, field_name: &str) -> bool {
+    match field_name.to_snake_case().as_str() {
+        "edges" | "page_info" => !msg_name.ends_with("Connection"),
+        "cursor" | "node" => !msg_name.ends_with("Edge"),
+        "id" => !is_node,
+        _ => false,
+    }
+}
+
+/// GraphQL method name of a shadowing field under `rename_shadowed_fields`
+pub(crate) fn renamed_shadowed_field(msg_name: &str, field_name: &str) -> String {
+    format!("{}_{}", msg_name.to_snake_case(), field_name.to_snake_case())
+}
+
+/// GraphQL name of an input field: its `name` override, or the camelCased proto name
+pub(crate) fn input_field_name(file_name: &str, msg_name: &str, field: &FieldDescriptorProto) -> String {
+    get_cached_graphql_field_options(file_name, msg_name, field.number.unwrap_or(0))
+        .map(|o| o.name)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| field.name.as_deref().unwrap_or("").to_lower_camel_case())
+}
+
+/// Attributes naming an input field explicitly under `graphql_names=explicit`
+///
+/// Under `graphql_serde=true` the serde name follows the GraphQL name.
+pub(crate) fn explicit_input_name(file_name: &str, msg_name: &str, field: &FieldDescriptorProto) -> TokenStream {
+    if !crate::params::graphql_explicit_names() {
+        return quote! {};
+    }
+    let name = input_field_name(file_name, msg_name, field);
+    let serde_rename = if crate::params::graphql_serde() {
+        quote! { #[serde(rename = #name)] }
+    } else {
+        quote! {}
+    };
+    quote! {
+        #[graphql(name = #name)]
+        #serde_rename
+    }
+}
+
+/// serde and schemars derives for a GraphQL input type under `graphql_serde=true`
+///
+/// Names are renamed with `rename_all` to match the GraphQL names, so a
+/// persisted value reads the same as the variables a client sends.
+pub(crate) fn input_serde_derives(rename_all: &str) -> TokenStream {
+    if !crate::params::graphql_serde() {
+        return quote! {};
+    }
+    quote! {
+        #[derive(serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+        #[serde(rename_all = #rename_all)]
+    }
+}
+
+/// Whether a mutation has `dry_run: true`
+pub(crate) fn is_dry_run(file_name: &str, service_name: &str, method_name: &str) -> bool {
+    get_cached_graphql_mutation_options(file_name, service_name, method_name).is_some_and(|o| o.dry_run)
+}
+
+/// Whether any mutation of the main file's package has `dry_run: true`
+pub(crate) fn package_has_dry_run(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> bool {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.service.iter().any(|s| {
+                let service_name = s.name.as_deref().unwrap_or("");
+                s.method.iter().any(|m| is_dry_run(file_name, service_name, m.name.as_deref().unwrap_or("")))
+            })
+        })
+}
+
+fn is_string_key(message: Option<&DescriptorProto>, field: &str) -> bool {
+    message
+        .and_then(|m| m.field.iter().find(|f| f.name.as_deref() == Some(field)))
+        .is_some_and(|f| f.r#type() == Type::String)
 }
@@ -1,10 +1,12 @@
 //! Relay Node interface generation
 //!
 //! Generates the Node interface for types marked with `node: true`.
-//! Provides global ID encoding/decoding using base62 for URL-safe IDs.
+//! Global IDs and cursors are base62 (hex in minimal mode), or Stripe-style
+//! `{prefix}_{id}` for types listed in the package's `id_prefixes`. Decoding
+//! also accepts the base64url `Type:id` of the types' `id` fields.
 
 use crate::error::GeneratorError;
-use crate::storage::seaorm::options::get_cached_graphql_type_options;
+use crate::storage::seaorm::options::{get_cached_graphql_id_prefixes, get_cached_graphql_type_options};
 use heck::ToUpperCamelCase;
 use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
@@ -35,11 +37,81 @@ pub fn collect_node_types(
     node_types
 }
 
+/// GraphQL names of the node types in the main file's package
+fn package_node_types(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> Vec<String> {
+    all_files
+        .iter()
+        .filter(|f| f.package == main_file.package)
+        .flat_map(|f| collect_node_types(f).into_iter().map(|(_, type_name)| type_name))
+        .collect()
+}
+
+/// Validated global ID prefixes of the main file's package, as (type name, prefix)
+pub fn package_id_prefixes(
+    all_files: &[FileDescriptorProto],
+    main_file: &FileDescriptorProto,
+) -> Result<Vec<(String, String)>, GeneratorError> {
+    let package = main_file.package.as_deref().unwrap_or("");
+    id_prefixes(package, &package_node_types(all_files, main_file))
+}
+
+/// Validate the global ID prefixes of a package against its `node_types`
+fn id_prefixes(package: &str, node_types: &[String]) -> Result<Vec<(String, String)>, GeneratorError> {
+    let prefixes = get_cached_graphql_id_prefixes(package);
+    for (i, (type_name, prefix)) in prefixes.iter().enumerate() {
+        if type_name.is_empty() {
+            return Err(GeneratorError::CodeGenError(format!(
+                "id prefix '{}' of package '{}' has no type",
+                prefix, package
+            )));
+        }
+        if !node_types.contains(type_name) {
+            return Err(GeneratorError::CodeGenError(format!(
+                "id prefix '{}' is set on '{}', which is not a node type of package '{}'; \
+                 only types with `node: true` have global IDs",
+                prefix, type_name, package
+            )));
+        }
+        if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()) {
+            return Err(GeneratorError::CodeGenError(format!(
+                "id prefix '{}' of type '{}' must be lowercase letters and digits",
+                prefix, type_name
+            )));
+        }
+        if let Some((other, _)) = prefixes[..i].iter().find(|(_, p)| p == prefix) {
+            return Err(GeneratorError::CodeGenError(format!(
+                "id prefix '{}' is used by both '{}' and '{}'",
+                prefix, other, type_name
+            )));
+        }
+        if prefixes[..i].iter().any(|(t, _)| t == type_name) {
+            return Err(GeneratorError::CodeGenError(format!(
+                "type '{}' has more than one id prefix",
+                type_name
+            )));
+        }
+    }
+    Ok(prefixes)
+}
+
+/// Global ID prefix of a type, if its package sets one
+pub fn id_prefix(package: &str, type_name: &str) -> Option<String> {
+    get_cached_graphql_id_prefixes(package)
+        .into_iter()
+        .find(|(t, _)| t == type_name)
+        .map(|(_, prefix)| prefix)
+}
+
 /// Generate the Node interface enum and node query
+///
+/// The package's ID prefixes are validated first, so prefixes on types that
+/// are not nodes fail generation even without a Node interface.
 pub fn generate_node_interface(
     file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
     node_types: &[(&DescriptorProto, String)],
 ) -> Result<Option<File>, GeneratorError> {
+    let prefixes = package_id_prefixes(all_files, file)?;
     if node_types.is_empty() {
         return Ok(None);
     }
@@ -53,6 +125,36 @@ pub fn generate_node_interface(
     // Generate nodes (batch) query resolver
     let nodes_resolver = generate_nodes_resolver(node_types);
 
+    // Registry of the package's Stripe-style ID prefixes: (prefix, type name)
+    let prefix_entries = prefixes
+        .iter()
+        .map(|(type_name, prefix)| quote! { (#prefix, #type_name) });
+
+    // base62 by default; minimal mode hex-encodes with std only. Global IDs
+    // also decode from the base64url of the types' `id` fields (hex in
+    // minimal mode, which the opaque decoding already covers).
+    let (encode, decode, decode_field_id) = if crate::params::minimal() {
+        (
+            quote! { bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>() },
+            quote! {
+                (0..s.len())
+                    .step_by(2)
+                    .map(|i| s.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok()))
+                    .collect::<Option<Vec<u8>>>()
+            },
+            quote! { None },
+        )
+    } else {
+        (
+            quote! { base62::encode(bytes) },
+            quote! { base62::decode(s).ok() },
+            quote! {{
+                use base64::Engine;
+                base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(s).ok()
+            }},
+        )
+    };
+
     let code = quote! {
         //! Relay Node interface
         //! @generated
@@ -79,30 +181,71 @@ pub fn generate_node_interface(
             #nodes_resolver
         }
 
-        /// Encode a local ID to a global Relay ID using base62
-        pub fn encode_global_id(type_name: &str, local_id: i64) -> ID {
-            let raw = format!("{}:{}", type_name, local_id);
-            ID(base62::encode(raw.as_bytes()))
+        /// Encode bytes into an opaque, URL-safe ID string
+        fn encode_opaque(bytes: &[u8]) -> String {
+            #encode
         }
 
-        /// Decode a global Relay ID to type name and local ID
-        pub fn decode_global_id(id: &ID) -> Option<(String, i64)> {
-            let bytes = base62::decode(id.as_str()).ok()?;
+        /// Decode an opaque ID string back into bytes
+        fn decode_opaque(s: &str) -> Option<Vec<u8>> {
+            #decode
+        }
+
+        /// Stripe-style global ID prefixes and their types; other types
+        /// have opaque `Type:id` global IDs
+        pub const ID_PREFIXES: &[(&str, &str)] = &[#(#prefix_entries),*];
+
+        /// Decode the base64url `Type:id` of a type's `id` field
+        fn decode_field_id(s: &str) -> Option<Vec<u8>> {
+            #decode_field_id
+        }
+
+        /// Split a decoded `Type:id` global ID
+        fn split_global<K: std::str::FromStr>(bytes: Vec<u8>) -> Option<(String, K)> {
             let s = String::from_utf8(bytes).ok()?;
             let (type_name, local_id) = s.split_once(':')?;
-            let id = local_id.parse().ok()?;
-            Some((type_name.to_string(), id))
+            if type_name.is_empty() || !type_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return None;
+            }
+            Some((type_name.to_string(), local_id.parse().ok()?))
+        }
+
+        /// Encode a local ID (integer or UUID) to a global Relay ID
+        pub fn encode_global_id(type_name: &str, local_id: impl std::fmt::Display) -> ID {
+            if let Some((prefix, _)) = ID_PREFIXES.iter().find(|(_, t)| *t == type_name) {
+                return ID(format!("{}_{}", prefix, local_id));
+            }
+            let raw = format!("{}:{}", type_name, local_id);
+            ID(encode_opaque(raw.as_bytes()))
+        }
+
+        /// Decode a global Relay ID to type name and local ID
+        ///
+        /// Accepts the opaque IDs of `encode_global_id`, the base64url IDs of
+        /// the types' `id` fields, and prefixed IDs. Base64url IDs may contain
+        /// `_`, so prefixes are only matched once the opaque decodings fail;
+        /// types with a prefix never have opaque IDs.
+        pub fn decode_global_id<K: std::str::FromStr>(id: &ID) -> Option<(String, K)> {
+            let opaque = decode_opaque(id.as_str())
+                .and_then(split_global)
+                .or_else(|| decode_field_id(id.as_str()).and_then(split_global))
+                .filter(|(type_name, _)| ID_PREFIXES.iter().all(|(_, t)| t != type_name));
+            if opaque.is_some() {
+                return opaque;
+            }
+            let (prefix, local_id) = id.as_str().split_once('_')?;
+            let (_, type_name) = ID_PREFIXES.iter().find(|(p, _)| *p == prefix)?;
+            Some((type_name.to_string(), local_id.parse().ok()?))
         }
 
         /// Cursor encoding for pagination
-        pub fn encode_cursor(id: i64) -> String {
-            base62::encode(id.to_string().as_bytes())
+        pub fn encode_cursor(id: impl std::fmt::Display) -> String {
+            encode_opaque(id.to_string().as_bytes())
         }
 
         /// Cursor decoding for pagination
-        pub fn decode_cursor(cursor: &str) -> Option<i64> {
-            base62::decode(cursor)
-                .ok()
+        pub fn decode_cursor<K: std::str::FromStr>(cursor: &str) -> Option<K> {
+            decode_opaque(cursor)
                 .and_then(|b| String::from_utf8(b).ok())
                 .and_then(|s| s.parse().ok())
         }
@@ -110,10 +253,7 @@ pub fn generate_node_interface(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Determine output file path
     let package = file.package.as_deref().unwrap_or("");
@@ -144,13 +284,17 @@ fn generate_node_resolver(node_types: &[(&DescriptorProto, String)]) -> TokenStr
     // Generate match arms for each type
     let match_arms: Vec<_> = node_types
         .iter()
-        .map(|(_, type_name)| {
+        .map(|(message, type_name)| {
             let type_str = type_name.as_str();
             let type_ident = format_ident!("{}", type_name);
             let loader_ident = format_ident!("{}Loader", type_name);
+            let key_ty = super::key_type(Some(message), "id");
 
             quote! {
                 #type_str => {
+                    let local_id = local_id
+                        .parse::<#key_ty>()
+                        .map_err(|_| async_graphql::Error::new("Invalid node ID"))?;
                     let loader = ctx.data_unchecked::<async_graphql::dataloader::DataLoader<#loader_ident>>();
                     let entity = loader.load_one(local_id).await?;
                     Ok(entity.map(Node::#type_ident))
@@ -162,7 +306,7 @@ fn generate_node_resolver(node_types: &[(&DescriptorProto, String)]) -> TokenStr
     quote! {
         /// Fetch any node by its global ID
         async fn node(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Node>> {
-            let (type_name, local_id) = decode_global_id(&id)
+            let (type_name, local_id) = decode_global_id::<String>(&id)
                 .ok_or_else(|| async_graphql::Error::new("Invalid node ID"))?;
 
             match type_name.as_str() {
@@ -178,17 +322,21 @@ fn generate_nodes_resolver(node_types: &[(&DescriptorProto, String)]) -> TokenSt
     // Generate match arms for each type (batch version)
     let match_arms: Vec<_> = node_types
         .iter()
-        .map(|(_, type_name)| {
+        .map(|(message, type_name)| {
             let type_str = type_name.as_str();
             let type_ident = format_ident!("{}", type_name);
             let loader_ident = format_ident!("{}Loader", type_name);
+            let key_ty = super::key_type(Some(message), "id");
 
             quote! {
-                #type_str => {
-                    let loader = ctx.data_unchecked::<async_graphql::dataloader::DataLoader<#loader_ident>>();
-                    let entity = loader.load_one(local_id).await?;
-                    results.push(entity.map(Node::#type_ident));
-                }
+                #type_str => match local_id.parse::<#key_ty>() {
+                    Ok(local_id) => {
+                        let loader = ctx.data_unchecked::<async_graphql::dataloader::DataLoader<#loader_ident>>();
+                        let entity = loader.load_one(local_id).await?;
+                        results.push(entity.map(Node::#type_ident));
+                    }
+                    Err(_) => results.push(None),
+                },
             }
         })
         .collect();
@@ -199,7 +347,7 @@ fn generate_nodes_resolver(node_types: &[(&DescriptorProto, String)]) -> TokenSt
             let mut results = Vec::with_capacity(ids.len());
 
             for id in ids {
-                let parsed = decode_global_id(&id);
+                let parsed = decode_global_id::<String>(&id);
 
                 if let Some((type_name, local_id)) = parsed {
                     match type_name.as_str() {
@@ -224,11 +372,11 @@ pub fn generate_type_node_impl(type_name: &str) -> TokenStream {
     quote! {
         /// Relay global ID
         pub fn global_id(&self) -> async_graphql::ID {
-            super::node::encode_global_id(#type_name_str, self.id)
+            super::node::encode_global_id(#type_name_str, &self.id)
         }
 
         /// Decode global Relay ID to local ID
-        pub fn from_global_id(id: &async_graphql::ID) -> Option<i64> {
+        pub fn from_global_id<K: std::str::FromStr>(id: &async_graphql::ID) -> Option<K> {
             let (type_name, local_id) = super::node::decode_global_id(id)?;
             if type_name != #type_name_str {
                 return None;
@@ -237,3 +385,99 @@ pub fn generate_type_node_impl(type_name: &str) -> TokenStream {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::seaorm::options::{
+        cache_file_package, cache_graphql_file_options, cache_graphql_type_options, graphql,
+    };
+
+    /// A file of `package` declaring `messages`, the first of them a node type
+    fn node_file(file_name: &str, package: &str, messages: &[&str], prefixes: &[(&str, &str)]) -> FileDescriptorProto {
+        cache_file_package(file_name, package);
+        cache_graphql_type_options(
+            file_name,
+            messages[0],
+            graphql::TypeOptions { node: true, ..Default::default() },
+        );
+        cache_graphql_file_options(
+            file_name,
+            graphql::FileOptions {
+                id_prefixes: prefixes
+                    .iter()
+                    .map(|(type_name, prefix)| graphql::IdPrefix {
+                        r#type: type_name.to_string(),
+                        prefix: prefix.to_string(),
+                    })
+                    .collect(),
+            },
+        );
+        FileDescriptorProto {
+            name: Some(file_name.to_string()),
+            package: Some(package.to_string()),
+            message_type: messages
+                .iter()
+                .map(|name| DescriptorProto {
+                    name: Some(name.to_string()),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_package_id_prefixes() {
+        let file = node_file("node_prefixes.proto", "node_prefixes", &["User", "Session"], &[("User", "usr")]);
+        let prefixes = package_id_prefixes(std::slice::from_ref(&file), &file).unwrap();
+        assert_eq!(prefixes, vec![("User".to_string(), "usr".to_string())]);
+    }
+
+    #[test]
+    fn test_package_id_prefixes_rejects_non_node_types() {
+        let file = node_file("node_non_node.proto", "node_non_node", &["User", "Session"], &[("Session", "ses")]);
+        let err = package_id_prefixes(std::slice::from_ref(&file), &file).unwrap_err().to_string();
+        assert!(err.contains("'ses' is set on 'Session', which is not a node type"));
+
+        // Also without any node type in the file
+        let err = generate_node_interface(&file, std::slice::from_ref(&file), &[]).unwrap_err().to_string();
+        assert!(err.contains("not a node type"));
+    }
+
+    #[test]
+    fn test_package_id_prefixes_rejects_invalid_prefixes() {
+        let file = node_file("node_invalid.proto", "node_invalid", &["User"], &[("User", "Usr")]);
+        let err = package_id_prefixes(std::slice::from_ref(&file), &file).unwrap_err().to_string();
+        assert!(err.contains("must be lowercase letters and digits"));
+    }
+
+    #[test]
+    fn test_generate_node_interface_emits_global_id_codec() {
+        let file = node_file("node_round_trip.proto", "node_round_trip", &["User", "Post"], &[("User", "usr")]);
+        let node_types = collect_node_types(&file);
+        let generated = generate_node_interface(&file, std::slice::from_ref(&file), &node_types).unwrap().unwrap();
+        let code = generated.content.unwrap();
+        let compact: String = code.split_whitespace().collect();
+
+        // Prefixed IDs are written and read through the same registry
+        assert!(code.contains(r#"pub const ID_PREFIXES: &[(&str, &str)] = &[("usr", "User")];"#));
+        assert!(code.contains(r#"return ID(format!("{}_{}", prefix, local_id));"#));
+        assert!(code.contains("ID_PREFIXES.iter().find(|(p, _)| *p == prefix)?"));
+
+        // Other IDs keep base62, and decode from base62 or the `id` fields' base64url
+        assert!(code.contains("ID(encode_opaque(raw.as_bytes()))"));
+        assert!(code.contains("base62::encode(bytes)"));
+        assert!(code.contains("URL_SAFE_NO_PAD.decode(s).ok()"));
+        assert!(!code.contains("URL_SAFE_NO_PAD.encode"));
+
+        // Base64url IDs may contain `_`: opaque decodings (never of prefixed
+        // types) are tried before the prefix registry
+        let opaque = compact
+            .find("letopaque=decode_opaque(id.as_str()).and_then(split_global).or_else(||decode_field_id(id.as_str()).and_then(split_global))")
+            .unwrap();
+        let prefixed = compact.find("let(prefix,local_id)=id.as_str().split_once('_')?;").unwrap();
+        assert!(opaque < prefixed);
+        assert!(compact.contains("ID_PREFIXES.iter().all(|(_,t)|t!=type_name)"));
+    }
+}
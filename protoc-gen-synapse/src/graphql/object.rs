@@ -4,11 +4,14 @@
 //! Handles both output types (#[Object]) and input types (#[InputObject]).
 
 use crate::error::GeneratorError;
+use crate::params::{graphql_explicit_names, graphql_hide_foreign_keys, graphql_hide_timestamps, time_crate};
+use crate::options::synapse::graphql::Visibility;
 use crate::options::synapse::storage::{RelationDef, RelationType};
+use crate::storage::seaorm::implementation::{GEO_POINT_TYPE, MANAGED_TIMESTAMPS};
 use crate::storage::seaorm::options::{
     get_cached_entity_options, get_cached_graphql_field_options, get_cached_graphql_type_options,
 };
-use heck::{ToSnakeCase, ToUpperCamelCase};
+use heck::{ToLowerCamelCase, ToSnakeCase, ToUpperCamelCase};
 use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
 use prost_types::field_descriptor_proto::Type;
@@ -72,16 +75,31 @@ fn generate_object_type(
     };
 
     // Generate struct fields
-    let struct_fields = generate_struct_fields(file_name, msg_name, &message.field)?;
+    let struct_fields = generate_struct_fields(file_name, msg_name, &message.field, false)?;
 
     // Generate resolver methods
-    let resolver_methods =
-        generate_resolver_methods(file_name, msg_name, &message.field, opts.node)?;
+    let entity_opts = get_cached_entity_options(file_name, msg_name);
+    let relations = entity_opts.as_ref().map(|e| e.relations.as_slice()).unwrap_or_default();
+    let hidden = hidden_by_default(relations);
+    let resolver_methods = generate_resolver_methods(
+        file_name,
+        msg_name,
+        &message.field,
+        opts.node,
+        opts.rename_shadowed_fields,
+        &hidden,
+    )?;
 
     // Generate relation resolver methods from storage options
-    let entity_opts = get_cached_entity_options(file_name, msg_name);
     let relation_resolvers = if let Some(ref entity) = entity_opts {
-        generate_relation_resolvers(&rust_name, &entity.relations, &message.field)?
+        generate_relation_resolvers(file_name, message, &rust_name, &entity.relations)?
+    } else {
+        quote! {}
+    };
+
+    // Generate union types for polymorphic relation targets
+    let relation_unions = if let Some(ref entity) = entity_opts {
+        generate_polymorphic_unions(&rust_name, &entity.relations)
     } else {
         quote! {}
     };
@@ -93,16 +111,28 @@ fn generate_object_type(
     let node_impl = if opts.node {
         // Find the id field to get its type
         let id_field = message.field.iter().find(|f| f.name.as_deref() == Some("id"));
-        generate_node_methods(&graphql_name, id_field)
+        let prefix = super::node::id_prefix(file.package.as_deref().unwrap_or(""), &graphql_name);
+        generate_node_methods(&graphql_name, id_field, prefix.as_deref())
     } else {
         quote! {}
     };
 
     // Generate the #[Object] attribute, with name if graphql_name differs from rust_name
-    let object_attr = if graphql_name != rust_name {
-        quote! { #[Object(name = #graphql_name)] }
-    } else {
+    let mut object_args = Vec::new();
+    if graphql_name != rust_name {
+        object_args.push(quote! { name = #graphql_name });
+    }
+    // Unhinted types are private, so their data never reaches shared caches
+    // through a response that also resolved hinted public types
+    let cache = opts.cache_control.unwrap_or(crate::options::synapse::graphql::CacheControl {
+        max_age: 0,
+        private: true,
+    });
+    object_args.push(cache_control_arg(&cache));
+    let object_attr = if object_args.is_empty() {
         quote! { #[Object] }
+    } else {
+        quote! { #[Object(#(#object_args),*)] }
     };
 
     let code = quote! {
@@ -121,6 +151,8 @@ fn generate_object_type(
             #struct_fields
         }
 
+        #relation_unions
+
         #object_attr
         impl #type_ident {
             #node_impl
@@ -133,10 +165,7 @@ fn generate_object_type(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Determine output file path - use package, not file name
     // This ensures all GraphQL types for a package go to the same directory
@@ -207,6 +236,7 @@ fn generate_input_type(
     // Generate From impl to convert GraphQL input to proto message
     let proto_ident = format_ident!("{}", msg_name);
     let from_impl = generate_input_from_impl(&type_ident, &proto_ident, &message.field);
+    let serde_derives = super::input_serde_derives("camelCase");
 
     let code = quote! {
         //! GraphQL InputObject type for #msg_name
@@ -219,6 +249,7 @@ fn generate_input_type(
         #filter_imports
 
         /// GraphQL input object type
+        #serde_derives
         #[derive(InputObject, Default)]
         pub struct #type_ident {
             #struct_fields
@@ -229,10 +260,7 @@ fn generate_input_type(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Determine output file path - use package, not file name
     // This ensures all GraphQL types for a package go to the same directory
@@ -255,9 +283,10 @@ fn generate_input_type(
 /// The skip option only affects resolver method generation, not struct fields.
 /// This allows relation resolvers to access FK fields that aren't exposed in GraphQL.
 fn generate_struct_fields(
-    _file_name: &str,
-    _msg_name: &str,
+    file_name: &str,
+    msg_name: &str,
     fields: &[FieldDescriptorProto],
+    input: bool,
 ) -> Result<TokenStream, GeneratorError> {
     use prost_types::field_descriptor_proto::Label;
 
@@ -272,8 +301,8 @@ fn generate_struct_fields(
         let rust_name = escape_rust_keyword(&snake_name);
         let rust_type = proto_type_to_rust_type(field);
 
-        // Check if optional or repeated
-        let is_optional = field.proto3_optional.unwrap_or(false);
+        // Check if optional or repeated (GeoPoint fields keep message presence)
+        let is_optional = field.proto3_optional.unwrap_or(false) || is_geo_point(field);
         let is_repeated = field.label() == Label::Repeated;
 
         let field_type = if is_repeated {
@@ -284,7 +313,14 @@ fn generate_struct_fields(
             quote! { #rust_type }
         };
 
+        // Input fields are named by the struct; object fields by their resolvers
+        let explicit_name = if input {
+            super::explicit_input_name(file_name, msg_name, field)
+        } else {
+            quote! {}
+        };
         field_tokens.push(quote! {
+            #explicit_name
             pub #rust_name: #field_type,
         });
     }
@@ -298,58 +334,84 @@ fn generate_input_fields(
     msg_name: &str,
     fields: &[FieldDescriptorProto],
 ) -> Result<TokenStream, GeneratorError> {
-    // Same as struct fields for now
-    generate_struct_fields(file_name, msg_name, fields)
+    generate_struct_fields(file_name, msg_name, fields, true)
+}
+
+/// Fields the generator policy leaves off object types
+///
+/// Managed timestamps with `graphql_timestamps=hidden`, and belongs-to and
+/// polymorphic foreign keys (reachable through their relation resolvers)
+/// with `graphql_foreign_keys=hidden`.
+fn hidden_by_default(relations: &[RelationDef]) -> Vec<String> {
+    let mut hidden = Vec::new();
+    if graphql_hide_timestamps() {
+        hidden.extend(MANAGED_TIMESTAMPS.iter().map(|name| name.to_string()));
+    }
+    if graphql_hide_foreign_keys() {
+        hidden.extend(
+            relations
+                .iter()
+                .filter(|r| matches!(r.r#type(), RelationType::BelongsTo | RelationType::Polymorphic))
+                .filter(|r| !r.foreign_key.is_empty())
+                .map(|r| r.foreign_key.to_snake_case()),
+        );
+    }
+    hidden
 }
 
 /// Generate resolver methods for an Object type
+///
+/// `hidden` fields are skipped unless their `visibility` option exposes them.
+/// With `rename_shadowed`, fields shadowing Relay fields get the type name as
+/// prefix unless they set their own name.
 fn generate_resolver_methods(
     file_name: &str,
     msg_name: &str,
     fields: &[FieldDescriptorProto],
     is_node: bool,
+    rename_shadowed: bool,
+    hidden: &[String],
 ) -> Result<TokenStream, GeneratorError> {
     let mut method_tokens = Vec::new();
 
     for field in fields {
-        let field_name = field.name.as_deref().unwrap_or("");
-        let field_number = field.number.unwrap_or(0);
-
-        // Check for graphql field options
-        let field_opts = get_cached_graphql_field_options(file_name, msg_name, field_number);
-
-        // Skip if marked
-        if field_opts.as_ref().is_some_and(|o| o.skip) {
-            continue;
-        }
-
-        // Skip 'id' field if this type implements Node interface (Node provides id() method)
-        if is_node && field_name == "id" {
+        let Some(names) = object_field_name(
+            file_name,
+            msg_name,
+            field,
+            is_node,
+            rename_shadowed,
+            hidden,
+            graphql_explicit_names(),
+        ) else {
             continue;
-        }
-
-        // Determine method name (use override if present)
-        let method_name = field_opts
-            .as_ref()
-            .filter(|o| !o.name.is_empty())
-            .map(|o| o.name.clone())
-            .unwrap_or_else(|| field_name.to_snake_case());
+        };
+        let field_opts = get_cached_graphql_field_options(file_name, msg_name, field.number.unwrap_or(0));
 
-        let method_ident = format_ident!("{}", method_name);
+        let method_ident = format_ident!("{}", names.method);
 
         // Check if optional
-        let is_optional = field.proto3_optional.unwrap_or(false);
+        let is_optional = field.proto3_optional.unwrap_or(false) || is_geo_point(field);
 
-        // Generate deprecation attribute if present
-        let deprecated_attr = if let Some(ref opts) = field_opts {
+        // Generate name, deprecation and cache hint attribute if present
+        let mut graphql_args = Vec::new();
+        if graphql_explicit_names() {
+            let name = &names.graphql;
+            graphql_args.push(quote! { name = #name });
+        }
+        if let Some(ref opts) = field_opts {
             if let Some(ref dep) = opts.deprecated {
                 let reason = &dep.reason;
-                quote! { #[graphql(deprecation = #reason)] }
-            } else {
-                quote! {}
+                graphql_args.push(quote! { deprecation = #reason });
             }
-        } else {
+            if let Some(ref cache) = opts.cache_control {
+                graphql_args.push(cache_control_arg(cache));
+            }
+        }
+        let graphql_attr = if graphql_args.is_empty() {
             quote! {}
+        } else {
+            quote! { #[graphql(#(#graphql_args),*)] }
         };
 
         // Determine return type based on field type
@@ -359,7 +421,7 @@ fn generate_resolver_methods(
         let method_body = generate_field_resolver_body(field, is_optional);
 
         method_tokens.push(quote! {
-            #deprecated_attr
+            #graphql_attr
             async fn #method_ident(&self) -> #return_type {
                 #method_body
             }
@@ -369,8 +431,126 @@ fn generate_resolver_methods(
     Ok(quote! { #(#method_tokens)* })
 }
 
+/// Rust method and GraphQL name of an object field
+struct ObjectFieldName {
+    method: String,
+    graphql: String,
+}
+
+/// Names of an object field's resolver, or `None` when the field isn't exposed
+///
+/// The `name` override is the method name, camelCased by async-graphql like
+/// any other, unless `explicit` (`graphql_names=explicit`): then it is the
+/// GraphQL name as written, and the method keeps the proto field's name.
+fn object_field_name(
+    file_name: &str,
+    msg_name: &str,
+    field: &FieldDescriptorProto,
+    is_node: bool,
+    rename_shadowed: bool,
+    hidden: &[String],
+    explicit: bool,
+) -> Option<ObjectFieldName> {
+    let field_name = field.name.as_deref().unwrap_or("");
+    let field_opts = get_cached_graphql_field_options(file_name, msg_name, field.number.unwrap_or(0));
+
+    // Skip if marked
+    if field_opts.as_ref().is_some_and(|o| o.skip) {
+        return None;
+    }
+
+    // Visibility overrides the generator policy either way
+    let visible = match field_opts.as_ref().map(|o| o.visibility()) {
+        Some(Visibility::Exposed) => true,
+        Some(Visibility::Hidden) => false,
+        _ => !hidden.contains(&field_name.to_snake_case()),
+    };
+    if !visible {
+        return None;
+    }
+
+    // Skip 'id' field if this type implements Node interface (Node provides id() method)
+    if is_node && field_name == "id" {
+        return None;
+    }
+
+    let rename = field_opts.map(|o| o.name).filter(|name| !name.is_empty());
+    let method = if rename_shadowed && super::shadows_relay_field(msg_name, is_node, field_name) {
+        super::renamed_shadowed_field(msg_name, field_name)
+    } else {
+        field_name.to_snake_case()
+    };
+    Some(match rename {
+        Some(name) if explicit => ObjectFieldName {
+            method,
+            graphql: name,
+        },
+        Some(name) => ObjectFieldName {
+            graphql: name.to_lower_camel_case(),
+            method: name,
+        },
+        None => ObjectFieldName {
+            graphql: method.to_lower_camel_case(),
+            method,
+        },
+    })
+}
+
+/// GraphQL names of a message's fields on its object type
+///
+/// `None` when the message has no object type; a field's name is `None`
+/// when the field isn't exposed on it.
+pub(crate) fn object_field_graphql_names(file_name: &str, message: &DescriptorProto) -> Option<Vec<Option<String>>> {
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let opts = get_cached_graphql_type_options(file_name, msg_name).filter(|o| !o.skip && !o.input)?;
+    let entity_opts = get_cached_entity_options(file_name, msg_name);
+    let relations = entity_opts.as_ref().map(|e| e.relations.as_slice()).unwrap_or_default();
+    let hidden = hidden_by_default(relations);
+    let names = message
+        .field
+        .iter()
+        .map(|field| {
+            if opts.node && field.name.as_deref() == Some("id") {
+                return Some("id".to_string());
+            }
+            object_field_name(
+                file_name,
+                msg_name,
+                field,
+                opts.node,
+                opts.rename_shadowed_fields,
+                &hidden,
+                graphql_explicit_names(),
+            )
+            .map(|names| names.graphql)
+        })
+        .collect();
+    Some(names)
+}
+
+/// `#[graphql(name = ...)]` of a generated resolver when `explicit`
+///
+/// Spells out the camelCase name async-graphql derives from `method`, so
+/// `graphql_names=explicit` leaves no field named implicitly.
+fn resolver_name_attr(method: &str, explicit: bool) -> TokenStream {
+    if !explicit {
+        return quote! {};
+    }
+    let name = method.to_lower_camel_case();
+    quote! { #[graphql(name = #name)] }
+}
+
+/// async-graphql `cache_control(...)` argument for a cache hint
+fn cache_control_arg(cache: &crate::options::synapse::graphql::CacheControl) -> TokenStream {
+    let max_age = proc_macro2::Literal::u32_unsuffixed(cache.max_age);
+    let scope = if cache.private { quote! { private } } else { quote! { public } };
+    quote! { cache_control(max_age = #max_age, #scope) }
+}
+
 /// Generate Node interface methods (global ID)
-fn generate_node_methods(type_name: &str, id_field: Option<&FieldDescriptorProto>) -> TokenStream {
+///
+/// Types with an ID `prefix` get Stripe-style `{prefix}_{id}` global IDs.
+fn generate_node_methods(type_name: &str, id_field: Option<&FieldDescriptorProto>, prefix: Option<&str>) -> TokenStream {
     let type_name_str = type_name;
 
     // Determine ID type category for proper handling
@@ -383,18 +563,37 @@ fn generate_node_methods(type_name: &str, id_field: Option<&FieldDescriptorProto
     // Check if this is a UUID type (by type name or field type name convention)
     let is_uuid = field_type_name.to_lowercase().contains("uuid");
 
-    // Use base64url encoding for global IDs (URL-safe, works with any bytes)
+    // Use base64url encoding for global IDs (URL-safe, works with any bytes);
+    // minimal mode hex-encodes with std only
+    let encode = if crate::params::minimal() {
+        quote! { raw.bytes().map(|b| format!("{:02x}", b)).collect::<String>() }
+    } else {
+        quote! {{
+            use base64::Engine;
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(raw.as_bytes())
+        }}
+    };
+    let id_name = resolver_name_attr("id", graphql_explicit_names());
+    let internal_id_name = resolver_name_attr("internal_id", graphql_explicit_names());
+    let global_id = match prefix {
+        Some(prefix) => quote! { ID(format!("{}_{}", #prefix, self.id)) },
+        None => quote! {
+            let raw = format!("{}:{}", #type_name_str, self.id);
+            ID(#encode)
+        },
+    };
+
     if is_uuid {
         // UUID-based ID - return as string reference
         quote! {
             /// Relay global ID
+            #id_name
             async fn id(&self) -> ID {
-                use base64::Engine;
-                let raw = format!("{}:{}", #type_name_str, self.id);
-                ID(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(raw.as_bytes()))
+                #global_id
             }
 
             /// Internal database ID (UUID)
+            #internal_id_name
             async fn internal_id(&self) -> ID {
                 ID(self.id.clone())
             }
@@ -403,13 +602,13 @@ fn generate_node_methods(type_name: &str, id_field: Option<&FieldDescriptorProto
         // String-based ID - clone directly into ID
         quote! {
             /// Relay global ID
+            #id_name
             async fn id(&self) -> ID {
-                use base64::Engine;
-                let raw = format!("{}:{}", #type_name_str, self.id);
-                ID(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(raw.as_bytes()))
+                #global_id
             }
 
             /// Internal database ID
+            #internal_id_name
             async fn internal_id(&self) -> ID {
                 ID(self.id.clone())
             }
@@ -418,13 +617,13 @@ fn generate_node_methods(type_name: &str, id_field: Option<&FieldDescriptorProto
         // Numeric ID - convert to string
         quote! {
             /// Relay global ID
+            #id_name
             async fn id(&self) -> ID {
-                use base64::Engine;
-                let raw = format!("{}:{}", #type_name_str, self.id);
-                ID(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(raw.as_bytes()))
+                #global_id
             }
 
             /// Internal database ID
+            #internal_id_name
             async fn internal_id(&self) -> ID {
                 ID(self.id.to_string())
             }
@@ -432,6 +631,41 @@ fn generate_node_methods(type_name: &str, id_field: Option<&FieldDescriptorProto
     }
 }
 
+/// Closure decoding a node type's global ID into its local ID string
+///
+/// The inverse of the `id` of `generate_node_methods`: `None` for the IDs of
+/// other types and for IDs that do not decode.
+pub(super) fn generate_global_id_decode(type_name: &str, prefix: Option<&str>) -> TokenStream {
+    if let Some(prefix) = prefix {
+        let prefix = format!("{}_", prefix);
+        return quote! {
+            |id: &async_graphql::ID| id.as_str().strip_prefix(#prefix).map(str::to_string)
+        };
+    }
+    let decode = if crate::params::minimal() {
+        quote! {
+            (0..s.len())
+                .step_by(2)
+                .map(|i| s.get(i..i + 2).and_then(|h| u8::from_str_radix(h, 16).ok()))
+                .collect::<Option<Vec<u8>>>()
+        }
+    } else {
+        quote! {{
+            use base64::Engine;
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(s).ok()
+        }}
+    };
+    let type_prefix = format!("{}:", type_name);
+    quote! {
+        |id: &async_graphql::ID| {
+            let s = id.as_str();
+            let bytes = #decode?;
+            let raw = String::from_utf8(bytes).ok()?;
+            raw.strip_prefix(#type_prefix).map(str::to_string)
+        }
+    }
+}
+
 /// Generate From impl for proto to GraphQL type conversion
 /// Note: All fields are converted, including those marked with skip.
 /// This allows relation resolvers to access FK fields.
@@ -457,13 +691,20 @@ fn generate_from_impl(
             .map(|t| t.contains("Timestamp"))
             .unwrap_or(false);
 
-        let conversion = if is_timestamp {
+        let conversion = if is_geo_point(field) {
+            quote! {
+                #rust_name: proto.#rust_name.map(Into::into),
+            }
+        } else if is_timestamp {
             // Convert Timestamp to ISO 8601 string
+            let rfc3339 = if crate::params::minimal() {
+                crate::params::std_rfc3339()
+            } else {
+                time_crate().proto_to_rfc3339()
+            };
             quote! {
                 #rust_name: proto.#rust_name.map(|t| {
-                    chrono::DateTime::from_timestamp(t.seconds, t.nanos as u32)
-                        .map(|dt| dt.to_rfc3339())
-                        .unwrap_or_default()
+                    #rfc3339
                 }).unwrap_or_default(),
             }
         } else {
@@ -555,6 +796,11 @@ fn generate_input_from_impl(
     }
 }
 
+/// Whether a field is a `synapse.relay.GeoPoint`
+fn is_geo_point(field: &FieldDescriptorProto) -> bool {
+    field.type_name.as_deref() == Some(GEO_POINT_TYPE)
+}
+
 /// Convert proto field type to Rust type
 fn proto_type_to_rust_type(field: &FieldDescriptorProto) -> TokenStream {
     let proto_type = field.r#type();
@@ -572,6 +818,10 @@ fn proto_type_to_rust_type(field: &FieldDescriptorProto) -> TokenStream {
         Type::Message | Type::Enum | Type::Group => {
             // For message types, use the type name
             if let Some(type_name) = field.type_name.as_ref() {
+                if type_name == GEO_POINT_TYPE {
+                    return quote! { super::super::super::synapse::relay::graphql::GeoPoint };
+                }
+
                 // Handle Timestamp specially - convert to String in GraphQL
                 if type_name.contains("Timestamp") {
                     return quote! { String };
@@ -636,14 +886,16 @@ fn generate_field_resolver_body(field: &FieldDescriptorProto, is_optional: bool)
 
 /// Generate relation resolver methods from storage entity relations
 fn generate_relation_resolvers(
+    file_name: &str,
+    message: &DescriptorProto,
     parent_type: &str,
     relations: &[RelationDef],
-    fields: &[FieldDescriptorProto],
 ) -> Result<TokenStream, GeneratorError> {
     let mut resolvers = Vec::new();
 
     for relation in relations {
-        let resolver = generate_single_relation_resolver(parent_type, relation, fields)?;
+        let non_critical = super::degrade::is_non_critical(file_name, message, relation);
+        let resolver = generate_single_relation_resolver(parent_type, relation, &message.field, non_critical)?;
         resolvers.push(resolver);
     }
 
@@ -651,21 +903,31 @@ fn generate_relation_resolvers(
 }
 
 /// Generate a single relation resolver
+///
+/// Non-critical relations are nullable and degrade to null when the related
+/// service fails (see `degrade_on_error`).
 fn generate_single_relation_resolver(
     parent_type: &str,
     relation: &RelationDef,
     fields: &[FieldDescriptorProto],
+    non_critical: bool,
 ) -> Result<TokenStream, GeneratorError> {
     let relation_name = &relation.name;
     let related_type = &relation.related;
     let foreign_key = &relation.foreign_key;
 
+    // Polymorphic relations name their target via a discriminator, not `related`
+    if relation.r#type() == RelationType::Polymorphic {
+        return Ok(generate_polymorphic_resolver(parent_type, relation, fields, non_critical));
+    }
+
     // Skip if relation_name or related_type is empty
     if relation_name.is_empty() || related_type.is_empty() {
         return Ok(quote! {});
     }
 
     let method_ident = format_ident!("{}", relation_name.to_snake_case());
+    let name_attr = resolver_name_attr(&relation_name.to_snake_case(), graphql_explicit_names());
 
     // Check if this is a cross-package relation (contains a dot like "iam.User")
     let (related_path, related_type_name) = if related_type.contains('.') {
@@ -721,14 +983,22 @@ fn generate_single_relation_resolver(
                 );
                 let loader_ident = format_ident!("{}", loader_name);
 
+                let (list_type, list_body) = relation_body(
+                    non_critical,
+                    quote! { Vec<super::#related_ident> },
+                    quote! {
+                        let loader = ctx.data_unchecked::<DataLoader<super::#loader_ident>>();
+                        Ok(loader.load_one(self.id.clone()).await?.unwrap_or_default())
+                    },
+                );
                 return Ok(quote! {
                     /// Resolve related #relation_name (uses DataLoader for batching)
+                    #name_attr
                     async fn #method_ident(
                         &self,
                         ctx: &Context<'_>,
-                    ) -> Result<Vec<super::#related_ident>> {
-                        let loader = ctx.data_unchecked::<DataLoader<super::#loader_ident>>();
-                        Ok(loader.load_one(self.id).await?.unwrap_or_default())
+                    ) -> Result<#list_type> {
+                        #list_body
                     }
                 });
             }
@@ -747,26 +1017,73 @@ fn generate_single_relation_resolver(
 
             // gRPC method and types for paginated access
             let related_service = format!("{}Service", related_type.to_upper_camel_case());
-            let client_module = format!("{}_client", related_service.to_snake_case());
-            let client_module_ident = format_ident!("{}", client_module);
-            let client_type = format!("{}Client", related_service);
-            let client_ident = format_ident!("{}", client_type);
+            let backend_ident = format_ident!("{}Backend", related_service);
             let list_method = format_ident!("list_{}s", related_type.to_snake_case());
             let list_request = format_ident!("List{}sRequest", related_type.to_upper_camel_case());
             let connection_type = format_ident!("{}Connection", related_type.to_upper_camel_case());
             let filter_type = format_ident!("{}Filter", related_type.to_upper_camel_case());
 
+            // FK filter matches the parent key (StringFilter for UUID keys)
+            let string_key = fields
+                .iter()
+                .any(|f| f.name.as_deref() == Some("id") && f.r#type() == Type::String);
+            let key_filter = if string_key {
+                format_ident!("StringFilter")
+            } else {
+                format_ident!("IntFilter")
+            };
+
+            let (list_type, list_body) = relation_body(
+                non_critical,
+                quote! { Vec<super::#related_ident> },
+                quote! {
+                    let loader = ctx.data_unchecked::<DataLoader<super::#loader_ident>>();
+                    Ok(loader.load_one(self.id.clone()).await?.unwrap_or_default())
+                },
+            );
+            let (connection_return, connection_body) = relation_body(
+                non_critical,
+                quote! { super::#connection_type },
+                quote! {
+                    use super::super::{#list_request, #filter_type};
+                    use super::super::super::synapse::relay::#key_filter;
+
+                    let client = &ctx.data_unchecked::<super::#backend_ident>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    let mut filter = #filter_type::default();
+                    filter.#fk_ident = Some(#key_filter {
+                        eq: Some(self.id.clone()),
+                        ..Default::default()
+                    });
+
+                    let request = #list_request {
+                        after,
+                        before: None,
+                        first,
+                        last: None,
+                        filter: Some(filter),
+                        order_by: None,
+                        ..Default::default()
+                    };
+
+                    let response = client.clone().#list_method(request).await
+                        .map_err(super::status_error)?;
+
+                    Ok(response.into_inner().into())
+                },
+            );
+
             Ok(quote! {
                 /// Resolve related #relation_name (uses DataLoader for batching)
                 ///
                 /// Returns all related items efficiently via batched loading.
                 /// For paginated access, use `#relation_name Collection`.
+                #name_attr
                 async fn #method_ident(
                     &self,
                     ctx: &Context<'_>,
-                ) -> Result<Vec<super::#related_ident>> {
-                    let loader = ctx.data_unchecked::<DataLoader<super::#loader_ident>>();
-                    Ok(loader.load_one(self.id).await?.unwrap_or_default())
+                ) -> Result<#list_type> {
+                    #list_body
                 }
 
                 /// Resolve related #relation_name with pagination
@@ -779,32 +1096,8 @@ fn generate_single_relation_resolver(
                     ctx: &Context<'_>,
                     first: Option<i32>,
                     after: Option<String>,
-                ) -> Result<super::#connection_type> {
-                    use tonic::transport::Channel;
-                    use super::super::#client_module_ident::#client_ident;
-                    use super::super::{#list_request, #filter_type};
-                    use super::super::super::synapse::relay::IntFilter;
-
-                    let client = ctx.data_unchecked::<#client_ident<Channel>>();
-                    let mut filter = #filter_type::default();
-                    filter.#fk_ident = Some(IntFilter {
-                        eq: Some(self.id),
-                        ..Default::default()
-                    });
-
-                    let request = #list_request {
-                        after,
-                        before: None,
-                        first,
-                        last: None,
-                        filter: Some(filter),
-                        order_by: None,
-                    };
-
-                    let response = client.clone().#list_method(request).await
-                        .map_err(|e| async_graphql::Error::new(e.message()))?;
-
-                    Ok(response.into_inner().into())
+                ) -> Result<#connection_return> {
+                    #connection_body
                 }
             })
         }
@@ -835,31 +1128,44 @@ fn generate_single_relation_resolver(
 
             // For BELONGS_TO, we need the FK on this type, not the related type
             // The FK is on the current entity pointing to the related entity
+            let fk_value = if fk_is_optional {
+                quote! { fk }
+            } else {
+                quote! { self.#fk_ident.clone() }
+            };
+            let body = degrade_on_error(
+                non_critical,
+                quote! {
+                    let loader = ctx.data_unchecked::<DataLoader<#loader_path>>();
+                    Ok(loader.load_one(#fk_value).await?)
+                },
+            );
+
             if fk_is_optional {
                 // Handle optional FK - return None if FK is not set
                 Ok(quote! {
                     /// Resolve related #relation_name (uses DataLoader for batching)
+                    #name_attr
                     async fn #method_ident(
                         &self,
                         ctx: &Context<'_>,
                     ) -> Result<Option<#related_path>> {
-                        let Some(fk) = self.#fk_ident else {
+                        let Some(fk) = self.#fk_ident.clone() else {
                             return Ok(None);
                         };
-                        let loader = ctx.data_unchecked::<DataLoader<#loader_path>>();
-                        Ok(loader.load_one(fk).await?)
+                        #body
                     }
                 })
             } else {
                 // Required FK - use directly
                 Ok(quote! {
                     /// Resolve related #relation_name (uses DataLoader for batching)
+                    #name_attr
                     async fn #method_ident(
                         &self,
                         ctx: &Context<'_>,
                     ) -> Result<Option<#related_path>> {
-                        let loader = ctx.data_unchecked::<DataLoader<#loader_path>>();
-                        Ok(loader.load_one(self.#fk_ident).await?)
+                        #body
                     }
                 })
             }
@@ -868,6 +1174,185 @@ fn generate_single_relation_resolver(
     }
 }
 
+/// Build the GraphQL type and loader paths for a relation target
+///
+/// Same-package targets resolve through `super`, cross-package targets
+/// (e.g. "iam.User") through the other package's graphql module.
+fn relation_target_paths(related_type: &str) -> (TokenStream, TokenStream) {
+    if related_type.contains('.') {
+        let parts: Vec<&str> = related_type.rsplitn(2, '.').collect();
+        let type_name = parts[0];
+        let package = parts.get(1).copied().unwrap_or("");
+        let package_ident = format_ident!("{}", package);
+        let type_ident = format_ident!("{}", type_name.to_upper_camel_case());
+        let loader_ident = format_ident!("{}Loader", type_name.to_upper_camel_case());
+        (
+            quote! { super::super::super::#package_ident::graphql::#type_ident },
+            quote! { super::super::super::#package_ident::graphql::#loader_ident },
+        )
+    } else {
+        let type_ident = format_ident!("{}", related_type.to_upper_camel_case());
+        let loader_ident = format_ident!("{}Loader", related_type.to_upper_camel_case());
+        (quote! { super::#type_ident }, quote! { super::#loader_ident })
+    }
+}
+
+/// Check whether a relation is a usable polymorphic relation
+fn is_polymorphic(relation: &RelationDef) -> bool {
+    relation.r#type() == RelationType::Polymorphic
+        && !relation.name.is_empty()
+        && !relation.foreign_key.is_empty()
+        && !relation.type_column.is_empty()
+        && !relation.candidates.is_empty()
+}
+
+/// Name of the GraphQL union for a polymorphic relation (e.g. CommentCommentable)
+fn polymorphic_union_name(parent_type: &str, relation: &RelationDef) -> String {
+    format!(
+        "{}{}",
+        parent_type.to_upper_camel_case(),
+        relation.name.to_upper_camel_case()
+    )
+}
+
+/// Generate GraphQL unions for the targets of polymorphic relations
+fn generate_polymorphic_unions(parent_type: &str, relations: &[RelationDef]) -> TokenStream {
+    let unions = relations.iter().filter(|r| is_polymorphic(r)).map(|relation| {
+        let union_ident = format_ident!("{}", polymorphic_union_name(parent_type, relation));
+        let variants = relation.candidates.iter().map(|candidate| {
+            let type_name = candidate.rsplit('.').next().unwrap_or(candidate);
+            let variant_ident = format_ident!("{}", type_name.to_upper_camel_case());
+            let (type_path, _) = relation_target_paths(candidate);
+            quote! { #variant_ident(#type_path), }
+        });
+        let doc = format!(
+            "Target of the polymorphic `{}` relation",
+            relation.name.to_snake_case()
+        );
+
+        quote! {
+            #[doc = #doc]
+            #[derive(async_graphql::Union, Clone)]
+            pub enum #union_ident {
+                #(#variants)*
+            }
+        }
+    });
+
+    quote! { #(#unions)* }
+}
+
+/// Generate a resolver for a polymorphic relation
+///
+/// Dispatches on the discriminator column to the candidate's DataLoader.
+/// Unknown discriminator values resolve to null.
+fn generate_polymorphic_resolver(
+    parent_type: &str,
+    relation: &RelationDef,
+    fields: &[FieldDescriptorProto],
+    non_critical: bool,
+) -> TokenStream {
+    if !is_polymorphic(relation) {
+        return quote! {};
+    }
+
+    let method_ident = format_ident!("{}", relation.name.to_snake_case());
+    let name_attr = resolver_name_attr(&relation.name.to_snake_case(), graphql_explicit_names());
+    let union_ident = format_ident!("{}", polymorphic_union_name(parent_type, relation));
+
+    let is_optional = |name: &str| {
+        let snake = name.to_snake_case();
+        fields
+            .iter()
+            .find(|f| f.name.as_deref().map(|n| n.to_snake_case()) == Some(snake.clone()))
+            .map(|f| f.proto3_optional.unwrap_or(false))
+            .unwrap_or(false)
+    };
+
+    let type_ident = format_ident!("{}", relation.type_column.to_snake_case());
+    let fk_ident = format_ident!("{}", relation.foreign_key.to_snake_case());
+
+    let type_value = if is_optional(&relation.type_column) {
+        quote! { self.#type_ident.as_deref() }
+    } else {
+        quote! { Some(self.#type_ident.as_str()) }
+    };
+    let fk_value = if is_optional(&relation.foreign_key) {
+        quote! { self.#fk_ident.clone() }
+    } else {
+        quote! { Some(self.#fk_ident.clone()) }
+    };
+
+    let arms = relation.candidates.iter().map(|candidate| {
+        let type_name = candidate.rsplit('.').next().unwrap_or(candidate).to_upper_camel_case();
+        let variant_ident = format_ident!("{}", type_name);
+        let (_, loader_path) = relation_target_paths(candidate);
+        quote! {
+            #type_name => ctx
+                .data_unchecked::<DataLoader<#loader_path>>()
+                .load_one(id)
+                .await?
+                .map(#union_ident::#variant_ident),
+        }
+    });
+
+    let doc = format!(
+        "Resolve polymorphic {} (uses DataLoader for batching)",
+        relation.name.to_snake_case()
+    );
+    let body = degrade_on_error(
+        non_critical,
+        quote! {
+            Ok(match type_name {
+                #(#arms)*
+                _ => None,
+            })
+        },
+    );
+
+    quote! {
+        #[doc = #doc]
+        #name_attr
+        async fn #method_ident(
+            &self,
+            ctx: &Context<'_>,
+        ) -> Result<Option<#union_ident>> {
+            let (Some(type_name), Some(id)) = (#type_value, #fk_value) else {
+                return Ok(None);
+            };
+            #body
+        }
+    }
+}
+
+/// Wrap a relation resolver body so a failure of the related service
+/// resolves to null with a warning instead of failing the query
+///
+/// The body evaluates to `Result<Option<T>>`; critical relations keep it as is.
+fn degrade_on_error(non_critical: bool, body: TokenStream) -> TokenStream {
+    if !non_critical {
+        return body;
+    }
+    quote! {
+        let resolved: Result<_> = async { #body }.await;
+        Ok(resolved.unwrap_or_else(|error| super::degrade::degrade(ctx, error)))
+    }
+}
+
+/// Return type and body of a relation resolver whose value is not nullable
+///
+/// Non-critical relations become nullable so they can degrade to null.
+fn relation_body(non_critical: bool, value_type: TokenStream, body: TokenStream) -> (TokenStream, TokenStream) {
+    if !non_critical {
+        return (value_type, body);
+    }
+    let body = quote! {
+        let resolved: Result<#value_type> = async { #body }.await;
+        Ok(resolved.map_or_else(|error| super::degrade::degrade(ctx, error), Some))
+    };
+    (quote! { Option<#value_type> }, body)
+}
+
 /// Escape Rust keywords by prefixing with r#
 fn escape_rust_keyword(name: &str) -> proc_macro2::Ident {
     // List of Rust keywords that need escaping
@@ -886,3 +1371,52 @@ fn escape_rust_keyword(name: &str) -> proc_macro2::Ident {
         format_ident!("{}", name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::synapse::graphql::FieldOptions;
+    use crate::storage::seaorm::options::cache_graphql_field_options;
+    use crate::test_util::field;
+
+    #[test]
+    fn test_object_field_name() {
+        let file_name = "object_field_names/entities.proto";
+        cache_graphql_field_options(
+            file_name,
+            "Post",
+            2,
+            FieldOptions {
+                name: "post_title".to_string(),
+                ..Default::default()
+            },
+        );
+        let names = |field: &FieldDescriptorProto, explicit: bool| {
+            object_field_name(file_name, "Post", field, true, false, &[], explicit)
+                .map(|names| (names.method, names.graphql))
+        };
+        let pair = |method: &str, graphql: &str| Some((method.to_string(), graphql.to_string()));
+
+        // A rename is the method name, camelCased like any other
+        assert_eq!(names(&field("title", 2, Type::String), false), pair("post_title", "postTitle"));
+        assert_eq!(names(&field("author_id", 3, Type::Int64), false), pair("author_id", "authorId"));
+        // Explicit names keep a rename as written and the field's method
+        assert_eq!(names(&field("title", 2, Type::String), true), pair("title", "post_title"));
+        assert_eq!(names(&field("author_id", 3, Type::Int64), true), pair("author_id", "authorId"));
+        // Node types resolve `id` through the Node interface
+        assert_eq!(names(&field("id", 1, Type::Int64), true), None);
+    }
+
+    #[test]
+    fn test_resolver_name_attr() {
+        assert!(resolver_name_attr("author", false).is_empty());
+        assert_eq!(
+            resolver_name_attr("author", true).to_string(),
+            "# [graphql (name = \"author\")]"
+        );
+        assert_eq!(
+            resolver_name_attr("internal_id", true).to_string(),
+            "# [graphql (name = \"internalId\")]"
+        );
+    }
+}
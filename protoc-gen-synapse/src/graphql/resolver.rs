@@ -4,67 +4,34 @@
 //! Resolvers use gRPC clients (tonic-generated) for data fetching.
 //!
 //! For mutations with context-injected fields, the resolver extracts values from
-//! the GraphQL context and passes them to `input.to_request()`.
+//! the GraphQL context and passes them to `input.to_request()` (creates,
+//! updates and custom mutations) or sets them on the request (deletes).
+//!
+//! Update-many and delete-many mutations take the entity's filter (and the
+//! input of the patch) and return the number of rows written.
 
 use crate::error::GeneratorError;
+use crate::storage::defaults::{affected_field, infer_operation};
+use crate::storage::seaorm::batch;
+use crate::storage::seaorm::implementation::update_many_patch_type;
 use crate::storage::seaorm::options::{
-    get_cached_graphql_field_options, get_cached_graphql_mutation_options,
-    get_cached_graphql_query_options, get_cached_graphql_service_options,
+    get_cached_graphql_mutation_options, get_cached_graphql_query_options,
+    get_cached_graphql_service_options, get_cached_graphql_type_options_in_package,
+    get_cached_rpc_method_options, get_cached_validate_message_options,
 };
-use heck::{ToSnakeCase, ToUpperCamelCase};
+use crate::validate::has_custom_validators;
+use heck::{ToLowerCamelCase, ToSnakeCase, ToUpperCamelCase};
 use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::Label;
 use prost_types::{FileDescriptorProto, MethodDescriptorProto, ServiceDescriptorProto};
 use quote::{format_ident, quote};
 
-/// Information about context-injected fields for a request message
-struct ContextFieldInfo {
-    /// Field name (snake_case)
-    name: String,
-    /// Context path (e.g., "current_user.id")
-    path: String,
-}
-
-/// Check a request message for context-injected fields
-fn get_context_fields(
-    file: &FileDescriptorProto,
-    request_type_name: &str,
-) -> Vec<ContextFieldInfo> {
-    let file_name = file.name.as_deref().unwrap_or("");
-
-    // Find the request message in the file
-    let msg = file
-        .message_type
-        .iter()
-        .find(|m| m.name.as_deref() == Some(request_type_name));
-
-    let Some(message) = msg else {
-        return Vec::new();
-    };
-
-    let mut context_fields = Vec::new();
-
-    for field in &message.field {
-        let field_name = field.name.as_deref().unwrap_or("");
-        let field_number = field.number.unwrap_or(0);
-
-        if let Some(opts) = get_cached_graphql_field_options(file_name, request_type_name, field_number) {
-            if let Some(ctx_source) = opts.from_context {
-                context_fields.push(ContextFieldInfo {
-                    name: field_name.to_snake_case(),
-                    path: ctx_source.path,
-                });
-            }
-        }
-    }
-
-    context_fields
-}
-
 /// Generate GraphQL resolvers from a proto service
 pub fn generate(
     file: &FileDescriptorProto,
     service: &ServiceDescriptorProto,
+    all_files: &[FileDescriptorProto],
 ) -> Result<Vec<File>, GeneratorError> {
     let file_name = file.name.as_deref().unwrap_or("");
     let svc_name = service.name.as_deref().unwrap_or("");
@@ -102,6 +69,10 @@ pub fn generate(
 
     let mut files = Vec::new();
 
+    // Backend the resolvers call through (gRPC client or in-process service)
+    let forward_user = crate::grpc::package_reads_current_user(all_files, file);
+    files.push(generate_backend(file, service, forward_user)?);
+
     // Generate Query struct if there are query methods
     if !queries.is_empty() {
         if let Some(query_file) = generate_query_struct(file, service, &queries)? {
@@ -119,7 +90,292 @@ pub fn generate(
     Ok(files)
 }
 
-/// Generate a Query struct with resolver methods using gRPC client
+/// Generate the resolver backend for a service
+///
+/// Resolvers call the service through `{Service}Backend`, which is either a
+/// tonic client (`Grpc`) or the service implementation itself (`InProcess`).
+/// In-process calls skip the network hop and protobuf encoding, so monolith
+/// deployments serve the same schema without looping through localhost.
+///
+/// With `forward_user`, an in-process backend carries the `CurrentUser` of the
+/// GraphQL request (bound by `for_request`) into the request extensions, where
+/// the service reads it for rate limits, actors, stamping and validators.
+fn generate_backend(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+    forward_user: bool,
+) -> Result<File, GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let svc_name = service.name.as_deref().unwrap_or("");
+    let svc_snake = svc_name.to_snake_case();
+    let backend_ident = format_ident!("{}Backend", svc_name.to_upper_camel_case());
+    let client_module_ident = format_ident!("{}_client", svc_snake);
+    let client_ident = format_ident!("{}Client", svc_name.to_upper_camel_case());
+    let server_module_ident = format_ident!("{}_server", svc_snake);
+    let server_trait_ident = format_ident!("{}", svc_name.to_upper_camel_case());
+
+    // With chaos=true, a `Faulty` variant calls through the fault-injection layer
+    let chaos = crate::params::chaos();
+    // With record_replay=true, a `Recorded` variant records to or replays from a cassette
+    let record_replay = crate::params::record_replay();
+    let request_context = crate::grpc::has_request_context(file_name, svc_name);
+
+    // Unary methods only; streaming RPCs aren't exposed through resolvers
+    let methods: Vec<_> = service
+        .method
+        .iter()
+        .filter(|m| !m.client_streaming() && !m.server_streaming())
+        .map(|method| {
+            let method_ident = format_ident!("{}", method.name().to_snake_case());
+            let type_ident = |t: &str| format_ident!("{}", t.rsplit('.').next().unwrap_or(t).to_upper_camel_case());
+            let input_ident = type_ident(method.input_type());
+            let output_ident = type_ident(method.output_type());
+            // Dry-run mutations forward the flag as request metadata
+            let (outgoing, incoming) = if super::is_dry_run(file_name, svc_name, method.name()) {
+                let request = quote! { super::super::prelude::dry_run_request(request) };
+                (request.clone(), request)
+            } else {
+                (quote! { request }, quote! { tonic::Request::new(request) })
+            };
+            // Remote calls carry the request context as metadata; in-process calls share the task-local
+            let outgoing = if request_context {
+                quote! { super::super::prelude::context_request(#outgoing) }
+            } else {
+                outgoing
+            };
+            let faulty_arm = chaos.then(|| quote! {
+                Self::Faulty(client) => client.clone().#method_ident(#outgoing).await,
+            });
+            let rpc = format!("{}/{}", svc_name, method.name());
+            let recorded_arms = record_replay.then(|| quote! {
+                Self::Recorded { cassette, live: Some(client) } => {
+                    let mut client = client.clone();
+                    cassette.record(#rpc, request, |request| async move { client.#method_ident(#outgoing).await }).await
+                }
+                Self::Recorded { cassette, live: None } => cassette.replay(#rpc, request).await,
+            });
+
+            let in_process_arm = if forward_user {
+                quote! {
+                    Self::InProcess(service, user) => {
+                        let mut request = #incoming;
+                        if let Some(user) = user {
+                            request.extensions_mut().insert(user.clone());
+                        }
+                        service.#method_ident(request).await
+                    }
+                }
+            } else {
+                quote! { Self::InProcess(service) => service.#method_ident(#incoming).await, }
+            };
+
+            quote! {
+                pub async fn #method_ident(
+                    &self,
+                    request: super::super::#input_ident,
+                ) -> Result<tonic::Response<super::super::#output_ident>, tonic::Status> {
+                    match self {
+                        Self::Grpc(client) => client.clone().#method_ident(#outgoing).await,
+                        #in_process_arm
+                        #faulty_arm
+                        #recorded_arms
+                    }
+                }
+            }
+        })
+        .collect();
+
+    // A trait object must name the associated stream type of every server-streaming
+    // RPC; the generated service boxes its streams, so in-process services use that type
+    let stream_types: Vec<_> = service
+        .method
+        .iter()
+        .filter(|m| m.server_streaming())
+        .map(|method| {
+            let stream_ident = format_ident!("{}Stream", method.name().to_upper_camel_case());
+            let output = method.output_type();
+            let output_ident = format_ident!("{}", output.rsplit('.').next().unwrap_or(output).to_upper_camel_case());
+            quote! {
+                #stream_ident = std::pin::Pin<
+                    Box<dyn tokio_stream::Stream<Item = Result<super::super::#output_ident, tonic::Status>> + Send + 'static>,
+                >
+            }
+        })
+        .collect();
+    let (server_bound, stream_doc) = if stream_types.is_empty() {
+        (quote! { #server_trait_ident }, None)
+    } else {
+        (
+            quote! { #server_trait_ident<#(#stream_types),*> },
+            Some(quote! {
+                ///
+                /// Server-streaming RPCs must return boxed streams, as the
+                /// generated service does.
+            }),
+        )
+    };
+
+    let (in_process_variant, in_process_ctor, ctx_param, for_request) = if forward_user {
+        (
+            quote! { InProcess(Arc<dyn #server_bound>, Option<crate::CurrentUser>) },
+            quote! { Self::InProcess(Arc::new(service), None) },
+            format_ident!("ctx"),
+            quote! {
+                match self {
+                    Self::InProcess(service, _) => {
+                        Self::InProcess(service.clone(), ctx.data_opt::<crate::CurrentUser>().cloned())
+                    }
+                    backend => backend.clone(),
+                }
+            },
+        )
+    } else {
+        (
+            quote! { InProcess(Arc<dyn #server_bound>) },
+            quote! { Self::InProcess(Arc::new(service)) },
+            format_ident!("_ctx"),
+            quote! { self.clone() },
+        )
+    };
+
+    let module_doc = format!("GraphQL resolver backend for {}", svc_name);
+    let doc = format!("Transport used by {} resolvers and loaders", svc_name);
+
+    let (faulty_import, faulty_variant, faulty_impls) = if chaos {
+        (
+            Some(quote! { use super::chaos::{FaultConfig, FaultLayer, FaultService}; }),
+            Some(quote! {
+                /// gRPC client calling through the fault-injection layer (test/staging)
+                Faulty(#client_ident<FaultService<Channel>>),
+            }),
+            Some(quote! {
+                impl #backend_ident {
+                    /// Call a remote service with the faults in `config` injected
+                    pub fn faulty(channel: Channel, config: FaultConfig) -> Self {
+                        use tower::Layer;
+                        Self::Faulty(#client_ident::new(FaultLayer::new(config).layer(channel)))
+                    }
+                }
+
+                impl From<#client_ident<FaultService<Channel>>> for #backend_ident {
+                    fn from(client: #client_ident<FaultService<Channel>>) -> Self {
+                        Self::Faulty(client)
+                    }
+                }
+            }),
+        )
+    } else {
+        (None, None, None)
+    };
+
+    let (recorded_import, recorded_variant, recorded_impls) = if record_replay {
+        (
+            Some(quote! { use super::cassette::Cassette; }),
+            Some(quote! {
+                /// Records calls made through `live` to the cassette, or replays them without it
+                Recorded {
+                    cassette: Arc<Cassette>,
+                    live: Option<#client_ident<Channel>>,
+                },
+            }),
+            Some(quote! {
+                impl #backend_ident {
+                    /// Call a remote service, recording every call to `cassette`
+                    pub fn recording(client: #client_ident<Channel>, cassette: Cassette) -> Self {
+                        Self::Recorded {
+                            cassette: Arc::new(cassette),
+                            live: Some(client),
+                        }
+                    }
+
+                    /// Answer every call from `cassette`, without a remote service
+                    pub fn replaying(cassette: Cassette) -> Self {
+                        Self::Recorded {
+                            cassette: Arc::new(cassette),
+                            live: None,
+                        }
+                    }
+                }
+            }),
+        )
+    } else {
+        (None, None, None)
+    };
+
+    let code = quote! {
+        #![doc = #module_doc]
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use std::sync::Arc;
+        use tonic::transport::Channel;
+        use super::super::#client_module_ident::#client_ident;
+        use super::super::#server_module_ident::#server_trait_ident;
+        #faulty_import
+        #recorded_import
+
+        #[doc = #doc]
+        ///
+        /// `Grpc` calls a remote service; `InProcess` calls the service
+        /// implementation directly, without a network hop or protobuf encoding.
+        #[derive(Clone)]
+        pub enum #backend_ident {
+            Grpc(#client_ident<Channel>),
+            #in_process_variant,
+            #faulty_variant
+            #recorded_variant
+        }
+
+        impl #backend_ident {
+            /// Call the service implementation in-process
+            #stream_doc
+            pub fn in_process(service: impl #server_bound) -> Self {
+                #in_process_ctor
+            }
+
+            /// The backend to call on behalf of the caller of a resolver
+            ///
+            /// In-process calls carry the request's `CurrentUser` into the
+            /// request extensions, as an authenticating gRPC server would.
+            pub fn for_request(&self, #ctx_param: &async_graphql::Context<'_>) -> Self {
+                #for_request
+            }
+
+            #(#methods)*
+        }
+
+        impl From<#client_ident<Channel>> for #backend_ident {
+            fn from(client: #client_ident<Channel>) -> Self {
+                Self::Grpc(client)
+            }
+        }
+
+        #faulty_impls
+
+        #recorded_impls
+    };
+
+    // Format the generated code
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let package = file.package.as_deref().unwrap_or("");
+    let output_path = format!(
+        "{}/graphql/{}_backend.rs",
+        package.replace('.', "/"),
+        svc_snake
+    );
+
+    Ok(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    })
+}
+
+/// Generate a Query struct with resolver methods using the service backend
 fn generate_query_struct(
     file: &FileDescriptorProto,
     service: &ServiceDescriptorProto,
@@ -132,28 +388,27 @@ fn generate_query_struct(
     let query_name = format!("{}Query", svc_name.to_upper_camel_case());
     let query_ident = format_ident!("{}", query_name);
 
-    // gRPC client type - in submodule {service}_client::{Service}Client
-    let client_module = format!("{}_client", svc_name.to_snake_case());
-    let client_module_ident = format_ident!("{}", client_module);
-    let client_type = format!("{}Client", svc_name.to_upper_camel_case());
-    let client_ident = format_ident!("{}", client_type);
+    // Resolver backend (gRPC client or in-process service)
+    let backend_ident = format_ident!("{}Backend", svc_name.to_upper_camel_case());
 
     // Generate resolver methods
-    let resolver_methods = generate_query_resolver_methods(svc_name, methods)?;
+    let resolver_methods = generate_query_resolver_methods(file, svc_name, methods)?;
+    let struct_doc = format!("Query resolvers from {} (via the service backend)", svc_name);
+
+    let module_doc = format!("GraphQL Query resolvers for {}", svc_name);
 
     let code = quote! {
-        //! GraphQL Query resolvers for #svc_name
+        #![doc = #module_doc]
         //! @generated
 
         #![allow(missing_docs)]
         #![allow(unused_imports)]
 
         use async_graphql::{Object, Context, Result};
-        use tonic::transport::Channel;
-        // Import gRPC client from parent module (blog/{svc}_client)
-        use super::super::#client_module_ident::#client_ident;
+        // Backend (gRPC client or in-process service) from the graphql module
+        use super::#backend_ident;
 
-        /// Query resolvers from #svc_name (uses gRPC client)
+        #[doc = #struct_doc]
         #[derive(Default)]
         pub struct #query_ident;
 
@@ -163,15 +418,12 @@ fn generate_query_struct(
         }
 
         // Client type alias
-        type Client = #client_ident<Channel>;
+        type Client = #backend_ident;
     };
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Determine output file path
     let package = file.package.as_deref().unwrap_or("");
@@ -188,7 +440,7 @@ fn generate_query_struct(
     }))
 }
 
-/// Generate a Mutation struct with resolver methods using gRPC client
+/// Generate a Mutation struct with resolver methods using the service backend
 fn generate_mutation_struct(
     file: &FileDescriptorProto,
     service: &ServiceDescriptorProto,
@@ -201,28 +453,27 @@ fn generate_mutation_struct(
     let mutation_name = format!("{}Mutation", svc_name.to_upper_camel_case());
     let mutation_ident = format_ident!("{}", mutation_name);
 
-    // gRPC client type - in submodule {service}_client::{Service}Client
-    let client_module = format!("{}_client", svc_name.to_snake_case());
-    let client_module_ident = format_ident!("{}", client_module);
-    let client_type = format!("{}Client", svc_name.to_upper_camel_case());
-    let client_ident = format_ident!("{}", client_type);
+    // Resolver backend (gRPC client or in-process service)
+    let backend_ident = format_ident!("{}Backend", svc_name.to_upper_camel_case());
 
-    // Generate resolver methods
-    let resolver_methods = generate_mutation_resolver_methods(file, svc_name, methods)?;
+    // Generate resolver methods, and result types of partial batch mutations
+    let (resolver_methods, result_types) = generate_mutation_resolver_methods(file, svc_name, methods)?;
+    let struct_doc = format!("Mutation resolvers from {} (via the service backend)", svc_name);
+
+    let module_doc = format!("GraphQL Mutation resolvers for {}", svc_name);
 
     let code = quote! {
-        //! GraphQL Mutation resolvers for #svc_name
+        #![doc = #module_doc]
         //! @generated
 
         #![allow(missing_docs)]
         #![allow(unused_imports)]
 
         use async_graphql::{Object, Context, Result};
-        use tonic::transport::Channel;
-        // Import gRPC client from parent module (blog/{svc}_client)
-        use super::super::#client_module_ident::#client_ident;
+        // Backend (gRPC client or in-process service) from the graphql module
+        use super::#backend_ident;
 
-        /// Mutation resolvers from #svc_name (uses gRPC client)
+        #[doc = #struct_doc]
         #[derive(Default)]
         pub struct #mutation_ident;
 
@@ -231,16 +482,15 @@ fn generate_mutation_struct(
             #resolver_methods
         }
 
+        #(#result_types)*
+
         // Client type alias
-        type Client = #client_ident<Channel>;
+        type Client = #backend_ident;
     };
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Determine output file path
     let package = file.package.as_deref().unwrap_or("");
@@ -257,14 +507,19 @@ fn generate_mutation_struct(
     }))
 }
 
-/// Generate Query resolver methods (get, list operations)
+/// Most ids a get-many resolver looks up in one request
+const MAX_GET_MANY_IDS: usize = 100;
+
+/// Generate Query resolver methods (get, get-many, list operations)
 fn generate_query_resolver_methods(
+    file: &FileDescriptorProto,
     svc_name: &str,
     methods: &[(
         &MethodDescriptorProto,
         crate::options::synapse::graphql::QueryOptions,
     )],
 ) -> Result<TokenStream, GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
     let mut method_tokens = Vec::new();
     let entity_name = svc_name.trim_end_matches("Service");
     let entity_snake = entity_name.to_snake_case();
@@ -272,6 +527,13 @@ fn generate_query_resolver_methods(
     for (method, opts) in methods {
         let method_name = method.name.as_deref().unwrap_or("");
 
+        // Get-many by the storage `operation` option or a `Get{Entities}ByIds` name
+        let is_get_many = get_cached_rpc_method_options(file_name, svc_name, method_name)
+            .filter(|o| !o.operation.is_empty())
+            .map(|o| o.operation.to_snake_case())
+            .unwrap_or_else(|| infer_operation(method_name).to_string())
+            == "get_many";
+
         // Determine Rust method name (snake_case - async-graphql converts to camelCase automatically)
         let field_name = if !opts.name.is_empty() {
             opts.name.to_snake_case()
@@ -312,12 +574,30 @@ fn generate_query_resolver_methods(
             })
             .unwrap_or_else(|| format_ident!("()"));
 
+        // Key type follows the request's id field (i64, or String for UUID keys)
+        let request_msg = method
+            .input_type
+            .as_ref()
+            .map(|t| t.rsplit('.').next().unwrap_or(t))
+            .and_then(|name| file.message_type.iter().find(|m| m.name.as_deref() == Some(name)));
+        let id_ty = super::key_type(request_msg, "id");
+
         let resolver = if is_list {
             // List operation - return connection with filter/orderBy support
             // Derive filter and orderBy types from entity name
             let filter_type = format_ident!("{}Filter", entity_name.to_upper_camel_case());
             let order_by_type = format_ident!("{}OrderBy", entity_name.to_upper_camel_case());
 
+            // Full-text search argument, when the request has a `search` field
+            let search_field = request_msg.and_then(|m| m.field.iter().find(|f| f.name.as_deref() == Some("search")));
+            let (search_arg, search_value) = match search_field {
+                Some(f) if f.proto3_optional.unwrap_or(false) => {
+                    (quote! { search: Option<String>, }, quote! { search, })
+                }
+                Some(_) => (quote! { search: Option<String>, }, quote! { search: search.unwrap_or_default(), }),
+                None => (quote! {}, quote! {}),
+            };
+
             quote! {
                 async fn #field_ident(
                     &self,
@@ -328,8 +608,10 @@ fn generate_query_resolver_methods(
                     last: Option<i32>,
                     filter: Option<super::#filter_type>,
                     order_by: Option<super::#order_by_type>,
+                    #search_arg
                 ) -> Result<super::#output_type> {
-                    let client = ctx.data_unchecked::<Client>();
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
                     let request = super::super::#request_type {
                         after,
                         before,
@@ -337,29 +619,84 @@ fn generate_query_resolver_methods(
                         last,
                         filter: filter.map(|f| f.into()),
                         order_by: order_by.map(|o| o.into()),
+                        #search_value
                     };
                     let response = client.clone().#grpc_method(request).await
-                        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+                        .map_err(super::status_error)?;
                     Ok(response.into_inner().into())
                 }
             }
+        } else if is_get_many {
+            // Get-many operation - one entry per id, in order, through the
+            // entity loader, which batches into the get-many RPC
+            let loader_ident = format_ident!("{}Loader", output_type);
+            let key_ty = super::key_type(request_msg, "ids");
+
+            // Node types hand out global IDs as `id`; other types their keys
+            let package = file.package.as_deref().unwrap_or("");
+            let output_msg = output_type.to_string();
+            let (decode, key) = match get_cached_graphql_type_options_in_package(package, &output_msg)
+                .filter(|o| o.node && !o.skip)
+            {
+                Some(type_opts) => {
+                    let graphql_name = if type_opts.name.is_empty() { output_msg } else { type_opts.name };
+                    let prefix = super::node::id_prefix(package, &graphql_name);
+                    let decode = super::object::generate_global_id_decode(&graphql_name, prefix.as_deref());
+                    (
+                        quote! { let decode = #decode; },
+                        quote! { decode(id).and_then(|local_id| local_id.parse().ok()) },
+                    )
+                }
+                None => (quote! {}, quote! { id.parse().ok() }),
+            };
+            let max_ids = proc_macro2::Literal::usize_unsuffixed(MAX_GET_MANY_IDS);
+            let too_many = format!("at most {} ids can be fetched at once", MAX_GET_MANY_IDS);
+            quote! {
+                async fn #field_ident(
+                    &self,
+                    ctx: &Context<'_>,
+                    ids: Vec<async_graphql::ID>,
+                ) -> Result<Vec<Option<super::#output_type>>> {
+                    if ids.len() > #max_ids {
+                        return Err(async_graphql::Error::new(#too_many));
+                    }
+                    let loader = ctx.data_unchecked::<async_graphql::dataloader::DataLoader<super::#loader_ident>>();
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    // Ids of other types and ids that are not keys resolve to
+                    // null, like ids without a row
+                    #decode
+                    let keys: Vec<Option<#key_ty>> = ids.iter().map(|id| #key).collect();
+                    let found = loader.load_many(keys.iter().flatten().cloned()).await?;
+                    Ok(keys
+                        .iter()
+                        .map(|key| key.as_ref().and_then(|key| found.get(key).cloned()))
+                        .collect())
+                }
+            }
         } else {
-            // Get operation - return single entity
+            // Get operation - return single entity; other request fields such
+            // as `expand` keep their defaults
+            let rest = if request_msg.is_some_and(|m| m.field.len() > 1) {
+                quote! { ..Default::default() }
+            } else {
+                quote! {}
+            };
             quote! {
                 async fn #field_ident(
                     &self,
                     ctx: &Context<'_>,
-                    id: i64,
+                    id: #id_ty,
                 ) -> Result<Option<super::#output_type>> {
-                    let client = ctx.data_unchecked::<Client>();
-                    let request = super::super::#request_type { id };
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    let request = super::super::#request_type { id, #rest };
                     match client.clone().#grpc_method(request).await {
                         Ok(response) => Ok(response.into_inner().#output_field.map(super::#output_type::from)),
                         Err(e) => {
                             if e.code() == tonic::Code::NotFound {
                                 Ok(None)
                             } else {
-                                Err(async_graphql::Error::new(e.to_string()))
+                                Err(super::status_error(e))
                             }
                         }
                     }
@@ -381,8 +718,10 @@ fn generate_mutation_resolver_methods(
         &MethodDescriptorProto,
         crate::options::synapse::graphql::MutationOptions,
     )],
-) -> Result<TokenStream, GeneratorError> {
+) -> Result<(TokenStream, Vec<TokenStream>), GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
     let mut method_tokens = Vec::new();
+    let mut result_types = Vec::new();
     let entity_name = svc_name.trim_end_matches("Service");
     let entity_snake = entity_name.to_snake_case();
 
@@ -409,6 +748,13 @@ fn generate_mutation_resolver_methods(
 
         let request_type = format_ident!("{}", request_type_name.to_upper_camel_case());
 
+        // Key type follows the request's id field (i64, or String for UUID keys)
+        let request_msg = file
+            .message_type
+            .iter()
+            .find(|m| m.name.as_deref() == Some(request_type_name.as_str()));
+        let id_ty = super::key_type(request_msg, "id");
+
         // Get output type from options
         let output_type = if !opts.output_type.is_empty() {
             format_ident!("{}", opts.output_type)
@@ -424,74 +770,294 @@ fn generate_mutation_resolver_methods(
         };
 
         // Determine operation type
-        let is_create = method_name.to_lowercase().starts_with("create");
+        // Upserts take the same input as creates
+        let is_create = method_name.to_lowercase().starts_with("create")
+            || method_name.to_lowercase().starts_with("upsert");
         let is_update = method_name.to_lowercase().starts_with("update");
         let is_delete = method_name.to_lowercase().starts_with("delete");
 
+        // Create-many mutations take a list of their item input
+        let create_many = request_msg
+            .filter(|_| method_name.to_lowercase().starts_with("createmany"))
+            .and_then(|_| super::input::create_many_input(file, method));
+        let input_request_name = create_many.as_ref().map_or(request_type_name.as_str(), |item| item.request.as_str());
+        let input_request_msg = file
+            .message_type
+            .iter()
+            .find(|m| m.name.as_deref() == Some(input_request_name));
+
         // Derive input type name from request type: CreateUserRequest → CreateUserInput
-        let derived_input_type = format_ident!(
-            "{}",
-            request_type.to_string().replace("Request", "Input")
-        );
+        let derived_input_type = match &create_many {
+            Some(item) => format_ident!("{}", item.input),
+            None => format_ident!("{}", input_request_name.to_upper_camel_case().replace("Request", "Input")),
+        };
 
-        // Check for context-injected fields in create operations
-        let context_fields = if is_create {
-            get_context_fields(file, &request_type_name)
+        // Context-injected fields are extracted before building the request
+        let context_fields = super::context::context_fields(file, input_request_name)?;
+        let ctx_extractions: Vec<_> = context_fields.iter().map(|cf| cf.extraction()).collect();
+        let ctx_args: Vec<_> = context_fields
+            .iter()
+            .map(|cf| format_ident!("{}", cf.name))
+            .collect();
+
+        // Custom validators run against the request built from the input before
+        // the gRPC call: creates, updates, and custom mutations with context fields
+        let builds_request = is_create || is_update || (!is_delete && !context_fields.is_empty());
+        let custom_validator = input_request_msg
+            .filter(|m| builds_request && has_custom_validators(file_name, m))
+            .and_then(|_| get_cached_validate_message_options(file_name, input_request_name))
+            .map(|opts| {
+                let domain_ident = format_ident!("{}", opts.name);
+                (
+                    quote! {
+                        let validation_ctx = super::super::prelude::ValidationContext {
+                            #[cfg(feature = "sea-orm")]
+                            db: ctx.data_opt::<sea_orm::DatabaseConnection>(),
+                            current_user: ctx.data_opt::<crate::CurrentUser>(),
+                        };
+                    },
+                    quote! { super::super::#domain_ident::validate_custom(&request, &validation_ctx).await },
+                )
+            });
+        let custom_check = match &custom_validator {
+            Some((validation_ctx, validate)) => quote! {
+                #validation_ctx
+                #validate.map_err(|e| {
+                    let messages: Vec<String> = e.errors().iter().map(ToString::to_string).collect();
+                    async_graphql::Error::new(messages.join("; "))
+                })?;
+            },
+            None => quote! {},
+        };
+
+        // `dryRun: true` runs the call with its writes rolled back
+        let (dry_run_arg, call) = if super::is_dry_run(file_name, svc_name, method_name) {
+            (
+                quote! {
+                    #[graphql(desc = "Validate and run the write without persisting it")]
+                    dry_run: Option<bool>,
+                },
+                quote! {
+                    super::super::prelude::with_dry_run(
+                        dry_run.unwrap_or(false),
+                        client.clone().#grpc_method(request),
+                    )
+                },
+            )
         } else {
-            Vec::new()
+            (quote! {}, quote! { client.clone().#grpc_method(request) })
         };
 
-        let resolver = if is_delete {
+        // Bulk updates and deletes take the entity's filter and return a row count
+        let bulk_filter = request_msg
+            .filter(|_| {
+                let lower = method_name.to_lowercase();
+                lower.starts_with("updatemany") || lower.starts_with("deletemany")
+            })
+            .and_then(|m| m.field.iter().find(|f| f.name.as_deref() == Some("filter")))
+            .and_then(|f| f.type_name.as_deref())
+            .map(|t| format_ident!("{}", t.rsplit('.').next().unwrap_or(t)));
+        let response_name = method
+            .output_type
+            .as_deref()
+            .map(|t| t.rsplit('.').next().unwrap_or(t))
+            .unwrap_or("");
+        let affected = affected_field(file, response_name);
+
+        let resolver = if let Some(filter_type) = bulk_filter.as_ref().filter(|_| is_update) {
+            // Update-many operation - a filter and the patch's input, returns the count
+            let patch_type = request_msg.and_then(update_many_patch_type).ok_or_else(|| {
+                GeneratorError::CodeGenError(format!(
+                    "{}: update-many requests need a `patch` message field",
+                    method_name
+                ))
+            })?;
+            let patch_input = format_ident!("{}", super::input::patch_input_name(&patch_type));
+            let patch_context = super::context::context_fields(file, &patch_type)?;
+            let patch_extractions: Vec<_> = patch_context.iter().map(|cf| cf.extraction()).collect();
+            let patch_args: Vec<_> = patch_context
+                .iter()
+                .map(|cf| format_ident!("{}", cf.name))
+                .collect();
+            let patch = if patch_context.is_empty() {
+                quote! { input.into() }
+            } else {
+                quote! { input.to_request(#(#patch_args),*) }
+            };
+            quote! {
+                async fn #field_ident(
+                    &self,
+                    ctx: &Context<'_>,
+                    filter: super::#filter_type,
+                    input: super::#patch_input,
+                    #dry_run_arg
+                ) -> Result<i64> {
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    #(#patch_extractions)*
+                    let request = super::super::#request_type {
+                        filter: Some(filter.into()),
+                        patch: Some(#patch),
+                    };
+                    let response = #call.await
+                        .map_err(super::status_error)?;
+                    Ok(response.into_inner().#affected as i64)
+                }
+            }
+        } else if let Some(filter_type) = bulk_filter.as_ref().filter(|_| is_delete) {
+            // Delete-many operation - a filter, returns the count
+            quote! {
+                async fn #field_ident(
+                    &self,
+                    ctx: &Context<'_>,
+                    filter: super::#filter_type,
+                    #dry_run_arg
+                ) -> Result<i64> {
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    let request = super::super::#request_type {
+                        filter: Some(filter.into()),
+                    };
+                    let response = #call.await
+                        .map_err(super::status_error)?;
+                    Ok(response.into_inner().#affected as i64)
+                }
+            }
+        } else if let Some(create_many) = &create_many {
+            // Create-many operation - a list of create inputs, returns the created nodes
+            let item_request = format_ident!("{}", create_many.request.to_upper_camel_case());
+            let response_msg = file
+                .message_type
+                .iter()
+                .find(|m| m.name.as_deref() == Some(response_name));
+            let output_field = if !opts.output_field.is_empty() {
+                format_ident!("{}", opts.output_field)
+            } else {
+                let field = response_msg
+                    .and_then(|m| {
+                        m.field
+                            .iter()
+                            .find(|f| f.label() == Label::Repeated && !batch::is_errors_field(f))
+                    })
+                    .and_then(|f| f.name.clone())
+                    .unwrap_or_else(|| format!("{}s", entity_snake));
+                format_ident!("{}", field)
+            };
+
+            let to_request = if context_fields.is_empty() {
+                quote! { super::super::#item_request::from }
+            } else {
+                quote! { |input| input.to_request(#(Clone::clone(&#ctx_args)),*) }
+            };
+            let item_checks = if custom_check.is_empty() {
+                quote! {}
+            } else {
+                quote! {
+                    for request in &items {
+                        #custom_check
+                    }
+                }
+            };
+
+            // Responses reporting per-item errors give a result per item
+            let batch_result = response_msg.and_then(batch::errors_field).map(|errors_field| {
+                let result = super::batch::BatchResult {
+                    ident: format_ident!("{}Result", method_name.to_upper_camel_case()),
+                    mutation: field_name.to_lower_camel_case(),
+                    output_type: output_type.clone(),
+                    output_field: output_field.clone(),
+                    errors_field: format_ident!("{}", errors_field),
+                };
+                result_types.push(result.generate_type());
+                result
+            });
+            // Items failing custom validators get their own results, in the storage's mode
+            let atomic = get_cached_rpc_method_options(file_name, svc_name, method_name).is_some_and(|o| o.atomic);
+            let (return_type, item_checks, results) = match (&batch_result, &custom_validator) {
+                (Some(result), Some((validation_ctx, validate))) => {
+                    let ident = &result.ident;
+                    let checks = result.generate_item_checks(validate.clone(), atomic);
+                    (
+                        quote! { Vec<#ident> },
+                        quote! {
+                            let count = items.len();
+                            #validation_ctx
+                            #checks
+                        },
+                        result.generate_results(true),
+                    )
+                }
+                (Some(result), None) => {
+                    let ident = &result.ident;
+                    (quote! { Vec<#ident> }, quote! { let count = items.len(); }, result.generate_results(false))
+                }
+                (None, _) => (
+                    quote! { Vec<super::#output_type> },
+                    item_checks,
+                    quote! {
+                        Ok(response.into_inner().#output_field.into_iter().map(super::#output_type::from).collect())
+                    },
+                ),
+            };
+
+            quote! {
+                async fn #field_ident(
+                    &self,
+                    ctx: &Context<'_>,
+                    input: Vec<super::#derived_input_type>,
+                    #dry_run_arg
+                ) -> Result<#return_type> {
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    // Extract context-injected fields
+                    #(#ctx_extractions)*
+                    let items: Vec<super::super::#item_request> = input
+                        .into_iter()
+                        .map(#to_request)
+                        .collect();
+                    #item_checks
+                    let request = super::super::#request_type { items };
+                    let response = #call.await
+                        .map_err(super::status_error)?;
+                    #results
+                }
+            }
+        } else if is_delete {
             // Delete operation - return bool
             quote! {
                 async fn #field_ident(
                     &self,
                     ctx: &Context<'_>,
-                    id: i64,
+                    id: #id_ty,
+                    #dry_run_arg
                 ) -> Result<bool> {
-                    let client = ctx.data_unchecked::<Client>();
-                    let request = super::super::#request_type { id };
-                    let response = client.clone().#grpc_method(request).await
-                        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    #(#ctx_extractions)*
+                    let request = super::super::#request_type { id, #(#ctx_args,)* };
+                    let response = #call.await
+                        .map_err(super::status_error)?;
                     Ok(response.into_inner().success)
                 }
             }
         } else if is_create && !context_fields.is_empty() {
             // Create operation with context-injected fields
             // Extract values from context and call to_request()
-            let ctx_extractions: Vec<_> = context_fields
-                .iter()
-                .map(|cf| {
-                    let field_ident = format_ident!("{}", cf.name);
-                    let path = &cf.path;
-                    // For now, generate a placeholder that extracts from CurrentUser
-                    // The actual implementation depends on the context type
-                    quote! {
-                        let #field_ident = ctx
-                            .data::<crate::CurrentUser>()
-                            .map_err(|_| async_graphql::Error::new(format!("Authentication required for field '{}' (from context path: {})", stringify!(#field_ident), #path)))?
-                            .id;
-                    }
-                })
-                .collect();
-
-            let ctx_args: Vec<_> = context_fields
-                .iter()
-                .map(|cf| format_ident!("{}", cf.name))
-                .collect();
-
             quote! {
                 async fn #field_ident(
                     &self,
                     ctx: &Context<'_>,
                     input: super::#derived_input_type,
+                    #dry_run_arg
                 ) -> Result<super::#output_type> {
-                    let client = ctx.data_unchecked::<Client>();
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
                     // Extract context-injected fields
                     #(#ctx_extractions)*
                     let request = input.to_request(#(#ctx_args),*);
-                    let response = client.clone().#grpc_method(request).await
-                        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+                    #custom_check
+                    let response = #call.await
+                        .map_err(super::status_error)?;
                     Ok(response.into_inner().#output_field.map(super::#output_type::from)
                         .ok_or_else(|| async_graphql::Error::new("Failed to create"))?)
                 }
@@ -503,11 +1069,14 @@ fn generate_mutation_resolver_methods(
                     &self,
                     ctx: &Context<'_>,
                     input: super::#derived_input_type,
+                    #dry_run_arg
                 ) -> Result<super::#output_type> {
-                    let client = ctx.data_unchecked::<Client>();
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
                     let request: super::super::#request_type = input.into();
-                    let response = client.clone().#grpc_method(request).await
-                        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+                    #custom_check
+                    let response = #call.await
+                        .map_err(super::status_error)?;
                     Ok(response.into_inner().#output_field.map(super::#output_type::from)
                         .ok_or_else(|| async_graphql::Error::new("Failed to create"))?)
                 }
@@ -518,17 +1087,41 @@ fn generate_mutation_resolver_methods(
                 async fn #field_ident(
                     &self,
                     ctx: &Context<'_>,
-                    id: i64,
+                    id: #id_ty,
                     input: super::#derived_input_type,
+                    #dry_run_arg
                 ) -> Result<super::#output_type> {
-                    let client = ctx.data_unchecked::<Client>();
-                    let request = input.to_request(id);
-                    let response = client.clone().#grpc_method(request).await
-                        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    #(#ctx_extractions)*
+                    let request = input.to_request(id, #(#ctx_args),*);
+                    #custom_check
+                    let response = #call.await
+                        .map_err(super::status_error)?;
                     Ok(response.into_inner().#output_field.map(super::#output_type::from)
                         .ok_or_else(|| async_graphql::Error::new("Failed to update"))?)
                 }
             }
+        } else if !context_fields.is_empty() {
+            // Custom mutation with context-injected fields - derived input without them
+            quote! {
+                async fn #field_ident(
+                    &self,
+                    ctx: &Context<'_>,
+                    input: super::#derived_input_type,
+                    #dry_run_arg
+                ) -> Result<super::#output_type> {
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    #(#ctx_extractions)*
+                    let request = input.to_request(#(#ctx_args),*);
+                    #custom_check
+                    let response = #call.await
+                        .map_err(super::status_error)?;
+                    Ok(response.into_inner().#output_field.map(super::#output_type::from)
+                        .ok_or_else(|| async_graphql::Error::new("Operation failed"))?)
+                }
+            }
         } else {
             // Generic mutation
             quote! {
@@ -536,10 +1129,12 @@ fn generate_mutation_resolver_methods(
                     &self,
                     ctx: &Context<'_>,
                     request: super::super::#request_type,
+                    #dry_run_arg
                 ) -> Result<super::#output_type> {
-                    let client = ctx.data_unchecked::<Client>();
-                    let response = client.clone().#grpc_method(request).await
-                        .map_err(|e| async_graphql::Error::new(e.to_string()))?;
+                    let client = &ctx.data_unchecked::<Client>().for_request(ctx);
+                    let _permit = super::concurrency::acquire(ctx).await;
+                    let response = #call.await
+                        .map_err(super::status_error)?;
                     Ok(response.into_inner().#output_field.map(super::#output_type::from)
                         .ok_or_else(|| async_graphql::Error::new("Operation failed"))?)
                 }
@@ -549,5 +1144,220 @@ fn generate_mutation_resolver_methods(
         method_tokens.push(resolver);
     }
 
-    Ok(quote! { #(#method_tokens)* })
+    Ok((quote! { #(#method_tokens)* }, result_types))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn method(name: &str, server_streaming: bool) -> MethodDescriptorProto {
+        MethodDescriptorProto {
+            name: Some(name.to_string()),
+            input_type: Some(format!(".blog.{}Request", name)),
+            output_type: Some(".blog.Post".to_string()),
+            server_streaming: Some(server_streaming),
+            ..Default::default()
+        }
+    }
+
+    fn backend(methods: Vec<MethodDescriptorProto>) -> String {
+        backend_with(methods, false)
+    }
+
+    fn backend_with(methods: Vec<MethodDescriptorProto>, forward_user: bool) -> String {
+        let file = FileDescriptorProto {
+            name: Some("blog/post.proto".to_string()),
+            package: Some("blog".to_string()),
+            ..Default::default()
+        };
+        let service = ServiceDescriptorProto {
+            name: Some("PostService".to_string()),
+            method: methods,
+            ..Default::default()
+        };
+        generate_backend(&file, &service, forward_user).unwrap().content.unwrap()
+    }
+
+    #[test]
+    fn test_in_process_backend_forwards_current_user() {
+        let code = backend_with(vec![method("GetPost", false)], true);
+        let compact: String = code.split_whitespace().collect();
+        assert!(compact.contains("InProcess(Arc<dynPostService>,Option<crate::CurrentUser>)"));
+        assert!(compact.contains("Self::InProcess(service.clone(),ctx.data_opt::<crate::CurrentUser>().cloned(),)"));
+        assert!(compact.contains("Self::InProcess(service,user)=>{letmutrequest=tonic::Request::new(request);"));
+        assert!(compact.contains("request.extensions_mut().insert(user.clone());"));
+
+        // Services that never read the principal don't name `CurrentUser`
+        let code = backend(vec![method("GetPost", false)]);
+        assert!(code.contains("pub fn for_request(&self, _ctx"));
+        assert!(!code.contains("CurrentUser"));
+    }
+
+    #[test]
+    fn test_backend_doc_names_service() {
+        let code = backend(vec![method("GetPost", false)]);
+        assert!(code.contains("GraphQL resolver backend for PostService"));
+        assert!(!code.contains("#svc_name"));
+    }
+
+    #[test]
+    fn test_resolver_module_docs_name_service() {
+        let file = FileDescriptorProto {
+            name: Some("blog/post.proto".to_string()),
+            package: Some("blog".to_string()),
+            ..Default::default()
+        };
+        let service = ServiceDescriptorProto {
+            name: Some("PostService".to_string()),
+            ..Default::default()
+        };
+
+        let query = generate_query_struct(&file, &service, &[]).unwrap().unwrap().content.unwrap();
+        assert!(query.contains("GraphQL Query resolvers for PostService"));
+        assert!(!query.contains("#svc_name"));
+
+        let mutation = generate_mutation_struct(&file, &service, &[]).unwrap().unwrap().content.unwrap();
+        assert!(mutation.contains("GraphQL Mutation resolvers for PostService"));
+        assert!(!mutation.contains("#svc_name"));
+    }
+
+    #[test]
+    fn test_backend_names_stream_types_of_in_process_service() {
+        let code = backend(vec![method("GetPost", false)]);
+        assert!(code.contains("InProcess(Arc<dyn PostService>)"));
+
+        let code = backend(vec![method("GetPost", false), method("StreamPosts", true)]);
+        let compact: String = code.split_whitespace().collect();
+        assert!(compact.contains("InProcess(Arc<dynPostService<StreamPostsStream=std::pin::Pin<"));
+        assert!(compact.contains("pubfnin_process(service:implPostService<StreamPostsStream=std::pin::Pin<"));
+        // Streaming RPCs still aren't exposed through resolvers
+        assert!(!code.contains("pub async fn stream_posts"));
+    }
+
+    #[test]
+    fn test_get_many_decodes_global_ids_of_node_types() {
+        use crate::options::synapse::graphql::QueryOptions;
+        use crate::storage::seaorm::options::{cache_file_package, cache_graphql_type_options, graphql};
+
+        let query = |package: &str| {
+            let file = FileDescriptorProto {
+                name: Some(format!("{}/services.proto", package)),
+                package: Some(package.to_string()),
+                ..Default::default()
+            };
+            let get_many = MethodDescriptorProto {
+                name: Some("GetPostsByIds".to_string()),
+                input_type: Some(format!(".{}.GetPostsByIdsRequest", package)),
+                ..Default::default()
+            };
+            generate_query_resolver_methods(&file, "PostService", &[(&get_many, QueryOptions::default())])
+                .unwrap()
+                .to_string()
+        };
+
+        // Node types decode the `Type:id` global IDs their `id` field hands out
+        cache_file_package("get_many_node/entities.proto", "get_many_node");
+        cache_graphql_type_options(
+            "get_many_node/entities.proto",
+            "Post",
+            graphql::TypeOptions { node: true, ..Default::default() },
+        );
+        let code = query("get_many_node");
+        assert!(code.contains("if ids . len () > 100 {"));
+        assert!(code.contains("raw . strip_prefix (\"Post:\")"));
+        assert!(code.contains("decode (id) . and_then (| local_id | local_id . parse () . ok ())"));
+
+        // Other types are looked up by their keys
+        let code = query("get_many_plain");
+        assert!(code.contains("if ids . len () > 100 {"));
+        assert!(code.contains("ids . iter () . map (| id | id . parse () . ok ())"));
+        assert!(!code.contains("strip_prefix"));
+    }
+
+    #[test]
+    fn test_custom_mutation_with_context_runs_custom_validators() {
+        use crate::options::synapse::graphql::{ContextSource, MutationOptions};
+        use crate::options::synapse::validate;
+        use crate::storage::seaorm::options::{
+            cache_graphql_field_options, cache_validate_field_options, cache_validate_message_options, graphql,
+        };
+        use crate::test_util::field;
+        use prost_types::field_descriptor_proto::Type;
+        use prost_types::DescriptorProto;
+
+        let mutation = |package: &str, custom: bool| {
+            let file_name = format!("{}/services.proto", package);
+            let file = FileDescriptorProto {
+                name: Some(file_name.clone()),
+                package: Some(package.to_string()),
+                message_type: vec![DescriptorProto {
+                    name: Some("ArchivePostRequest".to_string()),
+                    field: vec![field("post_id", 1, Type::Int64), field("archived_by", 2, Type::String)],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            };
+            cache_graphql_field_options(
+                &file_name,
+                "ArchivePostRequest",
+                2,
+                graphql::FieldOptions {
+                    from_context: Some(ContextSource {
+                        path: "current_user.id".to_string(),
+                        required: Some(true),
+                        error_message: String::new(),
+                    }),
+                    ..Default::default()
+                },
+            );
+            if custom {
+                cache_validate_message_options(
+                    &file_name,
+                    "ArchivePostRequest",
+                    validate::MessageOptions {
+                        name: "ArchivePost".to_string(),
+                        generate_conversion: true,
+                        ..Default::default()
+                    },
+                );
+                cache_validate_field_options(
+                    &file_name,
+                    "ArchivePostRequest",
+                    1,
+                    validate::FieldOptions {
+                        rules: Some(validate::Rules {
+                            custom: "validators::archivable".to_string(),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    },
+                );
+            }
+            let archive = MethodDescriptorProto {
+                name: Some("ArchivePost".to_string()),
+                input_type: Some(format!(".{}.ArchivePostRequest", package)),
+                output_type: Some(format!(".{}.ArchivePostResponse", package)),
+                ..Default::default()
+            };
+            let (code, _) =
+                generate_mutation_resolver_methods(&file, "PostService", &[(&archive, MutationOptions::default())])
+                    .unwrap();
+            code.to_string()
+        };
+
+        // The request built with the context's values is checked before the call
+        let code = mutation("custom_context_validated", true);
+        let built = code.find("let request = input . to_request (archived_by) ;").unwrap();
+        let checked = code
+            .find("super :: super :: ArchivePost :: validate_custom (& request , & validation_ctx) . await")
+            .unwrap();
+        let called = code.find("client . clone () . archive_post (request)").unwrap();
+        assert!(built < checked && checked < called);
+
+        // Without custom validators there is nothing to check
+        let code = mutation("custom_context_plain", false);
+        assert!(code.contains("let request = input . to_request (archived_by) ;"));
+        assert!(!code.contains("validate_custom"));
+    }
 }
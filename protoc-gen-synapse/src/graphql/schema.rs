@@ -4,9 +4,11 @@
 //! This creates the graphql/mod.rs that wires all generated types together.
 
 use crate::error::GeneratorError;
+use crate::storage::seaorm::implementation::update_many_patch_type;
 use crate::storage::seaorm::options::{
     get_cached_entity_options, get_cached_graphql_mutation_options,
-    get_cached_graphql_service_options, get_cached_graphql_type_options,
+    get_cached_graphql_service_options, get_cached_graphql_type_options, get_cached_service_options,
+    same_package_name,
 };
 use heck::{ToSnakeCase, ToUpperCamelCase};
 use proc_macro2::TokenStream;
@@ -15,6 +17,7 @@ use prost_types::FileDescriptorProto;
 use quote::{format_ident, quote};
 
 /// Collect information about all generated types for the schema
+#[derive(Default)]
 pub struct SchemaInfo {
     /// Entity types (message name -> snake_case module name)
     pub entities: Vec<(String, String)>,
@@ -28,6 +31,8 @@ pub struct SchemaInfo {
     pub has_auto_filters: bool,
     /// HasMany relations (parent_type, related_type) for DataLoader registration
     pub has_many_relations: Vec<(String, String)>,
+    /// Targets of remote relations in other packages (e.g. `iam.User`)
+    pub remote_relations: Vec<String>,
 }
 
 /// Collect schema information from a file descriptor
@@ -45,6 +50,7 @@ pub fn collect_schema_info(
         services: Vec::new(),
         has_auto_filters: false,
         has_many_relations: Vec::new(),
+        remote_relations: Vec::new(),
     };
 
     // Search all files for entities that belong to this package (including sub-packages)
@@ -95,6 +101,11 @@ pub fn collect_schema_info(
                             relation.related.clone(),
                         ));
                     }
+
+                    // Remote relations load through the other service's client
+                    if is_remote(proto_package, relation) && !info.remote_relations.contains(&relation.related) {
+                        info.remote_relations.push(relation.related.clone());
+                    }
                 }
             }
         }
@@ -128,16 +139,45 @@ pub fn collect_schema_info(
             }
 
             // Check if this is a create or update operation
-            let is_create = method_name.to_lowercase().starts_with("create");
+            // Upserts take the same input as creates
+            let is_create = method_name.to_lowercase().starts_with("create")
+                || method_name.to_lowercase().starts_with("upsert");
             let is_update = method_name.to_lowercase().starts_with("update");
 
-            if is_create || is_update {
-                // Derive input type name from request type
-                if let Some(request_type) = &method.input_type {
-                    let request_name = request_type.rsplit('.').next().unwrap_or(request_type);
-                    let input_name = request_name.replace("Request", "Input");
+            if let Some(request_type) = &method.input_type {
+                let request_name = request_type.rsplit('.').next().unwrap_or(request_type).to_string();
+                // Update-many mutations take the input of their patch
+                if method_name.to_lowercase().starts_with("updatemany") {
+                    if let Some(patch_type) = file
+                        .message_type
+                        .iter()
+                        .find(|m| m.name.as_deref() == Some(request_name.as_str()))
+                        .and_then(update_many_patch_type)
+                    {
+                        let input_name = super::input::patch_input_name(&patch_type);
+                        let input_snake = input_name.to_snake_case();
+                        if !info.auto_input_types.iter().any(|(name, _)| *name == input_name) {
+                            info.auto_input_types.push((input_name, input_snake));
+                        }
+                    }
+                    continue;
+                }
+                // Other mutations only get an input to hide context-injected fields
+                if is_create || is_update || super::input::is_custom_with_context(file, method_name, &request_name) {
+                    // Create-many mutations take a list of their item input
+                    let input_name = if method_name.to_lowercase().starts_with("createmany") {
+                        let Some(item) = super::input::create_many_input(file, method) else {
+                            continue;
+                        };
+                        item.input
+                    } else {
+                        // Derive input type name from request type
+                        request_name.replace("Request", "Input")
+                    };
                     let input_snake = input_name.to_snake_case();
-                    info.auto_input_types.push((input_name, input_snake));
+                    if !info.auto_input_types.iter().any(|(name, _)| *name == input_name) {
+                        info.auto_input_types.push((input_name, input_snake));
+                    }
                 }
             }
         }
@@ -146,6 +186,14 @@ pub fn collect_schema_info(
     info
 }
 
+/// Check whether a relation is a remote belongs_to into another package
+fn is_remote(package: &str, relation: &crate::options::synapse::storage::RelationDef) -> bool {
+    use crate::options::synapse::storage::RelationType;
+    relation.remote
+        && relation.r#type() == RelationType::BelongsTo
+        && same_package_name(package, &relation.related).is_none()
+}
+
 /// Generate the graphql/mod.rs file that wires everything together
 pub fn generate(
     file: &FileDescriptorProto,
@@ -211,8 +259,9 @@ pub fn generate(
         // Entity loader (for BelongsTo relations)
         let loader_mod = format_ident!("{}_loader", snake);
         let loader_type = format_ident!("{}", loader_name);
+        let fetcher_type = format_ident!("{}Fetcher", name);
         mod_declarations.push(quote! { mod #loader_mod; });
-        pub_uses.push(quote! { pub use #loader_mod::#loader_type; });
+        pub_uses.push(quote! { pub use #loader_mod::{#loader_type, #fetcher_type}; });
     }
 
     // HasMany relation loaders (e.g., PostsByUserLoader)
@@ -248,6 +297,69 @@ pub fn generate(
         pub_uses.push(quote! { pub use #mod_name::#type_name; });
     }
 
+    // Gateway HTTP wiring (request limits, CORS, CSRF for the axum router)
+    if !info.services.is_empty() {
+        mod_declarations.push(quote! { pub mod gateway; });
+        pub_uses.push(quote! { pub use gateway::{execute_graphql, GatewayConfig, Http2Config, CorsConfig, CsrfConfig, Problem, UpstreamConfig, UpstreamError}; });
+    }
+
+    // Per-document budget for the resolvers' gRPC calls
+    if !info.services.is_empty() {
+        mod_declarations.push(quote! { pub mod concurrency; });
+        pub_uses.push(quote! { pub use concurrency::{ConcurrencyBudget, ConcurrencyLimit}; });
+    }
+
+    // SDL compatibility checks against a committed snapshot
+    if !info.services.is_empty() {
+        mod_declarations.push(quote! { pub mod compat; });
+        pub_uses.push(quote! { pub use compat::{check_compat, CompatReport, SchemaChange}; });
+    }
+
+    // Partial degradation of non-critical relations
+    let degrades = !info.services.is_empty() && super::degrade::has_non_critical(file, all_files);
+    if degrades {
+        mod_declarations.push(quote! { pub mod degrade; });
+        pub_uses.push(quote! { pub use degrade::DegradedWarnings; });
+    }
+
+    // Fault injection for resolver gRPC clients (chaos=true)
+    if !info.services.is_empty() && crate::params::chaos() {
+        mod_declarations.push(quote! { pub mod chaos; });
+        pub_uses.push(quote! { pub use chaos::{FaultConfig, FaultLayer}; });
+    }
+
+    // Record/replay of resolver gRPC calls (record_replay=true)
+    if !info.services.is_empty() && crate::params::record_replay() {
+        mod_declarations.push(quote! { pub mod cassette; });
+        pub_uses.push(quote! { pub use cassette::Cassette; });
+    }
+
+    // Admin back-office (needs the package registry)
+    if super::admin::is_enabled(file, all_files) {
+        mod_declarations.push(quote! { pub mod admin; });
+    }
+
+    // Full-text search resolvers (merge SearchQuery into the query root)
+    if !super::search::collect(file, all_files).is_empty() {
+        mod_declarations.push(quote! { pub mod search; });
+        pub_uses.push(quote! { pub use search::SearchQuery; });
+    }
+
+    // Federation entity resolvers (merged into the query root)
+    let federation = !super::federation::collect(file, all_files).is_empty();
+    if federation {
+        mod_declarations.push(quote! { pub mod federation; });
+        pub_uses.push(quote! { pub use federation::{EntityCache, FederationQuery}; });
+    }
+
+    // Entity change subscriptions (the schema's subscription root)
+    let subscriptions = !super::subscription::collect(file, all_files).is_empty();
+    if subscriptions {
+        mod_declarations.push(quote! { pub mod subscription; });
+        pub_uses.push(quote! { pub use subscription::{MutationType, Subscription}; });
+        pub_uses.push(quote! { pub use gateway::{graphql_subscription, SUBSCRIPTION_PATH}; });
+    }
+
     // Service resolver modules (Query and Mutation)
     let mut query_imports = Vec::new();
     let mut mutation_imports = Vec::new();
@@ -269,6 +381,12 @@ pub fn generate(
         mod_declarations.push(quote! { mod #mutation_mod; });
         mutation_imports.push(quote! { pub use #mutation_mod::#mutation_type; });
 
+        // Resolver backend (gRPC client or in-process service)
+        let backend_mod = format_ident!("{}_backend", svc_snake);
+        let backend_type = format_ident!("{}Backend", svc_camel);
+        mod_declarations.push(quote! { mod #backend_mod; });
+        pub_uses.push(quote! { pub use #backend_mod::#backend_type; });
+
         // Client type (from tonic-generated submodule)
         let client_module = format_ident!("{}_client", svc_snake);
         let client_type = format_ident!("{}Client", svc_camel);
@@ -276,11 +394,23 @@ pub fn generate(
     }
 
     // Generate the combined Query and Mutation
-    let combined_query = generate_combined_query(&info.services);
+    let combined_query = generate_combined_query(&info.services, federation);
     let combined_mutation = generate_combined_mutation(&info.services);
 
+    // Status conversion for resolvers, and storage errors for in-process fetchers
+    let storage_services: Vec<String> =
+        info.services.iter().filter(|svc| generates_storage(file, svc)).cloned().collect();
+    let error_conversions = generate_error_conversions(&storage_services);
+
     // Generate schema builder
-    let schema_builder = generate_schema_builder(&info.services, &info.entities, &info.has_many_relations);
+    let limited = !super::concurrency::collect_limits(file).is_empty();
+    let schema_builder = generate_schema_builder(
+        &info,
+        limited,
+        degrades,
+        federation,
+        subscriptions,
+    );
 
     let code = quote! {
         //! GraphQL module
@@ -307,7 +437,15 @@ pub fn generate(
         use tonic::transport::Channel;
         #(#client_imports)*
 
-        // Combined Query
+        /// Storage-backed fetcher for DataLoaders
+        ///
+        /// Wraps a storage implementation so loaders query it in-process
+        /// instead of going through a gRPC client (monolith deployments).
+        pub struct StorageFetcher<S>(pub S);
+
+        #error_conversions
+
+                // Combined Query
         #combined_query
 
         // Combined Mutation
@@ -319,10 +457,7 @@ pub fn generate(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Determine output path using package name
     let package = file.package.as_deref().unwrap_or("");
@@ -335,8 +470,86 @@ pub fn generate(
     }))
 }
 
+/// Whether a service has a storage module, and so a `StorageError`
+fn generates_storage(file: &FileDescriptorProto, service_name: &str) -> bool {
+    let file_name = file.name.as_deref().unwrap_or("");
+    get_cached_service_options(file_name, service_name).is_some_and(|o| !o.skip && o.generate_storage)
+}
+
+/// Generate `status_error` and the `StorageError` conversions of the services
+///
+/// `services` are the services with a storage module.
+///
+/// Resolvers call services through gRPC statuses, which `status_error` turns
+/// into GraphQL errors with the status code in `extensions.code`. Storage
+/// errors of in-process fetchers convert through the same status.
+fn generate_error_conversions(services: &[String]) -> TokenStream {
+    let storage_conversions = services.iter().map(|svc| {
+        let storage_mod = format_ident!("{}_storage", svc.to_snake_case());
+        quote! {
+            impl From<super::storage::#storage_mod::StorageError> for async_graphql::Error {
+                fn from(e: super::storage::#storage_mod::StorageError) -> Self {
+                    status_error(e.into())
+                }
+            }
+        }
+    });
+
+    quote! {
+        /// GraphQL error of a failed service call
+        ///
+        /// The message is the status message. `extensions.code` is the
+        /// status code (e.g. `NOT_FOUND`), `extensions.field` names the
+        /// conflicting field of an `ALREADY_EXISTS`, and `extensions.retryAfter`
+        /// forwards the seconds of a status' `retry-after` metadata.
+        pub fn status_error(status: tonic::Status) -> async_graphql::Error {
+            use async_graphql::ErrorExtensions;
+            let code = match status.code() {
+                tonic::Code::Ok => "OK",
+                tonic::Code::Cancelled => "CANCELLED",
+                tonic::Code::Unknown => "UNKNOWN",
+                tonic::Code::InvalidArgument => "INVALID_ARGUMENT",
+                tonic::Code::DeadlineExceeded => "DEADLINE_EXCEEDED",
+                tonic::Code::NotFound => "NOT_FOUND",
+                tonic::Code::AlreadyExists => "ALREADY_EXISTS",
+                tonic::Code::PermissionDenied => "PERMISSION_DENIED",
+                tonic::Code::ResourceExhausted => "RESOURCE_EXHAUSTED",
+                tonic::Code::FailedPrecondition => "FAILED_PRECONDITION",
+                tonic::Code::Aborted => "ABORTED",
+                tonic::Code::OutOfRange => "OUT_OF_RANGE",
+                tonic::Code::Unimplemented => "UNIMPLEMENTED",
+                tonic::Code::Internal => "INTERNAL",
+                tonic::Code::Unavailable => "UNAVAILABLE",
+                tonic::Code::DataLoss => "DATA_LOSS",
+                tonic::Code::Unauthenticated => "UNAUTHENTICATED",
+            };
+            let field = status
+                .metadata()
+                .get("x-conflict-field")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let retry_after = status
+                .metadata()
+                .get("retry-after")
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<u64>().ok());
+            async_graphql::Error::new(status.message()).extend_with(|_, extensions| {
+                extensions.set("code", code);
+                if let Some(field) = &field {
+                    extensions.set("field", field.as_str());
+                }
+                if let Some(seconds) = retry_after {
+                    extensions.set("retryAfter", seconds);
+                }
+            })
+        }
+
+        #(#storage_conversions)*
+    }
+}
+
 /// Generate combined Query using MergedObject
-fn generate_combined_query(services: &[String]) -> TokenStream {
+fn generate_combined_query(services: &[String], federation: bool) -> TokenStream {
     if services.is_empty() {
         return quote! {
             pub struct Query;
@@ -348,13 +561,16 @@ fn generate_combined_query(services: &[String]) -> TokenStream {
         };
     }
 
-    let query_types: Vec<_> = services
+    let mut query_types: Vec<_> = services
         .iter()
         .map(|s| {
             let query_type = format_ident!("{}Query", s.to_upper_camel_case());
             quote! { #query_type }
         })
         .collect();
+    if federation {
+        query_types.push(quote! { FederationQuery });
+    }
 
     quote! {
         /// Combined Query merging all service queries
@@ -393,23 +609,37 @@ fn generate_combined_mutation(services: &[String]) -> TokenStream {
 
 /// Generate schema builder function
 fn generate_schema_builder(
-    services: &[String],
-    entities: &[(String, String)],
-    has_many_relations: &[(String, String)],
+    info: &SchemaInfo,
+    limited: bool,
+    degrades: bool,
+    federation: bool,
+    subscriptions: bool,
 ) -> TokenStream {
+    let services = &info.services;
+
     // Generate client parameters (one per service)
     let client_params: Vec<_> = services
         .iter()
         .map(|s| {
             let svc_snake = s.to_snake_case();
             let svc_camel = s.to_upper_camel_case();
-            let client_type = format_ident!("{}Client", svc_camel);
+            let backend_type = format_ident!("{}Backend", svc_camel);
             let param_name = format_ident!("{}_client", svc_snake);
-            quote! { #param_name: #client_type<Channel> }
+            quote! { #param_name: impl Into<#backend_type> }
         })
         .collect();
 
-    // Generate client data registration
+    // Convert clients/services into backends
+    let backend_bindings: Vec<_> = services
+        .iter()
+        .map(|s| {
+            let param_name = format_ident!("{}_client", s.to_snake_case());
+            let backend_type = format_ident!("{}Backend", s.to_upper_camel_case());
+            quote! { let #param_name: #backend_type = #param_name.into(); }
+        })
+        .collect();
+
+    // Generate backend data registration
     let client_data: Vec<_> = services
         .iter()
         .map(|s| {
@@ -420,7 +650,8 @@ fn generate_schema_builder(
         .collect();
 
     // Generate DataLoader creation and data registration for each entity (BelongsTo)
-    let loader_data: Vec<_> = entities
+    let loader_data: Vec<_> = info
+        .entities
         .iter()
         .map(|(name, _snake)| {
             let loader_type = format_ident!("{}Loader", name);
@@ -436,7 +667,8 @@ fn generate_schema_builder(
         .collect();
 
     // Generate DataLoader creation for HasMany relations (e.g., PostsByUserLoader)
-    let relation_loader_data: Vec<_> = has_many_relations
+    let relation_loader_data: Vec<_> = info
+        .has_many_relations
         .iter()
         .map(|(parent_type, related_type)| {
             let loader_type = format_ident!(
@@ -455,22 +687,188 @@ fn generate_schema_builder(
         })
         .collect();
 
+    // Load remote relations through the other package's backend
+    // (e.g. iam.User -> iam_user_service_client: impl Into<iam::graphql::UserServiceBackend>)
+    let mut remote_params = Vec::new();
+    let mut remote_bindings = Vec::new();
+    let mut remote_loader_data = Vec::new();
+    for related in &info.remote_relations {
+        let (package, type_name) = related.rsplit_once('.').unwrap_or(("", related));
+        let package_ident = format_ident!("{}", package);
+        let param_name = format_ident!(
+            "{}_{}_service_client",
+            package.to_snake_case(),
+            type_name.to_snake_case()
+        );
+        let backend_type = format_ident!("{}ServiceBackend", type_name.to_upper_camel_case());
+        let loader_type = format_ident!("{}Loader", type_name.to_upper_camel_case());
+        remote_params.push(quote! {
+            #param_name: impl Into<super::super::#package_ident::graphql::#backend_type>
+        });
+        remote_bindings.push(quote! {
+            let #param_name: super::super::#package_ident::graphql::#backend_type = #param_name.into();
+        });
+        remote_loader_data.push(quote! {
+            .data(DataLoader::new(
+                super::super::#package_ident::graphql::#loader_type::new(#param_name),
+                tokio::spawn
+            ))
+        });
+    }
+    let remote_doc = (!info.remote_relations.is_empty()).then(|| {
+        quote! {
+            ///
+            /// `{package}_{type}_service_client` parameters load remote relations
+            /// from the services of other packages.
+        }
+    });
+
+    // Budget documents when root fields set `max_concurrency`
+    let concurrency_limit = limited.then(|| quote! { .extension(ConcurrencyLimit::new()) });
+
+    // Report degraded non-critical relations in the response extensions
+    let degraded_warnings = degrades.then(|| quote! { .extension(DegradedWarnings) });
+
+    // Serve `_service` and `_entities` as a federation subgraph
+    let enable_federation = federation.then(|| quote! { .enable_federation() });
+
+    // Stream entity changes from the event source passed to `build_schema`
+    let (subscription_type, subscription_root, events_param, events_data, events_doc) = if subscriptions {
+        (
+            quote! { Subscription },
+            quote! { Subscription::default() },
+            Some(quote! { events: std::sync::Arc<dyn super::outbox::EventSource> }),
+            Some(quote! { .data(events) }),
+            Some(quote! {
+                ///
+                /// `events` feeds the subscriptions, e.g. the `BroadcastEvents` the
+                /// outbox relay publishes to, or a broker's event source.
+            }),
+        )
+    } else {
+        (quote! { EmptySubscription }, quote! { EmptySubscription }, None, None, None)
+    };
+    let params = client_params.iter().chain(remote_params.iter()).chain(events_param.iter());
+
     // Determine package name for schema type alias
     let schema_name = format_ident!("AppSchema");
 
     quote! {
         /// Schema type alias
-        pub type #schema_name = Schema<Query, Mutation, EmptySubscription>;
+        pub type #schema_name = Schema<Query, Mutation, #subscription_type>;
 
-        /// Build the GraphQL schema with gRPC clients
+        /// Build the GraphQL schema with gRPC clients or in-process services
         ///
+        /// Each parameter accepts a tonic client or a `{Service}Backend`; pass
+        /// `{Service}Backend::in_process(grpc_service)` to skip the network hop.
         /// Creates DataLoaders for efficient batched loading in relation resolvers.
-        pub fn build_schema(#(#client_params),*) -> #schema_name {
-            Schema::build(Query::default(), Mutation::default(), EmptySubscription)
+        #remote_doc
+        #events_doc
+        pub fn build_schema(#(#params),*) -> #schema_name {
+            #(#backend_bindings)*
+            #(#remote_bindings)*
+            Schema::build(Query::default(), Mutation::default(), #subscription_root)
                 #(#client_data)*
                 #(#loader_data)*
                 #(#relation_loader_data)*
+                #(#remote_loader_data)*
+                #events_data
+                #concurrency_limit
+                #degraded_warnings
+                #enable_federation
                 .finish()
         }
+
+        /// The schema in SDL, without clients or a server
+        ///
+        /// Resolvers only read their clients and loaders while executing, so
+        /// the schema is built without any to print the API from protos alone.
+        pub fn schema_sdl() -> String {
+            Schema::build(Query::default(), Mutation::default(), #subscription_root)
+                #enable_federation
+                .finish()
+                .sdl()
+        }
+
+        /// Entry point for a schema-printing binary
+        ///
+        /// `fn main() { print_schema() }` prints the SDL to stdout, or writes
+        /// it to the path given as the first argument (for CI diffs).
+        pub fn print_schema() {
+            let sdl = schema_sdl();
+            match std::env::args().nth(1) {
+                Some(path) => {
+                    if let Err(error) = std::fs::write(&path, sdl) {
+                        eprintln!("cannot write {}: {}", path, error);
+                        std::process::exit(1);
+                    }
+                }
+                None => print!("{}", sdl),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_builder_prints_sdl() {
+        let info = SchemaInfo {
+            services: vec!["PostService".to_string()],
+            ..Default::default()
+        };
+        let code = generate_schema_builder(&info, false, false, false, false).to_string();
+        assert!(code.contains("pub fn build_schema (post_service_client : impl Into < PostServiceBackend >)"));
+        assert!(code.contains("pub fn schema_sdl () -> String"));
+        assert!(code.contains("pub fn print_schema ()"));
+        assert!(!code.contains("ConcurrencyLimit"));
+        assert!(!code.contains("enable_federation"));
+
+        let code = generate_schema_builder(&info, false, false, true, false).to_string();
+        assert!(code.contains(". enable_federation () . finish ()"));
+    }
+
+    #[test]
+    fn test_schema_builder_with_subscriptions() {
+        let info = SchemaInfo {
+            services: vec!["PostService".to_string()],
+            ..Default::default()
+        };
+        let code = generate_schema_builder(&info, false, false, false, true).to_string();
+        assert!(code.contains("Schema < Query , Mutation , Subscription >"));
+        assert!(code.contains("events : std :: sync :: Arc < dyn super :: outbox :: EventSource >"));
+        assert!(code.contains(". data (events)"));
+        assert!(!code.contains("EmptySubscription"));
+    }
+
+    #[test]
+    fn test_schema_builder_with_remote_relations() {
+        let info = SchemaInfo {
+            services: vec!["AuthorService".to_string()],
+            remote_relations: vec!["iam.User".to_string()],
+            ..Default::default()
+        };
+        let code = generate_schema_builder(&info, false, false, false, false).to_string();
+        assert!(code.contains(
+            "iam_user_service_client : impl Into < super :: super :: iam :: graphql :: UserServiceBackend >"
+        ));
+        assert!(code.contains("super :: super :: iam :: graphql :: UserLoader :: new (iam_user_service_client)"));
+    }
+
+    #[test]
+    fn test_error_conversions() {
+        let code = generate_error_conversions(&["PostService".to_string()]).to_string();
+        assert!(code.contains("pub fn status_error (status : tonic :: Status) -> async_graphql :: Error"));
+        assert!(code.contains("tonic :: Code :: NotFound => \"NOT_FOUND\""));
+        assert!(code.contains("extensions . set (\"retryAfter\" , seconds)"));
+        assert!(code.contains(
+            "impl From < super :: storage :: post_service_storage :: StorageError > for async_graphql :: Error"
+        ));
+
+        let code = generate_error_conversions(&[]).to_string();
+        assert!(code.contains("pub fn status_error"));
+        assert!(!code.contains("StorageError"));
     }
 }
@@ -0,0 +1,140 @@
+//! Search resolver generation
+//!
+//! Generates graphql/search.rs for packages with `searchable` entities: a
+//! `SearchQuery` object with one `search{Entities}` field per entity. Each
+//! resolver asks the external index (the `SearchIndex` in the schema data)
+//! for matching ids, then hydrates them through the entity's DataLoader so
+//! results batch with the rest of the request.
+
+use crate::error::GeneratorError;
+use crate::storage::seaorm::search::{collect_searchable, SearchEntity};
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::{format_ident, quote};
+
+/// Default and maximum number of search hits returned by a resolver
+const DEFAULT_LIMIT: i32 = 20;
+const MAX_LIMIT: i32 = 100;
+
+/// Searchable entities exposed over GraphQL for this file
+///
+/// Resolvers need the entity loaders, which are registered by the schema
+/// builder of files with services.
+pub fn collect(file: &FileDescriptorProto, all_files: &[FileDescriptorProto]) -> Vec<SearchEntity> {
+    if file.service.is_empty() {
+        return Vec::new();
+    }
+    collect_searchable(all_files, file)
+        .into_iter()
+        .filter(|e| e.graphql_type.is_some())
+        .collect()
+}
+
+/// Generate the graphql/search.rs file for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    let entities = collect(file, all_files);
+    if package.is_empty() || entities.is_empty() {
+        return Ok(None);
+    }
+
+    let resolvers = entities.iter().map(generate_resolver);
+
+    let code = quote! {
+        //! Full-text search resolvers
+        //!
+        //! Merge `SearchQuery` into the schema's query root and add an
+        //! `Arc<dyn SearchIndex>` to the schema data.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use async_graphql::dataloader::DataLoader;
+        use async_graphql::{Context, Object, Result};
+        use std::sync::Arc;
+        use super::super::search::{self, SearchIndex};
+
+        /// Search queries backed by the external index
+        #[derive(Default)]
+        pub struct SearchQuery;
+
+        #[Object]
+        impl SearchQuery {
+            #(#resolvers)*
+        }
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/graphql/search.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the `search{Entities}` resolver for an entity
+fn generate_resolver(entity: &SearchEntity) -> TokenStream {
+    let type_name = entity.graphql_type.as_deref().unwrap_or_default();
+    let type_ident = format_ident!("{}", type_name);
+    let loader_ident = format_ident!("{}Loader", type_name);
+    let method = format_ident!("search_{}s", type_name.to_snake_case());
+    let index_const = format_ident!("{}_INDEX", entity.module.to_uppercase());
+    let fields_const = format_ident!("{}_FIELDS", entity.module.to_uppercase());
+    let key_ty = super::key_type(Some(&entity.message), "id");
+    let doc = format!("Search {} entities, most relevant first", type_name);
+
+    quote! {
+        #[doc = #doc]
+        async fn #method(
+            &self,
+            ctx: &Context<'_>,
+            query: String,
+            first: Option<i32>,
+        ) -> Result<Vec<super::#type_ident>> {
+            let limit = first.unwrap_or(#DEFAULT_LIMIT).clamp(1, #MAX_LIMIT) as usize;
+            let index = ctx.data::<Arc<dyn SearchIndex>>()?;
+            let ids = index.search(search::#index_const, &query, search::#fields_const, limit).await?;
+
+            // Hydrate through the loader, keeping the index's relevance order
+            let keys: Vec<#key_ty> = ids.iter().filter_map(|id| id.parse().ok()).collect();
+            let loader = ctx.data::<DataLoader<super::#loader_ident>>()?;
+            let mut found = loader.load_many(keys.iter().cloned()).await?;
+            Ok(keys.iter().filter_map(|key| found.remove(key)).collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::DescriptorProto;
+
+    #[test]
+    fn test_generate_resolver() {
+        let entity = SearchEntity {
+            message: DescriptorProto::default(),
+            module: "post".to_string(),
+            table: "posts".to_string(),
+            graphql_type: Some("Post".to_string()),
+            fields: vec![("title".to_string(), 1)],
+            soft_delete: false,
+        };
+        let code = generate_resolver(&entity).to_string();
+        assert!(code.contains("async fn search_posts"));
+        assert!(code.contains("search :: POST_INDEX"));
+        assert!(code.contains("DataLoader < super :: PostLoader >"));
+        assert!(code.contains("Vec < i64 >"));
+        assert!(code.contains("found . remove (key)"));
+        assert!(!code.contains("map_err"));
+    }
+}
@@ -0,0 +1,396 @@
+//! GraphQL subscriptions to entity change events
+//!
+//! Generates graphql/subscription.rs when `subscriptions=true`: the schema's
+//! `Subscription` root with a `{entity}Changed` field per `emit_events`
+//! entity. Fields stream the entity's published outbox events from the
+//! `EventSource` in the schema data as `{Entity}Event { mutation, node }`.
+//! Event payloads are redacted JSON, so created and updated entities are
+//! fetched by the event's id through the entity's DataLoader fetcher, and
+//! checked against the subscriber's filter: on the fetched entity when the
+//! filter only compares its fields (see `event_filter`), otherwise in the
+//! fetch itself. With request contexts, fetches carry the subscriber's, as
+//! queries do, so the service applies the same read checks. Deleted ones no
+//! longer match any filter and reach every subscriber.
+
+use super::event_filter::{self, PayloadFilter};
+use super::schema::collect_schema_info;
+use crate::error::GeneratorError;
+use crate::storage::seaorm::external_id::package_hides_ids;
+use crate::storage::seaorm::options::get_cached_graphql_type_options;
+use crate::storage::seaorm::outbox::collect_event_entities;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::{DescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+
+/// An entity with a subscription field
+pub struct SubscribedEntity {
+    /// Proto message name, also the outbox aggregate type
+    pub message_name: String,
+    /// GraphQL type name
+    pub type_name: String,
+    /// The entity message
+    pub message: DescriptorProto,
+    /// Whether the proto filter can be combined with an `id` match
+    pub filterable: bool,
+    /// Checks of a filterable entity's filter on event payloads, if it needs no storage
+    pub payload: Option<PayloadFilter>,
+    /// Whether event ids are external ids, decoded with `ids::internal_id`
+    pub hides_ids: bool,
+}
+
+/// `emit_events` entities of a file's package exposed through its services
+///
+/// Fields resolve through the `{Type}ServiceBackend`, so the entity's service
+/// must be in the file.
+pub fn collect(file: &FileDescriptorProto, all_files: &[FileDescriptorProto]) -> Vec<SubscribedEntity> {
+    if !crate::params::subscriptions() || file.service.is_empty() {
+        return Vec::new();
+    }
+    let package = file.package.as_deref().unwrap_or("");
+    let services = collect_schema_info(file, all_files).services;
+    let hides_ids = package_hides_ids(all_files, file);
+    collect_event_entities(all_files, file)
+        .into_iter()
+        .filter_map(|name| {
+            let (file_name, message) = all_files
+                .iter()
+                .filter(|f| f.package.as_deref().unwrap_or("") == package)
+                .find_map(|f| {
+                    let message = f.message_type.iter().find(|m| m.name.as_deref() == Some(name.as_str()))?;
+                    Some((f.name.as_deref().unwrap_or(""), message))
+                })?;
+            let type_options = get_cached_graphql_type_options(file_name, &name);
+            if type_options.as_ref().is_some_and(|o| o.skip || o.input) {
+                return None;
+            }
+            let type_name = type_options
+                .filter(|o| !o.name.is_empty())
+                .map(|o| o.name)
+                .unwrap_or_else(|| name.to_upper_camel_case());
+            if !services.contains(&format!("{}Service", type_name)) {
+                return None;
+            }
+            let filterable = is_filterable(all_files, &type_name);
+            let payload = filterable
+                .then(|| event_filter::payload_filter(all_files, message, &type_name))
+                .flatten();
+            Some(SubscribedEntity {
+                message_name: name,
+                type_name,
+                message: message.clone(),
+                filterable,
+                payload,
+                hides_ids,
+            })
+        })
+        .collect()
+}
+
+/// Whether the proto `{Type}Filter` has the `id` and `and` fields a match needs
+fn is_filterable(all_files: &[FileDescriptorProto], type_name: &str) -> bool {
+    let filter_name = format!("{}Filter", type_name);
+    all_files
+        .iter()
+        .flat_map(|f| f.message_type.iter())
+        .find(|m| m.name.as_deref() == Some(filter_name.as_str()))
+        .is_some_and(|filter| {
+            ["id", "and"]
+                .iter()
+                .all(|field| filter.field.iter().any(|f| f.name.as_deref() == Some(*field)))
+        })
+}
+
+/// Generate the graphql/subscription.rs file for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    let entities = collect(file, all_files);
+    if package.is_empty() || entities.is_empty() {
+        return Ok(None);
+    }
+
+    let request_context = crate::grpc::package_has_request_context(all_files, file);
+    let code = generate_module(&entities, request_context);
+    let output_path = format!("{}/graphql/subscription.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    }))
+}
+
+/// Generate the `MutationType` enum, event objects, and `Subscription` root
+fn generate_module(entities: &[SubscribedEntity], request_context: bool) -> TokenStream {
+    let events = entities.iter().map(|entity| {
+        let event_ident = format_ident!("{}Event", entity.type_name);
+        let type_ident = format_ident!("{}", entity.type_name);
+        let doc = format!("A change to a {}", entity.type_name);
+        let id_doc = format!("Id of the changed {}, as returned by the API", entity.type_name);
+        let node_doc = format!("The {} after the change; null for deletes", entity.type_name);
+        quote! {
+            #[doc = #doc]
+            #[derive(SimpleObject)]
+            pub struct #event_ident {
+                /// Kind of change
+                pub mutation: MutationType,
+                #[doc = #id_doc]
+                pub id: ID,
+                #[doc = #node_doc]
+                pub node: Option<super::#type_ident>,
+            }
+        }
+    });
+    let fields = entities.iter().map(|entity| generate_field(entity, request_context));
+    let helpers = event_filter::generate_helpers(entities.iter().filter_map(|e| e.payload.as_ref()));
+    let matchers = entities.iter().filter_map(|entity| {
+        let proto_ident = format_ident!("{}", entity.message_name.to_upper_camel_case());
+        let payload = entity.payload.as_ref()?;
+        Some(event_filter::generate_matcher(&entity.type_name, &proto_ident, payload))
+    });
+
+    quote! {
+        //! GraphQL subscriptions to entity changes
+        //!
+        //! Fields stream published outbox events from the `EventSource` that
+        //! `build_schema` puts in the schema data. Created and updated
+        //! entities are fetched through the service by the event's id, and a
+        //! subscriber's filter is checked on them or in the fetch when it
+        //! needs storage; deletes reach every subscriber.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use async_graphql::futures_util::{Stream, StreamExt};
+        use async_graphql::{Context, Enum, Result, SimpleObject, ID};
+        use std::sync::Arc;
+        use super::super::outbox::{event_topic, EventEnvelope, EventSource};
+
+        /// Kind of change an entity event reports
+        #[derive(Enum, Copy, Clone, Debug, PartialEq, Eq)]
+        pub enum MutationType {
+            Created,
+            Updated,
+            Deleted,
+        }
+
+        impl MutationType {
+            /// Change kind of an envelope, from its `{Entity}{Kind}` event type
+            fn of(envelope: &EventEnvelope) -> Option<Self> {
+                match envelope.event_type.strip_prefix(envelope.aggregate_type.as_str())? {
+                    "Created" => Some(Self::Created),
+                    "Updated" => Some(Self::Updated),
+                    "Deleted" => Some(Self::Deleted),
+                    _ => None,
+                }
+            }
+        }
+
+        #(#events)*
+
+        #helpers
+
+        #(#matchers)*
+
+        /// Subscription root streaming entity changes
+        #[derive(Default)]
+        pub struct Subscription;
+
+        #[async_graphql::Subscription]
+        impl Subscription {
+            #(#fields)*
+        }
+    }
+}
+
+/// Generate the `{entity}_changed` field of an entity
+///
+/// With `request_context`, each event is handled in the context the
+/// websocket connection was opened with.
+fn generate_field(entity: &SubscribedEntity, request_context: bool) -> TokenStream {
+    let aggregate = &entity.message_name;
+    let type_ident = format_ident!("{}", entity.type_name);
+    let event_ident = format_ident!("{}Event", entity.type_name);
+    let backend_ident = format_ident!("{}ServiceBackend", entity.type_name);
+    let fetcher_ident = format_ident!("{}Fetcher", entity.type_name);
+    let filter_ident = format_ident!("{}Filter", entity.type_name);
+    let method = format_ident!("{}_changed", entity.type_name.to_snake_case());
+
+    let key_ty = super::key_type(Some(&entity.message), "id");
+    let key = if entity.hides_ids {
+        quote! { super::super::ids::internal_id(#aggregate, &envelope.aggregate_id)?.parse::<#key_ty>().ok()? }
+    } else {
+        quote! { envelope.aggregate_id.parse::<#key_ty>().ok()? }
+    };
+    let fetch_by_id = quote! {
+        super::#fetcher_ident::fetch_by_ids(&backend, &[key]).await.ok()?.into_iter().next()?
+    };
+
+    let (doc, filter_param, fetch) = if entity.filterable {
+        let fetch = if entity.payload.is_some() {
+            let matcher = event_filter::matcher_ident(&entity.type_name);
+            quote! {
+                let node = #fetch_by_id;
+                if let Some(filter) = &filter {
+                    if !#matcher(filter, &node) {
+                        return None;
+                    }
+                }
+            }
+        } else {
+            // Storage ignores `and`, `or` and `not`, so the event's id narrows
+            // the subscriber's own `id` condition
+            quote! {
+                let node = match filter {
+                    Some(filter) => {
+                        let mut matching: super::super::#filter_ident = filter.into();
+                        let mut id = matching.id.take().unwrap_or_default();
+                        if id.eq.as_ref().is_some_and(|eq| *eq != key) {
+                            return None;
+                        }
+                        id.eq = Some(key);
+                        matching.id = Some(id);
+                        super::#fetcher_ident::fetch(&backend, matching, 1).await.ok()?.into_iter().next()?
+                    }
+                    None => #fetch_by_id,
+                };
+            }
+        };
+        (
+            format!("Changes to {} entities, limited to those matching `filter`", entity.type_name),
+            quote! { filter: Option<super::#filter_ident>, },
+            fetch,
+        )
+    } else {
+        (
+            format!("Changes to {} entities", entity.type_name),
+            quote! {},
+            quote! { let node = #fetch_by_id; },
+        )
+    };
+    let filter_clone = entity.filterable.then(|| quote! { let filter = filter.clone(); });
+    let handle = quote! {
+        async move {
+            let mutation = MutationType::of(&envelope)?;
+            if mutation == MutationType::Deleted {
+                let id = ID(envelope.aggregate_id);
+                return Some(#event_ident { mutation, id, node: None });
+            }
+            let key = #key;
+            #fetch
+            let id = ID(envelope.aggregate_id);
+            Some(#event_ident { mutation, id, node: Some(super::#type_ident::from(node)) })
+        }
+    };
+    let (context, context_clone, handle) = if request_context {
+        (
+            quote! {
+                let context = ctx
+                    .data_opt::<super::super::prelude::RequestContext>()
+                    .cloned()
+                    .unwrap_or_default();
+            },
+            quote! { let context = context.clone(); },
+            quote! { super::super::prelude::with_request_context(context, #handle) },
+        )
+    } else {
+        (quote! {}, quote! {}, handle)
+    };
+
+    quote! {
+        #[doc = #doc]
+        async fn #method(
+            &self,
+            ctx: &Context<'_>,
+            #filter_param
+        ) -> Result<impl Stream<Item = #event_ident>> {
+            let events = ctx.data::<Arc<dyn EventSource>>()?.subscribe(&event_topic(#aggregate));
+            let backend = ctx.data::<super::#backend_ident>()?.for_request(ctx);
+            #context
+            Ok(events.filter_map(move |envelope| {
+                let backend = backend.clone();
+                #filter_clone
+                #context_clone
+                #handle
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_module() {
+        let entity = SubscribedEntity {
+            message_name: "Post".to_string(),
+            type_name: "Post".to_string(),
+            message: DescriptorProto::default(),
+            filterable: true,
+            payload: None,
+            hides_ids: false,
+        };
+        let code = generate_module(&[entity], false).to_string();
+        assert!(code.contains("pub enum MutationType"));
+        assert!(code.contains("pub struct PostEvent"));
+        assert!(code.contains("async fn post_changed"));
+        assert!(code.contains("filter : Option < super :: PostFilter >"));
+        assert!(code.contains("event_topic (\"Post\")"));
+        assert!(code.contains("super :: PostFetcher :: fetch (& backend , matching , 1)"));
+        assert!(code.contains("id . eq = Some (key)"));
+        assert!(code.contains("let key = envelope . aggregate_id . parse :: < i64 > () . ok () ?"));
+        assert!(code.contains("None => super :: PostFetcher :: fetch_by_ids (& backend , & [key])"));
+        assert!(!code.contains("prost :: Message"));
+        assert!(!code.contains("fn post_matches"));
+        assert!(!code.contains("with_request_context"));
+    }
+
+    #[test]
+    fn test_generate_field_keeps_request_context() {
+        let entity = SubscribedEntity {
+            message_name: "Post".to_string(),
+            type_name: "Post".to_string(),
+            message: DescriptorProto::default(),
+            filterable: false,
+            payload: None,
+            hides_ids: false,
+        };
+        let code = generate_field(&entity, true).to_string();
+        assert!(code.contains("data_opt :: < super :: super :: prelude :: RequestContext > ()"));
+        assert!(code.contains("super :: super :: prelude :: with_request_context (context , async move"));
+        assert!(code.contains("let node = super :: PostFetcher :: fetch_by_ids (& backend , & [key])"));
+    }
+
+    #[test]
+    fn test_generate_module_with_payload_filter() {
+        let entity = SubscribedEntity {
+            message_name: "Post".to_string(),
+            type_name: "Post".to_string(),
+            message: DescriptorProto::default(),
+            filterable: true,
+            payload: Some(PayloadFilter {
+                fields: vec![event_filter::PayloadField {
+                    field: format_ident!("published"),
+                    check: event_filter::Check::Bool,
+                    value: quote! { Some(node.published) },
+                }],
+                or: true,
+                not: true,
+            }),
+            hides_ids: true,
+        };
+        let code = generate_module(&[entity], false).to_string();
+        assert!(code.contains("fn bool_matches (f : & BoolFilter , value : Option < bool >) -> bool"));
+        assert!(code.contains("fn post_matches"));
+        assert!(code.contains("if ! post_matches (filter , & node)"));
+        assert!(code.contains("super :: super :: ids :: internal_id (\"Post\" , & envelope . aggregate_id) ?"));
+        assert!(code.contains("let node = super :: PostFetcher :: fetch_by_ids (& backend , & [key])"));
+        assert!(!code.contains("PostFetcher :: fetch (& backend"));
+    }
+}
@@ -51,7 +51,8 @@ pub fn generate_service_error() -> TokenStream {
 
 /// Generate the From<ServiceError> for tonic::Status implementation
 ///
-/// This maps service errors to appropriate gRPC status codes.
+/// Storage errors convert through `From<StorageError>` (see
+/// `generate_storage_status_conversion`).
 pub fn generate_status_conversion() -> TokenStream {
     quote! {
         impl From<ServiceError> for tonic::Status {
@@ -60,15 +61,33 @@ pub fn generate_status_conversion() -> TokenStream {
                     ServiceError::Validation(v) => {
                         tonic::Status::invalid_argument(v.to_string())
                     }
-                    ServiceError::Storage(s) => match s {
-                        StorageError::NotFound(msg) => tonic::Status::not_found(msg),
-                        StorageError::Database(db_err) => {
-                            tonic::Status::internal(db_err.to_string())
-                        }
-                        StorageError::InvalidArgument(msg) => {
-                            tonic::Status::invalid_argument(msg)
+                    ServiceError::Storage(s) => s.into(),
+                }
+            }
+        }
+    }
+}
+
+/// Generate the From<StorageError> for tonic::Status implementation
+///
+/// Emitted next to each storage trait's `StorageError`. This maps storage
+/// errors to appropriate gRPC status codes.
+pub fn generate_storage_status_conversion() -> TokenStream {
+    quote! {
+        impl From<StorageError> for tonic::Status {
+            fn from(e: StorageError) -> Self {
+                match e {
+                    StorageError::NotFound(msg) => tonic::Status::not_found(msg),
+                    StorageError::Database(db_err) => tonic::Status::internal(db_err.to_string()),
+                    StorageError::InvalidArgument(msg) => tonic::Status::invalid_argument(msg),
+                    StorageError::AlreadyExists { field, message } => {
+                        // Clients read the conflicting field from metadata
+                        let mut status = tonic::Status::already_exists(message);
+                        if let Ok(value) = field.parse() {
+                            status.metadata_mut().insert("x-conflict-field", value);
                         }
-                    },
+                        status
+                    }
                 }
             }
         }
@@ -117,6 +136,17 @@ mod tests {
         // quote! generates "tonic :: Status" with spaces
         assert!(code.contains("tonic") && code.contains("Status"));
         assert!(code.contains("invalid_argument"));
+        assert!(code.contains("ServiceError :: Storage (s) => s . into ()"));
+    }
+
+    #[test]
+    fn test_generate_storage_status_conversion() {
+        let tokens = generate_storage_status_conversion();
+        let code = tokens.to_string();
+        assert!(code.contains("impl From < StorageError > for tonic :: Status"));
+        assert!(code.contains("invalid_argument"));
         assert!(code.contains("not_found"));
+        assert!(code.contains("already_exists"));
+        assert!(code.contains("x-conflict-field"));
     }
 }
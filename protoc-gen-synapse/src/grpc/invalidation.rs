@@ -0,0 +1,161 @@
+//! Cache invalidation hooks in gRPC services
+//!
+//! Services with `invalidation_hooks: true` hold an optional
+//! `InvalidationHook`, set with `with_invalidation_hook`. After a create,
+//! update, upsert, restore or delete RPC succeeds, or a bulk update or delete
+//! whose response lists `ids`, they call it with the entity, key and
+//! `ChangeKind` of each written row. Custom writes report rows the same way
+//! when their response or request holds keys; bulk and custom writes without
+//! keys, and client-streaming imports, call `invalidate_all` for the whole
+//! entity. Dry runs call nothing.
+//! The trait lives in the package prelude, shared by all its services.
+
+use super::writes::{self, WriteHook};
+use crate::storage::seaorm::options::{get_cached_grpc_method_options, get_cached_grpc_service_options};
+use heck::ToUpperCamelCase;
+use proc_macro2::TokenStream;
+use prost_types::{FileDescriptorProto, MethodDescriptorProto, ServiceDescriptorProto};
+use quote::{format_ident, quote};
+
+/// Whether a gRPC service has `invalidation_hooks: true`
+fn enabled(file_name: &str, service_name: &str) -> bool {
+    get_cached_grpc_service_options(file_name, service_name).is_some_and(|o| o.invalidation_hooks && !o.skip)
+}
+
+/// Whether any RPC of a gRPC service calls an invalidation hook
+pub(crate) fn has_invalidation_hooks(file: &FileDescriptorProto, service: &ServiceDescriptorProto) -> bool {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let service_name = service.name.as_deref().unwrap_or("");
+    if !enabled(file_name, service_name) {
+        return false;
+    }
+    service.method.iter().any(|method| {
+        let method_name = method.name.as_deref().unwrap_or("");
+        let skipped = get_cached_grpc_method_options(file_name, service_name, method_name).is_some_and(|o| o.skip);
+        !skipped && !method.server_streaming() && writes::written_rows(file, service_name, method).is_some()
+    })
+}
+
+/// Whether any gRPC service of the main file's package calls invalidation hooks
+pub(crate) fn package_has_invalidation_hooks(
+    all_files: &[FileDescriptorProto],
+    main_file: &FileDescriptorProto,
+) -> bool {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|f| f.service.iter().any(|s| has_invalidation_hooks(f, s)))
+}
+
+/// The invalidation hook call of an RPC, if its service has invalidation hooks
+pub(crate) fn write_hook(
+    file: &FileDescriptorProto,
+    service_name: &str,
+    method: &MethodDescriptorProto,
+) -> Option<WriteHook> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    if !enabled(file_name, service_name) {
+        return None;
+    }
+    let rows = writes::written_rows(file, service_name, method)?;
+    let entity = &rows.entity;
+    let change = format_ident!("{}", rows.kind.to_upper_camel_case());
+    let notify_all = quote! { invalidation.invalidate_all(#entity, ChangeKind::#change).await; };
+    Some(WriteHook {
+        field: "invalidation",
+        notify: Box::new(move |key| {
+            let entity = &rows.entity;
+            let change = format_ident!("{}", rows.kind.to_upper_camel_case());
+            quote! { invalidation.invalidate(#entity, &#key, ChangeKind::#change).await; }
+        }),
+        notify_all,
+    })
+}
+
+/// Service field, constructor initializer, and setter of the invalidation hook
+pub(crate) fn generate_hook_field(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> (TokenStream, TokenStream, TokenStream) {
+    if !has_invalidation_hooks(file, service) {
+        return (quote! {}, quote! {}, quote! {});
+    }
+    (
+        quote! { invalidation: Option<std::sync::Arc<dyn InvalidationHook>>, },
+        quote! { invalidation: None, },
+        quote! {
+            /// Call `hook` for every row a successful write changes
+            pub fn with_invalidation_hook(mut self, hook: std::sync::Arc<dyn InvalidationHook>) -> Self {
+                self.invalidation = Some(hook);
+                self
+            }
+        },
+    )
+}
+
+/// Generate the prelude's `InvalidationHook` trait and `ChangeKind`
+pub(crate) fn generate_prelude() -> TokenStream {
+    quote! {
+        /// How a write changed a row
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ChangeKind {
+            Created,
+            Updated,
+            Deleted,
+        }
+
+        /// Told about each row a gRPC write changed, once the write succeeded
+        ///
+        /// Hooks run before the response is sent, so keep them quick or hand
+        /// the work off. The write has committed, so a hook handles its own
+        /// errors.
+        #[async_trait::async_trait]
+        pub trait InvalidationHook: Send + Sync {
+            /// Drop cached copies of the `entity` row with key `id`
+            async fn invalidate(&self, entity: &str, id: &str, change: ChangeKind);
+
+            /// Drop cached copies of every `entity` row, after a write whose keys are unknown
+            async fn invalidate_all(&self, entity: &str, change: ChangeKind);
+        }
+
+        /// Calls every hook in order, e.g. a loader cache, then Redis, then a CDN purge
+        #[async_trait::async_trait]
+        impl InvalidationHook for Vec<std::sync::Arc<dyn InvalidationHook>> {
+            async fn invalidate(&self, entity: &str, id: &str, change: ChangeKind) {
+                for hook in self {
+                    hook.invalidate(entity, id, change).await;
+                }
+            }
+
+            async fn invalidate_all(&self, entity: &str, change: ChangeKind) {
+                for hook in self {
+                    hook.invalidate_all(entity, change).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_prelude() {
+        let code = generate_prelude().to_string();
+        assert!(code.contains("pub trait InvalidationHook"));
+        assert!(code.contains("pub enum ChangeKind"));
+        assert!(code.contains("impl InvalidationHook for Vec <"));
+    }
+
+    #[test]
+    fn test_write_hook_without_option() {
+        let file = FileDescriptorProto::default();
+        let method = MethodDescriptorProto {
+            name: Some("CreatePost".to_string()),
+            ..Default::default()
+        };
+        assert!(write_hook(&file, "PostService", &method).is_none());
+    }
+}
@@ -5,6 +5,55 @@
 //! delegate to storage traits.
 
 mod errors;
+mod invalidation;
+mod rate_limit;
+mod server;
 mod service;
+mod smoke;
+mod tls;
+mod writes;
 
+use crate::storage::seaorm::implementation::context_columns;
+use crate::storage::seaorm::options::get_cached_grpc_service_options;
+use prost_types::FileDescriptorProto;
+
+pub(crate) use errors::generate_storage_status_conversion;
+pub(crate) use invalidation::{generate_prelude as generate_invalidation_prelude, package_has_invalidation_hooks};
+pub(crate) use rate_limit::{generate_prelude as generate_rate_limit_prelude, package_has_rate_limits};
+pub use server::generate as generate_server;
 pub use service::generate;
+pub use smoke::generate as generate_smoke;
+pub use tls::generate as generate_tls;
+
+/// Whether a gRPC service has `request_context: true`
+pub(crate) fn has_request_context(file_name: &str, service_name: &str) -> bool {
+    get_cached_grpc_service_options(file_name, service_name).is_some_and(|o| o.request_context && !o.skip)
+}
+
+/// Whether any gRPC service of the main file's package captures a request context
+pub(crate) fn package_has_request_context(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> bool {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.service.iter().any(|s| has_request_context(file_name, s.name.as_deref().unwrap_or("")))
+        })
+}
+
+/// Whether the services of the main file's package read `CurrentUser` from the request
+///
+/// The request context records its actor, rate limits key on the actor,
+/// `from_context` columns are stamped from it and custom validators see it.
+pub(crate) fn package_reads_current_user(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> bool {
+    let package = main_file.package.as_deref().unwrap_or("");
+    package_has_request_context(all_files, main_file)
+        || package_has_rate_limits(all_files, main_file)
+        || crate::validate::package_has_custom_validators(all_files, main_file)
+        || all_files
+            .iter()
+            .filter(|f| f.package.as_deref().unwrap_or("") == package)
+            .flat_map(|f| &f.message_type)
+            .any(|m| context_columns(all_files, package, m.name.as_deref().unwrap_or("")).is_ok_and(|c| !c.is_empty()))
+}
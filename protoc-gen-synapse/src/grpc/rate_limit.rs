@@ -0,0 +1,373 @@
+//! Mutation rate limit generation
+//!
+//! Entities with `rate_limits` get per-actor quotas on their mutation RPCs.
+//! The gRPC service counts each call in a `RateLimitStore` (in-memory by
+//! default) once the request has passed validation, just before calling
+//! storage, and rejects calls over a limit with RESOURCE_EXHAUSTED and a
+//! `retry-after` header. Dry runs are never counted. Client-streaming
+//! imports count once per call against the create quota, before the first
+//! item is read.
+
+use crate::error::GeneratorError;
+use crate::options::synapse::storage;
+use crate::storage::defaults::{infer_entity_name, infer_operation};
+use crate::storage::seaorm::options::{
+    get_cached_entity_options_in_package, get_cached_grpc_method_options, get_cached_grpc_service_options,
+    get_cached_rpc_method_options,
+};
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::{FileDescriptorProto, ServiceDescriptorProto};
+use quote::{format_ident, quote};
+
+/// Operations a `RateLimit` can name
+const LIMITED_OPERATIONS: &[&str] = &["create", "update", "upsert", "delete", "restore"];
+
+/// Quotas on a mutation RPC
+struct MethodLimits {
+    /// A batch create, costing one call per item
+    per_item: bool,
+    /// Counter key prefix, limit, and window seconds of each quota
+    limits: Vec<(String, u64, u64)>,
+}
+
+/// Quotas that apply to an RPC, if it is a rate-limited mutation
+fn method_limits(
+    file: &FileDescriptorProto,
+    service_name: &str,
+    method_name: &str,
+) -> Result<Option<MethodLimits>, GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let package = file.package.as_deref().unwrap_or("");
+    let method_options = get_cached_rpc_method_options(file_name, service_name, method_name);
+    let operation = method_options
+        .as_ref()
+        .filter(|o| !o.operation.is_empty())
+        .map(|o| o.operation.as_str())
+        .unwrap_or_else(|| infer_operation(method_name));
+    // Batch creates share the create quota; a bulk update or delete counts
+    // once against the update or delete quota, and an import once against
+    // the create quota
+    let quota_operation = match operation {
+        "create_many" | "import" => "create",
+        "update_many" => "update",
+        "delete_many" => "delete",
+        op if LIMITED_OPERATIONS.contains(&op) => op,
+        _ => return Ok(None),
+    };
+
+    let entity = method_options
+        .as_ref()
+        .filter(|o| !o.entity_name.is_empty())
+        .map(|o| o.entity_name.clone())
+        .unwrap_or_else(|| infer_entity_name(method_name))
+        .to_upper_camel_case();
+    let Some(entity_options) = get_cached_entity_options_in_package(package, &entity) else {
+        return Ok(None);
+    };
+
+    let mut limits = Vec::new();
+    for rule in &entity_options.rate_limits {
+        validate(&entity, rule)?;
+        if !rule.operation.is_empty() && rule.operation != quota_operation {
+            continue;
+        }
+        let scope = if rule.operation.is_empty() { "*" } else { rule.operation.as_str() };
+        let prefix = format!("{}.{}/{}:{}", package, entity, scope, rule.window_seconds);
+        limits.push((prefix, rule.limit, rule.window_seconds));
+    }
+
+    if limits.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(MethodLimits {
+        per_item: operation == "create_many",
+        limits,
+    }))
+}
+
+/// Reject quotas that could never be met or name an unknown operation
+fn validate(entity: &str, rule: &storage::RateLimit) -> Result<(), GeneratorError> {
+    if !rule.operation.is_empty() && !LIMITED_OPERATIONS.contains(&rule.operation.as_str()) {
+        return Err(GeneratorError::CodeGenError(format!(
+            "{}: rate limit operation must be one of {}, got {:?}",
+            entity,
+            LIMITED_OPERATIONS.join(", "),
+            rule.operation
+        )));
+    }
+    if rule.limit == 0 || rule.window_seconds == 0 {
+        return Err(GeneratorError::CodeGenError(format!(
+            "{}: rate limit needs a positive limit and window_seconds",
+            entity
+        )));
+    }
+    Ok(())
+}
+
+/// Whether any RPC of a gRPC service is rate limited
+pub(crate) fn has_rate_limits(file: &FileDescriptorProto, service: &ServiceDescriptorProto) -> bool {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let service_name = service.name.as_deref().unwrap_or("");
+    if get_cached_grpc_service_options(file_name, service_name).is_none_or(|o| o.skip) {
+        return false;
+    }
+    service.method.iter().any(|method| {
+        let method_name = method.name.as_deref().unwrap_or("");
+        let skipped = get_cached_grpc_method_options(file_name, service_name, method_name).is_some_and(|o| o.skip);
+        !skipped && matches!(method_limits(file, service_name, method_name), Ok(Some(_)))
+    })
+}
+
+/// Whether any gRPC service of the main file's package is rate limited
+pub(crate) fn package_has_rate_limits(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> bool {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|f| f.service.iter().any(|s| has_rate_limits(f, s)))
+}
+
+/// Generate the rate limit check of an RPC, or nothing when it has no quota
+///
+/// Returns the capture of the actor and cost, which reads the request and so
+/// runs first, and the charge, which runs once validation has passed. The
+/// actor is the service's `context_actor` path (`current_user.id` by
+/// default), falling back to the client IP for calls without a principal.
+pub(crate) fn generate_check(
+    file: &FileDescriptorProto,
+    service_name: &str,
+    method_name: &str,
+) -> Result<(TokenStream, TokenStream), GeneratorError> {
+    let Some(method) = method_limits(file, service_name, method_name)? else {
+        return Ok((quote! {}, quote! {}));
+    };
+
+    let file_name = file.name.as_deref().unwrap_or("");
+    let actor_path: Vec<_> = get_cached_grpc_service_options(file_name, service_name)
+        .and_then(|o| o.context_actor.strip_prefix("current_user.").map(str::to_string))
+        .unwrap_or_else(|| "id".to_string())
+        .split('.')
+        .map(|p| format_ident!("{}", p.to_snake_case()))
+        .collect();
+
+    let cost = if method.per_item {
+        quote! { request.get_ref().items.len() as u64 }
+    } else {
+        quote! { 1 }
+    };
+    let checks = method.limits.iter().map(|(prefix, limit, window)| {
+        quote! {
+            check_rate_limit(
+                self.rate_limits.as_ref(),
+                &format!("{}:{}", #prefix, actor),
+                rate_cost,
+                #limit,
+                std::time::Duration::from_secs(#window),
+            )
+            .await?;
+        }
+    });
+
+    let capture = quote! {
+        // Per-actor mutation quotas, charged once the request is valid
+        let rate_actor = request
+            .extensions()
+            .get::<crate::CurrentUser>()
+            .map(|user| user #(.#actor_path)*.to_string())
+            .or_else(|| client_ip(&request, &self.trusted_proxies).map(|ip| format!("ip:{}", ip)));
+        let rate_cost = #cost;
+    };
+    let charge = quote! {
+        if let Some(actor) = &rate_actor {
+            #(#checks)*
+        }
+    };
+    Ok((capture, charge))
+}
+
+/// Generate the prelude's `RateLimitStore` trait, in-memory store, and check
+pub(crate) fn generate_prelude() -> TokenStream {
+    quote! {
+        /// Calls counted in an actor's current rate limit window
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct RateLimitUsage {
+            /// Cost counted in the window so far, this call included
+            pub count: u64,
+            /// Time until the window resets
+            pub reset_after: std::time::Duration,
+        }
+
+        /// Counter store behind the generated mutation rate limits
+        ///
+        /// Counts are per fixed window. A store error lets the call through,
+        /// so an outage of a shared store does not block writes.
+        #[async_trait::async_trait]
+        pub trait RateLimitStore: Send + Sync {
+            /// Add `cost` to `key`'s window, starting a `window`-long one if it has expired
+            async fn increment(
+                &self,
+                key: &str,
+                cost: u64,
+                window: std::time::Duration,
+            ) -> Result<RateLimitUsage, Box<dyn std::error::Error + Send + Sync>>;
+        }
+
+        /// Process-local `RateLimitStore`; each replica counts on its own
+        #[derive(Debug, Default)]
+        pub struct InMemoryRateLimitStore {
+            windows: std::sync::Mutex<std::collections::HashMap<String, (std::time::Instant, u64)>>,
+        }
+
+        #[async_trait::async_trait]
+        impl RateLimitStore for InMemoryRateLimitStore {
+            async fn increment(
+                &self,
+                key: &str,
+                cost: u64,
+                window: std::time::Duration,
+            ) -> Result<RateLimitUsage, Box<dyn std::error::Error + Send + Sync>> {
+                let now = std::time::Instant::now();
+                let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+                if windows.len() > 10_000 {
+                    windows.retain(|_, (resets_at, _)| *resets_at > now);
+                }
+                let entry = windows.entry(key.to_string()).or_insert((now + window, 0));
+                if entry.0 <= now {
+                    *entry = (now + window, 0);
+                }
+                entry.1 += cost;
+                Ok(RateLimitUsage {
+                    count: entry.1,
+                    reset_after: entry.0 - now,
+                })
+            }
+        }
+
+        /// The client's address, for quotas of calls without a principal
+        ///
+        /// A call from one of `trusted_proxies` is attributed to the nearest
+        /// untrusted `x-forwarded-for` hop; any other peer is the client. Hops
+        /// are read right to left, so a client can't prepend its way past a
+        /// trusted proxy.
+        pub fn client_ip<T>(
+            request: &tonic::Request<T>,
+            trusted_proxies: &[std::net::IpAddr],
+        ) -> Option<std::net::IpAddr> {
+            let peer = request.remote_addr()?.ip();
+            if !trusted_proxies.contains(&peer) {
+                return Some(peer);
+            }
+            let hops: Vec<&str> = request
+                .metadata()
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .collect();
+            for hop in hops.into_iter().rev() {
+                match hop.parse::<std::net::IpAddr>() {
+                    Ok(ip) if trusted_proxies.contains(&ip) => continue,
+                    Ok(ip) => return Some(ip),
+                    // A garbled hop can't be attributed; count the call against the proxy
+                    Err(_) => break,
+                }
+            }
+            Some(peer)
+        }
+
+        /// Count a call against a quota, failing with RESOURCE_EXHAUSTED over the limit
+        pub async fn check_rate_limit(
+            store: &dyn RateLimitStore,
+            key: &str,
+            cost: u64,
+            limit: u64,
+            window: std::time::Duration,
+        ) -> Result<(), tonic::Status> {
+            let Ok(usage) = store.increment(key, cost, window).await else {
+                return Ok(());
+            };
+            if usage.count <= limit {
+                return Ok(());
+            }
+            let retry_after = usage.reset_after.as_secs().max(1);
+            let mut status = tonic::Status::resource_exhausted(format!(
+                "rate limit of {} per {}s exceeded; retry after {}s",
+                limit,
+                window.as_secs(),
+                retry_after
+            ));
+            if let Ok(value) = retry_after.to_string().parse() {
+                status.metadata_mut().insert("retry-after", value);
+            }
+            Err(status)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(operation: &str, limit: u64, window_seconds: u64) -> storage::RateLimit {
+        storage::RateLimit {
+            operation: operation.to_string(),
+            limit,
+            window_seconds,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("Post", &rule("create", 100, 86400)).is_ok());
+        assert!(validate("Post", &rule("", 10, 60)).is_ok());
+        assert!(validate("Post", &rule("list", 10, 60)).is_err());
+        assert!(validate("Post", &rule("create", 0, 60)).is_err());
+        assert!(validate("Post", &rule("create", 10, 0)).is_err());
+    }
+
+    #[test]
+    fn test_generate_check_charges_after_capture() {
+        use crate::storage::seaorm::options::{cache_entity_options, cache_file_package};
+
+        let file_name = "rate_limit_check/services.proto";
+        cache_file_package(file_name, "rate_limit_check");
+        cache_entity_options(
+            file_name,
+            "Post",
+            storage::EntityOptions {
+                rate_limits: vec![rule("create", 100, 86400)],
+                ..Default::default()
+            },
+        );
+        let file = FileDescriptorProto {
+            name: Some(file_name.to_string()),
+            package: Some("rate_limit_check".to_string()),
+            ..Default::default()
+        };
+
+        let (capture, charge) = generate_check(&file, "PostService", "CreatePost").unwrap();
+        let capture = capture.to_string();
+        assert!(capture.contains("let rate_actor = request"));
+        assert!(capture.contains("client_ip (& request , & self . trusted_proxies)"));
+        assert!(!capture.contains("check_rate_limit"));
+        let charge = charge.to_string();
+        assert!(charge.contains("if let Some (actor) = & rate_actor"));
+        assert!(charge.contains("\"rate_limit_check.Post/create:86400\""));
+
+        let (capture, charge) = generate_check(&file, "PostService", "ListPosts").unwrap();
+        assert!(capture.is_empty() && charge.is_empty());
+    }
+
+    #[test]
+    fn test_generate_prelude() {
+        let code = generate_prelude().to_string();
+        assert!(code.contains("pub trait RateLimitStore"));
+        assert!(code.contains("impl RateLimitStore for InMemoryRateLimitStore"));
+        assert!(code.contains("resource_exhausted"));
+        assert!(code.contains("\"retry-after\""));
+        assert!(code.contains("pub fn client_ip < T >"));
+        assert!(code.contains("get_all (\"x-forwarded-for\")"));
+    }
+}
@@ -0,0 +1,222 @@
+//! gRPC server generation
+//!
+//! Generates grpc/server.rs when the `health=true` parameter is set: a
+//! `router()` adding the package's services to a tonic server builder next to
+//! a `tonic_health` service, and `serve()`/`serve_with_shutdown()` shortcuts
+//! for binaries that need nothing else. Each service's health status follows
+//! a periodic ping of its storage's databases (the primary, and the replica of
+//! `read_replica` services), so a load balancer stops routing to a process
+//! that lost a connection.
+
+use crate::error::GeneratorError;
+use crate::storage::seaorm::options::get_cached_service_options;
+use crate::storage::seaorm::package::collect_package_info_all_files;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::{format_ident, quote};
+
+/// Seconds between storage connectivity checks
+const HEALTH_CHECK_INTERVAL_SECONDS: u64 = 10;
+
+/// Generate the grpc/server.rs file for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || !crate::params::health() {
+        return Ok(None);
+    }
+
+    let file_name = file.name.as_deref().unwrap_or("");
+    let services: Vec<(String, bool)> = collect_package_info_all_files(all_files, file)
+        .services
+        .into_iter()
+        .map(|svc| {
+            // Storage has a read_db() only with default implementations
+            let replica = get_cached_service_options(file_name, &svc)
+                .is_some_and(|o| o.generate_implementation && o.read_replica);
+            (svc, replica)
+        })
+        .collect();
+    if services.is_empty() {
+        return Ok(None);
+    }
+
+    let code = generate_server(package, &services);
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/grpc/server.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the server module for a package's services
+///
+/// `services` pairs each service with whether its storage reads from a replica.
+fn generate_server(package: &str, services: &[(String, bool)]) -> TokenStream {
+    let mut imports = Vec::new();
+    let mut params = Vec::new();
+    let mut args = Vec::new();
+    let mut checks = Vec::new();
+    let mut adds = Vec::new();
+
+    for (svc, replica) in services {
+        let svc_camel = svc.to_upper_camel_case();
+        let svc_mod = format_ident!("{}", svc.to_snake_case());
+        let server_mod = format_ident!("{}_server", svc.to_snake_case());
+        let server_type = format_ident!("{}Server", svc_camel);
+        let storage_mod = format_ident!("{}_storage", svc.to_snake_case());
+        let storage_trait = format_ident!("{}Storage", svc_camel);
+        let grpc_type = format_ident!("{}GrpcService", svc_camel);
+        let health_name = format!("{}.{}", package, svc);
+
+        imports.push(quote! {
+            use super::super::#server_mod::#server_type;
+            use super::super::storage::#storage_mod::#storage_trait;
+            use super::#grpc_type;
+        });
+        params.push(quote! { #svc_mod: #grpc_type<impl #storage_trait + 'static> });
+        args.push(quote! { #svc_mod });
+        let replica = replica.then(|| quote! { #svc_mod.storage().read_db().clone(), });
+        checks.push(quote! { (#health_name, vec![#svc_mod.storage().db().clone(), #replica]) });
+        adds.push(quote! { .add_service(#server_type::new(#svc_mod)) });
+    }
+
+    let report_health = generate_report_health();
+
+    quote! {
+        //! gRPC server for the package's services
+        //!
+        //! Serves every service next to a `grpc.health.v1.Health` service
+        //! whose statuses follow a periodic storage connectivity check.
+        //! Requires the `tonic-health` crate.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(dead_code)]
+        #![allow(unused_imports)]
+
+        #(#imports)*
+        use tonic::transport::server::Router;
+        use tonic::transport::Server;
+        use tonic_health::server::HealthReporter;
+        use tonic_health::ServingStatus;
+
+        /// Interval between storage connectivity checks
+        pub const HEALTH_CHECK_INTERVAL: std::time::Duration =
+            std::time::Duration::from_secs(#HEALTH_CHECK_INTERVAL_SECONDS);
+
+        /// Add the package's services and a health service to a server builder
+        ///
+        /// Spawns a task pinging each service's databases every
+        /// [`HEALTH_CHECK_INTERVAL`]; a service is `SERVING` while all its
+        /// pings succeed, and the server as a whole (`""`) while every
+        /// service is. Use this over [`serve`] to configure the builder (TLS,
+        /// layers, timeouts) or add services of other packages.
+        pub fn router<L: Clone>(server: &mut Server<L>, #(#params),*) -> Router<L> {
+            let (reporter, health_service) = tonic_health::server::health_reporter();
+            let checks = vec![#(#checks),*];
+            tokio::spawn(report_health(reporter, checks));
+            server
+                .add_service(health_service)
+                #(#adds)*
+        }
+
+        /// Serve the package's services and a health service on `addr`
+        pub async fn serve(
+            addr: std::net::SocketAddr,
+            #(#params),*
+        ) -> Result<(), tonic::transport::Error> {
+            router(&mut Server::builder(), #(#args),*).serve(addr).await
+        }
+
+        /// Serve like [`serve`] until `signal` completes, then finish in-flight requests
+        pub async fn serve_with_shutdown(
+            addr: std::net::SocketAddr,
+            signal: impl std::future::Future<Output = ()>,
+            #(#params),*
+        ) -> Result<(), tonic::transport::Error> {
+            router(&mut Server::builder(), #(#args),*)
+                .serve_with_shutdown(addr, signal)
+                .await
+        }
+
+        #report_health
+    }
+}
+
+/// Generate the connectivity check loop updating the health reporter
+fn generate_report_health() -> TokenStream {
+    quote! {
+        /// Ping each service's databases forever, reporting its serving status
+        async fn report_health(
+            mut reporter: HealthReporter,
+            checks: Vec<(&'static str, Vec<sea_orm::DatabaseConnection>)>,
+        ) {
+            let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+            loop {
+                interval.tick().await;
+                let mut all_serving = true;
+                for (service, dbs) in &checks {
+                    let mut reachable = true;
+                    for db in dbs {
+                        reachable &= db.ping().await.is_ok();
+                    }
+                    let status = if reachable {
+                        ServingStatus::Serving
+                    } else {
+                        all_serving = false;
+                        ServingStatus::NotServing
+                    };
+                    reporter.set_service_status(*service, status).await;
+                }
+                let overall = if all_serving {
+                    ServingStatus::Serving
+                } else {
+                    ServingStatus::NotServing
+                };
+                reporter.set_service_status("", overall).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_server() {
+        let services = vec![("AuthorService".to_string(), false), ("PostService".to_string(), true)];
+        let code = generate_server("blog", &services).to_string();
+        assert!(code.contains("use super :: super :: author_service_server :: AuthorServiceServer ;"));
+        assert!(code.contains("post_service : PostServiceGrpcService < impl PostServiceStorage + 'static >"));
+        assert!(code.contains("(\"blog.AuthorService\" , vec ! [author_service . storage () . db () . clone () ,])"));
+        // Replicas are pinged next to the primary
+        assert!(code.contains(
+            "(\"blog.PostService\" , vec ! [post_service . storage () . db () . clone () , post_service . storage () . read_db () . clone () ,])"
+        ));
+        assert!(code.contains(". add_service (health_service) . add_service (AuthorServiceServer :: new (author_service))"));
+        // Layered builders are accepted
+        assert!(code.contains("pub fn router < L : Clone > (server : & mut Server < L > ,"));
+        assert!(code.contains("-> Router < L >"));
+        assert!(code.contains("router (& mut Server :: builder () , author_service , post_service) . serve (addr)"));
+        assert!(code.contains("pub async fn serve_with_shutdown (addr : std :: net :: SocketAddr , signal : impl std :: future :: Future < Output = () > ,"));
+        assert!(code.contains(". serve_with_shutdown (addr , signal)"));
+    }
+
+    #[test]
+    fn test_generate_report_health() {
+        let code = generate_report_health().to_string();
+        assert!(code.contains("for db in dbs { reachable &= db . ping () . await . is_ok () ; }"));
+        assert!(code.contains("set_service_status (\"\" , overall)"));
+    }
+}
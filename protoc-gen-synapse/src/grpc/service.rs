@@ -5,11 +5,18 @@
 //! and handle request validation and error conversion.
 
 use super::errors::generate_error_types;
+use super::rate_limit;
+use super::invalidation;
+use super::writes;
 use crate::storage::seaorm::options::{
     get_cached_grpc_method_options, get_cached_grpc_response_options,
-    get_cached_grpc_service_options, get_cached_validate_message_options,
+    get_cached_grpc_service_options, get_cached_service_options,
+    get_cached_rpc_method_options, get_cached_validate_message_options, grpc,
 };
 use crate::error::GeneratorError;
+use crate::storage::defaults::infer_entity_name;
+use crate::storage::seaorm::implementation::{context_columns, create_many_item_type, update_many_patch_type};
+use crate::validate::{generate_sanitize_message, has_custom_validators};
 use heck::{ToSnakeCase, ToUpperCamelCase};
 use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
@@ -20,6 +27,7 @@ use quote::{format_ident, quote};
 pub fn generate(
     file: &FileDescriptorProto,
     service: &ServiceDescriptorProto,
+    all_files: &[FileDescriptorProto],
 ) -> Result<Option<File>, GeneratorError> {
     let file_name = file.name.as_deref().unwrap_or("");
     let service_name = service.name.as_deref().unwrap_or("");
@@ -58,7 +66,7 @@ pub fn generate(
     );
 
     // Generate trait methods
-    let methods = generate_service_methods(file_name, service_name, &service.method)?;
+    let methods = generate_service_methods(file, service_name, &service.method, all_files)?;
 
     // Build identifiers
     let struct_ident = format_ident!("{}", struct_name);
@@ -84,6 +92,43 @@ pub fn generate(
     // Generate error types
     let error_types = generate_error_types();
 
+    // Rate-limited services hold the counter store their quotas use
+    let (limits_field, limits_init, limits_setter) = if rate_limit::has_rate_limits(file, service) {
+        (
+            quote! {
+                rate_limits: std::sync::Arc<dyn RateLimitStore>,
+                trusted_proxies: std::sync::Arc<[std::net::IpAddr]>,
+            },
+            quote! {
+                rate_limits: std::sync::Arc::new(InMemoryRateLimitStore::default()),
+                trusted_proxies: std::sync::Arc::from([]),
+            },
+            quote! {
+                /// Count rate limits in `store` instead of the in-memory default
+                pub fn with_rate_limit_store(mut self, store: impl RateLimitStore + 'static) -> Self {
+                    self.rate_limits = std::sync::Arc::new(store);
+                    self
+                }
+
+                /// Trust `x-forwarded-for` from these peers (e.g. the gateway)
+                ///
+                /// Calls without a principal are counted per client IP. Without
+                /// trusted proxies that is the peer address, so everything a
+                /// gateway relays would share one quota.
+                pub fn with_trusted_proxies(mut self, proxies: impl IntoIterator<Item = std::net::IpAddr>) -> Self {
+                    self.trusted_proxies = proxies.into_iter().collect();
+                    self
+                }
+            },
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {})
+    };
+
+    // Services with invalidation hooks hold an optional hook
+    let (invalidation_field, invalidation_init, invalidation_setter) =
+        invalidation::generate_hook_field(file, service);
+
     let code = quote! {
         #![doc = #module_doc]
         //!
@@ -100,16 +145,24 @@ pub fn generate(
         #error_types
 
         #[doc = #struct_doc]
-        pub struct #struct_ident<S: #storage_trait_ident + 'static> {
+        ///
+        /// Defaults to a type-erased `Arc<dyn Trait>` so implementations can be
+        /// swapped at runtime; any concrete storage type also works.
+        pub struct #struct_ident<S: #storage_trait_ident + 'static = std::sync::Arc<dyn #storage_trait_ident>> {
             storage: S,
+            #limits_field
+            #invalidation_field
         }
 
         impl<S: #storage_trait_ident + 'static> #struct_ident<S> {
             /// Create a new gRPC service with the given storage implementation
             pub fn new(storage: S) -> Self {
-                Self { storage }
+                Self { storage, #limits_init #invalidation_init }
             }
 
+            #limits_setter
+            #invalidation_setter
+
             /// Get a reference to the underlying storage
             pub fn storage(&self) -> &S {
                 &self.storage
@@ -126,6 +179,13 @@ pub fn generate(
             }
         }
 
+        impl #struct_ident {
+            /// Create a new gRPC service holding the storage as `Arc<dyn Trait>`
+            pub fn new_dyn<T: #storage_trait_ident + 'static>(storage: T) -> Self {
+                Self::new(std::sync::Arc::new(storage))
+            }
+        }
+
         #[tonic::async_trait]
         impl<S: #storage_trait_ident + 'static> #service_module_ident::#service_trait_ident for #struct_ident<S> {
             #(#methods)*
@@ -135,11 +195,8 @@ pub fn generate(
     // Format the generated code
     let content = code.to_string();
 
-    // Try to format with prettyplease if we can parse it
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content, // If parsing fails, use unformatted
-    };
+    // Format the generated code
+    let formatted = crate::params::format_code(content);
 
     Ok(Some(File {
         name: Some(output_filename),
@@ -150,12 +207,25 @@ pub fn generate(
 
 /// Generate service method implementations
 fn generate_service_methods(
-    file_name: &str,
+    file: &FileDescriptorProto,
     service_name: &str,
     methods: &[MethodDescriptorProto],
+    all_files: &[FileDescriptorProto],
 ) -> Result<Vec<TokenStream>, GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let package = file.package.as_deref().unwrap_or("");
     let mut result = Vec::new();
 
+    // Request context snapshot, taken before the request is consumed
+    let context_capture = generate_context_capture(file_name, service_name)?;
+    let in_context = |body: TokenStream| match &context_capture {
+        Some(capture) => quote! {
+            #capture
+            with_request_context(context, async move { #body }).await
+        },
+        None => body,
+    };
+
     for method in methods {
         let method_name = method.name.as_deref().unwrap_or("");
 
@@ -167,6 +237,18 @@ fn generate_service_methods(
             continue;
         }
 
+        // Paged streaming only changes how a declared stream is read
+        if method_options
+            .as_ref()
+            .is_some_and(|o| o.streaming == grpc::Streaming::Server as i32)
+            && !method.server_streaming()
+        {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{}.{}: streaming: STREAMING_SERVER needs a server-streaming RPC, declare it as `returns (stream ...)`",
+                service_name, method_name
+            )));
+        }
+
         // Determine Rust method name
         let rust_method_name = method_options
             .as_ref()
@@ -200,6 +282,31 @@ fn generate_service_methods(
             .map(|opts| opts.rich_errors)
             .unwrap_or(false);
 
+        // Streaming storage input, validated like the unary paths below
+        let stream_input = match &input_domain_type {
+            Some(domain_type) => {
+                let domain_ident = format_ident!("{}", domain_type);
+                quote! {
+                    #domain_ident::try_from(request.into_inner())
+                        .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?
+                }
+            }
+            None => quote! { request.into_inner() },
+        };
+
+        // Quotas are charged once the request is valid, and never for a dry run
+        let (rate_capture, rate_charge) = rate_limit::generate_check(file, service_name, method_name)?;
+        let is_dry_run_method = crate::graphql::is_dry_run(file_name, service_name, method_name);
+        let rate_charge = if is_dry_run_method && !rate_charge.is_empty() {
+            quote! {
+                if !is_dry_run {
+                    #rate_charge
+                }
+            }
+        } else {
+            rate_charge
+        };
+
         let method_body = if rich_errors {
             // Rich errors: return validation errors in response body
             if let Some(domain_type) = input_domain_type {
@@ -215,21 +322,23 @@ fn generate_service_methods(
                             }));
                         }
                     };
+                    #rate_charge
 
                     self.storage
                         .#method_ident(validated)
                         .await
                         .map(Response::new)
-                        .map_err(|e| tonic::Status::from(ServiceError::Storage(e)))
+                        .map_err(tonic::Status::from)
                 }
             } else {
                 // No validation, just call storage
                 quote! {
+                    #rate_charge
                     self.storage
                         .#method_ident(request.into_inner())
                         .await
                         .map(Response::new)
-                        .map_err(|e| tonic::Status::from(ServiceError::Storage(e)))
+                        .map_err(tonic::Status::from)
                 }
             }
         } else if let Some(domain_type) = input_domain_type {
@@ -239,30 +348,192 @@ fn generate_service_methods(
                 // Validate and convert to domain type
                 let validated = #domain_ident::try_from(request.into_inner())
                     .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+                #rate_charge
 
                 self.storage
                     .#method_ident(validated)
                     .await
                     .map(Response::new)
-                    .map_err(|e| tonic::Status::from(ServiceError::Storage(e)))
+                    .map_err(tonic::Status::from)
             }
         } else {
             // Without validation: pass request directly to storage
             quote! {
+                #rate_charge
                 self.storage
                     .#method_ident(request.into_inner())
                     .await
                     .map(Response::new)
-                    .map_err(|e| tonic::Status::from(ServiceError::Storage(e)))
+                    .map_err(tonic::Status::from)
+            }
+        };
+
+        // Cache invalidation sees the rows of successful writes
+        let write_hooks = invalidation::write_hook(file, service_name, method);
+        let method_body =
+            writes::wrap_body(file, service_name, method, method_body, write_hooks.into_iter().collect());
+
+        let stamp = generate_context_stamp(file, all_files, &request_type)?;
+
+        // Custom validators run before conversion; they may query the database
+        let custom_check = match file
+            .message_type
+            .iter()
+            .find(|m| m.name.as_deref() == Some(request_type.as_str()))
+            .filter(|m| has_custom_validators(file_name, m))
+            .and_then(|m| Some((m, get_cached_validate_message_options(file_name, &request_type)?)))
+        {
+            Some((request_msg, opts)) => {
+                let domain_ident = format_ident!("{}", opts.name);
+                // db() only exists on traits with generated default implementations
+                let db = if get_cached_service_options(file_name, service_name)
+                    .is_some_and(|o| o.generate_implementation)
+                {
+                    quote! { Some(self.storage.db()) }
+                } else {
+                    quote! { None }
+                };
+                let on_error = if rich_errors {
+                    quote! {
+                        return Ok(Response::new(#response_ident {
+                            errors: e.into_errors(),
+                            ..Default::default()
+                        }));
+                    }
+                } else {
+                    quote! { return Err(tonic::Status::invalid_argument(e.to_string())); }
+                };
+                // Validators see the values conversion will keep
+                let sanitize = match generate_sanitize_message(file_name, request_msg, quote! { inner }) {
+                    steps if steps.is_empty() => steps,
+                    steps => quote! {
+                        let mut request = request;
+                        let inner = request.get_mut();
+                        #steps
+                    },
+                };
+                quote! {
+                    #sanitize
+                    let ctx = ValidationContext {
+                        #[cfg(feature = "sea-orm")]
+                        db: #db,
+                        current_user: request.extensions().get::<crate::CurrentUser>(),
+                    };
+                    if let Err(e) = #domain_ident::validate_custom(request.get_ref(), &ctx).await {
+                        #on_error
+                    }
+                }
             }
+            None => quote! {},
         };
 
-        let method_token = quote! {
-            async fn #method_ident(
-                &self,
-                request: Request<#request_ident>,
-            ) -> Result<Response<#response_ident>, Status> {
+        let method_token = if method.client_streaming() && method.server_streaming() {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{}.{}: bidirectional streaming RPCs are not supported; declare a client-streaming import or a server-streaming list",
+                service_name, method_name
+            )));
+        } else if method.client_streaming() {
+            // Storage reads the requests as they arrive; a broken stream ends the import.
+            // The quota is charged once per call, and each item is stamped as it arrives.
+            let entity = get_cached_rpc_method_options(file_name, service_name, method_name)
+                .map(|o| o.entity_name)
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| infer_entity_name(method_name));
+            let (stamp_user, stamp_item) = generate_stream_stamp(all_files, package, &entity, &request_type)?;
+            let import = writes::wrap_body(
+                file,
+                service_name,
+                method,
+                quote! {
+                    self.storage
+                        .#method_ident(Box::pin(items))
+                        .await
+                        .map(Response::new)
+                        .map_err(tonic::Status::from)
+                },
+                invalidation::write_hook(file, service_name, method).into_iter().collect(),
+            );
+            let body = in_context(quote! {
+                #rate_capture
+                #rate_charge
+                #stamp_user
+                let items = tokio_stream::StreamExt::map(request.into_inner(), move |item| {
+                    item #stamp_item
+                        .map_err(|status| StorageError::InvalidArgument(format!("request stream: {}", status.message())))
+                });
+                #import
+            });
+            quote! {
+                async fn #method_ident(
+                    &self,
+                    request: Request<tonic::Streaming<#request_ident>>,
+                ) -> Result<Response<#response_ident>, Status> {
+                    #body
+                }
+            }
+        } else if method.server_streaming() {
+            // Storage returns a RowStream; its errors become per-item statuses
+            let stream_ident = format_ident!("{}Stream", method_name);
+            let body = in_context(quote! {
+                #stamp
+                #custom_check
+                let rows = self
+                    .storage
+                    .#method_ident(#stream_input)
+                    .await
+                    .map_err(tonic::Status::from)?;
+                let items = tokio_stream::StreamExt::map(rows, |item| {
+                    item.map_err(tonic::Status::from)
+                });
+                Ok(Response::new(Box::pin(items) as Self::#stream_ident))
+            });
+            quote! {
+                type #stream_ident = std::pin::Pin<
+                    Box<dyn tokio_stream::Stream<Item = Result<#response_ident, Status>> + Send + 'static>,
+                >;
+
+                async fn #method_ident(
+                    &self,
+                    request: Request<#request_ident>,
+                ) -> Result<Response<Self::#stream_ident>, Status> {
+                    #body
+                }
+            }
+        } else if is_dry_run_method {
+            // Dry runs come from in-process callers or as request metadata; storage rolls back their
+            // writes, and they are not charged against the caller's quota
+            let body = in_context(quote! {
+                let is_dry_run = dry_run() || dry_run_requested(request.metadata());
+                with_dry_run(is_dry_run, async move {
+                    #rate_capture
+                    #stamp
+                    #custom_check
+                    #method_body
+                })
+                .await
+            });
+            quote! {
+                async fn #method_ident(
+                    &self,
+                    request: Request<#request_ident>,
+                ) -> Result<Response<#response_ident>, Status> {
+                    #body
+                }
+            }
+        } else {
+            let body = in_context(quote! {
+                #rate_capture
+                #stamp
+                #custom_check
                 #method_body
+            });
+            quote! {
+                async fn #method_ident(
+                    &self,
+                    request: Request<#request_ident>,
+                ) -> Result<Response<#response_ident>, Status> {
+                    #body
+                }
             }
         };
 
@@ -272,6 +543,216 @@ fn generate_service_methods(
     Ok(result)
 }
 
+/// Generate code capturing the call's `RequestContext` (`request_context: true`)
+///
+/// A context already in scope (an in-process caller) is kept; otherwise it is
+/// read from the gateway headers in the request metadata. With `context_actor`,
+/// the actor id comes from the `CurrentUser` in the request extensions.
+fn generate_context_capture(
+    file_name: &str,
+    service_name: &str,
+) -> Result<Option<TokenStream>, GeneratorError> {
+    let Some(opts) =
+        get_cached_grpc_service_options(file_name, service_name).filter(|o| o.request_context)
+    else {
+        return Ok(None);
+    };
+    if opts.context_actor.is_empty() {
+        return Ok(Some(quote! {
+            let context = request_context().unwrap_or_else(|| RequestContext::from_metadata(&request));
+        }));
+    }
+
+    let path: Vec<_> = match opts.context_actor.split_once('.') {
+        Some(("current_user", rest)) => rest.split('.').map(|p| format_ident!("{}", p.to_snake_case())).collect(),
+        _ => {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{}: context_actor must be a current_user path, got {:?}",
+                service_name, opts.context_actor
+            )))
+        }
+    };
+    Ok(Some(quote! {
+        let mut context = request_context().unwrap_or_else(|| RequestContext::from_metadata(&request));
+        if let Some(user) = request.extensions().get::<crate::CurrentUser>() {
+            context.actor_id = Some(user #(.#path)*.to_string());
+        }
+    }))
+}
+
+/// Generate code overwriting `from_context` fields of a Create/Upsert/Update request
+///
+/// The principal is the `CurrentUser` an auth interceptor or layer put in the
+/// request extensions; calls without one are rejected as unauthenticated.
+/// Create-many requests stamp each of their `items` like a create. The entity
+/// may be declared in any of `all_files`, not only the service's file.
+fn generate_context_stamp(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+    request_type: &str,
+) -> Result<TokenStream, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    let find = |name: &str| file.message_type.iter().find(|m| m.name.as_deref() == Some(name));
+
+    // Update-many patches are stamped like a single update
+    if let Some(rest) = request_type.strip_prefix("UpdateMany").and_then(|r| r.strip_suffix("Request")) {
+        let entity = infer_entity_name(&format!("UpdateMany{}", rest));
+        let Some(patch) = find(request_type)
+            .and_then(update_many_patch_type)
+            .and_then(|name| all_files.iter().flat_map(|f| &f.message_type).find(|m| m.name.as_deref() == Some(&name)))
+        else {
+            return Ok(quote! {});
+        };
+        let (idents, values) = stamp_fields(all_files, package, patch, &entity, false)?;
+        if idents.is_empty() {
+            return Ok(quote! {});
+        }
+        return Ok(quote! {
+            // Stamp principal-owned fields of the patch; client-supplied values are ignored
+            let mut request = request;
+            let user = request
+                .extensions()
+                .get::<crate::CurrentUser>()
+                .ok_or_else(|| Status::unauthenticated("authentication required"))?;
+            #(let #idents = #values;)*
+            if let Some(patch) = request.get_mut().patch.as_mut() {
+                #(patch.#idents = #idents;)*
+            }
+        });
+    }
+
+    let item_type = request_type
+        .strip_prefix("CreateMany")
+        .and_then(|_| find(request_type))
+        .and_then(create_many_item_type);
+
+    let (idents, values) = context_stamp_fields(file, all_files, item_type.as_deref().unwrap_or(request_type))?;
+    if idents.is_empty() {
+        return Ok(quote! {});
+    }
+
+    let assign = if item_type.is_some() {
+        quote! {
+            for item in &mut request.get_mut().items {
+                #(item.#idents = Clone::clone(&#idents);)*
+            }
+        }
+    } else {
+        quote! {
+            let inner = request.get_mut();
+            #(inner.#idents = #idents;)*
+        }
+    };
+
+    Ok(quote! {
+        // Stamp principal-owned fields; client-supplied values are ignored
+        let mut request = request;
+        let user = request
+            .extensions()
+            .get::<crate::CurrentUser>()
+            .ok_or_else(|| Status::unauthenticated("authentication required"))?;
+        #(let #idents = #values;)*
+        #assign
+    })
+}
+
+/// Generate the stamping of the items of a client-streaming import
+///
+/// Returns the statements reading the principal's values before the stream
+/// is taken, and the `Result::map` assigning them to each `item`. Items are
+/// stamped like a create of `entity`, whose fields must be declared in
+/// `all_files` within `package`.
+fn generate_stream_stamp(
+    all_files: &[FileDescriptorProto],
+    package: &str,
+    entity: &str,
+    item_type: &str,
+) -> Result<(TokenStream, TokenStream), GeneratorError> {
+    let Some(item_msg) = all_files
+        .iter()
+        .flat_map(|f| &f.message_type)
+        .find(|m| m.name.as_deref() == Some(item_type))
+    else {
+        return Ok((quote! {}, quote! {}));
+    };
+    let (idents, values) = stamp_fields(all_files, package, item_msg, entity, true)?;
+    if idents.is_empty() {
+        return Ok((quote! {}, quote! {}));
+    }
+    Ok((
+        quote! {
+            // Stamp principal-owned fields of every item; client-supplied values are ignored
+            let user = request
+                .extensions()
+                .get::<crate::CurrentUser>()
+                .ok_or_else(|| Status::unauthenticated("authentication required"))?;
+            #(let #idents = #values;)*
+        },
+        quote! {
+            .map(|mut item| {
+                #(item.#idents = Clone::clone(&#idents);)*
+                item
+            })
+        },
+    ))
+}
+
+/// Fields of a Create/Upsert/Update request stamped from the principal, with their values
+fn context_stamp_fields(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+    request_type: &str,
+) -> Result<(Vec<proc_macro2::Ident>, Vec<TokenStream>), GeneratorError> {
+    let (entity, on_create) = match request_type.strip_suffix("Request") {
+        Some(rest) => match (
+            rest.strip_prefix("Create").or_else(|| rest.strip_prefix("Upsert")),
+            rest.strip_prefix("Update"),
+        ) {
+            (Some(entity), _) => (entity, true),
+            (_, Some(entity)) => (entity, false),
+            _ => return Ok(Default::default()),
+        },
+        None => return Ok(Default::default()),
+    };
+    let Some(request_msg) = file
+        .message_type
+        .iter()
+        .find(|m| m.name.as_deref() == Some(request_type))
+    else {
+        return Ok(Default::default());
+    };
+    stamp_fields(all_files, file.package.as_deref().unwrap_or(""), request_msg, entity, on_create)
+}
+
+/// Fields of `message` filled from the principal on create or update of `entity`, with their values
+fn stamp_fields(
+    all_files: &[FileDescriptorProto],
+    package: &str,
+    message: &prost_types::DescriptorProto,
+    entity: &str,
+    on_create: bool,
+) -> Result<(Vec<proc_macro2::Ident>, Vec<TokenStream>), GeneratorError> {
+    let columns = context_columns(all_files, package, entity)?;
+    Ok(columns
+        .iter()
+        .filter(|c| if on_create { c.on_create } else { c.on_update })
+        .filter_map(|c| {
+            let field = message
+                .field
+                .iter()
+                .find(|f| f.name.as_deref() == Some(c.name.as_str()))?;
+            let ident = format_ident!("{}", c.name.to_snake_case());
+            let path = c.path.iter().map(|p| format_ident!("{}", p.to_snake_case()));
+            let value = if field.proto3_optional.unwrap_or(false) {
+                quote! { Some(Clone::clone(&user #(.#path)*)) }
+            } else {
+                quote! { Clone::clone(&user #(.#path)*) }
+            };
+            Some((ident, value))
+        })
+        .unzip())
+}
+
 /// Extract a Rust type name from a protobuf type path
 ///
 /// Converts ".package.name.TypeName" to "TypeName"
@@ -291,3 +772,218 @@ fn extract_type_name(type_name: Option<&str>) -> String {
         None => "()".to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::seaorm::options::{cache_column_options, storage};
+    use prost_types::{DescriptorProto, FieldDescriptorProto};
+
+    fn message(name: &str, fields: &[&str]) -> DescriptorProto {
+        DescriptorProto {
+            name: Some(name.to_string()),
+            field: fields
+                .iter()
+                .enumerate()
+                .map(|(i, f)| FieldDescriptorProto {
+                    name: Some(f.to_string()),
+                    number: Some(i as i32 + 1),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_context_stamp_finds_entity_in_another_file() {
+        let entities = FileDescriptorProto {
+            name: Some("stamp_test/entities.proto".to_string()),
+            message_type: vec![message("Note", &["id", "title", "created_by"])],
+            ..Default::default()
+        };
+        let services = FileDescriptorProto {
+            name: Some("stamp_test/services.proto".to_string()),
+            message_type: vec![message("CreateNoteRequest", &["title", "created_by"])],
+            ..Default::default()
+        };
+        cache_column_options(
+            "stamp_test/entities.proto",
+            "Note",
+            3,
+            storage::ColumnOptions {
+                from_context: "current_user.id".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let all_files = [entities, services.clone()];
+        let code = generate_context_stamp(&services, &all_files, "CreateNoteRequest")
+            .unwrap()
+            .to_string();
+        assert!(code.contains("inner . created_by = created_by"));
+        assert!(code.contains("get :: < crate :: CurrentUser > ()"));
+
+        // Without the entity's file there is nothing to stamp
+        let code = generate_context_stamp(&services, std::slice::from_ref(&services), "CreateNoteRequest")
+            .unwrap()
+            .to_string();
+        assert!(code.is_empty());
+
+        // Nor with a same-named entity of another package
+        let foreign = FileDescriptorProto {
+            name: Some("stamp_other/entities.proto".to_string()),
+            package: Some("stamp_other".to_string()),
+            message_type: vec![message("Note", &["id", "title", "created_by"])],
+            ..Default::default()
+        };
+        cache_column_options(
+            "stamp_other/entities.proto",
+            "Note",
+            3,
+            storage::ColumnOptions {
+                from_context: "current_user.id".to_string(),
+                ..Default::default()
+            },
+        );
+        let code = generate_context_stamp(&services, &[foreign, services.clone()], "CreateNoteRequest")
+            .unwrap()
+            .to_string();
+        assert!(code.is_empty());
+    }
+
+    #[test]
+    fn test_custom_and_bulk_writes_call_invalidation_hook() {
+        use crate::storage::seaorm::options::{
+            cache_entity_options, cache_file_package, cache_graphql_mutation_options, cache_grpc_service_options,
+            cache_rpc_method_options, graphql, grpc,
+        };
+
+        let file_name = "invalidation_rpcs/services.proto";
+        cache_file_package(file_name, "invalidation_rpcs");
+        cache_entity_options(file_name, "Post", storage::EntityOptions::default());
+        cache_grpc_service_options(
+            file_name,
+            "PostService",
+            grpc::ServiceOptions {
+                invalidation_hooks: true,
+                ..Default::default()
+            },
+        );
+        cache_rpc_method_options(
+            file_name,
+            "PostService",
+            "PublishPost",
+            storage::MethodOptions {
+                custom: true,
+                entity_name: "Post".to_string(),
+                ..Default::default()
+            },
+        );
+        cache_graphql_mutation_options(file_name, "PostService", "PublishPost", graphql::MutationOptions::default());
+
+        let file = FileDescriptorProto {
+            name: Some(file_name.to_string()),
+            package: Some("invalidation_rpcs".to_string()),
+            message_type: vec![
+                message("Post", &["id"]),
+                message("PublishPostRequest", &["id"]),
+                message("PublishPostResponse", &["published"]),
+                message("UpdateManyPostsRequest", &["filter"]),
+                message("UpdateManyPostsResponse", &["updated"]),
+            ],
+            ..Default::default()
+        };
+        let method = |name: &str| MethodDescriptorProto {
+            name: Some(name.to_string()),
+            input_type: Some(format!(".invalidation_rpcs.{}Request", name)),
+            output_type: Some(format!(".invalidation_rpcs.{}Response", name)),
+            ..Default::default()
+        };
+        let methods = [method("PublishPost"), method("UpdateManyPosts")];
+        let service = ServiceDescriptorProto {
+            name: Some("PostService".to_string()),
+            method: methods.to_vec(),
+            ..Default::default()
+        };
+        assert!(invalidation::has_invalidation_hooks(&file, &service));
+
+        let code = generate_service_methods(&file, "PostService", &methods, std::slice::from_ref(&file)).unwrap();
+        let publish = code[0].to_string();
+        assert!(publish.contains("let invalidation_key = request . get_ref () . id . to_string () ;"));
+        assert!(publish.contains(
+            "if let Some (invalidation) = self . invalidation . as_ref () { invalidation . invalidate (\"Post\" , & invalidation_key , ChangeKind :: Updated) . await ; }"
+        ));
+        let update_many = code[1].to_string();
+        assert!(update_many.contains(
+            "if let Some (invalidation) = self . invalidation . as_ref () { invalidation . invalidate_all (\"Post\" , ChangeKind :: Updated) . await ; }"
+        ));
+    }
+
+    #[test]
+    fn test_import_is_rate_limited_stamped_and_invalidated() {
+        use crate::storage::seaorm::options::{cache_entity_options, cache_file_package, cache_grpc_service_options, grpc};
+
+        let file_name = "import_rpcs/services.proto";
+        cache_file_package(file_name, "import_rpcs");
+        cache_entity_options(
+            file_name,
+            "Post",
+            storage::EntityOptions {
+                rate_limits: vec![storage::RateLimit {
+                    operation: "create".to_string(),
+                    limit: 10,
+                    window_seconds: 60,
+                }],
+                ..Default::default()
+            },
+        );
+        cache_column_options(
+            file_name,
+            "Post",
+            3,
+            storage::ColumnOptions {
+                from_context: "current_user.id".to_string(),
+                ..Default::default()
+            },
+        );
+        cache_grpc_service_options(
+            file_name,
+            "PostService",
+            grpc::ServiceOptions {
+                invalidation_hooks: true,
+                ..Default::default()
+            },
+        );
+
+        let file = FileDescriptorProto {
+            name: Some(file_name.to_string()),
+            package: Some("import_rpcs".to_string()),
+            message_type: vec![
+                message("Post", &["id", "title", "author_id"]),
+                message("CreatePostRequest", &["title", "author_id"]),
+                message("ImportPostsResponse", &["inserted", "errors"]),
+            ],
+            ..Default::default()
+        };
+        let methods = [MethodDescriptorProto {
+            name: Some("ImportPosts".to_string()),
+            input_type: Some(".import_rpcs.CreatePostRequest".to_string()),
+            output_type: Some(".import_rpcs.ImportPostsResponse".to_string()),
+            client_streaming: Some(true),
+            ..Default::default()
+        }];
+
+        let code = generate_service_methods(&file, "PostService", &methods, std::slice::from_ref(&file)).unwrap();
+        let import = code[0].to_string();
+        // Charged once, before the stream is consumed
+        assert_eq!(import.matches("check_rate_limit").count(), 1);
+        assert!(import.find("check_rate_limit") < import.find("request . into_inner ()"));
+        assert!(import.contains("\"import_rpcs.Post/create:60\""));
+        // Every item is stamped from the principal
+        assert!(import.contains("let author_id = Clone :: clone (& user . id) ;"));
+        assert!(import.contains("item . map (| mut item | { item . author_id = Clone :: clone (& author_id) ; item }) . map_err"));
+        // Imported rows are dropped from caches as a whole
+        assert!(import.contains("invalidation . invalidate_all (\"Post\" , ChangeKind :: Created) . await ;"));
+    }
+}
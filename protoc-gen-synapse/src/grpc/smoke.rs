@@ -0,0 +1,544 @@
+//! Smoke test generation
+//!
+//! With `smoke=true`, generates {package}/smoke.rs: a post-deploy check that
+//! calls every service's generated RPCs through the tonic clients against a
+//! target endpoint. Without fixtures it only reads (list one record, get it
+//! back); with a `Create*Request` fixture per service it also runs
+//! create → get → list → update → delete on a disposable record. `cli()`
+//! prints a pass/fail summary and returns the process exit code, so a
+//! binary is a single line.
+
+use crate::error::GeneratorError;
+use crate::storage::seaorm::options::{get_cached_grpc_method_options, get_cached_grpc_service_options};
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FileDescriptorProto, ServiceDescriptorProto};
+use quote::{format_ident, quote};
+
+/// Environment variable holding the endpoint when no argument is given
+const ENDPOINT_ENV: &str = "SMOKE_ENDPOINT";
+
+/// An RPC the smoke test calls
+#[derive(Debug, Clone, PartialEq)]
+struct SmokeRpc {
+    /// Proto method name (e.g. `GetUser`), used in the report
+    name: String,
+    /// Request message name
+    request: String,
+}
+
+/// A message-typed response field carrying the entity (e.g. `user`)
+#[derive(Debug, Clone, PartialEq)]
+struct EntityRpc {
+    rpc: SmokeRpc,
+    field: String,
+}
+
+/// CRUD RPCs of one service, matched by name prefix
+#[derive(Debug, Clone, Default, PartialEq)]
+struct SmokeService {
+    service: String,
+    /// List RPC, and whether its request pages with `first`
+    list: Option<(SmokeRpc, bool)>,
+    get: Option<EntityRpc>,
+    create: Option<EntityRpc>,
+    update: Option<SmokeRpc>,
+    delete: Option<SmokeRpc>,
+}
+
+/// Find a message by (possibly qualified) type name
+fn find_message<'a>(all_files: &'a [FileDescriptorProto], type_name: &str) -> Option<&'a DescriptorProto> {
+    let name = type_name.rsplit('.').next().unwrap_or(type_name);
+    all_files
+        .iter()
+        .flat_map(|f| f.message_type.iter())
+        .find(|m| m.name.as_deref() == Some(name))
+}
+
+fn has_field(message: &DescriptorProto, field: &str) -> bool {
+    message.field.iter().any(|f| f.name.as_deref() == Some(field))
+}
+
+/// First message field of a response whose type has an `id`
+fn entity_field(all_files: &[FileDescriptorProto], response: &DescriptorProto) -> Option<String> {
+    response
+        .field
+        .iter()
+        .filter(|f| f.r#type() == Type::Message && f.label() != Label::Repeated)
+        .find(|f| {
+            f.type_name
+                .as_deref()
+                .and_then(|t| find_message(all_files, t))
+                .is_some_and(|m| has_field(m, "id"))
+        })
+        .and_then(|f| f.name.clone())
+}
+
+/// Whether a list response is a connection whose edge nodes have an `id`
+fn is_connection(all_files: &[FileDescriptorProto], response: &DescriptorProto) -> bool {
+    let edge = response
+        .field
+        .iter()
+        .find(|f| f.name.as_deref() == Some("edges") && f.r#type() == Type::Message)
+        .and_then(|f| f.type_name.as_deref())
+        .and_then(|t| find_message(all_files, t));
+    let node = edge
+        .and_then(|e| e.field.iter().find(|f| f.name.as_deref() == Some("node")))
+        .and_then(|f| f.type_name.as_deref())
+        .and_then(|t| find_message(all_files, t));
+    node.is_some_and(|n| has_field(n, "id"))
+}
+
+/// Collect the RPCs the smoke test can drive for a gRPC service
+fn collect_service(
+    file_name: &str,
+    service: &ServiceDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> SmokeService {
+    let service_name = service.name.as_deref().unwrap_or("");
+    let mut smoke = SmokeService {
+        service: service_name.to_string(),
+        ..Default::default()
+    };
+
+    for method in &service.method {
+        let method_name = method.name.as_deref().unwrap_or("");
+        if get_cached_grpc_method_options(file_name, service_name, method_name).is_some_and(|o| o.skip) {
+            continue;
+        }
+        let (Some(request), Some(response)) = (
+            method.input_type.as_deref().and_then(|t| find_message(all_files, t)),
+            method.output_type.as_deref().and_then(|t| find_message(all_files, t)),
+        ) else {
+            continue;
+        };
+        let rpc = SmokeRpc {
+            name: method_name.to_string(),
+            request: request.name.clone().unwrap_or_default(),
+        };
+        let by_id = has_field(request, "id");
+
+        if method_name.starts_with("List") && is_connection(all_files, response) {
+            smoke.list = Some((rpc, has_field(request, "first")));
+        } else if method_name.starts_with("Get") && by_id {
+            smoke.get = entity_field(all_files, response).map(|field| EntityRpc { rpc, field });
+        } else if method_name.starts_with("Create") && !method_name.starts_with("CreateMany") {
+            smoke.create = entity_field(all_files, response).map(|field| EntityRpc { rpc, field });
+        } else if method_name.starts_with("Update") && by_id {
+            smoke.update = Some(rpc);
+        } else if method_name.starts_with("Delete") && by_id {
+            smoke.delete = Some(rpc);
+        }
+    }
+
+    smoke
+}
+
+/// Generate smoke.rs for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || !crate::params::smoke() {
+        return Ok(None);
+    }
+
+    let services = collect_services(file, all_files);
+    if services.is_empty() {
+        return Ok(None);
+    }
+    let code = generate_smoke(package, &services);
+
+    Ok(Some(File {
+        name: Some(format!("{}/smoke.rs", package.replace('.', "/"))),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    }))
+}
+
+/// Services of the file that expose a gRPC server
+fn collect_services(file: &FileDescriptorProto, all_files: &[FileDescriptorProto]) -> Vec<SmokeService> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    file.service
+        .iter()
+        .filter(|svc| {
+            get_cached_grpc_service_options(file_name, svc.name.as_deref().unwrap_or(""))
+                .is_some_and(|o| !o.skip)
+        })
+        .map(|svc| collect_service(file_name, svc, all_files))
+        .collect()
+}
+
+/// Generate the smoke module for a package's services
+fn generate_smoke(package: &str, services: &[SmokeService]) -> TokenStream {
+    let doc = format!("Post-deploy smoke test for the {} package", package);
+    let fixture_fields = services.iter().filter_map(|s| {
+        let create = s.create.as_ref()?;
+        let field = format_ident!("{}", create.rpc.name.to_snake_case());
+        let request = format_ident!("{}", create.rpc.request);
+        Some(quote! { pub #field: Option<#request>, })
+    });
+    let calls = services.iter().map(|s| {
+        let function = format_ident!("smoke_{}", s.service.to_snake_case());
+        let fixture = match &s.create {
+            Some(create) => {
+                let field = format_ident!("{}", create.rpc.name.to_snake_case());
+                quote! { fixtures.#field.take() }
+            }
+            None => quote! { None },
+        };
+        quote! { #function(channel.clone(), #fixture, &mut report).await; }
+    });
+    let functions = services.iter().map(generate_service_check);
+
+    quote! {
+        #![doc = #doc]
+        //!
+        //! Calls each RPC through the generated clients and records pass/fail.
+        //! Read-only unless fixtures for the create requests are supplied.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+        #![allow(unused_mut)]
+        #![allow(unused_variables)]
+
+        use std::future::Future;
+        use std::time::{Duration, Instant};
+
+        use tonic::transport::Channel;
+
+        use super::*;
+
+        /// Outcome of one RPC call
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum SmokeOutcome {
+            Passed,
+            Failed(String),
+            Skipped(String),
+        }
+
+        /// One RPC call in the report
+        #[derive(Debug, Clone)]
+        pub struct SmokeCheck {
+            /// `Service.Method`
+            pub rpc: String,
+            pub outcome: SmokeOutcome,
+            pub elapsed: Duration,
+        }
+
+        /// Every check of a smoke run, in call order
+        #[derive(Debug, Clone, Default)]
+        pub struct SmokeReport {
+            pub checks: Vec<SmokeCheck>,
+        }
+
+        impl SmokeReport {
+            /// Whether no check failed
+            pub fn success(&self) -> bool {
+                !self.checks.iter().any(|c| matches!(c.outcome, SmokeOutcome::Failed(_)))
+            }
+
+            fn count(&self, f: impl Fn(&SmokeOutcome) -> bool) -> usize {
+                self.checks.iter().filter(|c| f(&c.outcome)).count()
+            }
+
+            fn skip(&mut self, rpc: &str, reason: &str) {
+                self.checks.push(SmokeCheck {
+                    rpc: rpc.to_string(),
+                    outcome: SmokeOutcome::Skipped(reason.to_string()),
+                    elapsed: Duration::ZERO,
+                });
+            }
+
+            /// Await an RPC, record its outcome, and return the response on success
+            async fn check<T>(
+                &mut self,
+                rpc: &str,
+                call: impl Future<Output = Result<tonic::Response<T>, tonic::Status>>,
+            ) -> Option<T> {
+                let started = Instant::now();
+                let result = call.await;
+                let (outcome, response) = match result {
+                    Ok(response) => (SmokeOutcome::Passed, Some(response.into_inner())),
+                    Err(status) => (
+                        SmokeOutcome::Failed(format!("{:?}: {}", status.code(), status.message())),
+                        None,
+                    ),
+                };
+                self.checks.push(SmokeCheck {
+                    rpc: rpc.to_string(),
+                    outcome,
+                    elapsed: started.elapsed(),
+                });
+                response
+            }
+        }
+
+        impl std::fmt::Display for SmokeReport {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                for check in &self.checks {
+                    match &check.outcome {
+                        SmokeOutcome::Passed => writeln!(f, "PASS {} ({:?})", check.rpc, check.elapsed)?,
+                        SmokeOutcome::Failed(e) => writeln!(f, "FAIL {} ({:?}): {}", check.rpc, check.elapsed, e)?,
+                        SmokeOutcome::Skipped(r) => writeln!(f, "SKIP {}: {}", check.rpc, r)?,
+                    }
+                }
+                write!(
+                    f,
+                    "{} passed, {} failed, {} skipped",
+                    self.count(|o| *o == SmokeOutcome::Passed),
+                    self.count(|o| matches!(o, SmokeOutcome::Failed(_))),
+                    self.count(|o| matches!(o, SmokeOutcome::Skipped(_))),
+                )
+            }
+        }
+
+        /// Create requests for disposable records, per service
+        ///
+        /// Services without a fixture get read-only checks.
+        #[derive(Debug, Clone, Default)]
+        pub struct SmokeFixtures {
+            #(#fixture_fields)*
+        }
+
+        /// Run every check against a channel
+        pub async fn run(channel: Channel, mut fixtures: SmokeFixtures) -> SmokeReport {
+            let mut report = SmokeReport::default();
+            #(#calls)*
+            report
+        }
+
+        /// Read-only smoke test against the endpoint given as the first
+        /// argument (or `SMOKE_ENDPOINT`), printing the report
+        ///
+        /// `fn main() -> ExitCode` can return this directly.
+        pub async fn cli() -> std::process::ExitCode {
+            let Some(endpoint) = std::env::args().nth(1).or_else(|| std::env::var(#ENDPOINT_ENV).ok()) else {
+                eprintln!("usage: smoke <endpoint> (or set {})", #ENDPOINT_ENV);
+                return std::process::ExitCode::from(2);
+            };
+            let channel = match Channel::from_shared(endpoint) {
+                Ok(endpoint) => match endpoint.connect().await {
+                    Ok(channel) => channel,
+                    Err(e) => {
+                        eprintln!("connect failed: {}", e);
+                        return std::process::ExitCode::FAILURE;
+                    }
+                },
+                Err(e) => {
+                    eprintln!("invalid endpoint: {}", e);
+                    return std::process::ExitCode::from(2);
+                }
+            };
+            let report = run(channel, SmokeFixtures::default()).await;
+            println!("{}", report);
+            if report.success() {
+                std::process::ExitCode::SUCCESS
+            } else {
+                std::process::ExitCode::FAILURE
+            }
+        }
+
+        #(#functions)*
+    }
+}
+
+/// Generate the check sequence for one service
+fn generate_service_check(s: &SmokeService) -> TokenStream {
+    let function = format_ident!("smoke_{}", s.service.to_snake_case());
+    let client_mod = format_ident!("{}_client", s.service.to_snake_case());
+    let client = format_ident!("{}Client", s.service);
+    let label = |rpc: &SmokeRpc| format!("{}.{}", s.service, rpc.name);
+
+    // List one record; its id drives the read-only get
+    let list = match &s.list {
+        Some((rpc, paging)) => {
+            let name = label(rpc);
+            let method = format_ident!("{}", rpc.name.to_snake_case());
+            let request = format_ident!("{}", rpc.request);
+            let page = if *paging { quote! { first: Some(1), } } else { quote! {} };
+            quote! {
+                let listed = report
+                    .check(#name, client.#method(#request { #page ..Default::default() }))
+                    .await;
+                let existing = listed.and_then(|c| c.edges.into_iter().find_map(|e| e.node)).map(|n| n.id);
+            }
+        }
+        None => quote! {},
+    };
+    let get = |id: TokenStream| match &s.get {
+        Some(get) => {
+            let name = label(&get.rpc);
+            let method = format_ident!("{}", get.rpc.name.to_snake_case());
+            let request = format_ident!("{}", get.rpc.request);
+            quote! {
+                report
+                    .check(#name, client.#method(#request { id: #id, ..Default::default() }))
+                    .await;
+            }
+        }
+        None => quote! {},
+    };
+    let read_get = match (&s.get, &s.list) {
+        (Some(rpc), Some(_)) => {
+            let get_existing = get(quote! { id });
+            let name = label(&rpc.rpc);
+            quote! {
+                match existing {
+                    Some(id) => { #get_existing }
+                    None => report.skip(#name, "no existing record to read"),
+                }
+            }
+        }
+        (Some(rpc), None) => {
+            let name = label(&rpc.rpc);
+            quote! { report.skip(#name, "no list RPC to find a record"); }
+        }
+        (None, _) => quote! {},
+    };
+
+    let write = match &s.create {
+        Some(create) => {
+            let create_name = label(&create.rpc);
+            let create_method = format_ident!("{}", create.rpc.name.to_snake_case());
+            let entity = format_ident!("{}", create.field);
+            let get_created = get(quote! { id.clone() });
+            let list_again = match &s.list {
+                Some((rpc, _)) => {
+                    let name = label(rpc);
+                    let method = format_ident!("{}", rpc.name.to_snake_case());
+                    let request = format_ident!("{}", rpc.request);
+                    quote! {
+                        report.check(#name, client.#method(#request::default())).await;
+                    }
+                }
+                None => quote! {},
+            };
+            let update = match &s.update {
+                Some(rpc) => {
+                    let name = label(rpc);
+                    let method = format_ident!("{}", rpc.name.to_snake_case());
+                    let request = format_ident!("{}", rpc.request);
+                    quote! {
+                        report
+                            .check(#name, client.#method(#request { id: id.clone(), ..Default::default() }))
+                            .await;
+                    }
+                }
+                None => quote! {},
+            };
+            let delete = match &s.delete {
+                Some(rpc) => {
+                    let name = label(rpc);
+                    let method = format_ident!("{}", rpc.name.to_snake_case());
+                    let request = format_ident!("{}", rpc.request);
+                    quote! {
+                        report
+                            .check(#name, client.#method(#request { id: id.clone(), ..Default::default() }))
+                            .await;
+                    }
+                }
+                None => quote! {},
+            };
+            quote! {
+                let Some(request) = fixture else {
+                    report.skip(#create_name, "no fixture, read-only run");
+                    return;
+                };
+                let created = report.check(#create_name, client.#create_method(request)).await;
+                let Some(id) = created.and_then(|r| r.#entity).map(|e| e.id) else {
+                    return;
+                };
+                #get_created
+                #list_again
+                #update
+                #delete
+            }
+        }
+        None => quote! { let _ = fixture; },
+    };
+    let fixture_type = match &s.create {
+        Some(create) => {
+            let request = format_ident!("{}", create.rpc.request);
+            quote! { Option<#request> }
+        }
+        None => quote! { Option<()> },
+    };
+
+    quote! {
+        async fn #function(channel: Channel, fixture: #fixture_type, report: &mut SmokeReport) {
+            let mut client = #client_mod::#client::new(channel);
+            #list
+            #read_get
+            #write
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rpc(name: &str, request: &str) -> SmokeRpc {
+        SmokeRpc {
+            name: name.to_string(),
+            request: request.to_string(),
+        }
+    }
+
+    fn user_service() -> SmokeService {
+        SmokeService {
+            service: "UserService".to_string(),
+            list: Some((rpc("ListUsers", "ListUsersRequest"), true)),
+            get: Some(EntityRpc {
+                rpc: rpc("GetUser", "GetUserRequest"),
+                field: "user".to_string(),
+            }),
+            create: Some(EntityRpc {
+                rpc: rpc("CreateUser", "CreateUserRequest"),
+                field: "user".to_string(),
+            }),
+            update: Some(rpc("UpdateUser", "UpdateUserRequest")),
+            delete: Some(rpc("DeleteUser", "DeleteUserRequest")),
+        }
+    }
+
+    #[test]
+    fn test_generate_service_check_order() {
+        let code = generate_service_check(&user_service()).to_string();
+        assert!(code.contains("user_service_client :: UserServiceClient :: new (channel)"));
+        assert!(code.contains("ListUsersRequest { first : Some (1) ,"));
+        let create = code.find("client . create_user (request)").unwrap();
+        let update = code.find("client . update_user").unwrap();
+        let delete = code.find("client . delete_user").unwrap();
+        assert!(create < update && update < delete);
+        assert!(code.contains("r . user"));
+        assert!(code.contains("\"no fixture, read-only run\""));
+    }
+
+    #[test]
+    fn test_generate_service_check_read_only() {
+        let service = SmokeService {
+            create: None,
+            update: None,
+            delete: None,
+            ..user_service()
+        };
+        let code = generate_service_check(&service).to_string();
+        assert!(code.contains("fixture : Option < () >"));
+        assert!(code.contains("client . get_user"));
+        assert!(!code.contains("create_user"));
+    }
+
+    #[test]
+    fn test_generate_smoke_fixtures() {
+        let code = generate_smoke("iam", &[user_service()]).to_string();
+        assert!(code.contains("pub create_user : Option < CreateUserRequest >"));
+        assert!(code.contains("smoke_user_service (channel . clone () , fixtures . create_user . take ()"));
+        assert!(code.contains("\"SMOKE_ENDPOINT\""));
+    }
+}
@@ -0,0 +1,126 @@
+//! rustls TLS helper generation
+//!
+//! Generates grpc/tls.rs when the `tls=rustls` parameter is set. The helpers
+//! only use tonic's rustls-based TLS support (`tls-ring` + `tls-webpki-roots`),
+//! so binaries built on them never link openssl.
+
+use crate::error::GeneratorError;
+use crate::params;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::quote;
+
+/// Environment variable holding the server certificate chain (PEM file path)
+const CERT_ENV: &str = "GRPC_TLS_CERT";
+
+/// Environment variable holding the server private key (PEM file path)
+const KEY_ENV: &str = "GRPC_TLS_KEY";
+
+/// Generate the grpc/tls.rs file for a package
+pub fn generate(file: &FileDescriptorProto) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || file.service.is_empty() || !params::rustls() {
+        return Ok(None);
+    }
+
+    let client = generate_client();
+    let server = generate_server();
+
+    let code = quote! {
+        //! rustls TLS for gRPC channels and servers
+        //!
+        //! Requires tonic's `tls-ring` and `tls-webpki-roots` features.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(dead_code)]
+
+        use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity, ServerTlsConfig};
+
+        #client
+
+        #server
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/grpc/tls.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the lazy client channel builder
+fn generate_client() -> TokenStream {
+    quote! {
+        /// Build a lazily-connected channel, using rustls with webpki roots for https endpoints
+        pub fn channel(endpoint: impl Into<String>) -> Result<Channel, tonic::transport::Error> {
+            let endpoint: String = endpoint.into();
+            let https = endpoint.starts_with("https://");
+            let mut endpoint = Endpoint::from_shared(endpoint)?;
+            if https {
+                endpoint = endpoint.tls_config(ClientTlsConfig::new().with_webpki_roots())?;
+            }
+            Ok(endpoint.connect_lazy())
+        }
+
+        /// Build a lazily-connected channel that trusts a private CA (PEM)
+        pub fn channel_with_ca(
+            endpoint: impl Into<String>,
+            ca_pem: impl AsRef<[u8]>,
+        ) -> Result<Channel, tonic::transport::Error> {
+            let tls = ClientTlsConfig::new().ca_certificate(Certificate::from_pem(ca_pem));
+            Ok(Endpoint::from_shared(endpoint.into())?.tls_config(tls)?.connect_lazy())
+        }
+    }
+}
+
+/// Generate the server TLS config loader
+fn generate_server() -> TokenStream {
+    quote! {
+        /// Load a server TLS config from the PEM files named in the environment
+        ///
+        /// Returns `Ok(None)` when neither variable is set, so services can fall
+        /// back to plaintext behind a terminating proxy.
+        pub fn server_tls_from_env() -> std::io::Result<Option<ServerTlsConfig>> {
+            let (cert, key) = match (std::env::var(#CERT_ENV), std::env::var(#KEY_ENV)) {
+                (Ok(cert), Ok(key)) => (cert, key),
+                (Err(_), Err(_)) => return Ok(None),
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        concat!(#CERT_ENV, " and ", #KEY_ENV, " must be set together"),
+                    ))
+                }
+            };
+            let identity = Identity::from_pem(std::fs::read(cert)?, std::fs::read(key)?);
+            Ok(Some(ServerTlsConfig::new().identity(identity)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_client() {
+        let code = generate_client().to_string();
+        assert!(code.contains("with_webpki_roots"));
+        assert!(code.contains("connect_lazy"));
+        assert!(!code.contains("openssl"));
+    }
+
+    #[test]
+    fn test_generate_server() {
+        let code = generate_server().to_string();
+        assert!(code.contains("\"GRPC_TLS_CERT\""));
+        assert!(code.contains("\"GRPC_TLS_KEY\""));
+        assert!(code.contains("ServerTlsConfig"));
+    }
+}
@@ -0,0 +1,361 @@
+//! Rows written by gRPC write RPCs
+//!
+//! Cache invalidation runs once a create, update, upsert, restore or delete
+//! RPC succeeds, for each row it wrote. Bulk updates, bulk deletes and
+//! imports report the rows whose keys their response lists in `ids`.
+//! Custom storage methods that write (a write operation, or a GraphQL
+//! mutation) report the entity in their response, the keys in `ids`, or the
+//! request's `id`. Writes whose keys are unknown report the whole entity.
+//! This module finds those rows and wraps an RPC's body to act on them.
+
+use crate::storage::defaults::{infer_entity_name, infer_operation, written_ids_field};
+use crate::storage::seaorm::options::{
+    get_cached_entity_options_in_package, get_cached_graphql_mutation_options, get_cached_rpc_method_options,
+};
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::field_descriptor_proto::Label;
+use prost_types::{FileDescriptorProto, MethodDescriptorProto};
+use quote::{format_ident, quote};
+
+/// The rows a write RPC reports after it succeeds
+pub(super) struct WrittenRows {
+    /// Proto message name of the entity
+    pub entity: String,
+    /// `created`, `updated` or `deleted`
+    pub kind: &'static str,
+    /// Where the written keys come from
+    source: KeySource,
+}
+
+/// Location of the keys of the rows an RPC wrote
+enum KeySource {
+    /// The request's `id`, read before the request is consumed
+    RequestId,
+    /// A singular response field holding the entity
+    Field(String),
+    /// A repeated response field holding the entities
+    Repeated(String),
+    /// A repeated response field holding the keys, filled by bulk writes
+    Ids(String),
+    /// Unknown keys: any row of the entity may have changed
+    All,
+}
+
+/// The rows an RPC writes, if it writes an entity of the file's package
+pub(super) fn written_rows(
+    file: &FileDescriptorProto,
+    service_name: &str,
+    method: &MethodDescriptorProto,
+) -> Option<WrittenRows> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let package = file.package.as_deref().unwrap_or("");
+    let method_name = method.name.as_deref().unwrap_or("");
+    let method_options = get_cached_rpc_method_options(file_name, service_name, method_name);
+    let operation = method_options
+        .as_ref()
+        .filter(|o| !o.operation.is_empty())
+        .map(|o| o.operation.as_str())
+        .unwrap_or_else(|| infer_operation(method_name));
+    let custom = method_options.as_ref().is_some_and(|o| o.custom);
+    let kind = match operation {
+        "create" | "create_many" | "import" => "created",
+        "update" | "upsert" | "restore" | "update_many" => "updated",
+        "delete" | "delete_many" => "deleted",
+        // A hand-written method exposed as a mutation changes rows somehow
+        _ if custom && get_cached_graphql_mutation_options(file_name, service_name, method_name).is_some() => {
+            "updated"
+        }
+        _ => return None,
+    };
+
+    let find_message = |type_name: Option<&str>| {
+        let name = type_name?.rsplit('.').next()?;
+        file.message_type.iter().find(|m| m.name.as_deref() == Some(name))
+    };
+    let is_entity = |name: &str| get_cached_entity_options_in_package(package, name).is_some_and(|o| !o.skip);
+    let response = find_message(method.output_type.as_deref());
+
+    // Custom methods named after no entity write the one their response holds
+    let named = method_options
+        .as_ref()
+        .filter(|o| !o.entity_name.is_empty())
+        .map(|o| o.entity_name.clone())
+        .unwrap_or_else(|| infer_entity_name(method_name))
+        .to_upper_camel_case();
+    let package_prefix = format!(".{}.", package);
+    let entity = if is_entity(&named) {
+        named
+    } else if custom {
+        response?
+            .field
+            .iter()
+            .filter_map(|f| f.type_name.as_deref()?.strip_prefix(package_prefix.as_str()))
+            .find(|name| is_entity(name))?
+            .to_string()
+    } else {
+        return None;
+    };
+
+    let entity_type = format!("{}{}", package_prefix, entity);
+    let entity_field = response
+        .and_then(|r| r.field.iter().find(|f| f.type_name.as_deref() == Some(entity_type.as_str())));
+    let ids = response
+        .and_then(|r| r.name.as_deref())
+        .and_then(|name| written_ids_field(file, name));
+    let request_id = find_message(method.input_type.as_deref())
+        .is_some_and(|r| r.field.iter().any(|f| f.name.as_deref() == Some("id")));
+
+    let source = if matches!(operation, "update_many" | "delete_many" | "import") {
+        ids.map(KeySource::Ids).unwrap_or(KeySource::All)
+    } else if kind == "deleted" && request_id {
+        KeySource::RequestId
+    } else if let Some(field) = entity_field {
+        let name = field.name.as_deref().unwrap_or("").to_snake_case();
+        if field.label() == Label::Repeated {
+            KeySource::Repeated(name)
+        } else {
+            KeySource::Field(name)
+        }
+    } else if !custom {
+        return None;
+    } else if let Some(ids) = ids {
+        KeySource::Ids(ids)
+    } else if request_id {
+        KeySource::RequestId
+    } else {
+        KeySource::All
+    };
+
+    Some(WrittenRows { entity, kind, source })
+}
+
+/// A service's hook on the keys an RPC wrote
+pub(super) struct WriteHook {
+    /// Optional service field holding the hook, bound by that name in `notify`
+    pub field: &'static str,
+    /// Statement acting on one key, given an expression of it as a `String`
+    pub notify: Box<dyn Fn(TokenStream) -> TokenStream>,
+    /// Statement acting on every row of the entity, when the keys are unknown
+    pub notify_all: TokenStream,
+}
+
+/// Wrap an RPC's body to run each hook for every written key once it succeeds
+///
+/// `body` evaluates to the RPC's `Result<Response<_>, Status>`. A hook runs
+/// only when its service field is set. Dry-run writes roll back, so they run
+/// no hooks.
+pub(super) fn wrap_body(
+    file: &FileDescriptorProto,
+    service_name: &str,
+    method: &MethodDescriptorProto,
+    body: TokenStream,
+    hooks: Vec<WriteHook>,
+) -> TokenStream {
+    let rows = match written_rows(file, service_name, method) {
+        Some(rows) if !hooks.is_empty() => rows,
+        _ => return body,
+    };
+    let file_name = file.name.as_deref().unwrap_or("");
+    let method_name = method.name.as_deref().unwrap_or("");
+    let live = if crate::graphql::is_dry_run(file_name, service_name, method_name) {
+        quote! { .filter(|_| !dry_run()) }
+    } else {
+        quote! {}
+    };
+
+    let mut captures = Vec::new();
+    let mut calls = Vec::new();
+    for hook in &hooks {
+        let field = format_ident!("{}", hook.field);
+        let statements = match &rows.source {
+            KeySource::RequestId => {
+                let key = format_ident!("{}_key", hook.field);
+                captures.push(quote! { let #key = request.get_ref().id.to_string(); });
+                (hook.notify)(quote! { #key })
+            }
+            KeySource::Field(name) => {
+                let name = format_ident!("{}", name);
+                let statement = (hook.notify)(quote! { row.id.to_string() });
+                quote! {
+                    if let Some(row) = &response.get_ref().#name {
+                        #statement
+                    }
+                }
+            }
+            KeySource::Repeated(name) => {
+                let name = format_ident!("{}", name);
+                let statement = (hook.notify)(quote! { row.id.to_string() });
+                quote! {
+                    for row in &response.get_ref().#name {
+                        #statement
+                    }
+                }
+            }
+            KeySource::Ids(name) => {
+                let name = format_ident!("{}", name);
+                let statement = (hook.notify)(quote! { key.clone() });
+                quote! {
+                    for key in &response.get_ref().#name {
+                        #statement
+                    }
+                }
+            }
+            KeySource::All => hook.notify_all.clone(),
+        };
+        calls.push(quote! {
+            if let Some(#field) = self.#field.as_ref() #live {
+                #statements
+            }
+        });
+    }
+
+    quote! {
+        #(#captures)*
+        let response = { #body }?;
+        #(#calls)*
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_body_without_hooks() {
+        let file = FileDescriptorProto::default();
+        let method = MethodDescriptorProto {
+            name: Some("CreatePost".to_string()),
+            ..Default::default()
+        };
+        let body = quote! { self.storage.create_post(request.into_inner()).await };
+        let wrapped = wrap_body(&file, "PostService", &method, body.clone(), Vec::new());
+        assert_eq!(wrapped.to_string(), body.to_string());
+    }
+
+    #[test]
+    fn test_bulk_writes_report_response_ids() {
+        use crate::storage::seaorm::options::{cache_entity_options, cache_file_package, storage};
+        use prost_types::{DescriptorProto, FieldDescriptorProto};
+
+        let file_name = "writes_bulk/services.proto";
+        cache_file_package(file_name, "writes_bulk");
+        cache_entity_options(file_name, "Post", storage::EntityOptions::default());
+        let ids = FieldDescriptorProto {
+            name: Some("ids".to_string()),
+            number: Some(2),
+            label: Some(Label::Repeated as i32),
+            r#type: Some(prost_types::field_descriptor_proto::Type::String as i32),
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some(file_name.to_string()),
+            package: Some("writes_bulk".to_string()),
+            message_type: vec![
+                DescriptorProto { name: Some("Post".to_string()), ..Default::default() },
+                DescriptorProto {
+                    name: Some("DeleteManyPostsResponse".to_string()),
+                    field: vec![ids],
+                    ..Default::default()
+                },
+                DescriptorProto { name: Some("UpdateManyPostsResponse".to_string()), ..Default::default() },
+            ],
+            ..Default::default()
+        };
+        let method = |name: &str, output: &str| MethodDescriptorProto {
+            name: Some(name.to_string()),
+            output_type: Some(format!(".writes_bulk.{}", output)),
+            ..Default::default()
+        };
+
+        let hook = || WriteHook {
+            field: "invalidation",
+            notify: Box::new(|key| quote! { invalidation.invalidate("Post", &#key).await; }),
+            notify_all: quote! { invalidation.invalidate_all("Post").await; },
+        };
+        let delete_many = method("DeleteManyPosts", "DeleteManyPostsResponse");
+        let rows = written_rows(&file, "PostService", &delete_many).unwrap();
+        assert_eq!((rows.entity.as_str(), rows.kind), ("Post", "deleted"));
+        let wrapped = wrap_body(&file, "PostService", &delete_many, quote! { body }, vec![hook()]).to_string();
+        assert!(wrapped.contains("for key in & response . get_ref () . ids { invalidation . invalidate (\"Post\" , & key . clone ()) . await ; }"));
+
+        // Without `ids` in the response any row may have changed
+        let update_many = method("UpdateManyPosts", "UpdateManyPostsResponse");
+        let rows = written_rows(&file, "PostService", &update_many).unwrap();
+        assert!(matches!(rows.source, KeySource::All));
+        let wrapped = wrap_body(&file, "PostService", &update_many, quote! { body }, vec![hook()]).to_string();
+        assert!(wrapped.contains("{ invalidation . invalidate_all (\"Post\") . await ; }"));
+    }
+
+    #[test]
+    fn test_custom_writes_report_rows() {
+        use crate::storage::seaorm::options::{
+            cache_entity_options, cache_file_package, cache_graphql_mutation_options, cache_rpc_method_options,
+            graphql, storage,
+        };
+        use crate::test_util::{field, message_field};
+        use prost_types::field_descriptor_proto::Type;
+        use prost_types::{DescriptorProto, FieldDescriptorProto};
+
+        let file_name = "writes_custom/services.proto";
+        cache_file_package(file_name, "writes_custom");
+        cache_entity_options(file_name, "Post", storage::EntityOptions::default());
+        let message = |name: &str, fields: Vec<FieldDescriptorProto>| DescriptorProto {
+            name: Some(name.to_string()),
+            field: fields,
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some(file_name.to_string()),
+            package: Some("writes_custom".to_string()),
+            message_type: vec![
+                message("Post", vec![field("id", 1, Type::Int64)]),
+                message("PublishPostRequest", vec![field("id", 1, Type::Int64)]),
+                message("PublishPostResponse", vec![message_field("post", 1, ".writes_custom.Post")]),
+                message("ArchiveStaleRequest", vec![field("days", 1, Type::Int32)]),
+                message("ArchiveStaleResponse", vec![field("archived", 1, Type::Int64)]),
+                message("CountPostsResponse", vec![field("count", 1, Type::Int64)]),
+            ],
+            ..Default::default()
+        };
+        let method = |name: &str, input: &str, output: &str| MethodDescriptorProto {
+            name: Some(name.to_string()),
+            input_type: Some(format!(".writes_custom.{}", input)),
+            output_type: Some(format!(".writes_custom.{}", output)),
+            ..Default::default()
+        };
+        let custom = |method_name: &str, entity_name: &str| {
+            cache_rpc_method_options(
+                file_name,
+                "PostService",
+                method_name,
+                storage::MethodOptions {
+                    custom: true,
+                    entity_name: entity_name.to_string(),
+                    ..Default::default()
+                },
+            );
+        };
+
+        // The response holds the written entity
+        custom("PublishPost", "");
+        cache_graphql_mutation_options(file_name, "PostService", "PublishPost", graphql::MutationOptions::default());
+        let publish = method("PublishPost", "PublishPostRequest", "PublishPostResponse");
+        let rows = written_rows(&file, "PostService", &publish).unwrap();
+        assert_eq!((rows.entity.as_str(), rows.kind), ("Post", "updated"));
+        assert!(matches!(rows.source, KeySource::Field(ref name) if name == "post"));
+
+        // Neither response nor request holds keys
+        custom("ArchiveStale", "Post");
+        cache_graphql_mutation_options(file_name, "PostService", "ArchiveStale", graphql::MutationOptions::default());
+        let archive = method("ArchiveStale", "ArchiveStaleRequest", "ArchiveStaleResponse");
+        let rows = written_rows(&file, "PostService", &archive).unwrap();
+        assert!(matches!(rows.source, KeySource::All));
+
+        // Custom reads are not writes
+        custom("CountPosts", "Post");
+        let count = method("CountPosts", "ArchiveStaleRequest", "CountPostsResponse");
+        assert!(written_rows(&file, "PostService", &count).is_none());
+    }
+}
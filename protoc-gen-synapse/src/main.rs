@@ -5,6 +5,32 @@
 //! Usage:
 //!   protoc --synapse_out=backend=seaorm:./gen proto/*.proto
 //!   protoc --synapse_out=backend=ecto:./gen proto/*.proto
+//!
+//! Parameters:
+//!   time_crate=time   emit `time::OffsetDateTime` timestamps instead of chrono
+//!   tls=rustls        emit rustls-only TLS helpers for gRPC channels and servers
+//!   minimal=true      skip formatting and avoid base64/base62/chrono in generated code
+//!   graphql_timestamps=hidden  leave created_at/updated_at off GraphQL object types by default
+//!   graphql_foreign_keys=hidden  leave belongs-to foreign key columns off GraphQL object types by default
+//!   graphql_names=explicit  name every GraphQL field explicitly and write <package>/names.json
+//!   migrations=true   emit <package>/migration.rs creating each package's tables
+//!   smoke=true        emit a per-package smoke test calling each RPC against an endpoint
+//!   chaos=true        emit a fault-injection layer for resolver gRPC clients (test/staging)
+//!   schema_diff=true  emit a check diffing a live Postgres schema against the entities
+//!   record_replay=true  emit record/replay of resolver gRPC calls for gateway tests
+//!   factories=true    emit per-entity test factories that insert rows with fake values
+//!   lint=true         check annotations and report problems as protoc errors; emit no code
+//!   baseline=api.binpb  fail on breaking entity/RPC changes against a descriptor set
+//!   graphql_snapshot=schema  emit a test failing on breaking changes to schema/<package>.graphql
+//!   emit_ir=json      write <package>/ir.json (entities, enums, methods, resolved options); emit no code
+//!   diff=src/generated  write synapse.diff comparing the output with that directory; overwrite nothing
+//!   report=true       add <package>/synapse-report.json with per-stage/per-generator timing, output sizes, cache stats
+//!   graphql_serde=true  derive serde and schemars JsonSchema on GraphQL input and filter types
+//!   federation=true   build the GraphQL schema as an Apollo Federation subgraph
+//!   subscriptions=true  stream emit_events entity changes through GraphQL subscriptions
+//!   app_error=anyhow::Error  emit storage::AppError/AppResult aliases over that error type
+//!   health=true       emit grpc/server.rs serving each package's services with tonic-health reporting
+//!   strict_layout=true  fail unless each proto file lives in its package's directory (e.g. blog/v1/*.proto)
 
 #![deny(warnings)]
 #![deny(missing_docs)]
@@ -18,7 +44,10 @@ mod error;
 mod graphql;
 mod grpc;
 pub mod options;
+mod params;
 mod storage;
+#[cfg(test)]
+mod test_util;
 mod validate;
 
 pub use error::GeneratorError;
@@ -0,0 +1,639 @@
+//! Generator parameters
+//!
+//! Parsed from the protoc plugin parameter string, e.g.
+//! `--synapse_out=backend=seaorm,time_crate=time:./gen`, and cached for the
+//! rest of the run so individual generators don't need them threaded through.
+
+use once_cell::sync::Lazy;
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+/// Global cache of the parsed parameters
+static PARAMS: Lazy<RwLock<GeneratorParams>> = Lazy::new(|| RwLock::new(GeneratorParams::default()));
+
+/// Parameters that change the shape of generated code
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeneratorParams {
+    /// Date/time crate used for timestamp columns and conversions
+    pub time_crate: TimeCrate,
+    /// Emit rustls-backed TLS helpers for gRPC channels and servers
+    pub rustls: bool,
+    /// Skip output formatting and avoid base64/base62/chrono in generated code
+    pub minimal: bool,
+    /// Leave `created_at`/`updated_at` off GraphQL object types by default
+    pub graphql_hide_timestamps: bool,
+    /// Leave belongs-to foreign key columns off GraphQL object types by default
+    pub graphql_hide_foreign_keys: bool,
+    /// Name every GraphQL field explicitly and report the proto/GraphQL/column name map
+    pub graphql_explicit_names: bool,
+    /// Emit a SeaORM migration creating each package's tables
+    pub migrations: bool,
+    /// Emit a post-deploy smoke test calling each package's RPCs
+    pub smoke: bool,
+    /// Emit a fault-injection layer for the resolvers' gRPC clients
+    pub chaos: bool,
+    /// Emit a check diffing a live Postgres schema against the entities
+    pub schema_diff: bool,
+    /// Emit record/replay of the resolvers' gRPC calls
+    pub record_replay: bool,
+    /// Emit test factories inserting entities with fake values
+    pub factories: bool,
+    /// Check annotations and report problems instead of generating code
+    pub lint: bool,
+    /// Descriptor set to check for breaking changes before generating
+    pub baseline: Option<String>,
+    /// Directory of committed GraphQL SDL snapshots checked by a generated test
+    pub graphql_snapshot: Option<String>,
+    /// Dump the resolved annotation model as JSON instead of generating code
+    pub emit_ir: bool,
+    /// Directory of existing output to diff against instead of writing files
+    pub diff: Option<String>,
+    /// Add a JSON report of generator timing, output sizes, and cache stats
+    pub report: bool,
+    /// Derive serde and schemars JsonSchema on GraphQL input and filter types
+    pub graphql_serde: bool,
+    /// Build the GraphQL schema as an Apollo Federation subgraph
+    pub federation: bool,
+    /// Stream `emit_events` entity changes through GraphQL subscriptions
+    pub subscriptions: bool,
+    /// Application error type behind the generated `AppError` and `AppResult` aliases
+    pub app_error: Option<String>,
+    /// Fail generation when a proto file's directory doesn't match its package
+    pub strict_layout: bool,
+    /// Emit a gRPC server builder serving each package's services with health reporting
+    pub health: bool,
+}
+
+/// Date/time crate backing generated timestamp handling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeCrate {
+    /// `chrono` (`DateTime<Utc>`), the default
+    #[default]
+    Chrono,
+    /// `time` (`OffsetDateTime`)
+    Time,
+}
+
+impl GeneratorParams {
+    /// Parse a comma-separated `key=value` parameter string
+    ///
+    /// Unknown keys are ignored so backend-specific parameters can coexist.
+    pub fn parse(param: &str) -> Self {
+        let mut params = Self::default();
+        for part in param.split(',') {
+            if let Some(value) = part.trim().strip_prefix("time_crate=") {
+                params.time_crate = match value {
+                    "time" => TimeCrate::Time,
+                    _ => TimeCrate::Chrono,
+                };
+            } else if let Some(value) = part.trim().strip_prefix("tls=") {
+                params.rustls = value == "rustls";
+            } else if let Some(value) = part.trim().strip_prefix("minimal=") {
+                params.minimal = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("graphql_timestamps=") {
+                params.graphql_hide_timestamps = value == "hidden";
+            } else if let Some(value) = part.trim().strip_prefix("graphql_foreign_keys=") {
+                params.graphql_hide_foreign_keys = value == "hidden";
+            } else if let Some(value) = part.trim().strip_prefix("graphql_names=") {
+                params.graphql_explicit_names = value == "explicit";
+            } else if let Some(value) = part.trim().strip_prefix("migrations=") {
+                params.migrations = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("smoke=") {
+                params.smoke = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("chaos=") {
+                params.chaos = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("schema_diff=") {
+                params.schema_diff = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("record_replay=") {
+                params.record_replay = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("factories=") {
+                params.factories = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("lint=") {
+                params.lint = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("baseline=") {
+                params.baseline = (!value.is_empty()).then(|| value.to_string());
+            } else if let Some(value) = part.trim().strip_prefix("graphql_snapshot=") {
+                params.graphql_snapshot = (!value.is_empty()).then(|| value.to_string());
+            } else if let Some(value) = part.trim().strip_prefix("emit_ir=") {
+                params.emit_ir = value == "json";
+            } else if let Some(value) = part.trim().strip_prefix("diff=") {
+                params.diff = (!value.is_empty()).then(|| value.to_string());
+            } else if let Some(value) = part.trim().strip_prefix("report=") {
+                params.report = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("graphql_serde=") {
+                params.graphql_serde = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("federation=") {
+                params.federation = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("subscriptions=") {
+                params.subscriptions = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("app_error=") {
+                params.app_error = (!value.is_empty()).then(|| value.to_string());
+            } else if let Some(value) = part.trim().strip_prefix("strict_layout=") {
+                params.strict_layout = value == "true";
+            } else if let Some(value) = part.trim().strip_prefix("health=") {
+                params.health = value == "true";
+            }
+        }
+        params
+    }
+}
+
+/// Cache parameters for the current run
+pub fn set_params(params: GeneratorParams) {
+    *PARAMS.write().unwrap() = params;
+}
+
+/// Date/time crate selected for the current run
+pub fn time_crate() -> TimeCrate {
+    PARAMS.read().unwrap().time_crate
+}
+
+/// Whether rustls TLS helpers are generated for the current run
+pub fn rustls() -> bool {
+    PARAMS.read().unwrap().rustls
+}
+
+/// Whether the minimal-dependency output profile is selected
+pub fn minimal() -> bool {
+    PARAMS.read().unwrap().minimal
+}
+
+/// Whether GraphQL object types hide managed timestamps by default
+pub fn graphql_hide_timestamps() -> bool {
+    PARAMS.read().unwrap().graphql_hide_timestamps
+}
+
+/// Whether GraphQL object types hide foreign key columns by default
+pub fn graphql_hide_foreign_keys() -> bool {
+    PARAMS.read().unwrap().graphql_hide_foreign_keys
+}
+
+/// Whether GraphQL field names are spelled out for the current run
+pub fn graphql_explicit_names() -> bool {
+    PARAMS.read().unwrap().graphql_explicit_names
+}
+
+/// Whether package migrations are generated for the current run
+pub fn migrations() -> bool {
+    PARAMS.read().unwrap().migrations
+}
+
+/// Whether package smoke tests are generated for the current run
+pub fn smoke() -> bool {
+    PARAMS.read().unwrap().smoke
+}
+
+/// Whether the fault-injection layer is generated for the current run
+pub fn chaos() -> bool {
+    PARAMS.read().unwrap().chaos
+}
+
+/// Whether the live schema diff is generated for the current run
+pub fn schema_diff() -> bool {
+    PARAMS.read().unwrap().schema_diff
+}
+
+/// Whether record/replay of resolver gRPC calls is generated for the current run
+pub fn record_replay() -> bool {
+    PARAMS.read().unwrap().record_replay
+}
+
+/// Whether entity test factories are generated for the current run
+pub fn factories() -> bool {
+    PARAMS.read().unwrap().factories
+}
+
+/// Whether the current run only lints annotations
+pub fn lint() -> bool {
+    PARAMS.read().unwrap().lint
+}
+
+/// Baseline descriptor set the current run is checked against
+pub fn baseline() -> Option<String> {
+    PARAMS.read().unwrap().baseline.clone()
+}
+
+/// Directory of the GraphQL SDL snapshots, relative to the crate root
+pub fn graphql_snapshot() -> Option<String> {
+    PARAMS.read().unwrap().graphql_snapshot.clone()
+}
+
+/// Whether the current run dumps the IR as JSON instead of generating code
+pub fn emit_ir() -> bool {
+    PARAMS.read().unwrap().emit_ir
+}
+
+/// Directory of existing output the current run is diffed against
+pub fn diff() -> Option<String> {
+    PARAMS.read().unwrap().diff.clone()
+}
+
+/// Whether the current run adds a generation report
+pub fn report() -> bool {
+    PARAMS.read().unwrap().report
+}
+
+/// Whether GraphQL input types derive serde and schemars for the current run
+pub fn graphql_serde() -> bool {
+    PARAMS.read().unwrap().graphql_serde
+}
+
+/// Whether the GraphQL schema is a federation subgraph for the current run
+pub fn federation() -> bool {
+    PARAMS.read().unwrap().federation
+}
+
+/// Whether `emit_events` entities get GraphQL subscriptions for the current run
+pub fn subscriptions() -> bool {
+    PARAMS.read().unwrap().subscriptions
+}
+
+/// Application error type the storage module aliases, if any, for the current run
+pub fn app_error() -> Option<String> {
+    PARAMS.read().unwrap().app_error.clone()
+}
+
+/// Whether proto files must live in their package's directory for the current run
+pub fn strict_layout() -> bool {
+    PARAMS.read().unwrap().strict_layout
+}
+
+/// Whether package gRPC servers with health reporting are generated for the current run
+pub fn health() -> bool {
+    PARAMS.read().unwrap().health
+}
+
+/// Format generated code with prettyplease, unless running in minimal mode
+///
+/// Falls back to the raw token string if the code doesn't parse.
+pub fn format_code(content: String) -> String {
+    if minimal() {
+        return content;
+    }
+    let started = std::time::Instant::now();
+    let formatted = match syn::parse_file(&content) {
+        Ok(parsed) => prettyplease::unparse(&parsed),
+        Err(_) => content,
+    };
+    FORMATTING_NANOS.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    formatted
+}
+
+/// Total time spent in [`format_code`] so far, for the generation report
+static FORMATTING_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Time spent formatting generated code so far
+pub fn formatting_time() -> std::time::Duration {
+    std::time::Duration::from_nanos(FORMATTING_NANOS.load(Ordering::Relaxed))
+}
+
+impl TimeCrate {
+    /// SeaORM prelude type for timestamp columns
+    pub fn datetime_type(self) -> &'static str {
+        match self {
+            TimeCrate::Chrono => "DateTimeUtc",
+            TimeCrate::Time => "TimeDateTimeWithTimeZone",
+        }
+    }
+
+    /// SeaORM prelude type for timestamp columns without a time zone
+    pub fn naive_datetime_type(self) -> &'static str {
+        match self {
+            TimeCrate::Chrono => "DateTime",
+            TimeCrate::Time => "TimeDateTime",
+        }
+    }
+
+    /// Expression for the current UTC time
+    pub fn now(self) -> TokenStream {
+        match self {
+            TimeCrate::Chrono => quote! { ChronoUtc::now() },
+            TimeCrate::Time => quote! { TimeDateTimeWithTimeZone::now_utc() },
+        }
+    }
+
+    /// Convert a timestamp column value into a `prost_types::Timestamp`
+    pub fn to_proto(self, value: TokenStream) -> TokenStream {
+        match self {
+            TimeCrate::Chrono => quote! {
+                prost_types::Timestamp {
+                    seconds: #value.timestamp(),
+                    nanos: #value.timestamp_subsec_nanos() as i32,
+                }
+            },
+            TimeCrate::Time => quote! {
+                prost_types::Timestamp {
+                    seconds: #value.unix_timestamp(),
+                    nanos: #value.nanosecond() as i32,
+                }
+            },
+        }
+    }
+
+    /// Convert a naive timestamp column value (UTC wall time) into a
+    /// `prost_types::Timestamp`
+    pub fn naive_to_proto(self, value: TokenStream) -> TokenStream {
+        match self {
+            TimeCrate::Chrono => self.to_proto(quote! { #value.and_utc() }),
+            TimeCrate::Time => self.to_proto(quote! { #value.assume_utc() }),
+        }
+    }
+
+    /// Convert a `prost_types::Timestamp` named `t` into an `Option` of the
+    /// column type (naive columns get the UTC wall time)
+    ///
+    /// Chrono is reached through SeaORM's re-export, so storage code doesn't
+    /// depend on the `chrono` crate directly.
+    pub fn proto_to_column(self, naive: bool) -> TokenStream {
+        match (self, naive) {
+            (TimeCrate::Chrono, false) => quote! {
+                sea_orm::prelude::ChronoDateTimeUtc::from_timestamp(t.seconds, t.nanos as u32)
+            },
+            (TimeCrate::Chrono, true) => quote! {
+                sea_orm::prelude::ChronoDateTimeUtc::from_timestamp(t.seconds, t.nanos as u32)
+                    .map(|dt| dt.naive_utc())
+            },
+            (TimeCrate::Time, false) => quote! {
+                time::OffsetDateTime::from_unix_timestamp_nanos(
+                    t.seconds as i128 * 1_000_000_000 + t.nanos as i128,
+                )
+                .ok()
+            },
+            (TimeCrate::Time, true) => quote! {
+                time::OffsetDateTime::from_unix_timestamp_nanos(
+                    t.seconds as i128 * 1_000_000_000 + t.nanos as i128,
+                )
+                .ok()
+                .map(|dt| time::PrimitiveDateTime::new(dt.date(), dt.time()))
+            },
+        }
+    }
+
+    /// Convert a `prost_types::Timestamp` named `t` into an RFC 3339 string
+    ///
+    /// Like `proto_to_column`, chrono is reached through SeaORM's re-export.
+    pub fn proto_to_rfc3339(self) -> TokenStream {
+        match self {
+            TimeCrate::Chrono => quote! {
+                sea_orm::prelude::ChronoDateTimeUtc::from_timestamp(t.seconds, t.nanos as u32)
+                    .map(|dt| dt.to_rfc3339())
+                    .unwrap_or_default()
+            },
+            TimeCrate::Time => quote! {
+                time::OffsetDateTime::from_unix_timestamp_nanos(
+                    t.seconds as i128 * 1_000_000_000 + t.nanos as i128,
+                )
+                .ok()
+                .and_then(|dt| dt.format(&time::format_description::well_known::Rfc3339).ok())
+                .unwrap_or_default()
+            },
+        }
+    }
+
+    /// Convert a `prost_types::Timestamp` named `t` into an RFC 2822 string
+    pub fn proto_to_rfc2822(self) -> TokenStream {
+        match self {
+            TimeCrate::Chrono => quote! {
+                sea_orm::prelude::ChronoDateTimeUtc::from_timestamp(t.seconds, t.nanos as u32)
+                    .map(|dt| dt.to_rfc2822())
+                    .unwrap_or_default()
+            },
+            TimeCrate::Time => quote! {
+                time::OffsetDateTime::from_unix_timestamp_nanos(
+                    t.seconds as i128 * 1_000_000_000 + t.nanos as i128,
+                )
+                .ok()
+                .and_then(|dt| dt.format(&time::format_description::well_known::Rfc2822).ok())
+                .unwrap_or_default()
+            },
+        }
+    }
+}
+
+/// Convert a `prost_types::Timestamp` named `t` into an RFC 3339 string
+/// using only std (days-to-civil conversion), for minimal mode
+pub fn std_rfc3339() -> TokenStream {
+    quote! {{
+        let days = t.seconds.div_euclid(86_400);
+        let secs = t.seconds.rem_euclid(86_400);
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60,
+            t.nanos.max(0)
+        )
+    }}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_defaults_to_chrono() {
+        assert_eq!(GeneratorParams::parse("").time_crate, TimeCrate::Chrono);
+        assert_eq!(GeneratorParams::parse("backend=seaorm").time_crate, TimeCrate::Chrono);
+    }
+
+    #[test]
+    fn test_parse_time_crate() {
+        let params = GeneratorParams::parse("backend=seaorm,time_crate=time");
+        assert_eq!(params.time_crate, TimeCrate::Time);
+        assert_eq!(params.time_crate.datetime_type(), "TimeDateTimeWithTimeZone");
+    }
+
+    #[test]
+    fn test_parse_tls() {
+        assert!(!GeneratorParams::parse("time_crate=time").rustls);
+        assert!(GeneratorParams::parse("time_crate=time,tls=rustls").rustls);
+    }
+
+    #[test]
+    fn test_parse_minimal() {
+        assert!(!GeneratorParams::parse("").minimal);
+        assert!(GeneratorParams::parse("minimal=true").minimal);
+        assert!(!GeneratorParams::parse("minimal=false").minimal);
+    }
+
+    #[test]
+    fn test_parse_graphql_visibility() {
+        let params = GeneratorParams::parse("graphql_timestamps=hidden,graphql_foreign_keys=hidden");
+        assert!(params.graphql_hide_timestamps);
+        assert!(params.graphql_hide_foreign_keys);
+        let params = GeneratorParams::parse("graphql_timestamps=exposed");
+        assert!(!params.graphql_hide_timestamps);
+        assert!(!params.graphql_hide_foreign_keys);
+    }
+
+    #[test]
+    fn test_parse_migrations() {
+        assert!(GeneratorParams::parse("migrations=true").migrations);
+        assert!(!GeneratorParams::parse("backend=seaorm").migrations);
+    }
+
+    #[test]
+    fn test_parse_smoke() {
+        assert!(GeneratorParams::parse("migrations=true,smoke=true").smoke);
+        assert!(!GeneratorParams::parse("migrations=true").smoke);
+    }
+
+    #[test]
+    fn test_parse_chaos() {
+        assert!(GeneratorParams::parse("chaos=true").chaos);
+        assert!(!GeneratorParams::parse("smoke=true").chaos);
+    }
+
+    #[test]
+    fn test_parse_schema_diff() {
+        assert!(GeneratorParams::parse("migrations=true,schema_diff=true").schema_diff);
+        assert!(!GeneratorParams::parse("migrations=true").schema_diff);
+    }
+
+    #[test]
+    fn test_parse_record_replay() {
+        assert!(GeneratorParams::parse("record_replay=true").record_replay);
+        assert!(!GeneratorParams::parse("chaos=true").record_replay);
+    }
+
+    #[test]
+    fn test_parse_federation() {
+        assert!(GeneratorParams::parse("backend=seaorm,federation=true").federation);
+        assert!(!GeneratorParams::parse("backend=seaorm").federation);
+    }
+
+    #[test]
+    fn test_parse_subscriptions() {
+        assert!(GeneratorParams::parse("backend=seaorm,subscriptions=true").subscriptions);
+        assert!(!GeneratorParams::parse("backend=seaorm").subscriptions);
+    }
+
+    #[test]
+    fn test_parse_strict_layout() {
+        assert!(GeneratorParams::parse("backend=seaorm,strict_layout=true").strict_layout);
+        assert!(!GeneratorParams::parse("backend=seaorm").strict_layout);
+    }
+
+    #[test]
+    fn test_parse_graphql_names() {
+        assert!(GeneratorParams::parse("graphql_names=explicit").graphql_explicit_names);
+        assert!(!GeneratorParams::parse("graphql_names=implicit").graphql_explicit_names);
+        assert!(!GeneratorParams::parse("backend=seaorm").graphql_explicit_names);
+    }
+
+    #[test]
+    fn test_parse_health() {
+        assert!(GeneratorParams::parse("backend=seaorm,health=true").health);
+        assert!(!GeneratorParams::parse("backend=seaorm").health);
+    }
+
+    #[test]
+    fn test_parse_app_error() {
+        let params = GeneratorParams::parse("backend=seaorm,app_error=anyhow::Error");
+        assert_eq!(params.app_error.as_deref(), Some("anyhow::Error"));
+        assert_eq!(GeneratorParams::parse("backend=seaorm").app_error, None);
+    }
+
+    #[test]
+    fn test_parse_factories() {
+        assert!(GeneratorParams::parse("factories=true").factories);
+        assert!(!GeneratorParams::parse("factories=false").factories);
+    }
+
+    #[test]
+    fn test_parse_lint() {
+        assert!(GeneratorParams::parse("backend=seaorm,lint=true").lint);
+        assert!(!GeneratorParams::parse("backend=seaorm").lint);
+    }
+
+    #[test]
+    fn test_parse_baseline() {
+        let params = GeneratorParams::parse("backend=seaorm,baseline=api.binpb");
+        assert_eq!(params.baseline.as_deref(), Some("api.binpb"));
+        assert_eq!(GeneratorParams::parse("baseline=").baseline, None);
+        assert_eq!(GeneratorParams::parse("backend=seaorm").baseline, None);
+    }
+
+    #[test]
+    fn test_parse_graphql_snapshot() {
+        let params = GeneratorParams::parse("backend=seaorm,graphql_snapshot=schema");
+        assert_eq!(params.graphql_snapshot.as_deref(), Some("schema"));
+        assert_eq!(GeneratorParams::parse("backend=seaorm").graphql_snapshot, None);
+    }
+
+    #[test]
+    fn test_parse_emit_ir() {
+        assert!(GeneratorParams::parse("backend=seaorm,emit_ir=json").emit_ir);
+        assert!(!GeneratorParams::parse("backend=seaorm,emit_ir=yaml").emit_ir);
+        assert!(!GeneratorParams::parse("backend=seaorm").emit_ir);
+    }
+
+    #[test]
+    fn test_parse_report() {
+        assert!(GeneratorParams::parse("backend=seaorm,report=true").report);
+        assert!(!GeneratorParams::parse("backend=seaorm").report);
+    }
+
+    #[test]
+    fn test_parse_graphql_serde() {
+        assert!(GeneratorParams::parse("backend=seaorm,graphql_serde=true").graphql_serde);
+        assert!(!GeneratorParams::parse("backend=seaorm").graphql_serde);
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let params = GeneratorParams::parse("backend=seaorm,diff=src/generated");
+        assert_eq!(params.diff.as_deref(), Some("src/generated"));
+        assert_eq!(GeneratorParams::parse("diff=").diff, None);
+        assert_eq!(GeneratorParams::parse("backend=seaorm").diff, None);
+    }
+
+    #[test]
+    fn test_std_rfc3339_avoids_chrono() {
+        let tokens = std_rfc3339().to_string();
+        assert!(tokens.contains("719_468"));
+        assert!(!tokens.contains("chrono"));
+    }
+
+    #[test]
+    fn test_time_conversions() {
+        let tokens = TimeCrate::Time.to_proto(quote! { model.created_at }).to_string();
+        assert!(tokens.contains("unix_timestamp"));
+        let tokens = TimeCrate::Chrono.now().to_string();
+        assert!(tokens.contains("ChronoUtc"));
+    }
+
+    #[test]
+    fn test_naive_conversions() {
+        assert_eq!(TimeCrate::Chrono.naive_datetime_type(), "DateTime");
+        assert_eq!(TimeCrate::Time.naive_datetime_type(), "TimeDateTime");
+        let tokens = TimeCrate::Chrono.naive_to_proto(quote! { model.starts_at }).to_string();
+        assert!(tokens.contains("model . starts_at . and_utc () . timestamp ()"));
+        let tokens = TimeCrate::Time.naive_to_proto(quote! { model.starts_at }).to_string();
+        assert!(tokens.contains("model . starts_at . assume_utc () . unix_timestamp ()"));
+        let tokens = TimeCrate::Chrono.proto_to_column(true).to_string();
+        assert!(tokens.contains("naive_utc"));
+        assert!(!tokens.contains("chrono ::"));
+        let tokens = TimeCrate::Time.proto_to_column(true).to_string();
+        assert!(tokens.contains("PrimitiveDateTime :: new"));
+        for tokens in [TimeCrate::Chrono.proto_to_rfc3339(), TimeCrate::Chrono.proto_to_rfc2822()] {
+            let tokens = tokens.to_string();
+            assert!(tokens.contains("sea_orm :: prelude :: ChronoDateTimeUtc :: from_timestamp"));
+            assert!(!tokens.contains("chrono ::"));
+        }
+    }
+}
@@ -0,0 +1,540 @@
+//! Cache decorator generation
+//!
+//! Generates a `cached_{service}_storage` module for services with
+//! `cache: true`: `Cached{Service}Storage<S, C>` wraps any implementation of
+//! the storage trait, answers get methods from a `StorageCache` (Redis by
+//! default) with a TTL, and drops an entity's cached gets once a create,
+//! update, upsert, delete or restore of it succeeds, including bulk ones.
+//! Every other method forwards to the wrapped storage.
+
+use super::defaults::{infer_entity_name, infer_operation, repeated_response_field, written_ids_field};
+use super::seaorm::options::{get_cached_rpc_method_options, get_cached_service_options, storage};
+use super::traits::service_methods;
+use crate::error::GeneratorError;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::{FileDescriptorProto, ServiceDescriptorProto};
+use quote::{format_ident, quote};
+
+/// How a storage method uses the cache
+#[derive(Debug, Clone, PartialEq)]
+enum CacheUse {
+    /// Answered from the cache by `request.id` and the rest of the request
+    Get { key_prefix: String },
+    /// Invalidates `request.id` (update, delete, restore)
+    RequestId,
+    /// Invalidates the id of the entity in `response.{field}` (create, upsert)
+    ResponseEntity(String),
+    /// Invalidates the ids of the entities in `response.{field}` (create-many)
+    ResponseEntities(String),
+    /// Invalidates the keys listed in `response.{field}` (update-many, delete-many)
+    ResponseIds(String),
+}
+
+/// A trait method that reads or invalidates the cache
+#[derive(Debug, Clone, PartialEq)]
+struct CachedMethod {
+    /// Storage trait method (e.g. `get_post`)
+    method: String,
+    /// Entity message name
+    entity: String,
+    /// Response message name
+    response: String,
+    cache_use: CacheUse,
+}
+
+/// Collect the methods of a service that use the cache
+///
+/// Writes only invalidate entities that have a cached get; a service without
+/// any get method has nothing to cache. Bulk updates and deletes of a cached
+/// entity must report the keys they wrote in `repeated string ids`.
+fn collect_methods(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Result<Vec<CachedMethod>, GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let service_name = service.name.as_deref().unwrap_or("");
+    let package = file.package.as_deref().unwrap_or("");
+
+    let mut methods = Vec::new();
+    for method in &service.method {
+        let method_name = method.name.as_deref().unwrap_or("");
+        let method_options = get_cached_rpc_method_options(file_name, service_name, method_name);
+        if method_options.as_ref().is_some_and(|o| o.skip || o.custom) || method.server_streaming() {
+            continue;
+        }
+
+        let rust_method = method_options
+            .as_ref()
+            .filter(|o| !o.method_name.is_empty())
+            .map(|o| o.method_name.clone())
+            .unwrap_or_else(|| method_name.to_snake_case());
+        let operation = method_options
+            .as_ref()
+            .filter(|o| !o.operation.is_empty())
+            .map(|o| o.operation.to_snake_case())
+            .unwrap_or_else(|| infer_operation(method_name).to_string());
+        let entity = method_options
+            .as_ref()
+            .filter(|o| !o.entity_name.is_empty())
+            .map(|o| o.entity_name.clone())
+            .unwrap_or_else(|| infer_entity_name(method_name))
+            .to_upper_camel_case();
+        let output = method.output_type.as_deref().unwrap_or("");
+        let response = output.rsplit('.').next().unwrap_or(output).to_string();
+
+        let cache_use = match operation.as_str() {
+            "get" => CacheUse::Get {
+                key_prefix: format!("{}.{}/{}", package, service_name, rust_method),
+            },
+            "update" | "delete" | "restore" => CacheUse::RequestId,
+            "create" | "upsert" => CacheUse::ResponseEntity(entity.to_snake_case()),
+            "create_many" => {
+                CacheUse::ResponseEntities(repeated_response_field(file, &response, &entity).to_string())
+            }
+            "update_many" | "delete_many" => match written_ids_field(file, &response) {
+                Some(field) => CacheUse::ResponseIds(field),
+                None => CacheUse::ResponseIds(String::new()),
+            },
+            _ => continue,
+        };
+        methods.push(CachedMethod {
+            method: rust_method,
+            entity,
+            response,
+            cache_use,
+        });
+    }
+
+    let cached: Vec<String> = methods
+        .iter()
+        .filter(|m| matches!(m.cache_use, CacheUse::Get { .. }))
+        .map(|m| m.entity.clone())
+        .collect();
+    methods.retain(|m| cached.contains(&m.entity));
+
+    if let Some(bulk) = methods.iter().find(|m| m.cache_use == CacheUse::ResponseIds(String::new())) {
+        return Err(GeneratorError::CodeGenError(format!(
+            "{}.{}: {} needs `repeated string ids` so the cache can drop the {} rows it writes",
+            service_name, bulk.method, bulk.response, bulk.entity
+        )));
+    }
+    Ok(methods)
+}
+
+/// Service options when the service gets a cache decorator
+fn cache_options(file: &FileDescriptorProto, service_name: &str) -> Option<storage::ServiceOptions> {
+    get_cached_service_options(file.name.as_deref().unwrap_or(""), service_name)
+        .filter(|o| !o.skip && o.generate_storage && o.cache)
+}
+
+/// Whether a service gets a cache decorator module
+pub fn has_cache(file: &FileDescriptorProto, service_name: &str) -> bool {
+    cache_options(file, service_name).is_some()
+        && file
+            .service
+            .iter()
+            .find(|s| s.name.as_deref() == Some(service_name))
+            .is_some_and(|s| collect_methods(file, s).is_ok_and(|m| !m.is_empty()))
+}
+
+/// Whether any service of the package gets a cache decorator
+pub fn package_has_cache(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> bool {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|f| f.service.iter().any(|s| has_cache(f, s.name.as_deref().unwrap_or(""))))
+}
+
+/// Generate the storage/cached_{service}_storage.rs file for a service
+pub fn generate(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    let service_name = service.name.as_deref().unwrap_or("");
+    if package.is_empty() {
+        return Ok(None);
+    }
+    let Some(service_options) = cache_options(file, service_name) else {
+        return Ok(None);
+    };
+    let cached = collect_methods(file, service)?;
+    if cached.is_empty() {
+        return Ok(None);
+    }
+
+    let trait_name = if service_options.trait_name.is_empty() {
+        format!("{}Storage", service_name)
+    } else {
+        service_options.trait_name.clone()
+    };
+    let trait_ident = format_ident!("{}", trait_name);
+    let trait_module = format_ident!("{}", trait_name.to_snake_case());
+    let cached_ident = format_ident!("Cached{}Storage", service_name.to_upper_camel_case());
+
+    let mut entities: Vec<&str> = cached.iter().map(|m| m.entity.as_str()).collect();
+    entities.sort();
+    entities.dedup();
+    let key_fns = entities.iter().map(|entity| generate_key_fn(entity, &cached));
+    let marker_prefix = format!("{}.{}", package, service_name);
+    let invalidate_fns = entities
+        .iter()
+        .map(|entity| generate_invalidate_fn(entity, &marker_prefix, service_options.read_replica));
+
+    // The wrapper stands in for the storage, so it passes db() through when the trait has it
+    let db_methods = if service_options.generate_implementation {
+        let read_db = if service_options.read_replica {
+            quote! {
+                fn read_db(&self) -> &sea_orm::DatabaseConnection {
+                    self.inner.read_db()
+                }
+            }
+        } else {
+            quote! {}
+        };
+        quote! {
+            fn db(&self) -> &sea_orm::DatabaseConnection {
+                self.inner.db()
+            }
+            #read_db
+        }
+    } else {
+        quote! {}
+    };
+
+    let methods = service_methods(file, service).into_iter().map(|method| {
+        match cached.iter().find(|c| *method.ident() == c.method) {
+            Some(cached) => method.to_item(generate_cached_body(cached, service_options.read_replica)),
+            None => method.to_delegating_item(quote! { self.inner }),
+        }
+    });
+
+    let module_doc = format!(
+        "Read-through cache for {} storage\n\n\
+        Wrap any storage with `{}::new(storage, redis_connection_manager)`.",
+        service_name, cached_ident
+    );
+    let struct_doc = format!(
+        "{} wrapper caching get responses in a `StorageCache`.\n\n\
+        Responses are stored protobuf-encoded for `ttl`, keyed by the whole request.\n\
+        A successful write drops the cached gets of the entities it touched; the TTL\n\
+        bounds how stale a read can be if that invalidation is lost.",
+        trait_name
+    );
+
+    // Replica reads right after a write may predate it, so they are not cached
+    let (lag_const, lag_field, lag_init, lag_setter) = if service_options.read_replica {
+        (
+            quote! {
+                /// Time after a write during which replica reads are not cached
+                pub const DEFAULT_REPLICA_LAG: Duration = Duration::from_secs(5);
+            },
+            quote! { replica_lag: Duration, },
+            quote! { replica_lag: DEFAULT_REPLICA_LAG, },
+            quote! {
+                /// Set how long after a write reads skip the cache
+                ///
+                /// Use at least the replica's worst expected lag.
+                pub fn with_replica_lag(mut self, replica_lag: Duration) -> Self {
+                    self.replica_lag = replica_lag;
+                    self
+                }
+            },
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {}, quote! {})
+    };
+
+    let code = quote! {
+        #![doc = #module_doc]
+        //!
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use std::time::Duration;
+        use prost::Message;
+        use super::super::prelude::*;
+        use super::#trait_module::*;
+        use super::#trait_module::StorageError;
+
+        /// Time cached responses live unless set with `with_ttl`
+        pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+        #lag_const
+
+        #[doc = #struct_doc]
+        #[derive(Clone)]
+        pub struct #cached_ident<S, C = redis::aio::ConnectionManager> {
+            inner: S,
+            cache: C,
+            ttl: Duration,
+            #lag_field
+        }
+
+        impl<S, C> #cached_ident<S, C> {
+            /// Wrap `inner`, caching in `cache` for `DEFAULT_CACHE_TTL`
+            pub fn new(inner: S, cache: C) -> Self {
+                Self { inner, cache, ttl: DEFAULT_CACHE_TTL, #lag_init }
+            }
+
+            /// Set how long cached responses live
+            pub fn with_ttl(mut self, ttl: Duration) -> Self {
+                self.ttl = ttl;
+                self
+            }
+
+            #lag_setter
+
+            /// Get a reference to the wrapped storage
+            pub fn inner(&self) -> &S {
+                &self.inner
+            }
+
+            /// Consume the wrapper and return the wrapped storage
+            pub fn into_inner(self) -> S {
+                self.inner
+            }
+        }
+
+        #(#key_fns)*
+
+        impl<S, C: StorageCache> #cached_ident<S, C> {
+            #(#invalidate_fns)*
+        }
+
+        #[async_trait::async_trait]
+        impl<S: #trait_ident, C: StorageCache> #trait_ident for #cached_ident<S, C> {
+            #db_methods
+            #(#methods)*
+        }
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!(
+        "{}/storage/cached_{}_storage.rs",
+        package.replace('.', "/"),
+        service_name.to_snake_case()
+    );
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate `{entity}_cache_keys`, the keys of every cached get of an entity
+fn generate_key_fn(entity: &str, cached: &[CachedMethod]) -> TokenStream {
+    let fn_ident = format_ident!("{}_cache_keys", entity.to_snake_case());
+    let keys = cached.iter().filter(|m| m.entity == entity).filter_map(|m| match &m.cache_use {
+        CacheUse::Get { key_prefix } => {
+            let format = format!("{}:{{}}", key_prefix);
+            Some(quote! { format!(#format, id) })
+        }
+        _ => None,
+    });
+    let doc = format!("Cache keys of every cached get of a {}", entity);
+    quote! {
+        #[doc = #doc]
+        fn #fn_ident(id: impl std::fmt::Display) -> Vec<String> {
+            vec![#(#keys),*]
+        }
+    }
+}
+
+/// Generate `invalidate_{entity}`, which drops the cached gets of written ids
+///
+/// With a read replica, the ids are first marked as written for the replica
+/// lag, so gets in that window read through without caching what may be a
+/// replica row from before the write.
+fn generate_invalidate_fn(entity: &str, marker_prefix: &str, read_replica: bool) -> TokenStream {
+    let snake = entity.to_snake_case();
+    let fn_ident = format_ident!("invalidate_{}", snake);
+    let keys_fn = format_ident!("{}_cache_keys", snake);
+    let doc = format!("Drop the cached gets of written {}s", entity);
+    let mark = read_replica.then(|| {
+        let format = format!("{}/{}:written:{{}}", marker_prefix, entity);
+        quote! {
+            // Marking before the delete means a get racing it can't re-cache a lagging read
+            let markers: Vec<String> = ids.iter().map(|id| format!(#format, id)).collect();
+            self.cache.delete(&markers).await;
+            for marker in &markers {
+                self.cache.set(marker, b"", Vec::new(), self.replica_lag).await;
+            }
+        }
+    });
+    quote! {
+        #[doc = #doc]
+        async fn #fn_ident(&self, ids: &[String]) {
+            #mark
+            let keys: Vec<String> = ids.iter().flat_map(#keys_fn).collect();
+            self.cache.delete(&keys).await;
+        }
+    }
+}
+
+/// Generate the body of a method that reads or invalidates the cache
+fn generate_cached_body(cached: &CachedMethod, read_replica: bool) -> TokenStream {
+    let method = format_ident!("{}", cached.method);
+    let invalidate = format_ident!("invalidate_{}", cached.entity.to_snake_case());
+    match &cached.cache_use {
+        CacheUse::Get { key_prefix } => {
+            let response = format_ident!("{}", cached.response);
+            let format = format!("{}:{{}}", key_prefix);
+            let store = if read_replica {
+                // Same key as invalidate_{entity}: the prefix minus the method, then the entity
+                let service = key_prefix.rsplit_once('/').map_or(key_prefix.as_str(), |(service, _)| service);
+                let marker = format!("{}/{}:written:{{}}", service, cached.entity);
+                quote! {
+                    let written = self.cache.get(&format!(#marker, id), b"").await.is_some();
+                    if !written {
+                        self.cache.set(&key, &variant, response.encode_to_vec(), self.ttl).await;
+                    }
+                }
+            } else {
+                quote! {
+                    self.cache.set(&key, &variant, response.encode_to_vec(), self.ttl).await;
+                }
+            };
+            let capture_id = read_replica.then(|| quote! { let id = request.id.clone(); });
+            quote! {
+                // Field masks and flags like include_deleted change the response, so
+                // each encoded request is its own entry under the entity's key
+                let key = format!(#format, request.id);
+                let variant = request.encode_to_vec();
+                let hit = self.cache.get(&key, &variant).await;
+                if let Some(response) = hit.and_then(|bytes| #response::decode(bytes.as_slice()).ok()) {
+                    return Ok(response);
+                }
+                #capture_id
+                let response = self.inner.#method(request).await?;
+                #store
+                Ok(response)
+            }
+        }
+        CacheUse::RequestId => quote! {
+            let ids = [request.id.to_string()];
+            let response = self.inner.#method(request).await?;
+            self.#invalidate(&ids).await;
+            Ok(response)
+        },
+        CacheUse::ResponseEntity(field) => {
+            let field = format_ident!("{}", field);
+            quote! {
+                let response = self.inner.#method(request).await?;
+                if let Some(entity) = &response.#field {
+                    self.#invalidate(&[entity.id.to_string()]).await;
+                }
+                Ok(response)
+            }
+        }
+        CacheUse::ResponseEntities(field) => {
+            let field = format_ident!("{}", field);
+            quote! {
+                let response = self.inner.#method(request).await?;
+                let ids: Vec<String> = response.#field.iter().map(|entity| entity.id.to_string()).collect();
+                self.#invalidate(&ids).await;
+                Ok(response)
+            }
+        }
+        CacheUse::ResponseIds(field) => {
+            let field = format_ident!("{}", field);
+            quote! {
+                let response = self.inner.#method(request).await?;
+                self.#invalidate(&response.#field).await;
+                Ok(response)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn method(method: &str, response: &str, cache_use: CacheUse) -> CachedMethod {
+        CachedMethod {
+            method: method.to_string(),
+            entity: "Post".to_string(),
+            response: response.to_string(),
+            cache_use,
+        }
+    }
+
+    #[test]
+    fn test_generate_cached_get() {
+        let get = method(
+            "get_post",
+            "GetPostResponse",
+            CacheUse::Get { key_prefix: "blog.PostService/get_post".to_string() },
+        );
+        let code = generate_cached_body(&get, false).to_string();
+        assert!(code.contains("format ! (\"blog.PostService/get_post:{}\" , request . id)"));
+        assert!(code.contains("let variant = request . encode_to_vec ()"));
+        assert!(code.contains("self . cache . get (& key , & variant)"));
+        assert!(code.contains("GetPostResponse :: decode"));
+        assert!(code.contains("self . cache . set (& key , & variant , response . encode_to_vec () , self . ttl)"));
+        assert!(!code.contains("written"));
+
+        let keys = generate_key_fn("Post", &[get]).to_string();
+        assert!(keys.contains("fn post_cache_keys"));
+        assert!(keys.contains("vec ! [format ! (\"blog.PostService/get_post:{}\" , id)]"));
+    }
+
+    #[test]
+    fn test_generate_cached_get_skips_replica_lag() {
+        let get = method(
+            "get_post",
+            "GetPostResponse",
+            CacheUse::Get { key_prefix: "blog.PostService/get_post".to_string() },
+        );
+        let code = generate_cached_body(&get, true).to_string();
+        assert!(code.contains("let id = request . id . clone ()"));
+        assert!(code.contains("format ! (\"blog.PostService/Post:written:{}\" , id)"));
+        assert!(code.contains("if ! written"));
+
+        let invalidate = generate_invalidate_fn("Post", "blog.PostService", true).to_string();
+        assert!(invalidate.contains("async fn invalidate_post (& self , ids : & [String])"));
+        assert!(invalidate.contains("format ! (\"blog.PostService/Post:written:{}\" , id)"));
+        assert!(invalidate.contains("self . replica_lag"));
+
+        let invalidate = generate_invalidate_fn("Post", "blog.PostService", false).to_string();
+        assert!(!invalidate.contains("replica_lag"));
+        assert!(invalidate.contains("ids . iter () . flat_map (post_cache_keys)"));
+    }
+
+    #[test]
+    fn test_generate_invalidations() {
+        let update = method("update_post", "UpdatePostResponse", CacheUse::RequestId);
+        let update = generate_cached_body(&update, false).to_string();
+        assert!(update.contains("let ids = [request . id . to_string ()]"));
+        assert!(update.contains("self . invalidate_post (& ids)"));
+
+        let create = method("create_post", "CreatePostResponse", CacheUse::ResponseEntity("post".to_string()));
+        let create = generate_cached_body(&create, false).to_string();
+        assert!(create.contains("if let Some (entity) = & response . post"));
+
+        let create_many = method(
+            "create_many_posts",
+            "CreateManyPostsResponse",
+            CacheUse::ResponseEntities("posts".to_string()),
+        );
+        let create_many = generate_cached_body(&create_many, false).to_string();
+        assert!(create_many.contains("response . posts . iter ()"));
+
+        let delete_many = method(
+            "delete_many_posts",
+            "DeleteManyPostsResponse",
+            CacheUse::ResponseIds("ids".to_string()),
+        );
+        let delete_many = generate_cached_body(&delete_many, false).to_string();
+        assert!(delete_many.contains("self . invalidate_post (& response . ids)"));
+    }
+}
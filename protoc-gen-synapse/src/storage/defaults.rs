@@ -6,18 +6,28 @@
 //! - Called by user code that overrides specific methods but wants to delegate
 //!   to the default behavior
 
+use super::seaorm::batch;
+use super::seaorm::expand;
 use super::seaorm::implementation::{
-    generate_create_impl, generate_delete_impl, generate_get_impl, generate_list_impl,
-    generate_update_impl,
+    column_name, create_many_item_type, generate_ancestors_impl, generate_bulk_condition, generate_create_impl,
+    generate_create_many_impl, generate_delete_impl, generate_delete_many_impl,
+    generate_descendants_impl, generate_encoded_list, generate_get_impl, generate_get_many_impl, generate_import_impl,
+    generate_list_encoded_impl, generate_list_impl, generate_list_paged_impl,
+    generate_list_stream_impl, generate_restore_impl, generate_update_impl,
+    generate_update_many_impl, generate_upsert_impl, update_many_patch_type, upsert_update_columns,
 };
 use super::seaorm::options::{
-    get_cached_entity_options, get_cached_rpc_method_options, get_cached_service_options,
-    get_cached_validate_message_options, storage,
+    get_cached_column_options, get_cached_entity_options, get_cached_entity_options_in_package,
+    get_cached_grpc_method_options,
+    get_cached_rpc_method_options, get_cached_service_options,
+    get_cached_validate_message_options, grpc, storage,
 };
 use crate::error::GeneratorError;
+use crate::validate::unique_in_db_fields;
 use heck::{ToSnakeCase, ToUpperCamelCase};
 use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::{Label, Type};
 use prost_types::{FileDescriptorProto, MethodDescriptorProto, ServiceDescriptorProto};
 use quote::{format_ident, quote};
 
@@ -73,6 +83,28 @@ pub fn generate(
     // Import the storage trait module to get StorageError
     let trait_module = format_ident!("{}", trait_name.to_snake_case());
 
+    // Polymorphic relation enums are defined alongside the trait
+    let polymorphic_enums: Vec<_> = polymorphic_relations(file, service)
+        .iter()
+        .map(|(entity_name, relation)| format_ident!("{}", polymorphic_enum_name(entity_name, relation)))
+        .collect();
+    let polymorphic_imports = if polymorphic_enums.is_empty() {
+        quote! {}
+    } else {
+        quote! { use super::#trait_module::{#(#polymorphic_enums),*}; }
+    };
+
+    let row_stream_import = if has_row_streams(service) {
+        quote! { use super::#trait_module::{RowStream, ROW_STREAM_BUFFER}; }
+    } else {
+        quote! {}
+    };
+    let import_stream_import = if has_import_streams(service) {
+        quote! { use super::#trait_module::ImportStream; }
+    } else {
+        quote! {}
+    };
+
     let code = quote! {
         #![doc = #module_doc]
         //!
@@ -108,6 +140,9 @@ pub fn generate(
         use super::super::prelude::*;
         use super::super::entities;
         use super::#trait_module::StorageError;
+        #row_stream_import
+        #import_stream_import
+        #polymorphic_imports
         use super::conversions::ApplyUpdate;
         // PageInfo is from synapse.relay package
         use super::super::super::synapse::relay::PageInfo;
@@ -122,11 +157,8 @@ pub fn generate(
     // Format the generated code
     let content = code.to_string();
 
-    // Try to format with prettyplease if we can parse it
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content, // If parsing fails, use unformatted
-    };
+    // Format the generated code
+    let formatted = crate::params::format_code(content);
 
     Ok(Some(File {
         name: Some(output_filename),
@@ -144,6 +176,7 @@ fn generate_default_functions(
     let file_name = file.name.as_deref().unwrap_or("");
     let service_name = service.name.as_deref().unwrap_or("");
     let mut result = Vec::new();
+    let mut encoded_lists = false;
 
     for method in &service.method {
         let method_name = method.name.as_deref().unwrap_or("");
@@ -151,8 +184,8 @@ fn generate_default_functions(
         // Check for method-level options
         let method_options = get_cached_rpc_method_options(file_name, service_name, method_name);
 
-        // Skip if marked
-        if method_options.as_ref().map(|o| o.skip).unwrap_or(false) {
+        // Skip if marked; custom methods are hand-written by the storage type
+        if method_options.as_ref().is_some_and(|o| o.skip || o.custom) {
             continue;
         }
 
@@ -163,6 +196,8 @@ fn generate_default_functions(
             .map(|o| o.method_name.clone())
             .unwrap_or_else(|| method_name.to_snake_case());
 
+        encoded_lists |= has_encoded_list(method, method_options.as_ref());
+
         // Extract entity name from method options or infer from method name
         let entity_name = method_options
             .as_ref()
@@ -178,6 +213,7 @@ fn generate_default_functions(
             &entity_name,
             &method_options,
             all_files,
+            crate::graphql::is_dry_run(file_name, service_name, method_name),
         )?;
 
         if let Some(impl_tokens) = function_impl {
@@ -185,10 +221,250 @@ fn generate_default_functions(
         }
     }
 
+    if encoded_lists {
+        result.push(generate_encoded_list());
+    }
+
+    // Tree queries for recursive self-referential relations
+    for (entity_name, relation) in recursive_relations(file, service) {
+        result.push(generate_tree_functions(file, all_files, &entity_name, &relation));
+    }
+
+    // Dispatch functions for polymorphic relations
+    for (entity_name, relation) in polymorphic_relations(file, service) {
+        result.push(generate_polymorphic_function(&entity_name, &relation));
+    }
+
     Ok(result)
 }
 
+/// Collect the entities a service manages along with their entity options
+///
+/// Entities are taken from method options or inferred from method names, in
+/// method order and without duplicates. Their options are looked up across the
+/// service's package, since entities are often declared in their own file.
+/// Entities without storage options are skipped.
+pub(crate) fn service_entities(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Vec<(String, storage::EntityOptions)> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let package = file.package.as_deref().unwrap_or("");
+    let service_name = service.name.as_deref().unwrap_or("");
+    let mut result: Vec<(String, storage::EntityOptions)> = Vec::new();
+
+    for method in &service.method {
+        let method_name = method.name.as_deref().unwrap_or("");
+        let method_options = get_cached_rpc_method_options(file_name, service_name, method_name);
+
+        if method_options.as_ref().map(|o| o.skip).unwrap_or(false) {
+            continue;
+        }
+
+        let entity_name = method_options
+            .as_ref()
+            .filter(|o| !o.entity_name.is_empty())
+            .map(|o| o.entity_name.clone())
+            .unwrap_or_else(|| infer_entity_name(method_name));
+
+        if result.iter().any(|(name, _)| name == &entity_name) {
+            continue;
+        }
+
+        if let Some(entity_options) =
+            get_cached_entity_options_in_package(package, &entity_name.to_upper_camel_case())
+        {
+            result.push((entity_name, entity_options));
+        }
+    }
+
+    result
+}
+
+/// Collect recursive self-referential relations for the entities a service manages
+///
+/// Returns `(entity_name, relation)` pairs for each entity that declares a
+/// `belongs_to` relation to itself with `recursive: true`. Only the first such
+/// relation per entity is used, since the generated method names are per entity.
+pub(crate) fn recursive_relations(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Vec<(String, storage::RelationDef)> {
+    service_entities(file, service)
+        .into_iter()
+        .filter_map(|(entity_name, entity_options)| {
+            let relation = entity_options.relations.into_iter().find(|rel| {
+                rel.recursive
+                    && rel.r#type() == storage::RelationType::BelongsTo
+                    && !rel.foreign_key.is_empty()
+                    && rel.related.to_snake_case() == entity_name.to_snake_case()
+            })?;
+            Some((entity_name, relation))
+        })
+        .collect()
+}
+
+/// Collect polymorphic relations for the entities a service manages
+///
+/// Returns `(entity_name, relation)` pairs for every relation with a foreign
+/// key, a discriminator column, and at least one same-package candidate.
+pub(crate) fn polymorphic_relations(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Vec<(String, storage::RelationDef)> {
+    let mut result = Vec::new();
+
+    for (entity_name, entity_options) in service_entities(file, service) {
+        for mut relation in entity_options.relations {
+            if relation.r#type() != storage::RelationType::Polymorphic
+                || relation.name.is_empty()
+                || relation.foreign_key.is_empty()
+                || relation.type_column.is_empty()
+            {
+                continue;
+            }
+
+            // Storage dispatch only reaches entities in this package
+            relation.candidates.retain(|c| !c.contains('.'));
+            if !relation.candidates.is_empty() {
+                result.push((entity_name.clone(), relation));
+            }
+        }
+    }
+
+    result
+}
+
+/// Name of the enum holding the target of a polymorphic relation
+///
+/// For example, `Comment.commentable` becomes `CommentCommentable`.
+pub(crate) fn polymorphic_enum_name(entity_name: &str, relation: &storage::RelationDef) -> String {
+    format!(
+        "{}{}",
+        entity_name.to_upper_camel_case(),
+        relation.name.to_upper_camel_case()
+    )
+}
+
+/// Generate the `{entity}_{relation}` function dispatching a polymorphic lookup
+fn generate_polymorphic_function(entity_name: &str, relation: &storage::RelationDef) -> TokenStream {
+    let function_ident = format_ident!(
+        "{}_{}",
+        entity_name.to_snake_case(),
+        relation.name.to_snake_case()
+    );
+    let enum_ident = format_ident!("{}", polymorphic_enum_name(entity_name, relation));
+    let relation_name = relation.name.to_snake_case();
+
+    let arms = relation.candidates.iter().map(|candidate| {
+        let type_name = candidate.to_upper_camel_case();
+        let variant_ident = format_ident!("{}", type_name);
+        let entity_module = format_ident!("{}", candidate.to_snake_case());
+        quote! {
+            #type_name => {
+                // Candidates may differ in key type, so the id is parsed per target
+                let key = parse_key::<entities::#entity_module::Entity>(id).ok_or_else(|| {
+                    StorageError::InvalidArgument(format!("invalid {} id: {}", #type_name, id))
+                })?;
+                entities::#entity_module::Entity::find_by_id(key)
+                    .one(db)
+                    .await
+                    .map_err(StorageError::Database)?
+                    .map(|m| #enum_ident::#variant_ident(m.into()))
+            }
+        }
+    });
+
+    let doc = format!(
+        "Default implementation for `{}`.\n\nLoads the `{}` target named by `{}` with the id stored in `{}`, given in string form and parsed into the target's key type.",
+        function_ident, relation_name, relation.type_column, relation.foreign_key
+    );
+
+    quote! {
+        #[doc = #doc]
+        pub async fn #function_ident(
+            db: &DatabaseConnection,
+            type_name: &str,
+            id: &str,
+        ) -> Result<Option<#enum_ident>, StorageError> {
+            let target = match type_name {
+                #(#arms)*
+                other => {
+                    return Err(StorageError::InvalidArgument(format!(
+                        "unknown {} type: {}",
+                        #relation_name, other
+                    )))
+                }
+            };
+
+            Ok(target)
+        }
+    }
+}
+
+/// Generate `{entity}_ancestors` and `{entity}_descendants` standalone functions
+fn generate_tree_functions(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+    entity_name: &str,
+    relation: &storage::RelationDef,
+) -> TokenStream {
+    let entity_snake = entity_name.to_snake_case();
+    let entity_module = format_ident!("{}", entity_snake);
+    let entity_type = format_ident!("{}", entity_name.to_upper_camel_case());
+    let ancestors_ident = format_ident!("{}_ancestors", entity_snake);
+    let descendants_ident = format_ident!("{}_descendants", entity_snake);
+
+    let references = if relation.references.is_empty() {
+        "id"
+    } else {
+        relation.references.as_str()
+    };
+    // The hand-written SQL names database columns, so overrides apply
+    let message = entity_name.to_upper_camel_case();
+    let pk_column = column_name(all_files, &message, references);
+    let fk_column = column_name(all_files, &message, &relation.foreign_key);
+    // The entity may be declared in another file of the service's package
+    let soft_delete = get_cached_entity_options_in_package(file.package.as_deref().unwrap_or(""), &message)
+        .is_some_and(|o| o.soft_delete);
+    let deleted_column = soft_delete.then(|| column_name(all_files, &message, "deleted_at"));
+    let ancestors_body =
+        generate_ancestors_impl(&entity_module, &fk_column, &pk_column, deleted_column.as_deref());
+    let descendants_body =
+        generate_descendants_impl(&entity_module, &fk_column, &pk_column, deleted_column.as_deref());
+
+    let ancestors_doc = format!(
+        "Default implementation for `{}_ancestors`.\n\nReturns every ancestor of the given {} following `{}`, nearest first.",
+        entity_snake, entity_snake, relation.foreign_key
+    );
+    let descendants_doc = format!(
+        "Default implementation for `{}_descendants`.\n\nReturns every descendant of the given {} following `{}`, breadth first.",
+        entity_snake, entity_snake, relation.foreign_key
+    );
+
+    quote! {
+        #[doc = #ancestors_doc]
+        pub async fn #ancestors_ident(
+            db: &DatabaseConnection,
+            id: KeyOf<entities::#entity_module::Entity>,
+        ) -> Result<Vec<#entity_type>, StorageError> {
+            #ancestors_body
+        }
+
+        #[doc = #descendants_doc]
+        pub async fn #descendants_ident(
+            db: &DatabaseConnection,
+            id: KeyOf<entities::#entity_module::Entity>,
+        ) -> Result<Vec<#entity_type>, StorageError> {
+            #descendants_body
+        }
+    }
+}
+
 /// Generate a standalone function implementation
+///
+/// With `dry_run`, a call made under the dry-run flag runs in a transaction
+/// that is rolled back, so it returns the would-be result without persisting.
 fn generate_function_impl(
     file: &FileDescriptorProto,
     method: &MethodDescriptorProto,
@@ -196,6 +472,7 @@ fn generate_function_impl(
     entity_name: &str,
     method_options: &Option<storage::MethodOptions>,
     all_files: &[FileDescriptorProto],
+    dry_run: bool,
 ) -> Result<Option<TokenStream>, GeneratorError> {
     let file_name = file.name.as_deref().unwrap_or("");
 
@@ -217,24 +494,222 @@ fn generate_function_impl(
     let response_ident = format_ident!("{}", response_type);
     let entity_module = format_ident!("{}", entity_name.to_snake_case());
 
-    // Check if we have entity options for this entity
-    let entity_options = get_cached_entity_options(file_name, &entity_name.to_upper_camel_case());
+    // Check if we have entity options for this entity, which may be declared
+    // in another file of the package
+    let entity_message = entity_name.to_upper_camel_case();
+    let entity_file_name = all_files
+        .iter()
+        .find(|f| f.message_type.iter().any(|m| m.name.as_deref() == Some(entity_message.as_str())))
+        .and_then(|f| f.name.as_deref())
+        .unwrap_or(file_name);
+    let entity_options = get_cached_entity_options(entity_file_name, &entity_message);
+
+    // `unique_in_db` rules live on the request message
+    let unique = all_files
+        .iter()
+        .find_map(|f| {
+            f.message_type
+                .iter()
+                .find(|m| m.name.as_deref() == Some(raw_input_type.as_str()))
+                .map(|m| unique_in_db_fields(f.name.as_deref().unwrap_or(""), m))
+        })
+        .unwrap_or_default();
+    for u in &unique {
+        if !has_unique_index(entity_file_name, &entity_message, entity_options.as_ref(), all_files, &[u.field.as_str()]) {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{}.{}: unique_in_db needs a unique index on {}.{} (column option `unique` or a unique single-column index)",
+                raw_input_type, u.field, entity_message, u.field
+            )));
+        }
+    }
+
+    let stream_rows = method_options.as_ref().is_some_and(|o| o.stream_rows);
 
     // Generate with for_standalone=true to use `db` parameter instead of `self.db`
     let method_body = match operation {
         "get" | "Get" | "GET" => {
-            generate_get_impl(&entity_module, &response_ident, entity_options.as_ref(), true)
+            let expansion = expand::generate(
+                all_files,
+                file.package.as_deref().unwrap_or(""),
+                &raw_input_type,
+                &response_type,
+                entity_name,
+                &quote! { db },
+            )?;
+            generate_get_impl(&entity_module, &response_ident, entity_options.as_ref(), expansion.as_ref(), true)
+        }
+        "get_many" | "GetMany" | "GET_MANY" => {
+            let response_field = repeated_response_field(file, &response_type, entity_name);
+            generate_get_many_impl(&entity_module, &response_ident, &response_field, entity_options.as_ref(), true)
         }
+        "list" | "List" | "LIST" if method.server_streaming() => match paged_list_function(file, method)? {
+            Some((list_fn, page_size)) => {
+                let nodes = response_type == entity_name.to_upper_camel_case();
+                generate_list_paged_impl(&list_fn, nodes, page_size)
+            }
+            None => generate_list_stream_impl(
+                file,
+                &request_type,
+                &entity_module,
+                &response_ident,
+                entity_options.as_ref(),
+                all_files,
+            ),
+        },
         "list" | "List" | "LIST" => {
-            generate_list_impl(file, &request_type, &entity_module, &response_ident, all_files, true)
+            generate_list_impl(
+                file,
+                &request_type,
+                &entity_module,
+                &response_ident,
+                entity_options.as_ref(),
+                all_files,
+                true,
+                stream_rows,
+            )
         }
         "create" | "Create" | "CREATE" => {
-            generate_create_impl(&entity_module, &response_ident, entity_options.as_ref(), true)
+            generate_create_impl(&entity_module, &response_ident, entity_options.as_ref(), &unique, true)
+        }
+        "create_many" | "CreateMany" | "CREATE_MANY" => {
+            let (item_domain, response_field) = create_many_fields(file, &raw_input_type, &response_type, entity_name);
+            let batch = file
+                .message_type
+                .iter()
+                .find(|m| m.name.as_deref() == Some(response_type.as_str()))
+                .and_then(batch::errors_field)
+                .map(|field| batch::BatchErrors {
+                    field: format_ident!("{}", field),
+                    atomic: method_options.as_ref().is_some_and(|o| o.atomic),
+                });
+            generate_create_many_impl(
+                &entity_module,
+                &response_ident,
+                &response_field,
+                item_domain.as_ref(),
+                entity_options.as_ref(),
+                &unique,
+                batch.as_ref(),
+                true,
+            )
         }
         "update" | "Update" | "UPDATE" => {
-            generate_update_impl(&entity_module, &response_ident, entity_options.as_ref(), true)
+            generate_update_impl(&entity_module, &response_ident, entity_options.as_ref(), &unique, true)
+        }
+        "upsert" | "Upsert" | "UPSERT" => {
+            let conflict_columns: Vec<String> = method_options
+                .as_ref()
+                .map(|o| o.conflict_columns.iter().map(|c| c.to_snake_case()).collect())
+                .unwrap_or_default();
+            // ON CONFLICT needs a unique index (or the key) on exactly these columns
+            let conflict: Vec<&str> = conflict_columns.iter().map(String::as_str).collect();
+            if !conflict.is_empty()
+                && !has_unique_index(entity_file_name, &entity_message, entity_options.as_ref(), all_files, &conflict)
+            {
+                return Err(GeneratorError::CodeGenError(format!(
+                    "{}: conflict_columns [{}] need a unique index on {} (column option `unique` or a unique index on exactly these columns)",
+                    method_name,
+                    conflict.join(", "),
+                    entity_message
+                )));
+            }
+            let request_msg = all_files
+                .iter()
+                .flat_map(|f| f.message_type.iter())
+                .find(|m| m.name.as_deref() == Some(raw_input_type.as_str()));
+            let update_columns = match request_msg {
+                Some(request_msg) => upsert_update_columns(
+                    all_files,
+                    file.package.as_deref().unwrap_or(""),
+                    &entity_name.to_upper_camel_case(),
+                    request_msg,
+                    entity_options.as_ref(),
+                    &conflict_columns,
+                )?,
+                None => Vec::new(),
+            };
+            generate_upsert_impl(
+                &entity_module,
+                &response_ident,
+                entity_options.as_ref(),
+                &conflict_columns,
+                &update_columns,
+                true,
+            )
+        }
+        "update_many" | "UpdateMany" | "UPDATE_MANY" => {
+            let condition = bulk_condition(file, method_name, &raw_input_type, &entity_module, all_files)?;
+            let patch_domain = file
+                .message_type
+                .iter()
+                .find(|m| m.name.as_deref() == Some(raw_input_type.as_str()))
+                .and_then(update_many_patch_type)
+                .and_then(|patch| {
+                    let domain = resolve_domain_type(file_name, &patch);
+                    (domain != patch).then(|| format_ident!("{}", domain))
+                });
+            generate_update_many_impl(
+                &entity_module,
+                &response_ident,
+                &affected_field(file, &response_type),
+                written_ids_field(file, &response_type).map(|f| format_ident!("{}", f)).as_ref(),
+                &condition,
+                patch_domain.as_ref(),
+                entity_options.as_ref(),
+                true,
+            )
+        }
+        "delete" | "Delete" | "DELETE" => {
+            generate_delete_impl(&entity_module, &response_ident, entity_options.as_ref(), true)
+        }
+        "delete_many" | "DeleteMany" | "DELETE_MANY" => {
+            let condition = bulk_condition(file, method_name, &raw_input_type, &entity_module, all_files)?;
+            generate_delete_many_impl(
+                &entity_module,
+                &response_ident,
+                &affected_field(file, &response_type),
+                written_ids_field(file, &response_type).map(|f| format_ident!("{}", f)).as_ref(),
+                &condition,
+                entity_options.as_ref(),
+                true,
+            )
+        }
+        "restore" | "Restore" | "RESTORE" => {
+            generate_restore_impl(&entity_module, &response_ident, entity_options.as_ref(), true)
+        }
+        "import" | "Import" | "IMPORT" => {
+            if !method.client_streaming() || method.server_streaming() {
+                return Err(GeneratorError::CodeGenError(format!(
+                    "{}: imports take a stream of requests and return one response, declare it as `rpc {}(stream {}) returns ({})`",
+                    method_name, method_name, raw_input_type, response_type
+                )));
+            }
+            let summary = file
+                .message_type
+                .iter()
+                .find(|m| m.name.as_deref() == Some(response_type.as_str()))
+                .and_then(batch::import_summary)
+                .ok_or_else(|| {
+                    GeneratorError::CodeGenError(format!(
+                        "{}: {} needs an integer `inserted` field and a repeated synapse.relay.BatchError field",
+                        method_name, response_type
+                    ))
+                })?;
+            let item_domain = (request_type != raw_input_type).then(|| request_ident.clone());
+            let batch_size = method_options
+                .as_ref()
+                .map(|o| o.batch_size)
+                .filter(|n| *n > 0)
+                .unwrap_or(500);
+            generate_import_impl(
+                &entity_module,
+                &response_ident,
+                item_domain.as_ref(),
+                entity_options.as_ref(),
+                &summary,
+                batch_size,
+            )
         }
-        "delete" | "Delete" | "DELETE" => generate_delete_impl(&entity_module, &response_ident, true),
         _ => {
             quote! {
                 todo!("Implement {} for {}", stringify!(#method_ident), stringify!(#entity_module))
@@ -248,27 +723,355 @@ fn generate_function_impl(
         rust_method_name
     );
 
+    // `stream_rows` lists also get a variant returning the encoded response
+    let encoded_token = if has_encoded_list(method, method_options.as_ref()) {
+        let (edges_tag, page_info_tag) =
+            list_response_tags(all_files, method.output_type.as_deref().unwrap_or(""))?;
+        let encoded_ident = format_ident!("{}_encoded", rust_method_name);
+        let encoded_doc = format!(
+            "Default implementation for `{}`, encoded row by row.\n\n\
+            Returns the `{}` as protobuf bytes without building the message, for\n\
+            large exports written straight to a response body.",
+            rust_method_name, response_type
+        );
+        let encoded_body = generate_list_encoded_impl(
+            file,
+            &request_type,
+            &entity_module,
+            entity_options.as_ref(),
+            all_files,
+            edges_tag,
+            page_info_tag,
+        );
+        let tests_ident = format_ident!("{}_encoded_tests", rust_method_name);
+        let edge_ident = format_ident!("{}Edge", entity_name.to_upper_camel_case());
+        quote! {
+            #[doc = #encoded_doc]
+            pub async fn #encoded_ident(
+                db: &DatabaseConnection,
+                request: #request_ident,
+            ) -> Result<prost::bytes::Bytes, StorageError> {
+                #encoded_body
+            }
+
+            #[cfg(test)]
+            mod #tests_ident {
+                use super::*;
+                use prost::Message as _;
+
+                #[test]
+                fn decodes_as_response() {
+                    let mut list = EncodedList::new(#edges_tag, #page_info_tag);
+                    for cursor in ["1", "2"] {
+                        list.push(&#edge_ident {
+                            cursor: cursor.to_string(),
+                            node: Some(Default::default()),
+                        });
+                    }
+                    let bytes = list.finish(&PageInfo {
+                        has_next_page: true,
+                        end_cursor: Some("2".to_string()),
+                        ..Default::default()
+                    });
+
+                    let response = #response_ident::decode(bytes).unwrap();
+                    let cursors: Vec<_> = response.edges.iter().map(|e| e.cursor.as_str()).collect();
+                    assert_eq!(cursors, ["1", "2"]);
+                    assert!(response.edges.iter().all(|e| e.node.is_some()));
+                    let page_info = response.page_info.unwrap();
+                    assert!(page_info.has_next_page);
+                    assert_eq!(page_info.end_cursor.as_deref(), Some("2"));
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Server-streaming RPCs return a stream of their response messages
+    let output = if method.server_streaming() {
+        quote! { RowStream<#response_ident> }
+    } else {
+        quote! { #response_ident }
+    };
+
+    // Client-streaming RPCs take the raw requests, validated one by one
+    let input = if method.client_streaming() {
+        let raw_input_ident = format_ident!("{}", raw_input_type);
+        quote! { ImportStream<#raw_input_ident> }
+    } else {
+        quote! { #request_ident }
+    };
+
+    // The body runs against the transaction as `db`; nested transactions become savepoints
+    let dry_run_token = if dry_run {
+        quote! {
+            if dry_run() {
+                use sea_orm::TransactionTrait;
+                let txn = db.begin().await.map_err(StorageError::Database)?;
+                let conn = &txn;
+                let result: Result<#output, StorageError> = async move {
+                    let db = conn;
+                    #method_body
+                }
+                .await;
+                txn.rollback().await.map_err(StorageError::Database)?;
+                return result;
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let function_token = quote! {
         #[doc = #doc]
         pub async fn #method_ident(
             db: &DatabaseConnection,
-            request: #request_ident,
-        ) -> Result<#response_ident, StorageError> {
+            request: #input,
+        ) -> Result<#output, StorageError> {
+            #dry_run_token
             #method_body
         }
+
+        #encoded_token
     };
 
     Ok(Some(function_token))
 }
 
+/// Default paged through by a `streaming: STREAMING_SERVER` RPC, with its page size
+///
+/// The RPC pages through the unary LIST of its service taking the same
+/// request. `None` when the RPC streams from a single query.
+fn paged_list_function(
+    file: &FileDescriptorProto,
+    method: &MethodDescriptorProto,
+) -> Result<Option<(proc_macro2::Ident, u32)>, GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let method_name = method.name.as_deref().unwrap_or("");
+    let Some(service) = file.service.iter().find(|s| s.method.iter().any(|m| std::ptr::eq(m, method))) else {
+        return Ok(None);
+    };
+    let service_name = service.name.as_deref().unwrap_or("");
+    let Some(grpc_options) = get_cached_grpc_method_options(file_name, service_name, method_name)
+        .filter(|o| o.streaming == grpc::Streaming::Server as i32)
+    else {
+        return Ok(None);
+    };
+
+    let list = service.method.iter().find_map(|m| {
+        let name = m.name.as_deref().unwrap_or("");
+        let options = get_cached_rpc_method_options(file_name, service_name, name);
+        let operation = options
+            .as_ref()
+            .filter(|o| !o.operation.is_empty())
+            .map(|o| o.operation.to_lowercase())
+            .unwrap_or_else(|| infer_operation(name).to_string());
+        let is_list = !m.server_streaming()
+            && m.input_type == method.input_type
+            && operation == "list"
+            && !options.as_ref().is_some_and(|o| o.skip || o.custom);
+        is_list.then(|| {
+            options
+                .filter(|o| !o.method_name.is_empty())
+                .map(|o| o.method_name)
+                .unwrap_or_else(|| name.to_snake_case())
+        })
+    });
+    let Some(list) = list else {
+        return Err(GeneratorError::CodeGenError(format!(
+            "{}.{} streams with STREAMING_SERVER, but the service has no generated list RPC taking {}",
+            service_name,
+            method_name,
+            extract_type_name(method.input_type.as_deref())
+        )));
+    };
+
+    let page_size = if grpc_options.page_size == 0 { 100 } else { grpc_options.page_size };
+    Ok(Some((format_ident!("{}", list), page_size)))
+}
+
+/// Whether a service has server-streaming RPCs, whose storage methods return a `RowStream`
+pub(crate) fn has_row_streams(service: &ServiceDescriptorProto) -> bool {
+    service.method.iter().any(|m| m.server_streaming())
+}
+
+/// Whether a service has client-streaming RPCs, whose storage methods take an `ImportStream`
+pub(crate) fn has_import_streams(service: &ServiceDescriptorProto) -> bool {
+    service.method.iter().any(|m| m.client_streaming())
+}
+
+/// Whether a method gets a `<method>_encoded` default: unary lists with `stream_rows`
+fn has_encoded_list(method: &MethodDescriptorProto, method_options: Option<&storage::MethodOptions>) -> bool {
+    let Some(options) = method_options.filter(|o| o.stream_rows) else {
+        return false;
+    };
+    let operation = if options.operation.is_empty() {
+        infer_operation(method.name.as_deref().unwrap_or(""))
+    } else {
+        options.operation.as_str()
+    };
+    !method.server_streaming() && matches!(operation, "list" | "List" | "LIST")
+}
+
+/// Field numbers of `edges` and `page_info` in a list response message
+///
+/// `output_type` is the method's fully qualified output type, which may be
+/// declared in any file of the request. The encoded bytes are only a valid
+/// response with the real field numbers, so a missing message or field is an
+/// error rather than a guess.
+fn list_response_tags(
+    all_files: &[FileDescriptorProto],
+    output_type: &str,
+) -> Result<(u32, u32), GeneratorError> {
+    let response = all_files
+        .iter()
+        .flat_map(|f| {
+            let package = f.package.as_deref().unwrap_or("");
+            f.message_type.iter().map(move |m| (package, m))
+        })
+        .find(|(package, m)| {
+            let name = m.name.as_deref().unwrap_or("");
+            let full_name = if package.is_empty() {
+                format!(".{}", name)
+            } else {
+                format!(".{}.{}", package, name)
+            };
+            full_name == output_type
+        })
+        .map(|(_, m)| m)
+        .ok_or_else(|| {
+            GeneratorError::CodeGenError(format!(
+                "stream_rows: list response {} not found",
+                output_type
+            ))
+        })?;
+    let tag = |name: &str| {
+        response
+            .field
+            .iter()
+            .find(|f| f.name.as_deref() == Some(name))
+            .and_then(|f| f.number)
+            .map(|n| n as u32)
+            .ok_or_else(|| {
+                GeneratorError::CodeGenError(format!(
+                    "stream_rows: list response {} has no `{}` field",
+                    output_type, name
+                ))
+            })
+    };
+    Ok((tag("edges")?, tag("page_info")?))
+}
+
+/// Domain type of a create-many request's items and the response field for the created rows
+///
+/// Items convert through the create request's domain type when it has one.
+fn create_many_fields(
+    file: &FileDescriptorProto,
+    request_type: &str,
+    response_type: &str,
+    entity_name: &str,
+) -> (Option<proc_macro2::Ident>, proc_macro2::Ident) {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let find = |name: &str| file.message_type.iter().find(|m| m.name.as_deref() == Some(name));
+
+    let item_domain = find(request_type)
+        .and_then(create_many_item_type)
+        .and_then(|item| {
+            let domain = resolve_domain_type(file_name, &item);
+            (domain != item).then(|| format_ident!("{}", domain))
+        });
+
+    (item_domain, repeated_response_field(file, response_type, entity_name))
+}
+
+/// Response field holding the rows of a create-many or get-many response
+///
+/// The response's first repeated field, `{entities}` when the response is not
+/// found.
+pub(crate) fn repeated_response_field(
+    file: &FileDescriptorProto,
+    response_type: &str,
+    entity_name: &str,
+) -> proc_macro2::Ident {
+    let response_field = file
+        .message_type
+        .iter()
+        .find(|m| m.name.as_deref() == Some(response_type))
+        .and_then(|m| {
+            m.field
+                .iter()
+                .find(|f| f.label() == Label::Repeated && !batch::is_errors_field(f))
+        })
+        .and_then(|f| f.name.clone())
+        .unwrap_or_else(|| format!("{}s", entity_name.to_snake_case()));
+    format_ident!("{}", response_field)
+}
+
+/// Filter condition of a bulk update or delete, which must take a `filter`
+fn bulk_condition(
+    file: &FileDescriptorProto,
+    method_name: &str,
+    request_type: &str,
+    entity_module: &proc_macro2::Ident,
+    all_files: &[FileDescriptorProto],
+) -> Result<TokenStream, GeneratorError> {
+    generate_bulk_condition(file, request_type, entity_module, all_files).ok_or_else(|| {
+        GeneratorError::CodeGenError(format!(
+            "{}: bulk updates and deletes need a `filter` field of the entity's filter type in {}",
+            method_name, request_type
+        ))
+    })
+}
+
+/// Response field holding the row count of a bulk update or delete
+///
+/// The response's first integer field, `affected` when the response is not
+/// found.
+pub(crate) fn affected_field(file: &FileDescriptorProto, response_type: &str) -> proc_macro2::Ident {
+    let field = file
+        .message_type
+        .iter()
+        .find(|m| m.name.as_deref() == Some(response_type))
+        .and_then(|m| {
+            m.field.iter().find(|f| {
+                f.label() != Label::Repeated
+                    && matches!(f.r#type(), Type::Int64 | Type::Uint64 | Type::Int32 | Type::Uint32)
+            })
+        })
+        .and_then(|f| f.name.clone())
+        .unwrap_or_else(|| "affected".to_string());
+    format_ident!("{}", field.to_snake_case())
+}
+
+/// Response field listing the keys of the rows a bulk update or delete wrote
+///
+/// A `repeated string ids` field of the response, if it has one. Webhooks
+/// and invalidation hooks of bulk writes act on these keys.
+pub(crate) fn written_ids_field(file: &FileDescriptorProto, response_type: &str) -> Option<String> {
+    file.message_type
+        .iter()
+        .find(|m| m.name.as_deref() == Some(response_type))?
+        .field
+        .iter()
+        .find(|f| f.name.as_deref() == Some("ids") && f.label() == Label::Repeated && f.r#type() == Type::String)
+        .map(|f| f.name.as_deref().unwrap_or("").to_snake_case())
+}
+
 /// Infer entity name from method name
-fn infer_entity_name(method_name: &str) -> String {
+pub(crate) fn infer_entity_name(method_name: &str) -> String {
     let name = method_name
         .strip_prefix("Get")
         .or_else(|| method_name.strip_prefix("List"))
+        .or_else(|| method_name.strip_prefix("CreateMany"))
         .or_else(|| method_name.strip_prefix("Create"))
+        .or_else(|| method_name.strip_prefix("UpdateMany"))
         .or_else(|| method_name.strip_prefix("Update"))
+        .or_else(|| method_name.strip_prefix("Upsert"))
+        .or_else(|| method_name.strip_prefix("DeleteMany"))
         .or_else(|| method_name.strip_prefix("Delete"))
+        .or_else(|| method_name.strip_prefix("Restore"))
+        .or_else(|| method_name.strip_prefix("Import"))
         .unwrap_or(method_name);
 
     let name = if let Some(idx) = name.find("By") {
@@ -277,7 +1080,13 @@ fn infer_entity_name(method_name: &str) -> String {
         name
     };
 
-    if method_name.starts_with("List") && name.ends_with('s') {
+    let plural = method_name.starts_with("List")
+        || method_name.starts_with("CreateMany")
+        || method_name.starts_with("UpdateMany")
+        || method_name.starts_with("DeleteMany")
+        || method_name.starts_with("Import")
+        || (method_name.starts_with("Get") && method_name.ends_with("ByIds"));
+    if plural && name.ends_with('s') {
         name.strip_suffix('s').unwrap_or(name).to_string()
     } else {
         name.to_string()
@@ -285,17 +1094,31 @@ fn infer_entity_name(method_name: &str) -> String {
 }
 
 /// Infer operation type from method name
-fn infer_operation(method_name: &str) -> &'static str {
-    if method_name.starts_with("Get") {
+pub(crate) fn infer_operation(method_name: &str) -> &'static str {
+    if method_name.starts_with("Get") && method_name.ends_with("ByIds") {
+        "get_many"
+    } else if method_name.starts_with("Get") {
         "get"
     } else if method_name.starts_with("List") {
         "list"
+    } else if method_name.starts_with("CreateMany") {
+        "create_many"
     } else if method_name.starts_with("Create") {
         "create"
+    } else if method_name.starts_with("UpdateMany") {
+        "update_many"
     } else if method_name.starts_with("Update") {
         "update"
+    } else if method_name.starts_with("Upsert") {
+        "upsert"
+    } else if method_name.starts_with("DeleteMany") {
+        "delete_many"
     } else if method_name.starts_with("Delete") {
         "delete"
+    } else if method_name.starts_with("Restore") {
+        "restore"
+    } else if method_name.starts_with("Import") {
+        "import"
     } else {
         "unknown"
     }
@@ -317,6 +1140,44 @@ fn extract_type_name(type_name: Option<&str>) -> String {
     }
 }
 
+/// Whether entity columns are covered by a unique index on exactly those columns
+///
+/// `unique_in_db` relies on it for single columns: outside Postgres the
+/// pre-write check cannot stop two concurrent inserts of the same value, the
+/// index can. Upserts need it for their `conflict_columns`, since
+/// `ON CONFLICT` only targets a unique index or the primary key.
+fn has_unique_index(
+    entity_file_name: &str,
+    entity_message: &str,
+    entity_options: Option<&storage::EntityOptions>,
+    all_files: &[FileDescriptorProto],
+    columns: &[&str],
+) -> bool {
+    let column_options: Vec<(String, storage::ColumnOptions)> = all_files
+        .iter()
+        .filter(|f| f.name.as_deref() == Some(entity_file_name))
+        .flat_map(|f| &f.message_type)
+        .filter(|m| m.name.as_deref() == Some(entity_message))
+        .flat_map(|m| &m.field)
+        .filter_map(|f| {
+            let options = get_cached_column_options(entity_file_name, entity_message, f.number.unwrap_or(0))?;
+            Some((f.name.as_deref().unwrap_or("").to_snake_case(), options))
+        })
+        .collect();
+    let same_columns = |other: &[String]| {
+        other.len() == columns.len() && columns.iter().all(|c| other.iter().any(|o| o.to_snake_case() == c.to_snake_case()))
+    };
+
+    let primary_key: Vec<String> = column_options.iter().filter(|(_, o)| o.primary_key).map(|(n, _)| n.clone()).collect();
+    let column_unique = match columns {
+        [column] => column_options.iter().any(|(n, o)| *n == column.to_snake_case() && o.unique),
+        _ => false,
+    };
+    column_unique
+        || same_columns(&primary_key)
+        || entity_options.is_some_and(|o| o.indexes.iter().any(|i| i.unique && same_columns(&i.columns)))
+}
+
 /// Resolve a message type to its domain type if one exists
 ///
 /// If the message has synapse.validate.message options with generate_conversion=true
@@ -330,3 +1191,225 @@ fn resolve_domain_type(file_name: &str, message_name: &str) -> String {
     }
     message_name.to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::seaorm::options::cache_column_options;
+    use crate::test_util::{field, message_field, repeated};
+    use prost_types::DescriptorProto;
+
+    #[test]
+    fn test_has_unique_index() {
+        let file_name = "test/defaults_unique_index.proto";
+        let files = vec![FileDescriptorProto {
+            name: Some(file_name.to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Author".to_string()),
+                field: vec![
+                    field("id", 1, Type::Int64),
+                    field("user_id", 2, Type::Int64),
+                    field("org_id", 3, Type::Int64),
+                    field("pen_name", 4, Type::String),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }];
+        cache_column_options(file_name, "Author", 1, storage::ColumnOptions { primary_key: true, ..Default::default() });
+        cache_column_options(file_name, "Author", 2, storage::ColumnOptions { unique: true, ..Default::default() });
+        let options = storage::EntityOptions {
+            indexes: vec![storage::IndexDef {
+                columns: vec!["org_id".to_string(), "pen_name".to_string()],
+                unique: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let unique = |columns: &[&str]| has_unique_index(file_name, "Author", Some(&options), &files, columns);
+
+        assert!(unique(&["id"]));
+        assert!(unique(&["user_id"]));
+        assert!(unique(&["pen_name", "org_id"]));
+        assert!(!unique(&["pen_name"]));
+        assert!(!unique(&["org_id"]));
+        assert!(!unique(&["user_id", "pen_name"]));
+    }
+
+    #[test]
+    fn test_encoded_lists() {
+        let files = vec![FileDescriptorProto {
+            package: Some("blog".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("PostConnection".to_string()),
+                    field: vec![
+                        message_field("page_info", 1, ".synapse.relay.PageInfo"),
+                        repeated(message_field("edges", 3, ".blog.PostEdge")),
+                    ],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("UserConnection".to_string()),
+                    field: vec![repeated(message_field("edges", 1, ".blog.UserEdge"))],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }];
+        assert_eq!(list_response_tags(&files, ".blog.PostConnection").unwrap(), (3, 1));
+        assert!(list_response_tags(&files, ".blog.UserConnection").is_err());
+        assert!(list_response_tags(&files, ".blog.TagConnection").is_err());
+        assert!(list_response_tags(&files, ".shop.PostConnection").is_err());
+
+        let method = |name: &str, server_streaming: bool| MethodDescriptorProto {
+            name: Some(name.to_string()),
+            server_streaming: Some(server_streaming),
+            ..Default::default()
+        };
+        let streamed = storage::MethodOptions { stream_rows: true, ..Default::default() };
+        assert!(has_encoded_list(&method("ListPosts", false), Some(&streamed)));
+        assert!(!has_encoded_list(&method("ListPosts", false), None));
+        assert!(!has_encoded_list(&method("ExportPosts", true), Some(&streamed)));
+        assert!(!has_encoded_list(&method("GetPost", false), Some(&streamed)));
+        let export = storage::MethodOptions { operation: "list".to_string(), ..streamed };
+        assert!(has_encoded_list(&method("ExportPosts", false), Some(&export)));
+    }
+
+    #[test]
+    fn test_tree_functions_use_column_names_and_skip_deleted() {
+        use crate::storage::seaorm::options::cache_entity_options;
+
+        let file_name = "test/defaults_tree.proto";
+        let file = FileDescriptorProto {
+            name: Some(file_name.to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Category".to_string()),
+                field: vec![
+                    field("id", 1, Type::Int64),
+                    field("parent_id", 2, Type::Int64),
+                    message_field("deleted_at", 3, ".google.protobuf.Timestamp"),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let column = |name: &str| storage::ColumnOptions { column_name: name.to_string(), ..Default::default() };
+        cache_column_options(file_name, "Category", 1, column("category_id"));
+        cache_column_options(file_name, "Category", 2, column("parent_category_id"));
+        cache_column_options(file_name, "Category", 3, column("removed_at"));
+        cache_entity_options(
+            file_name,
+            "Category",
+            storage::EntityOptions { soft_delete: true, ..Default::default() },
+        );
+        let relation = storage::RelationDef {
+            name: "parent".to_string(),
+            related: "Category".to_string(),
+            foreign_key: "parent_id".to_string(),
+            recursive: true,
+            ..Default::default()
+        };
+
+        let code = generate_tree_functions(&file, std::slice::from_ref(&file), "Category", &relation).to_string();
+        assert!(code.contains(
+            "WHERE t.category_id = (SELECT s.parent_category_id FROM {table} s WHERE s.category_id = {param}) AND t.removed_at IS NULL"
+        ));
+        assert!(code.contains("ON t.category_id = a.parent_category_id WHERE a.depth < 256 AND t.removed_at IS NULL"));
+        assert!(code.contains("WHERE t.parent_category_id = {param} AND t.removed_at IS NULL"));
+        assert!(code.contains("ON t.parent_category_id = d.category_id WHERE d.depth < 256 AND t.removed_at IS NULL"));
+        assert!(!code.contains("t.parent_id"));
+    }
+
+    #[test]
+    fn test_polymorphic_relations_of_entities_in_other_files() {
+        use crate::storage::seaorm::options::{cache_entity_options, cache_file_package};
+
+        let entity_file = "test/defaults_poly_entities.proto";
+        let service_file = FileDescriptorProto {
+            name: Some("test/defaults_poly_services.proto".to_string()),
+            package: Some("defaults_poly".to_string()),
+            ..Default::default()
+        };
+        cache_file_package(entity_file, "defaults_poly");
+        cache_file_package("test/defaults_poly_services.proto", "defaults_poly");
+        let relation = storage::RelationDef {
+            name: "commentable".to_string(),
+            r#type: storage::RelationType::Polymorphic as i32,
+            foreign_key: "commentable_id".to_string(),
+            type_column: "commentable_type".to_string(),
+            candidates: vec!["Post".to_string(), "shop.Product".to_string()],
+            ..Default::default()
+        };
+        cache_entity_options(
+            entity_file,
+            "Comment",
+            storage::EntityOptions { relations: vec![relation], ..Default::default() },
+        );
+        let service = ServiceDescriptorProto {
+            name: Some("CommentService".to_string()),
+            method: vec![MethodDescriptorProto { name: Some("GetComment".to_string()), ..Default::default() }],
+            ..Default::default()
+        };
+
+        let relations = polymorphic_relations(&service_file, &service);
+        assert_eq!(relations.len(), 1);
+        assert_eq!(relations[0].0, "Comment");
+        assert_eq!(relations[0].1.candidates, ["Post"]);
+    }
+
+    #[test]
+    fn test_tree_functions_of_entities_in_other_files() {
+        use crate::storage::seaorm::options::{cache_entity_options, cache_file_package};
+
+        let entity_file = FileDescriptorProto {
+            name: Some("test/defaults_tree_entities.proto".to_string()),
+            package: Some("defaults_tree_split".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Folder".to_string()),
+                field: vec![
+                    field("id", 1, Type::Int64),
+                    field("parent_id", 2, Type::Int64),
+                    message_field("deleted_at", 3, ".google.protobuf.Timestamp"),
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let service_file = FileDescriptorProto {
+            name: Some("test/defaults_tree_services.proto".to_string()),
+            package: Some("defaults_tree_split".to_string()),
+            ..Default::default()
+        };
+        cache_file_package("test/defaults_tree_entities.proto", "defaults_tree_split");
+        cache_file_package("test/defaults_tree_services.proto", "defaults_tree_split");
+        let relation = storage::RelationDef {
+            name: "parent".to_string(),
+            r#type: storage::RelationType::BelongsTo as i32,
+            related: "Folder".to_string(),
+            foreign_key: "parent_id".to_string(),
+            recursive: true,
+            ..Default::default()
+        };
+        cache_entity_options(
+            "test/defaults_tree_entities.proto",
+            "Folder",
+            storage::EntityOptions { soft_delete: true, relations: vec![relation], ..Default::default() },
+        );
+        let service = ServiceDescriptorProto {
+            name: Some("FolderService".to_string()),
+            method: vec![MethodDescriptorProto { name: Some("GetFolder".to_string()), ..Default::default() }],
+            ..Default::default()
+        };
+
+        let relations = recursive_relations(&service_file, &service);
+        assert_eq!(relations.len(), 1);
+        let (entity_name, relation) = &relations[0];
+        assert_eq!(entity_name, "Folder");
+
+        let files = [entity_file, service_file.clone()];
+        let code = generate_tree_functions(&service_file, &files, entity_name, relation).to_string();
+        assert!(code.contains("WHERE t.parent_id = {param} AND t.deleted_at IS NULL"));
+        assert!(code.contains("ON t.parent_id = d.id WHERE d.depth < 256 AND t.deleted_at IS NULL"));
+    }
+}
@@ -0,0 +1,681 @@
+//! Feed and sitemap generation
+//!
+//! Generates a `{service}_feed` storage module for services that list
+//! `public_feed` entities: an axum router serving RSS and Atom feeds per
+//! entity plus a sitemap.xml, all read through the service's storage trait
+//! List method so soft-delete and filter handling stay in one place. Links
+//! to entities whose ids the API hides use their external ids.
+
+use super::seaorm::external_id::hides_ids;
+use super::seaorm::options::{get_cached_entity_options, get_cached_rpc_method_options, get_cached_service_options};
+use crate::error::GeneratorError;
+use crate::params::time_crate;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::Type;
+use prost_types::{DescriptorProto, FileDescriptorProto, ServiceDescriptorProto};
+use quote::{format_ident, quote};
+
+/// Candidate columns for item titles, in preference order
+const TITLE_FIELDS: &[&str] = &["title", "name", "slug"];
+
+/// Candidate columns for item summaries, in preference order
+const SUMMARY_FIELDS: &[&str] = &["summary", "excerpt", "description", "content", "body"];
+
+/// Candidate timestamp columns for item dates and ordering, in preference order
+const DATE_FIELDS: &[&str] = &["published_at", "updated_at", "created_at"];
+
+/// An entity served as a feed, with the List method that feeds it
+#[derive(Debug, Clone, PartialEq)]
+struct FeedSource {
+    /// Entity message name
+    entity: String,
+    /// Table name, used for feed paths and item links
+    table: String,
+    /// Storage trait method (e.g. `list_posts`)
+    method: String,
+    /// List request message name
+    request: String,
+    /// Filter message name, when it can filter on `published`
+    published_filter: Option<String>,
+    /// Order-by message name, when it can order on the date column
+    order_by: Option<String>,
+    /// Whether the request has `first`/`after` cursor paging
+    has_paging: bool,
+    /// Whether item links use external ids
+    hides_id: bool,
+    title: Option<FieldRef>,
+    summary: Option<FieldRef>,
+    date: String,
+}
+
+/// A string column read from the proto message
+#[derive(Debug, Clone, PartialEq)]
+struct FieldRef {
+    name: String,
+    optional: bool,
+}
+
+/// Find a message by (possibly qualified) type name
+fn find_message<'a>(all_files: &'a [FileDescriptorProto], type_name: &str) -> Option<&'a DescriptorProto> {
+    let name = type_name.rsplit('.').next().unwrap_or(type_name);
+    all_files
+        .iter()
+        .flat_map(|f| f.message_type.iter())
+        .find(|m| m.name.as_deref() == Some(name))
+}
+
+/// Message type name of a field, if it is a message field
+fn field_message<'a>(message: &'a DescriptorProto, field: &str) -> Option<&'a str> {
+    message
+        .field
+        .iter()
+        .find(|f| f.name.as_deref() == Some(field) && f.r#type() == Type::Message)
+        .and_then(|f| f.type_name.as_deref())
+}
+
+fn has_field(message: &DescriptorProto, field: &str) -> bool {
+    message.field.iter().any(|f| f.name.as_deref() == Some(field))
+}
+
+/// First candidate that is a string field of the message
+fn string_field(message: &DescriptorProto, candidates: &[&str]) -> Option<FieldRef> {
+    candidates.iter().find_map(|name| {
+        message
+            .field
+            .iter()
+            .find(|f| f.name.as_deref() == Some(*name) && f.r#type() == Type::String)
+            .map(|f| FieldRef {
+                name: name.to_string(),
+                optional: f.proto3_optional.unwrap_or(false),
+            })
+    })
+}
+
+/// Collect the feed sources listed by a service
+fn collect_sources(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Vec<FeedSource>, GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let service_name = service.name.as_deref().unwrap_or("");
+    let mut sources = Vec::new();
+
+    for method in &service.method {
+        let method_name = method.name.as_deref().unwrap_or("");
+        let output = method.output_type.as_deref().unwrap_or("");
+        let output = output.rsplit('.').next().unwrap_or(output);
+        let Some(entity_name) = output.strip_suffix("Connection") else {
+            continue;
+        };
+        if !method_name.starts_with("List") {
+            continue;
+        }
+
+        let Some((entity_file, entity)) = all_files.iter().find_map(|f| {
+            f.message_type
+                .iter()
+                .find(|m| m.name.as_deref() == Some(entity_name))
+                .map(|m| (f, m))
+        }) else {
+            continue;
+        };
+        let Some(entity_options) =
+            get_cached_entity_options(entity_file.name.as_deref().unwrap_or(""), entity_name)
+        else {
+            continue;
+        };
+        if entity_options.skip || !entity_options.public_feed {
+            continue;
+        }
+
+        let method_options = get_cached_rpc_method_options(file_name, service_name, method_name);
+        if method_options.as_ref().is_some_and(|o| o.skip) {
+            continue;
+        }
+        let rust_method = method_options
+            .filter(|o| !o.method_name.is_empty())
+            .map(|o| o.method_name)
+            .unwrap_or_else(|| method_name.to_snake_case());
+
+        let date = DATE_FIELDS
+            .iter()
+            .find(|name| {
+                field_message(entity, name).is_some_and(|t| t.ends_with("google.protobuf.Timestamp"))
+            })
+            .ok_or_else(|| {
+                GeneratorError::CodeGenError(format!(
+                    "public_feed entity {} needs a published_at, updated_at, or created_at timestamp",
+                    entity_name
+                ))
+            })?
+            .to_string();
+
+        let input = method.input_type.as_deref().unwrap_or("");
+        let request = find_message(all_files, input);
+        let published_column = entity
+            .field
+            .iter()
+            .any(|f| f.name.as_deref() == Some("published") && f.r#type() == Type::Bool);
+        let published_filter = request
+            .and_then(|r| field_message(r, "filter"))
+            .filter(|_| published_column)
+            .filter(|t| find_message(all_files, t).is_some_and(|m| has_field(m, "published")))
+            .map(|t| t.rsplit('.').next().unwrap_or(t).to_string());
+        let order_by = request
+            .and_then(|r| field_message(r, "order_by"))
+            .filter(|t| find_message(all_files, t).is_some_and(|m| has_field(m, &date)))
+            .map(|t| t.rsplit('.').next().unwrap_or(t).to_string());
+
+        let table = if entity_options.table_name.is_empty() {
+            entity_name.to_snake_case()
+        } else {
+            entity_options.table_name.clone()
+        };
+
+        sources.push(FeedSource {
+            entity: entity_name.to_string(),
+            table,
+            method: rust_method,
+            request: input.rsplit('.').next().unwrap_or(input).to_string(),
+            published_filter,
+            order_by,
+            has_paging: request.is_some_and(|r| has_field(r, "first") && has_field(r, "after")),
+            hides_id: hides_ids(all_files, file, entity_name),
+            title: string_field(entity, TITLE_FIELDS),
+            summary: string_field(entity, SUMMARY_FIELDS),
+            date,
+        });
+    }
+
+    Ok(sources)
+}
+
+/// Whether a service gets a feed module
+///
+/// Feeds read through the storage trait, so the service must generate storage.
+pub fn has_feeds(
+    file: &FileDescriptorProto,
+    service_name: &str,
+    all_files: &[FileDescriptorProto],
+) -> bool {
+    storage_enabled(file, service_name)
+        && file
+            .service
+            .iter()
+            .find(|s| s.name.as_deref() == Some(service_name))
+            .and_then(|s| collect_sources(file, s, all_files).ok())
+            .is_some_and(|sources| !sources.is_empty())
+}
+
+/// Whether a service generates a storage trait
+fn storage_enabled(file: &FileDescriptorProto, service_name: &str) -> bool {
+    let file_name = file.name.as_deref().unwrap_or("");
+    get_cached_service_options(file_name, service_name).is_some_and(|o| !o.skip && o.generate_storage)
+}
+
+/// Generate the storage/{service}_feed.rs file for a service
+pub fn generate(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    let service_name = service.name.as_deref().unwrap_or("");
+    if package.is_empty() {
+        return Ok(None);
+    }
+
+    // Feeds read through the storage trait
+    if !storage_enabled(file, service_name) {
+        return Ok(None);
+    }
+
+    let sources = collect_sources(file, service, all_files)?;
+    if sources.is_empty() {
+        return Ok(None);
+    }
+
+    let trait_ident = format_ident!("{}Storage", service_name.to_upper_camel_case());
+    let mut type_imports: Vec<String> = Vec::new();
+    for source in &sources {
+        type_imports.push(source.request.clone());
+        type_imports.extend(source.published_filter.clone());
+        type_imports.extend(source.order_by.clone());
+    }
+    type_imports.sort();
+    type_imports.dedup();
+    let type_imports = type_imports.iter().map(|t| format_ident!("{}", t));
+
+    let routes = sources.iter().map(|s| {
+        let rss_path = format!("/feeds/{}.rss", s.table);
+        let atom_path = format!("/feeds/{}.atom", s.table);
+        let rss_fn = format_ident!("{}_rss", s.table);
+        let atom_fn = format_ident!("{}_atom", s.table);
+        quote! {
+            .route(#rss_path, get(#rss_fn))
+            .route(#atom_path, get(#atom_fn))
+        }
+    });
+    let source_items: Vec<TokenStream> = sources.iter().map(generate_source).collect();
+    let sitemap_sources = sources.iter().map(|s| {
+        let items_fn = format_ident!("{}_items", s.table);
+        quote! { #items_fn(&state, true).await }
+    });
+    let helpers = generate_helpers();
+
+    let module_doc = format!("RSS/Atom feeds and sitemap for {}", service_name);
+
+    let code = quote! {
+        #![doc = #module_doc]
+        //!
+        //! Merge `router(storage, config)` into the application router.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use std::sync::Arc;
+        use axum::extract::State;
+        use axum::http::{header, StatusCode};
+        use axum::response::{IntoResponse, Response};
+        use axum::routing::get;
+        use axum::Router;
+        use super::{#trait_ident, StorageError};
+        use super::super::{#(#type_imports),*};
+        use super::super::super::synapse::relay::{BoolFilter, OrderDirection};
+
+        /// Feed settings
+        #[derive(Debug, Clone)]
+        pub struct FeedConfig {
+            /// Public site URL; items link to `{base_url}/{table}/{id}`, with
+            /// the external id of entities whose ids the API hides
+            pub base_url: String,
+            /// Feed title
+            pub title: String,
+            /// Items per feed
+            pub limit: i32,
+        }
+
+        impl FeedConfig {
+            pub fn new(base_url: impl Into<String>, title: impl Into<String>) -> Self {
+                Self {
+                    base_url: base_url.into().trim_end_matches('/').to_string(),
+                    title: title.into(),
+                    limit: 50,
+                }
+            }
+
+            fn link(&self, path: &str) -> String {
+                format!("{}/{}", self.base_url, path)
+            }
+        }
+
+        #[derive(Clone)]
+        struct FeedState {
+            storage: Arc<dyn #trait_ident>,
+            config: Arc<FeedConfig>,
+        }
+
+        /// A feed entry read from storage
+        struct FeedItem {
+            /// Path below the base URL (`{table}/{id}`)
+            path: String,
+            title: String,
+            summary: String,
+            updated: prost_types::Timestamp,
+        }
+
+        /// Router serving the feeds and `/sitemap.xml`
+        pub fn router<S>(storage: Arc<dyn #trait_ident>, config: FeedConfig) -> Router<S>
+        where
+            S: Clone + Send + Sync + 'static,
+        {
+            Router::new()
+                #(#routes)*
+                .route("/sitemap.xml", get(sitemap))
+                .with_state(FeedState {
+                    storage,
+                    config: Arc::new(config),
+                })
+        }
+
+        async fn sitemap(State(state): State<FeedState>) -> Response {
+            let mut items = Vec::new();
+            for result in [#(#sitemap_sources),*] {
+                match result {
+                    Ok(found) => items.extend(found),
+                    Err(_) => return unavailable(),
+                }
+            }
+            xml_response("application/xml; charset=utf-8", render_sitemap(&state.config, &items))
+        }
+
+        #(#source_items)*
+
+        #helpers
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!(
+        "{}/storage/{}_feed.rs",
+        package.replace('.', "/"),
+        service_name.to_snake_case()
+    );
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the item loader and feed handlers for one entity
+fn generate_source(source: &FeedSource) -> TokenStream {
+    let items_fn = format_ident!("{}_items", source.table);
+    let rss_fn = format_ident!("{}_rss", source.table);
+    let atom_fn = format_ident!("{}_atom", source.table);
+    let method = format_ident!("{}", source.method);
+    let request = format_ident!("{}", source.request);
+    let table = &source.table;
+    let date = format_ident!("{}", source.date);
+    let fallback_title = format!("{} {{}}", source.entity);
+    let entity = &source.entity;
+    let id = if source.hides_id {
+        quote! { super::super::ids::external_id(#entity, &node.id) }
+    } else {
+        quote! { node.id }
+    };
+
+    let filter = match &source.published_filter {
+        Some(filter) => {
+            let filter = format_ident!("{}", filter);
+            quote! {
+                filter: Some(#filter {
+                    published: Some(BoolFilter { eq: Some(true), ..Default::default() }),
+                    ..Default::default()
+                }),
+            }
+        }
+        None => quote! {},
+    };
+    // Feeds take one page, newest first; the sitemap follows id cursors
+    let paging = if source.has_paging {
+        quote! {
+            first: Some(if sitemap { SITEMAP_PAGE_SIZE } else { state.config.limit }),
+            after: after.take(),
+        }
+    } else {
+        quote! {}
+    };
+    let next_page = if source.has_paging {
+        quote! {
+            let next = response.page_info.as_ref().filter(|p| p.has_next_page).and_then(|p| p.end_cursor.clone());
+        }
+    } else {
+        quote! { let next: Option<String> = None; }
+    };
+    let order_by = match &source.order_by {
+        Some(order_by) => {
+            let order_by = format_ident!("{}", order_by);
+            quote! {
+                order_by: if sitemap {
+                    None
+                } else {
+                    Some(#order_by {
+                        #date: Some(OrderDirection::Desc as i32),
+                        ..Default::default()
+                    })
+                },
+            }
+        }
+        None => quote! {},
+    };
+
+    let title = match &source.title {
+        Some(FieldRef { name, optional }) => {
+            let field = format_ident!("{}", name);
+            if *optional {
+                quote! { node.#field.clone().unwrap_or_default() }
+            } else {
+                quote! { node.#field.clone() }
+            }
+        }
+        None => quote! { format!(#fallback_title, node.id) },
+    };
+    let summary = match &source.summary {
+        Some(FieldRef { name, optional }) => {
+            let field = format_ident!("{}", name);
+            if *optional {
+                quote! { node.#field.clone().unwrap_or_default() }
+            } else {
+                quote! { node.#field.clone() }
+            }
+        }
+        None => quote! { String::new() },
+    };
+
+    quote! {
+        /// Read published rows: one page newest first, or every row for the sitemap
+        async fn #items_fn(state: &FeedState, sitemap: bool) -> Result<Vec<FeedItem>, StorageError> {
+            let mut items = Vec::new();
+            let mut after: Option<String> = None;
+            loop {
+                let request = #request {
+                    #filter
+                    #paging
+                    #order_by
+                    ..Default::default()
+                };
+                let response = state.storage.#method(request).await?;
+                #next_page
+                items.extend(response.edges.into_iter().filter_map(|e| e.node).map(|node| FeedItem {
+                    path: format!("{}/{}", #table, #id),
+                    title: #title,
+                    summary: #summary,
+                    updated: node.#date.clone().unwrap_or_default(),
+                }));
+                match next {
+                    Some(cursor) if sitemap && items.len() < SITEMAP_MAX_URLS => after = Some(cursor),
+                    _ => return Ok(items),
+                }
+            }
+        }
+
+        async fn #rss_fn(State(state): State<FeedState>) -> Response {
+            match #items_fn(&state, false).await {
+                Ok(items) => xml_response(
+                    "application/rss+xml; charset=utf-8",
+                    render_rss(&state.config, #table, &items),
+                ),
+                Err(_) => unavailable(),
+            }
+        }
+
+        async fn #atom_fn(State(state): State<FeedState>) -> Response {
+            match #items_fn(&state, false).await {
+                Ok(items) => xml_response(
+                    "application/atom+xml; charset=utf-8",
+                    render_atom(&state.config, #table, &items),
+                ),
+                Err(_) => unavailable(),
+            }
+        }
+    }
+}
+
+/// Generate the XML rendering helpers
+fn generate_helpers() -> TokenStream {
+    let (rfc3339, rfc2822) = (time_crate().proto_to_rfc3339(), time_crate().proto_to_rfc2822());
+
+    quote! {
+        /// URLs allowed in a single sitemap file
+        const SITEMAP_MAX_URLS: usize = 50_000;
+
+        /// Rows fetched per storage call while building the sitemap
+        const SITEMAP_PAGE_SIZE: i32 = 500;
+
+        fn rfc3339(t: &prost_types::Timestamp) -> String {
+            #rfc3339
+        }
+
+        fn rfc2822(t: &prost_types::Timestamp) -> String {
+            #rfc2822
+        }
+
+        /// Escape text for XML content and attributes
+        fn escape(value: &str) -> String {
+            let mut escaped = String::with_capacity(value.len());
+            for c in value.chars() {
+                match c {
+                    '&' => escaped.push_str("&amp;"),
+                    '<' => escaped.push_str("&lt;"),
+                    '>' => escaped.push_str("&gt;"),
+                    '"' => escaped.push_str("&quot;"),
+                    '\'' => escaped.push_str("&apos;"),
+                    _ => escaped.push(c),
+                }
+            }
+            escaped
+        }
+
+        fn xml_response(content_type: &'static str, body: String) -> Response {
+            ([(header::CONTENT_TYPE, content_type)], body).into_response()
+        }
+
+        fn unavailable() -> Response {
+            (StatusCode::SERVICE_UNAVAILABLE, "feed unavailable").into_response()
+        }
+
+        fn render_rss(config: &FeedConfig, table: &str, items: &[FeedItem]) -> String {
+            let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rss version=\"2.0\"><channel>");
+            xml.push_str(&format!(
+                "<title>{}</title><link>{}</link><description>{}</description>",
+                escape(&config.title),
+                escape(&config.link(table)),
+                escape(&config.title),
+            ));
+            for item in items {
+                let link = escape(&config.link(&item.path));
+                xml.push_str(&format!(
+                    "<item><title>{}</title><link>{}</link><guid>{}</guid><pubDate>{}</pubDate><description>{}</description></item>",
+                    escape(&item.title),
+                    link,
+                    link,
+                    rfc2822(&item.updated),
+                    escape(&item.summary),
+                ));
+            }
+            xml.push_str("</channel></rss>");
+            xml
+        }
+
+        fn render_atom(config: &FeedConfig, table: &str, items: &[FeedItem]) -> String {
+            let updated = items
+                .iter()
+                .map(|i| &i.updated)
+                .max_by_key(|t| (t.seconds, t.nanos))
+                .cloned()
+                .unwrap_or_default();
+            let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">");
+            xml.push_str(&format!(
+                "<title>{}</title><id>{}</id><link rel=\"self\" href=\"{}\"/><updated>{}</updated>",
+                escape(&config.title),
+                escape(&config.link(table)),
+                escape(&config.link(&format!("feeds/{}.atom", table))),
+                rfc3339(&updated),
+            ));
+            for item in items {
+                let link = escape(&config.link(&item.path));
+                xml.push_str(&format!(
+                    "<entry><title>{}</title><id>{}</id><link href=\"{}\"/><updated>{}</updated><summary>{}</summary></entry>",
+                    escape(&item.title),
+                    link,
+                    link,
+                    rfc3339(&item.updated),
+                    escape(&item.summary),
+                ));
+            }
+            xml.push_str("</feed>");
+            xml
+        }
+
+        fn render_sitemap(config: &FeedConfig, items: &[FeedItem]) -> String {
+            let mut xml = String::from(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">",
+            );
+            for item in items.iter().take(SITEMAP_MAX_URLS) {
+                xml.push_str(&format!(
+                    "<url><loc>{}</loc><lastmod>{}</lastmod></url>",
+                    escape(&config.link(&item.path)),
+                    rfc3339(&item.updated),
+                ));
+            }
+            xml.push_str("</urlset>");
+            xml
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> FeedSource {
+        FeedSource {
+            entity: "Post".to_string(),
+            table: "posts".to_string(),
+            method: "list_posts".to_string(),
+            request: "ListPostsRequest".to_string(),
+            published_filter: Some("PostFilter".to_string()),
+            order_by: Some("PostOrderBy".to_string()),
+            has_paging: true,
+            hides_id: false,
+            title: Some(FieldRef { name: "title".to_string(), optional: false }),
+            summary: None,
+            date: "created_at".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_generate_source() {
+        let code = generate_source(&source()).to_string();
+        assert!(code.contains("async fn posts_items"));
+        assert!(code.contains("async fn posts_rss"));
+        assert!(code.contains("async fn posts_atom"));
+        assert!(code.contains("published : Some (BoolFilter { eq : Some (true)"));
+        assert!(code.contains("created_at : Some (OrderDirection :: Desc as i32)"));
+        assert!(code.contains("state . storage . list_posts (request)"));
+        assert!(code.contains("format ! (\"{}/{}\" , \"posts\" , node . id)"));
+    }
+
+    #[test]
+    fn test_generate_source_with_hidden_ids() {
+        let mut source = source();
+        source.hides_id = true;
+        let code = generate_source(&source).to_string();
+        assert!(code.contains("super :: super :: ids :: external_id (\"Post\" , & node . id)"));
+    }
+
+    #[test]
+    fn test_generate_source_without_published_filter() {
+        let mut source = source();
+        source.published_filter = None;
+        source.title = None;
+        let code = generate_source(&source).to_string();
+        assert!(!code.contains("BoolFilter {"));
+        assert!(code.contains("\"Post {}\""));
+    }
+
+    #[test]
+    fn test_generate_helpers_escapes_xml() {
+        let code = generate_helpers().to_string();
+        assert!(code.contains("&amp;"));
+        assert!(code.contains("http://www.sitemaps.org/schemas/sitemap/0.9"));
+        assert!(code.contains("http://www.w3.org/2005/Atom"));
+    }
+}
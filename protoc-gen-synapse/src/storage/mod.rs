@@ -4,10 +4,16 @@
 //! - Trait generation for backend-agnostic storage interfaces
 //! - Default implementations as standalone functions for partial overrides
 //! - Backend implementations (SeaORM, Ecto, Diesel, etc.)
+//! - RSS/Atom feed and sitemap routers over the storage traits
+//! - Cache decorators wrapping the storage traits
 
+mod cache;
 pub mod defaults;
+mod feed;
 pub mod seaorm;
 mod traits;
 
+pub use cache::{generate as generate_cache, has_cache, package_has_cache};
 pub use defaults::generate as generate_defaults;
+pub use feed::{generate as generate_feed, has_feeds};
 pub use traits::generate;
@@ -0,0 +1,266 @@
+//! Stable API facade generation
+//!
+//! Generates the api.rs module: a curated set of re-exports that downstream
+//! crates can depend on (proto messages, entity models, service traits and
+//! clients, storage, gRPC services, domain types, and the GraphQL schema
+//! builder), plus an api.json manifest listing that surface. Everything else
+//! the package generates is an implementation detail that may change between
+//! plugin versions; the plumbing modules are `#[doc(hidden)]`.
+//!
+//! A consumer can commit api.json and diff it after regenerating: a removed
+//! or renamed entry is a breaking change.
+
+use super::ir::Json;
+use super::package::{collect_package_info_all_files, has_module, PackageInfo};
+use crate::error::GeneratorError;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::{format_ident, quote};
+
+
+/// One re-export of the stable API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiItem {
+    /// Name in the api module
+    pub name: String,
+    /// Path of the item within the package module
+    pub path: String,
+    /// What the item is: message, model, entity, column, type, server,
+    /// client, storage, storage_impl, grpc_service, domain_type, graphql, error
+    pub kind: &'static str,
+}
+
+impl ApiItem {
+    fn new(name: impl Into<String>, path: impl Into<String>, kind: &'static str) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
+            kind,
+        }
+    }
+}
+
+/// Generate api.rs and api.json for a package
+///
+/// Items of package modules missing from `produced` are left out.
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+    produced: &[String],
+) -> Result<Vec<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let info = collect_package_info_all_files(all_files, file);
+    if info.entities.is_empty() && info.services.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let dir = package.replace('.', "/");
+    let mut items = collect_items(&info);
+    items.retain(|item| is_available(item, |module| has_module(produced, &dir, module)));
+    let mut internal = internal_modules(&info);
+    internal.retain(|module| {
+        let top = module.split("::").next().unwrap_or(module);
+        top == "prelude" || has_module(produced, &dir, top)
+    });
+    Ok(vec![
+        generate_module(&dir, &items),
+        generate_manifest(&dir, package, &items, &internal),
+    ])
+}
+
+/// The stable surface of a package, in api module order
+pub fn collect_items(info: &PackageInfo) -> Vec<ApiItem> {
+    let mut items = Vec::new();
+
+    for entity in &info.entities {
+        let camel = entity.to_upper_camel_case();
+        let module = entity.to_snake_case();
+        items.push(ApiItem::new(&camel, &camel, "message"));
+        items.push(ApiItem::new(format!("{}Model", camel), format!("entities::{}::Model", module), "model"));
+        items.push(ApiItem::new(format!("{}Entity", camel), format!("entities::{}::Entity", module), "entity"));
+        items.push(ApiItem::new(format!("{}Column", camel), format!("entities::{}::Column", module), "column"));
+    }
+    if !info.entities.is_empty() {
+        items.push(ApiItem::new("FieldChange", "prelude::FieldChange", "type"));
+    }
+
+    for svc in &info.services {
+        let camel = svc.to_upper_camel_case();
+        let snake = svc.to_snake_case();
+        items.push(ApiItem::new(
+            format!("{}Server", camel),
+            format!("{}_server::{}Server", snake, camel),
+            "server",
+        ));
+        items.push(ApiItem::new(
+            format!("{}Client", camel),
+            format!("{}_client::{}Client", snake, camel),
+            "client",
+        ));
+        items.push(ApiItem::new(format!("{}Storage", camel), format!("storage::{}Storage", camel), "storage"));
+        items.push(ApiItem::new(
+            format!("SeaOrm{}Storage", camel),
+            format!("storage::SeaOrm{}Storage", camel),
+            "storage_impl",
+        ));
+        items.push(ApiItem::new(
+            format!("{}GrpcService", camel),
+            format!("grpc::{}GrpcService", camel),
+            "grpc_service",
+        ));
+    }
+    if !info.services.is_empty() {
+        items.push(ApiItem::new("StorageError", "storage::StorageError", "error"));
+    }
+
+    for domain_type in &info.domain_types {
+        let camel = domain_type.to_upper_camel_case();
+        items.push(ApiItem::new(
+            &camel,
+            format!("{}::{}", domain_type.to_snake_case(), camel),
+            "domain_type",
+        ));
+    }
+
+    items.push(ApiItem::new("AppSchema", "graphql::AppSchema", "graphql"));
+    items.push(ApiItem::new("build_schema", "graphql::build_schema", "graphql"));
+    items.push(ApiItem::new("schema_sdl", "graphql::schema_sdl", "graphql"));
+
+    items
+}
+
+/// Whether an item's module exists, given which package modules were produced
+///
+/// Messages, the tonic server and client modules, and the prelude are part
+/// of the package's own mod.rs and always exist.
+fn is_available(item: &ApiItem, has_module: impl Fn(&str) -> bool) -> bool {
+    match item.path.split_once("::") {
+        None => true,
+        Some((module, _)) => {
+            module == "prelude" || module.ends_with("_server") || module.ends_with("_client") || has_module(module)
+        }
+    }
+}
+
+/// Package modules that only exist for generated code, marked `#[doc(hidden)]`
+pub fn internal_modules(info: &PackageInfo) -> Vec<&'static str> {
+    let mut modules = vec!["prelude"];
+    if !info.entities.is_empty() {
+        modules.push("registry");
+    }
+    if !info.services.is_empty() {
+        modules.push("storage::conversions");
+    }
+    modules
+}
+
+fn generate_module(dir: &str, items: &[ApiItem]) -> File {
+    let uses = items.iter().map(|item| {
+        let segments = item.path.split("::").map(|s| format_ident!("{}", s));
+        let last = item.path.rsplit("::").next().unwrap_or(&item.path);
+        if last == item.name {
+            quote! { pub use super::#(#segments)::*; }
+        } else {
+            let name = format_ident!("{}", item.name);
+            quote! { pub use super::#(#segments)::* as #name; }
+        }
+    });
+
+    let code = quote! {
+        //! Stable API of this package
+        //!
+        //! Downstream crates should import generated items from here. These
+        //! names are kept across regenerations and listed in api.json; other
+        //! modules are implementation details and may change.
+        //! @generated
+
+        #(#uses)*
+    };
+
+    File {
+        name: Some(format!("{}/api.rs", dir)),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    }
+}
+
+fn generate_manifest(dir: &str, package: &str, items: &[ApiItem], internal: &[&str]) -> File {
+    let stable = items
+        .iter()
+        .map(|item| {
+            Json::object(vec![
+                ("name", Json::string(&item.name)),
+                ("path", Json::string(&item.path)),
+                ("kind", Json::string(item.kind)),
+            ])
+        })
+        .collect();
+    let internal = internal.iter().map(|m| Json::string(m)).collect();
+
+    let mut content = String::new();
+    Json::object(vec![
+        ("package", Json::string(package)),
+        ("stable", Json::Array(stable)),
+        ("internal", Json::Array(internal)),
+    ])
+    .write(&mut content, 0);
+    content.push('\n');
+
+    File {
+        name: Some(format!("{}/api.json", dir)),
+        content: Some(content),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_available() {
+        let produced = ["blog/entities/mod.rs".to_string(), "blog/create_post.rs".to_string()];
+        let has = |module: &str| has_module(&produced, "blog", module);
+        assert!(is_available(&ApiItem::new("Post", "Post", "message"), has));
+        assert!(is_available(&ApiItem::new("PostModel", "entities::post::Model", "model"), has));
+        assert!(is_available(&ApiItem::new("CreatePost", "create_post::CreatePost", "domain_type"), has));
+        assert!(is_available(
+            &ApiItem::new("PostServiceClient", "post_service_client::PostServiceClient", "client"),
+            has
+        ));
+        assert!(!is_available(&ApiItem::new("StorageError", "storage::StorageError", "error"), has));
+        assert!(!is_available(&ApiItem::new("AppSchema", "graphql::AppSchema", "graphql"), has));
+    }
+
+    #[test]
+    fn test_collect_items() {
+        let info = PackageInfo {
+            entities: vec!["Post".to_string()],
+            services: vec!["PostService".to_string()],
+            domain_types: vec!["CreatePost".to_string()],
+            exports: Vec::new(),
+        };
+        let items = collect_items(&info);
+        assert!(items.contains(&ApiItem::new("PostModel", "entities::post::Model", "model")));
+        assert!(items.contains(&ApiItem::new(
+            "PostServiceClient",
+            "post_service_client::PostServiceClient",
+            "client"
+        )));
+        assert!(items.contains(&ApiItem::new("CreatePost", "create_post::CreatePost", "domain_type")));
+
+        let code = generate_module("blog", &items).content.unwrap();
+        assert!(code.contains("pub use super::Post;"));
+        assert!(code.contains("pub use super::entities::post::Model as PostModel;"));
+        assert!(code.contains("pub use super::storage::StorageError;"));
+
+        let manifest = generate_manifest("blog", "blog", &items, &internal_modules(&info)).content.unwrap();
+        assert!(manifest.contains("\"path\": \"grpc::PostServiceGrpcService\""));
+        assert!(manifest.contains("\"prelude\""));
+    }
+}
@@ -0,0 +1,432 @@
+//! Breaking-change detection
+//!
+//! With `baseline=<path>`, the plugin compares the requested packages against a
+//! descriptor set of the previous release (`protoc --include_imports
+//! --descriptor_set_out=api.binpb ...`) before generating. Generation fails,
+//! with a JSON report of the changes as the protoc error, when:
+//! - an entity was removed, or stopped being one (its table would be dropped)
+//! - an entity field was removed (column dropped) or changed type
+//! - a service or RPC was removed
+//! - an RPC changed request/response type or streaming, or a field of its
+//!   request/response kept its number under another type
+//!
+//! Additions, renamed RPC arguments, and new columns are compatible and pass.
+
+use super::ir::Json;
+use super::options::{get_cached_entity_options, parse_descriptor_set_entities};
+use crate::error::GeneratorError;
+use prost::Message;
+use prost_types::compiler::CodeGeneratorRequest;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{
+    DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet, MethodDescriptorProto,
+    ServiceDescriptorProto,
+};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Kind of breaking change, as printed in the report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    EntityRemoved,
+    ColumnRemoved,
+    ColumnRetyped,
+    ServiceRemoved,
+    RpcRemoved,
+    RpcChanged,
+}
+
+impl ChangeKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ChangeKind::EntityRemoved => "entity-removed",
+            ChangeKind::ColumnRemoved => "column-removed",
+            ChangeKind::ColumnRetyped => "column-retyped",
+            ChangeKind::ServiceRemoved => "service-removed",
+            ChangeKind::RpcRemoved => "rpc-removed",
+            ChangeKind::RpcChanged => "rpc-changed",
+        }
+    }
+}
+
+/// A breaking change of one entity, column, service, or RPC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakingChange {
+    pub kind: ChangeKind,
+    /// Fully qualified name of what changed, e.g. `blog.Post.title`
+    pub target: String,
+    pub detail: String,
+}
+
+impl BreakingChange {
+    fn to_json(&self) -> Json {
+        Json::object(vec![
+            ("kind", Json::string(self.kind.as_str())),
+            ("target", Json::string(&self.target)),
+            ("detail", Json::string(&self.detail)),
+        ])
+    }
+}
+
+impl fmt::Display for BreakingChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}: {}", self.kind.as_str(), self.target, self.detail)
+    }
+}
+
+/// Messages, entities, and services of one side of the comparison
+struct Schema<'a> {
+    /// Every message by fully qualified name (`blog.Post`)
+    messages: HashMap<String, &'a DescriptorProto>,
+    /// Entities of the checked packages, in declaration order
+    entities: Vec<String>,
+    /// Services of the checked packages, in declaration order
+    services: Vec<(String, &'a ServiceDescriptorProto)>,
+}
+
+impl<'a> Schema<'a> {
+    /// Index `files`, treating a top-level message as an entity when
+    /// `is_entity(file_name, message_name)`
+    fn new(files: &'a [FileDescriptorProto], packages: &[&str], is_entity: impl Fn(&str, &str) -> bool) -> Self {
+        let mut schema = Schema {
+            messages: HashMap::new(),
+            entities: Vec::new(),
+            services: Vec::new(),
+        };
+        for file in files {
+            let file_name = file.name.as_deref().unwrap_or("");
+            let package = file.package.as_deref().unwrap_or("");
+            let checked = packages.contains(&package);
+            for message in &file.message_type {
+                let msg_name = message.name.as_deref().unwrap_or("");
+                if checked && is_entity(file_name, msg_name) {
+                    schema.entities.push(qualify(package, msg_name));
+                }
+                schema.index_message(package, message);
+            }
+            if checked {
+                for service in &file.service {
+                    schema
+                        .services
+                        .push((qualify(package, service.name.as_deref().unwrap_or("")), service));
+                }
+            }
+        }
+        schema
+    }
+
+    fn index_message(&mut self, prefix: &str, message: &'a DescriptorProto) {
+        let name = qualify(prefix, message.name.as_deref().unwrap_or(""));
+        for nested in &message.nested_type {
+            self.index_message(&name, nested);
+        }
+        self.messages.insert(name, message);
+    }
+
+    fn service(&self, name: &str) -> Option<&'a ServiceDescriptorProto> {
+        self.services.iter().find(|(n, _)| n == name).map(|(_, s)| *s)
+    }
+}
+
+/// Check the request against the baseline descriptor set at `path`
+///
+/// Returns the JSON report when there are breaking changes.
+pub fn check(request: &CodeGeneratorRequest, path: &str) -> Result<Option<String>, GeneratorError> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| GeneratorError::CodeGenError(format!("cannot read baseline {}: {}", path, e)))?;
+    let baseline_set = FileDescriptorSet::decode(&bytes[..])
+        .map_err(|e| GeneratorError::DecodeError(format!("baseline {}: {}", path, e)))?;
+    let baseline_entities = parse_descriptor_set_entities(&bytes).map_err(GeneratorError::DecodeError)?;
+
+    let mut packages: Vec<&str> = Vec::new();
+    for file_name in &request.file_to_generate {
+        if let Some(file) = request.proto_file.iter().find(|f| f.name.as_ref() == Some(file_name)) {
+            let package = file.package.as_deref().unwrap_or("");
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+    }
+
+    let baseline = Schema::new(&baseline_set.file, &packages, |file, msg| {
+        baseline_entities
+            .get(&(file.to_string(), msg.to_string()))
+            .is_some_and(|o| !o.skip)
+    });
+    let current = Schema::new(&request.proto_file, &packages, |file, msg| {
+        get_cached_entity_options(file, msg).is_some_and(|o| !o.skip)
+    });
+
+    let changes = compare(&baseline, &current);
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(report(path, &changes)))
+}
+
+/// The report of breaking changes against the baseline at `path`
+fn report(path: &str, changes: &[BreakingChange]) -> String {
+    let json = Json::object(vec![
+        ("baseline", Json::string(path)),
+        ("changes", Json::Array(changes.iter().map(BreakingChange::to_json).collect())),
+    ]);
+    let mut out = String::new();
+    json.write(&mut out, 0);
+    out
+}
+
+/// Breaking changes from `baseline` to `current`
+fn compare(baseline: &Schema, current: &Schema) -> Vec<BreakingChange> {
+    let mut changes = Vec::new();
+
+    for entity in &baseline.entities {
+        let old = baseline.messages[entity];
+        let new = match current.messages.get(entity) {
+            Some(new) if current.entities.contains(entity) => *new,
+            _ => {
+                changes.push(BreakingChange {
+                    kind: ChangeKind::EntityRemoved,
+                    target: entity.clone(),
+                    detail: "entity no longer generated; its table would be dropped".to_string(),
+                });
+                continue;
+            }
+        };
+        for field in &old.field {
+            let field_name = field.name.as_deref().unwrap_or("");
+            let target = format!("{}.{}", entity, field_name);
+            match new.field.iter().find(|f| f.name == field.name) {
+                None => changes.push(BreakingChange {
+                    kind: ChangeKind::ColumnRemoved,
+                    target,
+                    detail: format!("{} column removed", field_type(field)),
+                }),
+                Some(f) if field_type(f) != field_type(field) => changes.push(BreakingChange {
+                    kind: ChangeKind::ColumnRetyped,
+                    target,
+                    detail: format!("{} -> {}", field_type(field), field_type(f)),
+                }),
+                Some(_) => {}
+            }
+        }
+    }
+
+    for (service_name, old) in &baseline.services {
+        let Some(new) = current.service(service_name) else {
+            changes.push(BreakingChange {
+                kind: ChangeKind::ServiceRemoved,
+                target: service_name.clone(),
+                detail: format!("service removed with {} RPC(s)", old.method.len()),
+            });
+            continue;
+        };
+        for method in &old.method {
+            let target = format!("{}.{}", service_name, method.name.as_deref().unwrap_or(""));
+            match new.method.iter().find(|m| m.name == method.name) {
+                None => changes.push(BreakingChange {
+                    kind: ChangeKind::RpcRemoved,
+                    target,
+                    detail: "RPC removed".to_string(),
+                }),
+                Some(m) => {
+                    for detail in signature_changes(baseline, current, method, m) {
+                        changes.push(BreakingChange {
+                            kind: ChangeKind::RpcChanged,
+                            target: target.clone(),
+                            detail,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    changes
+}
+
+/// Incompatible differences between two versions of an RPC
+fn signature_changes(
+    baseline: &Schema,
+    current: &Schema,
+    old: &MethodDescriptorProto,
+    new: &MethodDescriptorProto,
+) -> Vec<String> {
+    let mut details = Vec::new();
+    let sides = [
+        ("request", &old.input_type, &new.input_type, old.client_streaming(), new.client_streaming()),
+        ("response", &old.output_type, &new.output_type, old.server_streaming(), new.server_streaming()),
+    ];
+    for (side, old_type, new_type, old_streaming, new_streaming) in sides {
+        let old_type = old_type.as_deref().unwrap_or("").trim_start_matches('.');
+        let new_type = new_type.as_deref().unwrap_or("").trim_start_matches('.');
+        if old_type != new_type {
+            details.push(format!("{} type {} -> {}", side, old_type, new_type));
+            continue;
+        }
+        if old_streaming != new_streaming {
+            let streaming = |s: bool| if s { "streaming" } else { "unary" };
+            details.push(format!("{} {} -> {}", side, streaming(old_streaming), streaming(new_streaming)));
+        }
+        // Same message: a reused field number under another type breaks the wire format
+        let (Some(old_msg), Some(new_msg)) = (baseline.messages.get(old_type), current.messages.get(new_type)) else {
+            continue;
+        };
+        for field in &old_msg.field {
+            if let Some(f) = new_msg.field.iter().find(|f| f.number == field.number)
+                && field_type(f) != field_type(field)
+            {
+                details.push(format!(
+                    "{} field {} `{}`: {} -> {}",
+                    side,
+                    field.number.unwrap_or(0),
+                    f.name.as_deref().unwrap_or(""),
+                    field_type(field),
+                    field_type(f)
+                ));
+            }
+        }
+    }
+    details
+}
+
+/// Type of a field as written in proto, e.g. `repeated string` or `blog.Post`
+pub(super) fn field_type(field: &FieldDescriptorProto) -> String {
+    let ty = match field.r#type() {
+        Type::Message | Type::Enum | Type::Group => field.type_name.as_deref().unwrap_or("").trim_start_matches('.').to_string(),
+        other => other.as_str_name().trim_start_matches("TYPE_").to_lowercase(),
+    };
+    if field.label() == Label::Repeated {
+        format!("repeated {}", ty)
+    } else {
+        ty
+    }
+}
+
+/// `package.Name`, or `Name` without a package
+fn qualify(package: &str, name: &str) -> String {
+    if package.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", package, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{field, message_field};
+
+    fn file(fields: Vec<FieldDescriptorProto>, methods: Vec<MethodDescriptorProto>) -> FileDescriptorProto {
+        FileDescriptorProto {
+            name: Some("blog/entities.proto".to_string()),
+            package: Some("blog".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Post".to_string()),
+                field: fields,
+                ..Default::default()
+            }],
+            service: vec![ServiceDescriptorProto {
+                name: Some("PostService".to_string()),
+                method: methods,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn get_post() -> MethodDescriptorProto {
+        MethodDescriptorProto {
+            name: Some("GetPost".to_string()),
+            input_type: Some(".blog.Post".to_string()),
+            output_type: Some(".blog.Post".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn diff(old: &[FileDescriptorProto], new: &[FileDescriptorProto]) -> Vec<String> {
+        let baseline = Schema::new(old, &["blog"], |_, msg| msg == "Post");
+        let current = Schema::new(new, &["blog"], |_, msg| msg == "Post");
+        compare(&baseline, &current).iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_field_type() {
+        let mut tags = field("tags", 1, Type::String);
+        assert_eq!(field_type(&tags), "string");
+        tags.label = Some(Label::Repeated as i32);
+        assert_eq!(field_type(&tags), "repeated string");
+        let author = message_field("author", 2, ".blog.Author");
+        assert_eq!(field_type(&author), "blog.Author");
+    }
+
+    #[test]
+    fn test_compatible_changes_pass() {
+        let old = vec![file(vec![field("id", 1, Type::Int64)], vec![get_post()])];
+        let new = vec![file(
+            vec![field("id", 1, Type::Int64), field("title", 2, Type::String)],
+            vec![get_post()],
+        )];
+        assert!(diff(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_column_changes() {
+        let old = vec![file(
+            vec![field("id", 1, Type::Int64), field("title", 2, Type::String), field("views", 3, Type::Int32)],
+            Vec::new(),
+        )];
+        let new = vec![file(
+            vec![field("id", 1, Type::Int64), field("views", 3, Type::String)],
+            Vec::new(),
+        )];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                "column-removed blog.Post.title: string column removed",
+                "column-retyped blog.Post.views: int32 -> string",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rpc_changes() {
+        let old = vec![file(vec![field("id", 1, Type::Int64)], vec![get_post()])];
+        let mut streaming = get_post();
+        streaming.server_streaming = Some(true);
+        let new = vec![file(vec![field("id", 1, Type::String)], vec![streaming])];
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                "column-retyped blog.Post.id: int64 -> string",
+                "rpc-changed blog.PostService.GetPost: request field 1 `id`: int64 -> string",
+                "rpc-changed blog.PostService.GetPost: response unary -> streaming",
+                "rpc-changed blog.PostService.GetPost: response field 1 `id`: int64 -> string",
+            ]
+        );
+
+        let removed = vec![file(vec![field("id", 1, Type::Int64)], Vec::new())];
+        assert_eq!(diff(&old, &removed), vec!["rpc-removed blog.PostService.GetPost: RPC removed"]);
+    }
+
+    #[test]
+    fn test_report_is_json() {
+        let changes = vec![BreakingChange {
+            kind: ChangeKind::ColumnRemoved,
+            target: "blog.Post.title".to_string(),
+            detail: "string column removed".to_string(),
+        }];
+        assert_eq!(
+            report("api.binpb", &changes),
+            r#"{
+  "baseline": "api.binpb",
+  "changes": [
+    {
+      "kind": "column-removed",
+      "target": "blog.Post.title",
+      "detail": "string column removed"
+    }
+  ]
+}"#
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Partial batch writes
+//!
+//! A create-many RPC whose response has a repeated `synapse.relay.BatchError`
+//! field reports failing items there instead of failing the whole call. Each
+//! error pairs the item's index with a code, field and message: one per field
+//! error for items failing validation, the status of the INSERT otherwise.
+//! The other items are written one by one. With the method option
+//! `atomic: true`, any failing item writes nothing and the other items are
+//! reported as `ABORTED`; when the batch's INSERT fails, the rows the
+//! database rejects are found by retrying them in a rolled-back transaction.
+//!
+//! Client-streaming import RPCs always report failing items this way, next to
+//! an `inserted` count.
+
+use super::options::{get_cached_rpc_method_options, get_cached_service_options};
+use crate::storage::defaults::infer_operation;
+use proc_macro2::TokenStream;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+
+/// Fully-qualified proto type of per-item batch errors
+pub(crate) const BATCH_ERROR_TYPE: &str = ".synapse.relay.BatchError";
+
+/// Whether a field holds per-item batch errors
+pub(crate) fn is_errors_field(field: &prost_types::FieldDescriptorProto) -> bool {
+    field.label() == Label::Repeated && field.type_name.as_deref() == Some(BATCH_ERROR_TYPE)
+}
+
+/// Response field holding the per-item errors of a batch write, if it has one
+pub(crate) fn errors_field(response: &DescriptorProto) -> Option<String> {
+    response
+        .field
+        .iter()
+        .find(|f| is_errors_field(f))
+        .and_then(|f| f.name.clone())
+}
+
+/// Summary fields of an import response
+pub struct ImportSummary {
+    /// Integer field counting the inserted rows
+    pub inserted: proc_macro2::Ident,
+    /// Response field the per-item errors are reported in
+    pub errors: proc_macro2::Ident,
+    /// Whether the response has other fields, left at their defaults
+    pub other_fields: bool,
+}
+
+/// Summary fields of an import response: `inserted` and a BatchError field
+pub(crate) fn import_summary(response: &DescriptorProto) -> Option<ImportSummary> {
+    let inserted = response.field.iter().find(|f| {
+        f.name.as_deref() == Some("inserted")
+            && f.label() != Label::Repeated
+            && matches!(f.r#type(), Type::Int32 | Type::Int64 | Type::Uint32 | Type::Uint64)
+    })?;
+    let errors = errors_field(response)?;
+    Some(ImportSummary {
+        inserted: format_ident!("{}", inserted.name.as_deref().unwrap_or("")),
+        errors: format_ident!("{}", errors),
+        other_fields: response.field.len() > 2,
+    })
+}
+
+/// Per-item error handling of a create-many storage method
+pub struct BatchErrors {
+    /// Response field the errors are reported in
+    pub field: proc_macro2::Ident,
+    /// Write nothing when any item fails
+    pub atomic: bool,
+}
+
+/// Whether any storage create-many or import RPC of the main file's package reports per-item errors
+pub(crate) fn package_has_batch_errors(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> bool {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|file| {
+            let file_name = file.name.as_deref().unwrap_or("");
+            file.service.iter().any(|service| {
+                let svc_name = service.name.as_deref().unwrap_or("");
+                if get_cached_service_options(file_name, svc_name).is_none_or(|o| o.skip) {
+                    return false;
+                }
+                service.method.iter().any(|method| {
+                    let method_name = method.name.as_deref().unwrap_or("");
+                    let operation = get_cached_rpc_method_options(file_name, svc_name, method_name)
+                        .filter(|o| !o.operation.is_empty())
+                        .map(|o| o.operation)
+                        .unwrap_or_else(|| infer_operation(method_name).to_string());
+                    let response = method.output_type.as_deref().and_then(|t| t.rsplit('.').next());
+                    (operation == "create_many" || operation == "import")
+                        && file
+                            .message_type
+                            .iter()
+                            .find(|m| m.name.as_deref() == response)
+                            .and_then(errors_field)
+                            .is_some()
+                })
+            })
+        })
+}
+
+/// Generate the prelude's `batch_error`
+pub(crate) fn generate_prelude() -> TokenStream {
+    quote! {
+        /// Error of the item at `index` of a batch write, from the status it failed with
+        ///
+        /// The code is the status code in upper snake case (e.g. `ALREADY_EXISTS`),
+        /// and the field is the conflicting field of an `ALREADY_EXISTS`.
+        pub fn batch_error(index: usize, status: tonic::Status) -> super::super::synapse::relay::BatchError {
+            let mut code = String::new();
+            for (i, c) in format!("{:?}", status.code()).chars().enumerate() {
+                if i > 0 && c.is_ascii_uppercase() {
+                    code.push('_');
+                }
+                code.push(c.to_ascii_uppercase());
+            }
+            let field = status
+                .metadata()
+                .get("x-conflict-field")
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            super::super::synapse::relay::BatchError {
+                index: index as i32,
+                code,
+                field,
+                message: status.message().to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{field, message_field, repeated};
+
+    #[test]
+    fn test_errors_field() {
+        let response = DescriptorProto {
+            name: Some("CreateManyPostsResponse".to_string()),
+            field: vec![
+                repeated(message_field("posts", 1, ".blog.Post")),
+                repeated(message_field("errors", 2, BATCH_ERROR_TYPE)),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(errors_field(&response).as_deref(), Some("errors"));
+
+        let atomic = DescriptorProto {
+            field: response.field[..1].to_vec(),
+            ..Default::default()
+        };
+        assert!(errors_field(&atomic).is_none());
+    }
+
+    #[test]
+    fn test_import_summary() {
+        let mut response = DescriptorProto {
+            name: Some("ImportPostsResponse".to_string()),
+            field: vec![
+                field("inserted", 1, Type::Int64),
+                repeated(message_field("errors", 2, BATCH_ERROR_TYPE)),
+            ],
+            ..Default::default()
+        };
+        let summary = import_summary(&response).unwrap();
+        assert_eq!(summary.inserted.to_string(), "inserted");
+        assert_eq!(summary.errors.to_string(), "errors");
+        assert!(!summary.other_fields);
+
+        response.field[0].r#type = Some(Type::String as i32);
+        assert!(import_summary(&response).is_none());
+        response.field.remove(0);
+        assert!(import_summary(&response).is_none());
+    }
+
+    #[test]
+    fn test_generate_prelude() {
+        let code = generate_prelude().to_string();
+        assert!(code.contains("pub fn batch_error (index : usize , status : tonic :: Status)"));
+        assert!(code.contains("x-conflict-field"));
+    }
+}
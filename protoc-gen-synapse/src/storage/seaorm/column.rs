@@ -2,7 +2,7 @@
 //!
 //! This module generates the #[sea_orm(...)] attributes for entity fields.
 
-use super::options::storage::ColumnOptions;
+use super::options::storage::{ColumnOptions, IdKind};
 use super::types::MappedType;
 
 /// Generated SeaORM column attributes for a field
@@ -24,7 +24,9 @@ pub fn generate_attributes(
         // SeaORM defaults to auto_increment = true for primary keys,
         // so we only need to specify when it's false
         if opts.primary_key {
-            if opts.auto_increment {
+            // UUID v7 and ULID keys are generated by storage, never by the database
+            let generated = matches!(opts.id_kind(), IdKind::UuidV7 | IdKind::Ulid);
+            if opts.auto_increment && !generated {
                 attributes.push("primary_key".to_string());
             } else {
                 attributes.push("primary_key, auto_increment = false".to_string());
@@ -47,6 +49,13 @@ pub fn generate_attributes(
         if !opts.column_type.is_empty() {
             let column_type = normalize_column_type(&opts.column_type);
             attributes.push(format!("column_type = \"{}\"", column_type));
+        } else if opts.geography {
+            // Read and written as EWKB through bytea casts (see the prelude's Geography)
+            attributes.push("column_type = \"custom(\\\"geography(Point, 4326)\\\")\"".to_string());
+            attributes.push("select_as = \"bytea\"".to_string());
+            attributes.push("save_as = \"geography\"".to_string());
+        } else if opts.citext {
+            attributes.push("column_type = \"custom(\\\"citext\\\")\"".to_string());
         } else if opts.embed {
             attributes.push("column_type = \"JsonBinary\"".to_string());
         }
@@ -60,6 +69,21 @@ pub fn generate_attributes(
         if !opts.default_expr.is_empty() {
             attributes.push(format!("default_expr = \"{}\"", opts.default_expr));
         }
+
+        // Extra column definition SQL: collation, then generation
+        let mut extra = Vec::new();
+        if !opts.collation.is_empty() {
+            extra.push(format!("COLLATE \\\"{}\\\"", opts.collation));
+        }
+        if !opts.generated_expr.is_empty() {
+            extra.push(format!(
+                "GENERATED ALWAYS AS ({}) STORED",
+                opts.generated_expr.replace('"', "\\\"")
+            ));
+        }
+        if !extra.is_empty() {
+            attributes.push(format!("extra = \"{}\"", extra.join(" ")));
+        }
     }
 
     ColumnAttributes { attributes }
@@ -120,6 +144,34 @@ mod tests {
             .any(|a| a.contains("auto_increment")));
     }
 
+    #[test]
+    fn test_uuid_v7_primary_key_disables_auto_increment() {
+        let opts = ColumnOptions {
+            primary_key: true,
+            auto_increment: true,
+            id_kind: IdKind::UuidV7 as i32,
+            ..Default::default()
+        };
+        let result = generate_attributes(Some(&opts), &default_mapped_type(), false);
+        assert!(result
+            .attributes
+            .contains(&"primary_key, auto_increment = false".to_string()));
+    }
+
+    #[test]
+    fn test_ulid_primary_key_disables_auto_increment() {
+        let opts = ColumnOptions {
+            primary_key: true,
+            auto_increment: true,
+            id_kind: IdKind::Ulid as i32,
+            ..Default::default()
+        };
+        let result = generate_attributes(Some(&opts), &default_mapped_type(), false);
+        assert!(result
+            .attributes
+            .contains(&"primary_key, auto_increment = false".to_string()));
+    }
+
     #[test]
     fn test_unique() {
         let opts = ColumnOptions {
@@ -166,6 +218,58 @@ mod tests {
             .contains(&"default_value = \"0\"".to_string()));
     }
 
+    #[test]
+    fn test_generated_expr() {
+        let opts = ColumnOptions {
+            generated_expr: "lower(email)".to_string(),
+            ..Default::default()
+        };
+        let result = generate_attributes(Some(&opts), &default_mapped_type(), false);
+        assert!(result
+            .attributes
+            .contains(&"extra = \"GENERATED ALWAYS AS (lower(email)) STORED\"".to_string()));
+    }
+
+    #[test]
+    fn test_geography() {
+        let opts = ColumnOptions {
+            geography: true,
+            ..Default::default()
+        };
+        let result = generate_attributes(Some(&opts), &default_mapped_type(), true);
+        assert!(result.attributes.contains(
+            &"column_type = \"custom(\\\"geography(Point, 4326)\\\")\"".to_string()
+        ));
+        assert!(result.attributes.contains(&"select_as = \"bytea\"".to_string()));
+        assert!(result.attributes.contains(&"save_as = \"geography\"".to_string()));
+    }
+
+    #[test]
+    fn test_citext() {
+        let opts = ColumnOptions {
+            citext: true,
+            ..Default::default()
+        };
+        let result = generate_attributes(Some(&opts), &default_mapped_type(), false);
+        assert!(result
+            .attributes
+            .contains(&"column_type = \"custom(\\\"citext\\\")\"".to_string()));
+    }
+
+    #[test]
+    fn test_collation_with_generated_expr() {
+        let opts = ColumnOptions {
+            collation: "case_insensitive".to_string(),
+            generated_expr: "lower(email)".to_string(),
+            ..Default::default()
+        };
+        let result = generate_attributes(Some(&opts), &default_mapped_type(), false);
+        assert!(result.attributes.contains(
+            &"extra = \"COLLATE \\\"case_insensitive\\\" GENERATED ALWAYS AS (lower(email)) STORED\""
+                .to_string()
+        ));
+    }
+
     #[test]
     fn test_default_expr() {
         let opts = ColumnOptions {
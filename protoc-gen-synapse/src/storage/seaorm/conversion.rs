@@ -7,8 +7,10 @@
 
 #![allow(dead_code)]
 
+use super::implementation::is_naive_timestamp;
 use super::options::{get_cached_column_options, get_cached_entity_options};
 use crate::error::GeneratorError;
+use crate::params::time_crate;
 use heck::ToSnakeCase;
 use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
@@ -74,10 +76,7 @@ pub fn generate(
     // Format the generated code
     let content = code.to_string();
 
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     Ok(Some(File {
         name: Some(output_filename),
@@ -100,17 +99,17 @@ fn generate_model_to_proto_fields(
         let rust_field = format_ident!("{}", field_name.to_snake_case());
 
         // Check for column options (skip certain fields)
-        let _column_options = get_cached_column_options(file_name, message_name, field_number);
+        let column_options = get_cached_column_options(file_name, message_name, field_number);
 
-        // Handle timestamp fields specially
+        // Handle timestamp fields specially; naive columns hold UTC wall time
         if is_timestamp_field(field) {
-            let conversion = quote! {
-                #rust_field: Some(prost_types::Timestamp {
-                    seconds: model.#rust_field.and_utc().timestamp(),
-                    nanos: model.#rust_field.and_utc().timestamp_subsec_nanos() as i32,
-                }),
+            let value = quote! { model.#rust_field };
+            let timestamp = if column_options.as_ref().is_some_and(is_naive_timestamp) {
+                time_crate().naive_to_proto(value)
+            } else {
+                time_crate().to_proto(value)
             };
-            fields.push(conversion);
+            fields.push(quote! { #rust_field: Some(#timestamp), });
         } else if is_optional_field(field) {
             // Optional fields map directly
             let conversion = quote! {
@@ -233,10 +232,7 @@ pub fn generate_input_conversions(
 
     let content = code.to_string();
 
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     Ok(Some(File {
         name: Some(output_filename),
@@ -0,0 +1,386 @@
+//! Dry-run diff against existing output
+//!
+//! With `diff=<dir>`, the generated files are compared against the files in
+//! `<dir>` (the directory the output normally goes to) and replaced by a
+//! single `synapse.diff` report, so nothing on disk is overwritten. The
+//! report opens with a summary of added, changed, and removed files, followed
+//! by a unified diff of each. A file counts as removed when it sits under
+//! the directory of a generated package in `<dir>`, carries `@generated` in
+//! its header comment, and is no longer produced. Other files are never
+//! reported.
+
+use crate::error::GeneratorError;
+use prost_types::compiler::code_generator_response::File;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Name of the report file written in place of the generated files
+pub const REPORT_FILE: &str = "synapse.diff";
+
+/// Lines of context around each hunk
+const CONTEXT: usize = 3;
+
+/// Edit distance past which a file is shown as fully replaced
+const MAX_EDITS: usize = 2000;
+
+/// Marker identifying files written by the generator
+const GENERATED_MARKER: &str = "@generated";
+
+/// Build the report comparing `files` against the contents of `dir`
+///
+/// `packages` are the generated packages; only their directories are
+/// searched for removed files.
+pub fn report(dir: &str, packages: &[&str], files: &[File]) -> Result<File, GeneratorError> {
+    let root = Path::new(dir);
+    if !root.is_dir() {
+        return Err(GeneratorError::CodeGenError(format!("diff directory {} does not exist", dir)));
+    }
+
+    let mut added = Vec::new();
+    let mut changed = Vec::new();
+    let mut unchanged = 0;
+    let mut diffs = String::new();
+
+    for file in files {
+        let name = file.name.as_deref().unwrap_or("");
+        let new = file.content.as_deref().unwrap_or("");
+        match std::fs::read_to_string(root.join(name)) {
+            Ok(old) if old == new => unchanged += 1,
+            Ok(old) => {
+                changed.push(name);
+                diffs.push_str(&unified_diff(&format!("a/{}", name), &format!("b/{}", name), &old, new));
+            }
+            Err(_) => {
+                added.push(name);
+                diffs.push_str(&unified_diff("/dev/null", &format!("b/{}", name), "", new));
+            }
+        }
+    }
+
+    let generated: BTreeSet<&str> = files.iter().filter_map(|f| f.name.as_deref()).collect();
+    let mut removed = Vec::new();
+    for name in generated_on_disk(root, packages) {
+        if generated.contains(name.as_str()) {
+            continue;
+        }
+        if let Ok(old) = std::fs::read_to_string(root.join(&name)) {
+            diffs.push_str(&unified_diff(&format!("a/{}", name), "/dev/null", &old, ""));
+        }
+        removed.push(name);
+    }
+
+    let mut content = String::new();
+    let _ = writeln!(
+        content,
+        "# synapse diff against {}: {} added, {} changed, {} removed, {} unchanged",
+        dir,
+        added.len(),
+        changed.len(),
+        removed.len(),
+        unchanged
+    );
+    for name in &added {
+        let _ = writeln!(content, "#   added    {}", name);
+    }
+    for name in &changed {
+        let _ = writeln!(content, "#   changed  {}", name);
+    }
+    for name in &removed {
+        let _ = writeln!(content, "#   removed  {}", name);
+    }
+    content.push_str(&diffs);
+
+    Ok(File {
+        name: Some(REPORT_FILE.to_string()),
+        content: Some(content),
+        ..Default::default()
+    })
+}
+
+/// Relative paths of the `.rs` files in the package directories under `root`
+/// whose header is marked `@generated`
+fn generated_on_disk(root: &Path, packages: &[&str]) -> Vec<String> {
+    let mut result = Vec::new();
+    let mut pending: Vec<_> = packages
+        .iter()
+        .filter(|p| !p.is_empty())
+        .map(|p| root.join(p.replace('.', "/")))
+        .collect();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "rs")
+                && std::fs::read_to_string(&path).is_ok_and(|content| has_generated_header(&content))
+                && let Ok(relative) = path.strip_prefix(root)
+            {
+                result.push(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// Whether the comments opening a file carry the `@generated` marker
+fn has_generated_header(content: &str) -> bool {
+    let mut in_block = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if !in_block && !line.starts_with("//") && !line.starts_with("/*") {
+            return false;
+        }
+        if line.contains(GENERATED_MARKER) {
+            return true;
+        }
+        if line.starts_with("/*") {
+            in_block = true;
+        }
+        if in_block && line.ends_with("*/") {
+            in_block = false;
+        }
+    }
+    false
+}
+
+/// One line of an edit script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Delete,
+    Insert,
+}
+
+/// Unified diff of two texts, empty when they are equal
+pub fn unified_diff(old_name: &str, new_name: &str, old: &str, new: &str) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let edits = diff_lines(&a, &b);
+    if edits.iter().all(|e| *e == Edit::Keep) {
+        return String::new();
+    }
+
+    // Position in both texts before each edit
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for edit in &edits {
+        positions.push((i, j));
+        match edit {
+            Edit::Keep => {
+                i += 1;
+                j += 1;
+            }
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+    positions.push((i, j));
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let changes: Vec<usize> = (0..edits.len()).filter(|&k| edits[k] != Edit::Keep).collect();
+    let mut k = 0;
+    while k < changes.len() {
+        // Group changes whose context overlaps into one hunk
+        let start = changes[k].saturating_sub(CONTEXT);
+        let mut last = changes[k];
+        while k + 1 < changes.len() && changes[k + 1] <= last + 2 * CONTEXT + 1 {
+            k += 1;
+            last = changes[k];
+        }
+        let end = (last + CONTEXT + 1).min(edits.len());
+        k += 1;
+
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+        let _ = writeln!(
+            out,
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        );
+        for (edit, &(i, j)) in edits[start..end].iter().zip(&positions[start..end]) {
+            let _ = match edit {
+                Edit::Keep => writeln!(out, " {}", a[i]),
+                Edit::Delete => writeln!(out, "-{}", a[i]),
+                Edit::Insert => writeln!(out, "+{}", b[j]),
+            };
+        }
+    }
+    out
+}
+
+/// Hunk range in unified diff notation (1-based; empty ranges name the line before)
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+/// Shortest edit script turning `a` into `b` (Myers' algorithm)
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    // Common prefix and suffix need no search
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_mid, b_mid) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut edits = vec![Edit::Keep; prefix];
+    edits.extend(myers(a_mid, b_mid));
+    edits.extend(std::iter::repeat_n(Edit::Keep, suffix));
+    edits
+}
+
+/// Myers' search, giving up past `MAX_EDITS`
+///
+/// Each round keeps only the diagonals it can reach, so memory grows with the
+/// square of the edit distance; past the cap the whole range is replaced.
+fn myers(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = ((n + m) as usize).min(MAX_EDITS) as isize;
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // Diagonals -d-1..=d+1 of `v` before round d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut found = false;
+
+    'search: for d in 0..=max {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) { v[idx + 1] } else { v[idx - 1] + 1 };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                found = true;
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    if !found {
+        let mut edits = vec![Edit::Delete; a.len()];
+        edits.extend(std::iter::repeat_n(Edit::Insert, b.len()));
+        return edits;
+    }
+
+    // Walk the trace back from the end to recover the edits
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let v = &trace[d as usize];
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) { k + 1 } else { k - 1 };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        edits.push(if x == prev_x { Edit::Insert } else { Edit::Delete });
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.extend(std::iter::repeat_n(Edit::Keep, x as usize));
+    edits.reverse();
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nb\nc\nD\ne\nf\ng\nh\ni\n";
+        assert_eq!(
+            unified_diff("a/x.rs", "b/x.rs", old, new),
+            "--- a/x.rs\n+++ b/x.rs\n@@ -1,8 +1,9 @@\n a\n b\n c\n-d\n+D\n e\n f\n g\n h\n+i\n"
+        );
+        assert_eq!(unified_diff("a/x.rs", "b/x.rs", old, old), "");
+    }
+
+    #[test]
+    fn test_unified_diff_splits_distant_hunks() {
+        let old: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let new: String = (1..=20)
+            .map(|n| match n {
+                2 => "two\n".to_string(),
+                19 => "nineteen\n".to_string(),
+                n => format!("{}\n", n),
+            })
+            .collect();
+        let diff = unified_diff("a/x.rs", "b/x.rs", &old, &new);
+        assert!(diff.contains("@@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n"));
+        assert!(diff.contains("@@ -16,5 +16,5 @@\n 16\n 17\n 18\n-19\n+nineteen\n 20\n"));
+    }
+
+    #[test]
+    fn test_diff_lines_gives_up_past_max_edits() {
+        let old: Vec<String> = (0..MAX_EDITS).map(|n| format!("old {}", n)).collect();
+        let new: Vec<String> = (0..MAX_EDITS).map(|n| format!("new {}", n)).collect();
+        let a: Vec<&str> = old.iter().map(String::as_str).collect();
+        let b: Vec<&str> = new.iter().map(String::as_str).collect();
+        let edits = diff_lines(&a, &b);
+        assert_eq!(edits.len(), 2 * MAX_EDITS);
+        assert!(edits[..MAX_EDITS].iter().all(|e| *e == Edit::Delete));
+    }
+
+    #[test]
+    fn test_has_generated_header() {
+        assert!(has_generated_header("//! Post entity\n//! @generated\nuse sea_orm::entity::prelude::*;\n"));
+        assert!(has_generated_header("/*!Read-through cache\n\nWrap any storage.*/\n//!\n//! @generated\n"));
+        assert!(!has_generated_header("//! Handwritten\nfn main() {}\n// not @generated\n"));
+    }
+
+    #[test]
+    fn test_removed_files_only_in_generated_packages() {
+        let root = std::env::temp_dir().join(format!("synapse-diff-{}", std::process::id()));
+        let write = |name: &str, content: &str| {
+            let path = root.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write("blog/old.rs", "//! @generated\n");
+        write("blog/handwritten.rs", "fn helper() {}\n// @generated\n");
+        write("iam/user.rs", "//! @generated\n");
+
+        let produced = File {
+            name: Some("blog/mod.rs".to_string()),
+            content: Some("//! @generated\n".to_string()),
+            ..Default::default()
+        };
+        let report = report(root.to_str().unwrap(), &["blog"], &[produced]).unwrap().content.unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(report.contains("#   removed  blog/old.rs\n"));
+        assert!(report.contains("1 added, 0 changed, 1 removed"));
+        assert!(!report.contains("handwritten.rs"));
+        assert!(!report.contains("iam/user.rs"));
+    }
+
+    #[test]
+    fn test_unified_diff_new_file() {
+        assert_eq!(unified_diff("/dev/null", "b/x.rs", "", "a\nb\n"), "--- /dev/null\n+++ b/x.rs\n@@ -0,0 +1,2 @@\n+a\n+b\n");
+    }
+}
@@ -0,0 +1,385 @@
+//! DTO generation
+//!
+//! Generates plain `{Entity}Dto` structs for entities marked `dto: true`, and
+//! for `emit_events` and `webhooks` entities, whose event payloads are
+//! serialized DTOs.
+//! dto.rs only uses std and serde, so other crates can share the entity
+//! shapes (e.g. `#[path = "gen/blog/dto.rs"] mod dto;`) without pulling in
+//! sea-orm, async-graphql, or prost. The From conversions that do need those
+//! crates live next to it in dto_conversions.rs.
+
+use super::entity::is_field_nullable;
+use super::implementation::has_string_primary_key;
+use super::options::{get_cached_column_options, get_cached_entity_options};
+use crate::error::GeneratorError;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+
+/// How a DTO field is carried over from the proto message
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FieldKind {
+    /// Same type as the prost field
+    Plain,
+    /// `google.protobuf.Timestamp`, as `Option<SystemTime>`
+    Timestamp,
+    /// Enum, as its proto `i32`; the model column holds the SeaORM enum
+    Enum,
+}
+
+/// A field of a generated DTO
+struct DtoField {
+    ident: proc_macro2::Ident,
+    ty: TokenStream,
+    kind: FieldKind,
+    /// Model column is `Option<_>` (proto3 optional)
+    nullable: bool,
+    pii: bool,
+}
+
+/// Entities in the main file's package that get a DTO
+fn collect_dto_entities<'a>(
+    all_files: &'a [FileDescriptorProto],
+    main_file: &FileDescriptorProto,
+) -> Vec<(&'a str, &'a DescriptorProto)> {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .flat_map(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.message_type.iter().map(move |m| (file_name, m))
+        })
+        .filter(|(file_name, m)| {
+            get_cached_entity_options(file_name, m.name.as_deref().unwrap_or(""))
+                .is_some_and(|o| !o.skip && (o.dto || o.emit_events || o.webhooks))
+        })
+        .collect()
+}
+
+/// Names of the PII columns in an entity's DTO
+pub fn pii_columns(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto, entity: &str) -> Vec<String> {
+    collect_dto_entities(all_files, main_file)
+        .into_iter()
+        .find(|(_, m)| m.name.as_deref() == Some(entity))
+        .map(|(file_name, message)| {
+            dto_fields(file_name, message)
+                .into_iter()
+                .filter(|f| f.pii)
+                .map(|f| f.ident.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Rust type of a scalar proto field, as prost generates it
+fn scalar_type(field: &FieldDescriptorProto) -> Option<TokenStream> {
+    let ty = match field.r#type() {
+        Type::Double => quote! { f64 },
+        Type::Float => quote! { f32 },
+        Type::Int64 | Type::Sint64 | Type::Sfixed64 => quote! { i64 },
+        Type::Uint64 | Type::Fixed64 => quote! { u64 },
+        Type::Int32 | Type::Sint32 | Type::Sfixed32 | Type::Enum => quote! { i32 },
+        Type::Uint32 | Type::Fixed32 => quote! { u32 },
+        Type::Bool => quote! { bool },
+        Type::String => quote! { String },
+        Type::Bytes => quote! { Vec<u8> },
+        Type::Message | Type::Group => return None,
+    };
+    Some(ty)
+}
+
+/// DTO fields for a message
+///
+/// Scalars, enums (as `i32`), and timestamps are carried over; other message
+/// fields and oneofs are left out so the DTO stays dependency-free.
+fn dto_fields(file_name: &str, message: &DescriptorProto) -> Vec<DtoField> {
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let mut fields = Vec::new();
+
+    for field in &message.field {
+        let in_oneof = field.oneof_index.is_some() && !field.proto3_optional.unwrap_or(false);
+        if in_oneof {
+            continue;
+        }
+
+        let name = field.name.as_deref().unwrap_or("");
+        let repeated = field.label() == Label::Repeated;
+        let nullable = is_field_nullable(field);
+        let is_timestamp = field.type_name.as_deref() == Some(".google.protobuf.Timestamp");
+
+        let (ty, kind) = if is_timestamp && !repeated {
+            (quote! { Option<std::time::SystemTime> }, FieldKind::Timestamp)
+        } else if let Some(scalar) = scalar_type(field) {
+            let ty = if repeated {
+                quote! { Vec<#scalar> }
+            } else if nullable {
+                quote! { Option<#scalar> }
+            } else {
+                scalar
+            };
+            let kind = if field.r#type() == Type::Enum && !repeated {
+                FieldKind::Enum
+            } else {
+                FieldKind::Plain
+            };
+            (ty, kind)
+        } else {
+            continue;
+        };
+
+        let pii = get_cached_column_options(file_name, msg_name, field.number.unwrap_or(0))
+            .map(|o| o.pii)
+            .unwrap_or(false);
+
+        fields.push(DtoField {
+            ident: format_ident!("{}", name.to_snake_case()),
+            ty,
+            kind,
+            nullable,
+            pii,
+        });
+    }
+
+    fields
+}
+
+/// Generate dto.rs and dto_conversions.rs for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Vec<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    let entities = collect_dto_entities(all_files, file);
+    if package.is_empty() || entities.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut structs = Vec::new();
+    let mut conversions = Vec::new();
+    for (file_name, message) in &entities {
+        let msg_name = message.name.as_deref().unwrap_or("");
+        let fields = dto_fields(file_name, message);
+        structs.push(generate_struct(msg_name, &fields));
+        conversions.push(generate_conversions(
+            msg_name,
+            &fields,
+            has_string_primary_key(all_files, msg_name),
+        ));
+    }
+
+    let dto_code = quote! {
+        //! Plain data transfer objects
+        //!
+        //! Depends only on std and serde, so it can be compiled into crates
+        //! that do not use sea-orm or async-graphql.
+        //! @generated
+
+        #![allow(missing_docs)]
+
+        #(#structs)*
+    };
+
+    let conversions_code = quote! {
+        //! Conversions between DTOs, SeaORM models, and proto messages
+        //! @generated
+
+        #![allow(missing_docs)]
+
+        use std::time::SystemTime;
+        use super::dto::*;
+        use super::entities;
+
+        #(#conversions)*
+    };
+
+    let base = package.replace('.', "/");
+    Ok(vec![
+        File {
+            name: Some(format!("{}/dto.rs", base)),
+            content: Some(crate::params::format_code(dto_code.to_string())),
+            ..Default::default()
+        },
+        File {
+            name: Some(format!("{}/dto_conversions.rs", base)),
+            content: Some(crate::params::format_code(conversions_code.to_string())),
+            ..Default::default()
+        },
+    ])
+}
+
+/// Generate the DTO struct (with a redacting Debug impl when it has PII)
+fn generate_struct(msg_name: &str, fields: &[DtoField]) -> TokenStream {
+    let dto_ident = format_ident!("{}Dto", msg_name.to_upper_camel_case());
+    let doc = format!("Plain data for {}", msg_name);
+    let field_defs = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let ty = &f.ty;
+        quote! { pub #ident: #ty }
+    });
+
+    let has_pii = fields.iter().any(|f| f.pii);
+    let debug_derive = if has_pii { quote! {} } else { quote! { Debug, } };
+    let debug_impl = if has_pii {
+        let dto_name = dto_ident.to_string();
+        let debug_fields = fields.iter().map(|f| {
+            let ident = &f.ident;
+            let name = ident.to_string();
+            if f.pii {
+                quote! { .field(#name, &"<redacted>") }
+            } else {
+                quote! { .field(#name, &self.#ident) }
+            }
+        });
+        quote! {
+            impl std::fmt::Debug for #dto_ident {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.debug_struct(#dto_name)
+                        #(#debug_fields)*
+                        .finish()
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, #debug_derive PartialEq, Default, serde::Serialize, serde::Deserialize)]
+        pub struct #dto_ident {
+            #(#field_defs,)*
+        }
+
+        #debug_impl
+    }
+}
+
+/// Generate Model -> DTO and proto <-> DTO conversions
+fn generate_conversions(msg_name: &str, fields: &[DtoField], string_key: bool) -> TokenStream {
+    let dto_ident = format_ident!("{}Dto", msg_name.to_upper_camel_case());
+    let proto_ident = format_ident!("{}", msg_name.to_upper_camel_case());
+    let entity_mod = format_ident!("{}", msg_name.to_snake_case());
+
+    let from_model = fields.iter().map(|f| {
+        let ident = &f.ident;
+        match f.kind {
+            FieldKind::Timestamp if f.nullable => quote! { #ident: model.#ident.map(SystemTime::from) },
+            FieldKind::Timestamp => quote! { #ident: Some(SystemTime::from(model.#ident)) },
+            FieldKind::Enum if f.nullable => quote! { #ident: model.#ident.map(i32::from) },
+            FieldKind::Enum => quote! { #ident: i32::from(model.#ident) },
+            FieldKind::Plain if string_key && ident == "id" => quote! { #ident: model.#ident.to_string() },
+            FieldKind::Plain => quote! { #ident: model.#ident },
+        }
+    });
+    let from_proto = fields.iter().map(|f| {
+        let ident = &f.ident;
+        match f.kind {
+            FieldKind::Timestamp => quote! { #ident: proto.#ident.and_then(|t| SystemTime::try_from(t).ok()) },
+            FieldKind::Enum | FieldKind::Plain => quote! { #ident: proto.#ident },
+        }
+    });
+    let to_proto = fields.iter().map(|f| {
+        let ident = &f.ident;
+        match f.kind {
+            FieldKind::Timestamp => quote! { #ident: dto.#ident.map(Into::into) },
+            FieldKind::Enum | FieldKind::Plain => quote! { #ident: dto.#ident },
+        }
+    });
+
+    quote! {
+        impl From<entities::#entity_mod::Model> for #dto_ident {
+            fn from(model: entities::#entity_mod::Model) -> Self {
+                Self {
+                    #(#from_model,)*
+                }
+            }
+        }
+
+        impl From<super::#proto_ident> for #dto_ident {
+            fn from(proto: super::#proto_ident) -> Self {
+                Self {
+                    #(#from_proto,)*
+                }
+            }
+        }
+
+        /// Fields the DTO leaves out (nested messages, oneofs) are defaulted
+        impl From<#dto_ident> for super::#proto_ident {
+            #[allow(clippy::needless_update)]
+            fn from(dto: #dto_ident) -> Self {
+                Self {
+                    #(#to_proto,)*
+                    ..Default::default()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, kind: FieldKind, nullable: bool, pii: bool) -> DtoField {
+        DtoField {
+            ident: format_ident!("{}", name),
+            ty: quote! { String },
+            kind,
+            nullable,
+            pii,
+        }
+    }
+
+    #[test]
+    fn test_generate_struct() {
+        let fields = vec![field("id", FieldKind::Plain, false, false)];
+        let code = generate_struct("Post", &fields).to_string();
+        assert!(code.contains("pub struct PostDto"));
+        assert!(code.contains("Debug ,"));
+        assert!(code.contains("serde :: Serialize"));
+        assert!(!code.contains("sea_orm"));
+        assert!(!code.contains("async_graphql"));
+    }
+
+    #[test]
+    fn test_generate_struct_redacts_pii() {
+        let fields = vec![
+            field("id", FieldKind::Plain, false, false),
+            field("email", FieldKind::Plain, false, true),
+        ];
+        let code = generate_struct("User", &fields).to_string();
+        assert!(code.contains("impl std :: fmt :: Debug for UserDto"));
+        assert!(code.contains("\"<redacted>\""));
+    }
+
+    #[test]
+    fn test_generate_conversions() {
+        let fields = vec![
+            field("id", FieldKind::Plain, false, false),
+            field("created_at", FieldKind::Timestamp, false, false),
+            field("deleted_at", FieldKind::Timestamp, true, false),
+        ];
+        let code = generate_conversions("Post", &fields, true).to_string();
+        assert!(code.contains("impl From < entities :: post :: Model > for PostDto"));
+        assert!(code.contains("id : model . id . to_string ()"));
+        assert!(code.contains("created_at : Some (SystemTime :: from (model . created_at))"));
+        assert!(code.contains("deleted_at : model . deleted_at . map (SystemTime :: from)"));
+        assert!(code.contains("impl From < PostDto > for super :: Post"));
+    }
+
+    #[test]
+    fn test_generate_conversions_maps_enums() {
+        let fields = vec![
+            field("status", FieldKind::Enum, false, false),
+            field("visibility", FieldKind::Enum, true, false),
+        ];
+        let code = generate_conversions("Post", &fields, false).to_string();
+        assert!(code.contains("status : i32 :: from (model . status)"));
+        assert!(code.contains("visibility : model . visibility . map (i32 :: from)"));
+        assert!(code.contains("status : proto . status"));
+    }
+}
@@ -3,6 +3,10 @@
 //! This module generates the main entity struct with SeaORM 2.0 dense format.
 
 use super::column;
+use super::implementation::{
+    generated_columns, is_naive_timestamp, is_ulid_key, is_uuid_v7_key, searchable_columns, searchable_fields,
+    GEO_POINT_TYPE, MANAGED_TIMESTAMPS, SEARCH_CONFIG, SEARCH_VECTOR_COLUMN,
+};
 use super::oneof::{
     extract_oneofs, generate_flatten_fields, generate_json_fields, generate_tagged_fields,
     is_oneof_field, OneofStrategy,
@@ -13,11 +17,13 @@ use super::options::{
 };
 use super::relation::generate_relation_fields;
 use super::types::map_proto_type;
+use crate::options::synapse::storage;
+use crate::params::time_crate;
 use crate::GeneratorError;
-use heck::ToSnakeCase;
+use heck::{ToSnakeCase, ToUpperCamelCase};
 use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
-use prost_types::field_descriptor_proto::Label;
+use prost_types::field_descriptor_proto::{Label, Type};
 use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto};
 use quote::{format_ident, quote};
 
@@ -66,7 +72,71 @@ pub fn generate(
     );
 
     // Generate field definitions
-    let fields = generate_fields(message, file_name, msg_name)?;
+    let mut fields = generate_fields(message, file_name, msg_name)?;
+
+    // Storage stamps managed columns with the UTC time type
+    for field in &message.field {
+        let name = field.name.as_deref().unwrap_or("");
+        let managed = (entity_options.timestamps && MANAGED_TIMESTAMPS.contains(&name))
+            || (entity_options.soft_delete && name == "deleted_at");
+        let naive = get_cached_column_options(file_name, msg_name, field.number.unwrap_or(0))
+            .is_some_and(|o| is_naive_timestamp(&o));
+        if managed && naive {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{}.{}: storage-managed timestamps cannot use timezone NAIVE",
+                msg_name, name
+            )));
+        }
+    }
+
+    // Storage-managed timestamps need columns even if the message omits them
+    if entity_options.timestamps {
+        for name in MANAGED_TIMESTAMPS {
+            if !fields.iter().any(|f| f.name == *name) {
+                fields.push(GeneratedField {
+                    name: name.to_string(),
+                    rust_type: time_crate().datetime_type().to_string(),
+                    attributes: Vec::new(),
+                    pii: false,
+                    primary_key: false,
+                    ordered: true,
+                });
+            }
+        }
+    }
+
+    // Soft-deleted entities track deletion time in a nullable column
+    if entity_options.soft_delete && !fields.iter().any(|f| f.name == "deleted_at") {
+        fields.push(GeneratedField {
+            name: "deleted_at".to_string(),
+            rust_type: format!("Option<{}>", time_crate().datetime_type()),
+            attributes: Vec::new(),
+            pii: false,
+            primary_key: false,
+            ordered: true,
+        });
+    }
+
+    // Index definitions become column attributes plus an index_statements() helper
+    let index_impl = generate_indexes(&entity_options.indexes, &table_name, &mut fields)?;
+
+    // Generated columns get an ALTER TABLE helper for existing tables
+    let generated_impl = generate_generated_columns(&generated_columns(file_name, message));
+
+    // Searchable columns feed a generated tsvector column added by search_statements()
+    let files = std::slice::from_ref(file);
+    if let Some((field, _)) = searchable_fields(files, message_name)
+        .into_iter()
+        .find(|(f, _)| f.r#type() != Type::String)
+    {
+        return Err(GeneratorError::CodeGenError(format!(
+            "{}.{} is searchable but not a string column",
+            message_name,
+            field.name.as_deref().unwrap_or("")
+        )));
+    }
+    let searchable = searchable_columns(files, message_name);
+    let search_impl = generate_search_vector(&table_name, &searchable);
 
     // Generate oneof fields
     let oneof_fields = generate_oneof_fields(message);
@@ -109,6 +179,30 @@ pub fn generate(
         })
         .collect();
 
+    // Entities with PII columns get a hand-written Debug impl that redacts them
+    let has_pii = fields.iter().any(|f| f.pii);
+    let debug_derive = if has_pii { quote! {} } else { quote! { Debug, } };
+    let debug_impl = if has_pii {
+        generate_pii_debug_impl(&fields)
+    } else {
+        quote! {}
+    };
+
+    // Field-level diff for audit logs and change events
+    let diff_impl = generate_diff_impl(&fields);
+
+    // Equality, hashing and ordering by primary key or all columns
+    let compare_by = entity_options.compare_by();
+    let eq_derive = if compare_by == storage::CompareBy::Unspecified {
+        quote! { PartialEq, Eq, }
+    } else {
+        quote! {}
+    };
+    let compare_impl = generate_compare_impl(&fields, compare_by, msg_name)?;
+
+    // Rows read by id, dropped by writes to the same id
+    let row_cache_impl = super::row_cache::generate_static(&entity_options);
+
     // Combine regular fields, oneof fields, and relation fields
     let all_field_tokens: Vec<TokenStream> = field_tokens
         .into_iter()
@@ -129,23 +223,34 @@ pub fn generate(
         use sea_orm::entity::prelude::*;
 
         #[sea_orm::model]
-        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[derive(Clone, #debug_derive #eq_derive DeriveEntityModel)]
         #[sea_orm(table_name = #table_name_lit)]
         pub struct #struct_name {
             #(#all_field_tokens),*
         }
 
+        #debug_impl
+
+        #diff_impl
+
+        #compare_impl
+
+        #index_impl
+
+        #generated_impl
+
+        #search_impl
+
         impl ActiveModelBehavior for ActiveModel {}
+
+        #row_cache_impl
     };
 
     // Format the generated code
     let content = code.to_string();
 
-    // Try to format with prettyplease if we can parse it
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content, // If parsing fails, use unformatted
-    };
+    // Format the generated code
+    let formatted = crate::params::format_code(content);
 
     Ok(Some(File {
         name: Some(output_filename),
@@ -155,14 +260,18 @@ pub fn generate(
 }
 
 /// Generated field information
-struct GeneratedField {
-    name: String,
-    rust_type: String,
-    attributes: Vec<String>,
+pub(super) struct GeneratedField {
+    pub name: String,
+    pub rust_type: String,
+    pub attributes: Vec<String>,
+    pub pii: bool,
+    pub primary_key: bool,
+    /// Column type implements `Ord` and `Hash`
+    pub ordered: bool,
 }
 
 /// Generate field definitions from message fields
-fn generate_fields(
+pub(super) fn generate_fields(
     message: &DescriptorProto,
     file_name: &str,
     msg_name: &str,
@@ -197,8 +306,34 @@ fn generate_fields(
         // Check if this is an embedded field (stored as JSON)
         let is_embedded = column_options.as_ref().map(|o| o.embed).unwrap_or(false);
 
+        // Postgres array columns hold repeated scalars
+        let is_array = column_options.as_ref().map(|o| o.array).unwrap_or(false);
+        if is_array {
+            let scalar = !matches!(proto_type, Type::Message | Type::Enum | Type::Bytes | Type::Group);
+            if field.label() != Label::Repeated || !scalar {
+                return Err(GeneratorError::CodeGenError(format!(
+                    "{}.{}: array columns must be repeated scalar fields",
+                    msg_name, field_name
+                )));
+            }
+        }
+
+        // Geography columns hold a synapse.relay.GeoPoint; proto3 message
+        // fields always have presence, so the column is nullable
+        let is_geography = column_options.as_ref().is_some_and(|o| o.geography);
+        if is_geography && type_name != Some(GEO_POINT_TYPE) {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{}.{}: geography columns must be synapse.relay.GeoPoint fields",
+                msg_name, field_name
+            )));
+        }
+
         // Build the final Rust type
-        let rust_type = if is_embedded {
+        let rust_type = if is_geography {
+            "Option<Geography>".to_string()
+        } else if is_array {
+            format!("Vec<{}>", mapped.rust_type)
+        } else if is_embedded {
             // Embedded fields are stored as typed JSON
             // In SeaORM 2.0, use the type directly with column_type = "JsonBinary"
             // The type should derive FromJsonQueryResult
@@ -217,8 +352,22 @@ fn generate_fields(
         // Override type if specified in options (but not for embedded fields)
         let rust_type = if !is_embedded {
             if let Some(ref opts) = column_options {
-                if !opts.column_type.is_empty() {
+                if is_uuid_v7_key(opts) {
+                    // The proto carries the key as a string; the column is a native UUID
+                    "Uuid".to_string()
+                } else if is_ulid_key(opts) {
+                    // String column wrapped so storage can generate and validate ULIDs
+                    "Ulid".to_string()
+                } else if !opts.column_type.is_empty() {
                     map_column_type_to_rust(&opts.column_type, is_nullable)
+                } else if is_naive_timestamp(opts) && type_name == Some(".google.protobuf.Timestamp") {
+                    // `timestamp` without time zone
+                    let naive = time_crate().naive_datetime_type();
+                    if is_nullable {
+                        format!("Option<{}>", naive)
+                    } else {
+                        naive.to_string()
+                    }
                 } else {
                     rust_type
                 }
@@ -235,16 +384,335 @@ fn generate_fields(
         // Use snake_case for field name
         let snake_name = field_name.to_snake_case();
 
+        // JSON, PostGIS and floating point columns have no total order
+        let json_column = column_options
+            .as_ref()
+            .is_some_and(|o| matches!(o.column_type.to_lowercase().as_str(), "json" | "jsonb" | "jsonbinary"));
+        let float = matches!(proto_type, Type::Float | Type::Double) || rust_type.contains("f32") || rust_type.contains("f64");
+        let ordered = !is_embedded && !is_geography && !json_column && !float;
+
         fields.push(GeneratedField {
             name: snake_name,
             rust_type,
             attributes: col_attrs.attributes,
+            pii: column_options.as_ref().map(|o| o.pii).unwrap_or(false),
+            primary_key: column_options.as_ref().is_some_and(|o| o.primary_key),
+            ordered,
         });
     }
 
     Ok(fields)
 }
 
+/// Generate the `diff` helper listing changed columns between two models
+///
+/// PII columns are compared as usual but reported through the prelude's `Pii`
+/// wrapper, so the change shows a placeholder rather than the value.
+fn generate_diff_impl(fields: &[GeneratedField]) -> TokenStream {
+    let checks = fields.iter().map(|f| {
+        let name = format_ident!("{}", f.name);
+        let label = &f.name;
+        let (old, new) = if f.pii {
+            (quote! { format!("{:?}", Pii(&old.#name)) }, quote! { format!("{:?}", Pii(&new.#name)) })
+        } else {
+            (quote! { format!("{:?}", old.#name) }, quote! { format!("{:?}", new.#name) })
+        };
+        let sensitive = f.pii;
+        quote! {
+            if old.#name != new.#name {
+                changes.push(FieldChange {
+                    field: #label,
+                    old: #old,
+                    new: #new,
+                    sensitive: #sensitive,
+                });
+            }
+        }
+    });
+
+    quote! {
+        /// Columns that differ between `old` and `new`, in declaration order
+        pub fn diff(old: &Model, new: &Model) -> Vec<FieldChange> {
+            let mut changes = Vec::new();
+            #(#checks)*
+            changes
+        }
+
+        impl Model {
+            /// Columns changed from `self` to `new`
+            pub fn diff(&self, new: &Model) -> Vec<FieldChange> {
+                diff(self, new)
+            }
+        }
+    }
+}
+
+/// Generate `compare_by` equality, hashing and ordering plus sort helpers
+///
+/// The model's PartialEq/Eq/Hash/PartialOrd/Ord all compare the same columns:
+/// the primary key, or every ordered column in declaration order (JSON,
+/// embedded, PostGIS and floating point columns, oneof columns and loaded
+/// relations are left out). `sort_by_field` sorts by any single column.
+fn generate_compare_impl(
+    fields: &[GeneratedField],
+    compare_by: storage::CompareBy,
+    msg_name: &str,
+) -> Result<TokenStream, GeneratorError> {
+    let compared: Vec<&GeneratedField> = match compare_by {
+        storage::CompareBy::Unspecified => return Ok(quote! {}),
+        storage::CompareBy::PrimaryKey => {
+            let keys: Vec<_> = fields.iter().filter(|f| f.primary_key).collect();
+            if keys.is_empty() {
+                return Err(GeneratorError::CodeGenError(format!(
+                    "{}: compare_by PRIMARY_KEY needs a primary_key column",
+                    msg_name
+                )));
+            }
+            if let Some(key) = keys.iter().find(|f| !f.ordered) {
+                return Err(GeneratorError::CodeGenError(format!(
+                    "{}.{}: compare_by PRIMARY_KEY needs an orderable key column",
+                    msg_name, key.name
+                )));
+            }
+            keys
+        }
+        storage::CompareBy::AllColumns => fields.iter().filter(|f| f.ordered).collect(),
+    };
+
+    let names: Vec<_> = compared.iter().map(|f| format_ident!("{}", f.name)).collect();
+    let (eq, cmp) = match names.split_first() {
+        Some((first, rest)) => (
+            quote! { self.#first == other.#first #(&& self.#rest == other.#rest)* },
+            quote! { self.#first.cmp(&other.#first) #(.then_with(|| self.#rest.cmp(&other.#rest)))* },
+        ),
+        None => (quote! { true }, quote! { std::cmp::Ordering::Equal }),
+    };
+    let sort_arms = fields.iter().filter(|f| f.ordered).map(|f| {
+        let name = format_ident!("{}", f.name);
+        let variant = format_ident!("{}", f.name.to_upper_camel_case());
+        quote! { Column::#variant => self.#name.cmp(&other.#name), }
+    });
+
+    Ok(quote! {
+        impl PartialEq for Model {
+            fn eq(&self, other: &Self) -> bool {
+                #eq
+            }
+        }
+
+        impl Eq for Model {}
+
+        impl std::hash::Hash for Model {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                #(std::hash::Hash::hash(&self.#names, state);)*
+            }
+        }
+
+        impl PartialOrd for Model {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl Ord for Model {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                #cmp
+            }
+        }
+
+        impl Model {
+            /// Compare two models by one column; unordered columns compare equal
+            pub fn cmp_by_field(&self, other: &Self, column: Column) -> std::cmp::Ordering {
+                #[allow(unreachable_patterns)]
+                match column {
+                    #(#sort_arms)*
+                    _ => std::cmp::Ordering::Equal,
+                }
+            }
+
+            /// Sort models by one column, keeping the order of equal rows
+            pub fn sort_by_field(models: &mut [Self], column: Column) {
+                models.sort_by(|a, b| a.cmp_by_field(b, column));
+            }
+        }
+    })
+}
+
+/// Generate a Debug impl that redacts PII columns
+///
+/// Columns marked `pii` are printed through the prelude's `Pii` wrapper, so
+/// any log statement formatting the model shows a placeholder, never the value.
+fn generate_pii_debug_impl(fields: &[GeneratedField]) -> TokenStream {
+    let entries = fields.iter().map(|f| {
+        let name = format_ident!("{}", f.name);
+        let label = &f.name;
+        if f.pii {
+            quote! { .field(#label, &Pii(&self.#name)) }
+        } else {
+            quote! { .field(#label, &self.#name) }
+        }
+    });
+
+    quote! {
+        impl std::fmt::Debug for Model {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct("Model")
+                    #(#entries)*
+                    .finish_non_exhaustive()
+            }
+        }
+    }
+}
+
+/// Generate `Entity::generated_column_statements()` for `generated_expr` columns
+///
+/// Schema sync creates the columns from their `extra` attribute; this helper
+/// adds them to tables that already exist.
+fn generate_generated_columns(columns: &[String]) -> TokenStream {
+    if columns.is_empty() {
+        return quote! {};
+    }
+    let variants = columns.iter().map(|c| format_ident!("{}", c.to_upper_camel_case()));
+    quote! {
+        impl Entity {
+            /// ALTER TABLE statements adding the database-generated columns
+            pub fn generated_column_statements(
+                backend: sea_orm::DbBackend,
+            ) -> Vec<sea_orm::sea_query::TableAlterStatement> {
+                let schema = sea_orm::Schema::new(backend);
+                vec![#(
+                    sea_orm::sea_query::Table::alter()
+                        .table(Entity)
+                        .add_column(schema.get_column_def::<Entity>(Column::#variants))
+                        .to_owned()
+                ),*]
+            }
+        }
+    }
+}
+
+/// Generate `Entity::search_statements()` for `searchable` columns
+///
+/// The `search_vector` column is generated by Postgres from the searchable
+/// columns and is not part of the model; the statements add it and its GIN
+/// index to the table.
+fn generate_search_vector(table_name: &str, columns: &[String]) -> TokenStream {
+    if columns.is_empty() {
+        return quote! {};
+    }
+    let document = columns
+        .iter()
+        .map(|c| format!("coalesce(\"{}\", '')", c))
+        .collect::<Vec<_>>()
+        .join(" || ' ' || ");
+    let add_column = format!(
+        "ALTER TABLE \"{table}\" ADD COLUMN IF NOT EXISTS {column} tsvector \
+         GENERATED ALWAYS AS (to_tsvector('{config}', {document})) STORED",
+        table = table_name,
+        column = SEARCH_VECTOR_COLUMN,
+        config = SEARCH_CONFIG,
+        document = document,
+    );
+    let create_index = format!(
+        "CREATE INDEX IF NOT EXISTS \"idx_{table}_{column}\" ON \"{table}\" USING GIN ({column})",
+        table = table_name,
+        column = SEARCH_VECTOR_COLUMN,
+    );
+    quote! {
+        impl Entity {
+            /// Statements adding the full-text search column and its GIN index (Postgres)
+            pub fn search_statements() -> [&'static str; 2] {
+                [#add_column, #create_index]
+            }
+        }
+    }
+}
+
+/// Name of an index: its declared name, or `idx_{table}_{columns}`
+pub(super) fn index_name(index: &storage::IndexDef, table_name: &str) -> String {
+    if index.name.is_empty() {
+        format!("idx_{}_{}", table_name, index.columns.join("_"))
+    } else {
+        index.name.clone()
+    }
+}
+
+/// Apply index definitions to the entity
+///
+/// Single-column indexes become `indexed`/`unique` column attributes and
+/// multi-column unique indexes a shared `unique_key`, so schema sync picks
+/// them up. Every index is also emitted from `Entity::index_statements()` for
+/// use in migrations.
+fn generate_indexes(
+    indexes: &[storage::IndexDef],
+    table_name: &str,
+    fields: &mut [GeneratedField],
+) -> Result<TokenStream, GeneratorError> {
+    if indexes.is_empty() {
+        return Ok(quote! {});
+    }
+
+    let mut statements = Vec::new();
+    for index in indexes {
+        if index.columns.is_empty() {
+            return Err(GeneratorError::CodeGenError(format!(
+                "index on {} has no columns",
+                table_name
+            )));
+        }
+        if let Some(missing) = index
+            .columns
+            .iter()
+            .find(|c| !fields.iter().any(|f| &f.name == *c))
+        {
+            return Err(GeneratorError::CodeGenError(format!(
+                "index on {} references unknown column {}",
+                table_name, missing
+            )));
+        }
+
+        let name = index_name(index, table_name);
+
+        let attr = match (index.columns.len(), index.unique) {
+            (1, true) => "unique".to_string(),
+            (1, false) => "indexed".to_string(),
+            (_, true) => format!("unique_key = \"{}\"", name),
+            (_, false) => String::new(),
+        };
+        if !attr.is_empty() {
+            for field in fields.iter_mut().filter(|f| index.columns.contains(&f.name)) {
+                if !field.attributes.contains(&attr) {
+                    field.attributes.push(attr.clone());
+                }
+            }
+        }
+
+        let columns = index.columns.iter().map(|c| {
+            let variant = format_ident!("{}", c.to_upper_camel_case());
+            quote! { .col(Column::#variant) }
+        });
+        let unique = if index.unique { quote! { .unique() } } else { quote! {} };
+        statements.push(quote! {
+            sea_orm::sea_query::Index::create()
+                .name(#name)
+                .table(Entity)
+                #(#columns)*
+                #unique
+                .if_not_exists()
+                .to_owned()
+        });
+    }
+
+    Ok(quote! {
+        impl Entity {
+            /// CREATE INDEX statements for the indexes declared in the proto
+            pub fn index_statements() -> Vec<sea_orm::sea_query::IndexCreateStatement> {
+                vec![#(#statements),*]
+            }
+        }
+    })
+}
+
 /// Generate fields for all oneofs in a message
 fn generate_oneof_fields(message: &DescriptorProto) -> Vec<TokenStream> {
     let oneofs = extract_oneofs(message);
@@ -263,7 +731,7 @@ fn generate_oneof_fields(message: &DescriptorProto) -> Vec<TokenStream> {
 }
 
 /// Determine if a field should be nullable based on proto definition
-fn is_field_nullable(field: &FieldDescriptorProto) -> bool {
+pub(crate) fn is_field_nullable(field: &FieldDescriptorProto) -> bool {
     // In proto3, repeated fields are never nullable (they're empty arrays)
     let label = field.label();
     if label == Label::Repeated {
@@ -290,7 +758,7 @@ fn map_column_type_to_rust(column_type: &str, is_nullable: bool) -> String {
         "date" => "Date",
         "time" => "Time",
         "datetime" => "DateTime",
-        "timestamp" | "timestampwithtimezone" => "DateTimeUtc",
+        "timestamp" | "timestampwithtimezone" => time_crate().datetime_type(),
         "binary" | "blob" | "varbinary" => "Vec<u8>",
         _ => column_type, // Use as-is if unknown
     };
@@ -318,9 +786,108 @@ fn extract_embedded_type_name(type_name: Option<&str>) -> String {
                 .trim_start_matches('.');
 
             // Convert to PascalCase for Rust convention
-            use heck::ToUpperCamelCase;
             type_part.to_upper_camel_case()
         }
         None => "serde_json::Value".to_string(), // Fallback for unknown types
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str) -> GeneratedField {
+        GeneratedField {
+            name: name.to_string(),
+            rust_type: "String".to_string(),
+            attributes: Vec::new(),
+            pii: false,
+            primary_key: false,
+            ordered: true,
+        }
+    }
+
+    #[test]
+    fn test_composite_unique_index() {
+        let mut fields = vec![field("org_id"), field("email")];
+        let indexes = vec![storage::IndexDef {
+            columns: vec!["org_id".to_string(), "email".to_string()],
+            unique: true,
+            name: String::new(),
+        }];
+
+        let tokens = generate_indexes(&indexes, "users", &mut fields).unwrap().to_string();
+        assert!(tokens.contains("idx_users_org_id_email"));
+        assert!(tokens.contains("Column :: OrgId"));
+        assert!(tokens.contains("unique ()"));
+        assert_eq!(fields[0].attributes, vec!["unique_key = \"idx_users_org_id_email\""]);
+    }
+
+    #[test]
+    fn test_generate_generated_columns() {
+        assert!(generate_generated_columns(&[]).is_empty());
+        let code = generate_generated_columns(&["email_lower".to_string()]).to_string();
+        assert!(code.contains("fn generated_column_statements"));
+        assert!(code.contains("Column :: EmailLower"));
+    }
+
+    #[test]
+    fn test_generate_search_vector() {
+        assert!(generate_search_vector("posts", &[]).is_empty());
+        let code = generate_search_vector("posts", &["title".to_string(), "content".to_string()]).to_string();
+        assert!(code.contains("fn search_statements () -> [& 'static str ; 2]"));
+        assert!(code.contains("to_tsvector('english', coalesce(\\\"title\\\", '') || ' ' || coalesce(\\\"content\\\", ''))"));
+        assert!(code.contains("USING GIN (search_vector)"));
+    }
+
+    #[test]
+    fn test_index_unknown_column() {
+        let mut fields = vec![field("email")];
+        let indexes = vec![storage::IndexDef {
+            columns: vec!["missing".to_string()],
+            ..Default::default()
+        }];
+        assert!(generate_indexes(&indexes, "users", &mut fields).is_err());
+    }
+
+    #[test]
+    fn test_diff_masks_pii() {
+        let mut email = field("email");
+        email.pii = true;
+        let tokens = generate_diff_impl(&[field("name"), email]).to_string();
+        assert!(tokens.contains("pub fn diff (old : & Model , new : & Model) -> Vec < FieldChange >"));
+        assert!(tokens.contains("if old . name != new . name"));
+        assert!(tokens.contains("Pii (& old . email)"));
+        assert!(tokens.contains("sensitive : true"));
+        assert!(!tokens.contains("Pii (& old . name)"));
+    }
+
+    #[test]
+    fn test_compare_by_primary_key() {
+        let mut id = field("id");
+        id.primary_key = true;
+        let mut payload = field("payload");
+        payload.ordered = false;
+        let fields = [id, field("title"), payload];
+
+        assert!(generate_compare_impl(&fields, storage::CompareBy::Unspecified, "Post")
+            .unwrap()
+            .is_empty());
+
+        let tokens = generate_compare_impl(&fields, storage::CompareBy::PrimaryKey, "Post")
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains("self . id == other . id }"));
+        assert!(tokens.contains("self . id . cmp (& other . id) }"));
+        assert!(tokens.contains("Column :: Title => self . title . cmp (& other . title)"));
+        assert!(!tokens.contains("Column :: Payload"));
+
+        let tokens = generate_compare_impl(&fields, storage::CompareBy::AllColumns, "Post")
+            .unwrap()
+            .to_string();
+        assert!(tokens.contains("then_with (|| self . title . cmp (& other . title))"));
+        assert!(!tokens.contains("self . payload =="));
+
+        assert!(generate_compare_impl(&[field("title")], storage::CompareBy::PrimaryKey, "Post").is_err());
+    }
+}
@@ -96,8 +96,9 @@ fn generate_enum_tokens(
     // Build prefix for stripping from values
     let prefix = format!("{}_", rust_enum_name.to_shouty_snake_case());
 
-    // Generate variants
+    // Generate variants, and their proto numbers for the `i32` conversion
     let mut variants = Vec::new();
+    let mut proto_numbers = Vec::new();
     let mut has_default = false;
 
     for value in &enum_desc.value {
@@ -177,6 +178,7 @@ fn generate_enum_tokens(
             #value_attr
             #variant_ident
         });
+        proto_numbers.push(quote! { #enum_ident::#variant_ident => #value_number });
     }
 
     // Generate type attributes based on db_type
@@ -195,9 +197,9 @@ fn generate_enum_tokens(
 
     // Add Default derive if we have a default variant
     let derives = if has_default {
-        quote! { #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum)] }
+        quote! { #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, EnumIter, DeriveActiveEnum)] }
     } else {
-        quote! { #[derive(Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum)] }
+        quote! { #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, EnumIter, DeriveActiveEnum)] }
     };
 
     Ok(quote! {
@@ -213,6 +215,15 @@ fn generate_enum_tokens(
         pub enum #enum_ident {
             #(#variants),*
         }
+
+        /// Proto enum value of a column value, whatever the column stores
+        impl From<#enum_ident> for i32 {
+            fn from(value: #enum_ident) -> Self {
+                match value {
+                    #(#proto_numbers,)*
+                }
+            }
+        }
     })
 }
 
@@ -237,6 +248,9 @@ fn format_code(tokens: TokenStream) -> Result<String, GeneratorError> {
     let parsed = syn::parse_file(&code).map_err(|e| {
         GeneratorError::CodeGenError(format!("Failed to parse generated code: {}", e))
     })?;
+    if crate::params::minimal() {
+        return Ok(code);
+    }
     Ok(prettyplease::unparse(&parsed))
 }
 
@@ -311,6 +325,17 @@ mod tests {
         assert!(code.contains("num_value"));
     }
 
+    #[test]
+    fn test_generate_enum_tokens_converts_to_proto_numbers() {
+        let enum_desc = create_test_enum();
+        let tokens = generate_enum_tokens("test.proto", &enum_desc, "Status", DbType::String).unwrap();
+        let code = tokens.to_string();
+
+        assert!(code.contains("impl From < Status > for i32"));
+        assert!(code.contains("Status :: Active => 1i32"));
+        assert!(code.contains("Status :: Inactive => 2i32"));
+    }
+
     #[test]
     fn test_convert_enum_variant_name() {
         // With matching prefix - should strip it
@@ -0,0 +1,599 @@
+//! Relation expansion for Get RPCs
+//!
+//! A Get request with a `repeated string expand` field and a response with an
+//! `{Entity}Node` field return a small object graph in one call. A node
+//! message holds the entity itself plus one field per relation to expand,
+//! typed as the related entity or, to expand further, its `{Related}Node`:
+//!
+//! ```protobuf
+//! message AuthorNode {
+//!   Author author = 1;
+//!   repeated PostNode posts = 2;
+//! }
+//! ```
+//!
+//! The storage default fetches each relation named in `expand` (dotted
+//! paths such as `posts.author`) down to `MAX_EXPAND_DEPTH` relations, and
+//! rejects paths the node messages do not declare. Like the GraphQL
+//! DataLoaders, each relation is loaded for all rows of its level with one
+//! `IN` query, so a path costs one query per relation rather than one per
+//! row. A has_many relation fails with INVALID_ARGUMENT when a row has more
+//! than `MAX_EXPAND_CHILDREN` children; list those through their own RPC.
+
+use super::options::{get_cached_entity_options_in_package, storage};
+use crate::error::GeneratorError;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+
+/// Most relations an `expand` path may chain, bounding the queries per call
+pub const MAX_EXPAND_DEPTH: usize = 3;
+
+/// Most children a has_many relation may expand per row, bounding the rows per call
+pub const MAX_EXPAND_CHILDREN: u64 = 100;
+
+/// Name of the Get request field listing the relations to expand
+const EXPAND_FIELD: &str = "expand";
+
+/// Generated expansion of a Get RPC
+pub struct Expansion {
+    /// Response field holding the `{Entity}Node`
+    pub field: proc_macro2::Ident,
+    /// Statements rejecting unknown `expand` paths
+    pub check: TokenStream,
+    /// Expression building the optional node from `model`
+    pub load: TokenStream,
+}
+
+/// A message and the package declaring it
+#[derive(Clone, Copy)]
+struct Message<'a> {
+    package: &'a str,
+    message: &'a DescriptorProto,
+}
+
+impl Message<'_> {
+    fn name(&self) -> &str {
+        self.message.name.as_deref().unwrap_or("")
+    }
+
+    /// Fully qualified type name, as used by `FieldDescriptorProto::type_name`
+    fn type_name(&self) -> String {
+        format!(".{}.{}", self.package, self.name())
+    }
+
+    fn field(&self, name: &str) -> Option<&FieldDescriptorProto> {
+        self.message.field.iter().find(|f| f.name.as_deref() == Some(name))
+    }
+}
+
+/// Find a message by package and name
+fn find_message<'a>(all_files: &'a [FileDescriptorProto], package: &str, name: &str) -> Option<Message<'a>> {
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .find_map(|f| {
+            let message = f.message_type.iter().find(|m| m.name.as_deref() == Some(name))?;
+            Some(Message {
+                package: f.package.as_deref().unwrap_or(""),
+                message,
+            })
+        })
+}
+
+/// Split a relation's `related` name into its package and message name
+fn split_related<'a>(package: &'a str, related: &'a str) -> (&'a str, &'a str) {
+    let related = related.trim_start_matches('.');
+    related.rsplit_once('.').unwrap_or((package, related))
+}
+
+/// Whether a Get request declares `repeated string expand`
+fn has_expand_field(request: &DescriptorProto) -> bool {
+    request.field.iter().any(|f| {
+        f.name.as_deref() == Some(EXPAND_FIELD) && f.label() == Label::Repeated && f.r#type() == Type::String
+    })
+}
+
+/// The response field typed as the entity's `{Entity}Node`, if any
+fn node_field<'a>(response: &'a DescriptorProto, package: &str, entity_name: &str) -> Option<&'a FieldDescriptorProto> {
+    let node_type = format!(".{}.{}Node", package, entity_name);
+    response
+        .field
+        .iter()
+        .find(|f| f.type_name.as_deref() == Some(node_type.as_str()))
+}
+
+/// Generate the expansion of a Get RPC, or `None` when it does not expand
+///
+/// `db_ref` is the connection expression the generated queries run on.
+pub fn generate(
+    all_files: &[FileDescriptorProto],
+    package: &str,
+    request_type: &str,
+    response_type: &str,
+    entity_name: &str,
+    db_ref: &TokenStream,
+) -> Result<Option<Expansion>, GeneratorError> {
+    let entity_name = entity_name.to_upper_camel_case();
+    let (Some(request), Some(response)) = (
+        find_message(all_files, package, request_type),
+        find_message(all_files, package, response_type),
+    ) else {
+        return Ok(None);
+    };
+    let expand = has_expand_field(request.message);
+    let node = node_field(response.message, package, &entity_name);
+    let node = match (expand, node) {
+        (false, None) => return Ok(None),
+        (true, Some(node)) => node,
+        (true, None) => {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{} has an `expand` field but {} has no {}Node field to return the expansion in",
+                request_type, response_type, entity_name
+            )));
+        }
+        (false, Some(_)) => {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{} returns a {}Node but {} has no `repeated string expand` field",
+                response_type, entity_name, request_type
+            )));
+        }
+    };
+
+    let node_name = format!("{}Node", entity_name);
+    let (Some(node_message), Some(entity)) = (
+        find_message(all_files, package, &node_name),
+        find_message(all_files, package, &entity_name),
+    ) else {
+        return Err(GeneratorError::CodeGenError(format!(
+            "{}.{} names {} but the message or entity is not in package {}",
+            response_type,
+            node.name.as_deref().unwrap_or(""),
+            node_name,
+            package
+        )));
+    };
+
+    let mut walker = Walker {
+        all_files,
+        package,
+        db_ref,
+        paths: Vec::new(),
+    };
+    let models = format_ident!("models_0");
+    let load = walker.generate_nodes(node_message, entity, &models, "", 0)?;
+    let paths = walker.paths;
+
+    let check = quote! {
+        const EXPANDABLE: &[&str] = &[#(#paths),*];
+        if let Some(path) = request.expand.iter().find(|p| !EXPANDABLE.contains(&p.as_str())) {
+            return Err(StorageError::InvalidArgument(format!(
+                "cannot expand {:?}; expandable relations: {}",
+                path,
+                EXPANDABLE.join(", ")
+            )));
+        }
+    };
+    let load = quote! {
+        if request.expand.is_empty() {
+            None
+        } else {
+            use sea_orm::QuerySelect;
+            // A relation is loaded when named, or when a path through it is
+            let expands = |path: &str| {
+                request.expand.iter().any(|e| {
+                    e == path || e.strip_prefix(path).is_some_and(|rest| rest.starts_with('.'))
+                })
+            };
+            let models_0 = vec![model.clone()];
+            let nodes = #load;
+            nodes.into_iter().next()
+        }
+    };
+
+    Ok(Some(Expansion {
+        field: format_ident!("{}", node.name.as_deref().unwrap_or("").to_snake_case()),
+        check,
+        load,
+    }))
+}
+
+/// Walks node messages, generating their loads and collecting expandable paths
+struct Walker<'a> {
+    all_files: &'a [FileDescriptorProto],
+    /// Package of the Get RPC, where the generated code lives
+    package: &'a str,
+    db_ref: &'a TokenStream,
+    paths: Vec<String>,
+}
+
+impl<'a> Walker<'a> {
+    /// Rust path of a message's prost type
+    fn message_path(&self, message: Message<'_>) -> TokenStream {
+        let ident = format_ident!("{}", message.name());
+        if message.package == self.package {
+            quote! { #ident }
+        } else {
+            let segments = message.package.split('.').map(|s| format_ident!("{}", s.to_snake_case()));
+            quote! { crate #(::#segments)*::#ident }
+        }
+    }
+
+    /// Rust path of an entity's SeaORM module
+    fn entity_path(&self, entity: Message<'_>) -> TokenStream {
+        let module = format_ident!("{}", entity.name().to_snake_case());
+        if entity.package == self.package {
+            quote! { entities::#module }
+        } else {
+            let segments = entity.package.split('.').map(|s| format_ident!("{}", s.to_snake_case()));
+            quote! { crate #(::#segments)*::entities::#module }
+        }
+    }
+
+    /// Generate an expression building an `{Entity}Node` per row of `models`
+    ///
+    /// `models` names a `Vec` of entity rows, consumed into the nodes' entity
+    /// fields once every relation of the level is loaded.
+    fn generate_nodes(
+        &mut self,
+        node: Message<'a>,
+        entity: Message<'a>,
+        models: &proc_macro2::Ident,
+        path: &str,
+        depth: usize,
+    ) -> Result<TokenStream, GeneratorError> {
+        let entity_type = entity.type_name();
+        let Some(self_field) = node
+            .message
+            .field
+            .iter()
+            .find(|f| f.type_name.as_deref() == Some(entity_type.as_str()))
+        else {
+            return Err(GeneratorError::CodeGenError(format!(
+                "{} needs a field of type {} holding the entity",
+                node.name(),
+                entity.name()
+            )));
+        };
+        let relations = get_cached_entity_options_in_package(entity.package, entity.name())
+            .map(|o| o.relations)
+            .unwrap_or_default();
+
+        let nodes_var = format_ident!("nodes_{}", depth);
+        let mut loads = Vec::new();
+        for field in &node.message.field {
+            if std::ptr::eq(field, self_field) {
+                continue;
+            }
+            let field_name = field.name.as_deref().unwrap_or("");
+            let Some(relation) = relations.iter().find(|r| r.name.to_snake_case() == field_name) else {
+                return Err(GeneratorError::CodeGenError(format!(
+                    "{}.{} does not name a relation of {}",
+                    node.name(),
+                    field_name,
+                    entity.name()
+                )));
+            };
+            let load = self.generate_relation(node, entity, models, &nodes_var, field, relation, path, depth)?;
+            loads.extend(load);
+        }
+
+        let node_type = self.message_path(node);
+        let self_ident = format_ident!("{}", self_field.name.as_deref().unwrap_or("").to_snake_case());
+        Ok(quote! {{
+            let mut #nodes_var: Vec<#node_type> = #models.iter().map(|_| #node_type::default()).collect();
+            #(#loads)*
+            for (node, model) in #nodes_var.iter_mut().zip(#models) {
+                node.#self_ident = Some(model.into());
+            }
+            #nodes_var
+        }})
+    }
+
+    /// Generate the batched load of one relation field of a node, `None` past the depth limit
+    #[allow(clippy::too_many_arguments)]
+    fn generate_relation(
+        &mut self,
+        node: Message<'a>,
+        entity: Message<'a>,
+        models: &proc_macro2::Ident,
+        nodes_var: &proc_macro2::Ident,
+        field: &FieldDescriptorProto,
+        relation: &storage::RelationDef,
+        path: &str,
+        depth: usize,
+    ) -> Result<Option<TokenStream>, GeneratorError> {
+        let field_name = field.name.as_deref().unwrap_or("");
+        let invalid = |reason: String| {
+            GeneratorError::CodeGenError(format!("{}.{}: {}", node.name(), field_name, reason))
+        };
+
+        // Remote rows live in another service's database
+        if relation.remote {
+            return Err(invalid(format!("{} is a remote relation", relation.name)));
+        }
+
+        let (related_package, related_name) = split_related(entity.package, &relation.related);
+        let related_name = related_name.to_upper_camel_case();
+        let Some(related) = find_message(self.all_files, related_package, &related_name) else {
+            return Err(invalid(format!("related entity {} not found", relation.related)));
+        };
+
+        // The field holds the related entity, or its node to expand further
+        let field_type = field.type_name.as_deref().unwrap_or("");
+        let child = if field_type == related.type_name() {
+            None
+        } else if field_type == format!("{}Node", related.type_name()) {
+            let child_name = format!("{}Node", related_name);
+            Some(
+                find_message(self.all_files, related_package, &child_name)
+                    .ok_or_else(|| invalid(format!("{} not found", child_name)))?,
+            )
+        } else {
+            return Err(invalid(format!("must be a {} or {}Node", related_name, related_name)));
+        };
+
+        let relation_type = relation.r#type();
+        let repeated = field.label() == Label::Repeated;
+        match relation_type {
+            storage::RelationType::BelongsTo | storage::RelationType::HasOne if repeated => {
+                return Err(invalid("a single relation cannot be repeated".to_string()));
+            }
+            storage::RelationType::HasMany if !repeated => {
+                return Err(invalid("a has_many relation must be repeated".to_string()));
+            }
+            storage::RelationType::BelongsTo | storage::RelationType::HasOne | storage::RelationType::HasMany => {}
+            _ => {
+                return Err(invalid(format!("{:?} relations cannot be expanded", relation_type)));
+            }
+        }
+
+        if depth >= MAX_EXPAND_DEPTH {
+            return Ok(None);
+        }
+        let child_path = if path.is_empty() {
+            field_name.to_string()
+        } else {
+            format!("{}.{}", path, field_name)
+        };
+        self.paths.push(child_path.clone());
+
+        let rows = format_ident!("models_{}", depth + 1);
+        let values = match child {
+            Some(child) => self.generate_nodes(child, related, &rows, &child_path, depth + 1)?,
+            None => {
+                let related_type = self.message_path(related);
+                quote! { #rows.into_iter().map(Into::into).collect::<Vec<#related_type>>() }
+            }
+        };
+
+        let target = self.entity_path(related);
+        let not_deleted = if get_cached_entity_options_in_package(related.package, related.name())
+            .is_some_and(|o| o.soft_delete)
+        {
+            quote! { .filter(#target::Column::DeletedAt.is_null()) }
+        } else {
+            quote! {}
+        };
+        let references = if relation.references.is_empty() {
+            "id".to_string()
+        } else {
+            relation.references.to_snake_case()
+        };
+
+        // Rows of this level and related rows are matched on a key column of
+        // each: a belongs_to's foreign key here against the referenced column
+        // there, otherwise the referenced column here against the foreign key there
+        let (parent_column, parent_field, related_column, related_field) =
+            if relation_type == storage::RelationType::BelongsTo {
+                let foreign_key = if relation.foreign_key.is_empty() {
+                    format!("{}_id", relation.name.to_snake_case())
+                } else {
+                    relation.foreign_key.to_snake_case()
+                };
+                let parent_field = entity.field(&foreign_key);
+                let related_field = related.field(&references);
+                (foreign_key, parent_field, references, related_field)
+            } else {
+                let foreign_key = if relation.foreign_key.is_empty() {
+                    format!("{}_id", entity.name().to_snake_case())
+                } else {
+                    relation.foreign_key.to_snake_case()
+                };
+                let parent_field = entity.field(&references);
+                let related_field = related.field(&foreign_key);
+                (references, parent_field, foreign_key, related_field)
+            };
+        let model = format_ident!("model");
+        let row = format_ident!("row");
+        let parent_key = key_expr(&model, &parent_column, parent_field);
+        let related_key = key_expr(&row, &related_column, related_field);
+        let column = format_ident!("{}", related_column.to_upper_camel_case());
+        let db_ref = self.db_ref;
+        let field_ident = format_ident!("{}", field_name);
+
+        // has_many reads at most one child past the cap per row, then rejects the call
+        let (limit, count, cap) = if relation_type == storage::RelationType::HasMany {
+            let too_many = format!(
+                "cannot expand {:?}: more than {} {} per {}",
+                child_path,
+                MAX_EXPAND_CHILDREN,
+                field_name,
+                entity.name()
+            );
+            (
+                quote! { .limit(keys.len() as u64 * #MAX_EXPAND_CHILDREN + 1) },
+                quote! { let row_count = #rows.len() as u64; },
+                quote! {
+                    if row_count > keys.len() as u64 * #MAX_EXPAND_CHILDREN
+                        || by_key.values().any(|values| values.len() as u64 > #MAX_EXPAND_CHILDREN)
+                    {
+                        return Err(StorageError::InvalidArgument(#too_many.to_string()));
+                    }
+                },
+            )
+        } else {
+            (quote! {}, quote! {}, quote! {})
+        };
+        let assign = if relation_type == storage::RelationType::HasMany {
+            quote! { node.#field_ident = values.clone(); }
+        } else {
+            quote! { node.#field_ident = values.first().cloned(); }
+        };
+
+        Ok(Some(quote! {
+            if expands(#child_path) {
+                let keys: Vec<_> = #models.iter().filter_map(|#model| #parent_key).collect();
+                let #rows = if keys.is_empty() {
+                    Vec::new()
+                } else {
+                    #target::Entity::find()
+                        .filter(#target::Column::#column.is_in(keys.clone()))
+                        #not_deleted
+                        #limit
+                        .all(#db_ref)
+                        .await
+                        .map_err(StorageError::Database)?
+                };
+                let row_keys: Vec<_> = #rows.iter().map(|#row| #related_key).collect();
+                #count
+                let values = #values;
+                let mut by_key = std::collections::HashMap::<_, Vec<_>>::new();
+                for (key, value) in row_keys.into_iter().zip(values) {
+                    if let Some(key) = key {
+                        by_key.entry(key).or_default().push(value);
+                    }
+                }
+                #cap
+                for (node, #model) in #nodes_var.iter_mut().zip(&#models) {
+                    if let Some(values) = #parent_key.and_then(|key| by_key.get(&key)) {
+                        #assign
+                    }
+                }
+            }
+        }))
+    }
+}
+
+/// Read a key column of `model` as an `Option`, cloning so the row stays usable
+///
+/// Optional columns are `Option`s already; rows of a batch are matched on the
+/// inner value, and rows without a key match nothing.
+fn key_expr(model: &proc_macro2::Ident, column: &str, field: Option<&FieldDescriptorProto>) -> TokenStream {
+    let column = format_ident!("{}", column);
+    if field.is_some_and(|f| f.proto3_optional.unwrap_or(false)) {
+        quote! { #model.#column.clone() }
+    } else {
+        quote! { Some(#model.#column.clone()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{field, message_field, repeated};
+
+    #[test]
+    fn test_split_related() {
+        assert_eq!(split_related("blog", "Author"), ("blog", "Author"));
+        assert_eq!(split_related("blog", "iam.User"), ("iam", "User"));
+        assert_eq!(split_related("blog", ".iam.User"), ("iam", "User"));
+    }
+
+    #[test]
+    fn test_expand_and_node_fields() {
+        let request = DescriptorProto {
+            name: Some("GetAuthorRequest".to_string()),
+            field: vec![
+                field("id", 1, Type::Int64),
+                repeated(field("expand", 2, Type::String)),
+            ],
+            ..Default::default()
+        };
+        assert!(has_expand_field(&request));
+
+        let singular = DescriptorProto {
+            field: vec![field("expand", 1, Type::String)],
+            ..Default::default()
+        };
+        assert!(!has_expand_field(&singular));
+
+        let response = DescriptorProto {
+            name: Some("GetAuthorResponse".to_string()),
+            field: vec![
+                message_field("author", 1, ".blog.Author"),
+                message_field("graph", 2, ".blog.AuthorNode"),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            node_field(&response, "blog", "Author").and_then(|f| f.name.as_deref()),
+            Some("graph")
+        );
+        assert!(node_field(&response, "blog", "Post").is_none());
+    }
+
+    #[test]
+    fn test_has_many_loads_each_level_in_one_batch() {
+        use super::super::options::{cache_entity_options, cache_file_package};
+
+        let file_name = "expand_batch/blog.proto";
+        cache_file_package(file_name, "expand_batch");
+        cache_entity_options(
+            file_name,
+            "Author",
+            storage::EntityOptions {
+                relations: vec![storage::RelationDef {
+                    name: "posts".to_string(),
+                    r#type: storage::RelationType::HasMany as i32,
+                    related: "Post".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+        let message = |name: &str, fields: Vec<FieldDescriptorProto>| DescriptorProto {
+            name: Some(name.to_string()),
+            field: fields,
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some(file_name.to_string()),
+            package: Some("expand_batch".to_string()),
+            message_type: vec![
+                message("Author", vec![field("id", 1, Type::Int64)]),
+                message("Post", vec![field("author_id", 1, Type::Int64)]),
+                message(
+                    "AuthorNode",
+                    vec![
+                        message_field("author", 1, ".expand_batch.Author"),
+                        repeated(message_field("posts", 2, ".expand_batch.Post")),
+                    ],
+                ),
+                message(
+                    "GetAuthorRequest",
+                    vec![repeated(field("expand", 1, Type::String))],
+                ),
+                message(
+                    "GetAuthorResponse",
+                    vec![message_field("graph", 1, ".expand_batch.AuthorNode")],
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let files = [file];
+        let db = quote! { db };
+        let expansion = generate(&files, "expand_batch", "GetAuthorRequest", "GetAuthorResponse", "Author", &db)
+            .unwrap()
+            .unwrap();
+        let load = expansion.load.to_string();
+        assert!(load.contains("entities :: post :: Column :: AuthorId . is_in (keys . clone ())"));
+        assert!(load.contains(". limit (keys . len () as u64 * 100u64 + 1)"));
+        assert!(load.contains("more than 100 posts per Author"));
+        assert!(load.contains("by_key . get (& key)"));
+        assert!(!load.contains(". one ("));
+    }
+}
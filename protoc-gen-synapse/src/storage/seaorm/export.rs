@@ -0,0 +1,218 @@
+//! Encrypted export generation
+//!
+//! Generates the storage/export.rs module for entities marked with
+//! `encrypted_export`. Each export function reads every row, serializes it
+//! as a JSON line, and encrypts the result to age recipients from config so
+//! plaintext exports never touch disk. Keys of entities whose ids the API
+//! hides are written as external ids.
+
+use super::external_id::{collect_hidden_ids, hidden_id_columns};
+use super::package::PackageInfo;
+use crate::error::GeneratorError;
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::{format_ident, quote};
+
+/// Environment variable holding comma-separated age recipient keys
+const RECIPIENTS_ENV: &str = "SYNAPSE_EXPORT_RECIPIENTS";
+
+/// Generate the storage/export.rs file for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+    info: &PackageInfo,
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || info.exports.is_empty() || info.services.is_empty() {
+        return Ok(None);
+    }
+
+    let hidden: Vec<String> = collect_hidden_ids(all_files, file).into_iter().map(|(entity, _)| entity).collect();
+    let functions: Vec<TokenStream> = info
+        .exports
+        .iter()
+        .map(|e| generate_export_fn(e, &hidden_id_columns(package, e, &hidden)))
+        .collect();
+    let encode_ids = if hidden.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            /// Replace key columns of a row with external ids
+            fn encode_ids(row: &mut serde_json::Value, columns: &[(&str, &str)]) {
+                for (column, entity) in columns {
+                    if let Some(value) = row.get_mut(*column) {
+                        let id = match value {
+                            serde_json::Value::Number(n) => n.to_string(),
+                            serde_json::Value::String(s) => s.clone(),
+                            _ => continue,
+                        };
+                        *value = serde_json::Value::String(super::super::ids::external_id(entity, id));
+                    }
+                }
+            }
+        }
+    };
+    let recipients_env = RECIPIENTS_ENV;
+
+    let code = quote! {
+        //! Encrypted entity exports
+        //!
+        //! Rows are written as JSON lines and encrypted to the configured age
+        //! recipients before being returned.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use std::io::Write;
+
+        use super::super::entities;
+        use sea_orm::{DatabaseConnection, EntityTrait};
+
+        /// Export error type
+        #[derive(Debug, thiserror::Error)]
+        pub enum ExportError {
+            /// Database error
+            #[error("database error: {0}")]
+            Database(#[from] sea_orm::DbErr),
+            /// No recipients configured, or a recipient key failed to parse
+            #[error("invalid recipients: {0}")]
+            Recipients(String),
+            /// Encryption failed
+            #[error("encryption error: {0}")]
+            Encryption(String),
+        }
+
+        /// Recipients that export files are encrypted to
+        #[derive(Debug, Clone, Default)]
+        pub struct ExportConfig {
+            /// age public keys (`age1...`)
+            pub recipients: Vec<String>,
+        }
+
+        impl ExportConfig {
+            /// Read recipients from the comma-separated export recipients variable
+            pub fn from_env() -> Self {
+                let recipients = std::env::var(#recipients_env)
+                    .map(|v| {
+                        v.split(',')
+                            .map(|s| s.trim().to_string())
+                            .filter(|s| !s.is_empty())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Self { recipients }
+            }
+        }
+
+        /// Encrypt plaintext to every configured recipient
+        ///
+        /// Refuses to run without recipients rather than falling back to plaintext.
+        pub fn encrypt(config: &ExportConfig, plaintext: &[u8]) -> Result<Vec<u8>, ExportError> {
+            if config.recipients.is_empty() {
+                return Err(ExportError::Recipients("no export recipients configured".into()));
+            }
+
+            let recipients = config
+                .recipients
+                .iter()
+                .map(|r| r.parse::<age::x25519::Recipient>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| ExportError::Recipients(e.to_string()))?;
+
+            let encryptor = age::Encryptor::with_recipients(
+                recipients.iter().map(|r| r as &dyn age::Recipient),
+            )
+            .map_err(|e| ExportError::Encryption(e.to_string()))?;
+
+            let mut output = Vec::new();
+            let mut writer = encryptor
+                .wrap_output(&mut output)
+                .map_err(|e| ExportError::Encryption(e.to_string()))?;
+            writer
+                .write_all(plaintext)
+                .map_err(|e| ExportError::Encryption(e.to_string()))?;
+            writer
+                .finish()
+                .map_err(|e| ExportError::Encryption(e.to_string()))?;
+
+            Ok(output)
+        }
+
+        #encode_ids
+
+        #(#functions)*
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/storage/export.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate an `export_{entity}` function
+///
+/// `id_columns` are the key columns written as external ids.
+fn generate_export_fn(entity: &str, id_columns: &[(String, String)]) -> TokenStream {
+    let entity_module = format_ident!("{}", entity.to_snake_case());
+    let fn_ident = format_ident!("export_{}", entity.to_snake_case());
+    let doc = format!(
+        "Export every {} row as encrypted JSON lines",
+        entity.to_snake_case()
+    );
+
+    let (row_binding, encode) = if id_columns.is_empty() {
+        (quote! { row }, quote! {})
+    } else {
+        let columns = id_columns.iter().map(|(column, entity)| quote! { (#column, #entity) });
+        (quote! { mut row }, quote! { encode_ids(&mut row, &[#(#columns),*]); })
+    };
+
+    quote! {
+        #[doc = #doc]
+        pub async fn #fn_ident(
+            db: &DatabaseConnection,
+            config: &ExportConfig,
+        ) -> Result<Vec<u8>, ExportError> {
+            let rows = entities::#entity_module::Entity::find()
+                .into_json()
+                .all(db)
+                .await?;
+
+            let mut plaintext = Vec::new();
+            for #row_binding in rows {
+                #encode
+                plaintext.extend_from_slice(row.to_string().as_bytes());
+                plaintext.push(b'\n');
+            }
+
+            encrypt(config, &plaintext)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_export_fn() {
+        let code = generate_export_fn("User", &[]).to_string();
+        assert!(code.contains("export_user"));
+        assert!(code.contains("into_json"));
+        assert!(code.contains("encrypt (config"));
+        assert!(!code.contains("encode_ids"));
+
+        let columns = [("id".to_string(), "User".to_string()), ("org_id".to_string(), "Org".to_string())];
+        let code = generate_export_fn("User", &columns).to_string();
+        assert!(code.contains("encode_ids (& mut row , & [(\"id\" , \"User\") , (\"org_id\" , \"Org\")])"));
+    }
+}
@@ -0,0 +1,290 @@
+//! External id generation for egress surfaces
+//!
+//! Entities whose GraphQL type is a Relay node hide their primary keys behind
+//! global ids at the API. For packages with such entities, generates the
+//! ids.rs module whose `external_id` encodes keys the same way, so encrypted
+//! exports, outbox events and feeds never leak raw sequential ids. The
+//! encoding is pluggable through an `IdEncoder`: global ids by default, or
+//! sqids behind the generated crate's `sqids` feature. `internal_id` decodes
+//! them again, for consumers of events that look the rows up.
+
+use super::options::{
+    get_cached_entity_options, get_cached_entity_options_in_package,
+    get_cached_graphql_type_options, same_package_name, storage,
+};
+use crate::error::GeneratorError;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::quote;
+
+/// Module name of the generated ids.rs
+pub const IDS_MODULE: &str = "ids";
+
+/// Entities of the main file's package with hidden ids, and their GraphQL type names
+pub fn collect_hidden_ids(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> Vec<(String, String)> {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .flat_map(|file| {
+            let file_name = file.name.as_deref().unwrap_or("");
+            file.message_type.iter().filter_map(move |message| {
+                let msg_name = message.name.as_deref().unwrap_or("");
+                get_cached_entity_options(file_name, msg_name).filter(|o| !o.skip)?;
+                let type_options = get_cached_graphql_type_options(file_name, msg_name).filter(|o| o.node && !o.skip)?;
+                let type_name = if type_options.name.is_empty() {
+                    msg_name.to_upper_camel_case()
+                } else {
+                    type_options.name
+                };
+                Some((msg_name.to_string(), type_name))
+            })
+        })
+        .collect()
+}
+
+/// Whether the main file's package has entities with hidden ids
+pub fn package_hides_ids(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> bool {
+    !collect_hidden_ids(all_files, main_file).is_empty()
+}
+
+/// Whether an entity of the main file's package has hidden ids
+pub fn hides_ids(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto, entity: &str) -> bool {
+    collect_hidden_ids(all_files, main_file).iter().any(|(name, _)| name == entity)
+}
+
+/// Key columns of an entity holding hidden ids, with the entity they identify
+///
+/// These are the primary key of a hidden entity and the foreign keys of its
+/// `belongs_to` relations to hidden entities of the same package.
+pub fn hidden_id_columns(package: &str, entity: &str, hidden: &[String]) -> Vec<(String, String)> {
+    let is_hidden = |name: &str| hidden.iter().any(|h| h == name);
+    let mut columns = Vec::new();
+    if is_hidden(entity) {
+        columns.push(("id".to_string(), entity.to_string()));
+    }
+    let relations = get_cached_entity_options_in_package(package, entity)
+        .map(|o| o.relations)
+        .unwrap_or_default();
+    for relation in relations {
+        if relation.r#type() != storage::RelationType::BelongsTo {
+            continue;
+        }
+        let Some(related) = same_package_name(package, &relation.related).filter(|r| is_hidden(r)) else {
+            continue;
+        };
+        let foreign_key = if relation.foreign_key.is_empty() {
+            format!("{}_id", relation.name.to_snake_case())
+        } else {
+            relation.foreign_key.to_snake_case()
+        };
+        columns.push((foreign_key, related.to_string()));
+    }
+    columns
+}
+
+/// Generate ids.rs for a package with hidden ids
+pub fn generate(file: &FileDescriptorProto, all_files: &[FileDescriptorProto]) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    let hidden = collect_hidden_ids(all_files, file);
+    if package.is_empty() || hidden.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(File {
+        name: Some(format!("{}/{}.rs", package.replace('.', "/"), IDS_MODULE)),
+        content: Some(crate::params::format_code(
+            generate_module(&hidden, &crate::graphql::package_id_prefixes(all_files, file)?).to_string(),
+        )),
+        ..Default::default()
+    }))
+}
+
+/// Generate the `IdEncoder` trait, its encoders, and `external_id`
+///
+/// `prefixes` are the package's Stripe-style global ID prefixes, as (type name, prefix).
+fn generate_module(hidden: &[(String, String)], prefixes: &[(String, String)]) -> TokenStream {
+    // Same encoding as the GraphQL `id` field of node types
+    let (encode, decode) = if crate::params::minimal() {
+        (
+            quote! { raw.bytes().map(|b| format!("{:02x}", b)).collect::<String>() },
+            quote! {
+                (0..id.len())
+                    .step_by(2)
+                    .map(|i| id.get(i..i + 2).and_then(|hex| u8::from_str_radix(hex, 16).ok()))
+                    .collect::<Option<Vec<u8>>>()?
+            },
+        )
+    } else {
+        (
+            quote! {{
+                use base64::Engine;
+                base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(raw.as_bytes())
+            }},
+            quote! {{
+                use base64::Engine;
+                base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(id).ok()?
+            }},
+        )
+    };
+    let hidden = hidden.iter().map(|(entity, type_name)| quote! { (#entity, #type_name) });
+    // Types with Stripe-style global ids keep them here too
+    let (prefix_registry, prefixed, unprefixed) = if prefixes.is_empty() {
+        (quote! {}, quote! {}, quote! {})
+    } else {
+        let prefixes = prefixes.iter().map(|(type_name, prefix)| quote! { (#prefix, #type_name) });
+        (
+            quote! {
+                /// Stripe-style global ID prefixes and their types
+                const ID_PREFIXES: &[(&str, &str)] = &[#(#prefixes),*];
+            },
+            quote! {
+                if let Some((prefix, _)) = ID_PREFIXES.iter().find(|(_, t)| *t == type_name) {
+                    return format!("{}_{}", prefix, id);
+                }
+            },
+            quote! {
+                if let Some((prefix, _)) = ID_PREFIXES.iter().find(|(_, t)| *t == type_name) {
+                    return id.strip_prefix(*prefix)?.strip_prefix('_').map(str::to_string);
+                }
+            },
+        )
+    };
+
+    quote! {
+        //! External ids of exported rows, events and feed links
+        //!
+        //! Entities whose GraphQL type is a Relay node are identified by the
+        //! same opaque ids the API returns instead of their primary keys. If
+        //! the API boundary encodes ids differently, install a matching
+        //! `IdEncoder` with `set_id_encoder` at startup.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unexpected_cfgs)]
+
+        /// Encodes primary keys into external ids
+        pub trait IdEncoder: Send + Sync {
+            /// External id of key `id` of a `type_name` row
+            fn encode(&self, type_name: &str, id: &str) -> String;
+
+            /// Key of a `type_name` row from its external id, if it is one
+            fn decode(&self, type_name: &str, id: &str) -> Option<String>;
+        }
+
+        /// Relay global ids, as returned by the GraphQL `id` fields
+        #[derive(Debug, Default, Clone, Copy)]
+        pub struct GlobalIdEncoder;
+
+        #prefix_registry
+
+        impl IdEncoder for GlobalIdEncoder {
+            fn encode(&self, type_name: &str, id: &str) -> String {
+                #prefixed
+                let raw = format!("{}:{}", type_name, id);
+                #encode
+            }
+
+            fn decode(&self, type_name: &str, id: &str) -> Option<String> {
+                #unprefixed
+                let raw = String::from_utf8(#decode).ok()?;
+                let (decoded_type, key) = raw.split_once(':')?;
+                (decoded_type == type_name).then(|| key.to_string())
+            }
+        }
+
+        /// Short ids from `sqids`; non-numeric keys such as UUIDs are kept as is
+        #[cfg(feature = "sqids")]
+        pub struct SqidsEncoder {
+            sqids: sqids::Sqids,
+        }
+
+        #[cfg(feature = "sqids")]
+        impl SqidsEncoder {
+            /// Encode with a configured `Sqids`, e.g. one with a custom alphabet
+            pub fn new(sqids: sqids::Sqids) -> Self {
+                Self { sqids }
+            }
+        }
+
+        #[cfg(feature = "sqids")]
+        impl IdEncoder for SqidsEncoder {
+            fn encode(&self, _type_name: &str, id: &str) -> String {
+                id.parse::<u64>()
+                    .ok()
+                    .and_then(|n| self.sqids.encode(&[n]).ok())
+                    .unwrap_or_else(|| id.to_string())
+            }
+
+            fn decode(&self, _type_name: &str, id: &str) -> Option<String> {
+                let numbers = self.sqids.decode(id);
+                // Only canonical sqids decode; anything else was kept as is
+                match numbers.as_slice() {
+                    [n] if self.sqids.encode(&numbers).ok().as_deref() == Some(id) => Some(n.to_string()),
+                    _ => Some(id.to_string()),
+                }
+            }
+        }
+
+        static ENCODER: std::sync::OnceLock<Box<dyn IdEncoder>> = std::sync::OnceLock::new();
+
+        /// Install the encoder of external ids, before any is encoded
+        ///
+        /// Returns false when an encoder is already in use.
+        pub fn set_id_encoder(encoder: impl IdEncoder + 'static) -> bool {
+            ENCODER.set(Box::new(encoder)).is_ok()
+        }
+
+        /// Entities with hidden ids and their GraphQL type names
+        const HIDDEN: &[(&str, &str)] = &[#(#hidden),*];
+
+        /// External id of an `entity` row: encoded for Relay node types, the key otherwise
+        pub fn external_id(entity: &str, id: impl std::fmt::Display) -> String {
+            let id = id.to_string();
+            match HIDDEN.iter().find(|(name, _)| *name == entity) {
+                Some((_, type_name)) => ENCODER
+                    .get_or_init(|| Box::new(GlobalIdEncoder))
+                    .encode(type_name, &id),
+                None => id,
+            }
+        }
+
+        /// Key of an `entity` row from its external id, `None` if it is not one
+        pub fn internal_id(entity: &str, id: &str) -> Option<String> {
+            match HIDDEN.iter().find(|(name, _)| *name == entity) {
+                Some((_, type_name)) => ENCODER
+                    .get_or_init(|| Box::new(GlobalIdEncoder))
+                    .decode(type_name, id),
+                None => Some(id.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_module() {
+        let code = generate_module(&[("Post".to_string(), "Article".to_string())], &[]).to_string();
+        assert!(code.contains("pub trait IdEncoder"));
+        assert!(code.contains("impl IdEncoder for GlobalIdEncoder"));
+        assert!(code.contains("# [cfg (feature = \"sqids\")]"));
+        assert!(code.contains("(\"Post\" , \"Article\")"));
+        assert!(code.contains("pub fn external_id"));
+        assert!(code.contains("pub fn internal_id"));
+        assert!(code.contains("fn decode (& self , type_name : & str , id : & str) -> Option < String >"));
+        assert!(!code.contains("ID_PREFIXES"));
+    }
+
+    #[test]
+    fn test_generate_module_with_id_prefixes() {
+        let hidden = [("User".to_string(), "User".to_string())];
+        let code = generate_module(&hidden, &[("User".to_string(), "usr".to_string())]).to_string();
+        assert!(code.contains("const ID_PREFIXES : & [(& str , & str)] = & [(\"usr\" , \"User\")]"));
+        assert!(code.contains("id . strip_prefix (* prefix) ? . strip_prefix ('_')"));
+    }
+}
@@ -0,0 +1,268 @@
+//! Test factory generation
+//!
+//! With `factories=true`, generates {package}/factories.rs: an `{Entity}Factory`
+//! per entity that starts from an ActiveModel filled with fake values (unique
+//! per call through a shared sequence), lets tests override any column, and
+//! inserts the row. Primary keys follow storage (`generate_key`, else the
+//! database), nullable columns stay NULL, and belongs-to keys are left for the
+//! test to set, since a fake id would break the foreign key.
+
+use super::entity::{generate_fields, GeneratedField};
+use super::implementation::{generated_columns, MANAGED_TIMESTAMPS};
+use super::options::{get_cached_column_options, get_cached_entity_options};
+use crate::error::GeneratorError;
+use crate::options::synapse::storage::{EntityOptions, RelationType};
+use crate::params::time_crate;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::{TokenStream, TokenTree};
+use prost_types::compiler::code_generator_response::File;
+use prost_types::{DescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+
+/// Generate factories.rs for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || !crate::params::factories() {
+        return Ok(None);
+    }
+
+    let mut factories = Vec::new();
+    for proto_file in all_files.iter().filter(|f| f.package.as_deref().unwrap_or("") == package) {
+        let file_name = proto_file.name.as_deref().unwrap_or("");
+        for message in &proto_file.message_type {
+            let msg_name = message.name.as_deref().unwrap_or("");
+            let Some(options) = get_cached_entity_options(file_name, msg_name) else {
+                continue;
+            };
+            if options.skip {
+                continue;
+            }
+            factories.push(generate_factory(file_name, message, &options)?);
+        }
+    }
+    if factories.is_empty() {
+        return Ok(None);
+    }
+
+    let code = quote! {
+        //! Entity factories for tests
+        //!
+        //! Each factory fills required columns with fake values; override any
+        //! column with its setter, then `insert` or `build`.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+        #![allow(clippy::new_without_default)]
+
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        use sea_orm::prelude::*;
+        use sea_orm::ActiveValue::Set;
+
+        use super::entities;
+
+        static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+        /// Next value of the sequence that keeps fake values unique
+        fn next_sequence() -> u64 {
+            SEQUENCE.fetch_add(1, Ordering::Relaxed)
+        }
+
+        #(#factories)*
+    };
+
+    Ok(Some(File {
+        name: Some(format!("{}/factories.rs", package.replace('.', "/"))),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    }))
+}
+
+/// Generate the factory of one entity
+fn generate_factory(
+    file_name: &str,
+    message: &DescriptorProto,
+    options: &EntityOptions,
+) -> Result<TokenStream, GeneratorError> {
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let module = format_ident!("{}", msg_name.to_snake_case());
+    let factory_ident = format_ident!("{}Factory", msg_name.to_upper_camel_case());
+
+    let computed = generated_columns(file_name, message);
+    let foreign_keys: Vec<&str> = options
+        .relations
+        .iter()
+        .filter(|r| r.r#type() == RelationType::BelongsTo)
+        .map(|r| r.foreign_key.as_str())
+        .collect();
+    let primary_key = message
+        .field
+        .iter()
+        .find(|f| get_cached_column_options(file_name, msg_name, f.number.unwrap_or(0)).is_some_and(|o| o.primary_key))
+        .map(|f| f.name.as_deref().unwrap_or("").to_snake_case());
+
+    let mut fields: Vec<GeneratedField> = generate_fields(message, file_name, msg_name)?;
+    fields.retain(|f| !computed.contains(&f.name));
+    // Storage-managed timestamps are columns even when the message omits them
+    if options.timestamps {
+        for name in MANAGED_TIMESTAMPS {
+            if !fields.iter().any(|f| f.name == *name) {
+                fields.push(GeneratedField {
+                    name: name.to_string(),
+                    rust_type: time_crate().datetime_type().to_string(),
+                    attributes: Vec::new(),
+                    pii: false,
+                    primary_key: false,
+                    ordered: true,
+                });
+            }
+        }
+    }
+
+    let mut fakes = Vec::new();
+    let mut setters = Vec::new();
+    for field in &fields {
+        let ident = format_ident!("{}", field.name);
+        let ty: syn::Type = syn::parse_str(&field.rust_type)
+            .map_err(|e| GeneratorError::CodeGenError(format!("{}.{}: {}", msg_name, field.name, e)))?;
+        setters.push(quote! {
+            pub fn #ident(mut self, value: impl Into<#ty>) -> Self {
+                self.model.#ident = Set(value.into());
+                self
+            }
+        });
+
+        let skip = primary_key.as_deref() == Some(field.name.as_str()) || foreign_keys.contains(&field.name.as_str());
+        if !skip && let Some(fake) = fake_value(msg_name, &field.name, &field.rust_type) {
+            fakes.push(quote! { #ident: Set(#fake), });
+        }
+    }
+
+    let set_key = primary_key.map(|pk| {
+        let pk = format_ident!("{}", pk);
+        quote! {
+            if let Some(id) = super::prelude::generate_key::<entities::#module::Entity>() {
+                model.#pk = Set(id);
+            }
+        }
+    });
+    // Bind the sequence value and clock only when a fake uses them
+    let fake_tokens = quote! { #(#fakes)* };
+    let uses = |ident: &str| mentions(fake_tokens.clone(), ident);
+    let sequence = uses("n").then(|| quote! { let n = next_sequence(); });
+    let now = uses("now").then(|| {
+        let now = time_crate().now();
+        quote! { let now = #now; }
+    });
+    let doc = format!("Builds and inserts `{}` rows for tests", msg_name);
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Debug, Clone)]
+        pub struct #factory_ident {
+            model: entities::#module::ActiveModel,
+        }
+
+        impl #factory_ident {
+            /// Factory with fake values for the required columns
+            pub fn new() -> Self {
+                #sequence
+                #now
+                let mut model = entities::#module::ActiveModel {
+                    #(#fakes)*
+                    ..Default::default()
+                };
+                #set_key
+                Self { model }
+            }
+
+            #(#setters)*
+
+            /// The ActiveModel, without inserting it
+            pub fn build(self) -> entities::#module::ActiveModel {
+                self.model
+            }
+
+            /// Insert the row and return it as stored
+            pub async fn insert<C: ConnectionTrait>(self, db: &C) -> Result<entities::#module::Model, DbErr> {
+                self.model.insert(db).await
+            }
+        }
+    })
+}
+
+/// Whether a token stream mentions an identifier, inside groups too
+fn mentions(tokens: TokenStream, ident: &str) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(i) => i == ident,
+        TokenTree::Group(g) => mentions(g.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Fake value expression for a column, using `n` (sequence) and `now`
+///
+/// `None` leaves the column unset: nullable, enum, and embedded columns.
+fn fake_value(msg_name: &str, field: &str, rust_type: &str) -> Option<TokenStream> {
+    let entity = msg_name.to_snake_case();
+    let fake = match rust_type {
+        "String" if field.contains("email") => {
+            let template = format!("{}{{}}@example.com", entity);
+            quote! { format!(#template, n) }
+        }
+        "String" if field.contains("url") => {
+            let template = format!("https://example.com/{}/{{}}", entity);
+            quote! { format!(#template, n) }
+        }
+        "String" if field.contains("slug") => {
+            let template = format!("{}-{{}}", entity.replace('_', "-"));
+            quote! { format!(#template, n) }
+        }
+        "String" => {
+            let template = format!("{} {} {{}}", msg_name, field.replace('_', " "));
+            quote! { format!(#template, n) }
+        }
+        "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" => {
+            let ty = format_ident!("{}", rust_type);
+            quote! { n as #ty }
+        }
+        "f32" | "f64" => {
+            let ty = format_ident!("{}", rust_type);
+            quote! { n as #ty }
+        }
+        "bool" => quote! { false },
+        "DateTimeUtc" | "TimeDateTimeWithTimeZone" => quote! { now },
+        "DateTime" => quote! { now.naive_utc() },
+        "TimeDateTime" => quote! { TimeDateTime::new(now.date(), now.time()) },
+        _ if rust_type.starts_with("Vec<") => quote! { Vec::new() },
+        _ => return None,
+    };
+    Some(fake)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_value() {
+        let email = fake_value("User", "email", "String").unwrap().to_string();
+        assert_eq!(email, "format ! (\"user{}@example.com\" , n)");
+        let slug = fake_value("BlogPost", "slug", "String").unwrap().to_string();
+        assert_eq!(slug, "format ! (\"blog-post-{}\" , n)");
+        let name = fake_value("User", "display_name", "String").unwrap().to_string();
+        assert_eq!(name, "format ! (\"User display name {}\" , n)");
+        assert_eq!(fake_value("User", "age", "i32").unwrap().to_string(), "n as i32");
+        assert_eq!(fake_value("User", "created_at", "DateTimeUtc").unwrap().to_string(), "now");
+    }
+
+    #[test]
+    fn test_fake_value_leaves_nullable_unset() {
+        assert!(fake_value("User", "bio", "Option<String>").is_none());
+        assert!(fake_value("User", "status", "UserStatus").is_none());
+    }
+}
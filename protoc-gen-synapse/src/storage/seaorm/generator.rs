@@ -3,16 +3,65 @@
 //! This module coordinates the overall code generation process,
 //! iterating through proto files and generating SeaORM entities, enums, and storage traits.
 
-use super::{entity, enum_gen, implementation, options, package};
+use super::report::{Recorder, Stage};
+use super::{baseline, diff, entity, enum_gen, implementation, ir, lint, options, package};
 use crate::error::GeneratorError;
 use crate::storage::seaorm::options::get_cached_entity_options;
-use crate::{graphql, grpc, validate};
+use crate::{graphql, grpc, params, validate};
 use prost::Message;
 use prost_types::compiler::{CodeGeneratorRequest, CodeGeneratorResponse};
+use std::time::{Duration, Instant};
 
 /// Generate SeaORM entities and enums from a CodeGeneratorRequest
-pub fn generate(request: CodeGeneratorRequest) -> Result<CodeGeneratorResponse, GeneratorError> {
+///
+/// `options_elapsed` is the time spent extracting options, for the report.
+pub fn generate(
+    request: CodeGeneratorRequest,
+    options_elapsed: Duration,
+) -> Result<CodeGeneratorResponse, GeneratorError> {
+    params::set_params(params::GeneratorParams::parse(request.parameter.as_deref().unwrap_or("")));
+
+    // Lint mode checks the annotations and generates nothing
+    if params::lint() {
+        return Ok(lint::lint(&request));
+    }
+
+    // IR mode dumps the resolved model for external tooling instead of code
+    if params::emit_ir() {
+        return Ok(ir::emit(&request));
+    }
+
+    // Breaking changes against the baseline fail generation before any output
+    if let Some(path) = params::baseline()
+        && let Some(report) = baseline::check(&request, &path)?
+    {
+        return Ok(CodeGeneratorResponse {
+            file: Vec::new(),
+            error: Some(report),
+            supported_features: Some(1), // FEATURE_PROTO3_OPTIONAL
+        });
+    }
+
+    // Fields shadowing generated Relay fields fail generation with guidance
+    if let Some(report) = lint::check_shadowed_fields(&request) {
+        return Ok(CodeGeneratorResponse {
+            file: Vec::new(),
+            error: Some(report),
+            supported_features: Some(1), // FEATURE_PROTO3_OPTIONAL
+        });
+    }
+
+    // Colliding modules and relations to entities that aren't generated fail generation
+    if let Some(report) = lint::check_layout(&request) {
+        return Ok(CodeGeneratorResponse {
+            file: Vec::new(),
+            error: Some(report),
+            supported_features: Some(1), // FEATURE_PROTO3_OPTIONAL
+        });
+    }
+
     let mut files = Vec::new();
+    let mut recorder = Recorder::new(options_elapsed);
 
     // Process each file that was requested for generation
     for file_name in &request.file_to_generate {
@@ -24,6 +73,7 @@ pub fn generate(request: CodeGeneratorRequest) -> Result<CodeGeneratorResponse,
             .ok_or_else(|| {
                 GeneratorError::CodeGenError(format!("File descriptor not found: {}", file_name))
             })?;
+        recorder.begin_file(file_name, file_descriptor.package.as_deref().unwrap_or(""));
 
         // Collect entities (messages with synapse.storage.entity option)
         // Only collect entities from files in the SAME package to avoid duplication
@@ -51,25 +101,23 @@ pub fn generate(request: CodeGeneratorRequest) -> Result<CodeGeneratorResponse,
         // Generate code for entities found in imports
         for (proto_file, message) in &entity_file_map {
             // Generate entity if has entity options
-            if let Some(generated) = entity::generate(proto_file, message)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Entity, "entity", &mut files, || entity::generate(proto_file, message))?;
             // Generate domain type if has validate options with generate_conversion
-            if let Some(generated) = validate::generate(proto_file, message)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Validate, "validate", &mut files, || {
+                validate::generate(proto_file, message)
+            })?;
             // Generate GraphQL Object type if has graphql options
-            if let Some(generated) = graphql::generate_message(proto_file, message)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Graphql, "graphql_message", &mut files, || {
+                graphql::generate_message(proto_file, message)
+            })?;
             // Generate DataLoaders for relations
-            for generated in graphql::generate_dataloaders(proto_file, message, &request.proto_file)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Graphql, "graphql_dataloaders", &mut files, || {
+                graphql::generate_dataloaders(proto_file, message, &request.proto_file)
+            })?;
             // Generate entity loader for BelongsTo relations
-            if let Some(generated) = graphql::generate_entity_loader(proto_file, message)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Graphql, "graphql_entity_loader", &mut files, || {
+                graphql::generate_entity_loader(proto_file, message, &request.proto_file)
+            })?;
         }
 
         // Also process non-entity messages in the main file (request/response types)
@@ -81,75 +129,164 @@ pub fn generate(request: CodeGeneratorRequest) -> Result<CodeGeneratorResponse,
                 continue;
             }
             // Generate domain type if has validate options with generate_conversion
-            if let Some(generated) = validate::generate(file_descriptor, message)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Validate, "validate", &mut files, || {
+                validate::generate(file_descriptor, message)
+            })?;
             // Generate GraphQL input types for request messages
-            if let Some(generated) = graphql::generate_message(file_descriptor, message)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Graphql, "graphql_message", &mut files, || {
+                graphql::generate_message(file_descriptor, message)
+            })?;
         }
 
         // Generate auto-generated filter types for entities
         if !entities.is_empty() {
             let entity_refs: Vec<_> = entities.iter().map(|e| *e).collect();
-            for generated in graphql::generate_filters(file_descriptor, &entity_refs, &request.proto_file)? {
-                files.push(generated);
-            }
-            for generated in graphql::generate_connections(file_descriptor, &entity_refs)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Graphql, "graphql_filters", &mut files, || {
+                graphql::generate_filters(file_descriptor, &entity_refs, &request.proto_file)
+            })?;
+            recorder.run(Stage::Graphql, "graphql_connections", &mut files, || {
+                graphql::generate_connections(file_descriptor, &entity_refs, &request.proto_file)
+            })?;
         }
 
         // Generate Node interface if there are node types in this file
-        if let Some(generated) = graphql::generate_node_interface(file_descriptor)? {
-            files.push(generated);
-        }
+        recorder.run(Stage::Graphql, "graphql_node", &mut files, || {
+            graphql::generate_node_interface(file_descriptor, &request.proto_file)
+        })?;
 
         // Generate unified GraphQL schema (mod.rs with Query/Mutation/schema builder)
-        if let Some(generated) = graphql::generate_schema(file_descriptor, &request.proto_file)? {
-            files.push(generated);
-        }
+        recorder.run(Stage::Graphql, "graphql_schema", &mut files, || {
+            graphql::generate_schema(file_descriptor, &request.proto_file)
+        })?;
+
+        // Generate gateway HTTP wiring (request limits, CORS, CSRF for the axum router)
+        recorder.run(Stage::Graphql, "graphql_gateway", &mut files, || {
+            graphql::generate_gateway(file_descriptor, &request.proto_file)
+        })?;
+
+        // Generate per-document concurrency budget for resolver gRPC calls
+        recorder.run(Stage::Graphql, "graphql_concurrency", &mut files, || {
+            graphql::generate_concurrency(file_descriptor)
+        })?;
+
+        // Generate SDL compatibility checks (and snapshot test with graphql_snapshot)
+        recorder.run(Stage::Graphql, "graphql_compat", &mut files, || {
+            graphql::generate_compat(file_descriptor)
+        })?;
+
+        // Generate partial degradation of non-critical relations
+        recorder.run(Stage::Graphql, "graphql_degrade", &mut files, || {
+            graphql::generate_degrade(file_descriptor, &request.proto_file)
+        })?;
+
+        // Generate fault-injection layer for resolver gRPC clients (chaos=true)
+        recorder.run(Stage::Graphql, "graphql_chaos", &mut files, || {
+            graphql::generate_chaos(file_descriptor)
+        })?;
+
+        // Generate record/replay of resolver gRPC calls (record_replay=true)
+        recorder.run(Stage::Graphql, "graphql_cassette", &mut files, || {
+            graphql::generate_cassette(file_descriptor)
+        })?;
+
+        // Generate admin CRUD UI router (driven by the registry)
+        recorder.run(Stage::Graphql, "graphql_admin", &mut files, || {
+            graphql::generate_admin(file_descriptor, &request.proto_file)
+        })?;
+
+        // Generate full-text search resolvers (searchable entities)
+        recorder.run(Stage::Graphql, "graphql_search", &mut files, || {
+            graphql::generate_search(file_descriptor, &request.proto_file)
+        })?;
+
+        // Generate federation entity resolvers (federation=true)
+        recorder.run(Stage::Graphql, "graphql_federation", &mut files, || {
+            graphql::generate_federation(file_descriptor, &request.proto_file)
+        })?;
+
+        // Generate entity change subscriptions (subscriptions=true)
+        recorder.run(Stage::Graphql, "graphql_subscription", &mut files, || {
+            graphql::generate_subscription(file_descriptor, &request.proto_file)
+        })?;
+
+        // Generate rustls TLS helpers for gRPC channels/servers (tls=rustls)
+        recorder.run(Stage::Grpc, "grpc_tls", &mut files, || grpc::generate_tls(file_descriptor))?;
+
+        // Generate the gRPC server with health reporting (health=true)
+        recorder.run(Stage::Grpc, "grpc_server", &mut files, || {
+            grpc::generate_server(file_descriptor, &request.proto_file)
+        })?;
+
+        // Generate post-deploy smoke test over the gRPC clients (smoke=true)
+        recorder.run(Stage::Grpc, "grpc_smoke", &mut files, || {
+            grpc::generate_smoke(file_descriptor, &request.proto_file)
+        })?;
 
         // Process each enum in the file
         for enum_desc in &file_descriptor.enum_type {
-            if let Some(generated) = enum_gen::generate(file_descriptor, enum_desc)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Entity, "enum", &mut files, || {
+                enum_gen::generate(file_descriptor, enum_desc)
+            })?;
         }
 
         // Process each service in the file
         for svc in &file_descriptor.service {
             // Storage defaults generation (standalone functions for partial overrides)
-            if let Some(generated) = crate::storage::generate_defaults(file_descriptor, svc, &request.proto_file)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Storage, "storage_defaults", &mut files, || {
+                crate::storage::generate_defaults(file_descriptor, svc, &request.proto_file)
+            })?;
             // Storage trait generation (with default impls that call defaults)
-            if let Some(generated) = crate::storage::generate(file_descriptor, svc)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Storage, "storage_trait", &mut files, || {
+                crate::storage::generate(file_descriptor, svc)
+            })?;
+            // RSS/Atom feeds and sitemap for public_feed entities (reads via the storage trait)
+            recorder.run(Stage::Storage, "storage_feed", &mut files, || {
+                crate::storage::generate_feed(file_descriptor, svc, &request.proto_file)
+            })?;
+            // Cache decorator wrapping the storage trait (cache=true)
+            recorder.run(Stage::Storage, "storage_cache", &mut files, || {
+                crate::storage::generate_cache(file_descriptor, svc)
+            })?;
             // Storage implementation generation (minimal SeaORM-based impl)
-            if let Some(generated) = implementation::generate(file_descriptor, svc, &request.proto_file)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Storage, "storage_implementation", &mut files, || {
+                implementation::generate(file_descriptor, svc, &request.proto_file)
+            })?;
             // gRPC service generation
-            if let Some(generated) = grpc::generate(file_descriptor, svc)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Grpc, "grpc_service", &mut files, || grpc::generate(file_descriptor, svc, &request.proto_file))?;
             // GraphQL resolver generation (Query/Mutation structs)
-            for generated in graphql::generate_service(file_descriptor, svc)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Graphql, "graphql_resolvers", &mut files, || {
+                graphql::generate_service(file_descriptor, svc, &request.proto_file)
+            })?;
             // GraphQL input types (auto-generated from request messages)
-            for generated in graphql::generate_inputs(file_descriptor, svc)? {
-                files.push(generated);
-            }
+            recorder.run(Stage::Graphql, "graphql_inputs", &mut files, || {
+                graphql::generate_inputs(file_descriptor, svc, &request.proto_file)
+            })?;
         }
 
         // Generate package mod.rs and subdirectory mod.rs files
-        for generated in package::generate_all(file_descriptor, &request.proto_file)? {
-            files.push(generated);
+        let produced: Vec<String> = files.iter().filter_map(|f| f.name.clone()).collect();
+        recorder.run(Stage::Package, "package", &mut files, || {
+            package::generate_all(file_descriptor, &request.proto_file, &produced)
+        })?;
+    }
+
+    // Timing report, built before the diff so it covers the real output
+    if params::report() {
+        files.extend(recorder.finish());
+    }
+
+    // Dry-run: report what would change on disk instead of writing it
+    if let Some(dir) = params::diff() {
+        let mut packages: Vec<&str> = Vec::new();
+        for file_name in &request.file_to_generate {
+            if let Some(file) = request.proto_file.iter().find(|f| f.name.as_ref() == Some(file_name)) {
+                let package = file.package.as_deref().unwrap_or("");
+                if !packages.contains(&package) {
+                    packages.push(package);
+                }
+            }
         }
+        files = vec![diff::report(&dir, &packages, &files)?];
     }
 
     Ok(CodeGeneratorResponse {
@@ -163,6 +300,8 @@ pub fn generate(request: CodeGeneratorRequest) -> Result<CodeGeneratorResponse,
 ///
 /// This entry point preserves extension data by using prost-reflect for decoding.
 pub fn generate_from_bytes(bytes: &[u8]) -> Result<CodeGeneratorResponse, GeneratorError> {
+    let started = Instant::now();
+
     // Pre-process bytes to extract extension data using prost-reflect
     options::preprocess_request_bytes(bytes).map_err(GeneratorError::DecodeError)?;
 
@@ -171,5 +310,5 @@ pub fn generate_from_bytes(bytes: &[u8]) -> Result<CodeGeneratorResponse, Genera
         .map_err(|e| GeneratorError::DecodeError(e.to_string()))?;
 
     // Generate using the regular path (which will use cached options)
-    generate(request)
+    generate(request, started.elapsed())
 }
@@ -4,12 +4,19 @@
 //! The generated implementations bridge between gRPC request/response types
 //! and SeaORM entities, handling CRUD operations.
 
-use super::options::{get_cached_service_options, storage};
+use super::batch::{BatchErrors, ImportSummary};
+use super::expand::Expansion;
+use super::options::{
+    get_cached_column_options, get_cached_service_options, parse_column_options, storage,
+};
 use crate::error::GeneratorError;
+use crate::params::time_crate;
+use crate::validate::UniqueField;
 use heck::{ToSnakeCase, ToUpperCamelCase};
 use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
-use prost_types::{DescriptorProto, FileDescriptorProto, ServiceDescriptorProto};
+use prost_types::field_descriptor_proto::Type;
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto, ServiceDescriptorProto};
 use quote::{format_ident, quote};
 
 /// Generate a SeaORM-based storage implementation from a protobuf service
@@ -78,6 +85,66 @@ pub fn generate(
     // Import the storage trait module
     let trait_module = format_ident!("{}", trait_name.to_snake_case());
 
+    // Custom methods have no trait default, so the SeaORM storage forwards them
+    // to a `{Trait}Custom` trait; until the user implements it for the storage,
+    // the storage does not compile
+    let custom_methods = crate::storage::traits::custom_methods(file, service);
+    let custom_trait_ident = format_ident!("{}Custom", trait_name);
+    let (custom_trait, custom_forwards) = if custom_methods.is_empty() {
+        (quote! {}, Vec::new())
+    } else {
+        let items = custom_methods.iter().map(|m| m.to_custom_item());
+        let custom_doc = format!(
+            "Hand-written `custom` methods of {}\n\n\
+            Implement this for {} with your own queries; `self.db()` is the\n\
+            connection the generated methods use.",
+            trait_name, impl_name
+        );
+        let custom_trait = quote! {
+            use super::super::prelude::*;
+            use super::#trait_module::*;
+            // Both globs export a StorageError; the trait's is the one it returns
+            use super::#trait_module::StorageError;
+
+            #[doc = #custom_doc]
+            #[async_trait::async_trait]
+            pub trait #custom_trait_ident: Send + Sync {
+                #(#items)*
+            }
+        };
+        let forwards = custom_methods
+            .iter()
+            .map(|m| m.to_trait_call_item(&custom_trait_ident))
+            .collect();
+        (custom_trait, forwards)
+    };
+
+    // With read_replica, reads run on a separate reader connection that
+    // defaults to the primary until `with_replica` supplies one
+    let (reader_field, reader_init, with_replica, read_db_method) = if service_options.read_replica {
+        (
+            quote! { reader: DatabaseConnection, },
+            quote! { reader: db.clone(), },
+            quote! {
+                /// Create a storage that writes to `primary` and reads from `replica`
+                ///
+                /// Get, get-many, list, tree and polymorphic lookups use the
+                /// replica; mutations, including the reads they make of their
+                /// own rows, use the primary.
+                pub fn with_replica(primary: DatabaseConnection, replica: DatabaseConnection) -> Self {
+                    Self { db: primary, reader: replica }
+                }
+            },
+            quote! {
+                fn read_db(&self) -> &DatabaseConnection {
+                    &self.reader
+                }
+            },
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {}, quote! {})
+    };
+
     let code = quote! {
         #![doc = #module_doc]
         //!
@@ -94,18 +161,21 @@ pub fn generate(
 
         use super::#trait_module::#trait_ident;
         use sea_orm::DatabaseConnection;
+        #custom_trait
 
         #[doc = #struct_doc]
         #[derive(Clone)]
         pub struct #impl_ident {
             db: DatabaseConnection,
+            #reader_field
         }
 
         impl #impl_ident {
             /// Create a new storage implementation with the given database connection
             pub fn new(db: DatabaseConnection) -> Self {
-                Self { db }
+                Self { #reader_init db }
             }
+            #with_replica
         }
 
         #[async_trait::async_trait]
@@ -113,6 +183,8 @@ pub fn generate(
             fn db(&self) -> &DatabaseConnection {
                 &self.db
             }
+            #read_db_method
+            #(#custom_forwards)*
             // All other methods use trait defaults
         }
     };
@@ -120,11 +192,8 @@ pub fn generate(
     // Format the generated code
     let content = code.to_string();
 
-    // Try to format with prettyplease if we can parse it
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content, // If parsing fails, use unformatted
-    };
+    // Format the generated code
+    let formatted = crate::params::format_code(content);
 
     Ok(Some(File {
         name: Some(output_filename),
@@ -140,11 +209,14 @@ pub fn generate(
 
 /// Generate a GET implementation
 ///
+/// With an `expansion`, the relations named in `request.expand` are loaded
+/// into the response's `{Entity}Node` field.
 /// If `for_standalone` is true, generates code using `db` parameter instead of `self.db`
 pub fn generate_get_impl(
     entity_module: &proc_macro2::Ident,
     response_ident: &proc_macro2::Ident,
-    _entity_options: Option<&storage::EntityOptions>,
+    entity_options: Option<&storage::EntityOptions>,
+    expansion: Option<&Expansion>,
     for_standalone: bool,
 ) -> TokenStream {
     // Derive the entity type name (e.g., user -> User)
@@ -156,29 +228,127 @@ pub fn generate_get_impl(
         quote! { &self.db }
     };
 
-    quote! {
-        let model = entities::#entity_module::Entity::find_by_id(request.id)
+    let not_deleted = generate_not_deleted_filter(entity_module, entity_options);
+
+    // Requested relations go in the response's `{Entity}Node` field
+    let (expand_check, expand_load, expand_field) = match expansion {
+        Some(Expansion { field, check, load }) => (
+            check.clone(),
+            quote! { let #field = #load; },
+            quote! { #field, },
+        ),
+        None => (quote! {}, quote! {}, quote! {}),
+    };
+
+    let find = |id: TokenStream| quote! {
+        entities::#entity_module::Entity::find_by_id(#id)
+            #not_deleted
             .one(#db_ref)
             .await
             .map_err(StorageError::Database)?
-            .ok_or_else(|| StorageError::NotFound(format!("{} with id {} not found", stringify!(#entity_type), request.id)))?;
+            .ok_or_else(|| StorageError::NotFound(format!("{} with id {} not found", stringify!(#entity_type), request.id)))?
+    };
+    // Rows read while a write to them commits are not cached, see `RowCache::insert`
+    let load = if super::row_cache::has_row_cache(entity_options) {
+        let cached_find = find(quote! { id.clone() });
+        quote! {
+            let model = match entities::#entity_module::ROW_CACHE.get(&id) {
+                Some(model) => model,
+                None => {
+                    let version = entities::#entity_module::ROW_CACHE.version();
+                    let model = #cached_find;
+                    entities::#entity_module::ROW_CACHE.insert(id, model.clone(), version);
+                    model
+                }
+            };
+        }
+    } else {
+        let find = find(quote! { id });
+        quote! { let model = #find; }
+    };
+
+    quote! {
+        let id = parse_key::<entities::#entity_module::Entity>(&request.id)
+            .ok_or_else(|| StorageError::InvalidArgument(format!("invalid id: {}", request.id)))?;
+        #expand_check
+        #load
+        #expand_load
 
         Ok(#response_ident {
             #entity_module: Some(model.into()),
+            #expand_field
         })
     }
 }
 
-/// Generate a LIST implementation with filter/orderBy support
+/// Generate a GET MANY implementation
 ///
+/// Loads every row of `request.ids` with one `IN` query and returns them in
+/// `response_field` in the order of the ids; ids without a row are skipped.
 /// If `for_standalone` is true, generates code using `db` parameter instead of `self.db`
+pub fn generate_get_many_impl(
+    entity_module: &proc_macro2::Ident,
+    response_ident: &proc_macro2::Ident,
+    response_field: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    for_standalone: bool,
+) -> TokenStream {
+    let db_ref = if for_standalone {
+        quote! { db }
+    } else {
+        quote! { &self.db }
+    };
+
+    let not_deleted = generate_not_deleted_filter(entity_module, entity_options);
+
+    quote! {
+        let mut keys = Vec::with_capacity(request.ids.len());
+        for id in &request.ids {
+            let key = parse_key::<entities::#entity_module::Entity>(id)
+                .ok_or_else(|| StorageError::InvalidArgument(format!("invalid id: {}", id)))?;
+            keys.push(key);
+        }
+        if keys.is_empty() {
+            return Ok(#response_ident {
+                #response_field: Vec::new(),
+            });
+        }
+
+        let models = entities::#entity_module::Entity::find()
+            .filter(entities::#entity_module::Column::Id.is_in(keys.clone()))
+            #not_deleted
+            .all(#db_ref)
+            .await
+            .map_err(StorageError::Database)?;
+
+        // Rows come back in database order; answer in the order of the ids
+        let by_id: std::collections::HashMap<String, entities::#entity_module::Model> =
+            models.into_iter().map(|m| (m.id.to_string(), m)).collect();
+        Ok(#response_ident {
+            #response_field: keys
+                .iter()
+                .filter_map(|key| by_id.get(&key.to_string()).cloned())
+                .map(Into::into)
+                .collect(),
+        })
+    }
+}
+
+/// Generate a LIST implementation with filter/orderBy support
+///
+/// If `for_standalone` is true, generates code using `db` parameter instead of `self.db`.
+/// With `stream_rows`, rows are read as a stream and turned into edges one at
+/// a time, so the models are never collected alongside the edges.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_list_impl(
     file: &FileDescriptorProto,
     request_type: &str,
     entity_module: &proc_macro2::Ident,
     response_ident: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
     all_files: &[FileDescriptorProto],
     for_standalone: bool,
+    stream_rows: bool,
 ) -> TokenStream {
     // Derive the edge type name (e.g., user -> UserEdge)
     let entity_type = entity_module.to_string().to_upper_camel_case();
@@ -190,6 +360,389 @@ pub fn generate_list_impl(
         quote! { &self.db }
     };
 
+    let query_code = generate_list_query(file, request_type, entity_module, entity_options, all_files, true);
+
+    let (ranked_rows, select_rows, unpack_row, cursor) =
+        generate_list_rows(file, request_type, entity_module, all_files);
+
+    let edges_code = if stream_rows {
+        quote! {
+            use tokio_stream::StreamExt;
+
+            // Stream one extra row to determine if there's a next page
+            let rows = query
+                .limit(limit + 1)
+                #select_rows
+                .stream(#db_ref)
+                .await
+                .map_err(StorageError::Database)?;
+            let mut rows = std::pin::pin!(rows);
+
+            let mut edges: Vec<#edge_ident> = Vec::with_capacity(limit.min(1024) as usize);
+            let mut has_next_page = false;
+            while let Some(m) = rows.next().await {
+                let m = m.map_err(StorageError::Database)?;
+                if edges.len() as u64 == limit {
+                    has_next_page = true;
+                    break;
+                }
+                #unpack_row
+                let cursor = #cursor;
+                edges.push(#edge_ident {
+                    cursor,
+                    node: Some(m.into()),
+                });
+            }
+        }
+    } else {
+        quote! {
+            // Fetch one extra to determine if there's a next page
+            let models = query
+                .limit(limit + 1)
+                #select_rows
+                .all(#db_ref)
+                .await
+                .map_err(StorageError::Database)?;
+
+            let has_next_page = models.len() > limit as usize;
+            let models: Vec<_> = models.into_iter().take(limit as usize).collect();
+
+            let edges: Vec<#edge_ident> = models
+                .into_iter()
+                .map(|m| {
+                    #unpack_row
+                    let cursor = #cursor;
+                    #edge_ident {
+                        cursor,
+                        node: Some(m.into()),
+                    }
+                })
+                .collect();
+        }
+    };
+
+    quote! {
+        #query_code
+
+        #ranked_rows
+
+        #edges_code
+
+        let start_cursor = edges.first().map(|e| e.cursor.clone());
+        let end_cursor = edges.last().map(|e| e.cursor.clone());
+
+        Ok(#response_ident {
+            edges,
+            page_info: Some(PageInfo {
+                has_next_page,
+                has_previous_page: request.after.is_some(),
+                start_cursor,
+                end_cursor,
+            }),
+        })
+    }
+}
+
+/// Generate how a LIST reads its rows and derives their cursors
+///
+/// Returns the row type setup, the select modifier, the statement unpacking
+/// a row into `m`, and the cursor expression. Ranked pages read each row's
+/// rank alongside it, for the `rank:id` cursor.
+fn generate_list_rows(
+    file: &FileDescriptorProto,
+    request_type: &str,
+    entity_module: &proc_macro2::Ident,
+    all_files: &[FileDescriptorProto],
+) -> (TokenStream, TokenStream, TokenStream, TokenStream) {
+    if list_search_field(file, request_type, entity_module, all_files).is_some() {
+        (
+            quote! {
+                struct Ranked {
+                    model: entities::#entity_module::Model,
+                    rank: f32,
+                }
+
+                impl sea_orm::FromQueryResult for Ranked {
+                    fn from_query_result(res: &sea_orm::QueryResult, pre: &str) -> Result<Self, sea_orm::DbErr> {
+                        Ok(Self {
+                            model: <entities::#entity_module::Model as sea_orm::FromQueryResult>::from_query_result(res, pre)?,
+                            rank: res.try_get(pre, "search_rank")?,
+                        })
+                    }
+                }
+
+                let rank_column = if ranked {
+                    rank()
+                } else {
+                    sea_orm::sea_query::Expr::cust("CAST(0 AS real)")
+                };
+            },
+            quote! { .column_as(rank_column, "search_rank").into_model::<Ranked>() },
+            quote! { let (m, rank) = (m.model, m.rank); },
+            quote! {
+                if ranked {
+                    format!("{}:{}", rank, m.id)
+                } else {
+                    m.id.to_string()
+                }
+            },
+        )
+    } else {
+        (quote! {}, quote! {}, quote! {}, quote! { m.id.to_string() })
+    }
+}
+
+/// Generate an encoded LIST implementation for `stream_rows` methods
+///
+/// Streams rows like [`generate_list_impl`] but encodes each edge straight
+/// into an `EncodedList` under the response's `edges` field, then
+/// `page_info`, and returns the `Bytes`. The result decodes as the response
+/// message; peak memory is the encoded output rather than models, edges, and
+/// the encoding of both. Always standalone.
+pub fn generate_list_encoded_impl(
+    file: &FileDescriptorProto,
+    request_type: &str,
+    entity_module: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    all_files: &[FileDescriptorProto],
+    edges_tag: u32,
+    page_info_tag: u32,
+) -> TokenStream {
+    let entity_type = entity_module.to_string().to_upper_camel_case();
+    let edge_ident = format_ident!("{}Edge", entity_type);
+
+    let query_code = generate_list_query(file, request_type, entity_module, entity_options, all_files, true);
+    let (ranked_rows, select_rows, unpack_row, cursor) =
+        generate_list_rows(file, request_type, entity_module, all_files);
+
+    quote! {
+        use tokio_stream::StreamExt;
+
+        #query_code
+
+        #ranked_rows
+
+        // Stream one extra row to determine if there's a next page
+        let rows = query
+            .limit(limit + 1)
+            #select_rows
+            .stream(db)
+            .await
+            .map_err(StorageError::Database)?;
+        let mut rows = std::pin::pin!(rows);
+
+        let mut list = EncodedList::new(#edges_tag, #page_info_tag);
+        let mut count = 0u64;
+        let mut has_next_page = false;
+        let mut start_cursor = None;
+        let mut end_cursor = None;
+        while let Some(m) = rows.next().await {
+            let m = m.map_err(StorageError::Database)?;
+            if count == limit {
+                has_next_page = true;
+                break;
+            }
+            count += 1;
+            #unpack_row
+            let cursor = #cursor;
+            start_cursor.get_or_insert_with(|| cursor.clone());
+            end_cursor = Some(cursor.clone());
+            list.push(&#edge_ident {
+                cursor,
+                node: Some(m.into()),
+            });
+        }
+
+        Ok(list.finish(&PageInfo {
+            has_next_page,
+            has_previous_page: request.after.is_some(),
+            start_cursor,
+            end_cursor,
+        }))
+    }
+}
+
+/// Generate `EncodedList`, the buffer the encoded LIST defaults write into
+///
+/// Emitted once per defaults module that has an encoded list.
+pub fn generate_encoded_list() -> TokenStream {
+    quote! {
+        /// A list response encoded edge by edge, for the `*_encoded` list defaults
+        ///
+        /// Holds the protobuf bytes of the response rather than its edges; the
+        /// finished bytes decode as the response message.
+        pub struct EncodedList {
+            buf: prost::bytes::BytesMut,
+            edges_tag: u32,
+            page_info_tag: u32,
+        }
+
+        impl EncodedList {
+            /// Start a response whose `edges` and `page_info` have these field numbers
+            pub fn new(edges_tag: u32, page_info_tag: u32) -> Self {
+                Self {
+                    buf: prost::bytes::BytesMut::new(),
+                    edges_tag,
+                    page_info_tag,
+                }
+            }
+
+            /// Append an edge
+            pub fn push<E: prost::Message>(&mut self, edge: &E) {
+                prost::encoding::message::encode(self.edges_tag, edge, &mut self.buf);
+            }
+
+            /// Append the page info and return the encoded response
+            pub fn finish(mut self, page_info: &PageInfo) -> prost::bytes::Bytes {
+                prost::encoding::message::encode(self.page_info_tag, page_info, &mut self.buf);
+                self.buf.freeze()
+            }
+        }
+    }
+}
+
+/// Generate a server-streaming LIST implementation
+///
+/// Rows are read by a spawned task and sent one at a time through a bounded
+/// channel, so at most `ROW_STREAM_BUFFER` converted rows are in memory. When
+/// the client disconnects, the receiving stream is dropped, the next send
+/// fails, and the task stops reading. There is no page size unless the
+/// request sets `first`/`last`. Items are edges of the entity, or the entity
+/// message itself when `item_ident` names it. Always standalone.
+pub fn generate_list_stream_impl(
+    file: &FileDescriptorProto,
+    request_type: &str,
+    entity_module: &proc_macro2::Ident,
+    item_ident: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    all_files: &[FileDescriptorProto],
+) -> TokenStream {
+    let entity_type = entity_module.to_string().to_upper_camel_case();
+
+    let query_code = generate_list_query(file, request_type, entity_module, entity_options, all_files, false);
+
+    let convert = if *item_ident == entity_type {
+        quote! { m.into() }
+    } else {
+        quote! {
+            #item_ident {
+                cursor: m.id.to_string(),
+                node: Some(m.into()),
+            }
+        }
+    };
+
+    quote! {
+        #query_code
+
+        if let Some(limit) = limit {
+            query = query.limit(limit);
+        }
+
+        let db = db.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(ROW_STREAM_BUFFER);
+        tokio::spawn(async move {
+            use tokio_stream::StreamExt;
+
+            let rows = match query.stream(&db).await {
+                Ok(rows) => rows,
+                Err(e) => {
+                    let _ = tx.send(Err(StorageError::Database(e))).await;
+                    return;
+                }
+            };
+            let mut rows = std::pin::pin!(rows);
+            while let Some(row) = rows.next().await {
+                let item = row.map(|m| #convert).map_err(StorageError::Database);
+                // The receiver is dropped when the client disconnects
+                if tx.send(item).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(tokio_stream::wrappers::ReceiverStream::new(rx))
+    }
+}
+
+/// Generate a server-streaming LIST paged through the default `list_fn`
+///
+/// A spawned task calls `list_fn` for pages of `page_size` rows, starting
+/// each page after the previous page's end cursor, and sends the rows through
+/// a bounded channel as in `generate_list_stream_impl`. Each page is a short
+/// query, so no connection is held while the client reads. `first`/`last`
+/// cap the rows streamed. Items are edges, or their nodes when `nodes` is
+/// set. Always standalone.
+pub fn generate_list_paged_impl(list_fn: &proc_macro2::Ident, nodes: bool, page_size: u32) -> TokenStream {
+    let page_size = page_size as u64;
+    let send = if nodes {
+        quote! {
+            for node in connection.edges.into_iter().filter_map(|edge| edge.node) {
+                if tx.send(Ok(node)).await.is_err() {
+                    return;
+                }
+            }
+        }
+    } else {
+        quote! {
+            for edge in connection.edges {
+                if tx.send(Ok(edge)).await.is_err() {
+                    return;
+                }
+            }
+        }
+    };
+
+    quote! {
+        let db = db.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(ROW_STREAM_BUFFER);
+        tokio::spawn(async move {
+            let mut remaining = request.first.or(request.last).map(|n| n.max(0) as u64);
+            let mut page = request;
+            page.last = None;
+            loop {
+                let size = remaining.map_or(#page_size, |n| n.min(#page_size));
+                if size == 0 {
+                    break;
+                }
+                page.first = Some(size as i32);
+                let connection = match #list_fn(&db, page.clone()).await {
+                    Ok(connection) => connection,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+                let page_info = connection.page_info.clone().unwrap_or_default();
+                // The receiver is dropped when the client disconnects
+                #send
+                if let Some(n) = remaining.as_mut() {
+                    *n -= size;
+                }
+                if !page_info.has_next_page || page_info.end_cursor.is_none() {
+                    break;
+                }
+                page.after = page_info.end_cursor;
+            }
+        });
+
+        Ok(tokio_stream::wrappers::ReceiverStream::new(rx))
+    }
+}
+
+/// Generate the filtered, ordered, cursor-bounded query of a LIST
+///
+/// Binds `limit` and `query` for the fetch code that follows. A `bounded`
+/// list defaults `limit` to a page of 20; otherwise `limit` is an `Option`
+/// set only by `first`/`last`.
+fn generate_list_query(
+    file: &FileDescriptorProto,
+    request_type: &str,
+    entity_module: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    all_files: &[FileDescriptorProto],
+    bounded: bool,
+) -> TokenStream {
     // Find the request message to get filter/order_by field types
     let request_msg = file
         .message_type
@@ -214,6 +767,82 @@ pub fn generate_list_impl(
         quote! {}
     };
 
+    let not_deleted = generate_not_deleted_filter(entity_module, entity_options);
+
+    // Full-text search, when the request has a `search` field and the entity has searchable columns
+    let rank_sql = format!("ts_rank({}, websearch_to_tsquery('{}', ?))", SEARCH_VECTOR_COLUMN, SEARCH_CONFIG);
+    let search_field = list_search_field(file, request_type, entity_module, all_files);
+    let search_code = search_field
+        .map(|field| {
+            let search = if field.proto3_optional.unwrap_or(false) {
+                quote! { request.search.as_deref().unwrap_or("") }
+            } else {
+                quote! { request.search.as_str() }
+            };
+            let matches = format!("{} @@ websearch_to_tsquery('{}', ?)", SEARCH_VECTOR_COLUMN, SEARCH_CONFIG);
+            quote! {
+                // Ranked full-text match; the best matches come first
+                let search = #search.trim().to_string();
+                let ranked = !search.is_empty();
+                let rank = || sea_orm::sea_query::Expr::cust_with_values(#rank_sql, [search.clone()]);
+                if ranked {
+                    use sea_orm::sea_query::{Expr, Order};
+                    query = query
+                        .filter(Expr::cust_with_values(#matches, [search.clone()]))
+                        .order_by(rank(), Order::Desc);
+                }
+            }
+        })
+        .unwrap_or_default();
+
+    // A ranked page's cursors are `rank:id`, so a page starts past both
+    let cursor_filter = |cursor: &str, rank_op: &str, id_op: &str| {
+        let cursor = format_ident!("{}", cursor);
+        let id_op = format_ident!("{}", id_op);
+        let by_id = quote! {
+            if let Some(cursor_id) = parse_key::<entities::#entity_module::Entity>(#cursor) {
+                query = query.filter(entities::#entity_module::Column::Id.#id_op(cursor_id));
+            }
+        };
+        if search_field.is_none() {
+            return quote! {
+                if let Some(ref #cursor) = request.#cursor {
+                    #by_id
+                }
+            };
+        }
+        let rank_op = format!("{} {} ?", rank_sql, rank_op);
+        let rank_eq = format!("{} = ?", rank_sql);
+        quote! {
+            if let Some(ref #cursor) = request.#cursor {
+                match #cursor.split_once(':').filter(|_| ranked) {
+                    Some((rank, id)) => {
+                        if let (Ok(rank), Some(cursor_id)) =
+                            (rank.parse::<f32>(), parse_key::<entities::#entity_module::Entity>(id))
+                        {
+                            use sea_orm::sea_query::Expr;
+                            let values = || [sea_orm::Value::from(search.clone()), sea_orm::Value::from(rank)];
+                            query = query.filter(
+                                Condition::any()
+                                    .add(Expr::cust_with_values(#rank_op, values()))
+                                    .add(
+                                        Condition::all()
+                                            .add(Expr::cust_with_values(#rank_eq, values()))
+                                            .add(entities::#entity_module::Column::Id.#id_op(cursor_id)),
+                                    ),
+                            );
+                        }
+                    }
+                    None => {
+                        #by_id
+                    }
+                }
+            }
+        }
+    };
+    let after_filter = cursor_filter("after", "<", "gt");
+    let before_filter = cursor_filter("before", ">", "lt");
+
     let ordering_code = if has_order_by {
         quote! {
             // Apply ordering from request
@@ -231,73 +860,80 @@ pub fn generate_list_impl(
         }
     };
 
+    let limit_code = if bounded {
+        quote! {
+            // Default limit
+            let limit = request.first.or(request.last).unwrap_or(20) as u64;
+        }
+    } else {
+        quote! {
+            let limit = request.first.or(request.last).map(|n| n as u64);
+        }
+    };
+
     quote! {
         use sea_orm::{QuerySelect, Condition};
 
-        // Default limit
-        let limit = request.first.or(request.last).unwrap_or(20) as u64;
+        #limit_code
 
         // Build base query
-        let mut query = entities::#entity_module::Entity::find();
+        let mut query = entities::#entity_module::Entity::find()#not_deleted;
 
         // Apply filters
         #filter_code
 
+        #search_code
+
         #ordering_code
 
         // Apply cursor filter (after = id to start after)
-        if let Some(ref after) = request.after {
-            if let Ok(cursor_id) = after.parse::<i64>() {
-                query = query.filter(entities::#entity_module::Column::Id.gt(cursor_id));
-            }
-        }
+        #after_filter
 
         // Apply cursor filter (before = id to end before)
-        if let Some(ref before) = request.before {
-            if let Ok(cursor_id) = before.parse::<i64>() {
-                query = query.filter(entities::#entity_module::Column::Id.lt(cursor_id));
-            }
-        }
-
-        // Fetch one extra to determine if there's a next page
-        let models = query
-            .limit(limit + 1)
-            .all(#db_ref)
-            .await
-            .map_err(StorageError::Database)?;
-
-        let has_next_page = models.len() > limit as usize;
-        let models: Vec<_> = models.into_iter().take(limit as usize).collect();
-
-        let edges: Vec<#edge_ident> = models
-            .into_iter()
-            .map(|m| {
-                let cursor = m.id.to_string();
-                #edge_ident {
-                    cursor,
-                    node: Some(m.into()),
-                }
-            })
-            .collect();
-
-        let start_cursor = edges.first().map(|e| e.cursor.clone());
-        let end_cursor = edges.last().map(|e| e.cursor.clone());
-
-        Ok(#response_ident {
-            edges,
-            page_info: Some(PageInfo {
-                has_next_page,
-                has_previous_page: request.after.is_some(),
-                start_cursor,
-                end_cursor,
-            }),
-        })
+        #before_filter
     }
 }
 
-/// Generate filter condition code from request message
-fn generate_filter_code(
-    request_msg: &DescriptorProto,
+/// The `search` field of a LIST request, when its entity has searchable columns
+fn list_search_field<'a>(
+    file: &'a FileDescriptorProto,
+    request_type: &str,
+    entity_module: &proc_macro2::Ident,
+    all_files: &[FileDescriptorProto],
+) -> Option<&'a FieldDescriptorProto> {
+    let entity_type = entity_module.to_string().to_upper_camel_case();
+    file.message_type
+        .iter()
+        .find(|m| m.name.as_deref() == Some(request_type))?
+        .field
+        .iter()
+        .find(|f| f.name.as_deref() == Some("search"))
+        .filter(|_| !searchable_columns(all_files, &entity_type).is_empty())
+}
+
+/// Generate filter condition code from request message
+fn generate_filter_code(
+    request_msg: &DescriptorProto,
+    entity_module: &proc_macro2::Ident,
+    all_files: &[FileDescriptorProto],
+) -> Option<TokenStream> {
+    let field_conditions = generate_filter_conditions(request_msg, entity_module, all_files)?;
+    Some(quote! {
+        if let Some(ref filter) = request.filter {
+            let mut cond = Condition::all();
+            #field_conditions
+            query = query.filter(cond);
+        }
+    })
+}
+
+/// Generate the statements adding the request's `filter` to `cond`
+///
+/// The code reads `filter` (the request's filter message) and adds one
+/// condition per set operator to a mutable `cond`. `None` when the request has
+/// no filter field or the filter has no supported fields.
+fn generate_filter_conditions(
+    request_msg: &DescriptorProto,
     entity_module: &proc_macro2::Ident,
     all_files: &[FileDescriptorProto],
 ) -> Option<TokenStream> {
@@ -316,6 +952,20 @@ fn generate_filter_code(
         .flat_map(|f| f.message_type.iter())
         .find(|m| m.name.as_deref() == Some(filter_type))?;
 
+    // UUID/ULID keys travel as strings in protos and are parsed before comparing
+    let entity_type = entity_module.to_string().to_upper_camel_case();
+    let string_key = has_string_primary_key(all_files, &entity_type);
+
+    // Entity message, for array element types
+    let entity_msg = all_files
+        .iter()
+        .flat_map(|f| f.message_type.iter())
+        .find(|m| m.name.as_deref() == Some(entity_type.as_str()));
+
+    // Filter timestamps are converted to the column's zone handling
+    let naive = naive_timestamp_columns(all_files, &entity_type);
+    let case_insensitive = case_insensitive_columns(all_files, &entity_type);
+
     // Generate condition code for each field in the filter
     let mut field_conditions = Vec::new();
 
@@ -326,12 +976,29 @@ fn generate_filter_code(
 
         // Determine the filter type from the field's type_name
         let type_name = field.type_name.as_ref()?;
-        let filter_kind = if type_name.contains("IntFilter") || type_name.contains("Int64Filter") || type_name.contains("Int32Filter") {
+        let filter_kind = if type_name.contains("StringArrayFilter") {
+            FilterKind::StringArray
+        } else if type_name.contains("IntArrayFilter") {
+            // Postgres compares arrays of the same element type only
+            let int32_column = entity_msg
+                .and_then(|m| m.field.iter().find(|f| f.name.as_deref() == Some(field_name)))
+                .is_some_and(|f| matches!(f.r#type(), Type::Int32 | Type::Sint32 | Type::Sfixed32));
+            FilterKind::IntArray { int32: int32_column }
+        } else if type_name.contains("IntFilter") || type_name.contains("Int64Filter") || type_name.contains("Int32Filter") {
             FilterKind::Int
+        } else if type_name.contains("StringFilter") && field_name == "id" && string_key {
+            FilterKind::Key
         } else if type_name.contains("StringFilter") {
-            FilterKind::String
+            match case_insensitive.iter().find(|(name, _)| name == field_name) {
+                Some((_, ci)) => FilterKind::CaseInsensitiveString(ci.clone()),
+                None => FilterKind::String,
+            }
         } else if type_name.contains("BoolFilter") {
             FilterKind::Bool
+        } else if type_name.contains("GeoPointFilter") {
+            FilterKind::GeoPoint
+        } else if type_name.contains("TimestampFilter") {
+            FilterKind::Timestamp { naive: naive.iter().any(|n| n == field_name) }
         } else {
             continue; // Unknown filter type, skip
         };
@@ -350,20 +1017,26 @@ fn generate_filter_code(
         return None;
     }
 
-    Some(quote! {
-        if let Some(ref filter) = request.filter {
-            let mut cond = Condition::all();
-            #(#field_conditions)*
-            query = query.filter(cond);
-        }
-    })
+    Some(quote! { #(#field_conditions)* })
 }
 
 /// Filter kind for code generation
 enum FilterKind {
     Int,
+    /// String-encoded primary key (UUID or ULID)
+    Key,
     String,
     Bool,
+    /// citext or collated column: eq/neq compare case-insensitively
+    CaseInsensitiveString(CaseInsensitive),
+    /// Postgres text[] column
+    StringArray,
+    /// Postgres integer[] (`int32`) or bigint[] column
+    IntArray { int32: bool },
+    /// `timestamptz` column, or `timestamp` when `naive`
+    Timestamp { naive: bool },
+    /// PostGIS geography point column
+    GeoPoint,
 }
 
 /// Generate filter condition code for a single field
@@ -387,6 +1060,21 @@ fn generate_field_filter_code(
                 }
             }
         }
+        FilterKind::Key => {
+            quote! {
+                if let Some(ref f) = filter.#field_ident {
+                    let key = |v: &String| parse_key::<entities::#entity_module::Entity>(v);
+                    if let Some(v) = f.eq.as_ref().and_then(key) { cond = cond.add(entities::#entity_module::Column::#column_ident.eq(v)); }
+                    if let Some(v) = f.neq.as_ref().and_then(key) { cond = cond.add(entities::#entity_module::Column::#column_ident.ne(v)); }
+                    if let Some(v) = f.gt.as_ref().and_then(key) { cond = cond.add(entities::#entity_module::Column::#column_ident.gt(v)); }
+                    if let Some(v) = f.lt.as_ref().and_then(key) { cond = cond.add(entities::#entity_module::Column::#column_ident.lt(v)); }
+                    if !f.r#in.is_empty() {
+                        let keys: Vec<_> = f.r#in.iter().filter_map(key).collect();
+                        cond = cond.add(entities::#entity_module::Column::#column_ident.is_in(keys));
+                    }
+                }
+            }
+        }
         FilterKind::String => {
             quote! {
                 if let Some(ref f) = filter.#field_ident {
@@ -405,6 +1093,110 @@ fn generate_field_filter_code(
                 }
             }
         }
+        FilterKind::CaseInsensitiveString(ci) => {
+            // The bound value is text: cast it to citext, or give it the
+            // column's collation, so Postgres compares without case
+            let value = match ci {
+                CaseInsensitive::Citext => quote! {
+                    Expr::val(v.clone()).cast_as(sea_orm::sea_query::Alias::new("citext"))
+                },
+                CaseInsensitive::Collation(collation) => {
+                    let sql = format!("? COLLATE \"{}\"", collation);
+                    quote! { Expr::cust_with_values(#sql, [v.clone()]) }
+                }
+            };
+            quote! {
+                if let Some(ref f) = filter.#field_ident {
+                    use sea_orm::sea_query::Expr;
+                    let column = || Expr::col((entities::#entity_module::Entity, entities::#entity_module::Column::#column_ident));
+                    let value = |v: &String| #value;
+                    if let Some(ref v) = f.eq { cond = cond.add(column().eq(value(v))); }
+                    if let Some(ref v) = f.neq { cond = cond.add(column().ne(value(v))); }
+                    if let Some(ref v) = f.contains { cond = cond.add(entities::#entity_module::Column::#column_ident.contains(v)); }
+                    if let Some(ref v) = f.starts_with { cond = cond.add(entities::#entity_module::Column::#column_ident.starts_with(v)); }
+                    if let Some(ref v) = f.ends_with { cond = cond.add(entities::#entity_module::Column::#column_ident.ends_with(v)); }
+                }
+            }
+        }
+        FilterKind::StringArray => generate_array_filter_code(entity_module, field_ident, column_ident, quote! { v.clone() }),
+        FilterKind::IntArray { int32 } => {
+            let value = if int32 { quote! { *v as i32 } } else { quote! { *v } };
+            generate_array_filter_code(entity_module, field_ident, column_ident, value)
+        }
+        FilterKind::GeoPoint => {
+            quote! {
+                if let Some(ref f) = filter.#field_ident {
+                    use sea_orm::sea_query::Expr;
+                    let column = || Expr::col((entities::#entity_module::Entity, entities::#entity_module::Column::#column_ident));
+                    if let Some(ref r) = f.within_radius {
+                        let center = r.center.unwrap_or_default();
+                        cond = cond.add(Expr::cust_with_exprs(
+                            "ST_DWithin($1, ST_SetSRID(ST_MakePoint($2, $3), 4326)::geography, $4)",
+                            [
+                                column().into(),
+                                Expr::val(center.longitude).into(),
+                                Expr::val(center.latitude).into(),
+                                Expr::val(r.meters).into(),
+                            ],
+                        ));
+                    }
+                    if let Some(is_null) = f.is_null {
+                        cond = cond.add(if is_null { column().is_null() } else { column().is_not_null() });
+                    }
+                }
+            }
+        }
+        FilterKind::Timestamp { naive } => {
+            let from_proto = time_crate().proto_to_column(naive);
+            quote! {
+                if let Some(ref f) = filter.#field_ident {
+                    let at = |t: &prost_types::Timestamp| #from_proto;
+                    if let Some(v) = f.eq.as_ref().and_then(at) { cond = cond.add(entities::#entity_module::Column::#column_ident.eq(v)); }
+                    if let Some(v) = f.neq.as_ref().and_then(at) { cond = cond.add(entities::#entity_module::Column::#column_ident.ne(v)); }
+                    if let Some(v) = f.gt.as_ref().and_then(at) { cond = cond.add(entities::#entity_module::Column::#column_ident.gt(v)); }
+                    if let Some(v) = f.gte.as_ref().and_then(at) { cond = cond.add(entities::#entity_module::Column::#column_ident.gte(v)); }
+                    if let Some(v) = f.lt.as_ref().and_then(at) { cond = cond.add(entities::#entity_module::Column::#column_ident.lt(v)); }
+                    if let Some(v) = f.lte.as_ref().and_then(at) { cond = cond.add(entities::#entity_module::Column::#column_ident.lte(v)); }
+                    if let Some(is_null) = f.is_null {
+                        cond = cond.add(if is_null {
+                            entities::#entity_module::Column::#column_ident.is_null()
+                        } else {
+                            entities::#entity_module::Column::#column_ident.is_not_null()
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Generate `has`/`has_any`/`has_all` conditions for an array column
+///
+/// `has` and `has_all` use `@>` (contains), `has_any` uses `&&` (overlap).
+/// `value` converts a filter element `v` into the column's element type.
+fn generate_array_filter_code(
+    entity_module: &proc_macro2::Ident,
+    field_ident: &proc_macro2::Ident,
+    column_ident: &proc_macro2::Ident,
+    value: TokenStream,
+) -> TokenStream {
+    quote! {
+        if let Some(ref f) = filter.#field_ident {
+            use sea_orm::sea_query::extension::postgres::PgBinOper;
+            use sea_orm::sea_query::Expr;
+            let column = || Expr::col((entities::#entity_module::Entity, entities::#entity_module::Column::#column_ident));
+            if let Some(ref v) = f.has {
+                cond = cond.add(column().binary(PgBinOper::Contains, Expr::val(vec![#value])));
+            }
+            if !f.has_any.is_empty() {
+                let values: Vec<_> = f.has_any.iter().map(|v| #value).collect();
+                cond = cond.add(column().binary(PgBinOper::Overlap, Expr::val(values)));
+            }
+            if !f.has_all.is_empty() {
+                let values: Vec<_> = f.has_all.iter().map(|v| #value).collect();
+                cond = cond.add(column().binary(PgBinOper::Contains, Expr::val(values)));
+            }
+        }
     }
 }
 
@@ -462,11 +1254,14 @@ fn generate_order_by_code(
 
 /// Generate a CREATE implementation
 ///
+/// `unique` fields (the `unique_in_db` validate rule) are checked for
+/// conflicts inside the insert transaction.
 /// If `for_standalone` is true, generates code using `db` parameter instead of `self.db`
 pub fn generate_create_impl(
     entity_module: &proc_macro2::Ident,
     response_ident: &proc_macro2::Ident,
-    _entity_options: Option<&storage::EntityOptions>,
+    entity_options: Option<&storage::EntityOptions>,
+    unique: &[UniqueField],
     for_standalone: bool,
 ) -> TokenStream {
     let db_ref = if for_standalone {
@@ -475,10 +1270,42 @@ pub fn generate_create_impl(
         quote! { &self.db }
     };
 
+    let set_timestamps = if has_managed_timestamps(entity_options) {
+        let now = time_crate().now();
+        quote! {
+            let now = #now;
+            active_model.created_at = Set(now);
+            active_model.updated_at = Set(now);
+        }
+    } else {
+        quote! {}
+    };
+
+    let record_writes = records_writes(entity_options);
+    let (begin, conn, commit) = generate_write_transaction(&db_ref, !unique.is_empty() || record_writes);
+    let unique_checks = generate_unique_checks(entity_module, unique, false, &conn);
+    let write_error = generate_write_error(entity_module, unique);
+    let record_event = if record_writes {
+        generate_record(entity_module, entity_options, "Created", &conn)
+    } else {
+        quote! {}
+    };
+    let index_events = generate_index_events(entity_module, entity_options, quote! { std::iter::once(&model) });
+
     quote! {
+        #begin
+        #unique_checks
         // Request fields are directly on the request (no nested input)
-        let active_model: entities::#entity_module::ActiveModel = request.into();
-        let model = active_model.insert(#db_ref).await.map_err(StorageError::Database)?;
+        let mut active_model: entities::#entity_module::ActiveModel = request.into();
+        // Server-generated keys (e.g. UUID v7); auto-increment keys are left to the database
+        if let Some(id) = generate_key::<entities::#entity_module::Entity>() {
+            active_model.id = Set(id);
+        }
+        #set_timestamps
+        let model = active_model.insert(#conn).await.map_err(#write_error)?;
+        #record_event
+        #commit
+        #index_events
 
         Ok(#response_ident {
             #entity_module: Some(model.into()),
@@ -486,52 +1313,468 @@ pub fn generate_create_impl(
     }
 }
 
-/// Generate an UPDATE implementation
+/// Generate a CREATE MANY implementation
 ///
-/// If `for_standalone` is true, generates code using `db` parameter instead of `self.db`
-pub fn generate_update_impl(
+/// Converts every item of `request.items` like a create and inserts them with a
+/// single multi-row INSERT, returning the created rows in `response_field`.
+/// Items are validated into `item_domain` first when the create request has a
+/// domain type. With `batch`, failing items are reported in its response
+/// field instead (see [`super::batch`]), violations of `unique` fields as
+/// `ALREADY_EXISTS` (see [`generate_write_error`]). If `for_standalone` is true,
+/// generates code using `db` parameter instead of `self.db`
+#[allow(clippy::too_many_arguments)]
+pub fn generate_create_many_impl(
     entity_module: &proc_macro2::Ident,
     response_ident: &proc_macro2::Ident,
-    _entity_options: Option<&storage::EntityOptions>,
+    response_field: &proc_macro2::Ident,
+    item_domain: Option<&proc_macro2::Ident>,
+    entity_options: Option<&storage::EntityOptions>,
+    unique: &[UniqueField],
+    batch: Option<&BatchErrors>,
     for_standalone: bool,
 ) -> TokenStream {
-    // Derive the entity type name (e.g., user -> User)
-    let entity_type = format_ident!("{}", entity_module.to_string().to_upper_camel_case());
-
     let db_ref = if for_standalone {
         quote! { db }
     } else {
         quote! { &self.db }
     };
 
+    let (now, set_timestamps) = if has_managed_timestamps(entity_options) {
+        let now = time_crate().now();
+        (
+            quote! { let now = #now; },
+            quote! {
+                active_model.created_at = Set(now);
+                active_model.updated_at = Set(now);
+            },
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    let record_writes = records_writes(entity_options);
+    let (begin, conn, commit) = generate_write_transaction(&db_ref, record_writes);
+
+    if let Some(batch) = batch {
+        return generate_partial_create_many(
+            entity_module,
+            response_ident,
+            response_field,
+            item_domain,
+            entity_options,
+            batch,
+            (now, set_timestamps),
+            (begin, conn, commit, record_writes),
+            (db_ref, generate_write_error(entity_module, unique)),
+        );
+    }
+    let index_events = generate_index_events(entity_module, entity_options, quote! { &models });
+
+    let record_events = if record_writes {
+        let record = generate_record(entity_module, entity_options, "Created", &conn);
+        quote! {
+            for model in &models {
+                #record
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let (items, validate) = match item_domain {
+        Some(domain) => (
+            quote! { (index, item) in request.items.into_iter().enumerate() },
+            quote! {
+                let item = #domain::try_from(item)
+                    .map_err(|e| StorageError::InvalidArgument(format!("items[{}]: {}", index, e)))?;
+            },
+        ),
+        None => (quote! { item in request.items }, quote! {}),
+    };
+
     quote! {
-        use sea_orm::IntoActiveModel;
+        if request.items.is_empty() {
+            return Ok(#response_ident {
+                #response_field: Vec::new(),
+            });
+        }
 
-        // Find existing entity
-        let model = entities::#entity_module::Entity::find_by_id(request.id)
-            .one(#db_ref)
+        #now
+        let mut active_models = Vec::with_capacity(request.items.len());
+        for #items {
+            #validate
+            let mut active_model: entities::#entity_module::ActiveModel = item.into();
+            // Server-generated keys (e.g. UUID v7); auto-increment keys are left to the database
+            if let Some(id) = generate_key::<entities::#entity_module::Entity>() {
+                active_model.id = Set(id);
+            }
+            #set_timestamps
+            active_models.push(active_model);
+        }
+
+        // One multi-row INSERT for the whole batch
+        #begin
+        let models = entities::#entity_module::Entity::insert_many(active_models)
+            .exec_with_returning_many(#conn)
             .await
-            .map_err(StorageError::Database)?
-            .ok_or_else(|| StorageError::NotFound(format!("{} with id {} not found", stringify!(#entity_type), request.id)))?;
+            .map_err(StorageError::Database)?;
+        #record_events
+        #commit
+        #index_events
 
-        // Convert to active model and apply updates from request
-        let mut active_model = model.into_active_model();
-        active_model.apply_update(&request);
+        Ok(#response_ident {
+            #response_field: models.into_iter().map(Into::into).collect(),
+        })
+    }
+}
+
+/// Generate the body of a create-many reporting failing items in `batch.field`
+///
+/// `timestamps` are the `now` binding and the statements setting managed
+/// timestamps; `write` the write transaction's begin, connection and commit,
+/// and whether it records outbox events; `errors` the database handle and
+/// the mapping of a failed INSERT's error. An atomic batch whose INSERT fails
+/// retries the rows one by one in a transaction it rolls back, to report the
+/// rows the database rejects.
+#[allow(clippy::too_many_arguments)]
+fn generate_partial_create_many(
+    entity_module: &proc_macro2::Ident,
+    response_ident: &proc_macro2::Ident,
+    response_field: &proc_macro2::Ident,
+    item_domain: Option<&proc_macro2::Ident>,
+    entity_options: Option<&storage::EntityOptions>,
+    batch: &BatchErrors,
+    timestamps: (TokenStream, TokenStream),
+    write: (TokenStream, TokenStream, TokenStream, bool),
+    errors: (TokenStream, TokenStream),
+) -> TokenStream {
+    let (now, set_timestamps) = timestamps;
+    let (begin, conn, commit, record_writes) = write;
+    let (db_ref, write_error) = errors;
+    let errors_field = &batch.field;
+
+    // Items failing validation get an error per field
+    let validate = match item_domain {
+        Some(domain) => quote! {
+            let item = match #domain::try_from(item) {
+                Ok(item) => item,
+                Err(e) => {
+                    batch_errors.extend(e.errors().iter().map(|error| super::super::super::synapse::relay::BatchError {
+                        index: index as i32,
+                        code: error.code.clone(),
+                        field: error.field.clone(),
+                        message: error.message.clone(),
+                    }));
+                    continue;
+                }
+            };
+        },
+        None => quote! {},
+    };
+    let build = quote! {
+        #validate
+        let mut active_model: entities::#entity_module::ActiveModel = item.into();
+        // Server-generated keys (e.g. UUID v7); auto-increment keys are left to the database
+        if let Some(id) = generate_key::<entities::#entity_module::Entity>() {
+            active_model.id = Set(id);
+        }
+        #set_timestamps
+    };
+
+    if batch.atomic {
+        let abort = quote! {
+            let failed: std::collections::HashSet<i32> = batch_errors.iter().map(|e| e.index).collect();
+            batch_errors.extend((0..count as i32).filter(|index| !failed.contains(index)).map(|index| {
+                super::super::super::synapse::relay::BatchError {
+                    index,
+                    code: "ABORTED".to_string(),
+                    field: String::new(),
+                    message: "not written: another item of the batch failed".to_string(),
+                }
+            }));
+            batch_errors.sort_by_key(|e| e.index);
+            return Ok(#response_ident {
+                #response_field: Vec::new(),
+                #errors_field: batch_errors,
+            });
+        };
+        let index_events = generate_index_events(entity_module, entity_options, quote! { &models });
+        let record_events = if record_writes {
+            let record = generate_record(entity_module, entity_options, "Created", &conn);
+            quote! {
+                for model in &models {
+                    #record
+                }
+            }
+        } else {
+            quote! {}
+        };
+        return quote! {
+            if request.items.is_empty() {
+                return Ok(#response_ident {
+                    #response_field: Vec::new(),
+                    #errors_field: Vec::new(),
+                });
+            }
+
+            #now
+            let count = request.items.len();
+            let mut active_models = Vec::with_capacity(count);
+            let mut batch_errors = Vec::new();
+            for (index, item) in request.items.into_iter().enumerate() {
+                #build
+                active_models.push(active_model);
+            }
+
+            // Any failing item writes nothing; the others are reported as aborted
+            if !batch_errors.is_empty() {
+                #abort
+            }
+
+            // One multi-row INSERT for the whole batch
+            let written: Result<Vec<entities::#entity_module::Model>, StorageError> = async {
+                #begin
+                let models = entities::#entity_module::Entity::insert_many(active_models.clone())
+                    .exec_with_returning_many(#conn)
+                    .await
+                    .map_err(StorageError::Database)?;
+                #record_events
+                #commit
+                Ok(models)
+            }
+            .await;
+            let models = match written {
+                Ok(models) => models,
+                Err(error) => {
+                    // Find the rows the database rejects: each is inserted in a
+                    // savepoint of a transaction that is rolled back
+                    use sea_orm::TransactionTrait;
+                    let txn = (#db_ref).begin().await.map_err(StorageError::Database)?;
+                    for (index, active_model) in active_models.into_iter().enumerate() {
+                        let savepoint = txn.begin().await.map_err(StorageError::Database)?;
+                        match active_model.insert(&savepoint).await {
+                            Ok(_) => savepoint.commit().await.map_err(StorageError::Database)?,
+                            Err(e) => batch_errors.push(batch_error(index, (#write_error)(e).into())),
+                        }
+                    }
+                    txn.rollback().await.map_err(StorageError::Database)?;
+                    // No row failed on its own (e.g. a lost connection)
+                    if batch_errors.is_empty() {
+                        return Err(error);
+                    }
+                    #abort
+                }
+            };
+            #index_events
 
-        let model = active_model.update(#db_ref).await.map_err(StorageError::Database)?;
+            Ok(#response_ident {
+                #response_field: models.into_iter().map(Into::into).collect(),
+                #errors_field: Vec::new(),
+            })
+        };
+    }
+
+    let record = if record_writes {
+        generate_record(entity_module, entity_options, "Created", &conn)
+    } else {
+        quote! {}
+    };
+    let index_events = generate_index_events(entity_module, entity_options, quote! { std::iter::once(&model) });
+    quote! {
+        #now
+        let mut models = Vec::with_capacity(request.items.len());
+        let mut batch_errors = Vec::new();
+        for (index, item) in request.items.into_iter().enumerate() {
+            #build
+
+            // Each item is its own INSERT, so a failing row leaves the others written
+            let inserted: Result<entities::#entity_module::Model, StorageError> = async {
+                #begin
+                let model = active_model.insert(#conn).await.map_err(#write_error)?;
+                #record
+                #commit
+                #index_events
+                Ok(model)
+            }
+            .await;
+            match inserted {
+                Ok(model) => models.push(model),
+                Err(error) => batch_errors.push(batch_error(index, error.into())),
+            }
+        }
 
         Ok(#response_ident {
-            #entity_module: Some(model.into()),
+            #response_field: models.into_iter().map(Into::into).collect(),
+            #errors_field: batch_errors,
         })
     }
 }
 
-/// Generate a DELETE implementation
+/// Generate an IMPORT implementation
+///
+/// Reads the client's stream of create requests, converting each like a
+/// create (validated into `item_domain` when the create request has a domain
+/// type), and writes them `batch_size` at a time with one multi-row INSERT
+/// per chunk. A chunk that fails is retried row by row, so only its failing
+/// rows are lost. The response counts the inserted rows and reports failing
+/// items in input order (see [`super::batch`]). A broken client stream fails
+/// the call; chunks written before it stay written. Always standalone.
+pub fn generate_import_impl(
+    entity_module: &proc_macro2::Ident,
+    response_ident: &proc_macro2::Ident,
+    item_domain: Option<&proc_macro2::Ident>,
+    entity_options: Option<&storage::EntityOptions>,
+    summary: &ImportSummary,
+    batch_size: u32,
+) -> TokenStream {
+    let batch_size = batch_size as usize;
+    let set_timestamps = if has_managed_timestamps(entity_options) {
+        let now = time_crate().now();
+        quote! {
+            let now = #now;
+            active_model.created_at = Set(now);
+            active_model.updated_at = Set(now);
+        }
+    } else {
+        quote! {}
+    };
+
+    let record_writes = records_writes(entity_options);
+    let (begin, conn, commit) = generate_write_transaction(&quote! { db }, record_writes);
+    let (record_chunk, record_row) = if record_writes {
+        let record = generate_record(entity_module, entity_options, "Created", &conn);
+        (
+            quote! {
+                for model in &models {
+                    #record
+                }
+            },
+            record,
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+    let index_chunk = generate_index_events(entity_module, entity_options, quote! { &models });
+    let index_row = generate_index_events(entity_module, entity_options, quote! { std::iter::once(&model) });
+
+    // Items failing validation get an error per field
+    let validate = match item_domain {
+        Some(domain) => quote! {
+            let item = match #domain::try_from(item) {
+                Ok(item) => item,
+                Err(e) => {
+                    batch_errors.extend(e.errors().iter().map(|error| super::super::super::synapse::relay::BatchError {
+                        index: index as i32,
+                        code: error.code.clone(),
+                        field: error.field.clone(),
+                        message: error.message.clone(),
+                    }));
+                    continue;
+                }
+            };
+        },
+        None => quote! {},
+    };
+
+    let inserted_field = &summary.inserted;
+    let errors_field = &summary.errors;
+    let rest = if summary.other_fields {
+        quote! { ..Default::default() }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        use tokio_stream::StreamExt;
+
+        let mut request = request;
+        let mut inserted: u64 = 0;
+        let mut batch_errors = Vec::new();
+        let mut chunk: Vec<(usize, entities::#entity_module::ActiveModel)> = Vec::with_capacity(#batch_size);
+        let mut count = 0usize;
+        loop {
+            let next = request.next().await;
+            let done = next.is_none();
+            if let Some(item) = next {
+                let item = item?;
+                let index = count;
+                count += 1;
+                #validate
+                let mut active_model: entities::#entity_module::ActiveModel = item.into();
+                // Server-generated keys (e.g. UUID v7); auto-increment keys are left to the database
+                if let Some(id) = generate_key::<entities::#entity_module::Entity>() {
+                    active_model.id = Set(id);
+                }
+                #set_timestamps
+                chunk.push((index, active_model));
+            }
+
+            if chunk.len() >= #batch_size || (done && !chunk.is_empty()) {
+                let rows = std::mem::take(&mut chunk);
+
+                // One multi-row INSERT per chunk
+                let written: Result<Vec<entities::#entity_module::Model>, StorageError> = async {
+                    #begin
+                    let models = entities::#entity_module::Entity::insert_many(rows.iter().map(|(_, m)| m.clone()))
+                        .exec_with_returning_many(#conn)
+                        .await
+                        .map_err(StorageError::Database)?;
+                    #record_chunk
+                    #commit
+                    #index_chunk
+                    Ok(models)
+                }
+                .await;
+
+                match written {
+                    Ok(models) => inserted += models.len() as u64,
+                    // Retry the chunk row by row so only the failing rows are lost
+                    Err(_) => {
+                        for (index, active_model) in rows {
+                            let written: Result<entities::#entity_module::Model, StorageError> = async {
+                                #begin
+                                let model = active_model.insert(#conn).await.map_err(StorageError::Database)?;
+                                #record_row
+                                #commit
+                                #index_row
+                                Ok(model)
+                            }
+                            .await;
+                            match written {
+                                Ok(_) => inserted += 1,
+                                Err(error) => batch_errors.push(batch_error(index, error.into())),
+                            }
+                        }
+                    }
+                }
+            }
+
+            if done {
+                break;
+            }
+        }
+        batch_errors.sort_by_key(|e| e.index);
+
+        Ok(#response_ident {
+            #inserted_field: inserted as _,
+            #errors_field: batch_errors,
+            #rest
+        })
+    }
+}
+
+/// Generate an UPSERT implementation
 ///
+/// Inserts the request as a new row; a row conflicting on `conflict_columns`
+/// (the primary key when empty) gets `update_columns` from the request instead.
+/// With no `update_columns` the conflicting row is left as is and returned.
 /// If `for_standalone` is true, generates code using `db` parameter instead of `self.db`
-pub fn generate_delete_impl(
+pub fn generate_upsert_impl(
     entity_module: &proc_macro2::Ident,
     response_ident: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    conflict_columns: &[String],
+    update_columns: &[String],
     for_standalone: bool,
 ) -> TokenStream {
     let db_ref = if for_standalone {
@@ -540,15 +1783,1619 @@ pub fn generate_delete_impl(
         quote! { &self.db }
     };
 
+    let set_timestamps = if has_managed_timestamps(entity_options) {
+        let now = time_crate().now();
+        quote! {
+            let now = #now;
+            active_model.created_at = Set(now);
+            active_model.updated_at = Set(now);
+        }
+    } else {
+        quote! {}
+    };
+
+    let column = |name: &str| {
+        let ident = format_ident!("{}", name.to_upper_camel_case());
+        quote! { entities::#entity_module::Column::#ident }
+    };
+    let conflict: Vec<_> = if conflict_columns.is_empty() {
+        vec![column("id")]
+    } else {
+        conflict_columns.iter().map(|c| column(c)).collect()
+    };
+    let update: Vec<_> = update_columns.iter().map(|c| column(c)).collect();
+
+    // Inserted and overwritten rows are not told apart, so both record Updated
+    let record_writes = records_writes(entity_options);
+    let (begin, conn, commit) = generate_write_transaction(&db_ref, record_writes);
+    let record_event = if record_writes {
+        generate_record(entity_module, entity_options, "Updated", &conn)
+    } else {
+        quote! {}
+    };
+
+    let uncache = super::row_cache::generate_invalidate(entity_module, entity_options, quote! { model.id });
+    let index_events = generate_index_events(entity_module, entity_options, quote! { std::iter::once(&model) });
+
+    // A conflicting row with nothing to update is left as is. DO NOTHING
+    // returns no row, so the conflicting row is read back by its conflict
+    // columns, and nothing was written that hooks would need to know about.
+    let write = if update.is_empty() {
+        quote! {
+            let mut conflicting = sea_orm::Condition::all();
+            for column in [#(#conflict),*] {
+                if let Some(value) = active_model.get(column).into_value() {
+                    conflicting = conflicting.add(column.eq(value));
+                }
+            }
+            let inserted = entities::#entity_module::Entity::insert(active_model)
+                .on_conflict(OnConflict::columns([#(#conflict),*]).do_nothing().to_owned())
+                .exec_with_returning(#conn)
+                .await;
+            let model = match inserted {
+                Ok(model) => {
+                    #record_event
+                    #commit
+                    #uncache
+                    #index_events
+                    model
+                }
+                Err(sea_orm::DbErr::RecordNotFound(_)) => {
+                    #commit
+                    entities::#entity_module::Entity::find()
+                        .filter(conflicting)
+                        .one(#db_ref)
+                        .await
+                        .map_err(StorageError::Database)?
+                        .ok_or_else(|| StorageError::NotFound("conflicting row was deleted".to_string()))?
+                }
+                Err(e) => return Err(StorageError::Database(e)),
+            };
+        }
+    } else {
+        quote! {
+            let model = entities::#entity_module::Entity::insert(active_model)
+                .on_conflict(OnConflict::columns([#(#conflict),*]).update_columns([#(#update),*]).to_owned())
+                .exec_with_returning(#conn)
+                .await
+                .map_err(StorageError::Database)?;
+            #record_event
+            #commit
+            #uncache
+            #index_events
+        }
+    };
+
     quote! {
-        let result = entities::#entity_module::Entity::delete_by_id(request.id)
-            .exec(#db_ref)
-            .await
-            .map_err(StorageError::Database)?;
+        use sea_orm::sea_query::OnConflict;
+
+        let mut active_model: entities::#entity_module::ActiveModel = request.into();
+        // Server-generated keys (e.g. UUID v7); auto-increment keys are left to the database
+        if let Some(id) = generate_key::<entities::#entity_module::Entity>() {
+            active_model.id = Set(id);
+        }
+        #set_timestamps
+        #begin
+        #write
 
         Ok(#response_ident {
-            success: result.rows_affected > 0,
+            #entity_module: Some(model.into()),
+        })
+    }
+}
+
+/// Columns an upsert overwrites on a conflicting row
+///
+/// The request's fields except the key, the conflict columns, and the columns
+/// storage owns, plus `updated_at` for managed timestamps and `deleted_at` for
+/// soft-deleted entities, so upserting a deleted row revives it.
+pub fn upsert_update_columns(
+    all_files: &[FileDescriptorProto],
+    package: &str,
+    entity: &str,
+    request: &DescriptorProto,
+    entity_options: Option<&storage::EntityOptions>,
+    conflict_columns: &[String],
+) -> Result<Vec<String>, GeneratorError> {
+    let Some((entity_file, entity_msg)) = all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .flat_map(|f| f.message_type.iter().map(move |m| (f, m)))
+        .find(|(_, m)| m.name.as_deref() == Some(entity))
+    else {
+        return Ok(Vec::new());
+    };
+    let entity_fields: Vec<String> = entity_msg
+        .field
+        .iter()
+        .map(|f| f.name.as_deref().unwrap_or("").to_snake_case())
+        .collect();
+    if let Some(unknown) = conflict_columns.iter().find(|c| !entity_fields.contains(c)) {
+        return Err(GeneratorError::CodeGenError(format!(
+            "{}: conflict column {} is not a field of the entity",
+            entity, unknown
+        )));
+    }
+
+    let generated = generated_columns(entity_file.name.as_deref().unwrap_or(""), entity_msg);
+    let context = context_columns(all_files, package, entity)?;
+    let mut columns: Vec<String> = request
+        .field
+        .iter()
+        .map(|f| f.name.as_deref().unwrap_or("").to_snake_case())
+        .filter(|name| {
+            name != "id"
+                && entity_fields.contains(name)
+                && !conflict_columns.contains(name)
+                && !MANAGED_TIMESTAMPS.contains(&name.as_str())
+                && !generated.contains(name)
+                && !context.iter().any(|c| !c.on_create && &c.name == name)
         })
+        .collect();
+    if has_managed_timestamps(entity_options) {
+        columns.push("updated_at".to_string());
+    }
+    if is_soft_delete(entity_options) {
+        columns.push("deleted_at".to_string());
     }
+    Ok(columns)
 }
 
+/// Generate an UPDATE implementation
+///
+/// `unique` fields are checked against other rows inside the update transaction.
+/// If `for_standalone` is true, generates code using `db` parameter instead of `self.db`
+pub fn generate_update_impl(
+    entity_module: &proc_macro2::Ident,
+    response_ident: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    unique: &[UniqueField],
+    for_standalone: bool,
+) -> TokenStream {
+    // Derive the entity type name (e.g., user -> User)
+    let entity_type = format_ident!("{}", entity_module.to_string().to_upper_camel_case());
+
+    let db_ref = if for_standalone {
+        quote! { db }
+    } else {
+        quote! { &self.db }
+    };
+
+    let not_deleted = generate_not_deleted_filter(entity_module, entity_options);
+
+    let bump_updated_at = if has_managed_timestamps(entity_options) {
+        let now = time_crate().now();
+        quote! { active_model.updated_at = Set(#now); }
+    } else {
+        quote! {}
+    };
+
+    let record_writes = records_writes(entity_options);
+    let (begin, conn, commit) = generate_write_transaction(&db_ref, !unique.is_empty() || record_writes);
+    let unique_checks = generate_unique_checks(entity_module, unique, true, &conn);
+    let write_error = generate_write_error(entity_module, unique);
+    let record_event = if record_writes {
+        generate_record(entity_module, entity_options, "Updated", &conn)
+    } else {
+        quote! {}
+    };
+
+    let uncache = super::row_cache::generate_invalidate(entity_module, entity_options, quote! { model.id });
+    let index_events = generate_index_events(entity_module, entity_options, quote! { std::iter::once(&model) });
+
+    quote! {
+        use sea_orm::IntoActiveModel;
+
+        // Find existing entity
+        let id = parse_key::<entities::#entity_module::Entity>(&request.id)
+            .ok_or_else(|| StorageError::InvalidArgument(format!("invalid id: {}", request.id)))?;
+        #begin
+        let model = entities::#entity_module::Entity::find_by_id(id)
+            #not_deleted
+            .one(#conn)
+            .await
+            .map_err(StorageError::Database)?
+            .ok_or_else(|| StorageError::NotFound(format!("{} with id {} not found", stringify!(#entity_type), request.id)))?;
+        #unique_checks
+
+        // Convert to active model and apply updates from request
+        let mut active_model = model.into_active_model();
+        active_model.apply_update(&request);
+        #bump_updated_at
+
+        let model = active_model.update(#conn).await.map_err(#write_error)?;
+        #record_event
+        #commit
+        #uncache
+        #index_events
+
+        Ok(#response_ident {
+            #entity_module: Some(model.into()),
+        })
+    }
+}
+
+/// Wrap a write in a transaction when it has `unique_in_db` checks or
+/// records outbox or webhook events
+///
+/// Returns the begin statement, the connection to use, and the commit statement.
+fn generate_write_transaction(
+    db_ref: &TokenStream,
+    transactional: bool,
+) -> (TokenStream, TokenStream, TokenStream) {
+    if !transactional {
+        return (quote! {}, db_ref.clone(), quote! {});
+    }
+    (
+        quote! {
+            use sea_orm::TransactionTrait;
+            let txn = (#db_ref).begin().await.map_err(StorageError::Database)?;
+        },
+        quote! { &txn },
+        quote! { txn.commit().await.map_err(StorageError::Database)?; },
+    )
+}
+
+/// Generate the search indexing storage runs once a write of `rows` commits
+///
+/// `rows` iterates the written `&Model`s. Empty unless the entity is `searchable`.
+fn generate_index_events(
+    entity_module: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    rows: TokenStream,
+) -> TokenStream {
+    match entity_options.filter(|o| o.searchable) {
+        Some(o) => super::search::generate_save_events(&entity_module.to_string(), o.soft_delete, rows),
+        None => quote! {},
+    }
+}
+
+/// Generate `unique_in_db` conflict checks against `conn`
+///
+/// `conn` is the write transaction. Matching rows are locked
+/// (`SELECT ... FOR UPDATE`) so a concurrent writer cannot change them before
+/// the insert/update lands. `FOR UPDATE` cannot lock a row that does not exist
+/// yet, so on Postgres each value also takes a transaction-scoped advisory
+/// lock, serializing concurrent writers of the same value; elsewhere the
+/// unique index is the final guard (see [`generate_write_error`]).
+/// Soft-deleted rows still count, since they still hold the unique index
+/// entry. For updates, the row being updated (`model`) is not a conflict.
+fn generate_unique_checks(
+    entity_module: &proc_macro2::Ident,
+    unique: &[UniqueField],
+    is_update: bool,
+    conn: &TokenStream,
+) -> TokenStream {
+    if unique.is_empty() {
+        return quote! {};
+    }
+    let entity_type = entity_module.to_string().to_upper_camel_case();
+
+    let checks = unique.iter().map(|u| {
+        let field_name = &u.field;
+        let field_ident = format_ident!("{}", u.field.to_snake_case());
+        let column = format_ident!("{}", u.field.to_upper_camel_case());
+        let message = format!("a {} with this {} already exists", entity_type, u.field);
+        // Update requests only carry the fields being changed
+        let optional = u.optional || is_update;
+        let value = if optional { quote! { value } } else { quote! { &request.#field_ident } };
+        let (fetch, is_conflict) = if is_update {
+            (quote! { .all(#conn) }, quote! { conflict.iter().any(|row| row.id != model.id) })
+        } else {
+            (quote! { .one(#conn) }, quote! { conflict.is_some() })
+        };
+        let lock_key = format!("{}.{}", entity_type, u.field);
+        let check = quote! {
+            if sea_orm::ConnectionTrait::get_database_backend(#conn) == sea_orm::DbBackend::Postgres {
+                sea_orm::ConnectionTrait::execute(#conn, sea_orm::Statement::from_sql_and_values(
+                    sea_orm::DbBackend::Postgres,
+                    "SELECT pg_advisory_xact_lock(hashtext($1))",
+                    [format!("{}:{:?}", #lock_key, #value).into()],
+                ))
+                .await
+                .map_err(StorageError::Database)?;
+            }
+            let conflict = entities::#entity_module::Entity::find()
+                .filter(entities::#entity_module::Column::#column.eq(#value.clone()))
+                .lock_exclusive()
+                #fetch
+                .await
+                .map_err(StorageError::Database)?;
+            if #is_conflict {
+                return Err(StorageError::AlreadyExists {
+                    field: #field_name.to_string(),
+                    message: #message.to_string(),
+                });
+            }
+        };
+        if optional {
+            quote! {
+                if let Some(ref value) = request.#field_ident {
+                    #check
+                }
+            }
+        } else {
+            check
+        }
+    });
+
+    quote! {
+        use sea_orm::QuerySelect;
+        #(#checks)*
+    }
+}
+
+/// Generate the error mapping of an insert/update with `unique_in_db` fields
+///
+/// The backstop for writes the checks could not serialize: a unique index
+/// violation (SQLSTATE 23505) becomes `AlreadyExists` for the
+/// field named in the violated constraint, or the first `unique` field;
+/// anything else stays a database error.
+fn generate_write_error(entity_module: &proc_macro2::Ident, unique: &[UniqueField]) -> TokenStream {
+    if unique.is_empty() {
+        return quote! { StorageError::Database };
+    }
+    let entity_type = entity_module.to_string().to_upper_camel_case();
+    let fields = unique.iter().map(|u| &u.field);
+    quote! {
+        |e: sea_orm::DbErr| match e.sql_err() {
+            Some(sea_orm::SqlErr::UniqueConstraintViolation(detail)) => {
+                let fields = [#(#fields),*];
+                let field = fields.iter().find(|f| detail.contains(*f)).unwrap_or(&fields[0]);
+                StorageError::AlreadyExists {
+                    field: field.to_string(),
+                    message: format!("a {} with this {} already exists", #entity_type, field),
+                }
+            }
+            _ => StorageError::Database(e),
+        }
+    }
+}
+
+/// Generate a DELETE implementation
+///
+/// If `for_standalone` is true, generates code using `db` parameter instead of `self.db`
+pub fn generate_delete_impl(
+    entity_module: &proc_macro2::Ident,
+    response_ident: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    for_standalone: bool,
+) -> TokenStream {
+    let db_ref = if for_standalone {
+        quote! { db }
+    } else {
+        quote! { &self.db }
+    };
+
+    // Drop the row from the search index once the delete commits
+    let search_event = if entity_options.is_some_and(|o| o.searchable) {
+        super::search::generate_delete_event(&entity_module.to_string())
+    } else {
+        quote! {}
+    };
+
+    let record_writes = records_writes(entity_options);
+    let (begin, conn, commit) = generate_write_transaction(&db_ref, record_writes);
+    let record_event = if record_writes {
+        generate_delete_record(entity_module, entity_options, &conn)
+    } else {
+        quote! {}
+    };
+
+    // The key is consumed by the statement, so a cached row is dropped by a copy
+    let (keep_id, uncache) = if super::row_cache::has_row_cache(entity_options) {
+        (
+            quote! { let cached_id = id.clone(); },
+            super::row_cache::generate_invalidate(entity_module, entity_options, quote! { cached_id }),
+        )
+    } else {
+        (quote! {}, quote! {})
+    };
+
+    if is_soft_delete(entity_options) {
+        // Soft delete: mark the row instead of removing it
+        let now = time_crate().now();
+        return quote! {
+            use sea_orm::sea_query::Expr;
+
+            let id = parse_key::<entities::#entity_module::Entity>(&request.id)
+            .ok_or_else(|| StorageError::InvalidArgument(format!("invalid id: {}", request.id)))?;
+            #begin
+            #keep_id
+            let result = entities::#entity_module::Entity::update_many()
+                .col_expr(
+                    entities::#entity_module::Column::DeletedAt,
+                    Expr::value(#now),
+                )
+                .filter(entities::#entity_module::Column::Id.eq(id))
+                .filter(entities::#entity_module::Column::DeletedAt.is_null())
+                .exec(#conn)
+                .await
+                .map_err(StorageError::Database)?;
+            #record_event
+            #commit
+            #uncache
+            #search_event
+
+            Ok(#response_ident {
+                success: result.rows_affected > 0,
+            })
+        };
+    }
+
+    quote! {
+        let id = parse_key::<entities::#entity_module::Entity>(&request.id)
+            .ok_or_else(|| StorageError::InvalidArgument(format!("invalid id: {}", request.id)))?;
+        #keep_id
+        #begin
+        let result = entities::#entity_module::Entity::delete_by_id(id)
+            .exec(#conn)
+            .await
+            .map_err(StorageError::Database)?;
+        #record_event
+        #commit
+        #uncache
+        #search_event
+
+        Ok(#response_ident {
+            success: result.rows_affected > 0,
+        })
+    }
+}
+
+/// Generate the `cond` of a bulk update or delete from the request's `filter`
+///
+/// `None` when the request has no filter field with supported fields. The
+/// generated code rejects a request without a filter, or whose filter sets no
+/// condition, rather than writing every row.
+pub fn generate_bulk_condition(
+    file: &FileDescriptorProto,
+    request_type: &str,
+    entity_module: &proc_macro2::Ident,
+    all_files: &[FileDescriptorProto],
+) -> Option<TokenStream> {
+    let request_msg = file
+        .message_type
+        .iter()
+        .find(|m| m.name.as_deref() == Some(request_type))?;
+    let field_conditions = generate_filter_conditions(request_msg, entity_module, all_files)?;
+    Some(quote! {
+        use sea_orm::Condition;
+
+        // A bulk write never applies to every row
+        let Some(ref filter) = request.filter else {
+            return Err(StorageError::InvalidArgument("filter is required".to_string()));
+        };
+        let mut cond = Condition::all();
+        #field_conditions
+        if cond.is_empty() {
+            return Err(StorageError::InvalidArgument("filter must set at least one condition".to_string()));
+        }
+    })
+}
+
+/// Generate the write of a bulk statement and the count of rows it affected
+///
+/// Entities recording outbox events or indexed for search, and responses
+/// listing the written keys, read the written rows back (`RETURNING`) as
+/// `models`; otherwise only the count is read.
+fn generate_bulk_exec(statement: TokenStream, conn: &TokenStream, returning: bool) -> TokenStream {
+    if returning {
+        quote! {
+            let models = #statement
+                .exec_with_returning(#conn)
+                .await
+                .map_err(StorageError::Database)?;
+            let affected = models.len() as u64;
+        }
+    } else {
+        quote! {
+            let affected = #statement
+                .exec(#conn)
+                .await
+                .map_err(StorageError::Database)?
+                .rows_affected;
+        }
+    }
+}
+
+/// Generate an UPDATE MANY implementation
+///
+/// Applies `request.patch` to every row matching `condition` (see
+/// [`generate_bulk_condition`]) with a single UPDATE and returns the number of
+/// rows written in `affected_field`, and their keys in `ids_field` when the
+/// response has one. The patch is validated into `patch_domain` first when it
+/// has a domain type. Soft-deleted rows are left alone. If `for_standalone` is
+/// true, generates code using `db` parameter instead of `self.db`
+#[allow(clippy::too_many_arguments)]
+pub fn generate_update_many_impl(
+    entity_module: &proc_macro2::Ident,
+    response_ident: &proc_macro2::Ident,
+    affected_field: &proc_macro2::Ident,
+    ids_field: Option<&proc_macro2::Ident>,
+    condition: &TokenStream,
+    patch_domain: Option<&proc_macro2::Ident>,
+    entity_options: Option<&storage::EntityOptions>,
+    for_standalone: bool,
+) -> TokenStream {
+    let db_ref = if for_standalone {
+        quote! { db }
+    } else {
+        quote! { &self.db }
+    };
+
+    let not_deleted = generate_not_deleted_filter(entity_module, entity_options);
+
+    let patch = match patch_domain {
+        Some(domain) => quote! {
+            let patch = #domain::try_from(request.patch.unwrap_or_default())
+                .map_err(|e| StorageError::InvalidArgument(format!("patch: {}", e)))?;
+        },
+        None => quote! {
+            let patch = request.patch.unwrap_or_default();
+        },
+    };
+
+    let bump_updated_at = if has_managed_timestamps(entity_options) {
+        let now = time_crate().now();
+        quote! { active_model.updated_at = Set(#now); }
+    } else {
+        quote! {}
+    };
+
+    let record_writes = records_writes(entity_options);
+    let searchable = entity_options.is_some_and(|o| o.searchable);
+    let (begin, conn, commit) = generate_write_transaction(&db_ref, record_writes);
+    let write = generate_bulk_exec(
+        quote! {
+            entities::#entity_module::Entity::update_many()
+                .set(active_model)
+                .filter(cond)
+                #not_deleted
+        },
+        &conn,
+        record_writes || searchable || ids_field.is_some(),
+    );
+    let written_ids = generate_written_ids(ids_field);
+    let record_events = if record_writes {
+        let record = generate_record(entity_module, entity_options, "Updated", &conn);
+        quote! {
+            for model in &models {
+                #record
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let uncache = super::row_cache::generate_clear(entity_module, entity_options);
+    let search_events = if searchable {
+        super::search::generate_bulk_events(&entity_module.to_string(), false)
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        #condition
+
+        #patch
+        let mut active_model = entities::#entity_module::ActiveModel::default();
+        active_model.apply_update(&patch);
+        if !active_model.is_changed() {
+            return Err(StorageError::InvalidArgument("patch must set at least one field".to_string()));
+        }
+        #bump_updated_at
+
+        // One UPDATE for every matching row
+        #begin
+        #write
+        #record_events
+        #commit
+        #uncache
+        #search_events
+
+        Ok(#response_ident {
+            #affected_field: affected as _,
+            #written_ids
+        })
+    }
+}
+
+/// Response field initializer listing the keys of the rows a bulk write returned
+fn generate_written_ids(ids_field: Option<&proc_macro2::Ident>) -> TokenStream {
+    match ids_field {
+        Some(ids) => quote! { #ids: models.iter().map(|m| m.id.to_string()).collect(), },
+        None => quote! {},
+    }
+}
+
+/// Generate a DELETE MANY implementation
+///
+/// Deletes every row matching `condition` (see [`generate_bulk_condition`])
+/// with a single DELETE, or a single UPDATE of `deleted_at` for soft-deleted
+/// entities, and returns the number of rows removed in `affected_field` and
+/// their keys in `ids_field` when the response has one. If `for_standalone`
+/// is true, generates code using `db` parameter instead of `self.db`
+pub fn generate_delete_many_impl(
+    entity_module: &proc_macro2::Ident,
+    response_ident: &proc_macro2::Ident,
+    affected_field: &proc_macro2::Ident,
+    ids_field: Option<&proc_macro2::Ident>,
+    condition: &TokenStream,
+    entity_options: Option<&storage::EntityOptions>,
+    for_standalone: bool,
+) -> TokenStream {
+    let db_ref = if for_standalone {
+        quote! { db }
+    } else {
+        quote! { &self.db }
+    };
+
+    let statement = if is_soft_delete(entity_options) {
+        let now = time_crate().now();
+        quote! {
+            entities::#entity_module::Entity::update_many()
+                .col_expr(
+                    entities::#entity_module::Column::DeletedAt,
+                    sea_orm::sea_query::Expr::value(#now),
+                )
+                .filter(cond)
+                .filter(entities::#entity_module::Column::DeletedAt.is_null())
+        }
+    } else {
+        quote! {
+            entities::#entity_module::Entity::delete_many().filter(cond)
+        }
+    };
+
+    let record_writes = records_writes(entity_options);
+    let searchable = entity_options.is_some_and(|o| o.searchable);
+    let (begin, conn, commit) = generate_write_transaction(&db_ref, record_writes);
+    let write = generate_bulk_exec(statement, &conn, record_writes || searchable || ids_field.is_some());
+    let written_ids = generate_written_ids(ids_field);
+    let record_events = if record_writes {
+        generate_bulk_delete_record(entity_module, entity_options, &conn)
+    } else {
+        quote! {}
+    };
+    let uncache = super::row_cache::generate_clear(entity_module, entity_options);
+    let search_events = if searchable {
+        super::search::generate_bulk_events(&entity_module.to_string(), true)
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        #condition
+
+        // One statement for every matching row
+        #begin
+        #write
+        #record_events
+        #commit
+        #uncache
+        #search_events
+
+        Ok(#response_ident {
+            #affected_field: affected as _,
+            #written_ids
+        })
+    }
+}
+
+
+/// Generate a RESTORE implementation for soft-deleted entities
+///
+/// Clears `deleted_at` on a deleted row and returns the restored entity.
+/// If `for_standalone` is true, generates code using `db` parameter instead of `self.db`
+pub fn generate_restore_impl(
+    entity_module: &proc_macro2::Ident,
+    response_ident: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    for_standalone: bool,
+) -> TokenStream {
+    // Derive the entity type name (e.g., user -> User)
+    let entity_type = format_ident!("{}", entity_module.to_string().to_upper_camel_case());
+
+    let db_ref = if for_standalone {
+        quote! { db }
+    } else {
+        quote! { &self.db }
+    };
+
+    if !is_soft_delete(entity_options) {
+        return quote! {
+            Err(StorageError::InvalidArgument(format!("{} does not support soft delete", stringify!(#entity_type))))
+        };
+    }
+
+    let record_writes = records_writes(entity_options);
+    let (begin, conn, commit) = generate_write_transaction(&db_ref, record_writes);
+    let record_event = if record_writes {
+        generate_record(entity_module, entity_options, "Updated", &conn)
+    } else {
+        quote! {}
+    };
+
+    let uncache = super::row_cache::generate_invalidate(entity_module, entity_options, quote! { model.id });
+    let index_events = generate_index_events(entity_module, entity_options, quote! { std::iter::once(&model) });
+
+    quote! {
+        use sea_orm::IntoActiveModel;
+
+        let id = parse_key::<entities::#entity_module::Entity>(&request.id)
+            .ok_or_else(|| StorageError::InvalidArgument(format!("invalid id: {}", request.id)))?;
+        #begin
+        let model = entities::#entity_module::Entity::find_by_id(id)
+            .filter(entities::#entity_module::Column::DeletedAt.is_not_null())
+            .one(#conn)
+            .await
+            .map_err(StorageError::Database)?
+            .ok_or_else(|| StorageError::NotFound(format!("deleted {} with id {} not found", stringify!(#entity_type), request.id)))?;
+
+        let mut active_model = model.into_active_model();
+        active_model.deleted_at = Set(None);
+        let model = active_model.update(#conn).await.map_err(StorageError::Database)?;
+        #record_event
+        #commit
+        #uncache
+        #index_events
+
+        Ok(#response_ident {
+            #entity_module: Some(model.into()),
+        })
+    }
+}
+
+/// Columns set by storage when an entity has `timestamps: true`
+pub const MANAGED_TIMESTAMPS: &[&str] = &["created_at", "updated_at"];
+
+/// Create request type held by a create-many request's `items` field
+///
+/// `CreateManyPostsRequest { repeated CreatePostRequest items = 1; }` gives
+/// `CreatePostRequest`.
+pub fn create_many_item_type(request: &DescriptorProto) -> Option<String> {
+    request
+        .field
+        .iter()
+        .find(|f| f.name.as_deref() == Some("items") && f.r#type() == Type::Message)
+        .and_then(|f| f.type_name.as_deref())
+        .map(|t| t.rsplit('.').next().unwrap_or(t).to_string())
+}
+
+/// Patch type held by an update-many request's `patch` field
+///
+/// `UpdateManyPostsRequest { PostFilter filter = 1; PostPatch patch = 2; }`
+/// gives `PostPatch`.
+pub fn update_many_patch_type(request: &DescriptorProto) -> Option<String> {
+    request
+        .field
+        .iter()
+        .find(|f| f.name.as_deref() == Some("patch") && f.r#type() == Type::Message)
+        .and_then(|f| f.type_name.as_deref())
+        .map(|t| t.rsplit('.').next().unwrap_or(t).to_string())
+}
+
+/// Database-generated (`generated_expr`) columns of an entity message
+///
+/// Storage never writes these, so request conversions leave them out.
+pub fn generated_columns(file_name: &str, message: &DescriptorProto) -> Vec<String> {
+    let msg_name = message.name.as_deref().unwrap_or("");
+    message
+        .field
+        .iter()
+        .filter(|field| {
+            get_cached_column_options(file_name, msg_name, field.number.unwrap_or(0))
+                .is_some_and(|o| !o.generated_expr.is_empty())
+        })
+        .map(|field| field.name.as_deref().unwrap_or("").to_snake_case())
+        .collect()
+}
+
+/// Check whether storage manages `created_at`/`updated_at` for an entity
+pub fn has_managed_timestamps(entity_options: Option<&storage::EntityOptions>) -> bool {
+    entity_options.map(|o| o.timestamps).unwrap_or(false)
+}
+
+/// Check whether a column is a storage-generated UUID v7 primary key
+pub fn is_uuid_v7_key(column: &storage::ColumnOptions) -> bool {
+    column.primary_key && column.id_kind() == storage::IdKind::UuidV7
+}
+
+/// Check whether a column is a storage-generated ULID primary key
+pub fn is_ulid_key(column: &storage::ColumnOptions) -> bool {
+    column.primary_key && column.id_kind() == storage::IdKind::Ulid
+}
+
+/// Check whether a timestamp column is stored without a time zone
+pub fn is_naive_timestamp(column: &storage::ColumnOptions) -> bool {
+    column.timezone() == storage::Timezone::Naive
+}
+
+/// `timezone: NAIVE` columns of an entity (by message name)
+pub fn naive_timestamp_columns(all_files: &[FileDescriptorProto], entity: &str) -> Vec<String> {
+    all_files
+        .iter()
+        .flat_map(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.message_type
+                .iter()
+                .filter(|m| m.name.as_deref() == Some(entity))
+                .flat_map(|m| m.field.iter())
+                .filter(move |field| {
+                    get_cached_column_options(file_name, entity, field.number.unwrap_or(0))
+                        .or_else(|| parse_column_options(field))
+                        .is_some_and(|o| is_naive_timestamp(&o))
+                })
+        })
+        .map(|field| field.name.as_deref().unwrap_or("").to_snake_case())
+        .collect()
+}
+
+/// Proto type of geography point fields
+pub const GEO_POINT_TYPE: &str = ".synapse.relay.GeoPoint";
+
+/// PostGIS `geography` columns of an entity (by message name)
+pub fn geography_columns(all_files: &[FileDescriptorProto], entity: &str) -> Vec<String> {
+    all_files
+        .iter()
+        .flat_map(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.message_type
+                .iter()
+                .filter(|m| m.name.as_deref() == Some(entity))
+                .flat_map(|m| m.field.iter())
+                .filter(move |field| {
+                    get_cached_column_options(file_name, entity, field.number.unwrap_or(0))
+                        .or_else(|| parse_column_options(field))
+                        .is_some_and(|o| o.geography)
+                })
+        })
+        .map(|field| field.name.as_deref().unwrap_or("").to_snake_case())
+        .collect()
+}
+
+/// Generated column holding an entity's full-text search document
+pub const SEARCH_VECTOR_COLUMN: &str = "search_vector";
+
+/// Postgres text search configuration for `searchable` columns
+pub const SEARCH_CONFIG: &str = "english";
+
+/// Full-text `searchable` fields of an entity (by message name), with their options
+pub fn searchable_fields<'a>(
+    all_files: &'a [FileDescriptorProto],
+    entity: &'a str,
+) -> Vec<(&'a FieldDescriptorProto, storage::ColumnOptions)> {
+    all_files
+        .iter()
+        .flat_map(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.message_type
+                .iter()
+                .filter(move |m| m.name.as_deref() == Some(entity))
+                .flat_map(|m| m.field.iter())
+                .filter_map(move |field| {
+                    get_cached_column_options(file_name, entity, field.number.unwrap_or(0))
+                        .or_else(|| parse_column_options(field))
+                        .filter(|o| o.searchable)
+                        .map(|o| (field, o))
+                })
+        })
+        .collect()
+}
+
+/// Database columns of an entity's full-text `searchable` fields, honoring `column_name`
+pub fn searchable_columns(all_files: &[FileDescriptorProto], entity: &str) -> Vec<String> {
+    searchable_fields(all_files, entity)
+        .into_iter()
+        .map(|(field, options)| {
+            if options.column_name.is_empty() {
+                field.name.as_deref().unwrap_or("").to_snake_case()
+            } else {
+                options.column_name
+            }
+        })
+        .collect()
+}
+
+/// Database column of an entity's field (by message and field name), honoring `column_name`
+pub fn column_name(all_files: &[FileDescriptorProto], entity: &str, field_name: &str) -> String {
+    let snake = field_name.to_snake_case();
+    all_files
+        .iter()
+        .find_map(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            let field = f
+                .message_type
+                .iter()
+                .filter(|m| m.name.as_deref() == Some(entity))
+                .flat_map(|m| m.field.iter())
+                .find(|field| field.name.as_deref().map(|n| n.to_snake_case()) == Some(snake.clone()))?;
+            get_cached_column_options(file_name, entity, field.number.unwrap_or(0))
+                .or_else(|| parse_column_options(field))
+                .map(|o| o.column_name)
+                .filter(|name| !name.is_empty())
+        })
+        .unwrap_or(snake)
+}
+
+/// Case-insensitive comparison of a string column
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaseInsensitive {
+    /// Postgres `citext` column
+    Citext,
+    /// Column with a case-insensitive collation
+    Collation(String),
+}
+
+/// How a column compares case-insensitively, if it does
+pub fn case_insensitive(column: &storage::ColumnOptions) -> Option<CaseInsensitive> {
+    if column.citext {
+        Some(CaseInsensitive::Citext)
+    } else if !column.collation.is_empty() {
+        Some(CaseInsensitive::Collation(column.collation.clone()))
+    } else {
+        None
+    }
+}
+
+/// Case-insensitive string columns of an entity (by message name)
+pub fn case_insensitive_columns(
+    all_files: &[FileDescriptorProto],
+    entity: &str,
+) -> Vec<(String, CaseInsensitive)> {
+    all_files
+        .iter()
+        .flat_map(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.message_type
+                .iter()
+                .filter(|m| m.name.as_deref() == Some(entity))
+                .flat_map(|m| m.field.iter())
+                .filter_map(move |field| {
+                    let column = get_cached_column_options(file_name, entity, field.number.unwrap_or(0))
+                        .or_else(|| parse_column_options(field))?;
+                    let name = field.name.as_deref().unwrap_or("").to_snake_case();
+                    case_insensitive(&column).map(|ci| (name, ci))
+                })
+        })
+        .collect()
+}
+
+/// Key generation strategy of an entity's (by message name) primary key
+pub fn primary_key_kind(all_files: &[FileDescriptorProto], entity: &str) -> storage::IdKind {
+    all_files
+        .iter()
+        .flat_map(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.message_type
+                .iter()
+                .filter(|m| m.name.as_deref() == Some(entity))
+                .flat_map(|m| m.field.iter())
+                .filter_map(move |field| {
+                    get_cached_column_options(file_name, entity, field.number.unwrap_or(0))
+                        .or_else(|| parse_column_options(field))
+                })
+        })
+        .find(|o| o.primary_key)
+        .map(|o| o.id_kind())
+        .unwrap_or(storage::IdKind::Unspecified)
+}
+
+/// Check whether an entity (by message name) has a UUID v7 primary key
+pub fn has_uuid_primary_key(all_files: &[FileDescriptorProto], entity: &str) -> bool {
+    primary_key_kind(all_files, entity) == storage::IdKind::UuidV7
+}
+
+/// Check whether an entity (by message name) has a ULID primary key
+pub fn has_ulid_primary_key(all_files: &[FileDescriptorProto], entity: &str) -> bool {
+    primary_key_kind(all_files, entity) == storage::IdKind::Ulid
+}
+
+/// Check whether an entity's primary key is generated by storage and carried
+/// as a string in protos (UUID v7 or ULID)
+pub fn has_string_primary_key(all_files: &[FileDescriptorProto], entity: &str) -> bool {
+    matches!(
+        primary_key_kind(all_files, entity),
+        storage::IdKind::UuidV7 | storage::IdKind::Ulid
+    )
+}
+
+/// A column stamped from the authenticated principal (`from_context`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextColumn {
+    /// Proto field name
+    pub name: String,
+    /// Fields below `current_user`, e.g. `["id"]` for "current_user.id"
+    pub path: Vec<String>,
+    pub on_create: bool,
+    pub on_update: bool,
+}
+
+/// Columns of an entity (by message name in `package`) filled from the principal
+///
+/// Only `current_user.*` paths are supported; the gRPC service reads
+/// `CurrentUser` from the request extensions.
+pub fn context_columns(
+    all_files: &[FileDescriptorProto],
+    package: &str,
+    entity: &str,
+) -> Result<Vec<ContextColumn>, GeneratorError> {
+    let mut columns = Vec::new();
+    for file in all_files.iter().filter(|f| f.package.as_deref().unwrap_or("") == package) {
+        let file_name = file.name.as_deref().unwrap_or("");
+        let Some(message) = file.message_type.iter().find(|m| m.name.as_deref() == Some(entity))
+        else {
+            continue;
+        };
+        for field in &message.field {
+            let Some(opts) = get_cached_column_options(file_name, entity, field.number.unwrap_or(0))
+            else {
+                continue;
+            };
+            if opts.from_context.is_empty() {
+                continue;
+            }
+            let name = field.name.as_deref().unwrap_or("");
+            let path: Vec<String> = match opts.from_context.split_once('.') {
+                Some(("current_user", rest)) => rest.split('.').map(str::to_string).collect(),
+                _ => {
+                    return Err(GeneratorError::CodeGenError(format!(
+                        "{}.{}: from_context must be a current_user path, got {:?}",
+                        entity, name, opts.from_context
+                    )))
+                }
+            };
+            let stamps: Vec<_> = opts.on().collect();
+            let both = stamps.is_empty();
+            columns.push(ContextColumn {
+                name: name.to_string(),
+                path,
+                on_create: both || stamps.contains(&storage::ContextStamp::Create),
+                on_update: both || stamps.contains(&storage::ContextStamp::Update),
+            });
+        }
+    }
+    Ok(columns)
+}
+
+/// Check whether an entity uses soft delete
+fn is_soft_delete(entity_options: Option<&storage::EntityOptions>) -> bool {
+    entity_options.map(|o| o.soft_delete).unwrap_or(false)
+}
+
+/// Check whether writes to an entity record outbox or webhook events
+fn records_writes(entity_options: Option<&storage::EntityOptions>) -> bool {
+    entity_options.is_some_and(|o| o.emit_events || o.webhooks)
+}
+
+/// Generate the outbox and webhook events of a write of `model` on `conn`
+fn generate_record(
+    entity_module: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    event: &str,
+    conn: &TokenStream,
+) -> TokenStream {
+    let outbox = entity_options
+        .filter(|o| o.emit_events)
+        .map(|_| super::outbox::generate_record(entity_module, event, conn));
+    let webhook = entity_options
+        .filter(|o| o.webhooks)
+        .map(|_| super::webhook::generate_record(entity_module, event, conn));
+    quote! { #outbox #webhook }
+}
+
+/// Generate the outbox and webhook events of a delete of `request.id` on `conn`
+fn generate_delete_record(
+    entity_module: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    conn: &TokenStream,
+) -> TokenStream {
+    let outbox = entity_options
+        .filter(|o| o.emit_events)
+        .map(|_| super::outbox::generate_delete_record(entity_module, conn));
+    let webhook = entity_options
+        .filter(|o| o.webhooks)
+        .map(|_| super::webhook::generate_delete_record(entity_module, conn));
+    quote! { #outbox #webhook }
+}
+
+/// Generate the outbox and webhook events of a bulk delete of `models` on `conn`
+fn generate_bulk_delete_record(
+    entity_module: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    conn: &TokenStream,
+) -> TokenStream {
+    let outbox = entity_options
+        .filter(|o| o.emit_events)
+        .map(|_| super::outbox::generate_bulk_delete_record(entity_module, conn));
+    let webhook = entity_options
+        .filter(|o| o.webhooks)
+        .map(|_| super::webhook::generate_bulk_delete_record(entity_module, conn));
+    quote! { #outbox #webhook }
+}
+
+/// Generate a `.filter(...)` excluding soft-deleted rows, or nothing
+fn generate_not_deleted_filter(
+    entity_module: &proc_macro2::Ident,
+    entity_options: Option<&storage::EntityOptions>,
+) -> TokenStream {
+    if is_soft_delete(entity_options) {
+        quote! { .filter(entities::#entity_module::Column::DeletedAt.is_null()) }
+    } else {
+        quote! {}
+    }
+}
+
+/// Upper bound on tree depth walked by recursive queries
+///
+/// Guards against runaway recursion when the data contains a cycle.
+const MAX_RECURSION_DEPTH: u32 = 256;
+
+/// Condition keeping rows of the recursive queries that aren't soft-deleted
+fn not_deleted_sql(deleted_column: Option<&str>) -> String {
+    deleted_column
+        .map(|column| format!(" AND t.{} IS NULL", column))
+        .unwrap_or_default()
+}
+
+/// Generate an ANCESTORS implementation for a recursive self-referential relation
+///
+/// Walks up the `foreign_key` column chain from the given id with a recursive
+/// CTE and returns every ancestor, nearest first. The starting row is not
+/// included. Columns are database names; with `deleted_column`, the walk
+/// stops at soft-deleted rows.
+pub fn generate_ancestors_impl(
+    entity_module: &proc_macro2::Ident,
+    foreign_key: &str,
+    references: &str,
+    deleted_column: Option<&str>,
+) -> TokenStream {
+    let sql = format!(
+        "WITH RECURSIVE ancestors AS (\
+            SELECT t.*, 1 AS depth FROM {{table}} t \
+            WHERE t.{pk} = (SELECT s.{fk} FROM {{table}} s WHERE s.{pk} = {{param}}){live} \
+            UNION ALL \
+            SELECT t.*, a.depth + 1 FROM {{table}} t INNER JOIN ancestors a ON t.{pk} = a.{fk} \
+            WHERE a.depth < {max}{live}\
+        ) SELECT * FROM ancestors ORDER BY depth",
+        pk = references,
+        fk = foreign_key,
+        max = MAX_RECURSION_DEPTH,
+        live = not_deleted_sql(deleted_column),
+    );
+
+    generate_recursive_query(entity_module, &sql)
+}
+
+/// Generate a DESCENDANTS implementation for a recursive self-referential relation
+///
+/// Walks down the `foreign_key` column chain from the given id with a
+/// recursive CTE and returns every descendant, breadth first. The starting
+/// row is not included. Columns are database names; with `deleted_column`,
+/// soft-deleted rows and their subtrees are left out.
+pub fn generate_descendants_impl(
+    entity_module: &proc_macro2::Ident,
+    foreign_key: &str,
+    references: &str,
+    deleted_column: Option<&str>,
+) -> TokenStream {
+    let sql = format!(
+        "WITH RECURSIVE descendants AS (\
+            SELECT t.*, 1 AS depth FROM {{table}} t WHERE t.{fk} = {{param}}{live} \
+            UNION ALL \
+            SELECT t.*, d.depth + 1 FROM {{table}} t INNER JOIN descendants d ON t.{fk} = d.{pk} \
+            WHERE d.depth < {max}{live}\
+        ) SELECT * FROM descendants ORDER BY depth, {pk}",
+        pk = references,
+        fk = foreign_key,
+        max = MAX_RECURSION_DEPTH,
+        live = not_deleted_sql(deleted_column),
+    );
+
+    generate_recursive_query(entity_module, &sql)
+}
+
+/// Generate the shared body for recursive CTE queries
+///
+/// The SQL template uses `{table}` and `{param}` placeholders which are filled
+/// in at runtime from the entity's table name and the connection's backend.
+fn generate_recursive_query(entity_module: &proc_macro2::Ident, sql: &str) -> TokenStream {
+    quote! {
+        use sea_orm::{ConnectionTrait, DbBackend, EntityName, Statement};
+
+        let backend = db.get_database_backend();
+        let param = match backend {
+            DbBackend::Postgres => "$1",
+            _ => "?",
+        };
+        let sql = #sql
+            .replace("{table}", entities::#entity_module::Entity.table_name())
+            .replace("{param}", param);
+
+        let models = entities::#entity_module::Entity::find()
+            .from_raw_sql(Statement::from_sql_and_values(backend, sql, [id.into()]))
+            .all(db)
+            .await
+            .map_err(StorageError::Database)?;
+
+        Ok(models.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_streams_rows() {
+        let file = FileDescriptorProto::default();
+        let entity = format_ident!("post");
+        let response = format_ident!("PostConnection");
+
+        let collected = generate_list_impl(&file, "ListPostsRequest", &entity, &response, None, &[], true, false).to_string();
+        assert!(collected.contains(". all (db)"));
+        assert!(!collected.contains(". stream (db)"));
+
+        let streamed = generate_list_impl(&file, "ListPostsRequest", &entity, &response, None, &[], true, true).to_string();
+        assert!(streamed.contains(". stream (db)"));
+        assert!(streamed.contains("edges . push (PostEdge {"));
+    }
+
+    #[test]
+    fn test_recursive_queries_skip_deleted_rows() {
+        let entity = format_ident!("category");
+        for code in [
+            generate_ancestors_impl(&entity, "parent_id", "id", None),
+            generate_descendants_impl(&entity, "parent_id", "id", None),
+        ] {
+            assert!(!code.to_string().contains("IS NULL"));
+        }
+
+        let ancestors = generate_ancestors_impl(&entity, "parent_id", "id", Some("deleted_at")).to_string();
+        assert!(ancestors.contains("WHERE s.id = {param}) AND t.deleted_at IS NULL UNION ALL"));
+        assert!(ancestors.contains("WHERE a.depth < 256 AND t.deleted_at IS NULL)"));
+        let descendants = generate_descendants_impl(&entity, "parent_id", "id", Some("deleted_at")).to_string();
+        assert!(descendants.contains("WHERE t.parent_id = {param} AND t.deleted_at IS NULL UNION ALL"));
+        assert!(descendants.contains("WHERE d.depth < 256 AND t.deleted_at IS NULL)"));
+    }
+
+    #[test]
+    fn test_searchable_columns_use_column_name() {
+        use crate::storage::seaorm::options::cache_column_options;
+        use crate::test_util::field;
+
+        let file = FileDescriptorProto {
+            name: Some("search_column_test/entities.proto".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Article".to_string()),
+                field: vec![field("id", 1, Type::Int64), field("title", 2, Type::String), field("body", 3, Type::String)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let searchable = |column_name: &str| storage::ColumnOptions {
+            searchable: true,
+            column_name: column_name.to_string(),
+            ..Default::default()
+        };
+        cache_column_options("search_column_test/entities.proto", "Article", 2, searchable(""));
+        cache_column_options("search_column_test/entities.proto", "Article", 3, searchable("body_text"));
+
+        assert_eq!(searchable_columns(&[file], "Article"), ["title", "body_text"]);
+    }
+
+    #[test]
+    fn test_list_search_cursor_carries_rank() {
+        use crate::storage::seaorm::options::cache_column_options;
+        use crate::test_util::field;
+
+        let file = FileDescriptorProto {
+            name: Some("search_cursor_test/blog.proto".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("Note".to_string()),
+                    field: vec![field("id", 1, Type::Int64), field("title", 2, Type::String)],
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("ListNotesRequest".to_string()),
+                    field: vec![
+                        field("first", 1, Type::Int32),
+                        field("after", 2, Type::String),
+                        field("search", 3, Type::String),
+                    ],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        cache_column_options(
+            "search_cursor_test/blog.proto",
+            "Note",
+            2,
+            storage::ColumnOptions { searchable: true, ..Default::default() },
+        );
+        let all_files = std::slice::from_ref(&file);
+        let entity = format_ident!("note");
+        let response = format_ident!("NoteConnection");
+
+        for stream_rows in [false, true] {
+            let code = generate_list_impl(&file, "ListNotesRequest", &entity, &response, None, all_files, true, stream_rows)
+                .to_string();
+            assert!(code.contains(". column_as (rank_column , \"search_rank\") . into_model :: < Ranked > ()"));
+            assert!(code.contains("format ! (\"{}:{}\" , rank , m . id)"));
+            assert!(code.contains("after . split_once (':') . filter (| _ | ranked)"));
+            assert!(code.contains("ts_rank(search_vector, websearch_to_tsquery('english', ?)) < ?"));
+            assert!(code.contains("Column :: Id . gt (cursor_id)"));
+        }
+        let code = generate_list_encoded_impl(&file, "ListNotesRequest", &entity, None, all_files, 1, 2).to_string();
+        assert!(code.contains(". column_as (rank_column , \"search_rank\") . into_model :: < Ranked > ()"));
+        assert!(code.contains("format ! (\"{}:{}\" , rank , m . id)"));
+
+        // Without a search field, cursors stay plain ids
+        let code = generate_list_impl(&file, "ListNotesRequest", &entity, &response, None, &[], true, false).to_string();
+        assert!(!code.contains("Ranked"));
+        assert!(code.contains("let cursor = m . id . to_string () ;"));
+    }
+
+    #[test]
+    fn test_create_many_single_insert() {
+        let entity = format_ident!("post");
+        let response = format_ident!("CreateManyPostsResponse");
+        let posts = format_ident!("posts");
+
+        let validated = generate_create_many_impl(&entity, &response, &posts, Some(&format_ident!("CreatePost")), None, &[], None, true).to_string();
+        assert!(validated.contains("let item = CreatePost :: try_from (item)"));
+        assert!(validated.contains("entities :: post :: Entity :: insert_many (active_models) . exec_with_returning_many (db)"));
+        assert!(validated.contains("posts : models . into_iter () . map (Into :: into) . collect ()"));
+
+        let raw = generate_create_many_impl(&entity, &response, &posts, None, None, &[], None, false).to_string();
+        assert!(raw.contains("for item in request . items {"));
+        assert!(!raw.contains("try_from"));
+    }
+
+    #[test]
+    fn test_create_many_with_batch_errors() {
+        let entity = format_ident!("post");
+        let response = format_ident!("CreateManyPostsResponse");
+        let posts = format_ident!("posts");
+        let domain = format_ident!("CreatePost");
+        let mut batch = BatchErrors { field: format_ident!("errors"), atomic: false };
+
+        let partial = generate_create_many_impl(&entity, &response, &posts, Some(&domain), None, &[], Some(&batch), true).to_string();
+        assert!(partial.contains("batch_errors . push (batch_error (index , error . into ()))"));
+        assert!(partial.contains("active_model . insert (db)"));
+        assert!(!partial.contains("insert_many"));
+
+        batch.atomic = true;
+        let atomic = generate_create_many_impl(&entity, &response, &posts, Some(&domain), None, &[], Some(&batch), true).to_string();
+        assert!(atomic.contains("\"ABORTED\""));
+        assert!(atomic.contains("insert_many (active_models . clone ())"));
+
+        // A failed INSERT is retried row by row, in savepoints of a rolled-back
+        // transaction, to report the rejected rows
+        assert!(atomic.contains("let savepoint = txn . begin () . await"));
+        assert!(atomic.contains("Err (e) => batch_errors . push (batch_error (index , (StorageError :: Database) (e) . into ()))"));
+        assert!(atomic.contains("txn . rollback () . await"));
+        assert!(atomic.contains("if batch_errors . is_empty () { return Err (error) ; }"));
+
+        // Unique fields map violations to ALREADY_EXISTS
+        let unique = [UniqueField { field: "slug".to_string(), optional: false }];
+        let atomic = generate_create_many_impl(&entity, &response, &posts, Some(&domain), None, &unique, Some(&batch), true).to_string();
+        assert!(atomic.contains("Some (sea_orm :: SqlErr :: UniqueConstraintViolation (detail))"));
+    }
+
+    #[test]
+    fn test_bulk_condition_requires_filter() {
+        use crate::test_util::message_field;
+
+        let message = |name: &str, fields: Vec<FieldDescriptorProto>| DescriptorProto {
+            name: Some(name.to_string()),
+            field: fields,
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            message_type: vec![
+                message("PostFilter", vec![message_field("published", 1, ".synapse.relay.BoolFilter")]),
+                message("DeleteManyPostsRequest", vec![message_field("filter", 1, ".blog.PostFilter")]),
+                message("PublishPostsRequest", vec![]),
+            ],
+            ..Default::default()
+        };
+        let entity = format_ident!("post");
+
+        let code = generate_bulk_condition(&file, "DeleteManyPostsRequest", &entity, std::slice::from_ref(&file))
+            .unwrap()
+            .to_string();
+        assert!(code.contains("let Some (ref filter) = request . filter else"));
+        assert!(code.contains("cond = cond . add (entities :: post :: Column :: Published . eq (v))"));
+        assert!(code.contains("if cond . is_empty ()"));
+
+        assert!(generate_bulk_condition(&file, "PublishPostsRequest", &entity, std::slice::from_ref(&file)).is_none());
+    }
+
+    #[test]
+    fn test_update_many_single_statement() {
+        let entity = format_ident!("post");
+        let response = format_ident!("UpdateManyPostsResponse");
+        let affected = format_ident!("affected");
+        let condition = quote! { let cond = Condition::all(); };
+
+        let code = generate_update_many_impl(&entity, &response, &affected, None, &condition, None, None, true).to_string();
+        assert!(code.contains("let patch = request . patch . unwrap_or_default ()"));
+        assert!(code.contains("entities :: post :: Entity :: update_many () . set (active_model) . filter (cond) . exec (db)"));
+        assert!(code.contains("affected : affected as _"));
+        assert!(!code.contains("exec_with_returning"));
+
+        let options = storage::EntityOptions { soft_delete: true, emit_events: true, ..Default::default() };
+        let domain = format_ident!("PostPatchValidated");
+        let code = generate_update_many_impl(&entity, &response, &affected, None, &condition, Some(&domain), Some(&options), true).to_string();
+        assert!(code.contains("PostPatchValidated :: try_from (request . patch . unwrap_or_default ())"));
+        assert!(code.contains(". filter (entities :: post :: Column :: DeletedAt . is_null ()) . exec_with_returning (& txn)"));
+        assert!(code.contains("\"PostUpdated\""));
+    }
+
+    #[test]
+    fn test_delete_many_single_statement() {
+        let entity = format_ident!("post");
+        let response = format_ident!("DeleteManyPostsResponse");
+        let affected = format_ident!("affected");
+        let condition = quote! { let cond = Condition::all(); };
+
+        let hard = generate_delete_many_impl(&entity, &response, &affected, None, &condition, None, true).to_string();
+        assert!(hard.contains("entities :: post :: Entity :: delete_many () . filter (cond) . exec (db)"));
+
+        let options = storage::EntityOptions {
+            soft_delete: true,
+            row_cache: Some(storage::RowCache { size: 8, ttl_seconds: 30 }),
+            ..Default::default()
+        };
+        let soft = generate_delete_many_impl(&entity, &response, &affected, None, &condition, Some(&options), true).to_string();
+        assert!(soft.contains("entities :: post :: Entity :: update_many () . col_expr (entities :: post :: Column :: DeletedAt"));
+        assert!(soft.contains("entities :: post :: ROW_CACHE . clear ()"));
+        assert!(!soft.contains("delete_many"));
+
+        let ids = format_ident!("ids");
+        let listed = generate_delete_many_impl(&entity, &response, &affected, Some(&ids), &condition, None, true).to_string();
+        assert!(listed.contains("delete_many () . filter (cond) . exec_with_returning (db)"));
+        assert!(listed.contains("ids : models . iter () . map (| m | m . id . to_string ()) . collect ()"));
+    }
+
+    #[test]
+    fn test_get_many_keeps_id_order() {
+        let entity = format_ident!("post");
+        let response = format_ident!("GetPostsByIdsResponse");
+        let posts = format_ident!("posts");
+
+        let code = generate_get_many_impl(&entity, &response, &posts, None, true).to_string();
+        assert!(code.contains("Column :: Id . is_in (keys . clone ())"));
+        assert!(code.contains(". all (db)"));
+        assert!(code.contains("posts : keys . iter () . filter_map (| key | by_id . get (& key . to_string ()) . cloned ())"));
+    }
+
+    #[test]
+    fn test_get_with_row_cache() {
+        let entity = format_ident!("organization");
+        let response = format_ident!("GetOrganizationResponse");
+        let options = storage::EntityOptions {
+            row_cache: Some(storage::RowCache { size: 8, ttl_seconds: 30 }),
+            ..Default::default()
+        };
+
+        let get = generate_get_impl(&entity, &response, Some(&options), None, true).to_string();
+        assert!(get.contains("match entities :: organization :: ROW_CACHE . get (& id)"));
+        assert!(get.contains("entities :: organization :: ROW_CACHE . insert (id , model . clone () , version)"));
+
+        let delete = generate_delete_impl(&entity, &format_ident!("DeleteOrganizationResponse"), Some(&options), true).to_string();
+        assert!(delete.contains("let cached_id = id . clone ()"));
+        assert!(delete.contains("ROW_CACHE . invalidate (& cached_id)"));
+
+        let uncached = generate_get_impl(&entity, &response, None, None, true).to_string();
+        assert!(!uncached.contains("ROW_CACHE"));
+    }
+
+    #[test]
+    fn test_upsert_on_conflict() {
+        let entity = format_ident!("author");
+        let response = format_ident!("UpsertAuthorResponse");
+
+        let by_key = generate_upsert_impl(&entity, &response, None, &[], &["pen_name".to_string()], true).to_string();
+        assert!(by_key.contains("OnConflict :: columns ([entities :: author :: Column :: Id]) . update_columns ([entities :: author :: Column :: PenName])"));
+        assert!(by_key.contains(". exec_with_returning (db)"));
+
+        let unchanged = generate_upsert_impl(&entity, &response, None, &["user_id".to_string()], &[], false).to_string();
+        assert!(unchanged.contains("OnConflict :: columns ([entities :: author :: Column :: UserId]) . do_nothing ()"));
+        assert!(unchanged.contains("for column in [entities :: author :: Column :: UserId]"));
+        assert!(unchanged.contains("Err (sea_orm :: DbErr :: RecordNotFound (_)) =>"));
+        assert!(unchanged.contains(". filter (conflicting) . one (& self . db)"));
+    }
+
+    #[test]
+    fn test_list_stream_stops_on_disconnect() {
+        let file = FileDescriptorProto::default();
+        let entity = format_ident!("post");
+        let edge = generate_list_stream_impl(&file, "ListPostsRequest", &entity, &format_ident!("PostEdge"), None, &[]).to_string();
+        assert!(edge.contains("let limit = request . first . or (request . last) . map (| n | n as u64)"));
+        assert!(edge.contains("if tx . send (item) . await . is_err () { break ; }"));
+        assert!(edge.contains("PostEdge { cursor : m . id . to_string () , node : Some (m . into ()) , }"));
+
+        let entity_items = generate_list_stream_impl(&file, "ListPostsRequest", &entity, &format_ident!("Post"), None, &[]).to_string();
+        assert!(entity_items.contains("row . map (| m | m . into ())"));
+    }
+
+    #[test]
+    fn test_import_inserts_in_chunks() {
+        let entity = format_ident!("post");
+        let response = format_ident!("ImportPostsResponse");
+        let summary = ImportSummary {
+            inserted: format_ident!("inserted"),
+            errors: format_ident!("errors"),
+            other_fields: false,
+        };
+        let code = generate_import_impl(&entity, &response, Some(&format_ident!("CreatePost")), None, &summary, 250).to_string();
+        assert!(code.contains("let item = item ? ;"));
+        assert!(code.contains("match CreatePost :: try_from (item)"));
+        assert!(code.contains("if chunk . len () >= 250usize || (done && ! chunk . is_empty ())"));
+        assert!(code.contains("insert_many (rows . iter () . map (| (_ , m) | m . clone ()))"));
+        assert!(code.contains("Err (error) => batch_errors . push (batch_error (index , error . into ()))"));
+        assert!(code.contains("ImportPostsResponse { inserted : inserted as _ , errors : batch_errors , }"));
+
+        let extra = ImportSummary { other_fields: true, ..summary };
+        let code = generate_import_impl(&entity, &response, None, None, &extra, 250).to_string();
+        assert!(!code.contains("try_from"));
+        assert!(code.contains(".. Default :: default ()"));
+    }
+
+    #[test]
+    fn test_list_paged_follows_end_cursor() {
+        let list_fn = format_ident!("list_posts");
+        let edges = generate_list_paged_impl(&list_fn, false, 250).to_string();
+        assert!(edges.contains("remaining . map_or (250u64 , | n | n . min (250u64))"));
+        assert!(edges.contains("match list_posts (& db , page . clone ()) . await"));
+        assert!(edges.contains("for edge in connection . edges"));
+        assert!(edges.contains("page . after = page_info . end_cursor ;"));
+
+        let nodes = generate_list_paged_impl(&list_fn, true, 250).to_string();
+        assert!(nodes.contains("filter_map (| edge | edge . node)"));
+    }
+
+    #[test]
+    fn test_list_encoded_uses_response_tags() {
+        let file = FileDescriptorProto::default();
+        let entity = format_ident!("post");
+        let code = generate_list_encoded_impl(&file, "ListPostsRequest", &entity, None, &[], 3, 4).to_string();
+        assert!(code.contains(". stream (db)"));
+        assert!(code.contains("let mut list = EncodedList :: new (3u32 , 4u32) ;"));
+        assert!(code.contains("list . push (& PostEdge { cursor , node : Some (m . into ()) , }) ;"));
+        assert!(code.contains("Ok (list . finish (& PageInfo {"));
+        assert!(!code.contains("Vec <"));
+
+        let code = generate_encoded_list().to_string();
+        assert!(code.contains("prost :: encoding :: message :: encode (self . edges_tag , edge , & mut self . buf)"));
+        assert!(code.contains("prost :: encoding :: message :: encode (self . page_info_tag , page_info , & mut self . buf)"));
+        assert!(code.contains("self . buf . freeze ()"));
+    }
+
+    #[test]
+    fn test_create_maps_unique_violations() {
+        let entity = format_ident!("user");
+        let response = format_ident!("CreateUserResponse");
+        let unique = [UniqueField { field: "email".to_string(), optional: false }];
+        let code = generate_create_impl(&entity, &response, None, &unique, true).to_string();
+
+        // The pre-check runs in the insert transaction and locks what it finds
+        assert!(code.contains("let txn = (db) . begin ()"));
+        assert!(code.contains("Column :: Email . eq (& request . email . clone ())) . lock_exclusive () . one (& txn)"));
+        assert!(code.contains("insert (& txn)"));
+        // Concurrent inserts of a value no row holds yet serialize on Postgres
+        assert!(code.contains("\"SELECT pg_advisory_xact_lock(hashtext($1))\""));
+        assert!(code.contains("format ! (\"{}:{:?}\" , \"User.email\" , & request . email)"));
+        // The unique index violation is the backstop
+        assert!(code.contains("Some (sea_orm :: SqlErr :: UniqueConstraintViolation (detail))"));
+        assert!(code.contains("let fields = [\"email\"]"));
+        assert!(code.contains("_ => StorageError :: Database (e)"));
+
+        let plain = generate_create_impl(&entity, &response, None, &[], true).to_string();
+        assert!(plain.contains("insert (db) . await . map_err (StorageError :: Database)"));
+    }
+}
@@ -0,0 +1,592 @@
+//! Intermediate representation dump
+//!
+//! With `emit_ir=json`, the plugin writes {package}/ir.json instead of code:
+//! the package's entities (with columns), enums, and services (with methods),
+//! each carrying the synapse options exactly as the generators resolved them,
+//! keyed by extension name (`synapse.storage.entity`, `synapse.graphql.type`,
+//! ...). Custom generators and docs can then read the same annotations without
+//! re-parsing protobuf extensions. Options follow the proto3 JSON mapping:
+//! unset fields are omitted and enums are written by name.
+
+use super::baseline::field_type;
+use super::options::{
+    get_cached_column_options, get_cached_entity_options, get_cached_enum_options, get_cached_enum_value_options,
+    get_cached_graphql_field_options, get_cached_graphql_mutation_options, get_cached_graphql_query_options,
+    get_cached_graphql_service_options, get_cached_graphql_type_options, get_cached_grpc_method_options,
+    get_cached_grpc_service_options, get_cached_rpc_method_options, get_cached_service_options,
+    get_cached_validate_field_options, get_cached_validate_message_options, to_dynamic_message,
+};
+use crate::storage::defaults::infer_entity_name;
+use heck::ToUpperCamelCase;
+use prost_reflect::{Kind, MapKey, Value};
+use prost_types::compiler::code_generator_response::File;
+use prost_types::compiler::{CodeGeneratorRequest, CodeGeneratorResponse};
+use prost_types::{DescriptorProto, EnumDescriptorProto, FileDescriptorProto, ServiceDescriptorProto};
+use std::fmt::Write as _;
+
+/// A JSON value, written by [`Json::write`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// A number, already formatted
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    /// An object, in insertion order
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub(super) fn object(fields: Vec<(&str, Json)>) -> Self {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub(super) fn string(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+
+    /// Pretty-print with two-space indentation
+    pub fn write(&self, out: &mut String, indent: usize) {
+        let pad = |n: usize| "  ".repeat(n);
+        match self {
+            Json::Null => out.push_str("null"),
+            Json::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Json::Number(n) => out.push_str(n),
+            Json::String(s) => write_string(out, s),
+            Json::Array(items) if items.is_empty() => out.push_str("[]"),
+            Json::Array(items) => {
+                out.push_str("[\n");
+                for (i, item) in items.iter().enumerate() {
+                    out.push_str(&pad(indent + 1));
+                    item.write(out, indent + 1);
+                    out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&pad(indent));
+                out.push(']');
+            }
+            Json::Object(fields) if fields.is_empty() => out.push_str("{}"),
+            Json::Object(fields) => {
+                out.push_str("{\n");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    out.push_str(&pad(indent + 1));
+                    write_string(out, key);
+                    out.push_str(": ");
+                    value.write(out, indent + 1);
+                    out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+                }
+                out.push_str(&pad(indent));
+                out.push('}');
+            }
+        }
+    }
+}
+
+/// Write a JSON string literal
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Resolved options of one descriptor, as (extension name, value)
+#[derive(Debug, Clone, Default)]
+pub struct Options(Vec<(&'static str, Json)>);
+
+impl Options {
+    /// Add an option message if set, as JSON of its proto type `full_name`
+    fn add(&mut self, extension: &'static str, full_name: &str, options: Option<impl prost::Message>) {
+        if let Some(dynamic) = options.and_then(|o| to_dynamic_message(full_name, &o)) {
+            self.0.push((extension, message_json(&dynamic)));
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        Json::Object(self.0.iter().map(|(k, v)| (k.to_string(), v.clone())).collect())
+    }
+}
+
+/// An entity field
+#[derive(Debug, Clone)]
+pub struct Column {
+    pub name: String,
+    pub number: i32,
+    /// Proto type, e.g. `string`, `repeated int64`, `blog.Status`
+    pub ty: String,
+    pub optional: bool,
+    pub options: Options,
+}
+
+/// A message with `synapse.storage.entity`
+#[derive(Debug, Clone)]
+pub struct Entity {
+    pub name: String,
+    pub file: String,
+    pub options: Options,
+    pub columns: Vec<Column>,
+}
+
+/// An enum value
+#[derive(Debug, Clone)]
+pub struct EnumValue {
+    pub name: String,
+    pub number: i32,
+    pub options: Options,
+}
+
+/// A top-level enum
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub name: String,
+    pub file: String,
+    pub options: Options,
+    pub values: Vec<EnumValue>,
+}
+
+/// An RPC
+#[derive(Debug, Clone)]
+pub struct Method {
+    pub name: String,
+    pub input_type: String,
+    pub output_type: String,
+    pub client_streaming: bool,
+    pub server_streaming: bool,
+    /// Entity the storage layer operates on (`entity_name` or inferred), if
+    /// it names an entity of the package
+    pub entity: Option<String>,
+    pub options: Options,
+}
+
+/// A service
+#[derive(Debug, Clone)]
+pub struct Service {
+    pub name: String,
+    pub file: String,
+    pub options: Options,
+    pub methods: Vec<Method>,
+}
+
+/// Everything the generators know about one package
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub entities: Vec<Entity>,
+    pub enums: Vec<Enum>,
+    pub services: Vec<Service>,
+}
+
+/// Dump the IR of the requested packages
+pub fn emit(request: &CodeGeneratorRequest) -> CodeGeneratorResponse {
+    let mut packages: Vec<&str> = Vec::new();
+    for file_name in &request.file_to_generate {
+        if let Some(file) = request.proto_file.iter().find(|f| f.name.as_ref() == Some(file_name)) {
+            let package = file.package.as_deref().unwrap_or("");
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+    }
+
+    let file = packages
+        .iter()
+        .map(|package| {
+            let mut content = String::new();
+            collect(package, &request.proto_file).to_json().write(&mut content, 0);
+            content.push('\n');
+            let dir = if package.is_empty() { String::new() } else { format!("{}/", package.replace('.', "/")) };
+            File {
+                name: Some(format!("{}ir.json", dir)),
+                content: Some(content),
+                ..Default::default()
+            }
+        })
+        .collect();
+
+    CodeGeneratorResponse {
+        file,
+        error: None,
+        supported_features: Some(1), // FEATURE_PROTO3_OPTIONAL
+    }
+}
+
+/// Build the IR of a package from the files declaring it
+pub fn collect(package: &str, files: &[FileDescriptorProto]) -> Package {
+    let mut ir = Package {
+        name: package.to_string(),
+        entities: Vec::new(),
+        enums: Vec::new(),
+        services: Vec::new(),
+    };
+    let package_files: Vec<&FileDescriptorProto> =
+        files.iter().filter(|f| f.package.as_deref().unwrap_or("") == package).collect();
+
+    for file in &package_files {
+        let file_name = file.name.as_deref().unwrap_or("");
+        for message in &file.message_type {
+            if get_cached_entity_options(file_name, message.name.as_deref().unwrap_or("")).is_some() {
+                ir.entities.push(collect_entity(file_name, message));
+            }
+        }
+        for enum_desc in &file.enum_type {
+            ir.enums.push(collect_enum(file_name, enum_desc));
+        }
+    }
+
+    let entity_names: Vec<String> = ir.entities.iter().map(|e| e.name.clone()).collect();
+    for file in &package_files {
+        let file_name = file.name.as_deref().unwrap_or("");
+        for service in &file.service {
+            ir.services.push(collect_service(file_name, service, &entity_names));
+        }
+    }
+
+    ir
+}
+
+fn collect_entity(file_name: &str, message: &DescriptorProto) -> Entity {
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let mut options = Options::default();
+    options.add("synapse.storage.entity", "synapse.storage.EntityOptions", get_cached_entity_options(file_name, msg_name));
+    options.add("synapse.graphql.type", "synapse.graphql.TypeOptions", get_cached_graphql_type_options(file_name, msg_name));
+    options.add(
+        "synapse.validate.message",
+        "synapse.validate.MessageOptions",
+        get_cached_validate_message_options(file_name, msg_name),
+    );
+
+    let columns = message
+        .field
+        .iter()
+        .map(|field| {
+            let number = field.number.unwrap_or(0);
+            let mut options = Options::default();
+            options.add(
+                "synapse.storage.column",
+                "synapse.storage.ColumnOptions",
+                get_cached_column_options(file_name, msg_name, number),
+            );
+            options.add(
+                "synapse.graphql.field",
+                "synapse.graphql.FieldOptions",
+                get_cached_graphql_field_options(file_name, msg_name, number),
+            );
+            options.add(
+                "synapse.validate.field",
+                "synapse.validate.FieldOptions",
+                get_cached_validate_field_options(file_name, msg_name, number),
+            );
+            Column {
+                name: field.name.clone().unwrap_or_default(),
+                number,
+                ty: field_type(field),
+                optional: field.proto3_optional.unwrap_or(false),
+                options,
+            }
+        })
+        .collect();
+
+    Entity {
+        name: msg_name.to_string(),
+        file: file_name.to_string(),
+        options,
+        columns,
+    }
+}
+
+fn collect_enum(file_name: &str, enum_desc: &EnumDescriptorProto) -> Enum {
+    let enum_name = enum_desc.name.as_deref().unwrap_or("");
+    let mut options = Options::default();
+    options.add("synapse.storage.enum_type", "synapse.storage.EnumOptions", get_cached_enum_options(file_name, enum_name));
+
+    let values = enum_desc
+        .value
+        .iter()
+        .map(|value| {
+            let number = value.number.unwrap_or(0);
+            let mut options = Options::default();
+            options.add(
+                "synapse.storage.enum_value",
+                "synapse.storage.EnumValueOptions",
+                get_cached_enum_value_options(file_name, enum_name, number),
+            );
+            EnumValue {
+                name: value.name.clone().unwrap_or_default(),
+                number,
+                options,
+            }
+        })
+        .collect();
+
+    Enum {
+        name: enum_name.to_string(),
+        file: file_name.to_string(),
+        options,
+        values,
+    }
+}
+
+fn collect_service(file_name: &str, service: &ServiceDescriptorProto, entities: &[String]) -> Service {
+    let svc_name = service.name.as_deref().unwrap_or("");
+    let mut options = Options::default();
+    options.add("synapse.storage.service", "synapse.storage.ServiceOptions", get_cached_service_options(file_name, svc_name));
+    options.add("synapse.grpc.service", "synapse.grpc.ServiceOptions", get_cached_grpc_service_options(file_name, svc_name));
+    options.add(
+        "synapse.graphql.service",
+        "synapse.graphql.ServiceOptions",
+        get_cached_graphql_service_options(file_name, svc_name),
+    );
+
+    let methods = service
+        .method
+        .iter()
+        .map(|method| {
+            let method_name = method.name.as_deref().unwrap_or("");
+            let storage = get_cached_rpc_method_options(file_name, svc_name, method_name);
+            let entity = storage
+                .as_ref()
+                .filter(|o| !o.entity_name.is_empty())
+                .map(|o| o.entity_name.clone())
+                .unwrap_or_else(|| infer_entity_name(method_name))
+                .to_upper_camel_case();
+
+            let mut options = Options::default();
+            options.add("synapse.storage.method", "synapse.storage.MethodOptions", storage);
+            options.add(
+                "synapse.grpc.method",
+                "synapse.grpc.MethodOptions",
+                get_cached_grpc_method_options(file_name, svc_name, method_name),
+            );
+            options.add(
+                "synapse.graphql.query",
+                "synapse.graphql.QueryOptions",
+                get_cached_graphql_query_options(file_name, svc_name, method_name),
+            );
+            options.add(
+                "synapse.graphql.mutation",
+                "synapse.graphql.MutationOptions",
+                get_cached_graphql_mutation_options(file_name, svc_name, method_name),
+            );
+
+            Method {
+                name: method_name.to_string(),
+                input_type: method.input_type.as_deref().unwrap_or("").trim_start_matches('.').to_string(),
+                output_type: method.output_type.as_deref().unwrap_or("").trim_start_matches('.').to_string(),
+                client_streaming: method.client_streaming(),
+                server_streaming: method.server_streaming(),
+                entity: entities.contains(&entity).then_some(entity),
+                options,
+            }
+        })
+        .collect();
+
+    Service {
+        name: svc_name.to_string(),
+        file: file_name.to_string(),
+        options,
+        methods,
+    }
+}
+
+impl Package {
+    pub fn to_json(&self) -> Json {
+        let entities = self
+            .entities
+            .iter()
+            .map(|e| {
+                let columns = e
+                    .columns
+                    .iter()
+                    .map(|c| {
+                        Json::object(vec![
+                            ("name", Json::string(&c.name)),
+                            ("number", Json::Number(c.number.to_string())),
+                            ("type", Json::string(&c.ty)),
+                            ("optional", Json::Bool(c.optional)),
+                            ("options", c.options.to_json()),
+                        ])
+                    })
+                    .collect();
+                Json::object(vec![
+                    ("name", Json::string(&e.name)),
+                    ("file", Json::string(&e.file)),
+                    ("options", e.options.to_json()),
+                    ("columns", Json::Array(columns)),
+                ])
+            })
+            .collect();
+
+        let enums = self
+            .enums
+            .iter()
+            .map(|e| {
+                let values = e
+                    .values
+                    .iter()
+                    .map(|v| {
+                        Json::object(vec![
+                            ("name", Json::string(&v.name)),
+                            ("number", Json::Number(v.number.to_string())),
+                            ("options", v.options.to_json()),
+                        ])
+                    })
+                    .collect();
+                Json::object(vec![
+                    ("name", Json::string(&e.name)),
+                    ("file", Json::string(&e.file)),
+                    ("options", e.options.to_json()),
+                    ("values", Json::Array(values)),
+                ])
+            })
+            .collect();
+
+        let services = self
+            .services
+            .iter()
+            .map(|s| {
+                let methods = s
+                    .methods
+                    .iter()
+                    .map(|m| {
+                        Json::object(vec![
+                            ("name", Json::string(&m.name)),
+                            ("input_type", Json::string(&m.input_type)),
+                            ("output_type", Json::string(&m.output_type)),
+                            ("client_streaming", Json::Bool(m.client_streaming)),
+                            ("server_streaming", Json::Bool(m.server_streaming)),
+                            ("entity", m.entity.as_deref().map_or(Json::Null, Json::string)),
+                            ("options", m.options.to_json()),
+                        ])
+                    })
+                    .collect();
+                Json::object(vec![
+                    ("name", Json::string(&s.name)),
+                    ("file", Json::string(&s.file)),
+                    ("options", s.options.to_json()),
+                    ("methods", Json::Array(methods)),
+                ])
+            })
+            .collect();
+
+        Json::object(vec![
+            ("package", Json::string(&self.name)),
+            ("entities", Json::Array(entities)),
+            ("enums", Json::Array(enums)),
+            ("services", Json::Array(services)),
+        ])
+    }
+}
+
+/// Set fields of a message, in field number order
+fn message_json(message: &prost_reflect::DynamicMessage) -> Json {
+    let mut fields: Vec<_> = message.fields().collect();
+    fields.sort_by_key(|(field, _)| field.number());
+    Json::Object(
+        fields
+            .into_iter()
+            .map(|(field, value)| (field.json_name().to_string(), value_json(value, &field.kind())))
+            .collect(),
+    )
+}
+
+fn value_json(value: &Value, kind: &Kind) -> Json {
+    match value {
+        Value::Bool(b) => Json::Bool(*b),
+        Value::I32(n) => Json::Number(n.to_string()),
+        Value::U32(n) => Json::Number(n.to_string()),
+        // 64-bit integers are strings in proto3 JSON
+        Value::I64(n) => Json::String(n.to_string()),
+        Value::U64(n) => Json::String(n.to_string()),
+        Value::F32(n) if n.is_finite() => Json::Number(n.to_string()),
+        Value::F64(n) if n.is_finite() => Json::Number(n.to_string()),
+        Value::F32(n) => Json::String(n.to_string()),
+        Value::F64(n) => Json::String(n.to_string()),
+        Value::String(s) => Json::string(s),
+        Value::Bytes(b) => Json::Array(b.iter().map(|b| Json::Number(b.to_string())).collect()),
+        Value::EnumNumber(n) => match kind.as_enum().and_then(|e| e.get_value(*n)) {
+            Some(value) => Json::string(value.name()),
+            None => Json::Number(n.to_string()),
+        },
+        Value::Message(m) => message_json(m),
+        Value::List(items) => Json::Array(items.iter().map(|item| value_json(item, kind)).collect()),
+        Value::Map(entries) => {
+            let value_kind = kind
+                .as_message()
+                .map(|entry| entry.map_entry_value_field().kind())
+                .unwrap_or_else(|| kind.clone());
+            let mut entries: Vec<(String, Json)> = entries
+                .iter()
+                .map(|(key, value)| (map_key(key), value_json(value, &value_kind)))
+                .collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Json::Object(entries)
+        }
+    }
+}
+
+fn map_key(key: &MapKey) -> String {
+    match key {
+        MapKey::Bool(b) => b.to_string(),
+        MapKey::I32(n) => n.to_string(),
+        MapKey::I64(n) => n.to_string(),
+        MapKey::U32(n) => n.to_string(),
+        MapKey::U64(n) => n.to_string(),
+        MapKey::String(s) => s.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::synapse::storage::{ColumnOptions, IdKind};
+
+    #[test]
+    fn test_json_write() {
+        let json = Json::object(vec![
+            ("name", Json::string("say \"hi\"\n")),
+            ("items", Json::Array(vec![Json::Bool(true), Json::Null])),
+            ("empty", Json::Object(Vec::new())),
+        ]);
+        let mut out = String::new();
+        json.write(&mut out, 0);
+        assert_eq!(
+            out,
+            "{\n  \"name\": \"say \\\"hi\\\"\\n\",\n  \"items\": [\n    true,\n    null\n  ],\n  \"empty\": {}\n}"
+        );
+    }
+
+    #[test]
+    fn test_options_omit_unset_fields() {
+        let mut options = Options::default();
+        options.add(
+            "synapse.storage.column",
+            "synapse.storage.ColumnOptions",
+            Some(ColumnOptions {
+                primary_key: true,
+                id_kind: IdKind::UuidV7 as i32,
+                ..Default::default()
+            }),
+        );
+        options.add("synapse.storage.column", "synapse.storage.ColumnOptions", None::<ColumnOptions>);
+
+        let Json::Object(fields) = options.to_json() else {
+            panic!("options are an object");
+        };
+        assert_eq!(fields.len(), 1);
+        assert_eq!(
+            fields[0].1,
+            Json::object(vec![("primaryKey", Json::Bool(true)), ("idKind", Json::string("ID_KIND_UUID_V7"))])
+        );
+    }
+}
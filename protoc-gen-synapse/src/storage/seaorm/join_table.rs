@@ -0,0 +1,294 @@
+//! Join-table synthesis for many-to-many relations
+//!
+//! A relation with `through: "team_members"` needs a junction entity for
+//! SeaORM's `via`. When no proto message declares that table, this module
+//! synthesizes one: a foreign key column per side, both forming a composite
+//! primary key, plus `belongs_to` relations back to each entity.
+
+use super::implementation::primary_key_kind;
+use super::options::{get_cached_column_options, get_cached_entity_options};
+use crate::error::GeneratorError;
+use crate::options::synapse::storage::{self, RelationType};
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::Type;
+use prost_types::FileDescriptorProto;
+use quote::{format_ident, quote};
+
+/// A synthesized junction entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinTable {
+    /// Entity module and table name (snake_case of `through`)
+    pub module: String,
+    /// Foreign key columns, in declaration order
+    pub columns: Vec<JoinColumn>,
+}
+
+/// A foreign key column of a junction entity
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinColumn {
+    /// Column name (e.g. `team_id`)
+    pub name: String,
+    /// Referenced entity (message name, optionally package-qualified)
+    pub entity: String,
+    /// Rust type of the referenced primary key
+    pub rust_type: String,
+}
+
+/// Collect junction tables referenced by `through` with no backing message
+pub fn collect_join_tables(
+    all_files: &[FileDescriptorProto],
+    main_file: &FileDescriptorProto,
+) -> Result<Vec<JoinTable>, GeneratorError> {
+    let package = main_file.package.as_deref().unwrap_or("");
+    let package_files: Vec<_> = all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .collect();
+
+    // Tables already declared as messages in this package
+    let declared: Vec<String> = package_files
+        .iter()
+        .flat_map(|f| f.message_type.iter())
+        .filter_map(|m| m.name.as_deref())
+        .map(|n| n.to_snake_case())
+        .collect();
+
+    let mut tables: Vec<JoinTable> = Vec::new();
+    for file in &package_files {
+        let file_name = file.name.as_deref().unwrap_or("");
+        for message in &file.message_type {
+            let msg_name = message.name.as_deref().unwrap_or("");
+            let Some(opts) = get_cached_entity_options(file_name, msg_name) else {
+                continue;
+            };
+            if opts.skip {
+                continue;
+            }
+
+            for rel in &opts.relations {
+                let is_many = matches!(rel.r#type(), RelationType::ManyToMany | RelationType::HasMany);
+                if !is_many || rel.through.is_empty() {
+                    continue;
+                }
+                let module = rel.through.to_snake_case();
+                if declared.contains(&module) {
+                    continue;
+                }
+
+                let owner_column = if rel.foreign_key.is_empty() {
+                    format!("{}_id", msg_name.to_snake_case())
+                } else {
+                    rel.foreign_key.clone()
+                };
+                let related_name = rel.related.rsplit('.').next().unwrap_or(&rel.related);
+                let related_column = format!("{}_id", related_name.to_snake_case());
+                if owner_column == related_column {
+                    return Err(GeneratorError::CodeGenError(format!(
+                        "relation {}.{} through {}: both join columns are named {}; set foreign_key",
+                        msg_name, rel.name, rel.through, owner_column
+                    )));
+                }
+
+                let columns = [
+                    JoinColumn {
+                        name: owner_column,
+                        entity: msg_name.to_string(),
+                        rust_type: key_rust_type(all_files, msg_name),
+                    },
+                    JoinColumn {
+                        name: related_column,
+                        entity: rel.related.clone(),
+                        rust_type: key_rust_type(all_files, related_name),
+                    },
+                ];
+
+                let table = match tables.iter_mut().find(|t| t.module == module) {
+                    Some(table) => table,
+                    None => {
+                        tables.push(JoinTable { module, columns: Vec::new() });
+                        tables.last_mut().unwrap()
+                    }
+                };
+                for column in columns {
+                    if !table.columns.iter().any(|c| c.name == column.name) {
+                        table.columns.push(column);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(tables)
+}
+
+/// Rust type of an entity's primary key column
+fn key_rust_type(all_files: &[FileDescriptorProto], entity: &str) -> String {
+    match primary_key_kind(all_files, entity) {
+        storage::IdKind::UuidV7 => return "Uuid".to_string(),
+        storage::IdKind::Ulid => return "Ulid".to_string(),
+        _ => {}
+    }
+
+    let pk_type = all_files.iter().find_map(|f| {
+        let file_name = f.name.as_deref().unwrap_or("");
+        let message = f.message_type.iter().find(|m| m.name.as_deref() == Some(entity))?;
+        message
+            .field
+            .iter()
+            .find(|field| {
+                get_cached_column_options(file_name, entity, field.number.unwrap_or(0))
+                    .map(|o| o.primary_key)
+                    .unwrap_or(false)
+            })
+            .map(|field| field.r#type())
+    });
+
+    match pk_type {
+        Some(Type::Int32) | Some(Type::Sint32) | Some(Type::Sfixed32) => "i32",
+        Some(Type::String) => "String",
+        _ => "i64",
+    }
+    .to_string()
+}
+
+/// Path to a referenced entity from inside the entities module
+fn entity_path(entity: &str) -> syn::Type {
+    let path = match entity.split_once('.') {
+        Some((package, name)) => format!(
+            "crate::{}::entities::{}::Entity",
+            package.to_snake_case(),
+            name.to_snake_case()
+        ),
+        None => format!("super::{}::Entity", entity.to_snake_case()),
+    };
+    syn::parse_str(&path).unwrap_or_else(|_| syn::parse_quote!(Entity))
+}
+
+/// Generate the entity files for all synthesized junction tables
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Vec<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    collect_join_tables(all_files, file)?
+        .iter()
+        .map(|table| {
+            let content = crate::params::format_code(generate_entity(table).to_string());
+            Ok(File {
+                name: Some(format!(
+                    "{}/entities/{}.rs",
+                    package.replace('.', "/"),
+                    table.module
+                )),
+                content: Some(content),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Generate the junction entity module
+fn generate_entity(table: &JoinTable) -> TokenStream {
+    let table_name = &table.module;
+
+    // Two columns pointing at the same entity need distinct relation enums
+    let self_ref = table
+        .columns
+        .iter()
+        .any(|c| table.columns.iter().filter(|o| o.entity == c.entity).count() > 1);
+
+    let key_fields = table.columns.iter().map(|c| {
+        let name = format_ident!("{}", c.name);
+        let ty: syn::Type = syn::parse_str(&c.rust_type).unwrap_or_else(|_| syn::parse_quote!(i64));
+        quote! {
+            #[sea_orm(primary_key, auto_increment = false)]
+            pub #name: #ty
+        }
+    });
+
+    let relation_fields = table.columns.iter().map(|c| {
+        let from_col = &c.name;
+        let field = format_ident!("{}", c.name.trim_end_matches("_id"));
+        let target = entity_path(&c.entity);
+        if self_ref {
+            let relation_enum = c.name.trim_end_matches("_id").to_upper_camel_case();
+            quote! {
+                #[sea_orm(belongs_to, relation_enum = #relation_enum, from = #from_col, to = "id")]
+                pub #field: HasOne<#target>
+            }
+        } else {
+            quote! {
+                #[sea_orm(belongs_to, from = #from_col, to = "id")]
+                pub #field: HasOne<#target>
+            }
+        }
+    });
+
+    quote! {
+        //! SeaORM junction entity for a many-to-many relation
+        //!
+        //! Synthesized by protoc-gen-synapse from a relation's `through` table.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use super::super::prelude::*;
+        use sea_orm::entity::prelude::*;
+
+        #[sea_orm::model]
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = #table_name)]
+        pub struct Model {
+            #(#key_fields,)*
+            #(#relation_fields),*
+        }
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, entity: &str) -> JoinColumn {
+        JoinColumn {
+            name: name.to_string(),
+            entity: entity.to_string(),
+            rust_type: "i64".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_generate_join_entity() {
+        let table = JoinTable {
+            module: "team_members".to_string(),
+            columns: vec![column("team_id", "Team"), column("user_id", "User")],
+        };
+        let code = generate_entity(&table).to_string();
+        assert!(code.contains("table_name = \"team_members\""));
+        assert!(code.contains("pub team_id : i64"));
+        assert!(code.contains("primary_key , auto_increment = false"));
+        assert!(code.contains("from = \"user_id\""));
+        assert!(code.contains("super :: user :: Entity"));
+        assert!(!code.contains("relation_enum"));
+    }
+
+    #[test]
+    fn test_self_referential_join_entity() {
+        let table = JoinTable {
+            module: "follows".to_string(),
+            columns: vec![column("follower_id", "User"), column("user_id", "User")],
+        };
+        let code = generate_entity(&table).to_string();
+        assert!(code.contains("relation_enum = \"Follower\""));
+        assert!(code.contains("relation_enum = \"User\""));
+    }
+}
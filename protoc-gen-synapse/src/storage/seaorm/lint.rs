@@ -0,0 +1,680 @@
+//! Annotation lint
+//!
+//! With `lint=true`, the plugin checks the synapse annotations of the requested
+//! packages and writes no code. Every problem found is reported in the protoc
+//! error, one per line as `file:line:column: message`:
+//! - entities without a primary key column
+//! - relations whose `related` entity does not exist
+//! - relation `foreign_key`s naming a field that does not exist
+//! - belongs_to `references` naming a field the related entity lacks
+//! - `inverse` set on a relation that is not belongs_to or that crosses packages
+//! - `remote` set on a relation that is not belongs_to or within the package
+//! - storage service methods operating on a message that is not an entity
+//! - GraphQL object fields shadowing generated Relay fields
+//! - entities of a package whose modules collide (e.g. `ApiKey` and `APIKey`)
+//! - with `strict_layout=true`, files outside their package's directory
+//!
+//! Lines come from the descriptors' source info, which protoc only passes for
+//! the files being generated; issues elsewhere are reported against the file.
+//!
+//! Shadowed fields are also checked on every generation, see
+//! [`check_shadowed_fields`], and so are the layout checks and relation
+//! targets, see [`check_layout`].
+
+use super::options::{
+    get_cached_column_options, get_cached_entity_options, get_cached_graphql_field_options,
+    get_cached_graphql_type_options, get_cached_rpc_method_options, get_cached_service_options,
+    same_package_name,
+};
+use crate::graphql::{renamed_shadowed_field, shadows_relay_field};
+use crate::options::synapse::storage::{EntityOptions, RelationDef, RelationType};
+use crate::storage::defaults::infer_entity_name;
+use heck::{ToLowerCamelCase, ToSnakeCase, ToUpperCamelCase};
+use prost_types::compiler::{CodeGeneratorRequest, CodeGeneratorResponse};
+use prost_types::{DescriptorProto, FileDescriptorProto};
+
+/// `FileDescriptorProto.message_type` field number, for source paths
+const MESSAGE_TYPE: i32 = 4;
+/// `FileDescriptorProto.service` field number
+const SERVICE: i32 = 6;
+/// `ServiceDescriptorProto.method` field number
+const METHOD: i32 = 2;
+/// `DescriptorProto.field` field number
+const FIELD: i32 = 2;
+
+/// An annotation problem at a descriptor
+struct LintIssue {
+    location: String,
+    message: String,
+}
+
+/// An entity message and where it is declared
+struct Entity<'a> {
+    package: &'a str,
+    file: &'a FileDescriptorProto,
+    index: usize,
+    message: &'a DescriptorProto,
+    options: EntityOptions,
+}
+
+/// Lint the packages of the requested files
+///
+/// Returns a response without files: its error lists the issues, or is unset
+/// when the annotations are clean.
+pub fn lint(request: &CodeGeneratorRequest) -> CodeGeneratorResponse {
+    let packages = requested_packages(request);
+
+    let entities = collect_entities(&request.proto_file);
+    let mut issues = Vec::new();
+    for entity in entities.iter().filter(|e| packages.contains(&e.package)) {
+        lint_entity(entity, &entities, &request.proto_file, &mut issues);
+    }
+    for file in request
+        .proto_file
+        .iter()
+        .filter(|f| packages.contains(&f.package.as_deref().unwrap_or("")))
+    {
+        lint_services(file, &entities, &mut issues);
+        lint_shadowed_fields(file, &mut issues);
+        lint_file_layout(file, &mut issues);
+    }
+    for package in &packages {
+        lint_module_collisions(package, &entities, &mut issues);
+    }
+
+    CodeGeneratorResponse {
+        file: Vec::new(),
+        error: report(&issues),
+        supported_features: Some(1), // FEATURE_PROTO3_OPTIONAL
+    }
+}
+
+/// Check the requested packages for GraphQL fields shadowing Relay fields
+///
+/// Runs before every generation, since such fields compile but confuse Relay
+/// clients. Returns the issues in lint format, or `None` when there are none.
+pub fn check_shadowed_fields(request: &CodeGeneratorRequest) -> Option<String> {
+    let packages = requested_packages(request);
+    let mut issues = Vec::new();
+    for file in request
+        .proto_file
+        .iter()
+        .filter(|f| packages.contains(&f.package.as_deref().unwrap_or("")))
+    {
+        lint_shadowed_fields(file, &mut issues);
+    }
+    report(&issues)
+}
+
+/// Check the requested packages for layout problems that break generated code
+///
+/// Runs before every generation: colliding entity modules and relations to
+/// entities that are not generated otherwise produce modules referencing
+/// missing files. With `strict_layout=true`, files must also live in their
+/// package's directory. Returns the issues in lint format, or `None` when
+/// there are none.
+pub fn check_layout(request: &CodeGeneratorRequest) -> Option<String> {
+    let packages = requested_packages(request);
+    let entities = collect_entities(&request.proto_file);
+    let mut issues = Vec::new();
+    for file in request
+        .proto_file
+        .iter()
+        .filter(|f| packages.contains(&f.package.as_deref().unwrap_or("")))
+    {
+        lint_file_layout(file, &mut issues);
+    }
+    for package in &packages {
+        lint_module_collisions(package, &entities, &mut issues);
+    }
+    for entity in entities.iter().filter(|e| packages.contains(&e.package)) {
+        let msg_name = entity.message.name.as_deref().unwrap_or("");
+        for relation in &entity.options.relations {
+            // Polymorphic targets are candidates, dispatched within the package
+            if relation.r#type() == RelationType::Polymorphic
+                || find_entity(&entities, entity.package, &relation.related).is_some()
+            {
+                continue;
+            }
+            issues.push(LintIssue {
+                location: location(entity.file, &[MESSAGE_TYPE, entity.index as i32]),
+                message: unresolved_relation(msg_name, entity.package, relation, &request.proto_file, &entities),
+            });
+        }
+    }
+    report(&issues)
+}
+
+/// Packages of the requested files, in request order
+fn requested_packages(request: &CodeGeneratorRequest) -> Vec<&str> {
+    let mut packages: Vec<&str> = Vec::new();
+    for file_name in &request.file_to_generate {
+        if let Some(file) = request.proto_file.iter().find(|f| f.name.as_ref() == Some(file_name)) {
+            let package = file.package.as_deref().unwrap_or("");
+            if !packages.contains(&package) {
+                packages.push(package);
+            }
+        }
+    }
+    packages
+}
+
+/// Issues one per line as `location: message`, or `None` when there are none
+fn report(issues: &[LintIssue]) -> Option<String> {
+    (!issues.is_empty()).then(|| {
+        issues
+            .iter()
+            .map(|issue| format!("{}: {}", issue.location, issue.message))
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+/// Entities (non-skipped) of every file in the request
+fn collect_entities(files: &[FileDescriptorProto]) -> Vec<Entity<'_>> {
+    let mut entities = Vec::new();
+    for file in files {
+        let file_name = file.name.as_deref().unwrap_or("");
+        for (index, message) in file.message_type.iter().enumerate() {
+            let msg_name = message.name.as_deref().unwrap_or("");
+            if let Some(options) = get_cached_entity_options(file_name, msg_name)
+                && !options.skip
+            {
+                entities.push(Entity {
+                    package: file.package.as_deref().unwrap_or(""),
+                    file,
+                    index,
+                    message,
+                    options,
+                });
+            }
+        }
+    }
+    entities
+}
+
+/// Find the entity a relation or method names, relative to a package
+///
+/// Accepts `User`, `iam.User`, and `.iam.User`.
+fn find_entity<'a, 'b>(entities: &'b [Entity<'a>], package: &str, name: &str) -> Option<&'b Entity<'a>> {
+    let name = name.trim_start_matches('.');
+    let (package, name) = name.rsplit_once('.').unwrap_or((package, name));
+    entities
+        .iter()
+        .find(|e| e.package == package && e.message.name.as_deref() == Some(name))
+}
+
+/// Check an entity's primary key and relations
+fn lint_entity(entity: &Entity, entities: &[Entity], files: &[FileDescriptorProto], issues: &mut Vec<LintIssue>) {
+    let file_name = entity.file.name.as_deref().unwrap_or("");
+    let msg_name = entity.message.name.as_deref().unwrap_or("");
+    let location = location(entity.file, &[MESSAGE_TYPE, entity.index as i32]);
+
+    let has_primary_key = entity.message.field.iter().any(|f| {
+        get_cached_column_options(file_name, msg_name, f.number.unwrap_or(0)).is_some_and(|o| o.primary_key)
+    });
+    if !has_primary_key {
+        issues.push(LintIssue {
+            location: location.clone(),
+            message: format!("entity {} has no primary key column", msg_name),
+        });
+    }
+
+    for relation in &entity.options.relations {
+        // Inverses are only declared for belongs_to relations within the package
+        if relation.inverse {
+            let problem = if relation.r#type() != RelationType::BelongsTo {
+                Some("is not belongs_to".to_string())
+            } else if same_package_name(entity.package, &relation.related).is_none() {
+                Some(format!("relates to `{}` in another package", relation.related))
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                issues.push(LintIssue {
+                    location: location.clone(),
+                    message: format!(
+                        "relation {}.{} sets inverse but {}",
+                        msg_name, relation.name, problem
+                    ),
+                });
+            }
+        }
+
+        // Remote relations point at another service's package
+        if relation.remote {
+            let problem = if relation.r#type() != RelationType::BelongsTo {
+                Some("is not belongs_to".to_string())
+            } else if same_package_name(entity.package, &relation.related).is_some() {
+                Some(format!("relates to `{}` in the same package", relation.related))
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                issues.push(LintIssue {
+                    location: location.clone(),
+                    message: format!(
+                        "relation {}.{} sets remote but {}",
+                        msg_name, relation.name, problem
+                    ),
+                });
+            }
+        }
+
+        let Some(related) = find_entity(entities, entity.package, &relation.related) else {
+            issues.push(LintIssue {
+                location: location.clone(),
+                message: unresolved_relation(msg_name, entity.package, relation, files, entities),
+            });
+            continue;
+        };
+
+        // belongs_to keys point at `references` on the related entity
+        let references = relation.references.as_str();
+        if relation.r#type() == RelationType::BelongsTo
+            && !references.is_empty()
+            && !related.message.field.iter().any(|f| f.name.as_deref() == Some(references))
+        {
+            issues.push(LintIssue {
+                location: location.clone(),
+                message: format!(
+                    "relation {}.{} has references `{}`, which is not a field of {}",
+                    msg_name,
+                    relation.name,
+                    references,
+                    related.message.name.as_deref().unwrap_or("")
+                ),
+            });
+        }
+
+        if relation.foreign_key.is_empty() {
+            continue;
+        }
+
+        // The foreign key lives on the side holding the reference
+        let owner = match relation.r#type() {
+            RelationType::BelongsTo | RelationType::Polymorphic => entity.message,
+            RelationType::HasMany | RelationType::HasOne => related.message,
+            // Many-to-many keys live on the through table
+            RelationType::ManyToMany | RelationType::Unspecified => continue,
+        };
+        if !owner.field.iter().any(|f| f.name.as_deref() == Some(relation.foreign_key.as_str())) {
+            issues.push(LintIssue {
+                location: location.clone(),
+                message: format!(
+                    "relation {}.{} has foreign_key `{}`, which is not a field of {}",
+                    msg_name,
+                    relation.name,
+                    relation.foreign_key,
+                    owner.name.as_deref().unwrap_or("")
+                ),
+            });
+        }
+    }
+}
+
+/// Why a relation's `related` entity is not found, with a fix
+fn unresolved_relation(
+    msg_name: &str,
+    package: &str,
+    relation: &RelationDef,
+    files: &[FileDescriptorProto],
+    entities: &[Entity],
+) -> String {
+    let related = relation.related.trim_start_matches('.');
+    let (related_package, related_name) = related.rsplit_once('.').unwrap_or((package, related));
+    let prefix = format!("relation {}.{} references `{}`", msg_name, relation.name, relation.related);
+
+    if related_name.is_empty() {
+        return format!("relation {}.{} has no related entity; set related", msg_name, relation.name);
+    }
+
+    // Declared, but not as a generated entity
+    let declared = files.iter().find(|f| {
+        f.package.as_deref().unwrap_or("") == related_package
+            && f.message_type.iter().any(|m| m.name.as_deref() == Some(related_name))
+    });
+    if let Some(file) = declared {
+        let file_name = file.name.as_deref().unwrap_or("");
+        let skipped = get_cached_entity_options(file_name, related_name).is_some_and(|o| o.skip);
+        return if skipped {
+            format!("{}, which sets skip in {} and is not generated; remove skip or the relation", prefix, file_name)
+        } else {
+            format!(
+                "{}, which is declared in {} without (synapse.storage.entity); annotate it or remove the relation",
+                prefix, file_name
+            )
+        };
+    }
+
+    // An entity of that name in another package
+    if let Some(other) = entities
+        .iter()
+        .find(|e| e.package != related_package && e.message.name.as_deref() == Some(related_name))
+    {
+        return format!(
+            "{}, which is not an entity of package `{}`; did you mean `{}.{}` (declared in {})?",
+            prefix,
+            related_package,
+            other.package,
+            related_name,
+            other.file.name.as_deref().unwrap_or("")
+        );
+    }
+
+    if !files.iter().any(|f| f.package.as_deref().unwrap_or("") == related_package) {
+        return format!(
+            "{}, but no imported file declares package `{}`; import the file declaring {}",
+            prefix, related_package, related_name
+        );
+    }
+    format!("{}, but package `{}` has no message {}", prefix, related_package, related_name)
+}
+
+/// Check that a file lives in its package's directory, with `strict_layout=true`
+///
+/// Output paths follow the package, so `blog/v1/posts.proto` should declare
+/// `package blog.v1;`.
+fn lint_file_layout(file: &FileDescriptorProto, issues: &mut Vec<LintIssue>) {
+    if !crate::params::strict_layout() {
+        return;
+    }
+    let file_name = file.name.as_deref().unwrap_or("");
+    let package = file.package.as_deref().unwrap_or("");
+    let (directory, base_name) = file_name.rsplit_once('/').unwrap_or(("", file_name));
+    let expected = package.replace('.', "/");
+    if directory == expected {
+        return;
+    }
+
+    let moved = if expected.is_empty() {
+        base_name.to_string()
+    } else {
+        format!("{}/{}", expected, base_name)
+    };
+    let package_hint = if directory.is_empty() {
+        "remove the package".to_string()
+    } else {
+        format!("declare `package {};`", directory.replace('/', "."))
+    };
+    let place = |d: &str| if d.is_empty() { "the root".to_string() } else { format!("`{}/`", d) };
+    issues.push(LintIssue {
+        location: file_name.to_string(),
+        message: format!(
+            "package `{}` is generated under {}, but the file is in {}; move it to {} or {}",
+            package,
+            place(&expected),
+            place(directory),
+            moved,
+            package_hint
+        ),
+    });
+}
+
+/// Check that a package's entities generate distinct modules
+///
+/// Entity modules are the snake_case message names, so `ApiKey` and `APIKey`
+/// both write `entities/api_key.rs`.
+fn lint_module_collisions(package: &str, entities: &[Entity], issues: &mut Vec<LintIssue>) {
+    let entities: Vec<_> = entities.iter().filter(|e| e.package == package).collect();
+    for (i, entity) in entities.iter().enumerate() {
+        let msg_name = entity.message.name.as_deref().unwrap_or("");
+        let module = msg_name.to_snake_case();
+        let Some(first) = entities[..i]
+            .iter()
+            .find(|e| e.message.name.as_deref().unwrap_or("").to_snake_case() == module)
+        else {
+            continue;
+        };
+        issues.push(LintIssue {
+            location: location(entity.file, &[MESSAGE_TYPE, entity.index as i32]),
+            message: format!(
+                "entity {} generates `{}/entities/{}.rs`, as does {} in {}; rename one of them",
+                msg_name,
+                package.replace('.', "/"),
+                module,
+                first.message.name.as_deref().unwrap_or(""),
+                first.file.name.as_deref().unwrap_or("")
+            ),
+        });
+    }
+}
+
+/// Check that storage service methods operate on entities
+fn lint_services(file: &FileDescriptorProto, entities: &[Entity], issues: &mut Vec<LintIssue>) {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let package = file.package.as_deref().unwrap_or("");
+
+    for (service_index, service) in file.service.iter().enumerate() {
+        let svc_name = service.name.as_deref().unwrap_or("");
+        let Some(service_options) = get_cached_service_options(file_name, svc_name) else {
+            continue;
+        };
+        if service_options.skip || !service_options.generate_storage {
+            continue;
+        }
+
+        for (method_index, method) in service.method.iter().enumerate() {
+            let method_name = method.name.as_deref().unwrap_or("");
+            let method_options = get_cached_rpc_method_options(file_name, svc_name, method_name);
+            if method_options.as_ref().is_some_and(|o| o.skip) {
+                continue;
+            }
+            let entity_name = method_options
+                .filter(|o| !o.entity_name.is_empty())
+                .map(|o| o.entity_name)
+                .unwrap_or_else(|| infer_entity_name(method_name));
+
+            if find_entity(entities, package, &entity_name.to_upper_camel_case()).is_none() {
+                issues.push(LintIssue {
+                    location: location(file, &[SERVICE, service_index as i32, METHOD, method_index as i32]),
+                    message: format!(
+                        "{}.{} operates on `{}`, which is not an entity (set entity_name or skip the method)",
+                        svc_name, method_name, entity_name
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Check GraphQL object types for fields named like generated Relay fields
+///
+/// Fields that are skipped, set their own GraphQL name, or belong to a type
+/// with `rename_shadowed_fields` are fine.
+fn lint_shadowed_fields(file: &FileDescriptorProto, issues: &mut Vec<LintIssue>) {
+    let file_name = file.name.as_deref().unwrap_or("");
+
+    for (message_index, message) in file.message_type.iter().enumerate() {
+        let msg_name = message.name.as_deref().unwrap_or("");
+        let Some(type_options) = get_cached_graphql_type_options(file_name, msg_name) else {
+            continue;
+        };
+        if type_options.skip || type_options.input || type_options.rename_shadowed_fields {
+            continue;
+        }
+
+        for (field_index, field) in message.field.iter().enumerate() {
+            let field_name = field.name.as_deref().unwrap_or("");
+            if !shadows_relay_field(msg_name, type_options.node, field_name) {
+                continue;
+            }
+            let field_options = get_cached_graphql_field_options(file_name, msg_name, field.number.unwrap_or(0));
+            if field_options.is_some_and(|o| o.skip || !o.name.is_empty()) {
+                continue;
+            }
+
+            let (owner, node_hint) = if field_name == "id" {
+                ("Node types", "set node: true, ")
+            } else {
+                ("connections and edges", "")
+            };
+            issues.push(LintIssue {
+                location: location(
+                    file,
+                    &[MESSAGE_TYPE, message_index as i32, FIELD, field_index as i32],
+                ),
+                message: format!(
+                    "{}.{} shadows the `{}` field of generated {}; {}rename it with \
+                     (synapse.graphql.field).name, or set rename_shadowed_fields on the type \
+                     to expose it as `{}`",
+                    msg_name,
+                    field_name,
+                    field_name.to_lower_camel_case(),
+                    owner,
+                    node_hint,
+                    renamed_shadowed_field(msg_name, field_name).to_lower_camel_case()
+                ),
+            });
+        }
+    }
+}
+
+/// `file:line:column` of a descriptor, or the file name without source info
+fn location(file: &FileDescriptorProto, path: &[i32]) -> String {
+    let file_name = file.name.as_deref().unwrap_or("");
+    file.source_code_info
+        .as_ref()
+        .and_then(|info| info.location.iter().find(|l| l.path == path))
+        .filter(|l| l.span.len() >= 2)
+        .map(|l| format!("{}:{}:{}", file_name, l.span[0] + 1, l.span[1] + 1))
+        .unwrap_or_else(|| file_name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost_types::source_code_info::Location;
+    use prost_types::SourceCodeInfo;
+
+    fn user_entity(file: &FileDescriptorProto) -> Entity<'_> {
+        Entity {
+            package: "iam",
+            file,
+            index: 0,
+            message: &file.message_type[0],
+            options: EntityOptions::default(),
+        }
+    }
+
+    #[test]
+    fn test_location() {
+        let mut file = FileDescriptorProto {
+            name: Some("iam/entities.proto".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(location(&file, &[MESSAGE_TYPE, 0]), "iam/entities.proto");
+
+        file.source_code_info = Some(SourceCodeInfo {
+            location: vec![Location {
+                path: vec![MESSAGE_TYPE, 0],
+                span: vec![11, 0, 30, 1],
+                ..Default::default()
+            }],
+        });
+        assert_eq!(location(&file, &[MESSAGE_TYPE, 0]), "iam/entities.proto:12:1");
+        assert_eq!(location(&file, &[MESSAGE_TYPE, 1]), "iam/entities.proto");
+    }
+
+    #[test]
+    fn test_find_entity() {
+        let file = FileDescriptorProto {
+            name: Some("iam/entities.proto".to_string()),
+            package: Some("iam".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("User".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let entities = vec![user_entity(&file)];
+
+        assert!(find_entity(&entities, "iam", "User").is_some());
+        assert!(find_entity(&entities, "blog", "iam.User").is_some());
+        assert!(find_entity(&entities, "blog", ".iam.User").is_some());
+        assert!(find_entity(&entities, "blog", "User").is_none());
+        assert!(find_entity(&entities, "iam", "Team").is_none());
+    }
+
+    #[test]
+    fn test_lint_module_collisions() {
+        let file = FileDescriptorProto {
+            name: Some("iam/entities.proto".to_string()),
+            package: Some("iam".to_string()),
+            message_type: vec![
+                DescriptorProto {
+                    name: Some("ApiKey".to_string()),
+                    ..Default::default()
+                },
+                DescriptorProto {
+                    name: Some("APIKey".to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let second = Entity {
+            index: 1,
+            message: &file.message_type[1],
+            ..user_entity(&file)
+        };
+        let entities = vec![user_entity(&file), second];
+
+        let mut issues = Vec::new();
+        lint_module_collisions("iam", &entities, &mut issues);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(
+            issues[0].message,
+            "entity APIKey generates `iam/entities/api_key.rs`, as does ApiKey in iam/entities.proto; rename one of them"
+        );
+
+        let mut issues = Vec::new();
+        lint_module_collisions("iam", &entities[..1], &mut issues);
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn test_unresolved_relation() {
+        let file = FileDescriptorProto {
+            name: Some("iam/entities.proto".to_string()),
+            package: Some("iam".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("User".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let files = vec![file.clone()];
+        let entities = vec![user_entity(&file)];
+        let relation = |related: &str| RelationDef {
+            name: "author".to_string(),
+            related: related.to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            unresolved_relation("Post", "blog", &relation("User"), &files, &entities),
+            "relation Post.author references `User`, which is not an entity of package `blog`; did you mean `iam.User` (declared in iam/entities.proto)?"
+        );
+        assert_eq!(
+            unresolved_relation("Post", "blog", &relation("billing.Account"), &files, &entities),
+            "relation Post.author references `billing.Account`, but no imported file declares package `billing`; import the file declaring Account"
+        );
+        assert_eq!(
+            unresolved_relation("Post", "blog", &relation("iam.Team"), &files, &entities),
+            "relation Post.author references `iam.Team`, but package `iam` has no message Team"
+        );
+    }
+
+    #[test]
+    fn test_shadows_relay_field() {
+        assert!(shadows_relay_field("Comment", false, "node"));
+        assert!(shadows_relay_field("Comment", false, "pageInfo"));
+        assert!(shadows_relay_field("Comment", false, "id"));
+        assert!(!shadows_relay_field("Comment", true, "id"));
+        assert!(!shadows_relay_field("CommentEdge", false, "cursor"));
+        assert!(!shadows_relay_field("CommentConnection", false, "edges"));
+        assert!(!shadows_relay_field("Comment", false, "body"));
+        assert_eq!(renamed_shadowed_field("BlogComment", "node").to_lower_camel_case(), "blogCommentNode");
+    }
+}
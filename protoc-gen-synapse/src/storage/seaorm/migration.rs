@@ -0,0 +1,395 @@
+//! Migration generation
+//!
+//! With `migrations=true`, generates {package}/migration.rs: a SeaORM
+//! migration that creates every table of the package from its entity (foreign
+//! keys included, referenced tables first), the declared indexes, the
+//! full-text search columns of `searchable` entities, the event outbox of
+//! `emit_events` entities, the webhook tables of `webhooks` entities, and
+//! the Postgres extensions that `citext` and `geography` columns need. Enums are
+//! stored as string or integer columns, so they need no types of their own.
+//! `Migrator` wraps the migration for crates that have no other migrations.
+
+use super::entity::index_name;
+use super::implementation::{case_insensitive_columns, geography_columns, searchable_columns, CaseInsensitive};
+use super::join_table::collect_join_tables;
+use super::options::get_cached_entity_options;
+use super::outbox::{package_emits_events, OUTBOX_MODULE};
+use super::webhook::{package_has_webhooks, DELIVERY_MODULE, EVENT_MODULE, WEBHOOK_MODULE};
+use crate::error::GeneratorError;
+use crate::options::synapse::storage::RelationType;
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::{format_ident, quote};
+
+/// A table created by the migration
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct MigrationTable {
+    /// Entity module name
+    pub module: String,
+    /// Modules of tables its foreign keys reference
+    pub depends_on: Vec<String>,
+    /// Names of the indexes from `index_statements()`
+    pub indexes: Vec<String>,
+    /// Has a full-text search column from `search_statements()`
+    pub searchable: bool,
+}
+
+/// Entity module of a relation target in `package`, if it lives there
+fn local_module(package: &str, related: &str) -> Option<String> {
+    match related.rsplit_once('.') {
+        Some((prefix, name)) if prefix == package => Some(name.to_snake_case()),
+        Some(_) => None,
+        None => Some(related.to_snake_case()),
+    }
+}
+
+/// Tables of the package's entities and synthesized junction tables
+pub(super) fn collect_tables(
+    all_files: &[FileDescriptorProto],
+    main_file: &FileDescriptorProto,
+) -> Result<Vec<MigrationTable>, GeneratorError> {
+    let package = main_file.package.as_deref().unwrap_or("");
+    let mut tables = Vec::new();
+
+    for file in all_files.iter().filter(|f| f.package.as_deref().unwrap_or("") == package) {
+        let file_name = file.name.as_deref().unwrap_or("");
+        for message in &file.message_type {
+            let msg_name = message.name.as_deref().unwrap_or("");
+            let Some(opts) = get_cached_entity_options(file_name, msg_name) else {
+                continue;
+            };
+            if opts.skip {
+                continue;
+            }
+
+            let module = msg_name.to_snake_case();
+            let table_name = if opts.table_name.is_empty() {
+                module.clone()
+            } else {
+                opts.table_name.clone()
+            };
+            let mut depends_on = Vec::new();
+            for rel in &opts.relations {
+                if rel.r#type() != RelationType::BelongsTo {
+                    continue;
+                }
+                if let Some(target) = local_module(package, &rel.related)
+                    && target != module
+                    && !depends_on.contains(&target)
+                {
+                    depends_on.push(target);
+                }
+            }
+            tables.push(MigrationTable {
+                module,
+                depends_on,
+                indexes: opts.indexes.iter().map(|i| index_name(i, &table_name)).collect(),
+                searchable: !searchable_columns(std::slice::from_ref(file), msg_name).is_empty(),
+            });
+        }
+    }
+
+    for join in collect_join_tables(all_files, main_file)? {
+        let depends_on = join
+            .columns
+            .iter()
+            .filter_map(|c| local_module(package, &c.entity))
+            .collect();
+        tables.push(MigrationTable {
+            module: join.module,
+            depends_on,
+            indexes: Vec::new(),
+            searchable: false,
+        });
+    }
+
+    if package_emits_events(all_files, main_file) {
+        tables.push(MigrationTable {
+            module: OUTBOX_MODULE.to_string(),
+            depends_on: Vec::new(),
+            indexes: Vec::new(),
+            searchable: false,
+        });
+    }
+
+    if package_has_webhooks(all_files, main_file) {
+        tables.push(MigrationTable {
+            module: WEBHOOK_MODULE.to_string(),
+            depends_on: Vec::new(),
+            indexes: Vec::new(),
+            searchable: false,
+        });
+        tables.push(MigrationTable {
+            module: EVENT_MODULE.to_string(),
+            depends_on: Vec::new(),
+            indexes: Vec::new(),
+            searchable: false,
+        });
+        tables.push(MigrationTable {
+            module: DELIVERY_MODULE.to_string(),
+            depends_on: Vec::new(),
+            indexes: Vec::new(),
+            searchable: false,
+        });
+    }
+
+    Ok(tables)
+}
+
+/// Order tables so every table comes after the tables it references
+///
+/// Keeps declaration order where foreign keys allow it. Dependencies on
+/// tables outside the package are assumed to exist already.
+pub(super) fn order_tables(mut pending: Vec<MigrationTable>) -> Result<Vec<MigrationTable>, GeneratorError> {
+    let mut ordered: Vec<MigrationTable> = Vec::new();
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|table| {
+            table.depends_on.iter().all(|dep| {
+                ordered.iter().any(|t| &t.module == dep) || !pending.iter().any(|t| &t.module == dep)
+            })
+        });
+        match ready {
+            Some(index) => ordered.push(pending.remove(index)),
+            None => {
+                let cycle: Vec<_> = pending.iter().map(|t| t.module.as_str()).collect();
+                return Err(GeneratorError::CodeGenError(format!(
+                    "cannot order migration: foreign keys form a cycle between {}",
+                    cycle.join(", ")
+                )));
+            }
+        }
+    }
+    Ok(ordered)
+}
+
+/// Postgres extensions needed by the package's columns
+fn required_extensions(all_files: &[FileDescriptorProto], tables: &[MigrationTable]) -> Vec<&'static str> {
+    let entities: Vec<String> = all_files
+        .iter()
+        .flat_map(|f| f.message_type.iter())
+        .filter_map(|m| m.name.clone())
+        .filter(|name| tables.iter().any(|t| t.module == name.to_snake_case()))
+        .collect();
+
+    let mut extensions = Vec::new();
+    let citext = entities.iter().any(|e| {
+        case_insensitive_columns(all_files, e)
+            .iter()
+            .any(|(_, ci)| *ci == CaseInsensitive::Citext)
+    });
+    if citext {
+        extensions.push("citext");
+    }
+    if entities.iter().any(|e| !geography_columns(all_files, e).is_empty()) {
+        extensions.push("postgis");
+    }
+    extensions
+}
+
+/// Generate migration.rs for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || !crate::params::migrations() {
+        return Ok(None);
+    }
+
+    let tables = order_tables(collect_tables(all_files, file)?)?;
+    if tables.is_empty() {
+        return Ok(None);
+    }
+    let extensions = required_extensions(all_files, &tables);
+    let code = generate_migration(package, &tables, &extensions);
+
+    Ok(Some(File {
+        name: Some(format!("{}/migration.rs", package.replace('.', "/"))),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    }))
+}
+
+/// Generate the migration and migrator for ordered tables
+fn generate_migration(package: &str, tables: &[MigrationTable], extensions: &[&str]) -> TokenStream {
+    let name = format!("m0001_create_{}", package.replace('.', "_"));
+    let doc = format!("Creates the tables of the {} package", package);
+
+    let create_extensions = if extensions.is_empty() {
+        quote! {}
+    } else {
+        let statements = extensions
+            .iter()
+            .map(|ext| format!("CREATE EXTENSION IF NOT EXISTS {}", ext));
+        quote! {
+            if backend == sea_orm::DbBackend::Postgres {
+                #(manager.get_connection().execute_unprepared(#statements).await?;)*
+            }
+        }
+    };
+
+    let create_tables = tables.iter().map(|t| {
+        let module = format_ident!("{}", t.module);
+        quote! {
+            manager
+                .create_table(
+                    schema
+                        .create_table_from_entity(entities::#module::Entity)
+                        .if_not_exists()
+                        .to_owned(),
+                )
+                .await?;
+        }
+    });
+    let create_indexes = tables.iter().filter(|t| !t.indexes.is_empty()).map(|t| {
+        let module = format_ident!("{}", t.module);
+        quote! {
+            for statement in entities::#module::Entity::index_statements() {
+                manager.create_index(statement).await?;
+            }
+        }
+    });
+    // Full-text search columns are Postgres generated columns
+    let search_tables: Vec<_> = tables
+        .iter()
+        .filter(|t| t.searchable)
+        .map(|t| format_ident!("{}", t.module))
+        .collect();
+    let create_search = if search_tables.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            if backend == sea_orm::DbBackend::Postgres {
+                #(
+                    for statement in entities::#search_tables::Entity::search_statements() {
+                        manager.get_connection().execute_unprepared(statement).await?;
+                    }
+                )*
+            }
+        }
+    };
+    let drop_tables = tables.iter().rev().map(|t| {
+        let module = format_ident!("{}", t.module);
+        quote! {
+            manager
+                .drop_table(Table::drop().table(entities::#module::Entity).if_exists().to_owned())
+                .await?;
+        }
+    });
+
+    quote! {
+        //! Schema migration derived from the entities
+        //!
+        //! Tables are created referenced-first so foreign keys resolve, and
+        //! dropped in reverse order.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use sea_orm::{ConnectionTrait, Schema};
+        use sea_orm_migration::prelude::*;
+
+        use super::entities;
+
+        #[doc = #doc]
+        pub struct Migration;
+
+        impl MigrationName for Migration {
+            fn name(&self) -> &str {
+                #name
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl MigrationTrait for Migration {
+            async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+                let backend = manager.get_database_backend();
+                #create_extensions
+                let schema = Schema::new(backend);
+                #(#create_tables)*
+                #(#create_indexes)*
+                #create_search
+                Ok(())
+            }
+
+            async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+                #(#drop_tables)*
+                Ok(())
+            }
+        }
+
+        /// Migrator running this package's migration
+        pub struct Migrator;
+
+        #[async_trait::async_trait]
+        impl MigratorTrait for Migrator {
+            fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+                vec![Box::new(Migration)]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(module: &str, depends_on: &[&str]) -> MigrationTable {
+        MigrationTable {
+            module: module.to_string(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            indexes: Vec::new(),
+            searchable: false,
+        }
+    }
+
+    #[test]
+    fn test_order_tables() {
+        let tables = vec![
+            table("comment", &["post", "user"]),
+            table("post", &["user", "organization"]),
+            table("user", &[]),
+        ];
+        let ordered: Vec<_> = order_tables(tables).unwrap().into_iter().map(|t| t.module).collect();
+        // organization lives in another package and is assumed to exist
+        assert_eq!(ordered, ["user", "post", "comment"]);
+    }
+
+    #[test]
+    fn test_order_tables_cycle() {
+        let tables = vec![table("a", &["b"]), table("b", &["a"])];
+        let err = order_tables(tables).unwrap_err().to_string();
+        assert!(err.contains("cycle between a, b"));
+    }
+
+    #[test]
+    fn test_local_module() {
+        assert_eq!(local_module("blog", "User").as_deref(), Some("user"));
+        assert_eq!(local_module("blog", "blog.BlogPost").as_deref(), Some("blog_post"));
+        assert_eq!(local_module("blog", "auth.User"), None);
+    }
+
+    #[test]
+    fn test_generate_migration() {
+        let mut post = table("post", &["user"]);
+        post.indexes = vec!["idx_post_title".to_string()];
+        post.searchable = true;
+        let tables = vec![table("user", &[]), post];
+        let code = generate_migration("blog", &tables, &["citext"]).to_string();
+        assert!(code.contains("\"m0001_create_blog\""));
+        assert!(code.contains("CREATE EXTENSION IF NOT EXISTS citext"));
+        let user = code.find("create_table_from_entity (entities :: user :: Entity)").unwrap();
+        let post = code.find("create_table_from_entity (entities :: post :: Entity)").unwrap();
+        assert!(user < post);
+        assert!(code.contains("entities :: post :: Entity :: index_statements ()"));
+        assert!(!code.contains("entities :: user :: Entity :: index_statements ()"));
+        assert!(code.contains("entities :: post :: Entity :: search_statements ()"));
+        let drop_post = code.find("table (entities :: post :: Entity) . if_exists ()").unwrap();
+        let drop_user = code.find("table (entities :: user :: Entity) . if_exists ()").unwrap();
+        assert!(drop_post < drop_user);
+        assert!(code.contains("impl MigratorTrait for Migrator"));
+    }
+}
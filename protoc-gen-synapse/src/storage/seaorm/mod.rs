@@ -2,14 +2,36 @@
 //!
 //! Generates SeaORM 2.0 entities with dense format from protobuf definitions.
 
+mod api;
+mod baseline;
+pub mod batch;
 mod column;
 pub mod conversion;
+mod diff;
+mod dto;
 mod entity;
 mod enum_gen;
+pub mod expand;
+mod export;
+pub mod external_id;
+mod factory;
+mod join_table;
+mod lint;
+mod migration;
+mod names;
 pub mod generator;
 pub mod implementation;
+mod ir;
 mod oneof;
+pub mod outbox;
 pub mod options;
 pub mod package;
+mod registry;
 mod relation;
+mod report;
+mod row_cache;
+mod schema_diff;
+pub mod search;
+mod seed;
 mod types;
+mod webhook;
@@ -0,0 +1,130 @@
+//! Name mapping report generation
+//!
+//! With `graphql_names=explicit`, generates {package}/names.json: for each
+//! entity field, its proto name, proto3 JSON name, GraphQL name and database
+//! column, each after its override (`json_name`, `(synapse.graphql.field).name`,
+//! `(synapse.storage.column).column_name`). GraphQL names come from the same
+//! function that names the generated resolvers, so the report can be checked
+//! against client queries and docs instead of re-deriving snake/camel cases.
+
+use super::ir::Json;
+use super::options::{get_cached_column_options, get_cached_entity_options};
+use crate::error::GeneratorError;
+use heck::{ToLowerCamelCase, ToSnakeCase};
+use prost_types::compiler::code_generator_response::File;
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+
+/// Generate names.json for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || !crate::params::graphql_explicit_names() {
+        return Ok(None);
+    }
+
+    let mut entities = Vec::new();
+    for proto_file in all_files {
+        if proto_file.package.as_deref().unwrap_or("") != package {
+            continue;
+        }
+        let file_name = proto_file.name.as_deref().unwrap_or("");
+        for message in &proto_file.message_type {
+            let msg_name = message.name.as_deref().unwrap_or("");
+            let Some(entity_options) = get_cached_entity_options(file_name, msg_name).filter(|o| !o.skip) else {
+                continue;
+            };
+            let table = if entity_options.table_name.is_empty() {
+                msg_name.to_snake_case()
+            } else {
+                entity_options.table_name.clone()
+            };
+            entities.push(entity_names(file_name, message, &table));
+        }
+    }
+
+    if entities.is_empty() {
+        return Ok(None);
+    }
+
+    let mut content = String::new();
+    Json::object(vec![
+        ("package", Json::string(package)),
+        ("entities", Json::Array(entities)),
+    ])
+    .write(&mut content, 0);
+    content.push('\n');
+
+    Ok(Some(File {
+        name: Some(format!("{}/names.json", package.replace('.', "/"))),
+        content: Some(content),
+        ..Default::default()
+    }))
+}
+
+/// The name map of one entity
+fn entity_names(file_name: &str, message: &DescriptorProto, table: &str) -> Json {
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let graphql_names = crate::graphql::object_field_graphql_names(file_name, message);
+    let fields = message
+        .field
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let graphql = graphql_names.as_ref().and_then(|names| names[i].as_deref());
+            field_names(file_name, msg_name, field, graphql)
+        })
+        .collect();
+
+    Json::object(vec![
+        ("message", Json::string(msg_name)),
+        ("table", Json::string(table)),
+        ("fields", Json::Array(fields)),
+    ])
+}
+
+/// The names of one field; `graphql` is `None` when it isn't exposed
+fn field_names(file_name: &str, msg_name: &str, field: &FieldDescriptorProto, graphql: Option<&str>) -> Json {
+    let name = field.name.as_deref().unwrap_or("");
+    let json = field
+        .json_name
+        .clone()
+        .unwrap_or_else(|| name.to_lower_camel_case());
+    let column = get_cached_column_options(file_name, msg_name, field.number.unwrap_or(0))
+        .map(|o| o.column_name)
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| name.to_snake_case());
+
+    Json::object(vec![
+        ("proto", Json::string(name)),
+        ("json", Json::string(&json)),
+        ("graphql", graphql.map_or(Json::Null, Json::string)),
+        ("column", Json::string(&column)),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_names() {
+        let field = FieldDescriptorProto {
+            name: Some("author_id".to_string()),
+            number: Some(3),
+            json_name: Some("writer".to_string()),
+            ..Default::default()
+        };
+        let mut out = String::new();
+        field_names("blog/names_test.proto", "Post", &field, Some("authorRef")).write(&mut out, 0);
+        assert!(out.contains("\"proto\": \"author_id\""));
+        assert!(out.contains("\"json\": \"writer\""));
+        assert!(out.contains("\"graphql\": \"authorRef\""));
+        assert!(out.contains("\"column\": \"author_id\""));
+
+        let mut out = String::new();
+        field_names("blog/names_test.proto", "Post", &field, None).write(&mut out, 0);
+        assert!(out.contains("\"graphql\": null"));
+    }
+}
@@ -11,14 +11,15 @@
 
 pub use crate::options::synapse::storage;
 pub use crate::options::synapse::{graphql, grpc, validate};
+use heck::ToSnakeCase;
 use once_cell::sync::Lazy;
-use prost_reflect::{DescriptorPool, DynamicMessage, Value};
+use prost_reflect::{DescriptorPool, DynamicMessage, MapKey, Value};
 use prost_types::{
     DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
     ServiceDescriptorProto, UninterpretedOption,
 };
-use std::collections::HashMap;
-use std::sync::RwLock;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, RwLock};
 
 /// File descriptor set bytes generated at build time by protoc
 static FILE_DESCRIPTOR_SET_BYTES: &[u8] =
@@ -48,6 +49,7 @@ const GRAPHQL_SERVICE_EXTENSION_NAME: &str = "synapse.graphql.service";
 const GRAPHQL_QUERY_EXTENSION_NAME: &str = "synapse.graphql.query";
 const GRAPHQL_MUTATION_EXTENSION_NAME: &str = "synapse.graphql.mutation";
 const GRAPHQL_SUBSCRIPTION_EXTENSION_NAME: &str = "synapse.graphql.subscription";
+const GRAPHQL_FILE_EXTENSION_NAME: &str = "synapse.graphql.file";
 
 /// Lazily initialized descriptor pool with our extension definitions
 static DESCRIPTOR_POOL: Lazy<DescriptorPool> = Lazy::new(|| {
@@ -61,6 +63,8 @@ static OPTIONS_CACHE: Lazy<RwLock<OptionsCache>> =
 /// Cache structure holding pre-parsed options
 #[derive(Default)]
 struct OptionsCache {
+    /// Packages: file_name -> package
+    packages: HashMap<String, String>,
     /// Entity options: (file_name, message_name) -> EntityOptions
     entity_options: HashMap<(String, String), storage::EntityOptions>,
     /// Column options: (file_name, message_name, field_number) -> ColumnOptions
@@ -95,6 +99,41 @@ struct OptionsCache {
     graphql_mutation_options: HashMap<(String, String, String), graphql::MutationOptions>,
     /// GraphQL subscription options: (file_name, service_name, method_name) -> graphql::SubscriptionOptions
     graphql_subscription_options: HashMap<(String, String, String), graphql::SubscriptionOptions>,
+    /// GraphQL file options: file_name -> graphql::FileOptions
+    graphql_file_options: HashMap<String, graphql::FileOptions>,
+}
+
+/// Options cache lookups per extension: (hits, misses)
+static CACHE_STATS: Lazy<Mutex<BTreeMap<&'static str, (u64, u64)>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Count a cache lookup for the generation report
+fn record_lookup<T>(extension: &'static str, options: Option<T>) -> Option<T> {
+    if let Ok(mut stats) = CACHE_STATS.lock() {
+        let (hits, misses) = stats.entry(extension).or_default();
+        if options.is_some() {
+            *hits += 1;
+        } else {
+            *misses += 1;
+        }
+    }
+    options
+}
+
+/// Options cache lookups so far, as (extension, hits, misses)
+pub fn cache_stats() -> Vec<(&'static str, u64, u64)> {
+    CACHE_STATS
+        .lock()
+        .map(|stats| stats.iter().map(|(ext, (hits, misses))| (*ext, *hits, *misses)).collect())
+        .unwrap_or_default()
+}
+
+/// Re-decode a parsed options message as a DynamicMessage of its proto type
+///
+/// `full_name` is the proto message name, e.g. `synapse.storage.EntityOptions`.
+/// Lets callers walk every set field generically (the IR dump does).
+pub fn to_dynamic_message(full_name: &str, message: &impl prost::Message) -> Option<DynamicMessage> {
+    let desc = DESCRIPTOR_POOL.get_message_by_name(full_name)?;
+    DynamicMessage::decode(desc, message.encode_to_vec().as_slice()).ok()
 }
 
 /// Pre-process raw CodeGeneratorRequest bytes to extract options using prost-reflect
@@ -125,10 +164,41 @@ pub fn preprocess_request_bytes(bytes: &[u8]) -> Result<(), String> {
             }
         }
     }
+    add_inverse_relations(&mut cache);
 
     Ok(())
 }
 
+/// Parse the entity options of a serialized FileDescriptorSet
+///
+/// Used for baseline descriptor sets, whose options are not part of the
+/// request: returns (file_name, message_name) -> EntityOptions for top-level
+/// messages.
+pub fn parse_descriptor_set_entities(
+    bytes: &[u8],
+) -> Result<HashMap<(String, String), storage::EntityOptions>, String> {
+    let set_desc = DESCRIPTOR_POOL
+        .get_message_by_name("google.protobuf.FileDescriptorSet")
+        .ok_or("FileDescriptorSet not found in descriptor pool")?;
+
+    let set = DynamicMessage::decode(set_desc, bytes)
+        .map_err(|e| format!("Failed to decode FileDescriptorSet: {}", e))?;
+
+    let mut cache = OptionsCache::default();
+    if let Some(cow) = set.get_field_by_name("file") {
+        if let Value::List(files) = cow.as_ref() {
+            for file_value in files.iter() {
+                if let Some(file_msg) = file_value.as_message() {
+                    extract_options_from_file(&mut cache, file_msg)?;
+                }
+            }
+        }
+    }
+    add_inverse_relations(&mut cache);
+
+    Ok(cache.entity_options)
+}
+
 /// Extract options from a FileDescriptorProto DynamicMessage
 fn extract_options_from_file(
     cache: &mut OptionsCache,
@@ -138,6 +208,25 @@ fn extract_options_from_file(
         .get_field_by_name("name")
         .and_then(|v| v.as_ref().as_str().map(|s| s.to_string()))
         .unwrap_or_default();
+    let package = file
+        .get_field_by_name("package")
+        .and_then(|v| v.as_ref().as_str().map(|s| s.to_string()))
+        .unwrap_or_default();
+    cache.packages.insert(file_name.clone(), package);
+
+    // Extract file-level options (synapse.graphql.file)
+    if let Some(cow) = file.get_field_by_name("options") {
+        if let Some(opts_msg) = cow.as_ref().as_message() {
+            if let Some(ext_field) = DESCRIPTOR_POOL.get_extension_by_name(GRAPHQL_FILE_EXTENSION_NAME) {
+                if opts_msg.has_extension(&ext_field) {
+                    let ext_value = opts_msg.get_extension(&ext_field);
+                    if let Some(file_opts) = convert_to_graphql_file_options(&ext_value) {
+                        cache.graphql_file_options.insert(file_name.clone(), file_opts);
+                    }
+                }
+            }
+        }
+    }
 
     // Extract message options
     if let Some(cow) = file.get_field_by_name("message_type") {
@@ -175,6 +264,81 @@ fn extract_options_from_file(
     Ok(())
 }
 
+/// Declare the HAS_MANY inverse of each `inverse` BELONGS_TO relation on its
+/// related entity
+///
+/// Only entities of the same package are extended. A relation the related
+/// entity already declares under the inverse name is left as is.
+fn add_inverse_relations(cache: &mut OptionsCache) {
+    let mut inverses = Vec::new();
+    for ((file_name, msg_name), options) in &cache.entity_options {
+        let package = cache.packages.get(file_name).map(String::as_str).unwrap_or("");
+        for relation in &options.relations {
+            if !relation.inverse || relation.r#type() != storage::RelationType::BelongsTo {
+                continue;
+            }
+            let Some(related) = same_package_name(package, &relation.related) else {
+                continue;
+            };
+            let target = cache.entity_options.keys().find(|(f, m)| {
+                m == related && cache.packages.get(f).map(String::as_str).unwrap_or("") == package
+            });
+            let Some(target) = target else {
+                continue;
+            };
+            let name = if relation.inverse_name.is_empty() {
+                plural(&msg_name.to_snake_case())
+            } else {
+                relation.inverse_name.clone()
+            };
+            inverses.push((
+                target.clone(),
+                storage::RelationDef {
+                    name,
+                    r#type: storage::RelationType::HasMany as i32,
+                    related: msg_name.clone(),
+                    foreign_key: relation.foreign_key.clone(),
+                    ..Default::default()
+                },
+            ));
+        }
+    }
+
+    // Map iteration order is arbitrary; keep the generated relations stable
+    inverses.sort_by(|(a, x), (b, y)| (a, &x.name).cmp(&(b, &y.name)));
+    for (target, relation) in inverses {
+        if let Some(options) = cache.entity_options.get_mut(&target)
+            && !options.relations.iter().any(|r| r.name == relation.name)
+        {
+            options.relations.push(relation);
+        }
+    }
+}
+
+/// A relation's `related` name within `package`, `None` for other packages
+///
+/// Accepts `Post`, `blog.Post` and `.blog.Post`.
+pub fn same_package_name<'a>(package: &str, related: &'a str) -> Option<&'a str> {
+    let related = related.trim_start_matches('.');
+    match related.rsplit_once('.') {
+        Some((related_package, name)) => (related_package == package).then_some(name),
+        None => Some(related),
+    }
+}
+
+/// English plural of a snake_case name, for default relation names
+fn plural(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix('y')
+        && !stem.ends_with(['a', 'e', 'i', 'o', 'u'])
+    {
+        format!("{}ies", stem)
+    } else if name.ends_with(['s', 'x', 'z']) || name.ends_with("ch") || name.ends_with("sh") {
+        format!("{}es", name)
+    } else {
+        format!("{}s", name)
+    }
+}
+
 /// Extract options from a DescriptorProto DynamicMessage
 fn extract_message_options(
     cache: &mut OptionsCache,
@@ -646,12 +810,30 @@ pub fn get_cached_entity_options(
     file_name: &str,
     msg_name: &str,
 ) -> Option<storage::EntityOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .entity_options
             .get(&(file_name.to_string(), msg_name.to_string()))
             .cloned()
-    })
+    });
+    record_lookup(ENTITY_EXTENSION_NAME, options)
+}
+
+/// Look up cached entity options for a message declared in any file of `package`
+pub fn get_cached_entity_options_in_package(
+    package: &str,
+    msg_name: &str,
+) -> Option<storage::EntityOptions> {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
+        cache
+            .entity_options
+            .iter()
+            .find(|((file, msg), _)| {
+                msg == msg_name && cache.packages.get(file).map(String::as_str).unwrap_or("") == package
+            })
+            .map(|(_, options)| options.clone())
+    });
+    record_lookup(ENTITY_EXTENSION_NAME, options)
 }
 
 /// Look up cached column options for a given file, message name, and field number
@@ -660,12 +842,48 @@ pub fn get_cached_column_options(
     msg_name: &str,
     field_number: i32,
 ) -> Option<storage::ColumnOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .column_options
             .get(&(file_name.to_string(), msg_name.to_string(), field_number))
             .cloned()
-    })
+    });
+    record_lookup(COLUMN_EXTENSION_NAME, options)
+}
+
+/// Cache entity options directly, for tests that exercise option lookups
+#[cfg(test)]
+pub fn cache_entity_options(file_name: &str, msg_name: &str, options: storage::EntityOptions) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .entity_options
+        .insert((file_name.to_string(), msg_name.to_string()), options);
+}
+
+/// Record the package of a file directly, for tests of package-wide lookups
+#[cfg(test)]
+pub fn cache_file_package(file_name: &str, package: &str) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .packages
+        .insert(file_name.to_string(), package.to_string());
+}
+
+/// Cache column options directly, for tests that exercise option lookups
+#[cfg(test)]
+pub fn cache_column_options(
+    file_name: &str,
+    msg_name: &str,
+    field_number: i32,
+    options: storage::ColumnOptions,
+) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .column_options
+        .insert((file_name.to_string(), msg_name.to_string(), field_number), options);
 }
 
 /// Look up cached enum options for a given file and enum name
@@ -673,12 +891,13 @@ pub fn get_cached_enum_options(
     file_name: &str,
     enum_name: &str,
 ) -> Option<storage::EnumOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .enum_options
             .get(&(file_name.to_string(), enum_name.to_string()))
             .cloned()
-    })
+    });
+    record_lookup(ENUM_EXTENSION_NAME, options)
 }
 
 /// Look up cached enum value options for a given file, enum name, and value number
@@ -687,12 +906,13 @@ pub fn get_cached_enum_value_options(
     enum_name: &str,
     value_number: i32,
 ) -> Option<storage::EnumValueOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .enum_value_options
             .get(&(file_name.to_string(), enum_name.to_string(), value_number))
             .cloned()
-    })
+    });
+    record_lookup(ENUM_VALUE_EXTENSION_NAME, options)
 }
 
 /// Look up cached service options for a given file and service name
@@ -700,12 +920,13 @@ pub fn get_cached_service_options(
     file_name: &str,
     service_name: &str,
 ) -> Option<storage::ServiceOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .service_options
             .get(&(file_name.to_string(), service_name.to_string()))
             .cloned()
-    })
+    });
+    record_lookup(SERVICE_EXTENSION_NAME, options)
 }
 
 /// Parse storage service options from a ServiceDescriptorProto
@@ -722,7 +943,7 @@ pub fn get_cached_rpc_method_options(
     service_name: &str,
     method_name: &str,
 ) -> Option<storage::MethodOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .method_options
             .get(&(
@@ -731,7 +952,48 @@ pub fn get_cached_rpc_method_options(
                 method_name.to_string(),
             ))
             .cloned()
-    })
+    });
+    record_lookup(METHOD_EXTENSION_NAME, options)
+}
+
+/// Cache method options directly, for tests that exercise option lookups
+#[cfg(test)]
+pub fn cache_rpc_method_options(
+    file_name: &str,
+    service_name: &str,
+    method_name: &str,
+    options: storage::MethodOptions,
+) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .method_options
+        .insert((file_name.to_string(), service_name.to_string(), method_name.to_string()), options);
+}
+
+/// Cache gRPC service options directly, for tests that exercise option lookups
+#[cfg(test)]
+pub fn cache_grpc_service_options(file_name: &str, service_name: &str, options: grpc::ServiceOptions) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .grpc_service_options
+        .insert((file_name.to_string(), service_name.to_string()), options);
+}
+
+/// Cache GraphQL mutation options directly, for tests that exercise option lookups
+#[cfg(test)]
+pub fn cache_graphql_mutation_options(
+    file_name: &str,
+    service_name: &str,
+    method_name: &str,
+    options: graphql::MutationOptions,
+) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .graphql_mutation_options
+        .insert((file_name.to_string(), service_name.to_string(), method_name.to_string()), options);
 }
 
 /// Look up cached gRPC service options for a given file and service name
@@ -739,12 +1001,13 @@ pub fn get_cached_grpc_service_options(
     file_name: &str,
     service_name: &str,
 ) -> Option<grpc::ServiceOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .grpc_service_options
             .get(&(file_name.to_string(), service_name.to_string()))
             .cloned()
-    })
+    });
+    record_lookup(GRPC_SERVICE_EXTENSION_NAME, options)
 }
 
 /// Look up cached gRPC method options for a given file, service, and method name
@@ -753,7 +1016,7 @@ pub fn get_cached_grpc_method_options(
     service_name: &str,
     method_name: &str,
 ) -> Option<grpc::MethodOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .grpc_method_options
             .get(&(
@@ -762,7 +1025,8 @@ pub fn get_cached_grpc_method_options(
                 method_name.to_string(),
             ))
             .cloned()
-    })
+    });
+    record_lookup(GRPC_METHOD_EXTENSION_NAME, options)
 }
 
 /// Look up cached validate message options for a given file and message name
@@ -770,12 +1034,13 @@ pub fn get_cached_validate_message_options(
     file_name: &str,
     msg_name: &str,
 ) -> Option<validate::MessageOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .validate_message_options
             .get(&(file_name.to_string(), msg_name.to_string()))
             .cloned()
-    })
+    });
+    record_lookup(VALIDATE_MESSAGE_EXTENSION_NAME, options)
 }
 
 /// Look up cached gRPC response options for a given file and message name
@@ -783,12 +1048,13 @@ pub fn get_cached_grpc_response_options(
     file_name: &str,
     msg_name: &str,
 ) -> Option<grpc::ResponseOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .grpc_response_options
             .get(&(file_name.to_string(), msg_name.to_string()))
             .cloned()
-    })
+    });
+    record_lookup(GRPC_RESPONSE_EXTENSION_NAME, options)
 }
 
 /// Look up cached validate field options for a given file, message name, and field number
@@ -797,12 +1063,73 @@ pub fn get_cached_validate_field_options(
     msg_name: &str,
     field_number: i32,
 ) -> Option<validate::FieldOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .validate_field_options
             .get(&(file_name.to_string(), msg_name.to_string(), field_number))
             .cloned()
-    })
+    });
+    record_lookup(VALIDATE_FIELD_EXTENSION_NAME, options)
+}
+
+/// Cache validate field options directly, for tests that exercise option lookups
+#[cfg(test)]
+pub fn cache_validate_field_options(
+    file_name: &str,
+    msg_name: &str,
+    field_number: i32,
+    options: validate::FieldOptions,
+) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .validate_field_options
+        .insert((file_name.to_string(), msg_name.to_string(), field_number), options);
+}
+
+/// Cache GraphQL type options directly, for tests that exercise option lookups
+#[cfg(test)]
+pub fn cache_graphql_type_options(file_name: &str, msg_name: &str, options: graphql::TypeOptions) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .graphql_type_options
+        .insert((file_name.to_string(), msg_name.to_string()), options);
+}
+
+/// Cache GraphQL field options directly, for tests that exercise option lookups
+#[cfg(test)]
+pub fn cache_graphql_field_options(
+    file_name: &str,
+    msg_name: &str,
+    field_number: i32,
+    options: graphql::FieldOptions,
+) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .graphql_field_options
+        .insert((file_name.to_string(), msg_name.to_string(), field_number), options);
+}
+
+/// Cache validate message options directly, for tests that exercise option lookups
+#[cfg(test)]
+pub fn cache_validate_message_options(file_name: &str, msg_name: &str, options: validate::MessageOptions) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .validate_message_options
+        .insert((file_name.to_string(), msg_name.to_string()), options);
+}
+
+/// Cache GraphQL file options directly, for tests of package-wide lookups
+#[cfg(test)]
+pub fn cache_graphql_file_options(file_name: &str, options: graphql::FileOptions) {
+    OPTIONS_CACHE
+        .write()
+        .expect("options cache lock")
+        .graphql_file_options
+        .insert(file_name.to_string(), options);
 }
 
 /// Look up cached GraphQL type options for a given file and message name
@@ -811,12 +1138,27 @@ pub fn get_cached_graphql_type_options(
     file_name: &str,
     msg_name: &str,
 ) -> Option<graphql::TypeOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .graphql_type_options
             .get(&(file_name.to_string(), msg_name.to_string()))
             .cloned()
-    })
+    });
+    record_lookup(GRAPHQL_TYPE_EXTENSION_NAME, options)
+}
+
+/// Look up cached GraphQL type options for a message declared in any file of `package`
+pub fn get_cached_graphql_type_options_in_package(package: &str, msg_name: &str) -> Option<graphql::TypeOptions> {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
+        cache
+            .graphql_type_options
+            .iter()
+            .find(|((file, msg), _)| {
+                msg == msg_name && cache.packages.get(file).map(String::as_str).unwrap_or("") == package
+            })
+            .map(|(_, options)| options.clone())
+    });
+    record_lookup(GRAPHQL_TYPE_EXTENSION_NAME, options)
 }
 
 /// Look up cached GraphQL field options for a given file, message name, and field number
@@ -826,12 +1168,13 @@ pub fn get_cached_graphql_field_options(
     msg_name: &str,
     field_number: i32,
 ) -> Option<graphql::FieldOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .graphql_field_options
             .get(&(file_name.to_string(), msg_name.to_string(), field_number))
             .cloned()
-    })
+    });
+    record_lookup(GRAPHQL_FIELD_EXTENSION_NAME, options)
 }
 
 /// Look up cached GraphQL service options for a given file and service name
@@ -840,12 +1183,32 @@ pub fn get_cached_graphql_service_options(
     file_name: &str,
     service_name: &str,
 ) -> Option<graphql::ServiceOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .graphql_service_options
             .get(&(file_name.to_string(), service_name.to_string()))
             .cloned()
-    })
+    });
+    record_lookup(GRAPHQL_SERVICE_EXTENSION_NAME, options)
+}
+
+/// Global id prefixes set by any file of `package`, as (type name, prefix)
+///
+/// Sorted by type name so the generated registry is stable.
+pub fn get_cached_graphql_id_prefixes(package: &str) -> Vec<(String, String)> {
+    let options: Option<Vec<(String, String)>> = OPTIONS_CACHE.read().ok().map(|cache| {
+        let mut prefixes: Vec<(String, String)> = cache
+            .graphql_file_options
+            .iter()
+            .filter(|(file, _)| cache.packages.get(*file).map(String::as_str).unwrap_or("") == package)
+            .flat_map(|(_, options)| options.id_prefixes.iter())
+            .map(|p| (p.r#type.clone(), p.prefix.clone()))
+            .collect();
+        prefixes.sort();
+        prefixes.dedup();
+        prefixes
+    });
+    record_lookup(GRAPHQL_FILE_EXTENSION_NAME, options.filter(|p| !p.is_empty())).unwrap_or_default()
 }
 
 /// Look up cached GraphQL query options for a given file, service, and method name
@@ -855,7 +1218,7 @@ pub fn get_cached_graphql_query_options(
     service_name: &str,
     method_name: &str,
 ) -> Option<graphql::QueryOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .graphql_query_options
             .get(&(
@@ -864,7 +1227,8 @@ pub fn get_cached_graphql_query_options(
                 method_name.to_string(),
             ))
             .cloned()
-    })
+    });
+    record_lookup(GRAPHQL_QUERY_EXTENSION_NAME, options)
 }
 
 /// Look up cached GraphQL mutation options for a given file, service, and method name
@@ -874,7 +1238,7 @@ pub fn get_cached_graphql_mutation_options(
     service_name: &str,
     method_name: &str,
 ) -> Option<graphql::MutationOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .graphql_mutation_options
             .get(&(
@@ -883,7 +1247,8 @@ pub fn get_cached_graphql_mutation_options(
                 method_name.to_string(),
             ))
             .cloned()
-    })
+    });
+    record_lookup(GRAPHQL_MUTATION_EXTENSION_NAME, options)
 }
 
 /// Look up cached GraphQL subscription options for a given file, service, and method name
@@ -893,7 +1258,7 @@ pub fn get_cached_graphql_subscription_options(
     service_name: &str,
     method_name: &str,
 ) -> Option<graphql::SubscriptionOptions> {
-    OPTIONS_CACHE.read().ok().and_then(|cache| {
+    let options = OPTIONS_CACHE.read().ok().and_then(|cache| {
         cache
             .graphql_subscription_options
             .get(&(
@@ -902,7 +1267,8 @@ pub fn get_cached_graphql_subscription_options(
                 method_name.to_string(),
             ))
             .cloned()
-    })
+    });
+    record_lookup(GRAPHQL_SUBSCRIPTION_EXTENSION_NAME, options)
 }
 
 // =============================================================================
@@ -966,6 +1332,189 @@ fn convert_to_entity_options(value: &Value) -> Option<storage::EntityOptions> {
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("encrypted_export") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.encrypted_export = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("soft_delete") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.soft_delete = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("timestamps") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.timestamps = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("indexes") {
+        if let Value::List(list) = cow.as_ref() {
+            for item in list.iter() {
+                if let Some(index) = convert_to_index_def(item) {
+                    result.indexes.push(index);
+                }
+            }
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("searchable") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.searchable = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("public_feed") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.public_feed = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("dto") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.dto = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("compare_by") {
+        if let Value::EnumNumber(n) = cow.as_ref() {
+            result.compare_by = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("emit_events") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.emit_events = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("webhooks") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.webhooks = *b;
+        }
+    }
+
+    if msg.has_field_by_name("row_cache") {
+        if let Some(cow) = msg.get_field_by_name("row_cache") {
+            result.row_cache = convert_to_row_cache(cow.as_ref());
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("rate_limits") {
+        if let Value::List(list) = cow.as_ref() {
+            for item in list.iter() {
+                if let Some(limit) = convert_to_rate_limit(item) {
+                    result.rate_limits.push(limit);
+                }
+            }
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("seeds") {
+        if let Value::List(list) = cow.as_ref() {
+            for item in list.iter() {
+                if let Some(row) = convert_to_seed_row(item) {
+                    result.seeds.push(row);
+                }
+            }
+        }
+    }
+
+    Some(result)
+}
+
+/// Convert a prost-reflect Value to a SeedRow
+fn convert_to_seed_row(value: &Value) -> Option<storage::SeedRow> {
+    let msg = value.as_message()?;
+    let mut result = storage::SeedRow::default();
+
+    if let Some(cow) = msg.get_field_by_name("values") {
+        if let Value::Map(map) = cow.as_ref() {
+            for (key, value) in map {
+                if let (MapKey::String(k), Value::String(v)) = (key, value) {
+                    result.values.insert(k.clone(), v.clone());
+                }
+            }
+        }
+    }
+
+    Some(result)
+}
+
+/// Convert a prost-reflect Value to a RowCache
+fn convert_to_row_cache(value: &Value) -> Option<storage::RowCache> {
+    let msg = value.as_message()?;
+    let mut result = storage::RowCache::default();
+
+    if let Some(cow) = msg.get_field_by_name("size") {
+        if let Value::U32(n) = cow.as_ref() {
+            result.size = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("ttl_seconds") {
+        if let Value::U32(n) = cow.as_ref() {
+            result.ttl_seconds = *n;
+        }
+    }
+
+    Some(result)
+}
+
+/// Convert a prost-reflect Value to a RateLimit
+fn convert_to_rate_limit(value: &Value) -> Option<storage::RateLimit> {
+    let msg = value.as_message()?;
+    let mut result = storage::RateLimit::default();
+
+    if let Some(cow) = msg.get_field_by_name("operation") {
+        if let Value::String(s) = cow.as_ref() {
+            result.operation = s.clone();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("limit") {
+        if let Value::U64(n) = cow.as_ref() {
+            result.limit = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("window_seconds") {
+        if let Value::U64(n) = cow.as_ref() {
+            result.window_seconds = *n;
+        }
+    }
+
+    Some(result)
+}
+
+/// Convert a prost-reflect Value to an IndexDef
+fn convert_to_index_def(value: &Value) -> Option<storage::IndexDef> {
+    let msg = value.as_message()?;
+    let mut result = storage::IndexDef::default();
+
+    if let Some(cow) = msg.get_field_by_name("columns") {
+        if let Value::List(list) = cow.as_ref() {
+            result.columns = list
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("unique") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.unique = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("name") {
+        if let Value::String(s) = cow.as_ref() {
+            result.name = s.clone();
+        }
+    }
+
     Some(result)
 }
 
@@ -1010,6 +1559,51 @@ fn convert_to_relation_def(value: &Value) -> Option<storage::RelationDef> {
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("recursive") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.recursive = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("type_column") {
+        if let Value::String(s) = cow.as_ref() {
+            result.type_column = s.clone();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("candidates") {
+        if let Value::List(list) = cow.as_ref() {
+            result.candidates = list
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("non_critical") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.non_critical = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("inverse") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.inverse = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("inverse_name") {
+        if let Value::String(s) = cow.as_ref() {
+            result.inverse_name = s.clone();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("remote") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.remote = *b;
+        }
+    }
+
     Some(result)
 }
 
@@ -1066,6 +1660,78 @@ fn convert_to_column_options(value: &Value) -> Option<storage::ColumnOptions> {
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("pii") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.pii = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("id_kind") {
+        if let Value::EnumNumber(n) = cow.as_ref() {
+            result.id_kind = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("search_weight") {
+        if let Value::U32(n) = cow.as_ref() {
+            result.search_weight = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("array") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.array = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("from_context") {
+        if let Value::String(s) = cow.as_ref() {
+            result.from_context = s.clone();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("on") {
+        if let Value::List(list) = cow.as_ref() {
+            result.on = list.iter().filter_map(|v| v.as_enum_number()).collect();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("generated_expr") {
+        if let Value::String(s) = cow.as_ref() {
+            result.generated_expr = s.clone();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("timezone") {
+        if let Value::EnumNumber(n) = cow.as_ref() {
+            result.timezone = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("citext") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.citext = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("collation") {
+        if let Value::String(s) = cow.as_ref() {
+            result.collation = s.clone();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("geography") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.geography = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("searchable") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.searchable = *b;
+        }
+    }
+
     Some(result)
 }
 
@@ -1150,6 +1816,18 @@ fn convert_to_service_options(value: &Value) -> Option<storage::ServiceOptions>
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("read_replica") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.read_replica = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("cache") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.cache = *b;
+        }
+    }
+
     Some(result)
 }
 
@@ -1182,6 +1860,39 @@ fn convert_to_method_options(value: &Value) -> Option<storage::MethodOptions> {
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("stream_rows") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.stream_rows = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("conflict_columns") {
+        if let Value::List(list) = cow.as_ref() {
+            result.conflict_columns = list
+                .iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("custom") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.custom = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("atomic") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.atomic = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("batch_size") {
+        if let Value::U32(n) = cow.as_ref() {
+            result.batch_size = *n;
+        }
+    }
+
     Some(result)
 }
 
@@ -1208,6 +1919,24 @@ fn convert_to_grpc_service_options(value: &Value) -> Option<grpc::ServiceOptions
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("request_context") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.request_context = *b;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("context_actor") {
+        if let Value::String(s) = cow.as_ref() {
+            result.context_actor = s.clone();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("invalidation_hooks") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.invalidation_hooks = *b;
+        }
+    }
+
     Some(result)
 }
 
@@ -1234,6 +1963,18 @@ fn convert_to_grpc_method_options(value: &Value) -> Option<grpc::MethodOptions>
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("streaming") {
+        if let Value::EnumNumber(n) = cow.as_ref() {
+            result.streaming = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("page_size") {
+        if let Value::U32(n) = cow.as_ref() {
+            result.page_size = *n;
+        }
+    }
+
     Some(result)
 }
 
@@ -1280,6 +2021,38 @@ fn convert_to_graphql_type_options(value: &Value) -> Option<graphql::TypeOptions
         }
     }
 
+    if msg.has_field_by_name("cache_control") {
+        if let Some(cow) = msg.get_field_by_name("cache_control") {
+            result.cache_control = convert_to_graphql_cache_control(cow.as_ref());
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("rename_shadowed_fields") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.rename_shadowed_fields = *b;
+        }
+    }
+
+    Some(result)
+}
+
+/// Convert a prost-reflect Value to graphql::CacheControl
+fn convert_to_graphql_cache_control(value: &Value) -> Option<graphql::CacheControl> {
+    let msg = value.as_message()?;
+    let mut result = graphql::CacheControl::default();
+
+    if let Some(cow) = msg.get_field_by_name("max_age") {
+        if let Value::U32(n) = cow.as_ref() {
+            result.max_age = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("private") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.private = *b;
+        }
+    }
+
     Some(result)
 }
 
@@ -1323,9 +2096,10 @@ fn convert_to_graphql_field_options(value: &Value) -> Option<graphql::FieldOptio
                 }
             }
 
-            if let Some(req_cow) = ctx_msg.get_field_by_name("required") {
-                if let Value::Bool(b) = req_cow.as_ref() {
-                    ctx_source.required = *b;
+            // Unset stays None, which callers treat as required
+            if ctx_msg.has_field_by_name("required") {
+                if let Some(Value::Bool(b)) = ctx_msg.get_field_by_name("required").as_deref() {
+                    ctx_source.required = Some(*b);
                 }
             }
 
@@ -1342,6 +2116,24 @@ fn convert_to_graphql_field_options(value: &Value) -> Option<graphql::FieldOptio
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("visibility") {
+        if let Value::EnumNumber(n) = cow.as_ref() {
+            result.visibility = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("non_critical") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.non_critical = *b;
+        }
+    }
+
+    if msg.has_field_by_name("cache_control") {
+        if let Some(cow) = msg.get_field_by_name("cache_control") {
+            result.cache_control = convert_to_graphql_cache_control(cow.as_ref());
+        }
+    }
+
     Some(result)
 }
 
@@ -1359,6 +2151,44 @@ fn convert_to_graphql_service_options(value: &Value) -> Option<graphql::ServiceO
     Some(result)
 }
 
+/// Convert a prost-reflect Value to graphql::FileOptions
+fn convert_to_graphql_file_options(value: &Value) -> Option<graphql::FileOptions> {
+    let msg = value.as_message()?;
+    let mut result = graphql::FileOptions::default();
+
+    if let Some(cow) = msg.get_field_by_name("id_prefixes") {
+        if let Value::List(list) = cow.as_ref() {
+            for item in list.iter() {
+                if let Some(prefix) = convert_to_graphql_id_prefix(item) {
+                    result.id_prefixes.push(prefix);
+                }
+            }
+        }
+    }
+
+    Some(result)
+}
+
+/// Convert a prost-reflect Value to graphql::IdPrefix
+fn convert_to_graphql_id_prefix(value: &Value) -> Option<graphql::IdPrefix> {
+    let msg = value.as_message()?;
+    let mut result = graphql::IdPrefix::default();
+
+    if let Some(cow) = msg.get_field_by_name("type") {
+        if let Value::String(s) = cow.as_ref() {
+            result.r#type = s.clone();
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("prefix") {
+        if let Value::String(s) = cow.as_ref() {
+            result.prefix = s.clone();
+        }
+    }
+
+    Some(result)
+}
+
 /// Convert a prost-reflect Value to graphql::QueryOptions
 fn convert_to_graphql_query_options(value: &Value) -> Option<graphql::QueryOptions> {
     let msg = value.as_message()?;
@@ -1388,6 +2218,12 @@ fn convert_to_graphql_query_options(value: &Value) -> Option<graphql::QueryOptio
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("max_concurrency") {
+        if let Value::U32(n) = cow.as_ref() {
+            result.max_concurrency = *n;
+        }
+    }
+
     Some(result)
 }
 
@@ -1426,6 +2262,18 @@ fn convert_to_graphql_mutation_options(value: &Value) -> Option<graphql::Mutatio
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("max_concurrency") {
+        if let Value::U32(n) = cow.as_ref() {
+            result.max_concurrency = *n;
+        }
+    }
+
+    if let Some(cow) = msg.get_field_by_name("dry_run") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.dry_run = *b;
+        }
+    }
+
     Some(result)
 }
 
@@ -1478,6 +2326,26 @@ fn convert_to_validate_message_options(value: &Value) -> Option<validate::Messag
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("rules") {
+        if let Value::List(rules) = cow.as_ref() {
+            result.rules = rules
+                .iter()
+                .filter_map(|v| v.as_message())
+                .map(|rule| {
+                    let text = |name: &str| {
+                        rule.get_field_by_name(name)
+                            .and_then(|v| v.as_str().map(str::to_string))
+                            .unwrap_or_default()
+                    };
+                    validate::CrossFieldRule {
+                        expr: text("expr"),
+                        message: text("message"),
+                    }
+                })
+                .collect();
+        }
+    }
+
     Some(result)
 }
 
@@ -1510,6 +2378,22 @@ fn convert_to_validate_field_options(value: &Value) -> Option<validate::FieldOpt
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("sanitize") {
+        if let Some(sanitize_msg) = cow.as_ref().as_message() {
+            let flag = |name: &str| {
+                sanitize_msg
+                    .get_field_by_name(name)
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false)
+            };
+            result.sanitize = Some(validate::Sanitize {
+                nfc: flag("nfc"),
+                trim: flag("trim"),
+                lowercase: flag("lowercase"),
+            });
+        }
+    }
+
     Some(result)
 }
 
@@ -1637,6 +2521,12 @@ fn convert_to_validate_rules(msg: &DynamicMessage) -> validate::Rules {
         }
     }
 
+    if let Some(cow) = msg.get_field_by_name("unique_in_db") {
+        if let Value::Bool(b) = cow.as_ref() {
+            result.unique_in_db = *b;
+        }
+    }
+
     result
 }
 
@@ -2099,4 +2989,62 @@ mod tests {
         let parts = split_aggregate_parts("key1: value1, key2: value2");
         assert_eq!(parts.len(), 2);
     }
+
+    #[test]
+    fn test_add_inverse_relations() {
+        let key = |name: &str| ("blog.proto".to_string(), name.to_string());
+        let belongs_to = |name: &str, related: &str, inverse_name: &str| storage::RelationDef {
+            name: name.to_string(),
+            r#type: storage::RelationType::BelongsTo as i32,
+            related: related.to_string(),
+            foreign_key: format!("{}_id", name),
+            inverse: true,
+            inverse_name: inverse_name.to_string(),
+            ..Default::default()
+        };
+
+        let mut cache = OptionsCache::default();
+        cache.packages.insert("blog.proto".to_string(), "blog".to_string());
+        cache.entity_options.insert(key("Author"), storage::EntityOptions::default());
+        cache.entity_options.insert(
+            key("Post"),
+            storage::EntityOptions {
+                relations: vec![belongs_to("author", "blog.Author", "")],
+                ..Default::default()
+            },
+        );
+        cache.entity_options.insert(
+            key("Category"),
+            storage::EntityOptions {
+                relations: vec![
+                    belongs_to("parent", "Category", "children"),
+                    belongs_to("user", "iam.User", ""),
+                ],
+                ..Default::default()
+            },
+        );
+        add_inverse_relations(&mut cache);
+
+        let author = &cache.entity_options[&key("Author")].relations;
+        assert_eq!(author.len(), 1);
+        assert_eq!(author[0].name, "posts");
+        assert_eq!(author[0].r#type(), storage::RelationType::HasMany);
+        assert_eq!(author[0].related, "Post");
+        assert_eq!(author[0].foreign_key, "author_id");
+
+        // Self-referential inverses use the given name; other packages are skipped
+        let category = &cache.entity_options[&key("Category")].relations;
+        assert_eq!(category.len(), 3);
+        assert_eq!(category[2].name, "children");
+        assert_eq!(category[2].related, "Category");
+    }
+
+    #[test]
+    fn test_plural() {
+        assert_eq!(plural("post"), "posts");
+        assert_eq!(plural("category"), "categories");
+        assert_eq!(plural("key"), "keys");
+        assert_eq!(plural("address"), "addresses");
+        assert_eq!(plural("blog_post"), "blog_posts");
+    }
 }
@@ -0,0 +1,1149 @@
+//! Transactional outbox generation
+//!
+//! For packages with `emit_events` entities, generates an `outbox` entity and
+//! the outbox.rs module: storage writes record `{Entity}Created/Updated/Deleted`
+//! rows in the same transaction as the change, and a relay task publishes the
+//! pending rows through a pluggable `EventPublisher`, marking them published.
+//! Kafka, NATS and Redis publishers are included behind the generated crate's
+//! `kafka`, `nats` and `redis` features, and each entity gets a typed
+//! `{Entity}Event` message to decode what they publish. Payloads are the
+//! entity's DTO as JSON, with PII columns redacted and keys of hidden-id
+//! entities as external ids. `EventSource`s
+//! subscribe to published events again, for GraphQL subscriptions: an
+//! in-process broadcast bus, NATS, Kafka and Redis pub/sub consumers behind
+//! the same features, and a Postgres LISTEN/NOTIFY source behind `pg-notify`,
+//! for which `record` also notifies inside the write's transaction.
+
+use super::dto::pii_columns;
+use super::external_id::{collect_hidden_ids, hidden_id_columns};
+use super::options::get_cached_entity_options;
+use crate::error::GeneratorError;
+use crate::params::time_crate;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::{format_ident, quote};
+
+/// Entity module and table name of the outbox
+pub const OUTBOX_MODULE: &str = "outbox";
+
+/// Payload shape of an `emit_events` or `webhooks` entity
+pub(super) struct EventPayload {
+    /// Entity message name
+    pub(super) entity: String,
+    /// DTO columns redacted in the payload
+    pub(super) pii: Vec<String>,
+    /// Key columns written as external ids, with the entity they identify
+    pub(super) id_columns: Vec<(String, String)>,
+}
+
+/// Message names of the `emit_events` entities in the main file's package
+pub fn collect_event_entities(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> Vec<String> {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .flat_map(|file| {
+            let file_name = file.name.as_deref().unwrap_or("");
+            file.message_type.iter().filter_map(move |message| {
+                let msg_name = message.name.as_deref().unwrap_or("");
+                get_cached_entity_options(file_name, msg_name)
+                    .filter(|o| !o.skip && o.emit_events)
+                    .map(|_| msg_name.to_string())
+            })
+        })
+        .collect()
+}
+
+/// Whether any entity in the main file's package has `emit_events`
+pub fn package_emits_events(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> bool {
+    !collect_event_entities(all_files, main_file).is_empty()
+}
+
+/// Generate the outbox entity and outbox.rs for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Vec<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    let entities = collect_event_entities(all_files, file);
+    if package.is_empty() || entities.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let declared = all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .flat_map(|f| f.message_type.iter())
+        .any(|m| m.name.as_deref().unwrap_or("").to_snake_case() == OUTBOX_MODULE);
+    if declared {
+        return Err(GeneratorError::CodeGenError(format!(
+            "package {} has emit_events entities, so its `{}` entity name is reserved for the event outbox",
+            package, OUTBOX_MODULE
+        )));
+    }
+
+    let hides_ids = super::external_id::package_hides_ids(all_files, file);
+    let payloads = event_payloads(all_files, file, entities);
+    let request_context = crate::grpc::package_has_request_context(all_files, file);
+    let dir = package.replace('.', "/");
+    Ok(vec![
+        File {
+            name: Some(format!("{}/entities/{}.rs", dir, OUTBOX_MODULE)),
+            content: Some(crate::params::format_code(generate_entity().to_string())),
+            ..Default::default()
+        },
+        File {
+            name: Some(format!("{}/outbox.rs", dir)),
+            content: Some(crate::params::format_code(generate_module(package, &payloads, hides_ids, request_context).to_string())),
+            ..Default::default()
+        },
+    ])
+}
+
+/// Generate the outbox entity module
+fn generate_entity() -> TokenStream {
+    let datetime: syn::Type = syn::parse_str(time_crate().datetime_type()).expect("datetime type");
+
+    quote! {
+        //! SeaORM entity for the transactional event outbox
+        //!
+        //! Synthesized by protoc-gen-synapse for `emit_events` entities.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use sea_orm::entity::prelude::*;
+
+        #[sea_orm::model]
+        #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+        #[sea_orm(table_name = "outbox")]
+        pub struct Model {
+            #[sea_orm(primary_key)]
+            pub id: i64,
+            /// Entity the event is about (e.g. `Post`)
+            pub aggregate_type: String,
+            /// Primary key of the entity row
+            pub aggregate_id: String,
+            /// Event name (e.g. `PostCreated`)
+            pub event_type: String,
+            /// Proto-encoded entity after the write; empty for deletes
+            pub payload: Vec<u8>,
+            /// JSON `RequestContext` of the write, when its service captures one
+            pub context: Option<String>,
+            pub created_at: #datetime,
+            /// Set by the relay once the event is published
+            pub published_at: Option<#datetime>,
+        }
+
+        impl ActiveModelBehavior for ActiveModel {}
+    }
+}
+
+/// Generate outbox.rs: the recorder, the publisher trait, and the relay
+///
+/// With `hides_ids`, envelopes carry the external ids of `ids.rs`. With
+/// `request_context`, events record the `RequestContext` of their write.
+fn generate_module(package: &str, payloads: &[EventPayload], hides_ids: bool, request_context: bool) -> TokenStream {
+    let entities: Vec<String> = payloads.iter().map(|p| p.entity.clone()).collect();
+    let now = time_crate().now();
+    let context = if request_context {
+        quote! { super::prelude::request_context().and_then(|context| serde_json::to_string(&context).ok()) }
+    } else {
+        quote! { None }
+    };
+    let envelope = generate_envelope(package, &entities, hides_ids);
+    let payload_fns = generate_payloads(payloads);
+    let publishers = generate_publishers();
+    let sources = generate_sources(package, &entities);
+    let channel = format!("{}_outbox", package.replace('.', "_"));
+
+    quote! {
+        //! Transactional outbox
+        //!
+        //! Storage records an event row with every write to an `emit_events`
+        //! entity, inside the write's transaction, so an event exists exactly
+        //! when its change was committed. `spawn_relay` publishes pending rows
+        //! in id order through an `EventPublisher`; delivery is at least once.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(dead_code)]
+        #![allow(unexpected_cfgs)]
+
+        use super::entities::outbox::{ActiveModel, Column, Entity};
+        use sea_orm::entity::prelude::*;
+        use sea_orm::sea_query::{Expr, LockBehavior, LockType};
+        use sea_orm::{DatabaseConnection, QueryOrder, QuerySelect, Set, TransactionTrait};
+        use std::sync::Arc;
+
+        pub use super::entities::outbox::Model as OutboxEvent;
+
+        /// Error reported by an `EventPublisher`
+        pub type PublishError = Box<dyn std::error::Error + Send + Sync>;
+
+        /// Outbox relay error
+        #[derive(Debug, thiserror::Error)]
+        pub enum RelayError {
+            #[error("publish error: {0}")]
+            Publish(PublishError),
+            #[error("database error: {0}")]
+            Database(#[from] DbErr),
+        }
+
+        /// Destination of outbox events (message broker, webhook, ...)
+        #[async_trait::async_trait]
+        pub trait EventPublisher: Send + Sync + 'static {
+            /// Publish one event; an error leaves it pending for the next poll
+            async fn publish(&self, event: &OutboxEvent) -> Result<(), PublishError>;
+        }
+
+        #payload_fns
+
+        /// Record an event on `db`, normally the write's transaction
+        pub async fn record<C: ConnectionTrait>(
+            db: &C,
+            aggregate_type: &str,
+            aggregate_id: impl std::fmt::Display,
+            event_type: &str,
+            payload: Vec<u8>,
+        ) -> Result<(), DbErr> {
+            let event = ActiveModel {
+                aggregate_type: Set(aggregate_type.to_string()),
+                aggregate_id: Set(aggregate_id.to_string()),
+                event_type: Set(event_type.to_string()),
+                payload: Set(payload),
+                context: Set(#context),
+                created_at: Set(#now),
+                published_at: Set(None),
+                ..Default::default()
+            };
+            let inserted = Entity::insert(event).exec(db).await?;
+            notify(db, inserted.last_insert_id).await
+        }
+
+        /// Postgres channel `record` notifies with the ids of new outbox rows
+        pub const NOTIFY_CHANNEL: &str = #channel;
+
+        /// Announce a new outbox row to `PgNotifyEvents` listeners
+        ///
+        /// Postgres delivers the notification when the transaction commits,
+        /// and drops it on rollback, like the row itself.
+        #[cfg(feature = "pg-notify")]
+        async fn notify<C: ConnectionTrait>(db: &C, id: i64) -> Result<(), DbErr> {
+            if db.get_database_backend() != sea_orm::DbBackend::Postgres {
+                return Ok(());
+            }
+            db.execute(sea_orm::Statement::from_sql_and_values(
+                sea_orm::DbBackend::Postgres,
+                "SELECT pg_notify($1, $2)",
+                [NOTIFY_CHANNEL.into(), id.to_string().into()],
+            ))
+            .await?;
+            Ok(())
+        }
+
+        #[cfg(not(feature = "pg-notify"))]
+        async fn notify<C: ConnectionTrait>(_db: &C, _id: i64) -> Result<(), DbErr> {
+            Ok(())
+        }
+
+        /// Relay tuning
+        #[derive(Debug, Clone)]
+        pub struct RelayConfig {
+            /// Events claimed per transaction
+            pub batch_size: u64,
+            /// Wait between polls once the outbox is drained
+            pub poll_interval: std::time::Duration,
+        }
+
+        impl Default for RelayConfig {
+            fn default() -> Self {
+                Self {
+                    batch_size: 100,
+                    poll_interval: std::time::Duration::from_secs(1),
+                }
+            }
+        }
+
+        /// Publish up to `batch_size` pending events, oldest first
+        ///
+        /// The batch is locked with `FOR UPDATE SKIP LOCKED`, so several relays
+        /// can share an outbox. Publishing stops at the first failure; the
+        /// events published before it are still marked, and the error is
+        /// returned. Returns the number of events published.
+        pub async fn relay_batch(
+            db: &DatabaseConnection,
+            publisher: &dyn EventPublisher,
+            batch_size: u64,
+        ) -> Result<u64, RelayError> {
+            let txn = db.begin().await?;
+            let events = Entity::find()
+                .filter(Column::PublishedAt.is_null())
+                .order_by_asc(Column::Id)
+                .limit(batch_size.max(1))
+                .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+                .all(&txn)
+                .await?;
+
+            let mut published = Vec::with_capacity(events.len());
+            let mut failure = None;
+            for event in &events {
+                match publisher.publish(event).await {
+                    Ok(()) => published.push(event.id),
+                    Err(err) => {
+                        failure = Some(err);
+                        break;
+                    }
+                }
+            }
+
+            if !published.is_empty() {
+                Entity::update_many()
+                    .col_expr(Column::PublishedAt, Expr::value(#now))
+                    .filter(Column::Id.is_in(published.clone()))
+                    .exec(&txn)
+                    .await?;
+            }
+            txn.commit().await?;
+
+            match failure {
+                Some(err) => Err(RelayError::Publish(err)),
+                None => Ok(published.len() as u64),
+            }
+        }
+
+        /// Start the background relay that publishes pending events
+        ///
+        /// Full batches are relayed back to back; otherwise the relay waits
+        /// `poll_interval`. Errors are passed to `on_error` and the events
+        /// retried on the next poll.
+        pub fn spawn_relay(
+            db: DatabaseConnection,
+            publisher: Arc<dyn EventPublisher>,
+            config: RelayConfig,
+            on_error: impl Fn(RelayError) + Send + 'static,
+        ) -> tokio::task::JoinHandle<()> {
+            tokio::spawn(async move {
+                loop {
+                    match relay_batch(&db, publisher.as_ref(), config.batch_size).await {
+                        Ok(count) if count >= config.batch_size.max(1) => continue,
+                        Ok(_) => {}
+                        Err(err) => on_error(err),
+                    }
+                    tokio::time::sleep(config.poll_interval).await;
+                }
+            })
+        }
+
+        #envelope
+
+        #publishers
+
+        #sources
+    }
+}
+
+/// Payload shapes of `entities` of the main file's package
+pub(super) fn event_payloads(
+    all_files: &[FileDescriptorProto],
+    main_file: &FileDescriptorProto,
+    entities: Vec<String>,
+) -> Vec<EventPayload> {
+    let package = main_file.package.as_deref().unwrap_or("");
+    let hidden: Vec<String> = collect_hidden_ids(all_files, main_file).into_iter().map(|(entity, _)| entity).collect();
+    entities
+        .into_iter()
+        .map(|entity| EventPayload {
+            pii: pii_columns(all_files, main_file, &entity),
+            id_columns: hidden_id_columns(package, &entity, &hidden),
+            entity,
+        })
+        .collect()
+}
+
+/// Generate the `{entity}_payload` functions building event payloads
+///
+/// Shared by outbox.rs and webhooks.rs, which both sit next to `dto` and `ids`.
+pub(super) fn generate_payloads(payloads: &[EventPayload]) -> TokenStream {
+    let mask_pii = payloads.iter().any(|p| !p.pii.is_empty()).then(|| {
+        quote! {
+            /// Redact PII columns of a payload
+            fn mask_pii(payload: &mut serde_json::Value, columns: &[&str]) {
+                for column in columns {
+                    if let Some(value) = payload.get_mut(*column).filter(|v| !v.is_null()) {
+                        *value = serde_json::Value::String("<redacted>".to_string());
+                    }
+                }
+            }
+        }
+    });
+    let encode_ids = payloads.iter().any(|p| !p.id_columns.is_empty()).then(|| {
+        quote! {
+            /// Replace key columns of a payload with external ids
+            fn encode_ids(payload: &mut serde_json::Value, columns: &[(&str, &str)]) {
+                for (column, entity) in columns {
+                    if let Some(value) = payload.get_mut(*column) {
+                        let id = match value {
+                            serde_json::Value::Number(n) => n.to_string(),
+                            serde_json::Value::String(s) => s.clone(),
+                            _ => continue,
+                        };
+                        *value = serde_json::Value::String(super::ids::external_id(entity, id));
+                    }
+                }
+            }
+        }
+    });
+
+    let functions = payloads.iter().map(|payload| {
+        let entity_module = format_ident!("{}", payload.entity.to_snake_case());
+        let fn_ident = format_ident!("{}_payload", payload.entity.to_snake_case());
+        let dto_ident = format_ident!("{}Dto", payload.entity.to_upper_camel_case());
+        let doc = format!("Event payload of a `{}`: its DTO as JSON", payload.entity);
+        let mask = (!payload.pii.is_empty()).then(|| {
+            let columns = &payload.pii;
+            quote! { mask_pii(&mut payload, &[#(#columns),*]); }
+        });
+        let encode = (!payload.id_columns.is_empty()).then(|| {
+            let columns = payload.id_columns.iter().map(|(column, entity)| quote! { (#column, #entity) });
+            quote! { encode_ids(&mut payload, &[#(#columns),*]); }
+        });
+        quote! {
+            #[doc = #doc]
+            pub fn #fn_ident(model: &super::entities::#entity_module::Model) -> serde_json::Value {
+                let mut payload = serde_json::to_value(super::dto::#dto_ident::from(model.clone()))
+                    .unwrap_or_default();
+                #mask
+                #encode
+                payload
+            }
+        }
+    });
+
+    quote! {
+        #mask_pii
+        #encode_ids
+        #(#functions)*
+    }
+}
+
+/// Generate the wire envelope, the typed per-entity event messages, and topics
+///
+/// `{Entity}Event` has the envelope's wire encoding, so consumers decode
+/// published envelopes straight into the typed message and read the entity
+/// from its JSON payload.
+fn generate_envelope(package: &str, entities: &[String], hides_ids: bool) -> TokenStream {
+    let occurred_at = time_crate().to_proto(quote! { event.created_at });
+    let aggregate_id = if hides_ids {
+        quote! { super::ids::external_id(&event.aggregate_type, &event.aggregate_id) }
+    } else {
+        quote! { event.aggregate_id.clone() }
+    };
+
+    let topic_arms = entities.iter().map(|entity| {
+        let topic = format!("{}.{}", package, entity.to_snake_case());
+        quote! { #entity => #topic.to_string(), }
+    });
+
+    let typed = entities.iter().map(|entity| {
+        let event_ident = format_ident!("{}Event", entity);
+        let field = format_ident!("{}", entity.to_snake_case());
+        let doc = format!("A published `{}` change, decoded from an `EventEnvelope`", entity);
+        let field_doc = format!("JSON `{}Dto` after the write; empty for deletes", entity);
+        let accessor_doc = format!("The `{}` after the write, as JSON; `None` for deletes", entity);
+        quote! {
+            #[doc = #doc]
+            #[derive(Clone, PartialEq, prost::Message)]
+            pub struct #event_ident {
+                #[prost(string, tag = "1")]
+                pub aggregate_type: String,
+                #[prost(string, tag = "2")]
+                pub aggregate_id: String,
+                #[prost(string, tag = "3")]
+                pub event_type: String,
+                #[doc = #field_doc]
+                #[prost(bytes = "vec", tag = "4")]
+                pub payload: Vec<u8>,
+                #[prost(int64, tag = "5")]
+                pub id: i64,
+                #[prost(message, optional, tag = "6")]
+                pub occurred_at: Option<prost_types::Timestamp>,
+                #[prost(string, optional, tag = "7")]
+                pub context: Option<String>,
+            }
+
+            impl #event_ident {
+                #[doc = #accessor_doc]
+                pub fn #field(&self) -> Option<serde_json::Value> {
+                    serde_json::from_slice(&self.payload).ok()
+                }
+            }
+        }
+    });
+
+    let fallback = format!("{}.{{}}", package);
+    quote! {
+        /// Wire format of a published event
+        ///
+        /// Field 4 holds the entity's DTO as JSON, with PII redacted and
+        /// hidden keys as external ids; the typed `{Entity}Event` messages
+        /// decode an envelope directly.
+        #[derive(Clone, PartialEq, prost::Message)]
+        pub struct EventEnvelope {
+            #[prost(string, tag = "1")]
+            pub aggregate_type: String,
+            /// External id of the entity row
+            #[prost(string, tag = "2")]
+            pub aggregate_id: String,
+            #[prost(string, tag = "3")]
+            pub event_type: String,
+            #[prost(bytes = "vec", tag = "4")]
+            pub payload: Vec<u8>,
+            /// Outbox row id, unique per event for deduplication
+            #[prost(int64, tag = "5")]
+            pub id: i64,
+            #[prost(message, optional, tag = "6")]
+            pub occurred_at: Option<prost_types::Timestamp>,
+            /// JSON `RequestContext` of the write (actor, request id, IP, user agent)
+            #[prost(string, optional, tag = "7")]
+            pub context: Option<String>,
+        }
+
+        impl From<&OutboxEvent> for EventEnvelope {
+            fn from(event: &OutboxEvent) -> Self {
+                Self {
+                    aggregate_type: event.aggregate_type.clone(),
+                    aggregate_id: #aggregate_id,
+                    event_type: event.event_type.clone(),
+                    payload: event.payload.clone(),
+                    id: event.id,
+                    occurred_at: Some(#occurred_at),
+                    context: event.context.clone(),
+                }
+            }
+        }
+
+        #(#typed)*
+
+        /// Kafka topic (NATS subject prefix, Redis channel) of an entity's events, e.g. `blog.post`
+        pub fn event_topic(aggregate_type: &str) -> String {
+            match aggregate_type {
+                #(#topic_arms)*
+                other => format!(#fallback, other.to_lowercase()),
+            }
+        }
+
+        /// Lowercase change kind of an event (`created`, `updated`, `deleted`)
+        pub fn event_kind(event: &OutboxEvent) -> String {
+            event
+                .event_type
+                .strip_prefix(event.aggregate_type.as_str())
+                .unwrap_or(&event.event_type)
+                .to_lowercase()
+        }
+    }
+}
+
+/// Generate the feature-gated Kafka, NATS and Redis `EventPublisher`s
+fn generate_publishers() -> TokenStream {
+    quote! {
+        /// Publishes events to Kafka, one topic per entity, keyed by entity id
+        ///
+        /// Keying by id keeps each entity's events in order within its
+        /// partition. The `event_type` header names the event.
+        #[cfg(feature = "kafka")]
+        pub struct KafkaPublisher {
+            producer: rdkafka::producer::FutureProducer,
+            timeout: std::time::Duration,
+        }
+
+        #[cfg(feature = "kafka")]
+        impl KafkaPublisher {
+            /// Connect an idempotent producer to `brokers` (`host:port,...`)
+            pub fn new(brokers: &str) -> Result<Self, rdkafka::error::KafkaError> {
+                let producer = rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", brokers)
+                    .set("enable.idempotence", "true")
+                    .create()?;
+                Ok(Self::from_producer(producer))
+            }
+
+            /// Publish through an already configured producer
+            pub fn from_producer(producer: rdkafka::producer::FutureProducer) -> Self {
+                Self {
+                    producer,
+                    timeout: std::time::Duration::from_secs(5),
+                }
+            }
+
+            /// How long a send may wait for the broker's acknowledgement
+            pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+                self.timeout = timeout;
+                self
+            }
+        }
+
+        #[cfg(feature = "kafka")]
+        #[async_trait::async_trait]
+        impl EventPublisher for KafkaPublisher {
+            async fn publish(&self, event: &OutboxEvent) -> Result<(), PublishError> {
+                use rdkafka::message::{Header, OwnedHeaders};
+                let topic = event_topic(&event.aggregate_type);
+                let envelope = EventEnvelope::from(event);
+                let payload = prost::Message::encode_to_vec(&envelope);
+                let headers = OwnedHeaders::new().insert(Header {
+                    key: "event_type",
+                    value: Some(event.event_type.as_str()),
+                });
+                let record = rdkafka::producer::FutureRecord::to(&topic)
+                    .key(&envelope.aggregate_id)
+                    .payload(&payload)
+                    .headers(headers);
+                self.producer
+                    .send(record, self.timeout)
+                    .await
+                    .map_err(|(err, _)| Box::new(err) as PublishError)?;
+                Ok(())
+            }
+        }
+
+        /// Publishes events to NATS on `{topic}.{kind}` subjects, e.g. `blog.post.created`
+        ///
+        /// The `Nats-Msg-Id` header carries the outbox id, so JetStream
+        /// streams drop the duplicates a relay retry can produce.
+        #[cfg(feature = "nats")]
+        pub struct NatsPublisher {
+            client: async_nats::Client,
+        }
+
+        #[cfg(feature = "nats")]
+        impl NatsPublisher {
+            pub fn new(client: async_nats::Client) -> Self {
+                Self { client }
+            }
+        }
+
+        #[cfg(feature = "nats")]
+        #[async_trait::async_trait]
+        impl EventPublisher for NatsPublisher {
+            async fn publish(&self, event: &OutboxEvent) -> Result<(), PublishError> {
+                let subject = format!("{}.{}", event_topic(&event.aggregate_type), event_kind(event));
+                let mut headers = async_nats::HeaderMap::new();
+                headers.insert("Nats-Msg-Id", event.id.to_string().as_str());
+                headers.insert("event_type", event.event_type.as_str());
+                let payload = prost::Message::encode_to_vec(&EventEnvelope::from(event));
+                self.client
+                    .publish_with_headers(subject, headers, payload.into())
+                    .await?;
+                // Publishing only buffers; wait until the server has the event
+                self.client.flush().await?;
+                Ok(())
+            }
+        }
+
+        /// Publishes events to Redis pub/sub, on one channel per entity (`event_topic`)
+        ///
+        /// Pub/sub keeps nothing: only subscribers connected at publish time,
+        /// such as the `RedisEventSource` of every gateway replica, get the event.
+        #[cfg(feature = "redis")]
+        pub struct RedisPublisher {
+            connection: redis::aio::ConnectionManager,
+        }
+
+        #[cfg(feature = "redis")]
+        impl RedisPublisher {
+            /// Connect to the Redis server at `url`, e.g. `redis://127.0.0.1/`
+            pub async fn new(url: &str) -> Result<Self, redis::RedisError> {
+                let client = redis::Client::open(url)?;
+                Ok(Self::from_connection(client.get_connection_manager().await?))
+            }
+
+            /// Publish through an existing, reconnecting connection
+            pub fn from_connection(connection: redis::aio::ConnectionManager) -> Self {
+                Self { connection }
+            }
+        }
+
+        #[cfg(feature = "redis")]
+        #[async_trait::async_trait]
+        impl EventPublisher for RedisPublisher {
+            async fn publish(&self, event: &OutboxEvent) -> Result<(), PublishError> {
+                let channel = event_topic(&event.aggregate_type);
+                let payload = prost::Message::encode_to_vec(&EventEnvelope::from(event));
+                let mut connection = self.connection.clone();
+                let _: i64 = redis::AsyncCommands::publish(&mut connection, channel, payload).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Generate `EventSource` with the broadcast, NATS, Kafka and Redis sources
+///
+/// Sources forward into a bounded channel from a task that stops once the
+/// subscriber drops the stream. The Redis source subscribes to the topics of
+/// `entities` in `package`.
+fn generate_sources(package: &str, entities: &[String]) -> TokenStream {
+    let channels = entities.iter().map(|entity| format!("{}.{}", package, entity.to_snake_case()));
+    quote! {
+        /// Published events as a stream, e.g. for GraphQL subscriptions
+        pub type EventStream = std::pin::Pin<Box<dyn tokio_stream::Stream<Item = EventEnvelope> + Send>>;
+
+        /// Where subscribers receive published events
+        pub trait EventSource: Send + Sync + 'static {
+            /// Events published from now on under `topic` (see `event_topic`)
+            fn subscribe(&self, topic: &str) -> EventStream;
+        }
+
+        /// In-process event bus, for a relay and subscribers in one process
+        ///
+        /// Publishing never fails, even without subscribers. A subscriber
+        /// more than `capacity` events behind skips the ones it missed.
+        #[derive(Clone)]
+        pub struct BroadcastEvents {
+            sender: tokio::sync::broadcast::Sender<EventEnvelope>,
+        }
+
+        impl BroadcastEvents {
+            pub fn new(capacity: usize) -> Self {
+                let (sender, _) = tokio::sync::broadcast::channel(capacity.max(1));
+                Self { sender }
+            }
+        }
+
+        impl Default for BroadcastEvents {
+            fn default() -> Self {
+                Self::new(1024)
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl EventPublisher for BroadcastEvents {
+            async fn publish(&self, event: &OutboxEvent) -> Result<(), PublishError> {
+                let _ = self.sender.send(EventEnvelope::from(event));
+                Ok(())
+            }
+        }
+
+        impl EventSource for BroadcastEvents {
+            fn subscribe(&self, topic: &str) -> EventStream {
+                use tokio::sync::broadcast::error::RecvError;
+                let mut receiver = self.sender.subscribe();
+                let topic = topic.to_string();
+                let (tx, rx) = tokio::sync::mpsc::channel(64);
+                tokio::spawn(async move {
+                    loop {
+                        let envelope = tokio::select! {
+                            _ = tx.closed() => break,
+                            received = receiver.recv() => match received {
+                                Ok(envelope) => envelope,
+                                Err(RecvError::Lagged(_)) => continue,
+                                Err(RecvError::Closed) => break,
+                            },
+                        };
+                        if event_topic(&envelope.aggregate_type) == topic && tx.send(envelope).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+                Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))
+            }
+        }
+
+        /// Subscribes to the `{topic}.>` subjects `NatsPublisher` publishes on
+        ///
+        /// Core NATS subscriptions only see events published while they are
+        /// open, which is what live subscribers want.
+        #[cfg(feature = "nats")]
+        pub struct NatsEventSource {
+            client: async_nats::Client,
+        }
+
+        #[cfg(feature = "nats")]
+        impl NatsEventSource {
+            pub fn new(client: async_nats::Client) -> Self {
+                Self { client }
+            }
+        }
+
+        #[cfg(feature = "nats")]
+        impl EventSource for NatsEventSource {
+            fn subscribe(&self, topic: &str) -> EventStream {
+                use tokio_stream::StreamExt;
+                let client = self.client.clone();
+                let subject = format!("{}.>", topic);
+                let (tx, rx) = tokio::sync::mpsc::channel(64);
+                tokio::spawn(async move {
+                    let Ok(mut messages) = client.subscribe(subject).await else {
+                        return;
+                    };
+                    loop {
+                        let message = tokio::select! {
+                            _ = tx.closed() => break,
+                            message = messages.next() => match message {
+                                Some(message) => message,
+                                None => break,
+                            },
+                        };
+                        let Ok(envelope) = <EventEnvelope as prost::Message>::decode(message.payload) else {
+                            continue;
+                        };
+                        if tx.send(envelope).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+                Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))
+            }
+        }
+
+        /// Listens for the notifications `record` sends on Postgres
+        ///
+        /// One connection of `db`'s pool LISTENs on `NOTIFY_CHANNEL` and loads
+        /// each announced outbox row, so subscribers get events as soon as
+        /// their transaction commits, without a relay or a broker. Rows
+        /// committed while the connection is being re-established are
+        /// missed. Events still stay pending in the outbox, so run a relay
+        /// (or prune the table) as well.
+        #[cfg(feature = "pg-notify")]
+        #[derive(Clone)]
+        pub struct PgNotifyEvents {
+            events: BroadcastEvents,
+        }
+
+        #[cfg(feature = "pg-notify")]
+        impl PgNotifyEvents {
+            /// Start listening on a Postgres connection
+            pub async fn listen(db: DatabaseConnection) -> Result<Self, DbErr> {
+                let mut listener = sqlx::postgres::PgListener::connect_with(db.get_postgres_connection_pool())
+                    .await
+                    .map_err(|e| DbErr::Conn(sea_orm::RuntimeErr::SqlxError(e.into())))?;
+                listener
+                    .listen(NOTIFY_CHANNEL)
+                    .await
+                    .map_err(|e| DbErr::Conn(sea_orm::RuntimeErr::SqlxError(e.into())))?;
+                let events = BroadcastEvents::default();
+                let bus = events.clone();
+                tokio::spawn(async move {
+                    loop {
+                        // The listener reconnects on the next call after an error
+                        let Ok(notification) = listener.recv().await else {
+                            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                            continue;
+                        };
+                        let Ok(id) = notification.payload().parse::<i64>() else {
+                            continue;
+                        };
+                        if let Ok(Some(event)) = Entity::find_by_id(id).one(&db).await {
+                            let _ = bus.publish(&event).await;
+                        }
+                    }
+                });
+                Ok(Self { events })
+            }
+        }
+
+        #[cfg(feature = "pg-notify")]
+        impl EventSource for PgNotifyEvents {
+            fn subscribe(&self, topic: &str) -> EventStream {
+                self.events.subscribe(topic)
+            }
+        }
+
+        /// Consumes the topics `KafkaPublisher` publishes to
+        ///
+        /// Every subscription reads from the end of the topic in a consumer
+        /// group of its own, so each subscriber sees every new event.
+        #[cfg(feature = "kafka")]
+        pub struct KafkaEventSource {
+            brokers: String,
+        }
+
+        #[cfg(feature = "kafka")]
+        impl KafkaEventSource {
+            /// Consume from `brokers` (`host:port,...`)
+            pub fn new(brokers: &str) -> Self {
+                Self { brokers: brokers.to_string() }
+            }
+        }
+
+        #[cfg(feature = "kafka")]
+        impl EventSource for KafkaEventSource {
+            fn subscribe(&self, topic: &str) -> EventStream {
+                use rdkafka::consumer::{Consumer, StreamConsumer};
+                use rdkafka::Message;
+                static SUBSCRIPTIONS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+                let group = format!(
+                    "synapse-subscription-{}-{}",
+                    std::process::id(),
+                    SUBSCRIPTIONS.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+                );
+                let consumer: Result<StreamConsumer, _> = rdkafka::ClientConfig::new()
+                    .set("bootstrap.servers", &self.brokers)
+                    .set("group.id", &group)
+                    .set("enable.auto.commit", "false")
+                    .set("auto.offset.reset", "latest")
+                    .create();
+                let topic = topic.to_string();
+                let (tx, rx) = tokio::sync::mpsc::channel(64);
+                tokio::spawn(async move {
+                    let Ok(consumer) = consumer else {
+                        return;
+                    };
+                    if consumer.subscribe(&[&topic]).is_err() {
+                        return;
+                    }
+                    loop {
+                        let envelope = tokio::select! {
+                            _ = tx.closed() => break,
+                            message = consumer.recv() => match message {
+                                Ok(message) => message
+                                    .payload()
+                                    .and_then(|payload| <EventEnvelope as prost::Message>::decode(payload).ok()),
+                                // Broker errors are retried by the client
+                                Err(_) => continue,
+                            },
+                        };
+                        if let Some(envelope) = envelope {
+                            if tx.send(envelope).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                });
+                Box::pin(tokio_stream::wrappers::ReceiverStream::new(rx))
+            }
+        }
+
+        /// Subscribes to the channels `RedisPublisher` publishes on
+        ///
+        /// One pub/sub connection receives the events of every entity and
+        /// fans them out to the subscriptions through a broadcast bus, so
+        /// share one source between them. Every replica serving
+        /// subscriptions receives every event published while it is
+        /// connected; the connection is re-established after errors, and
+        /// events published meanwhile are missed.
+        #[cfg(feature = "redis")]
+        #[derive(Clone)]
+        pub struct RedisEventSource {
+            events: BroadcastEvents,
+        }
+
+        #[cfg(feature = "redis")]
+        impl RedisEventSource {
+            /// Channels of the package's entities (see `event_topic`)
+            const CHANNELS: &'static [&'static str] = &[#(#channels),*];
+
+            /// Subscribe to the package's channels on one connection of `client`
+            pub async fn connect(client: redis::Client) -> Result<Self, redis::RedisError> {
+                let mut pubsub = client.get_async_pubsub().await?;
+                pubsub.subscribe(Self::CHANNELS).await?;
+                let events = BroadcastEvents::default();
+                let sender = events.sender.clone();
+                tokio::spawn(async move {
+                    use tokio_stream::StreamExt;
+                    loop {
+                        {
+                            let mut messages = pubsub.on_message();
+                            while let Some(message) = messages.next().await {
+                                if let Ok(envelope) =
+                                    <EventEnvelope as prost::Message>::decode(message.get_payload_bytes())
+                                {
+                                    let _ = sender.send(envelope);
+                                }
+                            }
+                        }
+                        // The connection closed: reconnect and subscribe again
+                        pubsub = loop {
+                            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                            let Ok(mut pubsub) = client.get_async_pubsub().await else {
+                                continue;
+                            };
+                            if pubsub.subscribe(Self::CHANNELS).await.is_ok() {
+                                break pubsub;
+                            }
+                        };
+                    }
+                });
+                Ok(Self { events })
+            }
+        }
+
+        #[cfg(feature = "redis")]
+        impl EventSource for RedisEventSource {
+            fn subscribe(&self, topic: &str) -> EventStream {
+                self.events.subscribe(topic)
+            }
+        }
+    }
+}
+
+/// Generate the statement recording an event for `model` on `conn`
+///
+/// `model` must be the entity model after the write; its masked DTO is the
+/// payload.
+pub fn generate_record(entity_module: &proc_macro2::Ident, event: &str, conn: &TokenStream) -> TokenStream {
+    let aggregate = entity_module.to_string().to_upper_camel_case();
+    let payload_fn = format_ident!("{}_payload", entity_module);
+    let event_type = format!("{}{}", aggregate, event);
+    quote! {
+        super::super::outbox::record(
+            #conn,
+            #aggregate,
+            &model.id,
+            #event_type,
+            serde_json::to_vec(&super::super::outbox::#payload_fn(&model)).unwrap_or_default(),
+        )
+        .await
+        .map_err(StorageError::Database)?;
+    }
+}
+
+/// Generate the statement recording a delete of `request.id` on `conn`
+///
+/// Runs after the bulk delete, only when a row was deleted.
+pub fn generate_delete_record(entity_module: &proc_macro2::Ident, conn: &TokenStream) -> TokenStream {
+    let aggregate = entity_module.to_string().to_upper_camel_case();
+    let event_type = format!("{}Deleted", aggregate);
+    quote! {
+        if result.rows_affected > 0 {
+            super::super::outbox::record(#conn, #aggregate, &request.id, #event_type, Vec::new())
+                .await
+                .map_err(StorageError::Database)?;
+        }
+    }
+}
+
+/// Generate the statements recording a delete of each of `models` on `conn`
+///
+/// Runs after a bulk delete returning the deleted rows.
+pub fn generate_bulk_delete_record(entity_module: &proc_macro2::Ident, conn: &TokenStream) -> TokenStream {
+    let aggregate = entity_module.to_string().to_upper_camel_case();
+    let event_type = format!("{}Deleted", aggregate);
+    quote! {
+        for model in &models {
+            super::super::outbox::record(#conn, #aggregate, &model.id, #event_type, Vec::new())
+                .await
+                .map_err(StorageError::Database)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(entity: &str) -> EventPayload {
+        EventPayload {
+            entity: entity.to_string(),
+            pii: Vec::new(),
+            id_columns: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_generate_entity() {
+        let code = generate_entity().to_string();
+        assert!(code.contains("table_name = \"outbox\""));
+        assert!(code.contains("pub payload : Vec < u8 >"));
+        assert!(code.contains("pub context : Option < String >"));
+        assert!(code.contains("pub published_at : Option <"));
+    }
+
+    #[test]
+    fn test_generate_module() {
+        let code = generate_module("blog", &[payload("Post")], false, false).to_string();
+        assert!(code.contains("pub trait EventPublisher"));
+        assert!(code.contains("pub async fn record"));
+        assert!(code.contains("LockBehavior :: SkipLocked"));
+        assert!(code.contains("pub fn spawn_relay"));
+        assert!(code.contains("pub const NOTIFY_CHANNEL : & str = \"blog_outbox\""));
+        assert!(code.contains("\"SELECT pg_notify($1, $2)\""));
+    }
+
+    #[test]
+    fn test_events_record_request_context() {
+        let code = generate_module("blog", &[payload("Post")], false, false).to_string();
+        assert!(code.contains("context : Set (None)"));
+
+        let code = generate_module("blog", &[payload("Post")], false, true).to_string();
+        assert!(code.contains("context : Set (super :: prelude :: request_context ()"));
+        assert!(code.contains("context : event . context . clone ()"));
+    }
+
+    #[test]
+    fn test_generate_envelope() {
+        let code = generate_envelope("blog", &["Post".to_string(), "BlogTag".to_string()], false).to_string();
+        assert!(code.contains("pub struct EventEnvelope"));
+        assert!(code.contains("aggregate_id : event . aggregate_id . clone ()"));
+        assert!(code.contains("pub struct PostEvent"));
+        assert!(code.contains("pub payload : Vec < u8 >"));
+        assert!(code.contains("pub fn post (& self) -> Option < serde_json :: Value >"));
+        assert!(code.contains("\"BlogTag\" => \"blog.blog_tag\" . to_string ()"));
+
+        let code = generate_envelope("blog", &["Post".to_string()], true).to_string();
+        assert!(code.contains("super :: ids :: external_id (& event . aggregate_type , & event . aggregate_id)"));
+    }
+
+    #[test]
+    fn test_generate_payloads() {
+        let code = generate_payloads(&[payload("Tag")]).to_string();
+        assert!(code.contains("pub fn tag_payload (model : & super :: entities :: tag :: Model) -> serde_json :: Value"));
+        assert!(code.contains("serde_json :: to_value (super :: dto :: TagDto :: from (model . clone ()))"));
+        assert!(!code.contains("fn mask_pii"));
+        assert!(!code.contains("fn encode_ids"));
+
+        let user = EventPayload {
+            entity: "User".to_string(),
+            pii: vec!["email".to_string()],
+            id_columns: vec![("id".to_string(), "User".to_string())],
+        };
+        let code = generate_payloads(&[user]).to_string();
+        assert!(code.contains("mask_pii (& mut payload , & [\"email\"])"));
+        assert!(code.contains("encode_ids (& mut payload , & [(\"id\" , \"User\")])"));
+        assert!(code.contains("super :: ids :: external_id (entity , id)"));
+    }
+
+    #[test]
+    fn test_generate_publishers() {
+        let code = generate_publishers().to_string();
+        assert!(code.contains("# [cfg (feature = \"kafka\")] pub struct KafkaPublisher"));
+        assert!(code.contains("# [cfg (feature = \"nats\")] pub struct NatsPublisher"));
+        assert!(code.contains("Nats-Msg-Id"));
+        assert!(code.contains("# [cfg (feature = \"redis\")] pub struct RedisPublisher"));
+    }
+
+    #[test]
+    fn test_generate_sources() {
+        let code = generate_sources("blog", &["Post".to_string()]).to_string();
+        assert!(code.contains("pub trait EventSource"));
+        assert!(code.contains("impl EventSource for BroadcastEvents"));
+        assert!(code.contains("# [cfg (feature = \"nats\")] pub struct NatsEventSource"));
+        assert!(code.contains("# [cfg (feature = \"kafka\")] pub struct KafkaEventSource"));
+        assert!(code.contains("# [cfg (feature = \"pg-notify\")] # [derive (Clone)] pub struct PgNotifyEvents"));
+        assert!(code.contains("# [cfg (feature = \"redis\")] # [derive (Clone)] pub struct RedisEventSource"));
+
+        // One pub/sub connection per source, fanned out to the subscriptions
+        assert!(code.contains("const CHANNELS : & 'static [& 'static str] = & [\"blog.post\"]"));
+        assert_eq!(code.matches("get_async_pubsub ()").count(), 2);
+        let redis = &code[code.find("impl EventSource for RedisEventSource").unwrap()..];
+        assert!(redis.contains("self . events . subscribe (topic)"));
+        assert!(!redis.contains("get_async_pubsub"));
+    }
+
+    #[test]
+    fn test_generate_record() {
+        let module = format_ident!("post");
+        let code = generate_record(&module, "Created", &quote! { &txn }).to_string();
+        assert!(code.contains("\"PostCreated\""));
+        assert!(code.contains("serde_json :: to_vec (& super :: super :: outbox :: post_payload (& model))"));
+        assert!(!code.contains("encode_to_vec"));
+
+        let code = generate_delete_record(&module, &quote! { &txn }).to_string();
+        assert!(code.contains("\"PostDeleted\""));
+        assert!(code.contains("result . rows_affected > 0"));
+    }
+}
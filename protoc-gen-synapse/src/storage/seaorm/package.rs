@@ -6,9 +6,22 @@
 //! - Storage traits and implementations (in storage/)
 //! - gRPC services (in grpc/)
 //! - GraphQL module (in graphql/)
-
-use super::options::{get_cached_entity_options, get_cached_service_options, get_cached_validate_message_options};
+//!
+//! Submodules and their re-exports are only declared for files that were
+//! actually produced, so generation profiles that skip a layer still compile.
+
+use super::implementation::{
+    context_columns, generated_columns, update_many_patch_type, has_string_primary_key, has_ulid_primary_key,
+    geography_columns, has_uuid_primary_key, naive_timestamp_columns, MANAGED_TIMESTAMPS,
+};
+use super::options::{
+    get_cached_column_options, get_cached_entity_options, get_cached_service_options,
+    get_cached_validate_message_options,
+};
 use crate::error::GeneratorError;
+use crate::options::synapse::storage;
+use crate::params::time_crate;
+use crate::validate::{field_sanitize, generate_sanitize, generate_sanitize_optional};
 use heck::{ToSnakeCase, ToUpperCamelCase};
 use proc_macro2::TokenStream;
 use prost_types::compiler::code_generator_response::File;
@@ -23,6 +36,8 @@ pub struct PackageInfo {
     pub services: Vec<String>,
     /// Domain types (validated request types)
     pub domain_types: Vec<String>,
+    /// Entities with encrypted exports
+    pub exports: Vec<String>,
 }
 
 /// Collect package information from files in the same package
@@ -36,6 +51,7 @@ pub fn collect_package_info_all_files(
         entities: Vec::new(),
         services: Vec::new(),
         domain_types: Vec::new(),
+        exports: Vec::new(),
     };
 
     // Collect entities and domain types from files in the SAME package
@@ -55,6 +71,9 @@ pub fn collect_package_info_all_files(
             if let Some(opts) = get_cached_entity_options(file_name, msg_name) {
                 if !opts.skip {
                     info.entities.push(msg_name.to_string());
+                    if opts.encrypted_export {
+                        info.exports.push(msg_name.to_string());
+                    }
                 }
             }
 
@@ -80,23 +99,41 @@ pub fn collect_package_info_all_files(
     info
 }
 
+/// Whether a module of a package directory was produced, as `{module}.rs` or `{module}/mod.rs`
+pub fn has_module(produced: &[String], dir: &str, module: &str) -> bool {
+    let file = format!("{}/{}.rs", dir, module);
+    let mod_file = format!("{}/{}/mod.rs", dir, module);
+    produced.iter().any(|name| *name == file || *name == mod_file)
+}
+
 /// Generate all package files (main mod.rs and subdirectory mod.rs files)
+///
+/// `produced` names the files generated so far; the main mod.rs is built
+/// last and only declares modules among them and this function's own files.
 pub fn generate_all(
     file: &FileDescriptorProto,
     all_files: &[FileDescriptorProto],
+    produced: &[String],
 ) -> Result<Vec<File>, GeneratorError> {
     let mut files = Vec::new();
 
-    // Generate main mod.rs
-    if let Some(main_mod) = generate(file, all_files)? {
-        files.push(main_mod);
-    }
-
     // Generate entities/mod.rs
     if let Some(entities_mod) = generate_entities_mod(file, all_files)? {
         files.push(entities_mod);
     }
 
+    // Generate junction entities for many-to-many relations without a message
+    files.extend(super::join_table::generate(file, all_files)?);
+
+    // Generate the outbox entity and outbox.rs for emit_events entities
+    files.extend(super::outbox::generate(file, all_files)?);
+
+    // Generate the webhook entities and webhooks.rs for webhooks entities
+    files.extend(super::webhook::generate(file, all_files)?);
+
+    // Generate ids.rs for entities whose ids the API hides
+    files.extend(super::external_id::generate(file, all_files)?);
+
     // Generate storage/mod.rs
     if let Some(storage_mod) = generate_storage_mod(file, all_files)? {
         files.push(storage_mod);
@@ -112,13 +149,113 @@ pub fn generate_all(
         files.push(conversions);
     }
 
+    // Generate storage/export.rs
+    let info = collect_package_info_all_files(all_files, file);
+    if let Some(export) = super::export::generate(file, all_files, &info)? {
+        files.push(export);
+    }
+
+    // Generate registry.rs
+    if let Some(registry) = super::registry::generate(file, all_files)? {
+        files.push(registry);
+    }
+
+    // Generate dto.rs and dto_conversions.rs
+    files.extend(super::dto::generate(file, all_files)?);
+
+    // Generate search.rs
+    if let Some(search) = super::search::generate(file, all_files)? {
+        files.push(search);
+    }
+
+    // Generate migration.rs
+    if let Some(migration) = super::migration::generate(file, all_files)? {
+        files.push(migration);
+    }
+
+    // Generate seed.rs
+    if let Some(seed) = super::seed::generate(file, all_files)? {
+        files.push(seed);
+    }
+
+    // Generate schema_diff.rs
+    if let Some(schema_diff) = super::schema_diff::generate(file, all_files)? {
+        files.push(schema_diff);
+    }
+
+    // Generate factories.rs
+    if let Some(factories) = super::factory::generate(file, all_files)? {
+        files.push(factories);
+    }
+
+    // Generate names.json
+    if let Some(names) = super::names::generate(file, all_files)? {
+        files.push(names);
+    }
+
+    let mut produced = produced.to_vec();
+    produced.extend(files.iter().filter_map(|f| f.name.clone()));
+
+    // Generate api.rs and api.json
+    let api = super::api::generate(file, all_files, &produced)?;
+    produced.extend(api.iter().filter_map(|f| f.name.clone()));
+    files.extend(api);
+
+    // Generate main mod.rs
+    if let Some(main_mod) = generate(file, all_files, &produced)? {
+        files.insert(0, main_mod);
+    }
+
     Ok(files)
 }
 
+/// `impl Geography` of the prelude, handed as tokens to `$emit`
+///
+/// The generator passes `quote`; the EWKB round-trip test compiles the same
+/// code.
+macro_rules! geography_codec {
+    ($emit:ident) => {
+        $emit! {
+            impl Geography {
+                /// Little-endian EWKB Point with SRID 4326
+                pub fn new(latitude: f64, longitude: f64) -> Self {
+                    let mut ewkb = vec![1, 0x01, 0x00, 0x00, 0x20, 0xE6, 0x10, 0x00, 0x00];
+                    ewkb.extend_from_slice(&longitude.to_le_bytes());
+                    ewkb.extend_from_slice(&latitude.to_le_bytes());
+                    Self(ewkb)
+                }
+
+                /// (longitude, latitude), in either byte order, with or without SRID
+                fn coordinates(&self) -> Option<(f64, f64)> {
+                    let bytes = self.0.as_slice();
+                    let little = *bytes.first()? == 1;
+                    let flags = bytes.get(1..5)?;
+                    let has_srid = if little { flags[3] & 0x20 != 0 } else { flags[0] & 0x20 != 0 };
+                    let offset = if has_srid { 9 } else { 5 };
+                    let read = |at: usize| -> Option<f64> {
+                        let b: [u8; 8] = bytes.get(at..at + 8)?.try_into().ok()?;
+                        Some(if little { f64::from_le_bytes(b) } else { f64::from_be_bytes(b) })
+                    };
+                    Some((read(offset)?, read(offset + 8)?))
+                }
+
+                pub fn latitude(&self) -> f64 {
+                    self.coordinates().map(|(_, lat)| lat).unwrap_or_default()
+                }
+
+                pub fn longitude(&self) -> f64 {
+                    self.coordinates().map(|(lon, _)| lon).unwrap_or_default()
+                }
+            }
+        }
+    };
+}
+
 /// Generate the main package mod.rs file
 pub fn generate(
     file: &FileDescriptorProto,
     all_files: &[FileDescriptorProto],
+    produced: &[String],
 ) -> Result<Option<File>, GeneratorError> {
     let package = file.package.as_deref().unwrap_or("");
     if package.is_empty() {
@@ -132,30 +269,40 @@ pub fn generate(
         return Ok(None);
     }
 
-    // Generate module declarations for subdirectories
+    let dir = package.replace('.', "/");
+    let has = |module: &str| has_module(produced, &dir, module);
+
+    // Generate module declarations for the produced submodules
     let mut mod_declarations = Vec::new();
     let mut pub_uses = Vec::new();
 
-    // Subdirectory modules
-    if !info.entities.is_empty() {
-        mod_declarations.push(quote! { pub mod entities; });
-    }
-    if !info.services.is_empty() {
-        mod_declarations.push(quote! { pub mod storage; });
-        mod_declarations.push(quote! { pub mod grpc; });
+    // Stable API facade, subdirectory modules, and optional layers, in order
+    let modules = [
+        "api", "entities", "registry", "dto", "dto_conversions", "search", "outbox", "webhooks", "ids",
+        "migration", "seed", "schema_diff", "factories", "smoke", "storage", "grpc", "graphql",
+    ];
+    for module in modules.into_iter().filter(|m| has(m)) {
+        let mod_name = format_ident!("{}", module);
+        if module == "registry" {
+            mod_declarations.push(quote! {
+                #[doc(hidden)]
+                pub mod #mod_name;
+            });
+        } else {
+            mod_declarations.push(quote! { pub mod #mod_name; });
+        }
     }
 
-    // GraphQL module
-    mod_declarations.push(quote! { pub mod graphql; });
-
     // Domain type modules (validated request types)
-    for domain_type in &info.domain_types {
+    let domain_types: Vec<_> = info.domain_types.iter().filter(|t| has(&t.to_snake_case())).collect();
+    for domain_type in &domain_types {
         let mod_name = format_ident!("{}", domain_type.to_snake_case());
         mod_declarations.push(quote! { pub mod #mod_name; });
     }
 
     // Re-exports for entities (from entities module)
-    for entity in &info.entities {
+    let entities: &[String] = if has("entities") { &info.entities } else { &[] };
+    for entity in entities {
         let entity_camel = entity.to_upper_camel_case();
         let model_alias = format_ident!("{}Model", entity_camel);
         let entity_mod = format_ident!("{}", entity.to_snake_case());
@@ -171,18 +318,375 @@ pub fn generate(
         let impl_name = format_ident!("SeaOrm{}Storage", svc_camel);
         let grpc_name = format_ident!("{}GrpcService", svc_camel);
 
-        pub_uses.push(quote! { pub use storage::#trait_name; });
-        pub_uses.push(quote! { pub use storage::#impl_name; });
-        pub_uses.push(quote! { pub use grpc::#grpc_name; });
+        if has("storage") {
+            pub_uses.push(quote! { pub use storage::#trait_name; });
+            pub_uses.push(quote! { pub use storage::#impl_name; });
+        }
+        if has("grpc") {
+            pub_uses.push(quote! { pub use grpc::#grpc_name; });
+        }
     }
 
     // Re-exports for domain types (validated request types)
-    for domain_type in &info.domain_types {
+    for domain_type in &domain_types {
         let mod_name = format_ident!("{}", domain_type.to_snake_case());
         let type_name = format_ident!("{}", domain_type.to_upper_camel_case());
         pub_uses.push(quote! { pub use #mod_name::#type_name; });
     }
 
+    // UUID keys need the uuid feature, so only emit the impl when used
+    let uuid_key_impl = if info.entities.iter().any(|e| has_uuid_primary_key(all_files, e)) {
+        quote! {
+            #[cfg(feature = "sea-orm")]
+            impl KeyValue for Uuid {
+                fn parse_key(s: &str) -> Option<Self> {
+                    Uuid::parse_str(s).ok()
+                }
+
+                fn generate_key() -> Option<Self> {
+                    Some(Uuid::now_v7())
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // ULID keys need the ulid crate, so only emit the type when used
+    let ulid_key_impl = if info.entities.iter().any(|e| has_ulid_primary_key(all_files, e)) {
+        quote! {
+            /// ULID primary key, stored as its canonical 26-character string
+            ///
+            /// ULIDs sort lexicographically in creation order, so the string
+            /// column works directly as a keyset for cursor pagination.
+            #[cfg(feature = "sea-orm")]
+            #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, DeriveValueType)]
+            pub struct Ulid(pub String);
+
+            #[cfg(feature = "sea-orm")]
+            impl std::fmt::Display for Ulid {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.write_str(&self.0)
+                }
+            }
+
+            #[cfg(feature = "sea-orm")]
+            impl sea_orm::TryFromU64 for Ulid {
+                fn try_from_u64(_: u64) -> Result<Self, DbErr> {
+                    Err(DbErr::ConvertFromU64("Ulid"))
+                }
+            }
+
+            #[cfg(feature = "sea-orm")]
+            impl KeyValue for Ulid {
+                fn parse_key(s: &str) -> Option<Self> {
+                    // Normalizes case so lookups and keyset comparisons match
+                    ulid::Ulid::from_string(s).ok().map(|u| Self(u.to_string()))
+                }
+
+                fn generate_key() -> Option<Self> {
+                    Some(Self(ulid::Ulid::new().to_string()))
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Geography columns are rare, so only emit the value type when used
+    let geography_codec = geography_codec!(quote);
+    let geography_impl = if info.entities.iter().any(|e| !geography_columns(all_files, e).is_empty()) {
+        quote! {
+            /// Point in a PostGIS `geography(Point, 4326)` column, as EWKB
+            ///
+            /// Columns are selected `::bytea` and saved `::geography`, so no
+            /// PostGIS driver types are needed.
+            #[derive(Clone, Debug, PartialEq)]
+            #[cfg_attr(feature = "sea-orm", derive(DeriveValueType))]
+            pub struct Geography(pub Vec<u8>);
+
+            #geography_codec
+
+            impl From<super::super::synapse::relay::GeoPoint> for Geography {
+                fn from(p: super::super::synapse::relay::GeoPoint) -> Self {
+                    Self::new(p.latitude, p.longitude)
+                }
+            }
+
+            impl From<Geography> for super::super::synapse::relay::GeoPoint {
+                fn from(g: Geography) -> Self {
+                    Self {
+                        latitude: g.latitude(),
+                        longitude: g.longitude(),
+                    }
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Custom validators reference the app's CurrentUser, so only emit when used
+    let validation_context = if crate::validate::package_has_custom_validators(all_files, file) {
+        quote! {
+            /// What a `custom` validator can see besides the value
+            pub struct ValidationContext<'a> {
+                /// Database connection, when the caller has one
+                #[cfg(feature = "sea-orm")]
+                pub db: Option<&'a DatabaseConnection>,
+                /// The authenticated user, when the request carries one
+                pub current_user: Option<&'a crate::CurrentUser>,
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Dry runs need a task-local flag, so only emit it when a mutation has `dry_run`
+    let dry_run_impl = if crate::graphql::package_has_dry_run(all_files, file) {
+        quote! {
+            /// gRPC metadata key marking a dry-run call
+            pub const DRY_RUN_HEADER: &str = "x-synapse-dry-run";
+
+            tokio::task_local! {
+                static DRY_RUN: bool;
+            }
+
+            /// Whether the current call is a dry run, whose writes are rolled back
+            pub fn dry_run() -> bool {
+                DRY_RUN.try_with(|d| *d).unwrap_or(false)
+            }
+
+            /// Whether request metadata asks for a dry run
+            ///
+            /// Only `true` or `1` (any case) do; `false` or anything else is a real write.
+            pub fn dry_run_requested(metadata: &tonic::metadata::MetadataMap) -> bool {
+                metadata
+                    .get(DRY_RUN_HEADER)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|value| value.trim().eq_ignore_ascii_case("true") || value.trim() == "1")
+            }
+
+            /// Run `f` as a dry run when `dry_run` is set
+            pub async fn with_dry_run<F: std::future::Future>(dry_run: bool, f: F) -> F::Output {
+                DRY_RUN.scope(dry_run, f).await
+            }
+
+            /// Wrap a message in a gRPC request carrying the current dry-run flag
+            pub fn dry_run_request<T>(message: T) -> tonic::Request<T> {
+                let mut request = tonic::Request::new(message);
+                if dry_run() {
+                    request
+                        .metadata_mut()
+                        .insert(DRY_RUN_HEADER, tonic::metadata::MetadataValue::from_static("true"));
+                }
+                request
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Request context snapshots need a task-local, so only emit them when a service captures one
+    let request_context_impl = if crate::grpc::package_has_request_context(all_files, file) {
+        quote! {
+            /// Header carrying the gateway-assigned request id
+            pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+            /// Provenance of the call being served
+            ///
+            /// Captured once by the gRPC service from gateway headers and the
+            /// principal, and readable from storage hooks through
+            /// `request_context()`, so audit logs, events and webhooks agree.
+            #[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+            pub struct RequestContext {
+                /// The authenticated principal
+                pub actor_id: Option<String>,
+                /// `x-request-id`
+                pub request_id: Option<String>,
+                /// First `x-forwarded-for` hop, else `x-real-ip`, else the peer address
+                pub ip: Option<String>,
+                /// `x-forwarded-user-agent`, else `user-agent`
+                pub user_agent: Option<String>,
+            }
+
+            impl RequestContext {
+                /// Snapshot the gateway headers of an HTTP request
+                pub fn from_headers(
+                    headers: &tonic::codegen::http::HeaderMap,
+                    remote_addr: Option<std::net::SocketAddr>,
+                ) -> Self {
+                    Self::from_lookup(
+                        |name| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string),
+                        remote_addr,
+                    )
+                }
+
+                /// Snapshot the gateway headers forwarded as gRPC metadata
+                pub fn from_metadata<T>(request: &tonic::Request<T>) -> Self {
+                    let metadata = request.metadata();
+                    Self::from_lookup(
+                        |name| metadata.get(name).and_then(|v| v.to_str().ok()).map(str::to_string),
+                        request.remote_addr(),
+                    )
+                }
+
+                fn from_lookup(
+                    get: impl Fn(&str) -> Option<String>,
+                    remote_addr: Option<std::net::SocketAddr>,
+                ) -> Self {
+                    let forwarded = get("x-forwarded-for")
+                        .and_then(|v| v.split(',').next().map(|ip| ip.trim().to_string()))
+                        .filter(|ip| !ip.is_empty());
+                    Self {
+                        actor_id: None,
+                        request_id: get(REQUEST_ID_HEADER),
+                        ip: forwarded
+                            .or_else(|| get("x-real-ip"))
+                            .or_else(|| remote_addr.map(|addr| addr.ip().to_string())),
+                        // tonic appends its own user-agent, so hops forward the original separately
+                        user_agent: get("x-forwarded-user-agent").or_else(|| get("user-agent")),
+                    }
+                }
+
+                /// Copy the snapshot onto an outgoing gRPC request's metadata
+                pub fn forward<T>(&self, request: &mut tonic::Request<T>) {
+                    let metadata = request.metadata_mut();
+                    let headers = [
+                        (REQUEST_ID_HEADER, &self.request_id),
+                        ("x-forwarded-for", &self.ip),
+                        ("x-forwarded-user-agent", &self.user_agent),
+                    ];
+                    for (name, value) in headers {
+                        if let Some(value) = value.as_deref().and_then(|v| v.parse().ok()) {
+                            metadata.insert(name, value);
+                        }
+                    }
+                }
+            }
+
+            tokio::task_local! {
+                static REQUEST_CONTEXT: RequestContext;
+            }
+
+            /// The context of the call being served, if one was captured
+            pub fn request_context() -> Option<RequestContext> {
+                REQUEST_CONTEXT.try_with(Clone::clone).ok()
+            }
+
+            /// Run `f` with `context` as the current request context
+            pub async fn with_request_context<F: std::future::Future>(
+                context: RequestContext,
+                f: F,
+            ) -> F::Output {
+                REQUEST_CONTEXT.scope(context, f).await
+            }
+
+            /// Forward the current request context on an outgoing gRPC request
+            pub fn context_request<T>(request: impl tonic::IntoRequest<T>) -> tonic::Request<T> {
+                let mut request = request.into_request();
+                if let Some(context) = request_context() {
+                    context.forward(&mut request);
+                }
+                request
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Mutation quotas share one counter store trait across services
+    let rate_limit_impl = if crate::grpc::package_has_rate_limits(all_files, file) {
+        crate::grpc::generate_rate_limit_prelude()
+    } else {
+        quote! {}
+    };
+
+    // Invalidation hooks share one trait across services
+    let invalidation_impl = if crate::grpc::package_has_invalidation_hooks(all_files, file) {
+        crate::grpc::generate_invalidation_prelude()
+    } else {
+        quote! {}
+    };
+
+    // Partial batch writes report item failures as structured errors
+    let batch_error_impl = if super::batch::package_has_batch_errors(all_files, file) {
+        super::batch::generate_prelude()
+    } else {
+        quote! {}
+    };
+
+    // Entities with `row_cache` keep rows read by id in per-process LRUs
+    let row_cache_impl = if super::row_cache::package_has_row_cache(all_files, file) {
+        super::row_cache::generate_prelude()
+    } else {
+        quote! {}
+    };
+
+    // Cache decorators share one cache trait, implemented for Redis
+    let storage_cache_impl = if crate::storage::package_has_cache(all_files, file) {
+        quote! {
+            /// Byte cache behind the generated `Cached*Storage` decorators
+            ///
+            /// Each key holds a map of variants, one per distinct request, so
+            /// deleting the key drops every variant at once. Caching is best
+            /// effort: a failed lookup is a miss and a failed write or delete is
+            /// dropped, so a cache outage degrades to reading through to storage.
+            #[async_trait::async_trait]
+            pub trait StorageCache: Send + Sync {
+                /// Cached bytes of `variant` under `key`
+                async fn get(&self, key: &str, variant: &[u8]) -> Option<Vec<u8>>;
+
+                /// Store bytes as `variant` under `key`, expiring the key `ttl`
+                /// after its first variant was stored
+                async fn set(&self, key: &str, variant: &[u8], value: Vec<u8>, ttl: std::time::Duration);
+
+                /// Remove cached keys with all their variants
+                async fn delete(&self, keys: &[String]);
+            }
+
+            /// Keys are hashes with a field per variant
+            ///
+            /// `EXPIRE ... NX` needs Redis 7.
+            #[cfg(feature = "redis")]
+            #[async_trait::async_trait]
+            impl StorageCache for redis::aio::ConnectionManager {
+                async fn get(&self, key: &str, variant: &[u8]) -> Option<Vec<u8>> {
+                    use redis::AsyncCommands;
+                    let mut conn = self.clone();
+                    conn.hget::<_, _, Option<Vec<u8>>>(key, variant).await.ok().flatten()
+                }
+
+                async fn set(&self, key: &str, variant: &[u8], value: Vec<u8>, ttl: std::time::Duration) {
+                    // NX keeps the first variant's expiry, so the TTL still bounds staleness
+                    let mut conn = self.clone();
+                    let _: redis::RedisResult<()> = redis::pipe()
+                        .atomic()
+                        .hset(key, variant, value)
+                        .ignore()
+                        .cmd("EXPIRE")
+                        .arg(key)
+                        .arg(ttl.as_secs().max(1))
+                        .arg("NX")
+                        .ignore()
+                        .query_async(&mut conn)
+                        .await;
+                }
+
+                async fn delete(&self, keys: &[String]) {
+                    use redis::AsyncCommands;
+                    if keys.is_empty() {
+                        return;
+                    }
+                    let mut conn = self.clone();
+                    let _ = conn.del::<_, ()>(keys).await;
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let code = quote! {
         //! Package module - combines tonic and synapse generated code
         //!
@@ -193,13 +697,17 @@ pub fn generate(
         #![allow(unused_imports)]
         #![allow(clippy::all)]
         #![allow(dead_code)]
+        #![allow(unexpected_cfgs)]
 
         // Include tonic-generated gRPC code (from OUT_DIR)
         tonic::include_proto!(#package);
 
         // Prelude for synapse-generated code
+        #[doc(hidden)]
         pub mod prelude {
+            #[cfg(feature = "sea-orm")]
             pub use sea_orm::entity::prelude::*;
+            #[cfg(feature = "sea-orm")]
             pub use sea_orm::{DatabaseConnection, DbErr};
 
             // Re-export proto types from parent
@@ -208,12 +716,115 @@ pub fn generate(
             /// Storage error type
             #[derive(Debug, thiserror::Error)]
             pub enum StorageError {
+                #[cfg(feature = "sea-orm")]
                 #[error("database error: {0}")]
                 Database(#[from] sea_orm::DbErr),
                 #[error("not found: {0}")]
                 NotFound(String),
                 #[error("invalid argument: {0}")]
                 InvalidArgument(String),
+                #[error("already exists: {message}")]
+                AlreadyExists { field: String, message: String },
+            }
+
+            /// Debug wrapper that prints a placeholder instead of a PII value
+            ///
+            /// Nothing derived from the value is printed: an unkeyed hash of
+            /// low-entropy data (emails, phone numbers) is trivially reversed.
+            pub struct Pii<'a, T: ?Sized>(pub &'a T);
+
+            impl<T: ?Sized> std::fmt::Debug for Pii<'_, T> {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    f.write_str("pii:<redacted>")
+                }
+            }
+
+            /// A changed field between two versions of a row (see each entity's `diff`)
+            ///
+            /// Values are Debug-formatted. Only columns marked `pii` are masked
+            /// (as `pii:<redacted>`); every other value is printed as stored, so
+            /// mark sensitive columns before recording changes anywhere.
+            #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+            pub struct FieldChange {
+                /// Column name
+                pub field: &'static str,
+                pub old: String,
+                pub new: String,
+                /// Values are masked
+                pub sensitive: bool,
+            }
+
+            /// Primary key value that can be parsed from request ids and cursors
+            pub trait KeyValue: Sized {
+                /// Parse a key from its string form
+                fn parse_key(s: &str) -> Option<Self>;
+
+                /// Generate a key in storage; `None` leaves it to the database
+                fn generate_key() -> Option<Self> {
+                    None
+                }
+            }
+
+            impl KeyValue for i32 {
+                fn parse_key(s: &str) -> Option<Self> {
+                    s.parse().ok()
+                }
+            }
+
+            impl KeyValue for i64 {
+                fn parse_key(s: &str) -> Option<Self> {
+                    s.parse().ok()
+                }
+            }
+
+            impl KeyValue for String {
+                fn parse_key(s: &str) -> Option<Self> {
+                    Some(s.to_string())
+                }
+            }
+
+            #uuid_key_impl
+
+            #ulid_key_impl
+
+            #geography_impl
+
+            #validation_context
+
+            #dry_run_impl
+
+            #request_context_impl
+
+            #storage_cache_impl
+
+            #rate_limit_impl
+
+            #invalidation_impl
+
+            #batch_error_impl
+
+            #row_cache_impl
+
+            /// Primary key value type of an entity
+            #[cfg(feature = "sea-orm")]
+            pub type KeyOf<E> = <<E as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType;
+
+            /// Parse a request id or cursor into an entity's primary key
+            #[cfg(feature = "sea-orm")]
+            pub fn parse_key<E: EntityTrait>(id: impl std::fmt::Display) -> Option<KeyOf<E>>
+            where
+                KeyOf<E>: KeyValue,
+            {
+                KeyOf::<E>::parse_key(&id.to_string())
+            }
+
+            /// Generate a primary key for a new entity, if storage owns key generation
+            #[cfg(feature = "sea-orm")]
+            pub fn generate_key<E: EntityTrait>() -> Option<KeyOf<E>>
+            where
+                KeyOf<E>: KeyValue,
+            {
+                KeyOf::<E>::generate_key()
             }
         }
 
@@ -226,10 +837,7 @@ pub fn generate(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output path
     let output_path = format!("{}/mod.rs", package.replace('.', "/"));
@@ -258,7 +866,7 @@ pub fn generate_entities_mod(
     }
 
     // Generate module declarations for entities
-    let mod_declarations: Vec<_> = info
+    let mut mod_declarations: Vec<_> = info
         .entities
         .iter()
         .map(|entity| {
@@ -267,6 +875,26 @@ pub fn generate_entities_mod(
         })
         .collect();
 
+    // Synthesized junction tables for many-to-many relations
+    for table in super::join_table::collect_join_tables(all_files, file)? {
+        let mod_name = format_ident!("{}", table.module);
+        mod_declarations.push(quote! { pub mod #mod_name; });
+    }
+
+    // Event outbox for emit_events entities
+    if super::outbox::package_emits_events(all_files, file) {
+        let mod_name = format_ident!("{}", super::outbox::OUTBOX_MODULE);
+        mod_declarations.push(quote! { pub mod #mod_name; });
+    }
+
+    // Registered webhooks, recorded events, and their deliveries
+    if super::webhook::package_has_webhooks(all_files, file) {
+        for module in [super::webhook::WEBHOOK_MODULE, super::webhook::EVENT_MODULE, super::webhook::DELIVERY_MODULE] {
+            let mod_name = format_ident!("{}", module);
+            mod_declarations.push(quote! { pub mod #mod_name; });
+        }
+    }
+
     // Re-export Models with aliases
     let pub_uses: Vec<_> = info
         .entities
@@ -295,10 +923,7 @@ pub fn generate_entities_mod(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output path
     let output_path = format!("{}/entities/mod.rs", package.replace('.', "/"));
@@ -358,10 +983,58 @@ pub fn generate_storage_mod(
         pub_uses.push(quote! { pub use #impl_mod::#impl_name; });
     }
 
+    // Cache decorators (cache=true)
+    for svc in &info.services {
+        let cached = all_files
+            .iter()
+            .filter(|f| f.package == file.package)
+            .any(|f| crate::storage::has_cache(f, svc));
+        if cached {
+            let cache_mod = format_ident!("cached_{}_storage", svc.to_snake_case());
+            mod_declarations.push(quote! { pub mod #cache_mod; });
+
+            let cache_name = format_ident!("Cached{}Storage", svc.to_upper_camel_case());
+            pub_uses.push(quote! { pub use #cache_mod::#cache_name; });
+        }
+    }
+
+    // Feed routers (public_feed entities)
+    for svc in &info.services {
+        if crate::storage::has_feeds(file, svc, all_files) {
+            let feed_mod = format_ident!("{}_feed", svc.to_snake_case());
+            mod_declarations.push(quote! { pub mod #feed_mod; });
+        }
+    }
+
+    // Application error alias (app_error=<type>)
+    let app_error_alias = match crate::params::app_error() {
+        Some(app_error) => {
+            let app_error: syn::Type = syn::parse_str(&app_error)
+                .map_err(|e| GeneratorError::CodeGenError(format!("invalid app_error type {:?}: {}", app_error, e)))?;
+            quote! {
+                /// Application error the generated errors convert into with `?`
+                pub type AppError = #app_error;
+
+                /// Result of application code built on the generated layers
+                pub type AppResult<T, E = AppError> = std::result::Result<T, E>;
+            }
+        }
+        None => quote! {},
+    };
+
     // Conversions module
-    mod_declarations.push(quote! { pub mod conversions; });
+    mod_declarations.push(quote! {
+        #[doc(hidden)]
+        pub mod conversions;
+    });
     pub_uses.push(quote! { pub use conversions::ApplyUpdate; });
 
+    // Encrypted exports module
+    if !info.exports.is_empty() {
+        mod_declarations.push(quote! { pub mod export; });
+        pub_uses.push(quote! { pub use export::{ExportConfig, ExportError}; });
+    }
+
     let code = quote! {
         //! Storage traits and implementations
         //!
@@ -381,14 +1054,13 @@ pub fn generate_storage_mod(
 
         // Re-exports
         #(#pub_uses)*
+
+        #app_error_alias
     };
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output path
     let output_path = format!("{}/storage/mod.rs", package.replace('.', "/"));
@@ -428,6 +1100,16 @@ pub fn generate_grpc_mod(
         pub_uses.push(quote! { pub use #svc_mod::#grpc_name; });
     }
 
+    // rustls helpers (see grpc::generate_tls)
+    if crate::params::rustls() {
+        mod_declarations.push(quote! { pub mod tls; });
+    }
+
+    // Server with health reporting (see grpc::generate_server)
+    if crate::params::health() {
+        mod_declarations.push(quote! { pub mod server; });
+    }
+
     let code = quote! {
         //! gRPC service implementations
         //!
@@ -445,10 +1127,7 @@ pub fn generate_grpc_mod(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output path
     let output_path = format!("{}/grpc/mod.rs", package.replace('.', "/"));
@@ -482,18 +1161,61 @@ pub fn generate_conversions(
     for entity in &info.entities {
         let entity_mod = format_ident!("{}", entity.to_snake_case());
         let proto_type = format_ident!("{}", entity.to_upper_camel_case());
-        let create_request = format_ident!("Create{}Request", entity.to_upper_camel_case());
         let update_request = format_ident!("Update{}Request", entity.to_upper_camel_case());
 
-        // Find the entity message across all files
-        let message = all_files
+        // Find the entity message across the package's files
+        let message_with_file = all_files
+            .iter()
+            .filter(|f| f.package.as_deref().unwrap_or("") == package)
+            .flat_map(|f| f.message_type.iter().map(move |m| (f, m)))
+            .find(|(_, m)| m.name.as_deref() == Some(entity));
+        let message = message_with_file.map(|(_, m)| m);
+
+        // Storage sets managed timestamps, so requests never write them
+        let managed: &[&str] = if entity_has_managed_timestamps(all_files, package, entity) {
+            MANAGED_TIMESTAMPS
+        } else {
+            &[]
+        };
+
+        // from_context columns are stamped by the gRPC service on the operations
+        // they name; on the others the client value is dropped
+        let context = context_columns(all_files, package, entity)?;
+        // Database-generated columns are never written
+        let generated: Vec<String> = all_files
+            .iter()
+            .flat_map(|f| {
+                let file_name = f.name.as_deref().unwrap_or("");
+                f.message_type
+                    .iter()
+                    .filter(|m| m.name.as_deref() == Some(entity))
+                    .flat_map(move |m| generated_columns(file_name, m))
+            })
+            .collect();
+        let create_managed: Vec<&str> = managed
             .iter()
-            .flat_map(|f| f.message_type.iter())
-            .find(|m| m.name.as_deref() == Some(entity));
+            .copied()
+            .chain(generated.iter().map(String::as_str))
+            .chain(context.iter().filter(|c| !c.on_create).map(|c| c.name.as_str()))
+            .collect();
+        let update_managed: Vec<&str> = managed
+            .iter()
+            .copied()
+            .chain(generated.iter().map(String::as_str))
+            .chain(context.iter().filter(|c| !c.on_update).map(|c| c.name.as_str()))
+            .collect();
+
+        // PostGIS columns convert through the prelude's Geography
+        let geography = geography_columns(all_files, entity);
 
         if let Some(msg) = message {
             // Generate Model -> Proto conversion
-            let model_fields = generate_model_to_proto_fields(msg);
+            let model_fields = generate_model_to_proto_fields(
+                msg,
+                has_string_primary_key(all_files, entity),
+                &naive_timestamp_columns(all_files, entity),
+                &geography,
+            );
 
             conversions.push(quote! {
                 /// Convert SeaORM Model to proto message
@@ -506,53 +1228,55 @@ pub fn generate_conversions(
                 }
             });
 
-            // Find CreateRequest message and check for domain type
-            let create_request_name = format!("Create{}Request", entity);
-            let create_msg_with_file = all_files
-                .iter()
-                .flat_map(|f| f.message_type.iter().map(move |m| (f, m)))
-                .find(|(_, m)| m.name.as_deref() == Some(&create_request_name));
+            // compare_by also makes the proto message usable in sets and maps
+            if let Some((file, _)) = message_with_file {
+                conversions.push(generate_proto_compare_impl(
+                    file.name.as_deref().unwrap_or(""),
+                    msg,
+                    &proto_type,
+                )?);
+            }
+
+            // Find the Create and Upsert request messages and check for domain types;
+            // both convert to a new ActiveModel
+            for operation in ["Create", "Upsert"] {
+                let request_name = format!("{}{}Request", operation, entity);
+                let request_ident = format_ident!("{}", request_name);
+                let Some((file, request)) = all_files
+                    .iter()
+                    .flat_map(|f| f.message_type.iter().map(move |m| (f, m)))
+                    .find(|(_, m)| m.name.as_deref() == Some(&request_name))
+                else {
+                    continue;
+                };
 
-            if let Some((file, create)) = create_msg_with_file {
                 let file_name = file.name.as_deref().unwrap_or("");
-                let create_fields = generate_create_fields(create);
+                let create_fields =
+                    generate_create_fields(file_name, request, &create_managed, &geography);
 
                 // Check if there's a domain type for this request
-                if let Some(opts) = get_cached_validate_message_options(file_name, &create_request_name) {
-                    if opts.generate_conversion && !opts.name.is_empty() {
-                        // Use domain type
-                        let domain_type = format_ident!("{}", opts.name);
-                        conversions.push(quote! {
-                            /// Convert validated domain type to SeaORM ActiveModel
-                            impl From<super::super::#domain_type> for super::super::entities::#entity_mod::ActiveModel {
-                                fn from(request: super::super::#domain_type) -> Self {
-                                    use sea_orm::ActiveValue::Set;
-                                    Self {
-                                        #(#create_fields)*
-                                        ..Default::default()
-                                    }
-                                }
-                            }
-                        });
-                    } else {
-                        conversions.push(quote! {
-                            /// Convert CreateRequest to SeaORM ActiveModel
-                            impl From<#create_request> for super::super::entities::#entity_mod::ActiveModel {
-                                fn from(request: #create_request) -> Self {
-                                    use sea_orm::ActiveValue::Set;
-                                    Self {
-                                        #(#create_fields)*
-                                        ..Default::default()
-                                    }
+                let domain_type = get_cached_validate_message_options(file_name, &request_name)
+                    .filter(|opts| opts.generate_conversion && !opts.name.is_empty())
+                    .map(|opts| format_ident!("{}", opts.name));
+                if let Some(domain_type) = domain_type {
+                    conversions.push(quote! {
+                        /// Convert validated domain type to SeaORM ActiveModel
+                        impl From<super::super::#domain_type> for super::super::entities::#entity_mod::ActiveModel {
+                            fn from(request: super::super::#domain_type) -> Self {
+                                use sea_orm::ActiveValue::Set;
+                                Self {
+                                    #(#create_fields)*
+                                    ..Default::default()
                                 }
                             }
-                        });
-                    }
+                        }
+                    });
                 } else {
+                    let doc = format!(" Convert {}Request to SeaORM ActiveModel", operation);
                     conversions.push(quote! {
-                        /// Convert CreateRequest to SeaORM ActiveModel
-                        impl From<#create_request> for super::super::entities::#entity_mod::ActiveModel {
-                            fn from(request: #create_request) -> Self {
+                        #[doc = #doc]
+                        impl From<#request_ident> for super::super::entities::#entity_mod::ActiveModel {
+                            fn from(request: #request_ident) -> Self {
                                 use sea_orm::ActiveValue::Set;
                                 Self {
                                     #(#create_fields)*
@@ -573,7 +1297,8 @@ pub fn generate_conversions(
 
             if let Some((file, update)) = update_msg_with_file {
                 let file_name = file.name.as_deref().unwrap_or("");
-                let update_fields = generate_update_fields(update, msg);
+                let update_fields =
+                    generate_update_fields(file_name, update, msg, &update_managed, &geography);
 
                 // Check if there's a domain type for this request
                 if let Some(opts) = get_cached_validate_message_options(file_name, &update_request_name) {
@@ -612,6 +1337,44 @@ pub fn generate_conversions(
                     });
                 }
             }
+
+            // Patches of update-many requests filtering this entity; the gRPC
+            // service stamps them like a single update
+            let filter_type = format!("{}Filter", entity.to_upper_camel_case());
+            let mut patches: Vec<String> = Vec::new();
+            for file in all_files {
+                let file_name = file.name.as_deref().unwrap_or("");
+                for request in &file.message_type {
+                    let filters_entity = request.field.iter().any(|f| {
+                        f.name.as_deref() == Some("filter")
+                            && f.type_name.as_deref().is_some_and(|t| t.rsplit('.').next() == Some(filter_type.as_str()))
+                    });
+                    let Some(patch_name) = update_many_patch_type(request).filter(|_| filters_entity) else {
+                        continue;
+                    };
+                    if patch_name == update_request_name || patches.contains(&patch_name) {
+                        continue;
+                    }
+                    let Some(patch) = file.message_type.iter().find(|m| m.name.as_deref() == Some(&patch_name)) else {
+                        continue;
+                    };
+                    let patch_fields = generate_update_fields(file_name, patch, msg, &update_managed, &geography);
+                    let patch_type = get_cached_validate_message_options(file_name, &patch_name)
+                        .filter(|opts| opts.generate_conversion && !opts.name.is_empty())
+                        .map(|opts| format_ident!("{}", opts.name))
+                        .unwrap_or_else(|| format_ident!("{}", patch_name));
+                    conversions.push(quote! {
+                        /// Apply an update-many patch to SeaORM ActiveModel
+                        impl ApplyUpdate<&super::super::#patch_type> for super::super::entities::#entity_mod::ActiveModel {
+                            fn apply_update(&mut self, request: &super::super::#patch_type) {
+                                use sea_orm::ActiveValue::Set;
+                                #(#patch_fields)*
+                            }
+                        }
+                    });
+                    patches.push(patch_name);
+                }
+            }
         }
     }
 
@@ -636,10 +1399,7 @@ pub fn generate_conversions(
 
     // Format the generated code
     let content = code.to_string();
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    let formatted = crate::params::format_code(content);
 
     // Output path (now in storage/ subdirectory)
     let output_path = format!("{}/storage/conversions.rs", package.replace('.', "/"));
@@ -651,8 +1411,77 @@ pub fn generate_conversions(
     }))
 }
 
+/// Generate Eq and Hash for an entity's proto message under `compare_by`
+///
+/// prost derives field-wise PartialEq, so Eq only holds without float
+/// fields. Hash covers the primary key, or every hashable field; either is a
+/// subset of the compared fields, which keeps it consistent with equality.
+/// There is no Ord, as it would disagree with the derived PartialEq; sort
+/// through the model's `sort_by_field` instead.
+fn generate_proto_compare_impl(
+    file_name: &str,
+    message: &prost_types::DescriptorProto,
+    proto_type: &proc_macro2::Ident,
+) -> Result<TokenStream, GeneratorError> {
+    use prost_types::field_descriptor_proto::Type;
+
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let compare_by = get_cached_entity_options(file_name, msg_name)
+        .map(|o| o.compare_by())
+        .unwrap_or_default();
+    if compare_by == storage::CompareBy::Unspecified {
+        return Ok(quote! {});
+    }
+
+    if let Some(field) = message
+        .field
+        .iter()
+        .find(|f| matches!(f.r#type(), Type::Float | Type::Double))
+    {
+        return Err(GeneratorError::CodeGenError(format!(
+            "{}.{}: compare_by needs Eq, which float fields cannot implement",
+            msg_name,
+            field.name.as_deref().unwrap_or("")
+        )));
+    }
+
+    let hashed = message.field.iter().filter(|f| {
+        // prost oneof enums do not implement Hash
+        let in_oneof = f.oneof_index.is_some() && !f.proto3_optional.unwrap_or(false);
+        let hashable = match f.r#type() {
+            Type::Message => f.type_name.as_deref() == Some(".google.protobuf.Timestamp"),
+            Type::Group => false,
+            _ => true,
+        };
+        let selected = compare_by == storage::CompareBy::AllColumns
+            || get_cached_column_options(file_name, msg_name, f.number.unwrap_or(0))
+                .is_some_and(|o| o.primary_key);
+        !in_oneof && hashable && selected
+    });
+    let names: Vec<_> = hashed
+        .map(|f| format_ident!("{}", f.name.as_deref().unwrap_or("").to_snake_case()))
+        .collect();
+
+    Ok(quote! {
+        impl Eq for #proto_type {}
+
+        impl std::hash::Hash for #proto_type {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                #(std::hash::Hash::hash(&self.#names, state);)*
+            }
+        }
+    })
+}
+
 /// Generate field assignments for Model -> Proto conversion
-fn generate_model_to_proto_fields(message: &prost_types::DescriptorProto) -> Vec<TokenStream> {
+///
+/// `naive` timestamp columns hold UTC wall time without a zone.
+fn generate_model_to_proto_fields(
+    message: &prost_types::DescriptorProto,
+    string_key: bool,
+    naive: &[String],
+    geography: &[String],
+) -> Vec<TokenStream> {
     let mut fields = Vec::new();
 
     for field in &message.field {
@@ -666,14 +1495,34 @@ fn generate_model_to_proto_fields(message: &prost_types::DescriptorProto) -> Vec
             .map(|t| t.contains("Timestamp"))
             .unwrap_or(false);
 
-        if is_timestamp {
-            // SeaORM uses DateTime<Utc> which has .timestamp() directly
+        if string_key && field_name == "id" {
+            // UUID/ULID column, string in the proto
+            fields.push(quote! {
+                #rust_field: model.#rust_field.to_string(),
+            });
+        } else if geography.contains(&field_name.to_snake_case()) {
             fields.push(quote! {
-                #rust_field: Some(prost_types::Timestamp {
-                    seconds: model.#rust_field.timestamp(),
-                    nanos: model.#rust_field.timestamp_subsec_nanos() as i32,
-                }),
+                #rust_field: model.#rust_field.map(Into::into),
             });
+        } else if is_timestamp {
+            // DateTime<Utc> or OffsetDateTime, depending on the time crate;
+            // NaiveDateTime or PrimitiveDateTime for naive columns
+            let optional = field.proto3_optional.unwrap_or(false);
+            let value = if optional { quote! { v } } else { quote! { model.#rust_field } };
+            let timestamp = if naive.contains(&field_name.to_snake_case()) {
+                time_crate().naive_to_proto(value)
+            } else {
+                time_crate().to_proto(value)
+            };
+            if optional {
+                fields.push(quote! {
+                    #rust_field: model.#rust_field.map(|v| #timestamp),
+                });
+            } else {
+                fields.push(quote! {
+                    #rust_field: Some(#timestamp),
+                });
+            }
         } else {
             fields.push(quote! {
                 #rust_field: model.#rust_field,
@@ -684,16 +1533,52 @@ fn generate_model_to_proto_fields(message: &prost_types::DescriptorProto) -> Vec
     fields
 }
 
+/// Check whether an entity (by message name in `package`) has storage-managed timestamps
+fn entity_has_managed_timestamps(all_files: &[FileDescriptorProto], package: &str, entity: &str) -> bool {
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.message_type.iter().any(|m| m.name.as_deref() == Some(entity))
+                && get_cached_entity_options(file_name, entity).is_some_and(|o| o.timestamps)
+        })
+}
+
 /// Generate field assignments for CreateRequest -> ActiveModel conversion
-fn generate_create_fields(message: &prost_types::DescriptorProto) -> Vec<TokenStream> {
+///
+/// String fields with `sanitize` options are normalized on the way in.
+fn generate_create_fields(
+    file_name: &str,
+    message: &prost_types::DescriptorProto,
+    managed: &[&str],
+    geography: &[String],
+) -> Vec<TokenStream> {
+    let message_name = message.name.as_deref().unwrap_or("");
     let mut fields = Vec::new();
 
     for field in &message.field {
         let field_name = field.name.as_deref().unwrap_or("");
+
+        // Skip fields storage sets itself
+        if managed.contains(&field_name) {
+            continue;
+        }
         let rust_field = format_ident!("{}", field_name.to_snake_case());
 
+        let value = match field_sanitize(file_name, message_name, field) {
+            _ if geography.contains(&field_name.to_snake_case()) => {
+                quote! { request.#rust_field.map(Into::into) }
+            }
+            Some(sanitize) if field.proto3_optional.unwrap_or(false) => {
+                generate_sanitize_optional(quote! { request.#rust_field }, &sanitize)
+            }
+            Some(sanitize) => generate_sanitize(quote! { request.#rust_field }, &sanitize),
+            None => quote! { request.#rust_field },
+        };
+
         fields.push(quote! {
-            #rust_field: Set(request.#rust_field),
+            #rust_field: Set(#value),
         });
     }
 
@@ -702,9 +1587,13 @@ fn generate_create_fields(message: &prost_types::DescriptorProto) -> Vec<TokenSt
 
 /// Generate field update assignments for UpdateRequest -> ActiveModel
 fn generate_update_fields(
+    file_name: &str,
     update_message: &prost_types::DescriptorProto,
     entity_message: &prost_types::DescriptorProto,
+    managed: &[&str],
+    geography: &[String],
 ) -> Vec<TokenStream> {
+    let update_name = update_message.name.as_deref().unwrap_or("");
     let mut fields = Vec::new();
 
     for field in &update_message.field {
@@ -715,6 +1604,11 @@ fn generate_update_fields(
             continue;
         }
 
+        // Skip fields storage sets itself
+        if managed.contains(&field_name) {
+            continue;
+        }
+
         let rust_field = format_ident!("{}", field_name.to_snake_case());
 
         // Check if the corresponding field in the entity is optional
@@ -723,21 +1617,30 @@ fn generate_update_fields(
             .iter()
             .find(|f| f.name.as_deref() == Some(field_name));
 
-        let is_optional_in_entity = entity_field
-            .map(|f| f.proto3_optional.unwrap_or(false))
-            .unwrap_or(false);
+        // Geography columns are always nullable
+        let is_geography = geography.contains(&field_name.to_snake_case());
+        let is_optional_in_entity = is_geography
+            || entity_field
+                .map(|f| f.proto3_optional.unwrap_or(false))
+                .unwrap_or(false);
+
+        let value = match field_sanitize(file_name, update_name, field) {
+            _ if is_geography => quote! { Geography::from(value.clone()) },
+            Some(sanitize) => generate_sanitize(quote! { value.clone() }, &sanitize),
+            None => quote! { value.clone() },
+        };
 
         // For optional entity fields, wrap value in Some()
         if is_optional_in_entity {
             fields.push(quote! {
                 if let Some(ref value) = request.#rust_field {
-                    self.#rust_field = Set(Some(value.clone()));
+                    self.#rust_field = Set(Some(#value));
                 }
             });
         } else {
             fields.push(quote! {
                 if let Some(ref value) = request.#rust_field {
-                    self.#rust_field = Set(value.clone());
+                    self.#rust_field = Set(#value);
                 }
             });
         }
@@ -745,3 +1648,128 @@ fn generate_update_fields(
 
     fields
 }
+
+#[cfg(test)]
+mod tests {
+    macro_rules! items {
+        ($($item:item)*) => {
+            $($item)*
+        };
+    }
+
+    /// The prelude's `Geography`, compiled from the generated code
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct Geography(pub Vec<u8>);
+
+    geography_codec!(items);
+
+    #[test]
+    fn test_geography_ewkb_round_trip() {
+        let point = Geography::new(51.5007, -0.1246);
+        // SRID=4326;POINT(-0.1246 51.5007) as PostGIS prints it with ST_AsEWKB
+        assert_eq!(&point.0[..9], &[0x01, 0x01, 0x00, 0x00, 0x20, 0xE6, 0x10, 0x00, 0x00]);
+        assert_eq!(point.0.len(), 25);
+        assert_eq!(point.latitude(), 51.5007);
+        assert_eq!(point.longitude(), -0.1246);
+    }
+
+    #[test]
+    fn test_geography_decodes_big_endian_without_srid() {
+        let mut wkb = vec![0x00, 0x00, 0x00, 0x00, 0x01];
+        wkb.extend_from_slice(&13.4050f64.to_be_bytes());
+        wkb.extend_from_slice(&52.5200f64.to_be_bytes());
+        let point = Geography(wkb);
+        assert_eq!(point.latitude(), 52.52);
+        assert_eq!(point.longitude(), 13.405);
+
+        // Truncated values decode as the origin rather than panicking
+        assert_eq!(Geography(vec![0x01]).latitude(), 0.0);
+    }
+
+    #[test]
+    fn test_migration_module_follows_produced_files() {
+        use super::super::options::{cache_entity_options, storage};
+        use prost_types::{DescriptorProto, FileDescriptorProto};
+
+        let file = FileDescriptorProto {
+            name: Some("mig_test/entities.proto".to_string()),
+            package: Some("mig_test".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Widget".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        cache_entity_options("mig_test/entities.proto", "Widget", storage::EntityOptions::default());
+        let all_files = std::slice::from_ref(&file);
+        let mod_rs = |produced: &[&str]| {
+            let produced: Vec<String> = produced.iter().map(|p| p.to_string()).collect();
+            super::generate(&file, all_files, &produced).unwrap().unwrap().content.unwrap()
+        };
+
+        // migrations=false produces no migration.rs, so nothing declares it
+        let without = mod_rs(&["mig_test/entities/mod.rs"]);
+        assert!(without.contains("pub mod entities;"));
+        assert!(!without.contains("migration"));
+
+        let with = mod_rs(&["mig_test/entities/mod.rs", "mig_test/migration.rs"]);
+        assert!(with.contains("pub mod migration;"));
+    }
+
+    #[test]
+    fn test_prelude_gates_backend_reexports() {
+        use super::super::options::{cache_entity_options, storage};
+        use prost_types::{DescriptorProto, FileDescriptorProto};
+
+        let file = FileDescriptorProto {
+            name: Some("gate_test/entities.proto".to_string()),
+            package: Some("gate_test".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Widget".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        cache_entity_options("gate_test/entities.proto", "Widget", storage::EntityOptions::default());
+        let produced = vec!["gate_test/entities/mod.rs".to_string()];
+        let mod_rs = super::generate(&file, std::slice::from_ref(&file), &produced)
+            .unwrap()
+            .unwrap()
+            .content
+            .unwrap();
+        let gated = |item: &str| {
+            let at = mod_rs.find(item).unwrap();
+            mod_rs[..at].trim_end().ends_with("#[cfg(feature = \"sea-orm\")]")
+        };
+        assert!(gated("pub use sea_orm::entity::prelude::*;"));
+        assert!(gated("pub use sea_orm::{DatabaseConnection, DbErr};"));
+        assert!(gated("pub type KeyOf<E>"));
+        assert!(mod_rs.contains("#![allow(unexpected_cfgs)]"));
+    }
+
+    #[test]
+    fn test_managed_timestamps_stay_in_package() {
+        use super::super::options::{cache_entity_options, storage};
+        use prost_types::{DescriptorProto, FileDescriptorProto};
+
+        let file = |name: &str, package: &str| FileDescriptorProto {
+            name: Some(name.to_string()),
+            package: Some(package.to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Order".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let files = [file("ts_blog/entities.proto", "ts_blog"), file("ts_shop/entities.proto", "ts_shop")];
+        cache_entity_options("ts_blog/entities.proto", "Order", storage::EntityOptions::default());
+        cache_entity_options(
+            "ts_shop/entities.proto",
+            "Order",
+            storage::EntityOptions { timestamps: true, ..Default::default() },
+        );
+
+        assert!(!super::entity_has_managed_timestamps(&files, "ts_blog", "Order"));
+        assert!(super::entity_has_managed_timestamps(&files, "ts_shop", "Order"));
+    }
+}
@@ -0,0 +1,298 @@
+//! Schema registry generation
+//!
+//! Generates the registry.rs module: static slices describing every entity in
+//! the package (table, columns, relations, and GraphQL exposure) plus a small
+//! lookup API, so applications can drive admin UIs, permission matrices, or
+//! search indexing from the generated domain without reparsing protos.
+
+use super::entity::is_field_nullable;
+use super::options::{
+    get_cached_column_options, get_cached_entity_options, get_cached_graphql_field_options,
+    get_cached_graphql_type_options,
+};
+use crate::error::GeneratorError;
+use crate::options::synapse::storage::RelationType;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::Type;
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+use quote::quote;
+
+/// Generate the registry.rs file for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() {
+        return Ok(None);
+    }
+
+    let mut entries = Vec::new();
+    for proto_file in all_files {
+        if proto_file.package.as_deref().unwrap_or("") != package {
+            continue;
+        }
+        for message in &proto_file.message_type {
+            if let Some(entry) = generate_entity_info(proto_file, message) {
+                entries.push(entry);
+            }
+        }
+    }
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    let code = quote! {
+        //! Schema registry
+        //!
+        //! Static description of the entities generated for this package.
+        //! @generated
+
+        #![allow(missing_docs)]
+
+        /// Kind of relation between two entities
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum RelationKind {
+            BelongsTo,
+            HasOne,
+            HasMany,
+            ManyToMany,
+            Polymorphic,
+        }
+
+        /// A column of a generated entity
+        #[derive(Debug, Clone, Copy)]
+        pub struct FieldInfo {
+            /// Proto field name
+            pub name: &'static str,
+            /// Database column name
+            pub column: &'static str,
+            /// Proto type (scalar name or fully-qualified message/enum name)
+            pub proto_type: &'static str,
+            pub primary_key: bool,
+            pub nullable: bool,
+            pub unique: bool,
+            pub pii: bool,
+            /// Exposed on the GraphQL object type
+            pub graphql: bool,
+        }
+
+        /// A relation declared on a generated entity
+        #[derive(Debug, Clone, Copy)]
+        pub struct RelationInfo {
+            pub name: &'static str,
+            pub kind: RelationKind,
+            /// Related entity name
+            pub related: &'static str,
+            pub foreign_key: &'static str,
+        }
+
+        /// A generated entity
+        #[derive(Debug, Clone, Copy)]
+        pub struct EntityInfo {
+            /// Proto message name
+            pub name: &'static str,
+            pub table: &'static str,
+            pub fields: &'static [FieldInfo],
+            pub relations: &'static [RelationInfo],
+            /// GraphQL type name, if the entity is exposed over GraphQL
+            pub graphql_type: Option<&'static str>,
+            /// Implements the Relay Node interface
+            pub node: bool,
+            pub soft_delete: bool,
+            pub timestamps: bool,
+        }
+
+        impl EntityInfo {
+            /// Look up a field by proto field name
+            pub fn field(&self, name: &str) -> Option<&'static FieldInfo> {
+                self.fields.iter().find(|f| f.name == name)
+            }
+
+            /// Look up a relation by name
+            pub fn relation(&self, name: &str) -> Option<&'static RelationInfo> {
+                self.relations.iter().find(|r| r.name == name)
+            }
+        }
+
+        /// All entities in this package
+        pub static ENTITIES: &[EntityInfo] = &[#(#entries),*];
+
+        /// All entities in this package
+        pub fn entities() -> &'static [EntityInfo] {
+            ENTITIES
+        }
+
+        /// Look up an entity by proto message name
+        pub fn entity(name: &str) -> Option<&'static EntityInfo> {
+            ENTITIES.iter().find(|e| e.name == name)
+        }
+
+        /// Look up an entity by table name
+        pub fn entity_by_table(table: &str) -> Option<&'static EntityInfo> {
+            ENTITIES.iter().find(|e| e.table == table)
+        }
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/registry.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the EntityInfo literal for a message, if it is an entity
+fn generate_entity_info(file: &FileDescriptorProto, message: &DescriptorProto) -> Option<TokenStream> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let entity_options = get_cached_entity_options(file_name, msg_name)?;
+    if entity_options.skip {
+        return None;
+    }
+
+    let table = if entity_options.table_name.is_empty() {
+        msg_name.to_snake_case()
+    } else {
+        entity_options.table_name.clone()
+    };
+
+    let type_options = get_cached_graphql_type_options(file_name, msg_name).filter(|o| !o.skip);
+    let exposed = type_options.is_some();
+    let graphql_type = match &type_options {
+        Some(opts) if !opts.name.is_empty() => {
+            let name = &opts.name;
+            quote! { Some(#name) }
+        }
+        Some(_) => {
+            let name = msg_name.to_upper_camel_case();
+            quote! { Some(#name) }
+        }
+        None => quote! { None },
+    };
+    let node = type_options.map(|o| o.node).unwrap_or(false);
+
+    let fields = message
+        .field
+        .iter()
+        .map(|f| generate_field_info(file_name, msg_name, f, exposed));
+
+    let relations = entity_options.relations.iter().map(|rel| {
+        let kind = match rel.r#type() {
+            RelationType::HasOne => quote! { HasOne },
+            RelationType::HasMany => quote! { HasMany },
+            RelationType::ManyToMany => quote! { ManyToMany },
+            RelationType::Polymorphic => quote! { Polymorphic },
+            RelationType::BelongsTo | RelationType::Unspecified => quote! { BelongsTo },
+        };
+        let name = &rel.name;
+        let related = &rel.related;
+        let foreign_key = &rel.foreign_key;
+        quote! {
+            RelationInfo {
+                name: #name,
+                kind: RelationKind::#kind,
+                related: #related,
+                foreign_key: #foreign_key,
+            }
+        }
+    });
+
+    let soft_delete = entity_options.soft_delete;
+    let timestamps = entity_options.timestamps;
+
+    Some(quote! {
+        EntityInfo {
+            name: #msg_name,
+            table: #table,
+            fields: &[#(#fields),*],
+            relations: &[#(#relations),*],
+            graphql_type: #graphql_type,
+            node: #node,
+            soft_delete: #soft_delete,
+            timestamps: #timestamps,
+        }
+    })
+}
+
+/// Generate the FieldInfo literal for a field
+fn generate_field_info(
+    file_name: &str,
+    msg_name: &str,
+    field: &FieldDescriptorProto,
+    exposed: bool,
+) -> TokenStream {
+    let name = field.name.as_deref().unwrap_or("");
+    let column_options = get_cached_column_options(file_name, msg_name, field.number.unwrap_or(0));
+    let column = column_options
+        .as_ref()
+        .map(|o| o.column_name.clone())
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| name.to_snake_case());
+    let proto_type = proto_type_name(field);
+    let primary_key = column_options.as_ref().map(|o| o.primary_key).unwrap_or(false);
+    let unique = column_options.as_ref().map(|o| o.unique).unwrap_or(false);
+    let pii = column_options.as_ref().map(|o| o.pii).unwrap_or(false);
+    let nullable = is_field_nullable(field);
+    let graphql = exposed
+        && !get_cached_graphql_field_options(file_name, msg_name, field.number.unwrap_or(0))
+            .map(|o| o.skip)
+            .unwrap_or(false);
+
+    quote! {
+        FieldInfo {
+            name: #name,
+            column: #column,
+            proto_type: #proto_type,
+            primary_key: #primary_key,
+            nullable: #nullable,
+            unique: #unique,
+            pii: #pii,
+            graphql: #graphql,
+        }
+    }
+}
+
+/// Proto type name for a field: scalar name, or the message/enum name without the leading dot
+fn proto_type_name(field: &FieldDescriptorProto) -> String {
+    match field.r#type() {
+        Type::Message | Type::Enum => field
+            .type_name
+            .as_deref()
+            .unwrap_or("")
+            .trim_start_matches('.')
+            .to_string(),
+        other => other
+            .as_str_name()
+            .trim_start_matches("TYPE_")
+            .to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proto_type_name() {
+        let field = FieldDescriptorProto {
+            r#type: Some(Type::Int64 as i32),
+            ..Default::default()
+        };
+        assert_eq!(proto_type_name(&field), "int64");
+
+        let field = FieldDescriptorProto {
+            r#type: Some(Type::Message as i32),
+            type_name: Some(".google.protobuf.Timestamp".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(proto_type_name(&field), "google.protobuf.Timestamp");
+    }
+}
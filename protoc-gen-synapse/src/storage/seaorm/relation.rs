@@ -95,7 +95,8 @@ fn generate_relation_field_with_reverse(
 ) -> Option<proc_macro2::TokenStream> {
     use quote::{format_ident, quote};
 
-    if rel_def.name.is_empty() || rel_def.related.is_empty() {
+    // Remote entities live in another service's database
+    if rel_def.name.is_empty() || rel_def.related.is_empty() || rel_def.remote {
         return None;
     }
 
@@ -263,7 +264,9 @@ fn generate_relation_field_with_reverse(
                 })
             }
         }
-        RelationType::Unspecified => None,
+        // Polymorphic relations have no database-level FK; they are resolved
+        // by dispatching on the discriminator column instead
+        RelationType::Polymorphic | RelationType::Unspecified => None,
     }
 }
 
@@ -303,4 +306,33 @@ mod tests {
         let fields = generate_relation_fields(&[rel_def], "user");
         assert!(fields.is_empty());
     }
+
+    #[test]
+    fn test_polymorphic_relation_has_no_field() {
+        let rel_def = RelationDef {
+            name: "commentable".to_string(),
+            r#type: RelationType::Polymorphic as i32,
+            related: "post".to_string(),
+            foreign_key: "commentable_id".to_string(),
+            type_column: "commentable_type".to_string(),
+            candidates: vec!["Post".to_string(), "Photo".to_string()],
+            ..Default::default()
+        };
+        let fields = generate_relation_fields(&[rel_def], "comment");
+        assert!(fields.is_empty());
+    }
+
+    #[test]
+    fn test_remote_relation_has_no_field() {
+        let rel_def = RelationDef {
+            name: "user".to_string(),
+            r#type: RelationType::BelongsTo as i32,
+            related: "iam.User".to_string(),
+            foreign_key: "user_id".to_string(),
+            remote: true,
+            ..Default::default()
+        };
+        let fields = generate_relation_fields(&[rel_def], "author");
+        assert!(fields.is_empty());
+    }
 }
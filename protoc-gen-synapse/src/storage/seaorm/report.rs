@@ -0,0 +1,304 @@
+//! Generation report
+//!
+//! With `report=true`, a `<package>/synapse-report.json` is written with each
+//! generated package: total and per-stage time (options extraction, entity,
+//! validate, storage, graphql, grpc, package, and the formatting share of
+//! each), per proto file of the package the generators that ran with their time and output
+//! files and sizes, and options cache lookups per extension. Every generator
+//! call goes through a [`Recorder`], which is cheap enough to run always; the
+//! report file is only added when requested.
+
+use super::ir::Json;
+use crate::error::GeneratorError;
+use prost_types::compiler::code_generator_response::File;
+use std::time::{Duration, Instant};
+
+/// Name of the report file
+pub const REPORT_FILE: &str = "synapse-report.json";
+
+/// Generation stage a generator belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Entity,
+    Validate,
+    Storage,
+    Graphql,
+    Grpc,
+    Package,
+}
+
+impl Stage {
+    const ALL: [Stage; 6] = [
+        Stage::Entity,
+        Stage::Validate,
+        Stage::Storage,
+        Stage::Graphql,
+        Stage::Grpc,
+        Stage::Package,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Entity => "entity",
+            Stage::Validate => "validate",
+            Stage::Storage => "storage",
+            Stage::Graphql => "graphql",
+            Stage::Grpc => "grpc",
+            Stage::Package => "package",
+        }
+    }
+}
+
+/// Runs of one generator for one proto file
+#[derive(Debug)]
+struct GeneratorRuns {
+    name: &'static str,
+    stage: Stage,
+    runs: u32,
+    elapsed: Duration,
+    formatting: Duration,
+    /// Output path and size in bytes
+    outputs: Vec<(String, usize)>,
+}
+
+/// Generators run for one proto file
+#[derive(Debug)]
+struct FileReport {
+    proto: String,
+    package: String,
+    elapsed: Duration,
+    generators: Vec<GeneratorRuns>,
+}
+
+/// Records generator runs for the report
+#[derive(Debug)]
+pub struct Recorder {
+    started: Instant,
+    options: Duration,
+    files: Vec<FileReport>,
+}
+
+impl Recorder {
+    /// Start recording; `options` is the time spent extracting options
+    pub fn new(options: Duration) -> Self {
+        Self {
+            started: Instant::now(),
+            options,
+            files: Vec::new(),
+        }
+    }
+
+    /// Start the runs of a requested proto file
+    pub fn begin_file(&mut self, proto: &str, package: &str) {
+        self.files.push(FileReport {
+            proto: proto.to_string(),
+            package: package.to_string(),
+            elapsed: Duration::ZERO,
+            generators: Vec::new(),
+        });
+    }
+
+    /// Run a generator, timing it and appending its output to `files`
+    ///
+    /// Accepts generators returning `Option<File>` or `Vec<File>`.
+    pub fn run<T: IntoIterator<Item = File>>(
+        &mut self,
+        stage: Stage,
+        name: &'static str,
+        files: &mut Vec<File>,
+        generate: impl FnOnce() -> Result<T, GeneratorError>,
+    ) -> Result<(), GeneratorError> {
+        let formatting_before = crate::params::formatting_time();
+        let started = Instant::now();
+        let generated = generate()?;
+        let elapsed = started.elapsed();
+        let formatting = crate::params::formatting_time().saturating_sub(formatting_before);
+
+        let first = files.len();
+        files.extend(generated);
+        let outputs = files[first..]
+            .iter()
+            .map(|f| {
+                let name = f.name.clone().unwrap_or_default();
+                (name, f.content.as_deref().map_or(0, str::len))
+            });
+
+        let Some(file) = self.files.last_mut() else {
+            return Ok(());
+        };
+        file.elapsed += elapsed;
+        let runs = match file.generators.iter_mut().find(|g| g.name == name) {
+            Some(runs) => runs,
+            None => {
+                file.generators.push(GeneratorRuns {
+                    name,
+                    stage,
+                    runs: 0,
+                    elapsed: Duration::ZERO,
+                    formatting: Duration::ZERO,
+                    outputs: Vec::new(),
+                });
+                file.generators.last_mut().expect("just pushed")
+            }
+        };
+        runs.runs += 1;
+        runs.elapsed += elapsed;
+        runs.formatting += formatting;
+        runs.outputs.extend(outputs);
+        Ok(())
+    }
+
+    /// Build the report files, one in each package directory
+    pub fn finish(&self) -> Vec<File> {
+        let mut packages: Vec<&str> = Vec::new();
+        for file in &self.files {
+            if !packages.contains(&file.package.as_str()) {
+                packages.push(&file.package);
+            }
+        }
+        packages
+            .into_iter()
+            .map(|package| {
+                let mut content = String::new();
+                self.to_json(package).write(&mut content, 0);
+                content.push('\n');
+                let name = if package.is_empty() {
+                    REPORT_FILE.to_string()
+                } else {
+                    format!("{}/{}", package.replace('.', "/"), REPORT_FILE)
+                };
+                File {
+                    name: Some(name),
+                    content: Some(content),
+                    ..Default::default()
+                }
+            })
+            .collect()
+    }
+
+    /// Report of the proto files in `package`
+    fn to_json(&self, package: &str) -> Json {
+        let package_files = || self.files.iter().filter(|f| f.package == package);
+        let all_runs = || package_files().flat_map(|f| &f.generators);
+
+        let mut stages = vec![("options".to_string(), millis(self.options))];
+        for stage in Stage::ALL {
+            let elapsed = all_runs().filter(|g| g.stage == stage).map(|g| g.elapsed).sum();
+            stages.push((stage.name().to_string(), millis(elapsed)));
+        }
+        stages.push(("formatting".to_string(), millis(all_runs().map(|g| g.formatting).sum())));
+
+        let output_files = all_runs().map(|g| g.outputs.len()).sum::<usize>();
+        let output_bytes = all_runs().flat_map(|g| &g.outputs).map(|(_, bytes)| bytes).sum::<usize>();
+
+        let files = package_files()
+            .map(|file| {
+                let generators = file
+                    .generators
+                    .iter()
+                    .map(|g| {
+                        let outputs = g
+                            .outputs
+                            .iter()
+                            .map(|(path, bytes)| {
+                                Json::object(vec![("path", Json::string(path)), ("bytes", number(*bytes))])
+                            })
+                            .collect();
+                        Json::object(vec![
+                            ("name", Json::string(g.name)),
+                            ("stage", Json::string(g.stage.name())),
+                            ("runs", number(g.runs as usize)),
+                            ("elapsed_ms", millis(g.elapsed)),
+                            ("formatting_ms", millis(g.formatting)),
+                            ("outputs", Json::Array(outputs)),
+                        ])
+                    })
+                    .collect();
+                Json::object(vec![
+                    ("proto", Json::string(&file.proto)),
+                    ("elapsed_ms", millis(file.elapsed)),
+                    ("generators", Json::Array(generators)),
+                ])
+            })
+            .collect();
+
+        let cache = super::options::cache_stats()
+            .into_iter()
+            .map(|(extension, hits, misses)| {
+                (
+                    extension.to_string(),
+                    Json::object(vec![("hits", number(hits as usize)), ("misses", number(misses as usize))]),
+                )
+            })
+            .collect();
+
+        Json::object(vec![
+            ("package", Json::string(package)),
+            ("total_ms", millis(self.options + self.started.elapsed())),
+            ("stages_ms", Json::Object(stages)),
+            ("output_files", number(output_files)),
+            ("output_bytes", number(output_bytes)),
+            ("options_cache", Json::Object(cache)),
+            ("files", Json::Array(files)),
+        ])
+    }
+}
+
+fn millis(duration: Duration) -> Json {
+    Json::Number(format!("{:.3}", duration.as_secs_f64() * 1000.0))
+}
+
+fn number(n: usize) -> Json {
+    Json::Number(n.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(name: &str, content: &str) -> File {
+        File {
+            name: Some(name.to_string()),
+            content: Some(content.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_recorder_groups_runs_per_generator() {
+        let mut recorder = Recorder::new(Duration::from_millis(2));
+        let mut files = Vec::new();
+        recorder.begin_file("blog/services.proto", "blog");
+        recorder
+            .run(Stage::Entity, "entity", &mut files, || Ok(Some(output("blog/entities/post.rs", "abc"))))
+            .unwrap();
+        recorder
+            .run(Stage::Entity, "entity", &mut files, || Ok(Some(output("blog/entities/author.rs", "de"))))
+            .unwrap();
+        recorder.run(Stage::Graphql, "graphql_object", &mut files, || Ok(None::<File>)).unwrap();
+
+        assert_eq!(files.len(), 2);
+        let entity = &recorder.files[0].generators[0];
+        assert_eq!((entity.name, entity.runs), ("entity", 2));
+        assert_eq!(
+            entity.outputs,
+            vec![("blog/entities/post.rs".to_string(), 3), ("blog/entities/author.rs".to_string(), 2)]
+        );
+        assert_eq!(recorder.files[0].generators[1].runs, 1);
+
+        recorder.begin_file("iam/user.proto", "iam.v1");
+        recorder
+            .run(Stage::Entity, "entity", &mut files, || Ok(Some(output("iam/v1/entities/user.rs", "fghi"))))
+            .unwrap();
+
+        let reports = recorder.finish();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].name.as_deref(), Some("blog/synapse-report.json"));
+        assert_eq!(reports[1].name.as_deref(), Some("iam/v1/synapse-report.json"));
+        let blog = reports[0].content.as_deref().unwrap();
+        assert!(blog.contains("\"options\": 2.000"));
+        assert!(blog.contains("\"output_bytes\": 5"));
+        assert!(!blog.contains("iam/user.proto"));
+        assert!(reports[1].content.as_deref().unwrap().contains("\"output_bytes\": 4"));
+    }
+}
@@ -0,0 +1,222 @@
+//! Per-process row cache for get-by-id
+//!
+//! An entity with `row_cache` gets a `ROW_CACHE` static in its entity module:
+//! an LRU of at most `size` rows, each served for `ttl_seconds`. The generated
+//! get-by-id storage method reads through it, and the generated update,
+//! upsert, restore and delete drop the row they wrote once it commits; bulk
+//! updates and deletes drop every row. Writes made by other processes are only
+//! seen once the cached row expires.
+
+use super::options::{get_cached_entity_options, storage};
+use proc_macro2::{Ident, TokenStream};
+use prost_types::FileDescriptorProto;
+use quote::quote;
+
+/// TTL of cached rows when `ttl_seconds` is unset
+const DEFAULT_TTL_SECONDS: u32 = 60;
+
+/// Whether an entity caches rows read by id
+pub(crate) fn has_row_cache(entity_options: Option<&storage::EntityOptions>) -> bool {
+    entity_options
+        .and_then(|o| o.row_cache.as_ref())
+        .is_some_and(|c| c.size > 0)
+}
+
+/// Whether any entity in the main file's package caches rows read by id
+pub(crate) fn package_has_row_cache(all_files: &[FileDescriptorProto], main_file: &FileDescriptorProto) -> bool {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|file| {
+            let file_name = file.name.as_deref().unwrap_or("");
+            file.message_type.iter().any(|message| {
+                let msg_name = message.name.as_deref().unwrap_or("");
+                get_cached_entity_options(file_name, msg_name)
+                    .is_some_and(|o| !o.skip && has_row_cache(Some(&o)))
+            })
+        })
+}
+
+/// Generate the entity module's `ROW_CACHE`, if the entity has one
+pub(crate) fn generate_static(entity_options: &storage::EntityOptions) -> TokenStream {
+    let Some(cache) = entity_options.row_cache.as_ref().filter(|c| c.size > 0) else {
+        return quote! {};
+    };
+    let size = cache.size as usize;
+    let ttl = if cache.ttl_seconds == 0 {
+        DEFAULT_TTL_SECONDS
+    } else {
+        cache.ttl_seconds
+    } as u64;
+    let doc = format!("Rows read by id: up to {} rows, each kept for {}s", size, ttl);
+    quote! {
+        #[doc = #doc]
+        pub static ROW_CACHE: std::sync::LazyLock<RowCache<KeyOf<Entity>, Model>> =
+            std::sync::LazyLock::new(|| RowCache::new(#size, std::time::Duration::from_secs(#ttl)));
+    }
+}
+
+/// Drop the row under `key` from the entity's `ROW_CACHE`, if it has one
+pub(crate) fn generate_invalidate(
+    entity_module: &Ident,
+    entity_options: Option<&storage::EntityOptions>,
+    key: TokenStream,
+) -> TokenStream {
+    if !has_row_cache(entity_options) {
+        return quote! {};
+    }
+    quote! { entities::#entity_module::ROW_CACHE.invalidate(&#key); }
+}
+
+/// Drop every row from the entity's `ROW_CACHE` after a bulk write, if it has one
+pub(crate) fn generate_clear(entity_module: &Ident, entity_options: Option<&storage::EntityOptions>) -> TokenStream {
+    if !has_row_cache(entity_options) {
+        return quote! {};
+    }
+    quote! { entities::#entity_module::ROW_CACHE.clear(); }
+}
+
+/// Generate the prelude's `RowCache`
+pub(crate) fn generate_prelude() -> TokenStream {
+    quote! {
+        /// Per-process LRU of rows read by id, each kept for a fixed TTL
+        ///
+        /// Meant for a handful of hot rows: eviction scans every cached row.
+        pub struct RowCache<K, V> {
+            capacity: usize,
+            ttl: std::time::Duration,
+            state: std::sync::Mutex<RowCacheState<K, V>>,
+        }
+
+        struct RowCacheState<K, V> {
+            /// Value, expiry and last read tick of each row
+            rows: std::collections::HashMap<K, (V, std::time::Instant, u64)>,
+            tick: u64,
+            /// Invalidations so far
+            version: u64,
+        }
+
+        impl<K: std::hash::Hash + Eq + Clone, V: Clone> RowCache<K, V> {
+            /// Cache of at most `capacity` rows, each kept for `ttl`
+            pub fn new(capacity: usize, ttl: std::time::Duration) -> Self {
+                Self {
+                    capacity,
+                    ttl,
+                    state: std::sync::Mutex::new(RowCacheState {
+                        rows: std::collections::HashMap::new(),
+                        tick: 0,
+                        version: 0,
+                    }),
+                }
+            }
+
+            fn lock(&self) -> std::sync::MutexGuard<'_, RowCacheState<K, V>> {
+                self.state.lock().unwrap_or_else(|e| e.into_inner())
+            }
+
+            /// The cached row under `key`, unless it expired
+            pub fn get(&self, key: &K) -> Option<V> {
+                let mut guard = self.lock();
+                let state = &mut *guard;
+                state.tick += 1;
+                let (value, expires, used) = state.rows.get_mut(key)?;
+                if *expires > std::time::Instant::now() {
+                    *used = state.tick;
+                    return Some(value.clone());
+                }
+                state.rows.remove(key);
+                None
+            }
+
+            /// Invalidation count, taken before reading a row to cache
+            pub fn version(&self) -> u64 {
+                self.lock().version
+            }
+
+            /// Cache a row read after `version` was taken
+            ///
+            /// A row is not cached when an invalidation happened since, as the
+            /// write it was for may have committed after the row was read.
+            pub fn insert(&self, key: K, value: V, version: u64) {
+                if self.capacity == 0 {
+                    return;
+                }
+                let mut guard = self.lock();
+                let state = &mut *guard;
+                if state.version != version {
+                    return;
+                }
+                let now = std::time::Instant::now();
+                if !state.rows.contains_key(&key) && state.rows.len() >= self.capacity {
+                    state.rows.retain(|_, (_, expires, _)| *expires > now);
+                }
+                if !state.rows.contains_key(&key) && state.rows.len() >= self.capacity {
+                    let oldest = state
+                        .rows
+                        .iter()
+                        .min_by_key(|(_, (_, _, used))| *used)
+                        .map(|(key, _)| key.clone());
+                    if let Some(oldest) = oldest {
+                        state.rows.remove(&oldest);
+                    }
+                }
+                state.tick += 1;
+                state.rows.insert(key, (value, now + self.ttl, state.tick));
+            }
+
+            /// Drop the row under `key` after a write to it
+            pub fn invalidate(&self, key: &K) {
+                let mut state = self.lock();
+                state.version += 1;
+                state.rows.remove(key);
+            }
+
+            /// Drop every row (e.g. after a bulk write or raw SQL)
+            pub fn clear(&self) {
+                let mut state = self.lock();
+                state.version += 1;
+                state.rows.clear();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::format_ident;
+
+    fn options(size: u32, ttl_seconds: u32) -> storage::EntityOptions {
+        storage::EntityOptions {
+            row_cache: Some(storage::RowCache { size, ttl_seconds }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_generate_static() {
+        let code = generate_static(&options(16, 0)).to_string();
+        assert!(code.contains("pub static ROW_CACHE"));
+        assert!(code.contains("RowCache :: new (16usize , std :: time :: Duration :: from_secs (60u64))"));
+
+        assert!(generate_static(&options(0, 30)).is_empty());
+        assert!(generate_static(&storage::EntityOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn test_generate_invalidate() {
+        let module = format_ident!("organization");
+        let code = generate_invalidate(&module, Some(&options(4, 5)), quote! { model.id }).to_string();
+        assert_eq!(code, "entities :: organization :: ROW_CACHE . invalidate (& model . id) ;");
+        assert!(generate_invalidate(&module, None, quote! { model.id }).is_empty());
+    }
+
+    #[test]
+    fn test_generate_prelude() {
+        let code = generate_prelude().to_string();
+        assert!(code.contains("pub struct RowCache < K , V >"));
+        assert!(code.contains("if state . version != version"));
+        assert!(code.contains("min_by_key"));
+    }
+}
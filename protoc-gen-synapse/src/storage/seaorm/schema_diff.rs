@@ -0,0 +1,515 @@
+//! Live schema diff generation
+//!
+//! With `schema_diff=true`, generates {package}/schema_diff.rs: checks a live
+//! Postgres database against the package's entities before a deploy. It reads
+//! `information_schema` and `pg_indexes`, then reports missing tables, missing
+//! columns, type and nullability mismatches, and indexes from
+//! `index_statements()` that do not exist. Expected column types come from the
+//! entities' `ColumnDef`s at runtime, so they match what the migration creates.
+//!
+//! Every `belongs_to` relation is also checked against the live foreign key
+//! constraints: the `foreign_key` column must have a constraint to the
+//! `references` column, and both columns must have the same type. A typo in a
+//! relation annotation otherwise only shows up as wrong query results.
+
+use super::migration::{collect_tables, MigrationTable};
+use super::options::{get_cached_column_options, get_cached_entity_options};
+use crate::error::GeneratorError;
+use crate::options::synapse::storage::RelationType;
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::FileDescriptorProto;
+use quote::{format_ident, quote};
+
+/// Environment variable read by the generated `cli()` when no argument is given
+const DATABASE_URL_ENV: &str = "DATABASE_URL";
+
+/// Generate schema_diff.rs for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() || !crate::params::schema_diff() {
+        return Ok(None);
+    }
+
+    let tables = collect_tables(all_files, file)?;
+    if tables.is_empty() {
+        return Ok(None);
+    }
+    let foreign_keys = collect_foreign_keys(all_files, package);
+    let code = generate_schema_diff(&tables, &foreign_keys);
+
+    Ok(Some(File {
+        name: Some(format!("{}/schema_diff.rs", package.replace('.', "/"))),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    }))
+}
+
+/// Foreign key of a `belongs_to` relation, as database names
+#[derive(Debug, Clone, PartialEq)]
+struct ForeignKey {
+    /// Entity module holding the key
+    module: String,
+    column: String,
+    references_table: String,
+    references_column: String,
+}
+
+/// Database column name of a message field
+fn column_name(file_name: &str, message: &prost_types::DescriptorProto, field: &str) -> Option<String> {
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let field = message.field.iter().find(|f| f.name.as_deref() == Some(field))?;
+    let custom = get_cached_column_options(file_name, msg_name, field.number.unwrap_or(0))
+        .map(|o| o.column_name)
+        .filter(|n| !n.is_empty());
+    Some(custom.unwrap_or_else(|| field.name.as_deref().unwrap_or("").to_snake_case()))
+}
+
+/// Foreign keys of the package's `belongs_to` relations
+///
+/// Related entities may live in other packages. Relations whose fields do not
+/// exist are left to `lint=true`.
+fn collect_foreign_keys(all_files: &[FileDescriptorProto], package: &str) -> Vec<ForeignKey> {
+    let find_message = |related: &str| {
+        let (related_package, name) = related.rsplit_once('.').unwrap_or((package, related));
+        all_files
+            .iter()
+            .filter(|f| f.package.as_deref().unwrap_or("") == related_package)
+            .flat_map(|f| f.message_type.iter().map(move |m| (f, m)))
+            .find(|(_, m)| m.name.as_deref() == Some(name))
+    };
+
+    let mut keys = Vec::new();
+    for file in all_files.iter().filter(|f| f.package.as_deref().unwrap_or("") == package) {
+        let file_name = file.name.as_deref().unwrap_or("");
+        for message in &file.message_type {
+            let msg_name = message.name.as_deref().unwrap_or("");
+            let Some(opts) = get_cached_entity_options(file_name, msg_name).filter(|o| !o.skip) else {
+                continue;
+            };
+            // Remote relations have no foreign key into another service's database
+            for rel in opts.relations.iter().filter(|r| r.r#type() == RelationType::BelongsTo && !r.remote) {
+                let Some((related_file, related)) = find_message(&rel.related) else {
+                    continue;
+                };
+                let related_file_name = related_file.name.as_deref().unwrap_or("");
+                let related_name = related.name.as_deref().unwrap_or("");
+                // Same defaults as the entity's relation attributes
+                let foreign_key = if rel.foreign_key.is_empty() {
+                    format!("{}_id", rel.related.to_snake_case())
+                } else {
+                    rel.foreign_key.clone()
+                };
+                let references = if rel.references.is_empty() { "id" } else { rel.references.as_str() };
+                let (Some(column), Some(references_column)) = (
+                    column_name(file_name, message, &foreign_key),
+                    column_name(related_file_name, related, references),
+                ) else {
+                    continue;
+                };
+                let references_table = get_cached_entity_options(related_file_name, related_name)
+                    .map(|o| o.table_name)
+                    .filter(|t| !t.is_empty())
+                    .unwrap_or_else(|| related_name.to_snake_case());
+                keys.push(ForeignKey {
+                    module: msg_name.to_snake_case(),
+                    column,
+                    references_table,
+                    references_column,
+                });
+            }
+        }
+    }
+    keys
+}
+
+/// Generate the diff types, queries, and per-entity checks
+fn generate_schema_diff(tables: &[MigrationTable], foreign_keys: &[ForeignKey]) -> TokenStream {
+    let checks = tables.iter().map(|t| {
+        let module = format_ident!("{}", t.module);
+        let indexes = &t.indexes;
+        let keys = foreign_keys.iter().filter(|k| k.module == t.module).map(|k| {
+            let (column, table, references) = (&k.column, &k.references_table, &k.references_column);
+            quote! { (#column, #table, #references) }
+        });
+        quote! {
+            diff_entity(db, entities::#module::Entity, &[#(#indexes),*], &[#(#keys),*], &mut issues).await?;
+        }
+    });
+
+    quote! {
+        //! Diff of a live Postgres schema against the entities
+        //!
+        //! Run before a deploy to catch drift the code would trip over.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use sea_orm::sea_query::ColumnType;
+        use sea_orm::{
+            ColumnTrait, ConnectionTrait, DatabaseConnection, DbBackend, DbErr, EntityName, EntityTrait,
+            FromQueryResult, IdenStatic, Iterable, Statement,
+        };
+
+        use super::entities;
+
+        /// A difference between the database and the entities
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum SchemaIssue {
+            MissingTable { table: String },
+            MissingColumn { table: String, column: String },
+            TypeMismatch { table: String, column: String, expected: String, found: String },
+            NullabilityMismatch { table: String, column: String, expected_nullable: bool },
+            MissingIndex { table: String, index: String },
+            /// A relation's foreign key column has no foreign key constraint
+            MissingForeignKey { table: String, column: String, references: String },
+            /// The constraint on a relation's foreign key references another column
+            ForeignKeyMismatch { table: String, column: String, expected: String, found: String },
+            /// A relation references a column that does not exist
+            UnknownReference { table: String, column: String, references: String },
+            /// A relation's foreign key and referenced columns have different types
+            ForeignKeyTypeMismatch { table: String, column: String, found: String, references: String, references_type: String },
+        }
+
+        impl std::fmt::Display for SchemaIssue {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    Self::MissingTable { table } => write!(f, "missing table {}", table),
+                    Self::MissingColumn { table, column } => write!(f, "missing column {}.{}", table, column),
+                    Self::TypeMismatch { table, column, expected, found } => {
+                        write!(f, "type mismatch on {}.{}: expected {}, found {}", table, column, expected, found)
+                    }
+                    Self::NullabilityMismatch { table, column, expected_nullable } => write!(
+                        f,
+                        "{}.{} should be {}",
+                        table,
+                        column,
+                        if *expected_nullable { "NULL" } else { "NOT NULL" }
+                    ),
+                    Self::MissingIndex { table, index } => write!(f, "missing index {} on {}", index, table),
+                    Self::MissingForeignKey { table, column, references } => {
+                        write!(f, "missing foreign key {}.{} -> {}", table, column, references)
+                    }
+                    Self::ForeignKeyMismatch { table, column, expected, found } => write!(
+                        f,
+                        "foreign key {}.{} references {}, relation expects {}",
+                        table, column, found, expected
+                    ),
+                    Self::UnknownReference { table, column, references } => {
+                        write!(f, "relation on {}.{} references missing column {}", table, column, references)
+                    }
+                    Self::ForeignKeyTypeMismatch { table, column, found, references, references_type } => write!(
+                        f,
+                        "type mismatch on foreign key {}.{} ({}) -> {} ({})",
+                        table, column, found, references, references_type
+                    ),
+                }
+            }
+        }
+
+        /// All differences found, in entity order
+        #[derive(Debug, Clone, Default)]
+        pub struct SchemaDiff {
+            pub issues: Vec<SchemaIssue>,
+        }
+
+        impl SchemaDiff {
+            /// Whether the database matches the entities
+            pub fn is_empty(&self) -> bool {
+                self.issues.is_empty()
+            }
+        }
+
+        impl std::fmt::Display for SchemaDiff {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                for issue in &self.issues {
+                    writeln!(f, "{}", issue)?;
+                }
+                write!(f, "{} issue(s)", self.issues.len())
+            }
+        }
+
+        /// A column as `information_schema` reports it
+        #[derive(Debug, FromQueryResult)]
+        struct LiveColumn {
+            column_name: String,
+            udt_name: String,
+            is_nullable: String,
+        }
+
+        /// An index as `pg_indexes` reports it
+        #[derive(Debug, FromQueryResult)]
+        struct LiveIndex {
+            indexname: String,
+        }
+
+        /// A foreign key column as `information_schema` reports it
+        #[derive(Debug, FromQueryResult)]
+        struct LiveForeignKey {
+            column_name: String,
+            foreign_table: String,
+            foreign_column: String,
+        }
+
+        /// Postgres `udt_name` of a column type, when it can be compared
+        ///
+        /// Enums, arrays, and types without a fixed name are not compared.
+        fn udt_name(column_type: &ColumnType) -> Option<String> {
+            let name = match column_type {
+                ColumnType::Char(_) => "bpchar",
+                ColumnType::String(_) => "varchar",
+                ColumnType::Text => "text",
+                ColumnType::SmallInteger => "int2",
+                ColumnType::Integer => "int4",
+                ColumnType::BigInteger => "int8",
+                ColumnType::Float => "float4",
+                ColumnType::Double => "float8",
+                ColumnType::Decimal(_) => "numeric",
+                ColumnType::Boolean => "bool",
+                ColumnType::Uuid => "uuid",
+                ColumnType::Date => "date",
+                ColumnType::Time => "time",
+                ColumnType::DateTime | ColumnType::Timestamp => "timestamp",
+                ColumnType::TimestampWithTimeZone => "timestamptz",
+                ColumnType::Json => "json",
+                ColumnType::JsonBinary => "jsonb",
+                ColumnType::Binary(_) | ColumnType::VarBinary(_) => "bytea",
+                // e.g. citext, or geography(Point, 4326)
+                ColumnType::Custom(name) => {
+                    let name = name.to_string();
+                    return Some(name.split('(').next().unwrap_or_default().trim().to_lowercase());
+                }
+                _ => return None,
+            };
+            Some(name.to_string())
+        }
+
+        /// Compare one entity's table with the database
+        async fn diff_entity<E>(
+            db: &DatabaseConnection,
+            entity: E,
+            indexes: &[&str],
+            foreign_keys: &[(&str, &str, &str)],
+            issues: &mut Vec<SchemaIssue>,
+        ) -> Result<(), DbErr>
+        where
+            E: EntityTrait,
+        {
+            let table = entity.table_name().to_string();
+
+            let columns = LiveColumn::find_by_statement(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "SELECT column_name::text, udt_name::text, is_nullable::text \
+                 FROM information_schema.columns \
+                 WHERE table_schema = current_schema() AND table_name = $1",
+                [table.clone().into()],
+            ))
+            .all(db)
+            .await?;
+            if columns.is_empty() {
+                issues.push(SchemaIssue::MissingTable { table });
+                return Ok(());
+            }
+
+            for column in E::Column::iter() {
+                let def = column.def();
+                let name = column.as_str();
+                let Some(live) = columns.iter().find(|c| c.column_name == name) else {
+                    issues.push(SchemaIssue::MissingColumn {
+                        table: table.clone(),
+                        column: name.to_string(),
+                    });
+                    continue;
+                };
+                if let Some(expected) = udt_name(def.get_column_type())
+                    && expected != live.udt_name
+                {
+                    issues.push(SchemaIssue::TypeMismatch {
+                        table: table.clone(),
+                        column: name.to_string(),
+                        expected,
+                        found: live.udt_name.clone(),
+                    });
+                }
+                let nullable = live.is_nullable == "YES";
+                if def.is_null() != nullable {
+                    issues.push(SchemaIssue::NullabilityMismatch {
+                        table: table.clone(),
+                        column: name.to_string(),
+                        expected_nullable: def.is_null(),
+                    });
+                }
+            }
+
+            let live_indexes = LiveIndex::find_by_statement(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "SELECT indexname::text FROM pg_indexes \
+                 WHERE schemaname = current_schema() AND tablename = $1",
+                [table.clone().into()],
+            ))
+            .all(db)
+            .await?;
+            for index in indexes {
+                if !live_indexes.iter().any(|i| i.indexname == *index) {
+                    issues.push(SchemaIssue::MissingIndex {
+                        table: table.clone(),
+                        index: index.to_string(),
+                    });
+                }
+            }
+
+            let live_keys = LiveForeignKey::find_by_statement(Statement::from_sql_and_values(
+                DbBackend::Postgres,
+                "SELECT kcu.column_name::text, ccu.table_name::text AS foreign_table, \
+                 ccu.column_name::text AS foreign_column \
+                 FROM information_schema.table_constraints tc \
+                 JOIN information_schema.key_column_usage kcu \
+                 ON kcu.constraint_name = tc.constraint_name AND kcu.table_schema = tc.table_schema \
+                 JOIN information_schema.constraint_column_usage ccu \
+                 ON ccu.constraint_name = tc.constraint_name AND ccu.table_schema = tc.table_schema \
+                 WHERE tc.constraint_type = 'FOREIGN KEY' \
+                 AND tc.table_schema = current_schema() AND tc.table_name = $1",
+                [table.clone().into()],
+            ))
+            .all(db)
+            .await?;
+            for (column, references_table, references_column) in foreign_keys {
+                // A missing column is already reported above
+                let Some(live) = columns.iter().find(|c| c.column_name == *column) else {
+                    continue;
+                };
+                let references = format!("{}.{}", references_table, references_column);
+
+                let targets: Vec<String> = live_keys
+                    .iter()
+                    .filter(|k| k.column_name == *column)
+                    .map(|k| format!("{}.{}", k.foreign_table, k.foreign_column))
+                    .collect();
+                if targets.is_empty() {
+                    issues.push(SchemaIssue::MissingForeignKey {
+                        table: table.clone(),
+                        column: column.to_string(),
+                        references: references.clone(),
+                    });
+                } else if !targets.contains(&references) {
+                    issues.push(SchemaIssue::ForeignKeyMismatch {
+                        table: table.clone(),
+                        column: column.to_string(),
+                        expected: references.clone(),
+                        found: targets.join(", "),
+                    });
+                }
+
+                let referenced = LiveColumn::find_by_statement(Statement::from_sql_and_values(
+                    DbBackend::Postgres,
+                    "SELECT column_name::text, udt_name::text, is_nullable::text \
+                     FROM information_schema.columns \
+                     WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2",
+                    [references_table.to_string().into(), references_column.to_string().into()],
+                ))
+                .one(db)
+                .await?;
+                match referenced {
+                    None => issues.push(SchemaIssue::UnknownReference {
+                        table: table.clone(),
+                        column: column.to_string(),
+                        references,
+                    }),
+                    Some(referenced) if referenced.udt_name != live.udt_name => {
+                        issues.push(SchemaIssue::ForeignKeyTypeMismatch {
+                            table: table.clone(),
+                            column: column.to_string(),
+                            found: live.udt_name.clone(),
+                            references,
+                            references_type: referenced.udt_name,
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            Ok(())
+        }
+
+        /// Diff the connected Postgres schema against every entity of the package
+        pub async fn diff(db: &DatabaseConnection) -> Result<SchemaDiff, DbErr> {
+            let mut issues = Vec::new();
+            #(#checks)*
+            Ok(SchemaDiff { issues })
+        }
+
+        /// Diff the database at the URL given as the first argument (or
+        /// `DATABASE_URL`), printing every issue
+        ///
+        /// Exits non-zero on drift, so a deploy pipeline can gate on it.
+        pub async fn cli() -> std::process::ExitCode {
+            let Some(url) = std::env::args().nth(1).or_else(|| std::env::var(#DATABASE_URL_ENV).ok()) else {
+                eprintln!("usage: schema-diff <database-url> (or set {})", #DATABASE_URL_ENV);
+                return std::process::ExitCode::from(2);
+            };
+            let db = match sea_orm::Database::connect(url).await {
+                Ok(db) => db,
+                Err(e) => {
+                    eprintln!("connect failed: {}", e);
+                    return std::process::ExitCode::FAILURE;
+                }
+            };
+            match diff(&db).await {
+                Ok(diff) => {
+                    println!("{}", diff);
+                    if diff.is_empty() {
+                        std::process::ExitCode::SUCCESS
+                    } else {
+                        std::process::ExitCode::FAILURE
+                    }
+                }
+                Err(e) => {
+                    eprintln!("schema diff failed: {}", e);
+                    std::process::ExitCode::FAILURE
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_schema_diff() {
+        let tables = vec![
+            MigrationTable {
+                module: "user".to_string(),
+                depends_on: Vec::new(),
+                indexes: Vec::new(),
+                searchable: false,
+            },
+            MigrationTable {
+                module: "post".to_string(),
+                depends_on: vec!["user".to_string()],
+                indexes: vec!["idx_posts_author_id_created_at".to_string()],
+                searchable: false,
+            },
+        ];
+        let foreign_keys = vec![ForeignKey {
+            module: "post".to_string(),
+            column: "author_id".to_string(),
+            references_table: "users".to_string(),
+            references_column: "id".to_string(),
+        }];
+        let code = generate_schema_diff(&tables, &foreign_keys).to_string();
+        assert!(code.contains("diff_entity (db , entities :: user :: Entity , & [] , & [] , & mut issues)"));
+        assert!(code.contains(
+            "diff_entity (db , entities :: post :: Entity , & [\"idx_posts_author_id_created_at\"] , & [(\"author_id\" , \"users\" , \"id\")] , & mut issues)"
+        ));
+        assert!(code.contains("constraint_type = 'FOREIGN KEY'"));
+        assert!(code.contains("FROM information_schema.columns"));
+        assert!(code.contains("\"DATABASE_URL\""));
+    }
+}
@@ -0,0 +1,693 @@
+//! Search indexing generation
+//!
+//! Generates the search.rs module for packages with `searchable` entities: a
+//! `SearchIndex` trait with Meilisearch and Elasticsearch implementations,
+//! per-entity document builders and backfill jobs, and a bounded event channel
+//! storage feeds once each write commits, so saves and deletes reach the index
+//! incrementally.
+
+use super::options::{get_cached_column_options, get_cached_entity_options, get_cached_graphql_type_options};
+use crate::error::GeneratorError;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::TokenStream;
+use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+
+/// A searchable entity
+#[derive(Debug, Clone)]
+pub struct SearchEntity {
+    /// Proto message
+    pub message: DescriptorProto,
+    /// Entity module name (snake_case of the message)
+    pub module: String,
+    /// Table name, also used as the index name
+    pub table: String,
+    /// GraphQL type name, if the entity is exposed over GraphQL
+    pub graphql_type: Option<String>,
+    /// Indexed columns with their weights, highest weight first
+    pub fields: Vec<(String, u32)>,
+    pub soft_delete: bool,
+}
+
+/// Collect searchable entities in the main file's package
+pub fn collect_searchable(
+    all_files: &[FileDescriptorProto],
+    main_file: &FileDescriptorProto,
+) -> Vec<SearchEntity> {
+    let package = main_file.package.as_deref().unwrap_or("");
+    let mut entities = Vec::new();
+
+    for file in all_files {
+        if file.package.as_deref().unwrap_or("") != package {
+            continue;
+        }
+        let file_name = file.name.as_deref().unwrap_or("");
+        for message in &file.message_type {
+            let msg_name = message.name.as_deref().unwrap_or("");
+            let Some(opts) = get_cached_entity_options(file_name, msg_name) else {
+                continue;
+            };
+            if opts.skip || !opts.searchable {
+                continue;
+            }
+
+            let table = if opts.table_name.is_empty() {
+                msg_name.to_snake_case()
+            } else {
+                opts.table_name.clone()
+            };
+
+            let graphql_type = get_cached_graphql_type_options(file_name, msg_name)
+                .filter(|o| !o.skip && !o.input)
+                .map(|o| {
+                    if o.name.is_empty() {
+                        msg_name.to_upper_camel_case()
+                    } else {
+                        o.name
+                    }
+                });
+
+            entities.push(SearchEntity {
+                message: message.clone(),
+                module: msg_name.to_snake_case(),
+                table,
+                graphql_type,
+                fields: searchable_fields(file_name, message),
+                soft_delete: opts.soft_delete,
+            });
+        }
+    }
+
+    entities
+}
+
+/// Weighted string columns of an entity
+///
+/// Columns with a `search_weight` are indexed; when none has one, every
+/// singular string column (other than the primary key) is indexed at weight 1.
+fn searchable_fields(file_name: &str, message: &DescriptorProto) -> Vec<(String, u32)> {
+    let msg_name = message.name.as_deref().unwrap_or("");
+    let candidates: Vec<(String, u32, bool)> = message
+        .field
+        .iter()
+        .filter(|f| f.r#type() == Type::String && f.label() != Label::Repeated)
+        .map(|f| {
+            let opts = get_cached_column_options(file_name, msg_name, f.number.unwrap_or(0));
+            let weight = opts.as_ref().map(|o| o.search_weight).unwrap_or(0);
+            let primary_key = opts.as_ref().map(|o| o.primary_key).unwrap_or(false);
+            (f.name.as_deref().unwrap_or("").to_snake_case(), weight, primary_key)
+        })
+        .collect();
+
+    let weighted = candidates.iter().any(|(_, weight, _)| *weight > 0);
+    let mut fields: Vec<(String, u32)> = candidates
+        .into_iter()
+        .filter(|(_, weight, primary_key)| if weighted { *weight > 0 } else { !primary_key })
+        .map(|(name, weight, _)| (name, weight.max(1)))
+        .collect();
+    fields.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+    fields
+}
+
+/// Generate the search.rs file for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    if package.is_empty() {
+        return Ok(None);
+    }
+
+    let entities = collect_searchable(all_files, file);
+    if entities.is_empty() {
+        return Ok(None);
+    }
+
+    for entity in &entities {
+        if entity.fields.is_empty() {
+            return Err(GeneratorError::CodeGenError(format!(
+                "searchable entity {} has no string columns to index",
+                entity.message.name.as_deref().unwrap_or("")
+            )));
+        }
+    }
+
+    let index_backends = generate_backends();
+    let events = generate_events(crate::graphql::package_has_dry_run(all_files, file));
+    let entity_items = entities.iter().map(generate_entity_items);
+
+    let code = quote! {
+        //! Search indexing
+        //!
+        //! Mirrors searchable entities into Meilisearch or Elasticsearch. Run
+        //! the `backfill_*` jobs once, then `start_indexer` to apply entity
+        //! saves and deletes as they happen.
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(dead_code)]
+
+        use super::entities;
+        use sea_orm::entity::prelude::*;
+        use sea_orm::{DatabaseConnection, PaginatorTrait, QueryOrder};
+        use std::sync::{Arc, OnceLock};
+
+        /// Search indexing error
+        #[derive(Debug, thiserror::Error)]
+        pub enum SearchError {
+            #[error("search backend error: {0}")]
+            Backend(String),
+            #[error("database error: {0}")]
+            Database(#[from] DbErr),
+        }
+
+        impl From<reqwest::Error> for SearchError {
+            fn from(err: reqwest::Error) -> Self {
+                Self::Backend(err.to_string())
+            }
+        }
+
+        /// An external full-text index
+        ///
+        /// `fields` lists the indexed columns with their weights, highest first.
+        #[async_trait::async_trait]
+        pub trait SearchIndex: Send + Sync + 'static {
+            /// Apply index settings (searchable attributes and their ranking)
+            async fn configure(&self, index: &str, fields: &[(&str, u32)]) -> Result<(), SearchError>;
+
+            /// Insert or replace documents, keyed by their `id`
+            async fn upsert(&self, index: &str, documents: Vec<serde_json::Value>) -> Result<(), SearchError>;
+
+            /// Remove documents by id
+            async fn delete(&self, index: &str, ids: Vec<String>) -> Result<(), SearchError>;
+
+            /// Query the index, returning matching ids by relevance
+            async fn search(
+                &self,
+                index: &str,
+                query: &str,
+                fields: &[(&str, u32)],
+                limit: usize,
+            ) -> Result<Vec<String>, SearchError>;
+        }
+
+        #index_backends
+
+        #events
+
+        #(#entity_items)*
+    };
+
+    let content = code.to_string();
+    let formatted = crate::params::format_code(content);
+
+    let output_path = format!("{}/search.rs", package.replace('.', "/"));
+
+    Ok(Some(File {
+        name: Some(output_path),
+        content: Some(formatted),
+        ..Default::default()
+    }))
+}
+
+/// Generate the Meilisearch and Elasticsearch `SearchIndex` implementations
+fn generate_backends() -> TokenStream {
+    quote! {
+        /// Read a document id as a string (backends may return numbers)
+        fn document_id(value: &serde_json::Value) -> Option<String> {
+            match value {
+                serde_json::Value::String(s) => Some(s.clone()),
+                serde_json::Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            }
+        }
+
+        /// Meilisearch over its HTTP API
+        ///
+        /// Meilisearch ranks by attribute order, so weights only decide the
+        /// order of `searchableAttributes`.
+        #[derive(Clone)]
+        pub struct MeilisearchIndex {
+            client: reqwest::Client,
+            url: String,
+            api_key: Option<String>,
+        }
+
+        impl MeilisearchIndex {
+            pub fn new(url: impl Into<String>, api_key: Option<String>) -> Self {
+                Self {
+                    client: reqwest::Client::new(),
+                    url: url.into().trim_end_matches('/').to_string(),
+                    api_key,
+                }
+            }
+
+            fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+                let request = self.client.request(method, format!("{}{}", self.url, path));
+                match &self.api_key {
+                    Some(key) => request.bearer_auth(key),
+                    None => request,
+                }
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl SearchIndex for MeilisearchIndex {
+            async fn configure(&self, index: &str, fields: &[(&str, u32)]) -> Result<(), SearchError> {
+                let attributes: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+                self.request(reqwest::Method::PUT, &format!("/indexes/{}/settings/searchable-attributes", index))
+                    .json(&attributes)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            }
+
+            async fn upsert(&self, index: &str, documents: Vec<serde_json::Value>) -> Result<(), SearchError> {
+                if documents.is_empty() {
+                    return Ok(());
+                }
+                self.request(reqwest::Method::POST, &format!("/indexes/{}/documents?primaryKey=id", index))
+                    .json(&documents)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            }
+
+            async fn delete(&self, index: &str, ids: Vec<String>) -> Result<(), SearchError> {
+                if ids.is_empty() {
+                    return Ok(());
+                }
+                self.request(reqwest::Method::POST, &format!("/indexes/{}/documents/delete-batch", index))
+                    .json(&ids)
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            }
+
+            async fn search(
+                &self,
+                index: &str,
+                query: &str,
+                _fields: &[(&str, u32)],
+                limit: usize,
+            ) -> Result<Vec<String>, SearchError> {
+                let body = serde_json::json!({
+                    "q": query,
+                    "limit": limit,
+                    "attributesToRetrieve": ["id"],
+                });
+                let response: serde_json::Value = self
+                    .request(reqwest::Method::POST, &format!("/indexes/{}/search", index))
+                    .json(&body)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(response["hits"]
+                    .as_array()
+                    .map(|hits| hits.iter().filter_map(|hit| document_id(&hit["id"])).collect())
+                    .unwrap_or_default())
+            }
+        }
+
+        /// Elasticsearch (or OpenSearch) over its HTTP API
+        ///
+        /// Weights become `field^weight` boosts in a `multi_match` query.
+        #[derive(Clone)]
+        pub struct ElasticsearchIndex {
+            client: reqwest::Client,
+            url: String,
+            credentials: Option<(String, String)>,
+        }
+
+        impl ElasticsearchIndex {
+            pub fn new(url: impl Into<String>, credentials: Option<(String, String)>) -> Self {
+                Self {
+                    client: reqwest::Client::new(),
+                    url: url.into().trim_end_matches('/').to_string(),
+                    credentials,
+                }
+            }
+
+            fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+                let request = self.client.request(method, format!("{}{}", self.url, path));
+                match &self.credentials {
+                    Some((user, password)) => request.basic_auth(user, Some(password)),
+                    None => request,
+                }
+            }
+
+            async fn bulk(&self, lines: Vec<serde_json::Value>) -> Result<(), SearchError> {
+                let mut body = String::new();
+                for line in lines {
+                    body.push_str(&line.to_string());
+                    body.push('\n');
+                }
+                let response: serde_json::Value = self
+                    .request(reqwest::Method::POST, "/_bulk")
+                    .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+                    .body(body)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                if response["errors"].as_bool().unwrap_or(false) {
+                    return Err(SearchError::Backend(format!("bulk request failed: {}", response["items"])));
+                }
+                Ok(())
+            }
+        }
+
+        #[async_trait::async_trait]
+        impl SearchIndex for ElasticsearchIndex {
+            async fn configure(&self, _index: &str, _fields: &[(&str, u32)]) -> Result<(), SearchError> {
+                // Dynamic mapping indexes strings as text; boosts are applied at query time
+                Ok(())
+            }
+
+            async fn upsert(&self, index: &str, documents: Vec<serde_json::Value>) -> Result<(), SearchError> {
+                if documents.is_empty() {
+                    return Ok(());
+                }
+                let mut lines = Vec::with_capacity(documents.len() * 2);
+                for document in documents {
+                    let id = document_id(&document["id"]).unwrap_or_default();
+                    lines.push(serde_json::json!({ "index": { "_index": index, "_id": id } }));
+                    lines.push(document);
+                }
+                self.bulk(lines).await
+            }
+
+            async fn delete(&self, index: &str, ids: Vec<String>) -> Result<(), SearchError> {
+                if ids.is_empty() {
+                    return Ok(());
+                }
+                let lines = ids
+                    .into_iter()
+                    .map(|id| serde_json::json!({ "delete": { "_index": index, "_id": id } }))
+                    .collect();
+                self.bulk(lines).await
+            }
+
+            async fn search(
+                &self,
+                index: &str,
+                query: &str,
+                fields: &[(&str, u32)],
+                limit: usize,
+            ) -> Result<Vec<String>, SearchError> {
+                let boosted: Vec<String> = fields
+                    .iter()
+                    .map(|(name, weight)| format!("{}^{}", name, weight))
+                    .collect();
+                let body = serde_json::json!({
+                    "size": limit,
+                    "_source": false,
+                    "query": { "multi_match": { "query": query, "fields": boosted } },
+                });
+                let response: serde_json::Value = self
+                    .request(reqwest::Method::POST, &format!("/{}/_search", index))
+                    .json(&body)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(response["hits"]["hits"]
+                    .as_array()
+                    .map(|hits| hits.iter().filter_map(|hit| document_id(&hit["_id"])).collect())
+                    .unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Generate the entity event channel and indexer worker
+///
+/// With `dry_run`, events emitted during a dry run are dropped: its writes
+/// commit to a savepoint that is rolled back afterwards.
+fn generate_events(dry_run: bool) -> TokenStream {
+    let dry_run_guard = if dry_run {
+        quote! {
+            if super::prelude::dry_run() {
+                return;
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        /// A change to apply to the index
+        #[derive(Debug, Clone)]
+        pub enum SearchEvent {
+            Upsert { index: &'static str, document: serde_json::Value },
+            Delete { index: &'static str, id: String },
+        }
+
+        /// Events the indexer may fall behind by before new ones are dropped
+        pub const EVENT_QUEUE_CAPACITY: usize = 10_000;
+
+        static EVENTS: OnceLock<tokio::sync::mpsc::Sender<SearchEvent>> = OnceLock::new();
+
+        static DROPPED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+        /// Queue an event for the indexer
+        ///
+        /// Storage calls this once a write has committed. Events are dropped
+        /// when no indexer is running or the write is a dry run, or counted in
+        /// `dropped_events` when its queue is full.
+        pub fn emit(event: SearchEvent) {
+            #dry_run_guard
+            if let Some(events) = EVENTS.get() {
+                if events.try_send(event).is_err() {
+                    DROPPED.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                }
+            }
+        }
+
+        /// Events dropped because the indexer's queue was full
+        ///
+        /// A non-zero count means the index is missing writes; rerun the backfill.
+        pub fn dropped_events() -> u64 {
+            DROPPED.load(std::sync::atomic::Ordering::Relaxed)
+        }
+
+        /// Start the background indexer that applies entity events
+        ///
+        /// Only the first call installs the event channel; later calls return
+        /// `None`. Failed events are passed to `on_error` and not retried, so
+        /// rerun the backfill after a backend outage.
+        pub fn start_indexer(
+            index: Arc<dyn SearchIndex>,
+            on_error: impl Fn(SearchError) + Send + 'static,
+        ) -> Option<tokio::task::JoinHandle<()>> {
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(EVENT_QUEUE_CAPACITY);
+            EVENTS.set(sender).ok()?;
+            Some(tokio::spawn(async move {
+                while let Some(event) = receiver.recv().await {
+                    let result = match event {
+                        SearchEvent::Upsert { index: name, document } => index.upsert(name, vec![document]).await,
+                        SearchEvent::Delete { index: name, id } => index.delete(name, vec![id]).await,
+                    };
+                    if let Err(err) = result {
+                        on_error(err);
+                    }
+                }
+            }))
+        }
+    }
+}
+
+/// Generate the index constants, document builder, and backfill job for an entity
+fn generate_entity_items(entity: &SearchEntity) -> TokenStream {
+    let upper = entity.module.to_uppercase();
+    let index_const = format_ident!("{}_INDEX", upper);
+    let fields_const = format_ident!("{}_FIELDS", upper);
+    let document_fn = format_ident!("{}_document", entity.module);
+    let backfill_fn = format_ident!("backfill_{}", entity.table);
+    let entity_mod = format_ident!("{}", entity.module);
+    let table = &entity.table;
+
+    let weights = entity.fields.iter().map(|(name, weight)| quote! { (#name, #weight) });
+    let names = entity.fields.iter().map(|(name, _)| name);
+    let idents = entity.fields.iter().map(|(name, _)| format_ident!("{}", name));
+
+    let live_filter = if entity.soft_delete {
+        quote! { .filter(entities::#entity_mod::Column::DeletedAt.is_null()) }
+    } else {
+        quote! {}
+    };
+
+    quote! {
+        pub const #index_const: &str = #table;
+
+        /// Indexed columns and their weights, highest first
+        pub const #fields_const: &[(&str, u32)] = &[#(#weights),*];
+
+        /// Build the search document for a row
+        pub fn #document_fn(model: &entities::#entity_mod::Model) -> serde_json::Value {
+            serde_json::json!({
+                "id": model.id.to_string(),
+                #(#names: model.#idents.clone()),*
+            })
+        }
+
+        /// Configure the index and load every row into it, `batch_size` rows at a time
+        ///
+        /// Returns the number of rows indexed.
+        pub async fn #backfill_fn(
+            db: &DatabaseConnection,
+            index: &dyn SearchIndex,
+            batch_size: u64,
+        ) -> Result<u64, SearchError> {
+            index.configure(#index_const, #fields_const).await?;
+            let mut pages = entities::#entity_mod::Entity::find()
+                #live_filter
+                .order_by_asc(entities::#entity_mod::Column::Id)
+                .paginate(db, batch_size.max(1));
+            let mut indexed = 0;
+            while let Some(models) = pages.fetch_and_next().await? {
+                indexed += models.len() as u64;
+                index.upsert(#index_const, models.iter().map(#document_fn).collect()).await?;
+            }
+            Ok(indexed)
+        }
+    }
+}
+
+/// Generate the statements storage runs after committing a write of `rows`
+///
+/// `rows` iterates the written `&Model`s, which are re-indexed (or dropped
+/// from the index once soft-deleted). Events are only emitted after commit, so
+/// a rolled-back write never reaches the index.
+pub fn generate_save_events(module: &str, soft_delete: bool, rows: TokenStream) -> TokenStream {
+    let index_const = format_ident!("{}_INDEX", module.to_uppercase());
+    let document_fn = format_ident!("{}_document", module);
+
+    let event = if soft_delete {
+        quote! {
+            if model.deleted_at.is_some() {
+                SearchEvent::Delete { index: #index_const, id: model.id.to_string() }
+            } else {
+                SearchEvent::Upsert { index: #index_const, document: #document_fn(model) }
+            }
+        }
+    } else {
+        quote! {
+            SearchEvent::Upsert { index: #index_const, document: #document_fn(model) }
+        }
+    };
+
+    quote! {
+        {
+            use super::super::search::{#document_fn, #index_const, SearchEvent, emit};
+            for model in #rows {
+                emit(#event);
+            }
+        }
+    }
+}
+
+/// Generate the statement storage runs after committing a delete of `request.id`
+pub fn generate_delete_event(module: &str) -> TokenStream {
+    let index_const = format_ident!("{}_INDEX", module.to_uppercase());
+    quote! {
+        if result.rows_affected > 0 {
+            super::super::search::emit(super::super::search::SearchEvent::Delete {
+                index: super::super::search::#index_const,
+                id: request.id.to_string(),
+            });
+        }
+    }
+}
+
+/// Generate the statements storage runs after committing a bulk write returning `models`
+///
+/// `update_many` rows are re-indexed; `delete_many` rows (soft or hard) are
+/// dropped from the index.
+pub fn generate_bulk_events(module: &str, deleted: bool) -> TokenStream {
+    let index_const = format_ident!("{}_INDEX", module.to_uppercase());
+    let document_fn = format_ident!("{}_document", module);
+    let event = if deleted {
+        quote! { SearchEvent::Delete { index: #index_const, id: model.id.to_string() } }
+    } else {
+        quote! { SearchEvent::Upsert { index: #index_const, document: #document_fn(model) } }
+    };
+    quote! {
+        {
+            use super::super::search::{#document_fn, #index_const, SearchEvent, emit};
+            for model in &models {
+                emit(#event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(soft_delete: bool) -> SearchEntity {
+        SearchEntity {
+            message: DescriptorProto::default(),
+            module: "post".to_string(),
+            table: "posts".to_string(),
+            graphql_type: Some("Post".to_string()),
+            fields: vec![("title".to_string(), 3), ("body".to_string(), 1)],
+            soft_delete,
+        }
+    }
+
+    #[test]
+    fn test_generate_entity_items() {
+        let code = generate_entity_items(&entity(true)).to_string();
+        assert!(code.contains("pub const POST_INDEX : & str = \"posts\""));
+        assert!(code.contains("(\"title\" , 3u32)"));
+        assert!(code.contains("pub fn post_document"));
+        assert!(code.contains("pub async fn backfill_posts"));
+        assert!(code.contains("DeletedAt . is_null ()"));
+    }
+
+    #[test]
+    fn test_generate_save_events() {
+        let code = generate_save_events("post", true, quote! { &models }).to_string();
+        assert!(code.contains("for model in & models"));
+        assert!(code.contains("model . deleted_at . is_some ()"));
+        assert!(code.contains("post_document (model)"));
+
+        let code = generate_save_events("post", false, quote! { &models }).to_string();
+        assert!(!code.contains("deleted_at"));
+    }
+
+    #[test]
+    fn test_events_use_a_bounded_queue() {
+        let code = generate_events(false).to_string();
+        assert!(code.contains("mpsc :: channel (EVENT_QUEUE_CAPACITY)"));
+        assert!(code.contains("try_send"));
+        assert!(!code.contains("unbounded"));
+        assert!(!code.contains("dry_run"));
+    }
+
+    #[test]
+    fn test_events_skip_dry_runs() {
+        let code = generate_events(true).to_string();
+        assert!(code.contains("if super :: prelude :: dry_run () { return ; }"));
+    }
+
+    #[test]
+    fn test_generate_backends() {
+        let code = generate_backends().to_string();
+        assert!(code.contains("impl SearchIndex for MeilisearchIndex"));
+        assert!(code.contains("impl SearchIndex for ElasticsearchIndex"));
+        assert!(code.contains("multi_match"));
+    }
+}
@@ -0,0 +1,363 @@
+//! Seed generation
+//!
+//! Entities with `seeds` rows get a generated {package}/seed.rs whose
+//! `seed(db)` inserts those rows through the entities' ActiveModels. Each
+//! insert is `ON CONFLICT DO NOTHING` on the row's primary key (or a unique
+//! column it sets), so seeding is idempotent and never overwrites edits.
+//! Tables are seeded referenced-first, in migration order. On Postgres, the
+//! key sequence of a table whose seeds set integer keys is advanced past them.
+
+use super::entity::is_field_nullable;
+use super::implementation::MANAGED_TIMESTAMPS;
+use super::migration::{collect_tables, order_tables};
+use super::options::{get_cached_column_options, get_cached_entity_options};
+use crate::error::GeneratorError;
+use crate::options::synapse::storage::{EntityOptions, SeedRow};
+use crate::params::time_crate;
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use proc_macro2::{Literal, TokenStream};
+use prost_types::compiler::code_generator_response::File;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+
+/// An entity with seed rows
+struct SeedEntity<'a> {
+    file_name: &'a str,
+    message: &'a DescriptorProto,
+    options: EntityOptions,
+}
+
+/// Entities of the package that declare seed rows, in declaration order
+fn collect_seed_entities<'a>(
+    all_files: &'a [FileDescriptorProto],
+    main_file: &FileDescriptorProto,
+) -> Vec<SeedEntity<'a>> {
+    let package = main_file.package.as_deref().unwrap_or("");
+    let mut entities = Vec::new();
+    for file in all_files.iter().filter(|f| f.package.as_deref().unwrap_or("") == package) {
+        let file_name = file.name.as_deref().unwrap_or("");
+        for message in &file.message_type {
+            let msg_name = message.name.as_deref().unwrap_or("");
+            if let Some(options) = get_cached_entity_options(file_name, msg_name)
+                && !options.skip
+                && !options.seeds.is_empty()
+            {
+                entities.push(SeedEntity {
+                    file_name,
+                    message,
+                    options,
+                });
+            }
+        }
+    }
+    entities
+}
+
+/// Generate seed.rs for a package
+pub fn generate(
+    file: &FileDescriptorProto,
+    all_files: &[FileDescriptorProto],
+) -> Result<Option<File>, GeneratorError> {
+    let package = file.package.as_deref().unwrap_or("");
+    let mut entities = collect_seed_entities(all_files, file);
+    if package.is_empty() || entities.is_empty() {
+        return Ok(None);
+    }
+
+    let order: Vec<String> = order_tables(collect_tables(all_files, file)?)?
+        .into_iter()
+        .map(|t| t.module)
+        .collect();
+    entities.sort_by_key(|e| {
+        let module = e.message.name().to_snake_case();
+        order.iter().position(|m| *m == module)
+    });
+
+    let mut inserts = Vec::new();
+    let mut stamps = false;
+    for entity in &entities {
+        for row in &entity.options.seeds {
+            let (insert, stamped) = generate_insert(entity, row)?;
+            inserts.push(insert);
+            stamps |= stamped;
+        }
+        inserts.extend(generate_sequence_sync(entity));
+    }
+    let now = stamps.then(|| {
+        let now = time_crate().now();
+        quote! { let now = #now; }
+    });
+
+    let code = quote! {
+        //! Baseline rows from the entities' `seeds` options
+        //! @generated
+
+        #![allow(missing_docs)]
+        #![allow(unused_imports)]
+
+        use sea_orm::prelude::*;
+        use sea_orm::sea_query::OnConflict;
+        use sea_orm::ActiveValue::Set;
+
+        use super::entities;
+
+        /// Insert the package's seed rows, skipping rows that already exist
+        ///
+        /// Safe to run on every start: rows are keyed by their primary key or a
+        /// unique column and left untouched when present.
+        pub async fn seed<C: ConnectionTrait>(db: &C) -> Result<(), DbErr> {
+            #now
+            #(#inserts)*
+            Ok(())
+        }
+    };
+
+    Ok(Some(File {
+        name: Some(format!("{}/seed.rs", package.replace('.', "/"))),
+        content: Some(crate::params::format_code(code.to_string())),
+        ..Default::default()
+    }))
+}
+
+/// Generate the idempotent insert of one seed row, and whether it uses `now`
+fn generate_insert(entity: &SeedEntity, row: &SeedRow) -> Result<(TokenStream, bool), GeneratorError> {
+    let msg_name = entity.message.name();
+    let module = format_ident!("{}", msg_name.to_snake_case());
+
+    if let Some(unknown) = row
+        .values
+        .keys()
+        .find(|k| !entity.message.field.iter().any(|f| f.name() == k.as_str()))
+    {
+        return Err(GeneratorError::CodeGenError(format!(
+            "seed row for {} sets unknown field {}",
+            msg_name, unknown
+        )));
+    }
+
+    let mut assignments = Vec::new();
+    let mut stamped = false;
+    let mut key_column = None;
+    let mut unique_column = None;
+    for field in &entity.message.field {
+        let name = field.name();
+        let ident = format_ident!("{}", name);
+        let column = get_cached_column_options(entity.file_name, msg_name, field.number());
+        let unique = column.as_ref().is_some_and(|c| c.unique)
+            || entity
+                .options
+                .indexes
+                .iter()
+                .any(|i| i.unique && i.columns.len() == 1 && i.columns[0] == name);
+
+        let value = match row.values.get(name) {
+            Some(value) => seed_value(msg_name, field, value)?,
+            // Unlisted timestamps are stamped like storage stamps new rows
+            None if is_timestamp(field) && !is_field_nullable(field) => quote! { now },
+            None => continue,
+        };
+        stamped |= is_timestamp(field);
+        assignments.push(quote! { #ident: Set(#value), });
+
+        if row.values.contains_key(name) {
+            let variant = format_ident!("{}", name.to_upper_camel_case());
+            if column.as_ref().is_some_and(|c| c.primary_key) {
+                key_column.get_or_insert(variant);
+            } else if unique {
+                unique_column.get_or_insert(variant);
+            }
+        }
+    }
+
+    // Storage-managed timestamps the message leaves out are still columns
+    if entity.options.timestamps {
+        for name in MANAGED_TIMESTAMPS {
+            if !entity.message.field.iter().any(|f| f.name() == *name) {
+                let ident = format_ident!("{}", name);
+                assignments.push(quote! { #ident: Set(now), });
+                stamped = true;
+            }
+        }
+    }
+
+    let Some(conflict_column) = key_column.or(unique_column) else {
+        return Err(GeneratorError::CodeGenError(format!(
+            "seed row for {} must set the primary key or a unique column",
+            msg_name
+        )));
+    };
+
+    let insert = quote! {
+        entities::#module::Entity::insert(entities::#module::ActiveModel {
+            #(#assignments)*
+            ..Default::default()
+        })
+        .on_conflict(OnConflict::column(entities::#module::Column::#conflict_column).do_nothing().to_owned())
+        .exec_without_returning(db)
+        .await?;
+    };
+    Ok((insert, stamped))
+}
+
+/// Generate the advance of an entity's key sequence past its seeded keys
+///
+/// Seeds that set a serial (or identity) key leave the sequence behind, so
+/// the next regular insert would collide with them. `None` unless an integer
+/// primary key is set by a seed row.
+fn generate_sequence_sync(entity: &SeedEntity) -> Option<TokenStream> {
+    let msg_name = entity.message.name();
+    let key = entity.message.field.iter().find(|f| {
+        get_cached_column_options(entity.file_name, msg_name, f.number()).is_some_and(|c| c.primary_key)
+    })?;
+    let integer = matches!(
+        key.r#type(),
+        Type::Int32 | Type::Sint32 | Type::Sfixed32 | Type::Int64 | Type::Sint64 | Type::Sfixed64
+    );
+    if !integer || !entity.options.seeds.iter().any(|row| row.values.contains_key(key.name())) {
+        return None;
+    }
+
+    let module = format_ident!("{}", msg_name.to_snake_case());
+    let column = format_ident!("{}", key.name().to_upper_camel_case());
+    Some(quote! {
+        if db.get_database_backend() == sea_orm::DbBackend::Postgres {
+            let table = entities::#module::Entity.table_name();
+            let key = entities::#module::Column::#column.as_str();
+            db.execute_unprepared(&format!(
+                "SELECT setval(pg_get_serial_sequence('{table}', '{key}'), MAX(\"{key}\")) FROM \"{table}\" HAVING MAX(\"{key}\") IS NOT NULL"
+            ))
+            .await?;
+        }
+    })
+}
+
+/// Whether a field is a `google.protobuf.Timestamp`
+fn is_timestamp(field: &FieldDescriptorProto) -> bool {
+    field.r#type() == Type::Message && field.type_name() == ".google.protobuf.Timestamp"
+}
+
+/// Rust expression for a seed value, parsed per the field type
+fn seed_value(msg_name: &str, field: &FieldDescriptorProto, value: &str) -> Result<TokenStream, GeneratorError> {
+    let invalid = || {
+        GeneratorError::CodeGenError(format!(
+            "seed value {:?} is not valid for {}.{}",
+            value,
+            msg_name,
+            field.name()
+        ))
+    };
+
+    if field.label() == Label::Repeated {
+        return Err(GeneratorError::CodeGenError(format!(
+            "seed rows cannot set repeated field {}.{}",
+            msg_name,
+            field.name()
+        )));
+    }
+
+    let tokens = match field.r#type() {
+        Type::String => quote! { #value.to_string() },
+        Type::Bool => {
+            let b: bool = value.parse().map_err(|_| invalid())?;
+            quote! { #b }
+        }
+        Type::Int32 | Type::Sint32 | Type::Sfixed32 | Type::Int64 | Type::Sint64 | Type::Sfixed64 => {
+            let literal = Literal::i64_unsuffixed(value.parse().map_err(|_| invalid())?);
+            quote! { #literal }
+        }
+        Type::Uint32 | Type::Fixed32 | Type::Uint64 | Type::Fixed64 => {
+            let literal = Literal::u64_unsuffixed(value.parse().map_err(|_| invalid())?);
+            quote! { #literal }
+        }
+        Type::Float | Type::Double => {
+            let literal = Literal::f64_unsuffixed(value.parse().map_err(|_| invalid())?);
+            quote! { #literal }
+        }
+        Type::Message if is_timestamp(field) && value == "now" => quote! { now },
+        _ => return Err(invalid()),
+    };
+
+    if is_field_nullable(field) {
+        Ok(quote! { Some(#tokens) })
+    } else {
+        Ok(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{field, message_field};
+
+    #[test]
+    fn test_seed_value() {
+        let name = field("name", 1, Type::String);
+        assert_eq!(
+            seed_value("User", &name, "Admin").unwrap().to_string(),
+            "\"Admin\" . to_string ()"
+        );
+
+        let org = FieldDescriptorProto {
+            proto3_optional: Some(true),
+            ..field("organization_id", 1, Type::Int64)
+        };
+        assert_eq!(seed_value("User", &org, "1").unwrap().to_string(), "Some (1)");
+
+        let active = field("is_active", 1, Type::Bool);
+        assert_eq!(seed_value("User", &active, "true").unwrap().to_string(), "true");
+    }
+
+    #[test]
+    fn test_seed_value_invalid() {
+        let id = field("id", 1, Type::Int64);
+        let err = seed_value("User", &id, "one").unwrap_err().to_string();
+        assert!(err.contains("\"one\" is not valid for User.id"));
+
+        let created_at = message_field("created_at", 1, ".google.protobuf.Timestamp");
+        assert_eq!(seed_value("User", &created_at, "now").unwrap().to_string(), "now");
+        assert!(seed_value("User", &created_at, "2024-01-01").is_err());
+    }
+
+    #[test]
+    fn test_sequence_sync_after_explicit_keys() {
+        use crate::options::synapse::storage::ColumnOptions;
+        use crate::storage::seaorm::options::cache_column_options;
+
+        let message = DescriptorProto {
+            name: Some("Plan".to_string()),
+            field: vec![field("id", 1, Type::Int64), field("name", 2, Type::String)],
+            ..Default::default()
+        };
+        cache_column_options(
+            "seed_test.proto",
+            "Plan",
+            1,
+            ColumnOptions {
+                primary_key: true,
+                ..Default::default()
+            },
+        );
+        let row = |values: &[(&str, &str)]| SeedRow {
+            values: values.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        };
+        let entity = |seeds| SeedEntity {
+            file_name: "seed_test.proto",
+            message: &message,
+            options: EntityOptions {
+                seeds,
+                ..Default::default()
+            },
+        };
+
+        let code = generate_sequence_sync(&entity(vec![row(&[("id", "1"), ("name", "Free")])]))
+            .unwrap()
+            .to_string();
+        assert!(code.contains("db . get_database_backend () == sea_orm :: DbBackend :: Postgres"));
+        assert!(code.contains("entities :: plan :: Column :: Id . as_str ()"));
+        assert!(code.contains("SELECT setval(pg_get_serial_sequence("));
+
+        // Keys left to the database need no sync
+        assert!(generate_sequence_sync(&entity(vec![row(&[("name", "Free")])])).is_none());
+    }
+}
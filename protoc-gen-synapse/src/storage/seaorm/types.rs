@@ -4,6 +4,7 @@
 //! corresponding Rust types for SeaORM entities.
 
 use prost_types::field_descriptor_proto::Type;
+use crate::params::time_crate;
 
 /// Represents a mapped Rust type for SeaORM entities
 #[derive(Debug, Clone)]
@@ -55,7 +56,7 @@ pub fn map_proto_type(proto_type: Type, type_name: Option<&str>) -> MappedType {
 fn map_message_type(type_name: Option<&str>) -> MappedType {
     match type_name {
         Some(".google.protobuf.Timestamp") => MappedType {
-            rust_type: "DateTimeUtc".to_string(),
+            rust_type: time_crate().datetime_type().to_string(),
         },
         Some(".google.protobuf.Duration") => MappedType {
             rust_type: "TimeDelta".to_string(),
@@ -9,6 +9,9 @@
 //! - Each operation has a default implementation that calls `defaults::*`
 //! - Users can override specific methods while using defaults for others
 
+use super::defaults::{
+    infer_operation, polymorphic_enum_name, polymorphic_relations, recursive_relations,
+};
 use super::seaorm::options::{
     get_cached_rpc_method_options, get_cached_service_options, get_cached_validate_message_options,
     parse_service_options,
@@ -51,6 +54,8 @@ pub fn generate(
 
     // Check if we should generate default implementations
     let generate_defaults = service_options.generate_implementation;
+    // Read defaults go through read_db() when reads may use a replica
+    let read_replica = generate_defaults && service_options.read_replica;
 
     // Generate the output filename (in storage/ subdirectory)
     let module_name = trait_name.to_snake_case();
@@ -61,7 +66,13 @@ pub fn generate(
     );
 
     // Generate trait methods (with or without default implementations)
-    let methods = generate_trait_methods(file_name, service_name, &service.method, generate_defaults, &module_name)?;
+    let trait_methods = service_methods(file, service);
+    let methods: Vec<_> = trait_methods
+        .iter()
+        .map(|m| m.to_trait_item(generate_defaults, read_replica))
+        .collect();
+    let forwarding_methods: Vec<_> = trait_methods.iter().map(|m| m.to_forwarding_item()).collect();
+    let polymorphic_enums = generate_polymorphic_enums(file, service);
 
     // Build the trait
     let trait_ident = format_ident!("{}", trait_name);
@@ -85,6 +96,26 @@ pub fn generate(
         quote! {}
     };
 
+    // Smart pointers forward db() only when the trait has it
+    let forwarding_db = if generate_defaults {
+        quote! {
+            fn db(&self) -> &sea_orm::DatabaseConnection {
+                (**self).db()
+            }
+        }
+    } else {
+        quote! {}
+    };
+    let forwarding_read_db = if read_replica {
+        quote! {
+            fn read_db(&self) -> &sea_orm::DatabaseConnection {
+                (**self).read_db()
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     // The db() method - required for all implementations
     let db_method = if generate_defaults {
         quote! {
@@ -97,6 +128,54 @@ pub fn generate(
         quote! {}
     };
 
+    // The read_db() method - reads may go to a replica, defaulting to the primary
+    let read_db_method = if read_replica {
+        quote! {
+            /// Get the connection read-only defaults use (get, list, tree and
+            /// polymorphic lookups).
+            ///
+            /// Defaults to `db()`; return a replica connection to move reads
+            /// off the primary.
+            fn read_db(&self) -> &sea_orm::DatabaseConnection {
+                self.db()
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    // Services and resolvers turn storage errors into statuses with `?` or `into()`
+    let status_conversion = crate::grpc::generate_storage_status_conversion();
+
+    // Item stream returned by server-streaming methods
+    let row_stream = if super::defaults::has_row_streams(service) {
+        quote! {
+            /// Rows converted and sent one at a time by server-streaming methods
+            ///
+            /// Dropping the stream (as tonic does when the client disconnects)
+            /// stops the database read.
+            pub type RowStream<T> = tokio_stream::wrappers::ReceiverStream<Result<T, StorageError>>;
+
+            /// Converted rows buffered ahead of a slow client
+            pub const ROW_STREAM_BUFFER: usize = 64;
+        }
+    } else {
+        quote! {}
+    };
+
+    // Item stream taken by client-streaming (import) methods
+    let import_stream = if super::defaults::has_import_streams(service) {
+        quote! {
+            /// Requests sent one at a time to client-streaming (import) methods
+            ///
+            /// An error item means the client's stream broke, e.g. it disconnected.
+            pub type ImportStream<T> =
+                std::pin::Pin<Box<dyn tokio_stream::Stream<Item = Result<T, StorageError>> + Send + 'static>>;
+        }
+    } else {
+        quote! {}
+    };
+
     let code = quote! {
         #![doc = #module_doc]
         //!
@@ -149,24 +228,58 @@ pub fn generate(
             /// Invalid argument
             #[error("invalid argument: {0}")]
             InvalidArgument(String),
+            /// A `unique_in_db` field conflicts with an existing row
+            #[error("already exists: {message}")]
+            AlreadyExists {
+                /// The conflicting field
+                field: String,
+                message: String,
+            },
         }
 
+        #status_conversion
+
+        #row_stream
+
+        #import_stream
+
+        #(#polymorphic_enums)*
+
         #[doc = #trait_doc]
         #[async_trait::async_trait]
         pub trait #trait_ident: Send + Sync {
             #db_method
+            #read_db_method
             #(#methods)*
         }
+
+        // Forwarding impls let services hold `Arc<dyn Trait>` or `Box<dyn Trait>`
+        // and swap implementations at runtime (mocks in tests, caches in prod).
+
+        #[async_trait::async_trait]
+        impl<T: #trait_ident + ?Sized> #trait_ident for std::sync::Arc<T> {
+            #forwarding_db
+            #forwarding_read_db
+            #(#forwarding_methods)*
+        }
+
+        #[async_trait::async_trait]
+        impl<T: #trait_ident + ?Sized> #trait_ident for Box<T> {
+            #forwarding_db
+            #forwarding_read_db
+            #(#forwarding_methods)*
+        }
+
+        /// Compile-time check that the trait stays object safe
+        #[allow(dead_code)]
+        fn assert_object_safe(_: &dyn #trait_ident) {}
     };
 
     // Format the generated code
     let content = code.to_string();
 
-    // Try to format with prettyplease if we can parse it
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content, // If parsing fails, use unformatted
-    };
+    // Format the generated code
+    let formatted = crate::params::format_code(content);
 
     Ok(Some(File {
         name: Some(output_filename),
@@ -175,14 +288,112 @@ pub fn generate(
     }))
 }
 
+/// A storage trait method signature
+///
+/// Rendered both as a trait item (abstract or delegating to `defaults`) and as a
+/// forwarding method for the smart-pointer impls.
+pub(crate) struct TraitMethod {
+    ident: proc_macro2::Ident,
+    /// Typed parameters after `&self`
+    params: TokenStream,
+    /// Parameter names, for forwarding calls
+    args: TokenStream,
+    /// Success type of the returned `Result`
+    output: TokenStream,
+    /// Hand-written method (`custom: true`) without a generated default
+    custom: bool,
+    /// Read-only default, run on `read_db()` under `read_replica`
+    read: bool,
+}
+
+impl TraitMethod {
+    /// Render as a trait item, with a default body calling `defaults::*` if requested
+    fn to_trait_item(&self, generate_defaults: bool, read_replica: bool) -> TokenStream {
+        let TraitMethod { ident, params, args, output, custom, read } = self;
+        let db = if read_replica && *read {
+            quote! { self.read_db() }
+        } else {
+            quote! { self.db() }
+        };
+        if *custom {
+            quote! {
+                /// Custom method: no default implementation is generated
+                async fn #ident(&self, #params) -> Result<#output, StorageError>;
+            }
+        } else if generate_defaults {
+            quote! {
+                async fn #ident(&self, #params) -> Result<#output, StorageError> {
+                    defaults::#ident(#db, #args).await
+                }
+            }
+        } else {
+            quote! {
+                async fn #ident(&self, #params) -> Result<#output, StorageError>;
+            }
+        }
+    }
+
+    /// Render as an abstract item of a hand-written `custom` trait
+    pub(crate) fn to_custom_item(&self) -> TokenStream {
+        let TraitMethod { ident, params, output, .. } = self;
+        quote! {
+            async fn #ident(&self, #params) -> Result<#output, StorageError>;
+        }
+    }
+
+    /// Render as a method calling the same method of `Self` as `trait_ident`
+    pub(crate) fn to_trait_call_item(&self, trait_ident: &proc_macro2::Ident) -> TokenStream {
+        let args = &self.args;
+        let ident = &self.ident;
+        self.to_item(quote! { <Self as #trait_ident>::#ident(self, #args).await })
+    }
+
+    /// Render as a method forwarding to the pointee (`(**self)`)
+    fn to_forwarding_item(&self) -> TokenStream {
+        self.to_delegating_item(quote! { (**self) })
+    }
+
+    /// Render as a method calling the same method on `target`
+    pub(crate) fn to_delegating_item(&self, target: TokenStream) -> TokenStream {
+        let args = &self.args;
+        let ident = &self.ident;
+        self.to_item(quote! { #target.#ident(#args).await })
+    }
+
+    /// Render with the given body
+    pub(crate) fn to_item(&self, body: TokenStream) -> TokenStream {
+        let TraitMethod { ident, params, output, .. } = self;
+        quote! {
+            async fn #ident(&self, #params) -> Result<#output, StorageError> {
+                #body
+            }
+        }
+    }
+
+    pub(crate) fn ident(&self) -> &proc_macro2::Ident {
+        &self.ident
+    }
+}
+
+/// All trait methods of a service: its RPCs, then tree and polymorphic lookups
+pub(crate) fn service_methods(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Vec<TraitMethod> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let service_name = service.name.as_deref().unwrap_or("");
+    let mut methods = generate_trait_methods(file_name, service_name, &service.method);
+    methods.extend(generate_tree_methods(file, service));
+    methods.extend(generate_polymorphic_methods(file, service));
+    methods
+}
+
 /// Generate trait method signatures from service methods
 fn generate_trait_methods(
     file_name: &str,
     service_name: &str,
     methods: &[MethodDescriptorProto],
-    generate_defaults: bool,
-    _module_name: &str,
-) -> Result<Vec<TokenStream>, GeneratorError> {
+) -> Vec<TraitMethod> {
     let mut result = Vec::new();
 
     for method in methods {
@@ -203,33 +414,150 @@ fn generate_trait_methods(
             .map(|o| o.method_name.clone())
             .unwrap_or_else(|| method_name.to_snake_case());
 
+        // Operation from method options or inferred from the method name
+        let operation = method_options
+            .as_ref()
+            .filter(|o| !o.operation.is_empty())
+            .map(|o| o.operation.clone())
+            .unwrap_or_else(|| infer_operation(method_name).to_string());
+
         // Extract input/output types - check for domain type first
         let raw_input_type = extract_type_name(method.input_type.as_deref());
         let input_type = resolve_domain_type(file_name, &raw_input_type);
         let output_type = extract_type_name(method.output_type.as_deref());
 
-        let method_ident = format_ident!("{}", rust_method_name);
         let input_ident = format_ident!("{}", input_type);
         let output_ident = format_ident!("{}", output_type);
 
-        let method_token = if generate_defaults {
-            // Generate method with default implementation that calls defaults::*
-            quote! {
-                async fn #method_ident(&self, request: #input_ident) -> Result<#output_ident, StorageError> {
-                    defaults::#method_ident(self.db(), request).await
-                }
-            }
+        // Client-streaming RPCs take the raw requests, validated one by one
+        let input = if method.client_streaming() {
+            let raw_input_ident = format_ident!("{}", raw_input_type);
+            quote! { ImportStream<#raw_input_ident> }
         } else {
-            // Generate abstract method signature only
-            quote! {
-                async fn #method_ident(&self, request: #input_ident) -> Result<#output_ident, StorageError>;
-            }
+            quote! { #input_ident }
+        };
+
+        // Server-streaming RPCs return a stream of their response messages
+        let output = if method.server_streaming() {
+            quote! { RowStream<#output_ident> }
+        } else {
+            quote! { #output_ident }
         };
 
-        result.push(method_token);
+        result.push(TraitMethod {
+            ident: format_ident!("{}", rust_method_name),
+            params: quote! { request: #input },
+            args: quote! { request },
+            output,
+            custom: method_options.as_ref().is_some_and(|o| o.custom),
+            read: is_read_operation(&operation),
+        });
     }
 
-    Ok(result)
+    result
+}
+
+/// Trait methods of a service marked `custom: true`
+pub(crate) fn custom_methods(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Vec<TraitMethod> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let service_name = service.name.as_deref().unwrap_or("");
+    let methods: Vec<_> = service
+        .method
+        .iter()
+        .filter(|m| {
+            let method_name = m.name.as_deref().unwrap_or("");
+            get_cached_rpc_method_options(file_name, service_name, method_name)
+                .is_some_and(|o| o.custom && !o.skip)
+        })
+        .cloned()
+        .collect();
+    generate_trait_methods(file_name, service_name, &methods)
+}
+
+/// Whether an operation only reads, so it may run on the read replica
+fn is_read_operation(operation: &str) -> bool {
+    matches!(operation.to_snake_case().as_str(), "get" | "get_many" | "list")
+}
+
+/// Generate tree traversal methods for recursive self-referential relations
+fn generate_tree_methods(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Vec<TraitMethod> {
+    recursive_relations(file, service)
+        .into_iter()
+        .flat_map(|(entity_name, _)| {
+            let entity_snake = entity_name.to_snake_case();
+            let entity_module = format_ident!("{}", entity_snake);
+            let entity_type = format_ident!("{}", entity_name.to_upper_camel_case());
+            ["ancestors", "descendants"].map(|direction| TraitMethod {
+                ident: format_ident!("{}_{}", entity_snake, direction),
+                params: quote! { id: KeyOf<entities::#entity_module::Entity> },
+                args: quote! { id },
+                output: quote! { Vec<#entity_type> },
+                custom: false,
+                read: true,
+            })
+        })
+        .collect()
+}
+
+/// Generate enums holding the target of each polymorphic relation
+fn generate_polymorphic_enums(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Vec<TokenStream> {
+    polymorphic_relations(file, service)
+        .into_iter()
+        .map(|(entity_name, relation)| {
+            let enum_ident = format_ident!("{}", polymorphic_enum_name(&entity_name, &relation));
+            let variants = relation.candidates.iter().map(|candidate| {
+                let variant_ident = format_ident!("{}", candidate.to_upper_camel_case());
+                quote! { #variant_ident(#variant_ident), }
+            });
+            let doc = format!(
+                "Target of the polymorphic `{}.{}` relation",
+                entity_name.to_upper_camel_case(),
+                relation.name.to_snake_case()
+            );
+
+            quote! {
+                #[doc = #doc]
+                #[derive(Debug, Clone)]
+                pub enum #enum_ident {
+                    #(#variants)*
+                }
+            }
+        })
+        .collect()
+}
+
+/// Generate dispatch methods for polymorphic relations
+fn generate_polymorphic_methods(
+    file: &FileDescriptorProto,
+    service: &ServiceDescriptorProto,
+) -> Vec<TraitMethod> {
+    polymorphic_relations(file, service)
+        .into_iter()
+        .map(|(entity_name, relation)| {
+            let enum_ident = format_ident!("{}", polymorphic_enum_name(&entity_name, &relation));
+            TraitMethod {
+                ident: format_ident!(
+                    "{}_{}",
+                    entity_name.to_snake_case(),
+                    relation.name.to_snake_case()
+                ),
+                params: quote! { type_name: &str, id: &str },
+                args: quote! { type_name, id },
+                output: quote! { Option<#enum_ident> },
+                custom: false,
+                read: true,
+            }
+        })
+        .collect()
 }
 
 /// Resolve a message type to its domain type if one exists
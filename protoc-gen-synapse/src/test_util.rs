@@ -0,0 +1,34 @@
+//! Descriptor fixtures shared by the generators' unit tests
+//!
+//! Tests set `proto3_optional` and the like with struct update syntax, e.g.
+//! `FieldDescriptorProto { proto3_optional: Some(true), ..field("bio", 3, Type::String) }`.
+
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::FieldDescriptorProto;
+
+/// A singular field of scalar type `ty`
+pub fn field(name: &str, number: i32, ty: Type) -> FieldDescriptorProto {
+    FieldDescriptorProto {
+        name: Some(name.to_string()),
+        number: Some(number),
+        label: Some(Label::Optional as i32),
+        r#type: Some(ty as i32),
+        ..Default::default()
+    }
+}
+
+/// A singular field of message type `type_name` (fully qualified, e.g. `.blog.Author`)
+pub fn message_field(name: &str, number: i32, type_name: &str) -> FieldDescriptorProto {
+    FieldDescriptorProto {
+        type_name: Some(type_name.to_string()),
+        ..field(name, number, Type::Message)
+    }
+}
+
+/// The field, made repeated
+pub fn repeated(field: FieldDescriptorProto) -> FieldDescriptorProto {
+    FieldDescriptorProto {
+        label: Some(Label::Repeated as i32),
+        ..field
+    }
+}
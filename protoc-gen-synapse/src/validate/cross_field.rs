@@ -0,0 +1,396 @@
+//! Cross-field validation
+//!
+//! Generates the checks that look at more than one field of a request:
+//! message-level `rules` (`starts_at < ends_at`) and the field-level
+//! `required_if`/`required_unless` conditions. Comparisons are skipped while
+//! either side is unset, so they compose with `required`.
+
+use crate::error::GeneratorError;
+use crate::options::synapse::validate;
+use crate::storage::seaorm::options::get_cached_validate_field_options;
+use heck::ToSnakeCase;
+use proc_macro2::{Literal, TokenStream};
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+use quote::{format_ident, quote};
+
+/// Comparison operator in a rule expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Op {
+    fn tokens(self) -> TokenStream {
+        match self {
+            Op::Lt => quote! { < },
+            Op::Le => quote! { <= },
+            Op::Gt => quote! { > },
+            Op::Ge => quote! { >= },
+            Op::Eq => quote! { == },
+            Op::Ne => quote! { != },
+        }
+    }
+}
+
+/// A parsed `lhs op rhs` expression
+#[derive(Debug, PartialEq)]
+struct Comparison<'a> {
+    lhs: &'a str,
+    op: Op,
+    rhs: &'a str,
+}
+
+/// Split an expression at its (first) comparison operator
+fn parse_comparison(expr: &str) -> Option<Comparison<'_>> {
+    let pos = expr.find(['<', '>', '=', '!'])?;
+    let rest = &expr[pos..];
+    let (op, len) = match (rest.as_bytes()[0], rest.as_bytes().get(1)) {
+        (b'<', Some(b'=')) => (Op::Le, 2),
+        (b'>', Some(b'=')) => (Op::Ge, 2),
+        (b'=', Some(b'=')) => (Op::Eq, 2),
+        (b'!', Some(b'=')) => (Op::Ne, 2),
+        (b'<', _) => (Op::Lt, 1),
+        (b'>', _) => (Op::Gt, 1),
+        _ => return None,
+    };
+    let lhs = expr[..pos].trim();
+    let rhs = expr[pos + len..].trim();
+    if lhs.is_empty() || rhs.is_empty() {
+        return None;
+    }
+    Some(Comparison { lhs, op, rhs })
+}
+
+/// Generate cross-field checks for a message
+///
+/// The returned statements push into `errors` like the per-field rules.
+pub fn generate(
+    file: &FileDescriptorProto,
+    message: &DescriptorProto,
+    rules: &[validate::CrossFieldRule],
+    field_error_ident: &proc_macro2::Ident,
+) -> Result<Vec<TokenStream>, GeneratorError> {
+    let file_name = file.name.as_deref().unwrap_or("");
+    let message_name = message.name.as_deref().unwrap_or("");
+    let mut checks = Vec::new();
+
+    for rule in rules {
+        let cmp = parse_comparison(&rule.expr).ok_or_else(|| {
+            invalid(message_name, &rule.expr, "expected `<field> <op> <field or literal>`")
+        })?;
+        let holds = generate_condition(file, message, &cmp, &rule.expr)?;
+        let field_name = cmp.lhs;
+        let error_message = if rule.message.is_empty() {
+            format!("{} must hold", rule.expr)
+        } else {
+            rule.message.clone()
+        };
+        checks.push(quote! {
+            if let Some(false) = #holds {
+                errors.push(#field_error_ident {
+                    code: "cross_field".to_string(),
+                    message: #error_message.to_string(),
+                    field: #field_name.to_string(),
+                });
+            }
+        });
+    }
+
+    for field in &message.field {
+        let Some(rules) = get_cached_validate_field_options(file_name, message_name, field.number.unwrap_or(0))
+            .filter(|opts| !opts.skip)
+            .and_then(|opts| opts.rules)
+        else {
+            continue;
+        };
+        let field_name = field.name.as_deref().unwrap_or("");
+        let present = generate_presence(field);
+
+        for (condition, required_when) in [(&rules.required_if, true), (&rules.required_unless, false)] {
+            if condition.is_empty() {
+                continue;
+            }
+            // A bare field name means "that field is set"
+            let holds = match parse_comparison(condition) {
+                Some(cmp) => {
+                    let holds = generate_condition(file, message, &cmp, condition)?;
+                    quote! { (#holds) == Some(true) }
+                }
+                None => {
+                    let other = find_field(message, condition.trim())
+                        .ok_or_else(|| invalid(message_name, condition, "unknown field"))?;
+                    generate_presence(other)
+                }
+            };
+            let (applies, word) = if required_when {
+                (holds, "when")
+            } else {
+                (quote! { !(#holds) }, "unless")
+            };
+            let error_message = format!("{} is required {} {}", field_name, word, condition);
+            checks.push(quote! {
+                if #applies && !(#present) {
+                    errors.push(#field_error_ident {
+                        code: "required".to_string(),
+                        message: #error_message.to_string(),
+                        field: #field_name.to_string(),
+                    });
+                }
+            });
+        }
+    }
+
+    Ok(checks)
+}
+
+/// Generate an `Option<bool>` expression: `None` while either side is unset
+fn generate_condition(
+    file: &FileDescriptorProto,
+    message: &DescriptorProto,
+    cmp: &Comparison<'_>,
+    expr: &str,
+) -> Result<TokenStream, GeneratorError> {
+    let message_name = message.name.as_deref().unwrap_or("");
+    let lhs_field = find_field(message, cmp.lhs)
+        .ok_or_else(|| invalid(message_name, expr, "left side must be a field"))?;
+    let lhs = generate_accessor(lhs_field)
+        .ok_or_else(|| invalid(message_name, expr, "field type cannot be compared"))?;
+    let rhs = match find_field(message, cmp.rhs) {
+        Some(field) => generate_accessor(field)
+            .ok_or_else(|| invalid(message_name, expr, "field type cannot be compared"))?,
+        None => {
+            let literal = generate_literal(file, lhs_field, cmp.rhs)
+                .ok_or_else(|| invalid(message_name, expr, "right side is not a field or a valid literal"))?;
+            quote! { Some(#literal) }
+        }
+    };
+    let op = cmp.op.tokens();
+    Ok(quote! {
+        match (#lhs, #rhs) {
+            (Some(lhs), Some(rhs)) => Some(lhs #op rhs),
+            _ => None,
+        }
+    })
+}
+
+/// Expression reading a field as an `Option` of something comparable
+fn generate_accessor(field: &FieldDescriptorProto) -> Option<TokenStream> {
+    if field.label() == Label::Repeated {
+        return None;
+    }
+    let ident = field_ident(field);
+    let optional = field.proto3_optional.unwrap_or(false);
+    let accessor = match field.r#type() {
+        Type::Message if field.type_name.as_deref() == Some(".google.protobuf.Timestamp") => {
+            quote! { request.#ident.as_ref().map(|t| (t.seconds, t.nanos)) }
+        }
+        Type::Message | Type::Group => return None,
+        Type::String if optional => quote! { request.#ident.as_deref() },
+        Type::String => quote! { Some(request.#ident.as_str()) },
+        Type::Bytes if optional => quote! { request.#ident.as_deref() },
+        Type::Bytes => quote! { Some(request.#ident.as_slice()) },
+        _ if optional => quote! { request.#ident },
+        _ => quote! { Some(request.#ident) },
+    };
+    Some(accessor)
+}
+
+/// A literal compared against `field`, typed to match it
+///
+/// Enum fields take value names (`EXTERNAL` or `EVENT_TYPE_EXTERNAL`),
+/// strings take quoted text, numbers and bools their usual spelling.
+fn generate_literal(
+    file: &FileDescriptorProto,
+    field: &FieldDescriptorProto,
+    text: &str,
+) -> Option<TokenStream> {
+    let literal = match field.r#type() {
+        Type::String => {
+            let unquoted = text
+                .strip_prefix('"')
+                .and_then(|t| t.strip_suffix('"'))
+                .or_else(|| text.strip_prefix('\'').and_then(|t| t.strip_suffix('\'')))?;
+            quote! { #unquoted }
+        }
+        Type::Bool => match text {
+            "true" => quote! { true },
+            "false" => quote! { false },
+            _ => return None,
+        },
+        Type::Double | Type::Float => {
+            let value = Literal::f64_unsuffixed(text.parse().ok()?);
+            quote! { #value }
+        }
+        Type::Enum => {
+            let number = match text.parse::<i32>() {
+                Ok(n) => n,
+                Err(_) => enum_value(file, field.type_name.as_deref()?, text)?,
+            };
+            let value = Literal::i32_unsuffixed(number);
+            quote! { #value }
+        }
+        Type::Message | Type::Group | Type::Bytes => return None,
+        _ => {
+            let value = Literal::i64_unsuffixed(text.parse().ok()?);
+            quote! { #value }
+        }
+    };
+    Some(literal)
+}
+
+/// Number of an enum value named `name` (with or without the type prefix)
+fn enum_value(file: &FileDescriptorProto, type_name: &str, name: &str) -> Option<i32> {
+    fn find<'a>(enums: &'a [EnumDescriptorProto], messages: &'a [DescriptorProto], short: &str) -> Option<&'a EnumDescriptorProto> {
+        enums
+            .iter()
+            .find(|e| e.name.as_deref() == Some(short))
+            .or_else(|| messages.iter().find_map(|m| find(&m.enum_type, &m.nested_type, short)))
+    }
+
+    let short = type_name.rsplit('.').next().unwrap_or(type_name);
+    let suffix = format!("_{}", name);
+    find(&file.enum_type, &file.message_type, short)?
+        .value
+        .iter()
+        .find(|v| {
+            let value_name = v.name.as_deref().unwrap_or("");
+            value_name == name || value_name.ends_with(&suffix)
+        })
+        .and_then(|v| v.number)
+}
+
+/// Expression that is true when a field has a value
+fn generate_presence(field: &FieldDescriptorProto) -> TokenStream {
+    let ident = field_ident(field);
+    if field.label() == Label::Repeated {
+        return quote! { !request.#ident.is_empty() };
+    }
+    if field.proto3_optional.unwrap_or(false) {
+        return quote! { request.#ident.is_some() };
+    }
+    match field.r#type() {
+        Type::Message | Type::Group => quote! { request.#ident.is_some() },
+        Type::String | Type::Bytes => quote! { !request.#ident.is_empty() },
+        Type::Bool => quote! { request.#ident },
+        _ => quote! { request.#ident != Default::default() },
+    }
+}
+
+/// Rust identifier prost uses for a field (raw for keywords like `type`)
+fn field_ident(field: &FieldDescriptorProto) -> proc_macro2::Ident {
+    let name = field.name.as_deref().unwrap_or("").to_snake_case();
+    if syn::parse_str::<syn::Ident>(&name).is_ok() {
+        format_ident!("{}", name)
+    } else {
+        format_ident!("r#{}", name)
+    }
+}
+
+fn find_field<'a>(message: &'a DescriptorProto, name: &str) -> Option<&'a FieldDescriptorProto> {
+    message.field.iter().find(|f| f.name.as_deref() == Some(name))
+}
+
+fn invalid(message_name: &str, expr: &str, reason: &str) -> GeneratorError {
+    GeneratorError::CodeGenError(format!(
+        "{}: invalid validation rule {:?}: {}",
+        message_name, expr, reason
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{field, message_field};
+    use prost_types::EnumValueDescriptorProto;
+
+    #[test]
+    fn test_parse_comparison() {
+        assert_eq!(
+            parse_comparison("starts_at < ends_at"),
+            Some(Comparison { lhs: "starts_at", op: Op::Lt, rhs: "ends_at" })
+        );
+        assert_eq!(parse_comparison("a>=b").map(|c| c.op), Some(Op::Ge));
+        assert_eq!(parse_comparison("type == EXTERNAL").map(|c| c.rhs), Some("EXTERNAL"));
+        assert_eq!(parse_comparison("other_field"), None);
+        assert_eq!(parse_comparison("a = b"), None);
+    }
+
+    #[test]
+    fn test_generate_cross_field_rule() {
+        let message = DescriptorProto {
+            name: Some("CreateEventRequest".to_string()),
+            field: vec![
+                message_field("starts_at", 1, ".google.protobuf.Timestamp"),
+                message_field("ends_at", 2, ".google.protobuf.Timestamp"),
+            ],
+            ..Default::default()
+        };
+        let rules = vec![validate::CrossFieldRule {
+            expr: "starts_at < ends_at".to_string(),
+            message: String::new(),
+        }];
+        let error = format_ident!("CreateEventFieldError");
+        let code = generate(&FileDescriptorProto::default(), &message, &rules, &error)
+            .unwrap()
+            .into_iter()
+            .map(|t| t.to_string())
+            .collect::<String>();
+        assert!(code.contains("request . starts_at . as_ref () . map (| t | (t . seconds , t . nanos))"));
+        assert!(code.contains("Some (lhs < rhs)"));
+        assert!(code.contains("\"starts_at < ends_at must hold\""));
+    }
+
+    #[test]
+    fn test_enum_literal() {
+        let file = FileDescriptorProto {
+            enum_type: vec![EnumDescriptorProto {
+                name: Some("EventType".to_string()),
+                value: vec![
+                    EnumValueDescriptorProto {
+                        name: Some("EVENT_TYPE_UNSPECIFIED".to_string()),
+                        number: Some(0),
+                        ..Default::default()
+                    },
+                    EnumValueDescriptorProto {
+                        name: Some("EVENT_TYPE_EXTERNAL".to_string()),
+                        number: Some(2),
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let kind = FieldDescriptorProto {
+            type_name: Some(".events.EventType".to_string()),
+            ..field("type", 1, Type::Enum)
+        };
+        let code = generate_literal(&file, &kind, "EXTERNAL").unwrap().to_string();
+        assert_eq!(code, "2");
+        assert!(generate_literal(&file, &kind, "MISSING").is_none());
+    }
+
+    #[test]
+    fn test_keyword_field() {
+        let code = generate_presence(&field("type", 1, Type::Int32)).to_string();
+        assert!(code.contains("r#type"));
+    }
+
+    #[test]
+    fn test_generate_presence() {
+        assert_eq!(
+            generate_presence(&field("url", 1, Type::String)).to_string(),
+            "! request . url . is_empty ()"
+        );
+        assert_eq!(
+            generate_presence(&field("count", 1, Type::Int32)).to_string(),
+            "request . count != Default :: default ()"
+        );
+    }
+}
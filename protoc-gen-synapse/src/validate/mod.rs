@@ -6,7 +6,18 @@
 //! Generated code includes:
 //! - Domain type struct with validated fields
 //! - ValidationError with `into_errors()` for rich error responses
-//! - `TryFrom<ProtoMessage>` implementation with validation
+//! - `TryFrom<ProtoMessage>` implementation with validation, after sanitizing
+//! - Cross-field checks (`starts_at < ends_at`, `required_if`) in the same
+//!   `TryFrom`, reported per rule
+//! - `validate_custom` for rules naming a user-provided async validator
+
+mod cross_field;
+mod sanitize;
+
+pub use sanitize::{
+    field_sanitize, generate as generate_sanitize, generate_message as generate_sanitize_message,
+    generate_optional as generate_sanitize_optional,
+};
 
 use crate::error::GeneratorError;
 use crate::options::synapse::validate;
@@ -50,10 +61,35 @@ pub fn generate(
     let error_ident = format_ident!("{}ValidationError", domain_name);
     let field_error_ident = format_ident!("{}FieldError", domain_name);
 
+    // Rules apply to the sanitized values
+    let sanitize = match generate_sanitize_message(file_name, message, quote! { request }) {
+        steps if steps.is_empty() => steps,
+        steps => quote! {
+            let mut request = request;
+            #steps
+        },
+    };
+
     // Generate field definitions and validation
     let (field_defs, field_validations, field_assignments) =
         generate_fields(file_name, message_name, &message.field, &field_error_ident)?;
 
+    // Checks spanning several fields (message rules, required_if/unless)
+    let cross_field_validations = cross_field::generate(
+        file,
+        message,
+        &validate_options.rules,
+        &field_error_ident,
+    )?;
+
+    let custom_impl = generate_custom_impl(
+        &custom_validators(file_name, message)?,
+        &domain_ident,
+        &proto_ident,
+        &error_ident,
+        &field_error_ident,
+    );
+
     // Build doc comments
     let module_doc = format!("Domain type {} generated from {}", domain_name, proto_name);
     let struct_doc = format!("Validated domain type for {}", proto_name);
@@ -128,9 +164,11 @@ pub fn generate(
             type Error = #error_ident;
 
             fn try_from(request: #proto_ident) -> Result<Self, Self::Error> {
+                #sanitize
                 let mut errors = Vec::new();
 
                 #(#field_validations)*
+                #(#cross_field_validations)*
 
                 if !errors.is_empty() {
                     return Err(#error_ident { errors });
@@ -141,16 +179,15 @@ pub fn generate(
                 })
             }
         }
+
+        #custom_impl
     };
 
     // Format the generated code
     let content = code.to_string();
 
-    // Try to format with prettyplease if we can parse it
-    let formatted = match syn::parse_file(&content) {
-        Ok(parsed) => prettyplease::unparse(&parsed),
-        Err(_) => content,
-    };
+    // Format the generated code
+    let formatted = crate::params::format_code(content);
 
     Ok(Some(File {
         name: Some(output_filename),
@@ -431,11 +468,253 @@ fn generate_field_validation(
         }
     }
 
+    // Range validation - numeric fields only
+    let is_numeric = !matches!(
+        field.r#type(),
+        Type::String | Type::Bytes | Type::Message | Type::Group | Type::Enum | Type::Bool
+    );
+    if is_numeric {
+        if let Some(ref range) = rules.range {
+            let (lower, upper) = range_bounds(range);
+            let value = if is_optional {
+                quote! { request.#field_ident }
+            } else {
+                quote! { Some(request.#field_ident) }
+            };
+            if let Some(Bound { value: min, exclusive }) = lower {
+                let (out_of_range, word) = if exclusive {
+                    (quote! { (v as f64) <= #min }, "greater than")
+                } else {
+                    (quote! { (v as f64) < #min }, "at least")
+                };
+                validations.push(quote! {
+                    if let Some(v) = #value {
+                        if #out_of_range {
+                            errors.push(#field_error_ident {
+                                code: "min_value".to_string(),
+                                message: format!("{} must be {} {}", #field_name, #word, #min),
+                                field: #field_name.to_string(),
+                            });
+                        }
+                    }
+                });
+            }
+            if let Some(Bound { value: max, exclusive }) = upper {
+                let (out_of_range, word) = if exclusive {
+                    (quote! { (v as f64) >= #max }, "less than")
+                } else {
+                    (quote! { (v as f64) > #max }, "at most")
+                };
+                validations.push(quote! {
+                    if let Some(v) = #value {
+                        if #out_of_range {
+                            errors.push(#field_error_ident {
+                                code: "max_value".to_string(),
+                                message: format!("{} must be {} {}", #field_name, #word, #max),
+                                field: #field_name.to_string(),
+                            });
+                        }
+                    }
+                });
+            }
+        }
+    }
+
     quote! {
         #(#validations)*
     }
 }
 
+/// A field rule naming a user-provided async validator (`custom`)
+#[derive(Clone)]
+pub struct CustomValidator {
+    pub field: String,
+    /// Function path, resolved from the crate root
+    pub path: syn::Path,
+    /// The proto field is `optional`; the validator only sees set values
+    pub optional: bool,
+}
+
+/// Custom validators declared on a message's fields
+pub fn custom_validators(
+    file_name: &str,
+    message: &prost_types::DescriptorProto,
+) -> Result<Vec<CustomValidator>, GeneratorError> {
+    let message_name = message.name.as_deref().unwrap_or("");
+    let mut validators = Vec::new();
+    for field in &message.field {
+        let Some(rules) = get_cached_validate_field_options(file_name, message_name, field.number.unwrap_or(0))
+            .filter(|opts| !opts.skip)
+            .and_then(|opts| opts.rules)
+        else {
+            continue;
+        };
+        if rules.custom.is_empty() {
+            continue;
+        }
+        let field_name = field.name.as_deref().unwrap_or("");
+        let path = rules.custom.trim_start_matches("crate::");
+        let path = syn::parse_str::<syn::Path>(&format!("crate::{}", path)).map_err(|e| {
+            GeneratorError::CodeGenError(format!(
+                "{}.{}: invalid custom validator {:?}: {}",
+                message_name, field_name, rules.custom, e
+            ))
+        })?;
+        validators.push(CustomValidator {
+            field: field_name.to_string(),
+            path,
+            optional: field.proto3_optional.unwrap_or(false),
+        });
+    }
+    Ok(validators)
+}
+
+/// Whether a message's domain type has `validate_custom`
+pub fn has_custom_validators(file_name: &str, message: &prost_types::DescriptorProto) -> bool {
+    let message_name = message.name.as_deref().unwrap_or("");
+    get_cached_validate_message_options(file_name, message_name)
+        .is_some_and(|opts| opts.generate_conversion && !opts.name.is_empty())
+        && custom_validators(file_name, message).is_ok_and(|v| !v.is_empty())
+}
+
+/// Whether any domain type in the main file's package has custom validators
+pub fn package_has_custom_validators(
+    all_files: &[FileDescriptorProto],
+    main_file: &FileDescriptorProto,
+) -> bool {
+    let package = main_file.package.as_deref().unwrap_or("");
+    all_files
+        .iter()
+        .filter(|f| f.package.as_deref().unwrap_or("") == package)
+        .any(|f| {
+            let file_name = f.name.as_deref().unwrap_or("");
+            f.message_type.iter().any(|m| has_custom_validators(file_name, m))
+        })
+}
+
+/// Generate the async `validate_custom` associated function
+///
+/// Each validator is called as `path(&value, &ValidationContext).await` and
+/// returns `Result<(), String>`; errors are collected per field like the
+/// synchronous rules.
+fn generate_custom_impl(
+    validators: &[CustomValidator],
+    domain_ident: &proc_macro2::Ident,
+    proto_ident: &proc_macro2::Ident,
+    error_ident: &proc_macro2::Ident,
+    field_error_ident: &proc_macro2::Ident,
+) -> TokenStream {
+    if validators.is_empty() {
+        return quote! {};
+    }
+
+    let checks = validators.iter().map(|v| {
+        let field_name = &v.field;
+        let field_ident = format_ident!("{}", v.field.to_snake_case());
+        let path = &v.path;
+        let push = quote! {
+            errors.push(#field_error_ident {
+                code: "custom".to_string(),
+                message,
+                field: #field_name.to_string(),
+            });
+        };
+        if v.optional {
+            quote! {
+                if let Some(ref value) = request.#field_ident {
+                    if let Err(message) = #path(value, ctx).await {
+                        #push
+                    }
+                }
+            }
+        } else {
+            quote! {
+                if let Err(message) = #path(&request.#field_ident, ctx).await {
+                    #push
+                }
+            }
+        }
+    });
+
+    quote! {
+        impl #domain_ident {
+            /// Run the custom (async) validators against a request
+            ///
+            /// Called by the gRPC service and GraphQL resolvers before
+            /// conversion, so validators can query the database.
+            pub async fn validate_custom(
+                request: &#proto_ident,
+                ctx: &ValidationContext<'_>,
+            ) -> Result<(), #error_ident> {
+                let mut errors = Vec::new();
+
+                #(#checks)*
+
+                if errors.is_empty() {
+                    Ok(())
+                } else {
+                    Err(#error_ident { errors })
+                }
+            }
+        }
+    }
+}
+
+/// A request field with the `unique_in_db` rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct UniqueField {
+    pub field: String,
+    /// The proto field is `optional`; unset values are not checked
+    pub optional: bool,
+}
+
+/// Fields of a request message that must not collide with existing rows
+pub fn unique_in_db_fields(
+    file_name: &str,
+    message: &prost_types::DescriptorProto,
+) -> Vec<UniqueField> {
+    let message_name = message.name.as_deref().unwrap_or("");
+    message
+        .field
+        .iter()
+        .filter(|field| {
+            get_cached_validate_field_options(file_name, message_name, field.number.unwrap_or(0))
+                .filter(|opts| !opts.skip)
+                .and_then(|opts| opts.rules)
+                .is_some_and(|rules| rules.unique_in_db)
+        })
+        .map(|field| UniqueField {
+            field: field.name.as_deref().unwrap_or("").to_string(),
+            optional: field.proto3_optional.unwrap_or(false),
+        })
+        .collect()
+}
+
+/// One end of a numeric range rule
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bound {
+    pub value: f64,
+    /// The bound itself is out of range
+    pub exclusive: bool,
+}
+
+/// Lower and upper bounds of a range rule
+///
+/// `greater_than`/`less_than` are exclusive; `min`/`max` are inclusive unless
+/// `exclusive_min`/`exclusive_max` is set. When both forms are given the
+/// exclusive one wins.
+pub fn range_bounds(range: &validate::RangeConstraint) -> (Option<Bound>, Option<Bound>) {
+    let lower = range
+        .greater_than
+        .map(|value| Bound { value, exclusive: true })
+        .or(range.min.map(|value| Bound { value, exclusive: range.exclusive_min }));
+    let upper = range
+        .less_than
+        .map(|value| Bound { value, exclusive: true })
+        .or(range.max.map(|value| Bound { value, exclusive: range.exclusive_max }));
+    (lower, upper)
+}
+
 /// Convert proto field type to Rust type token
 fn proto_type_to_rust(field: &FieldDescriptorProto) -> TokenStream {
     use prost_types::field_descriptor_proto::Type;
@@ -0,0 +1,158 @@
+//! Input sanitization
+//!
+//! Turns `(synapse.validate.field).sanitize` into expressions that normalize
+//! a string value. Sanitizing comes before validation: domain conversions and
+//! custom validators see the sanitized request, and GraphQL inputs sanitize
+//! while parsing, before their validators run. The request -> ActiveModel
+//! conversions wrap field values too, for requests that skip validation.
+
+use crate::options::synapse::validate;
+use crate::storage::seaorm::options::get_cached_validate_field_options;
+use heck::ToSnakeCase;
+use proc_macro2::TokenStream;
+use prost_types::field_descriptor_proto::Type;
+use prost_types::{DescriptorProto, FieldDescriptorProto};
+use quote::{format_ident, quote};
+
+/// Sanitize options of a string field, if any apply
+pub fn field_sanitize(
+    file_name: &str,
+    message_name: &str,
+    field: &FieldDescriptorProto,
+) -> Option<validate::Sanitize> {
+    if field.r#type() != Type::String {
+        return None;
+    }
+    get_cached_validate_field_options(file_name, message_name, field.number.unwrap_or(0))
+        // Fields converted to a custom domain type are not plain strings
+        .filter(|opts| !opts.skip && opts.r#type.is_empty())
+        .and_then(|opts| opts.sanitize)
+        .filter(|s| s.nfc || s.trim || s.lowercase)
+}
+
+/// Wrap a `String` expression so it yields the sanitized `String`
+///
+/// Steps run in a fixed order: NFC, trim, lowercase.
+pub fn generate(value: TokenStream, sanitize: &validate::Sanitize) -> TokenStream {
+    let mut steps = Vec::new();
+    if sanitize.nfc {
+        steps.push(quote! {
+            let value: String = unicode_normalization::UnicodeNormalization::nfc(value.as_str()).collect();
+        });
+    }
+    if sanitize.trim {
+        steps.push(quote! { let value = value.trim().to_string(); });
+    }
+    if sanitize.lowercase {
+        steps.push(quote! { let value = value.to_lowercase(); });
+    }
+    if steps.is_empty() {
+        return value;
+    }
+    quote! {
+        {
+            let value: String = #value;
+            #(#steps)*
+            value
+        }
+    }
+}
+
+/// Generate statements sanitizing the string fields of `target` in place
+///
+/// `target` is a mutable place of the message's Rust type. Empty when no
+/// field has `sanitize` options.
+pub fn generate_message(
+    file_name: &str,
+    message: &DescriptorProto,
+    target: TokenStream,
+) -> TokenStream {
+    let message_name = message.name.as_deref().unwrap_or("");
+    let steps = message.field.iter().filter_map(|field| {
+        let sanitize = field_sanitize(file_name, message_name, field)?;
+        let ident = format_ident!("{}", field.name.as_deref().unwrap_or("").to_snake_case());
+        let value = quote! { std::mem::take(&mut #target.#ident) };
+        let sanitized = if field.proto3_optional.unwrap_or(false) {
+            generate_optional(value, &sanitize)
+        } else {
+            generate(value, &sanitize)
+        };
+        Some(quote! { #target.#ident = #sanitized; })
+    });
+    quote! { #(#steps)* }
+}
+
+/// Like [`generate`], for an `Option<String>` expression
+pub fn generate_optional(value: TokenStream, sanitize: &validate::Sanitize) -> TokenStream {
+    let inner = generate(quote! { value }, sanitize);
+    quote! { #value.map(|value| #inner) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_sanitize() {
+        let sanitize = validate::Sanitize {
+            nfc: false,
+            trim: true,
+            lowercase: true,
+        };
+        let code = generate(quote! { request.email }, &sanitize).to_string();
+        assert!(code.contains("let value : String = request . email"));
+        assert!(code.contains("value . trim () . to_string ()"));
+        assert!(code.contains("value . to_lowercase ()"));
+        assert!(!code.contains("unicode_normalization"));
+    }
+
+    #[test]
+    fn test_generate_sanitize_noop() {
+        let code = generate(quote! { request.name }, &validate::Sanitize::default()).to_string();
+        assert_eq!(code, "request . name");
+    }
+
+    #[test]
+    fn test_generate_optional() {
+        let sanitize = validate::Sanitize {
+            nfc: true,
+            ..Default::default()
+        };
+        let code = generate_optional(quote! { input.nickname }, &sanitize).to_string();
+        assert!(code.starts_with("input . nickname . map (| value |"));
+        assert!(code.contains("UnicodeNormalization :: nfc"));
+    }
+
+    #[test]
+    fn test_generate_message() {
+        use crate::storage::seaorm::options::cache_validate_field_options;
+        use crate::test_util::field;
+
+        let message = DescriptorProto {
+            name: Some("SignupRequest".to_string()),
+            field: vec![
+                field("email", 1, Type::String),
+                FieldDescriptorProto {
+                    proto3_optional: Some(true),
+                    ..field("nickname", 2, Type::String)
+                },
+                field("bio", 3, Type::String),
+            ],
+            ..Default::default()
+        };
+        let trimmed = validate::FieldOptions {
+            sanitize: Some(validate::Sanitize {
+                trim: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        cache_validate_field_options("sanitize_test.proto", "SignupRequest", 1, trimmed.clone());
+        cache_validate_field_options("sanitize_test.proto", "SignupRequest", 2, trimmed);
+
+        let code = generate_message("sanitize_test.proto", &message, quote! { request }).to_string();
+        assert!(code.contains("request . email = { let value : String = std :: mem :: take (& mut request . email)"));
+        assert!(code.contains("request . nickname = std :: mem :: take (& mut request . nickname) . map (| value |"));
+        assert!(!code.contains("bio"));
+    }
+}
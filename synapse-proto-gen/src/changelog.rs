@@ -0,0 +1,151 @@
+//! Schema changelog generation
+//!
+//! Compares two revisions of a set of proto files and renders the
+//! schema-affecting differences as Markdown release notes: added and removed
+//! entities, fields, and RPC methods, deprecations, and breaking changes.
+
+use crate::parser::{EntityInfo, FieldInfo, ServiceInfo};
+
+/// Entities and services of one proto revision
+pub struct Revision {
+    pub entities: Vec<EntityInfo>,
+    pub services: Vec<ServiceInfo>,
+}
+
+/// Changes between two revisions, grouped by changelog section
+#[derive(Debug, Default)]
+pub struct Changelog {
+    pub breaking: Vec<String>,
+    pub added: Vec<String>,
+    pub deprecated: Vec<String>,
+}
+
+impl Changelog {
+    /// Whether the revisions differ in any schema-affecting way
+    pub fn is_empty(&self) -> bool {
+        self.breaking.is_empty() && self.added.is_empty() && self.deprecated.is_empty()
+    }
+}
+
+/// Compare two revisions
+pub fn diff(old: &Revision, new: &Revision) -> Changelog {
+    let mut changelog = Changelog::default();
+
+    for entity in &new.entities {
+        match old.entities.iter().find(|e| e.name == entity.name) {
+            None => changelog.added.push(format!("Entity `{}`", entity.name)),
+            Some(previous) => diff_entity(previous, entity, &mut changelog),
+        }
+    }
+    for entity in &old.entities {
+        if !new.entities.iter().any(|e| e.name == entity.name) {
+            changelog.breaking.push(format!("Removed entity `{}`", entity.name));
+        }
+    }
+
+    for service in &new.services {
+        let previous = old.services.iter().find(|s| s.name == service.name);
+        for method in &service.methods {
+            if !previous.is_some_and(|s| s.methods.contains(method)) {
+                changelog.added.push(format!("RPC `{}.{}`", service.name, method));
+            }
+        }
+    }
+    for service in &old.services {
+        let current = new.services.iter().find(|s| s.name == service.name);
+        for method in &service.methods {
+            if !current.is_some_and(|s| s.methods.contains(method)) {
+                changelog.breaking.push(format!("Removed RPC `{}.{}`", service.name, method));
+            }
+        }
+    }
+
+    changelog
+}
+
+/// Compare the fields of an entity present in both revisions
+fn diff_entity(old: &EntityInfo, new: &EntityInfo, changelog: &mut Changelog) {
+    let entity = &new.name;
+
+    if new.deprecated && !old.deprecated {
+        changelog.deprecated.push(format!("Entity `{}`", entity));
+    }
+
+    for field in &new.fields {
+        let Some(previous) = old.fields.iter().find(|f| f.name == field.name) else {
+            changelog.added.push(format!("Field `{}.{}` ({})", entity, field.name, describe(field)));
+            continue;
+        };
+
+        if previous.type_name != field.type_name {
+            changelog.breaking.push(format!(
+                "Changed type of `{}.{}` from `{}` to `{}`",
+                entity, field.name, previous.type_name, field.type_name
+            ));
+        }
+        if previous.number != field.number {
+            changelog.breaking.push(format!(
+                "Renumbered `{}.{}` from {} to {}",
+                entity, field.name, previous.number, field.number
+            ));
+        }
+        // Nullability shows up in GraphQL: required inputs break clients that
+        // omit the field, nullable outputs break clients that assume a value
+        if previous.optional != field.optional {
+            let (from, to) = if field.optional {
+                ("required", "optional")
+            } else {
+                ("optional", "required")
+            };
+            changelog.breaking.push(format!(
+                "Changed `{}.{}` from {} to {}",
+                entity, field.name, from, to
+            ));
+        }
+        if field.deprecated && !previous.deprecated {
+            changelog.deprecated.push(format!("Field `{}.{}`", entity, field.name));
+        }
+    }
+
+    for field in &old.fields {
+        if !new.fields.iter().any(|f| f.name == field.name) {
+            changelog.breaking.push(format!("Removed field `{}.{}`", entity, field.name));
+        }
+    }
+}
+
+/// Short description of a field's type for the changelog
+fn describe(field: &FieldInfo) -> String {
+    if field.optional {
+        format!("`{}`, optional", field.type_name)
+    } else {
+        format!("`{}`", field.type_name)
+    }
+}
+
+/// Render a changelog as Markdown
+pub fn render(changelog: &Changelog, title: &str) -> String {
+    let mut output = format!("## {}\n", title);
+
+    if changelog.is_empty() {
+        output.push_str("\nNo schema changes.\n");
+        return output;
+    }
+
+    let sections = [
+        ("Breaking changes", &changelog.breaking),
+        ("Added", &changelog.added),
+        ("Deprecated", &changelog.deprecated),
+    ];
+    for (heading, entries) in sections {
+        if entries.is_empty() {
+            continue;
+        }
+        output.push_str(&format!("\n### {}\n\n", heading));
+        for entry in entries {
+            output.push_str(&format!("- {}\n", entry));
+        }
+    }
+
+    output
+}
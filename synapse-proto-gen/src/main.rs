@@ -6,17 +6,24 @@
 //! - PageInfo message
 //! - Entity-specific Filter, OrderBy, Edge, Connection types
 //! - Request/Response messages for CRUD operations
+//!
+//! `synapse-proto-gen changelog --old <files> --new <files>` instead compares
+//! two revisions of the protos and prints Markdown release notes.
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
+mod changelog;
 mod generator;
 mod parser;
 
 #[derive(Parser, Debug)]
 #[command(name = "synapse-proto-gen")]
 #[command(about = "Generate auxiliary proto types from entity definitions")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Input proto file(s) containing entity definitions
     #[arg(required = true)]
     input: Vec<PathBuf>,
@@ -30,9 +37,35 @@ struct Args {
     proto_paths: Vec<PathBuf>,
 }
 
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print a Markdown changelog of schema changes between two proto revisions
+    Changelog {
+        /// Proto file(s) of the previous revision
+        #[arg(long, required = true, num_args = 1..)]
+        old: Vec<PathBuf>,
+
+        /// Proto file(s) of the new revision
+        #[arg(long, required = true, num_args = 1..)]
+        new: Vec<PathBuf>,
+
+        /// Changelog heading (e.g. the release version)
+        #[arg(long, default_value = "Schema changes")]
+        title: String,
+
+        /// Write the changelog to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    if let Some(Command::Changelog { old, new, title, output }) = args.command {
+        return changelog(&old, &new, &title, output, &args.proto_paths);
+    }
+
     // Parse input proto files
     let entities = parser::parse_proto_files(&args.input, &args.proto_paths)?;
 
@@ -64,3 +97,33 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+/// Compare two proto revisions and print or write the changelog
+fn changelog(
+    old: &[PathBuf],
+    new: &[PathBuf],
+    title: &str,
+    output: Option<PathBuf>,
+    proto_paths: &[PathBuf],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let revision = |files: &[PathBuf]| -> Result<changelog::Revision, Box<dyn std::error::Error>> {
+        Ok(changelog::Revision {
+            entities: parser::parse_proto_files(files, proto_paths)?,
+            services: parser::parse_services(files)?,
+        })
+    };
+    let changes = changelog::diff(&revision(old)?, &revision(new)?);
+    let rendered = changelog::render(&changes, title);
+
+    match output {
+        Some(path) => std::fs::write(path, rendered)?,
+        None => print!("{}", rendered),
+    }
+
+    // Summarize on stderr so it shows up in CI logs
+    if !changes.breaking.is_empty() {
+        eprintln!("{} breaking change(s)", changes.breaking.len());
+    }
+
+    Ok(())
+}
@@ -16,6 +16,8 @@ pub struct EntityInfo {
     pub table_name: String,
     /// Fields in the entity
     pub fields: Vec<FieldInfo>,
+    /// `option deprecated = true` on the message
+    pub deprecated: bool,
 }
 
 /// Information about a field in an entity
@@ -25,12 +27,27 @@ pub struct FieldInfo {
     pub name: String,
     /// Proto field type
     pub proto_type: ProtoType,
+    /// Type as written in the proto (e.g. "google.protobuf.Timestamp")
+    pub type_name: String,
+    /// Field number
+    pub number: u32,
     /// Whether the field is optional (reserved for future use)
     #[allow(dead_code)]
     pub optional: bool,
     /// Whether this is a primary key (reserved for future use)
     #[allow(dead_code)]
     pub primary_key: bool,
+    /// `deprecated = true` field option
+    pub deprecated: bool,
+}
+
+/// A service and its RPC method names
+#[derive(Debug, Clone)]
+pub struct ServiceInfo {
+    /// Service name (e.g., "UserService")
+    pub name: String,
+    /// RPC method names, in declaration order
+    pub methods: Vec<String>,
 }
 
 /// Supported proto types for filter generation
@@ -47,6 +64,8 @@ pub enum ProtoType {
     String,
     Bytes,
     Timestamp,
+    /// `synapse.relay.GeoPoint` (PostGIS geography column)
+    GeoPoint,
     Message,
     Enum,
 }
@@ -62,6 +81,7 @@ impl ProtoType {
             ProtoType::Bool => Some("BoolFilter"),
             ProtoType::String => Some("StringFilter"),
             ProtoType::Timestamp => Some("TimestampFilter"),
+            ProtoType::GeoPoint => Some("GeoPointFilter"),
             _ => None,
         }
     }
@@ -142,6 +162,7 @@ fn parse_entities_from_content(
     let mut current_message: Option<String> = None;
     let mut current_fields: Vec<FieldInfo> = Vec::new();
     let mut is_entity = false;
+    let mut deprecated = false;
     let mut table_name = String::new();
     let mut message_brace_depth = 0;
     let mut in_multi_line_option = false;
@@ -151,6 +172,12 @@ fn parse_entities_from_content(
 
         // Track multi-line options/fields (those that don't end with ; or { on same line)
         if in_multi_line_option {
+            // Deprecation inside a multi-line field option list
+            if (line.contains("deprecated = true") || line.contains("deprecated: true"))
+                && let Some(field) = current_fields.last_mut()
+            {
+                field.deprecated = true;
+            }
             // Check if we're closing options with ];
             if line.contains("];") || line.ends_with("};") {
                 in_multi_line_option = false;
@@ -187,6 +214,7 @@ fn parse_entities_from_content(
             current_message = Some(name.to_string());
             current_fields.clear();
             is_entity = false;
+            deprecated = false;
             table_name.clear();
             message_brace_depth = 1;
             continue;
@@ -209,6 +237,9 @@ fn parse_entities_from_content(
             // Skip option lines and their continuations
             // Be careful: "optional" fields also start with "option"!
             if line.starts_with("option ") || line.starts_with("option(") {
+                if message_brace_depth == 1 && line.contains("deprecated = true") {
+                    deprecated = true;
+                }
                 if !line.ends_with(';') && !line.ends_with("};") {
                     in_multi_line_option = true;
                 }
@@ -232,6 +263,7 @@ fn parse_entities_from_content(
                             package: package.to_string(),
                             table_name: tbl,
                             fields: std::mem::take(&mut current_fields),
+                            deprecated,
                         });
                     }
                     current_message = None;
@@ -334,16 +366,93 @@ fn parse_field(line: &str) -> Option<FieldInfo> {
         "string" => ProtoType::String,
         "bytes" => ProtoType::Bytes,
         t if t.contains("Timestamp") => ProtoType::Timestamp,
+        t if t.ends_with("GeoPoint") => ProtoType::GeoPoint,
         _ => ProtoType::Message, // Assume unknown types are messages
     };
 
+    // Field number follows the '='
+    let number = rest
+        .split('=')
+        .nth(1)
+        .and_then(|n| n.trim().split(|c: char| !c.is_ascii_digit()).next())
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0);
+
     // Check for primary key
     let primary_key = line.contains("primary_key: true") || line.contains("primary_key:true");
+    let deprecated = line.contains("deprecated = true") || line.contains("deprecated: true");
 
     Some(FieldInfo {
         name: name.to_string(),
         proto_type,
+        type_name: type_str.to_string(),
+        number,
         optional: is_optional,
         primary_key,
+        deprecated,
     })
 }
+
+/// Parse services and their RPC method names from proto files
+pub fn parse_services(
+    input_files: &[impl AsRef<Path>],
+) -> Result<Vec<ServiceInfo>, Box<dyn std::error::Error>> {
+    let mut services = Vec::new();
+    for input_file in input_files {
+        let content = std::fs::read_to_string(input_file.as_ref())?;
+        services.extend(parse_services_from_content(&content));
+    }
+    Ok(services)
+}
+
+/// Parse services from proto file content
+fn parse_services_from_content(content: &str) -> Vec<ServiceInfo> {
+    let mut services = Vec::new();
+    let mut current: Option<ServiceInfo> = None;
+    let mut depth = 0;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with("//") {
+            continue;
+        }
+
+        if current.is_none() {
+            if line.starts_with("service ") && line.contains('{') {
+                let name = line
+                    .trim_start_matches("service ")
+                    .split('{')
+                    .next()
+                    .unwrap_or("")
+                    .trim();
+                current = Some(ServiceInfo {
+                    name: name.to_string(),
+                    methods: Vec::new(),
+                });
+                depth = 1;
+            }
+            continue;
+        }
+
+        // Method names sit at service level: "rpc GetUser(GetUserRequest) ..."
+        if depth == 1 && line.starts_with("rpc ") {
+            let name = line
+                .trim_start_matches("rpc ")
+                .split('(')
+                .next()
+                .unwrap_or("")
+                .trim();
+            if let Some(service) = current.as_mut() {
+                service.methods.push(name.to_string());
+            }
+        }
+
+        depth += line.matches('{').count();
+        depth -= line.matches('}').count().min(depth);
+        if depth == 0 {
+            services.extend(current.take());
+        }
+    }
+
+    services
+}